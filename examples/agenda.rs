@@ -0,0 +1,72 @@
+//! Prints today's agenda from ./data using the library alone: the todos
+//! overdue or due today, most urgent first, and the time tracked today.
+//!
+//!     cargo run --example agenda --no-default-features
+
+use chrono::{Duration, Utc};
+use std::process;
+
+use work_time_cli::{
+    due_in, load_config, local_midnight, set_zone, sort_todos, time_per_category, DueWindow,
+    JsonStorage, Storage, TodoStatus, Zone,
+};
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        process::exit(err.exit_code());
+    }
+}
+
+fn run() -> Result<(), work_time_cli::Error> {
+    let config = load_config()?;
+    let zone = config.timezone.unwrap_or(Zone::System);
+    set_zone(zone);
+    let db = JsonStorage.load()?;
+    let now = Utc::now().with_timezone(&zone);
+
+    let is_due = |window, todo| due_in(window, todo, &db.todos, now, config.week_start);
+    let mut due: Vec<_> = db
+        .todos
+        .iter()
+        .filter(|t| t.status() != TodoStatus::Done)
+        .filter(|t| is_due(DueWindow::Overdue, t) || is_due(DueWindow::Today, t))
+        .cloned()
+        .collect();
+    sort_todos(&mut due, "urgency", &db.todos, &config, Utc::now()).expect("urgency is a sort key");
+
+    println!("Agenda for {}", now.format("%A %Y-%m-%d"));
+    if due.is_empty() {
+        println!("  nothing due");
+    }
+    for todo in &due {
+        let when = match is_due(DueWindow::Overdue, todo) {
+            true => "overdue",
+            false => "today",
+        };
+        println!(
+            "  #{:<4} {:<8} {} @{}",
+            todo.id, when, todo.title, todo.category
+        );
+    }
+
+    let today = now.date_naive();
+    let tracked = time_per_category(
+        &db.entries,
+        local_midnight(today, zone),
+        local_midnight(today + Duration::days(1), zone),
+        Utc::now(),
+    );
+    if !tracked.is_empty() {
+        println!("Tracked today");
+        for (category, time) in tracked {
+            println!(
+                "  {:<16} {}h{:02}m",
+                category,
+                time.num_hours(),
+                time.num_minutes() % 60
+            );
+        }
+    }
+    Ok(())
+}
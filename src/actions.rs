@@ -0,0 +1,1534 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::chrome::Chrome;
+use crate::cursor;
+use crate::widgets::{centered_rect, Input};
+use crate::MenuItem;
+
+/// Everything the user can do from the board and tabs, whether by key or
+/// from the command palette.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ActionId {
+    CommandLine,
+    Palette,
+    Search,
+    Help,
+    Quit,
+    HomeTab,
+    TodosTab,
+    TimersTab,
+    TimeTrackingTab,
+    NextTab,
+    GotoRunning,
+    ToggleBreak,
+    Undo,
+    ToggleBadges,
+    TrackedWeek,
+    NextSection,
+    PreviousSection,
+    ToggleSection,
+    NextTodo,
+    PreviousTodo,
+    FocusLeft,
+    FocusRight,
+    CollapseColumn,
+    ColumnMenu,
+    ColumnSort,
+    ColumnArchive,
+    ColumnWipLimit,
+    ColumnExport,
+    ColumnClearSubtasks,
+    BadgeFilter,
+    DueFilter,
+    FollowTodoReference,
+    OpenTodo,
+    NewTodo,
+    TodoMenu,
+    MoveTodo,
+    MoveTodoLeft,
+    MoveTodoRight,
+    StartTimer,
+    DeleteTodo,
+    MarkTodo,
+    TogglePin,
+    CycleLabel,
+    BatchEdit,
+    Attach,
+    EditTodo,
+    EditDescription,
+    ToggleDetail,
+    GrowDetail,
+    ShrinkDetail,
+    NextTimer,
+    PreviousTimer,
+    NewTimer,
+    TimerStart,
+    TimerPause,
+    TimerStop,
+    ToggleTimeline,
+    NextEntry,
+    PreviousEntry,
+    EarlierDay,
+    LaterDay,
+    FollowEntryReference,
+    EntryNote,
+    EntryTags,
+    EntryClock,
+    Timesheet,
+    BreakdownScale,
+    ReportTag,
+    ReportBy,
+    Today,
+    Yesterday,
+    Calendar,
+    Stats,
+    Storage,
+    Inconsistencies,
+    Share,
+    ExportItem,
+    Snooze,
+    Refresh,
+    SaveNow,
+}
+
+/// Where an action's keys work.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scope {
+    Global,
+    Tab(MenuItem),
+}
+
+/// What has to be there for an action to do anything.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Needs {
+    Nothing,
+    /// A todo selected on the board or open in the todo view.
+    Todo,
+    MarkedTodos,
+    /// An entry selected in the entries table.
+    Entry,
+    /// A timer selected on the Timers tab.
+    Timer,
+    Timeline,
+    RunningTodo,
+    Undo,
+    DoneColumn,
+}
+
+impl Needs {
+    /// Why the action is grayed out when this isn't met.
+    pub fn reason(self) -> &'static str {
+        match self {
+            Needs::Nothing => "",
+            Needs::Todo => "select a todo first",
+            Needs::MarkedTodos => "mark todos with space first",
+            Needs::Entry => "select an entry first",
+            Needs::Timer => "select a timer first",
+            Needs::Timeline => "only on the timeline",
+            Needs::RunningTodo => "no running timer is linked to a todo",
+            Needs::Undo => "nothing to undo",
+            Needs::DoneColumn => "only on the Done column",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+pub const fn key(c: char) -> Key {
+    Key {
+        code: KeyCode::Char(c),
+        modifiers: KeyModifiers::NONE,
+    }
+}
+
+const fn code(code: KeyCode) -> Key {
+    Key {
+        code,
+        modifiers: KeyModifiers::NONE,
+    }
+}
+
+const fn ctrl(code: KeyCode) -> Key {
+    Key {
+        code,
+        modifiers: KeyModifiers::CONTROL,
+    }
+}
+
+const fn shift(code: KeyCode) -> Key {
+    Key {
+        code,
+        modifiers: KeyModifiers::SHIFT,
+    }
+}
+
+/// Undo's key, which an undo toast claims in every context while it is up.
+pub const UNDO_KEY: Key = key('u');
+
+impl Key {
+    /// Shift is part of the char already, so `T` matches with or without
+    /// the modifier the terminal reports.
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        let modifiers = match event.code {
+            KeyCode::Char(_) => event.modifiers - KeyModifiers::SHIFT,
+            _ => event.modifiers,
+        };
+        self.code == event.code && self.modifiers == modifiers
+    }
+
+    /// Reads a key as `label` writes it, e.g. "x", "Ctrl+p", "Shift+Left",
+    /// "F5" or "Space".
+    pub fn parse(text: &str) -> Option<Key> {
+        let (modifiers, rest) = match (text.strip_prefix("Ctrl+"), text.strip_prefix("Shift+")) {
+            (Some(rest), _) => (KeyModifiers::CONTROL, rest),
+            (_, Some(rest)) => (KeyModifiers::SHIFT, rest),
+            _ => (KeyModifiers::NONE, text),
+        };
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest {
+                "Space" => KeyCode::Char(' '),
+                "Enter" => KeyCode::Enter,
+                "Esc" => KeyCode::Esc,
+                "Up" => KeyCode::Up,
+                "Down" => KeyCode::Down,
+                "Left" => KeyCode::Left,
+                "Right" => KeyCode::Right,
+                "Tab" => KeyCode::Tab,
+                "BackTab" => KeyCode::BackTab,
+                "Delete" => KeyCode::Delete,
+                "Backspace" => KeyCode::Backspace,
+                "Insert" => KeyCode::Insert,
+                "Home" => KeyCode::Home,
+                "End" => KeyCode::End,
+                "PageUp" => KeyCode::PageUp,
+                "PageDown" => KeyCode::PageDown,
+                _ => KeyCode::F(rest.strip_prefix('F')?.parse().ok()?),
+            },
+        };
+        Some(Key { code, modifiers })
+    }
+
+    pub fn label(&self) -> String {
+        let code = match self.code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Enter => "Enter".to_string(),
+            KeyCode::Up => "Up".to_string(),
+            KeyCode::Down => "Down".to_string(),
+            KeyCode::Left => "Left".to_string(),
+            KeyCode::Right => "Right".to_string(),
+            KeyCode::Tab => "Tab".to_string(),
+            KeyCode::Delete => "Delete".to_string(),
+            KeyCode::F(n) => format!("F{}", n),
+            other => format!("{:?}", other),
+        };
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            format!("Ctrl+{}", code)
+        } else if self.modifiers.contains(KeyModifiers::SHIFT) {
+            format!("Shift+{}", code)
+        } else {
+            code
+        }
+    }
+}
+
+/// e.g. `"Ctrl+p"`, in the `keys` of the config.
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Key, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Key::parse(&text).ok_or_else(|| de::Error::custom(format!("unknown key `{}`", text)))
+    }
+}
+
+/// An action by its name as help and the palette show it, e.g. `"collapse
+/// column"`, in the `keys` of the config.
+impl<'de> Deserialize<'de> for ActionId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ActionId, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        ACTIONS
+            .iter()
+            .find(|a| a.name == name)
+            .map(|a| a.id)
+            .ok_or_else(|| de::Error::custom(format!("unknown action `{}`", name)))
+    }
+}
+
+/// By the same name, for macros saved in the session.
+impl Serialize for ActionId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match ACTIONS.iter().find(|a| a.id == *self) {
+            Some(action) => serializer.serialize_str(action.name),
+            None => Err(ser::Error::custom(format!(
+                "unregistered action {:?}",
+                self
+            ))),
+        }
+    }
+}
+
+pub struct Action {
+    pub id: ActionId,
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Empty for actions only the palette or a `:` command reaches. The
+    /// config can bind others in their place, see [`Action::keys`].
+    pub default_keys: &'static [Key],
+    pub scope: Scope,
+    pub needs: Needs,
+    /// Shown on the hint bar of its tab.
+    pub hint: bool,
+}
+
+impl Action {
+    /// The keys bound to the action in `keymap`, its defaults unless the
+    /// keymap has others.
+    pub fn keys_in<'a>(&'a self, keymap: &'a Keymap) -> &'a [Key] {
+        keymap
+            .get(&self.id)
+            .map_or(self.default_keys, Vec::as_slice)
+    }
+
+    /// The keys bound to the action now.
+    pub fn keys(&self) -> Vec<Key> {
+        self.keys_in(&current_keymap()).to_vec()
+    }
+
+    pub fn keys_label(&self) -> String {
+        self.keys()
+            .iter()
+            .map(Key::label)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+const fn action(
+    id: ActionId,
+    name: &'static str,
+    description: &'static str,
+    default_keys: &'static [Key],
+    scope: Scope,
+    needs: Needs,
+    hint: bool,
+) -> Action {
+    Action {
+        id,
+        name,
+        description,
+        default_keys,
+        scope,
+        needs,
+        hint,
+    }
+}
+
+use ActionId as A;
+use Scope::{Global, Tab};
+
+const HOME: Scope = Tab(MenuItem::Home);
+const TODOS: Scope = Tab(MenuItem::Todos);
+const TIMERS: Scope = Tab(MenuItem::Timers);
+const TIME: Scope = Tab(MenuItem::TimeTracking);
+
+/// The registry the keymap, help, hint bar and palette are all read from.
+/// A key means one action per scope; tab actions take precedence over
+/// global ones on their tab.
+pub const ACTIONS: &[Action] = &[
+    action(
+        A::CommandLine,
+        "command line",
+        "type a : command",
+        &[key(':')],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    // terminals send Ctrl+Shift+p as Ctrl+p
+    action(
+        A::Palette,
+        "command palette",
+        "search every action and run it",
+        &[ctrl(KeyCode::Char('p')), code(KeyCode::F(2))],
+        Global,
+        Needs::Nothing,
+        true,
+    ),
+    action(
+        A::Search,
+        "find todo",
+        "find a todo by title, on the board, archived or filtered out",
+        &[key('/'), code(KeyCode::F(3))],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::Help,
+        "help",
+        "list every action and its keys",
+        &[key('?'), code(KeyCode::F(1))],
+        Global,
+        Needs::Nothing,
+        true,
+    ),
+    // q reaches the macro recorder first, which quits on a second q in time
+    action(
+        A::Quit,
+        "quit",
+        "leave the app",
+        &[ctrl(KeyCode::Char('q')), key('q'), code(KeyCode::F(10))],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::HomeTab,
+        "home tab",
+        "show the Home tab",
+        &[key('w')],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::TodosTab,
+        "todos tab",
+        "show the Todos tab",
+        &[key('t')],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::TimersTab,
+        "timers tab",
+        "show the Timers tab",
+        &[key('i')],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::TimeTrackingTab,
+        "time tracking tab",
+        "show the TimeTracking tab",
+        &[key('m')],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::NextTab,
+        "next tab",
+        "show the tab to the right, the first after the last",
+        &[code(KeyCode::Tab)],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::GotoRunning,
+        "go to running todo",
+        "select the todo the running timer is on",
+        &[ctrl(KeyCode::Char('g'))],
+        Global,
+        Needs::RunningTodo,
+        false,
+    ),
+    action(
+        A::ToggleBreak,
+        "break",
+        "pause every running timer, or come back from the break",
+        &[ctrl(KeyCode::Char('b'))],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::Undo,
+        "undo",
+        "undo the last change to the todos or an entry's tags",
+        &[UNDO_KEY],
+        Global,
+        Needs::Undo,
+        false,
+    ),
+    action(
+        A::ToggleBadges,
+        "toggle badges",
+        "show or hide the per-category counts under the status bar",
+        &[key('b')],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::TrackedWeek,
+        "tracked this week",
+        "show the time tracked on each of the last 7 days",
+        &[key('S')],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::NextSection,
+        "next section",
+        "focus the next dashboard section",
+        &[key('j'), code(KeyCode::Down)],
+        HOME,
+        Needs::Nothing,
+        true,
+    ),
+    action(
+        A::PreviousSection,
+        "previous section",
+        "focus the previous dashboard section",
+        &[key('k'), code(KeyCode::Up)],
+        HOME,
+        Needs::Nothing,
+        true,
+    ),
+    action(
+        A::ToggleSection,
+        "toggle section",
+        "expand or fold the focused section",
+        &[code(KeyCode::Enter)],
+        HOME,
+        Needs::Nothing,
+        true,
+    ),
+    action(
+        A::NextTodo,
+        "next todo",
+        "select the next todo",
+        &[key('j'), code(KeyCode::Down)],
+        TODOS,
+        Needs::Nothing,
+        true,
+    ),
+    action(
+        A::PreviousTodo,
+        "previous todo",
+        "select the previous todo",
+        &[key('k'), code(KeyCode::Up)],
+        TODOS,
+        Needs::Nothing,
+        true,
+    ),
+    action(
+        A::FocusLeft,
+        "focus left",
+        "focus the column to the left",
+        &[key('h'), code(KeyCode::Left)],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::FocusRight,
+        "focus right",
+        "focus the column to the right",
+        &[key('l'), code(KeyCode::Right)],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::CollapseColumn,
+        "collapse column",
+        "collapse or expand the focused column",
+        &[key('z')],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::ColumnMenu,
+        "column menu",
+        "sort, collapse, archive or export the focused column from a menu",
+        &[key('.')],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::ColumnSort,
+        "column: sort",
+        "pick how the columns are sorted",
+        &[],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::ColumnArchive,
+        "column: archive old",
+        "archive the column's todos done more than some days ago",
+        &[],
+        TODOS,
+        Needs::DoneColumn,
+        false,
+    ),
+    action(
+        A::ColumnWipLimit,
+        "column: wip limit",
+        "set how many todos the focused column should hold at most",
+        &[],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::ColumnExport,
+        "column: export",
+        "write the column's todos to a Markdown file",
+        &[],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::ColumnClearSubtasks,
+        "column: clear done subtasks",
+        "detach the done subtasks of the column's todos, so progress counts open ones",
+        &[],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::BadgeFilter,
+        "badge filter",
+        "filter the board by the next category badge, clearing after the last",
+        &[key('B')],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::DueFilter,
+        "due filter",
+        "then 1, 2 or 3 to show only the todos due today, this week or overdue, again to show all",
+        &[key('f')],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::FollowTodoReference,
+        "follow reference",
+        "go to the todo the description mentions",
+        &[],
+        TODOS,
+        Needs::Todo,
+        false,
+    ),
+    action(
+        A::OpenTodo,
+        "open todo",
+        "show the todo with its work history",
+        &[key('o')],
+        TODOS,
+        Needs::Todo,
+        true,
+    ),
+    action(
+        A::NewTodo,
+        "new todo",
+        "add a todo with a title, description and category",
+        &[key('n'), code(KeyCode::Insert)],
+        TODOS,
+        Needs::Nothing,
+        true,
+    ),
+    action(
+        A::TodoMenu,
+        "todo menu",
+        "move, edit, time or delete the todo from a menu",
+        &[code(KeyCode::Enter)],
+        TODOS,
+        Needs::Todo,
+        true,
+    ),
+    action(
+        A::MoveTodo,
+        "move todo",
+        "move the todo to Doing, or on to Done",
+        &[],
+        TODOS,
+        Needs::Todo,
+        false,
+    ),
+    action(
+        A::MoveTodoLeft,
+        "move todo left",
+        "move the todo in the focused column to the column to the left",
+        &[key('H'), shift(KeyCode::Left)],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::MoveTodoRight,
+        "move todo right",
+        "move the todo in the focused column to the column to the right",
+        &[key('L'), shift(KeyCode::Right)],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::StartTimer,
+        "start timer",
+        "start a timer on the todo, in its category",
+        &[],
+        TODOS,
+        Needs::Todo,
+        false,
+    ),
+    action(
+        A::DeleteTodo,
+        "delete todo",
+        "delete the todo, after asking",
+        &[key('d'), code(KeyCode::Delete)],
+        TODOS,
+        Needs::Todo,
+        false,
+    ),
+    action(
+        A::MarkTodo,
+        "mark todo",
+        "mark or unmark the todo for a batch edit",
+        &[key(' ')],
+        TODOS,
+        Needs::Todo,
+        true,
+    ),
+    action(
+        A::CycleLabel,
+        "color label",
+        "cycle the todo's color label, the last step clears it",
+        &[key('C')],
+        TODOS,
+        Needs::Todo,
+        false,
+    ),
+    action(
+        A::TogglePin,
+        "pin todo",
+        "pin or unpin the todo",
+        &[key('!'), key('*')],
+        TODOS,
+        Needs::Todo,
+        false,
+    ),
+    action(
+        A::BatchEdit,
+        "batch edit",
+        "edit every marked todo at once",
+        &[key('E')],
+        TODOS,
+        Needs::MarkedTodos,
+        true,
+    ),
+    action(
+        A::Attach,
+        "attach file",
+        "add a file path to the todo",
+        &[key('a')],
+        TODOS,
+        Needs::Todo,
+        true,
+    ),
+    action(
+        A::EditTodo,
+        "edit todo",
+        "edit the todo's title, description and category in a form",
+        &[key('e')],
+        TODOS,
+        Needs::Todo,
+        true,
+    ),
+    action(
+        A::EditDescription,
+        "edit description",
+        "edit the todo's description, over several lines",
+        &[],
+        TODOS,
+        Needs::Todo,
+        false,
+    ),
+    action(
+        A::ToggleDetail,
+        "toggle detail",
+        "show or hide the detail pane",
+        &[key('x')],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::GrowDetail,
+        "grow detail",
+        "make the detail pane taller",
+        &[ctrl(KeyCode::Up)],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::ShrinkDetail,
+        "shrink detail",
+        "make the detail pane shorter",
+        &[ctrl(KeyCode::Down)],
+        TODOS,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::NextTimer,
+        "next timer",
+        "select the next timer",
+        &[key('j'), code(KeyCode::Down)],
+        TIMERS,
+        Needs::Nothing,
+        true,
+    ),
+    action(
+        A::PreviousTimer,
+        "previous timer",
+        "select the previous timer",
+        &[key('k'), code(KeyCode::Up)],
+        TIMERS,
+        Needs::Nothing,
+        true,
+    ),
+    action(
+        A::NewTimer,
+        "new timer",
+        "add a named timer with a category",
+        &[key('a'), code(KeyCode::Insert)],
+        TIMERS,
+        Needs::Nothing,
+        true,
+    ),
+    action(
+        A::TimerStart,
+        "timer: start",
+        "start or resume the timer",
+        &[key('s')],
+        TIMERS,
+        Needs::Timer,
+        true,
+    ),
+    action(
+        A::TimerPause,
+        "timer: pause",
+        "pause the timer, logging the time it ran",
+        &[key('p')],
+        TIMERS,
+        Needs::Timer,
+        true,
+    ),
+    action(
+        A::TimerStop,
+        "timer: stop",
+        "stop the timer, logging the time it ran, and set it back to zero",
+        &[key('x')],
+        TIMERS,
+        Needs::Timer,
+        true,
+    ),
+    action(
+        A::ToggleTimeline,
+        "toggle timeline",
+        "switch between the entries and a day timeline",
+        &[key('T')],
+        TIME,
+        Needs::Nothing,
+        true,
+    ),
+    action(
+        A::NextEntry,
+        "next entry",
+        "select the next entry",
+        &[key('j'), code(KeyCode::Down)],
+        TIME,
+        Needs::Nothing,
+        true,
+    ),
+    action(
+        A::PreviousEntry,
+        "previous entry",
+        "select the previous entry",
+        &[key('k'), code(KeyCode::Up)],
+        TIME,
+        Needs::Nothing,
+        true,
+    ),
+    action(
+        A::EarlierDay,
+        "earlier day",
+        "show the day before on the timeline",
+        &[key('h'), code(KeyCode::Left)],
+        TIME,
+        Needs::Timeline,
+        false,
+    ),
+    action(
+        A::LaterDay,
+        "later day",
+        "show the day after on the timeline",
+        &[key('l'), code(KeyCode::Right)],
+        TIME,
+        Needs::Timeline,
+        false,
+    ),
+    action(
+        A::FollowEntryReference,
+        "go to entry todo",
+        "go to the todo the entry is on",
+        &[code(KeyCode::Enter)],
+        TIME,
+        Needs::Entry,
+        false,
+    ),
+    action(
+        A::EntryNote,
+        "edit entry note",
+        "edit the selected entry's note",
+        &[key('n')],
+        TIME,
+        Needs::Entry,
+        true,
+    ),
+    action(
+        A::EntryTags,
+        "edit entry tags",
+        "tag the selected entry, in place of its todo's tags",
+        &[key('a')],
+        TIME,
+        Needs::Entry,
+        false,
+    ),
+    action(
+        A::EntryClock,
+        "entry clock",
+        "show entries by the current zone or by the clock they were recorded on",
+        &[key('Z')],
+        TIME,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::Timesheet,
+        "timesheet",
+        "preview this month's hours per category and day",
+        &[key('g')],
+        TIME,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::BreakdownScale,
+        "breakdown scale",
+        "chart the time per category by day, week or month, in turn",
+        &[key('s')],
+        TIME,
+        Needs::Nothing,
+        true,
+    ),
+    action(
+        A::ReportTag,
+        "report tag",
+        "count only the time with a tag in the week summary and the timesheet",
+        &[key('f')],
+        TIME,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::ReportBy,
+        "report by tag",
+        "switch the week summary and the timesheet between a row per category and per tag",
+        &[key('B')],
+        TIME,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::Today,
+        "today",
+        "review what happened today",
+        &[],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::Yesterday,
+        "yesterday",
+        "review the last working day, for a standup",
+        &[],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::Calendar,
+        "calendar",
+        "show the month with the todos due each day",
+        &[],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::Stats,
+        "pomodoro stats",
+        "show the pomodoro statistics",
+        &[],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::Storage,
+        "storage",
+        "show how much room the data files take",
+        &[],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::Inconsistencies,
+        "inconsistencies",
+        "list dangling references and fix them",
+        &[],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::Share,
+        "share todo",
+        "copy the todo as markdown to the clipboard",
+        &[],
+        Global,
+        Needs::Todo,
+        false,
+    ),
+    action(
+        A::ExportItem,
+        "export todo",
+        "write the todo and its history to todo-ID.md",
+        &[],
+        Global,
+        Needs::Todo,
+        false,
+    ),
+    action(
+        A::Snooze,
+        "snooze due alerts",
+        "hold back due alerts for the configured minutes",
+        &[],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::Refresh,
+        "refresh",
+        "reload what another process changed on disk, the ● in the status bar",
+        &[code(KeyCode::F(5))],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+    action(
+        A::SaveNow,
+        "save now",
+        "save the todos now, or try again a save that failed",
+        &[ctrl(KeyCode::Char('s'))],
+        Global,
+        Needs::Nothing,
+        false,
+    ),
+];
+
+/// Keys by action in place of the defaults, `keys` in the config. Actions
+/// it doesn't list keep their default keys.
+pub type Keymap = BTreeMap<ActionId, Vec<Key>>;
+
+/// Shared rather than copied out, as every frame looks keys up; None
+/// until the config binds any.
+static KEYMAP: Mutex<Option<Arc<Keymap>>> = Mutex::new(None);
+
+/// Binds the keys of `keymap` in place of the defaults, for every lookup
+/// from now on.
+pub fn set_keymap(keymap: Keymap) {
+    *KEYMAP
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Arc::new(keymap));
+}
+
+/// The keymap in use.
+pub fn current_keymap() -> Arc<Keymap> {
+    KEYMAP
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+/// The scopes searched for a key on `tab`, in order: the tab's own
+/// bindings win over the global ones. Whatever else claims keys comes
+/// before all of them, in this order: an open popup or text prompt takes
+/// every key, then an undo toast's key, the macro keys and the keys of
+/// an open view.
+pub fn precedence(tab: MenuItem) -> [Scope; 2] {
+    [Tab(tab), Global]
+}
+
+/// The key an undo toast claims: undo's first key now, or its default
+/// when the config left it none.
+pub fn undo_key() -> Key {
+    ACTIONS
+        .iter()
+        .find(|a| a.id == ActionId::Undo)
+        .and_then(|a| a.keys().first().copied())
+        .unwrap_or(UNDO_KEY)
+}
+
+/// The action `event` runs on `tab`.
+pub fn lookup(event: &KeyEvent, tab: MenuItem) -> Option<ActionId> {
+    let keymap = current_keymap();
+    precedence(tab).into_iter().find_map(|scope| {
+        ACTIONS
+            .iter()
+            .find(|a| a.scope == scope && a.keys_in(&keymap).iter().any(|k| k.matches(event)))
+            .map(|a| a.id)
+    })
+}
+
+/// What the todo menu Enter opens offers besides moving the todo, in
+/// order.
+pub const MENU: &[ActionId] = &[
+    A::EditTodo,
+    A::EditDescription,
+    A::StartTimer,
+    A::DeleteTodo,
+    A::OpenTodo,
+    A::FollowTodoReference,
+    A::Attach,
+    A::MarkTodo,
+    A::BatchEdit,
+    A::TogglePin,
+    A::CycleLabel,
+    A::Share,
+    A::ExportItem,
+];
+
+/// What the column menu `.` opens offers, in order.
+pub const COLUMN_MENU: &[ActionId] = &[
+    A::ColumnSort,
+    A::CollapseColumn,
+    A::ColumnArchive,
+    A::ColumnWipLimit,
+    A::ColumnExport,
+    A::ColumnClearSubtasks,
+];
+
+/// The short list of keys for the status line on `tab`.
+pub fn hints(tab: MenuItem) -> String {
+    ACTIONS
+        .iter()
+        .filter(|a| a.hint && (a.scope == Tab(tab) || a.scope == Global))
+        .map(|a| format!("{}: {}", a.keys_label(), a.name))
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
+
+pub fn scope_title(scope: Scope) -> &'static str {
+    match scope {
+        Global => "Everywhere",
+        Tab(MenuItem::Home) => "Home",
+        Tab(MenuItem::Todos) => "Todos",
+        Tab(MenuItem::Timers) => "Timers",
+        Tab(MenuItem::TimeTracking) => "TimeTracking",
+    }
+}
+
+/// Scores `query` as a subsequence of `text`, ignoring case: matches at the
+/// start of a word and runs of consecutive chars count more. None when a
+/// char of the query is missing.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut at = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = (at..text.len()).find(|i| text[*i] == wanted)?;
+        score += 1;
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 2;
+        }
+        score -= (found - at).min(3) as i32;
+        previous = Some(found);
+        at = found + 1;
+    }
+    Some(score)
+}
+
+pub enum PaletteAction {
+    Continue,
+    Cancel,
+    Run(ActionId),
+    /// Enter on an action that can't run here, with the reason.
+    Unavailable(&'static str, String),
+}
+
+/// The popup searching the registry. Which actions can run is worked out
+/// when it opens, since nothing else changes while it is up.
+pub struct Palette {
+    input: Input,
+    /// Every action with the reason it can't run here, if any.
+    actions: Vec<(&'static Action, Option<String>)>,
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl Palette {
+    pub fn new(actions: Vec<(&'static Action, Option<String>)>) -> Palette {
+        let mut palette = Palette {
+            input: Input::default(),
+            actions,
+            matches: vec![],
+            selected: 0,
+        };
+        palette.search();
+        palette
+    }
+
+    /// Best matches first; actions that can run go before grayed ones with
+    /// the same score, and the registry order breaks ties.
+    fn search(&mut self) {
+        let query = self.input.value();
+        let mut scored: Vec<(i32, bool, usize)> = self
+            .actions
+            .iter()
+            .enumerate()
+            .filter(|(_, (a, _))| a.id != ActionId::Palette)
+            .filter_map(|(i, (action, blocked))| {
+                let score = fuzzy_score(query, action.name)
+                    .or_else(|| fuzzy_score(query, action.description).map(|s| s - 10))?;
+                Some((score, blocked.is_some(), i))
+            })
+            .collect();
+        scored.sort_by_key(|(score, blocked, i)| (-score, *blocked, *i));
+        self.matches = scored.into_iter().map(|(_, _, i)| i).collect();
+        self.selected = 0;
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> PaletteAction {
+        match key.code {
+            KeyCode::Esc => return PaletteAction::Cancel,
+            KeyCode::Enter => {
+                return match self.matches.get(self.selected).map(|i| &self.actions[*i]) {
+                    Some((action, None)) => PaletteAction::Run(action.id),
+                    Some((action, Some(reason))) => {
+                        PaletteAction::Unavailable(action.name, reason.clone())
+                    }
+                    None => PaletteAction::Continue,
+                }
+            }
+            KeyCode::Down | KeyCode::Tab => {
+                self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1))
+            }
+            KeyCode::Up | KeyCode::BackTab => self.selected = self.selected.saturating_sub(1),
+            _ => {
+                if self.input.handle_key(key) {
+                    self.search();
+                }
+            }
+        }
+        PaletteAction::Continue
+    }
+}
+
+pub fn render_palette<B: Backend>(rect: &mut Frame<B>, palette: &Palette, area: Rect) {
+    let popup = centered_rect(60, 60, area);
+    let rows = popup.height.saturating_sub(4) as usize;
+    let offset = (palette.selected + 1).saturating_sub(rows);
+    let cursor = cursor::current();
+    let mut lines = vec![
+        Spans::from(vec![Span::raw(format!("> {}", palette.input.value()))]),
+        Spans::from(vec![Span::raw("")]),
+    ];
+    for (row, i) in palette.matches.iter().enumerate().skip(offset).take(rows) {
+        let (action, blocked) = &palette.actions[*i];
+        let (marker, style) = cursor.row(
+            row == palette.selected,
+            match blocked {
+                Some(_) => Style::default().fg(Color::DarkGray),
+                None => Style::default().fg(Color::White),
+            },
+        );
+        let mut spans = vec![
+            Span::styled(format!("{}{:<22}", marker, action.name), style),
+            Span::styled(
+                format!(" {:<10}", action.keys_label()),
+                Style::default().fg(Color::Yellow),
+            ),
+            Span::styled(
+                format!(" {}", blocked.as_deref().unwrap_or(action.description)),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ];
+        if blocked.is_none() {
+            spans[2].style = Style::default().fg(Color::Gray);
+        }
+        lines.push(Spans::from(spans));
+    }
+    if palette.matches.is_empty() {
+        lines.push(Spans::from(vec![Span::styled(
+            "no action matches",
+            Style::default().fg(Color::DarkGray),
+        )]));
+    }
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title("Commands (Up/Down: select, Enter: run, Esc: close)")
+            .border_type(BorderType::Plain),
+    );
+
+    rect.render_widget(Clear, popup);
+    rect.render_widget(paragraph, popup);
+    rect.set_cursor(popup.x + 3 + palette.input.cursor() as u16, popup.y + 1);
+}
+
+/// Every action by where it works, scrolled `offset` lines down.
+pub fn render_help<B: Backend>(rect: &mut Frame<B>, offset: u16, chrome: Chrome, area: Rect) {
+    let scopes = [Global, HOME, TODOS, TIMERS, TIME];
+    let mut lines = vec![Spans::from(vec![Span::styled(
+        "A tab's keys win over the keys that work everywhere; popups take every key.",
+        Style::default().fg(Color::DarkGray),
+    )])];
+    for scope in scopes {
+        let actions: Vec<&Action> = ACTIONS.iter().filter(|a| a.scope == scope).collect();
+        if actions.is_empty() {
+            continue;
+        }
+        lines.push(Spans::from(vec![Span::raw("")]));
+        lines.push(Spans::from(vec![Span::styled(
+            scope_title(scope),
+            Style::default()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::BOLD),
+        )]));
+        for action in actions {
+            let keys = match action.keys_label() {
+                keys if keys.is_empty() => "palette".to_string(),
+                keys => keys,
+            };
+            lines.push(Spans::from(vec![
+                Span::styled(
+                    format!("  {:<14}", keys),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::styled(
+                    format!("{:<22}", action.name),
+                    Style::default().fg(Color::White),
+                ),
+                Span::styled(action.description, Style::default().fg(Color::Gray)),
+            ]));
+        }
+    }
+    let paragraph = Paragraph::new(lines)
+        .scroll((offset, 0))
+        .block(chrome.block().title("Help (j/k: scroll, Esc: close)"));
+    rect.render_widget(paragraph, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keymap;
+
+    /// Default keys bound to more than one action of the same scope, of
+    /// which only the first could ever run. The ones a keymap from the
+    /// config brings are resolved on screen instead, see
+    /// `keymap::KeyConflicts`.
+    fn conflicts(actions: &[Action]) -> Vec<String> {
+        keymap::conflicts(actions, &Keymap::new())
+            .iter()
+            .map(|conflict| conflict.describe(actions))
+            .collect()
+    }
+
+    /// Actions bound only to letters that the arrow keys reach through the
+    /// palette (F2) alone, which lists them all.
+    const PALETTE_ROUTE: &[ActionId] = &[
+        A::CommandLine,
+        A::HomeTab,
+        A::TodosTab,
+        A::TimersTab,
+        A::TimeTrackingTab,
+        A::GotoRunning,
+        A::ToggleBreak,
+        A::SaveNow,
+        A::Undo,
+        A::ToggleBadges,
+        A::TrackedWeek,
+        A::CollapseColumn,
+        A::ColumnMenu,
+        A::TimerStart,
+        A::TimerPause,
+        A::TimerStop,
+        A::BadgeFilter,
+        A::DueFilter,
+        A::ToggleDetail,
+        A::ToggleTimeline,
+        A::EntryNote,
+        A::EntryTags,
+        A::EntryClock,
+        A::Timesheet,
+        A::BreakdownScale,
+        A::ReportTag,
+        A::ReportBy,
+    ];
+
+    /// How an action runs without letter keys: a key of its own that isn't
+    /// a character, an entry in the todo menu, or the palette. None when only
+    /// letters reach it.
+    fn arrow_route(action: &Action) -> Option<String> {
+        let keys = action.keys();
+        if let Some(key) = keys.iter().find(|k| !matches!(k.code, KeyCode::Char(_))) {
+            return Some(key.label());
+        }
+        if keys.is_empty() || PALETTE_ROUTE.contains(&action.id) {
+            return Some("the palette".to_string());
+        }
+        MENU.contains(&action.id)
+            .then(|| "the todo menu".to_string())
+    }
+
+    /// Actions only letters reach, e.g. "Todos: z (collapse column) has no
+    /// arrow-key route".
+    fn arrowless(actions: &[Action]) -> Vec<String> {
+        actions
+            .iter()
+            .filter(|action| arrow_route(action).is_none())
+            .map(|action| {
+                format!(
+                    "{}: {} ({}) has no arrow-key route",
+                    scope_title(action.scope),
+                    action.keys_label(),
+                    action.name
+                )
+            })
+            .collect()
+    }
+
+    /// Arrows, Enter, Esc, Tab, Delete and the F keys alone drive the
+    /// whole app.
+    #[test]
+    fn every_action_has_an_arrow_key_route() {
+        assert_eq!(arrowless(ACTIONS), Vec::<String>::new());
+    }
+
+    #[test]
+    fn letters_alone_are_no_route() {
+        const Z: &[Key] = &[key('z')];
+        const F4: &[Key] = &[code(KeyCode::F(4))];
+        let todos = Tab(MenuItem::Todos);
+        let actions = [
+            action(A::Search, "find todo", "", Z, todos, Needs::Nothing, false),
+            action(
+                A::Palette,
+                "command palette",
+                "",
+                F4,
+                todos,
+                Needs::Nothing,
+                false,
+            ),
+            action(
+                A::CommandLine,
+                "command line",
+                "",
+                &[],
+                todos,
+                Needs::Nothing,
+                false,
+            ),
+        ];
+        assert_eq!(
+            arrowless(&actions),
+            ["Todos: z (find todo) has no arrow-key route"]
+        );
+        assert_eq!(arrow_route(&actions[1]).as_deref(), Some("F4"));
+        assert_eq!(arrow_route(&actions[2]).as_deref(), Some("the palette"));
+    }
+
+    #[test]
+    fn default_keys_do_not_conflict() {
+        assert_eq!(conflicts(ACTIONS), Vec::<String>::new());
+    }
+
+    #[test]
+    fn a_key_shared_within_a_scope_conflicts() {
+        const H: &[Key] = &[key('h')];
+        let todos = Tab(MenuItem::Todos);
+        let actions = [
+            action(A::Search, "find todo", "", H, todos, Needs::Nothing, false),
+            action(
+                A::Palette,
+                "command palette",
+                "",
+                H,
+                todos,
+                Needs::Nothing,
+                false,
+            ),
+            // another scope may use the key for something else
+            action(
+                A::CommandLine,
+                "command line",
+                "",
+                H,
+                Global,
+                Needs::Nothing,
+                false,
+            ),
+        ];
+        assert_eq!(
+            conflicts(&actions),
+            ["Todos: h is bound to both find todo and command palette"]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::sync::Mutex;
+
+use crate::{write_atomic, Error, Todo, TodoStatus};
+
+const ACTIVITY_PATH: &str = "./data/activity.json";
+
+/// Why `record` last failed, until `take_warning` reports it.
+static WARNING: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ActivityKind {
+    Created,
+    Moved { from: TodoStatus, to: TodoStatus },
+    Deleted,
+}
+
+/// One entry of the append-only log of what happened to todos.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ActivityEvent {
+    pub at: DateTime<Utc>,
+    pub todo_id: usize,
+    pub title: String,
+    pub kind: ActivityKind,
+}
+
+pub fn read_activity() -> Result<Vec<ActivityEvent>, Error> {
+    match fs::read_to_string(ACTIVITY_PATH) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(Error::ReadFileError(ACTIVITY_PATH.to_string(), err)),
+    }
+}
+
+/// The events that turn `before` into `after`, matched by id.
+pub fn changes(before: &[Todo], after: &[Todo], at: DateTime<Utc>) -> Vec<ActivityEvent> {
+    let event = |todo: &Todo, kind| ActivityEvent {
+        at,
+        todo_id: todo.id,
+        title: todo.title.clone(),
+        kind,
+    };
+
+    let mut events = vec![];
+    for todo in after {
+        match before.iter().find(|t| t.id == todo.id) {
+            None => events.push(event(todo, ActivityKind::Created)),
+            Some(old) if old.status() != todo.status() => events.push(event(
+                todo,
+                ActivityKind::Moved {
+                    from: old.status(),
+                    to: todo.status(),
+                },
+            )),
+            Some(_) => {}
+        }
+    }
+    for todo in before
+        .iter()
+        .filter(|t| !after.iter().any(|a| a.id == t.id))
+    {
+        events.push(event(todo, ActivityKind::Deleted));
+    }
+    events
+}
+
+pub fn append(events: &[ActivityEvent]) -> Result<(), Error> {
+    if events.is_empty() {
+        return Ok(());
+    }
+    let mut log = read_activity()?;
+    log.extend_from_slice(events);
+    write_atomic(ACTIVITY_PATH, &serde_json::to_string_pretty(&log)?)
+}
+
+/// Appends `events` for a save that already happened. The save stands
+/// either way, so a log that can't be written is a warning for
+/// `take_warning` rather than an error of the save.
+pub fn record(events: &[ActivityEvent]) {
+    if let Err(err) = append(events) {
+        *WARNING
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) =
+            Some(format!("saved, but the activity log missed it: {}", err));
+    }
+}
+
+/// The warning of the last `record` that failed, once.
+pub fn take_warning() -> Option<String> {
+    WARNING
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take()
+}
@@ -0,0 +1,92 @@
+use chrono::prelude::*;
+
+use crate::cli;
+use crate::db;
+use crate::suggest::CategoryIndex;
+use crate::{read_db, update_db, Error, Todo};
+
+pub const DEFAULT_CATEGORY: &str = "inbox";
+
+const USAGE: &str = "usage: add TITLE [--description TEXT] [--category NAME] [--parent ID]";
+
+/// Runs `add TITLE [--description TEXT] [--category NAME] [--parent ID]`
+/// and returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let mut title = vec![];
+    let mut description = String::new();
+    let mut category = None;
+    let mut parent = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--description" | "--category" | "--parent" => {
+                let value = match args.next() {
+                    Some(value) => value.clone(),
+                    None => return cli::usage(format!("{} needs a value", arg)),
+                };
+                match arg.as_str() {
+                    "--description" => description = value,
+                    "--category" => category = Some(value),
+                    _ => match value.parse::<usize>() {
+                        Ok(id) => parent = Some(id),
+                        Err(_) => return cli::usage(format!("invalid --parent '{}'", value)),
+                    },
+                }
+            }
+            _ => title.push(arg.as_str()),
+        }
+    }
+    let title = title.join(" ");
+    if title.trim().is_empty() {
+        return cli::usage(USAGE);
+    }
+
+    // only used for the category guess, the write below re-reads under the lock
+    let todos = match read_db() {
+        Ok(todos) => todos,
+        Err(err) => return cli::fail(&err),
+    };
+    if let Some(parent) = parent {
+        if !todos.iter().any(|t| t.id == parent) {
+            return cli::usage(format!("no todo {} for --parent", parent));
+        }
+    }
+    let category = match category {
+        Some(category) => category,
+        None => match CategoryIndex::build(&todos).suggest(&title) {
+            Some(suggestion) => {
+                println!(
+                    "category guessed: {} (from '{}')",
+                    suggestion.category, suggestion.word
+                );
+                suggestion.category
+            }
+            None => DEFAULT_CATEGORY.to_string(),
+        },
+    };
+
+    let mut todo = new_todo(title, category);
+    if let Err(err) = todo.set_description(description) {
+        return cli::usage(format!("--description: {}", err));
+    }
+    todo.parent = parent;
+    todo.source = Some("cli".to_string());
+    let id = match insert(todo) {
+        Ok(id) => id,
+        Err(err) => return cli::fail(&err),
+    };
+    println!("added todo {}", id);
+    0
+}
+
+/// A todo in ToDo with nothing but a title and category, for the add
+/// commands to fill in.
+pub fn new_todo(title: String, category: String) -> Todo {
+    Todo::new(title, category, Utc::now())
+}
+
+/// Adds `todo` under the next free id and returns that id.
+pub fn insert(todo: Todo) -> Result<usize, Error> {
+    update_db(|todos| db::push_todo(todos, todo))
+}
@@ -0,0 +1,504 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use std::fs;
+
+use crate::cli;
+use crate::config;
+use crate::escalation::{effective_priority, EffectivePriority, EscalationRule};
+use crate::subtasks::effective_due;
+use crate::timetracking::local_midnight;
+use crate::zone::{self, Zone};
+use crate::{read_db, Error, Todo, TodoStatus};
+
+/// What `lpr` prints on a line and a page.
+const WIDTH: usize = 80;
+const PAGE_LINES: usize = 66;
+
+/// Lines an overdue section or a day gets before the rest is counted as
+/// "+N more".
+const MAX_OVERDUE: usize = 6;
+const MAX_PER_DAY: usize = 6;
+
+const USAGE: &str = "usage: agenda [--week YYYY-Www] [--format pdf-ready-text|md] [--output FILE]";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AgendaFormat {
+    Text,
+    Markdown,
+}
+
+/// The Monday of the ISO week `value` names, as YYYY-Www.
+pub fn parse_week(value: &str) -> Option<NaiveDate> {
+    let (year, week) = value.split_once("-W")?;
+    NaiveDate::from_isoywd_opt(year.parse().ok()?, week.parse().ok()?, Weekday::Mon)
+}
+
+pub struct AgendaItem {
+    pub id: usize,
+    pub title: String,
+    pub priority: EffectivePriority,
+    pub due: DateTime<Utc>,
+}
+
+/// A week of due todos laid out for paper: Monday to Friday with what is
+/// due each day, and what was due before the week and isn't done.
+pub struct Agenda {
+    pub monday: NaiveDate,
+    pub zone: Zone,
+    /// Each weekday with its todos, highest priority first.
+    pub days: Vec<(NaiveDate, Vec<AgendaItem>)>,
+    pub overdue: Vec<AgendaItem>,
+}
+
+impl Agenda {
+    /// The agenda of the week starting on `monday`, by the days of `zone`
+    /// and the effective due dates. Priorities are raised by `rules` as
+    /// they are at `now`.
+    ///
+    /// ```
+    /// use chrono::{NaiveDate, TimeZone, Utc};
+    /// use work_time_cli::{Agenda, Todo, Zone};
+    ///
+    /// let mut todo = Todo::new("File taxes".into(), "home".into(), Utc::now());
+    /// todo.due = Some(Utc.with_ymd_and_hms(2024, 5, 15, 12, 0, 0).unwrap());
+    /// let monday = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+    /// let now = Utc.with_ymd_and_hms(2024, 5, 13, 9, 0, 0).unwrap();
+    ///
+    /// let agenda = Agenda::build(&[todo], monday, &[], Zone::parse("UTC").unwrap(), now);
+    /// let (wednesday, items) = &agenda.days[2];
+    /// assert_eq!(wednesday.to_string(), "2024-05-15");
+    /// assert_eq!(items[0].title, "File taxes");
+    /// assert!(agenda.overdue.is_empty());
+    /// ```
+    pub fn build(
+        todos: &[Todo],
+        monday: NaiveDate,
+        rules: &[EscalationRule],
+        zone: Zone,
+        now: DateTime<Utc>,
+    ) -> Agenda {
+        let mut open: Vec<AgendaItem> = todos
+            .iter()
+            .filter(|t| t.status() != TodoStatus::Done)
+            .filter_map(|t| {
+                Some(AgendaItem {
+                    id: t.id,
+                    title: t.title.clone(),
+                    priority: effective_priority(t, todos, now, rules),
+                    due: effective_due(t, todos)?,
+                })
+            })
+            .collect();
+        open.sort_by(|a, b| {
+            b.priority
+                .priority
+                .cmp(&a.priority.priority)
+                .then(a.due.cmp(&b.due))
+                .then(a.id.cmp(&b.id))
+        });
+
+        let start = local_midnight(monday, zone);
+        let mut days: Vec<(NaiveDate, Vec<AgendaItem>)> = (0..5)
+            .map(|i| (monday + Duration::days(i), vec![]))
+            .collect();
+        let mut overdue = vec![];
+        for item in open {
+            if item.due < start {
+                overdue.push(item);
+                continue;
+            }
+            let day = item.due.with_timezone(&zone).date_naive();
+            if let Some((_, items)) = days.iter_mut().find(|(d, _)| *d == day) {
+                items.push(item);
+            }
+        }
+        Agenda {
+            monday,
+            zone,
+            days,
+            overdue,
+        }
+    }
+
+    fn title(&self) -> String {
+        let friday = self.monday + Duration::days(4);
+        format!(
+            "Agenda {}, {} - {}",
+            self.monday.format("%G-W%V"),
+            self.monday.format("%b %d"),
+            friday.format("%b %d %Y")
+        )
+    }
+
+    /// The agenda as one page of plain text, 80 columns wide, the rest of
+    /// the page ruled for notes.
+    pub fn text(&self) -> String {
+        let mut lines = vec![self.title(), "=".repeat(WIDTH)];
+        if !self.overdue.is_empty() {
+            lines.push("Overdue".to_string());
+            lines.extend(self.text_lines(&self.overdue, MAX_OVERDUE, true));
+            lines.push("-".repeat(WIDTH));
+        }
+        for (day, items) in &self.days {
+            lines.push(day.format("%A, %b %d").to_string());
+            if items.is_empty() {
+                lines.push("  -".to_string());
+            }
+            lines.extend(self.text_lines(items, MAX_PER_DAY, false));
+            lines.push(String::new());
+        }
+        lines.push("Notes".to_string());
+        while lines.len() < PAGE_LINES - 1 {
+            lines.push(String::new());
+            lines.push("_".repeat(WIDTH));
+        }
+        lines.truncate(PAGE_LINES - 1);
+        lines.push(format!("{:>width$}", self.zone.name(), width = WIDTH));
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    /// The agenda as Markdown with a checkbox per todo.
+    pub fn markdown(&self) -> String {
+        let mut out = format!("# {}\n\n", self.title());
+        if !self.overdue.is_empty() {
+            out.push_str("## Overdue\n\n");
+            out.push_str(&self.markdown_lines(&self.overdue, MAX_OVERDUE, true));
+            out.push('\n');
+        }
+        for (day, items) in &self.days {
+            out.push_str(&format!("## {}\n\n", day.format("%A, %b %d")));
+            if items.is_empty() {
+                out.push_str("Nothing due.\n");
+            }
+            out.push_str(&self.markdown_lines(items, MAX_PER_DAY, false));
+            out.push('\n');
+        }
+        out.push_str("## Notes\n\n\n\n");
+        out
+    }
+
+    /// A line per item with the due time right aligned, cut to fit.
+    fn text_lines(&self, items: &[AgendaItem], max: usize, with_date: bool) -> Vec<String> {
+        let (shown, more) = visible(items, max);
+        let mut lines: Vec<String> = shown
+            .iter()
+            .map(|item| {
+                let when = self.when(item, with_date);
+                let head = format!(
+                    "  {:<3} #{} ",
+                    item.priority.marker().unwrap_or(""),
+                    item.id
+                );
+                let room = WIDTH - head.chars().count() - when.len() - 1;
+                let title: String = if item.title.chars().count() > room {
+                    let cut: String = item.title.chars().take(room - 3).collect();
+                    format!("{}...", cut)
+                } else {
+                    item.title.clone()
+                };
+                format!("{}{:<room$} {}", head, title, when, room = room)
+                    .trim_end()
+                    .to_string()
+            })
+            .collect();
+        if more > 0 {
+            lines.push(format!("  +{} more", more));
+        }
+        lines
+    }
+
+    fn markdown_lines(&self, items: &[AgendaItem], max: usize, with_date: bool) -> String {
+        let (shown, more) = visible(items, max);
+        let mut out = String::new();
+        for item in shown {
+            out.push_str("- [ ] ");
+            if let Some(marker) = item.priority.marker() {
+                out.push_str(marker);
+                out.push(' ');
+            }
+            out.push_str(&format!("#{} {}", item.id, item.title));
+            let when = self.when(item, with_date);
+            if !when.is_empty() {
+                out.push_str(&format!(" ({})", when));
+            }
+            out.push('\n');
+        }
+        if more > 0 {
+            out.push_str(&format!("\n+{} more\n", more));
+        }
+        out
+    }
+
+    /// When `item` is due: the time, after the date with `with_date`. A
+    /// due date without a time has none.
+    fn when(&self, item: &AgendaItem, with_date: bool) -> String {
+        let due = item.due.with_timezone(&self.zone);
+        let time = due.format("%H:%M").to_string();
+        match (with_date, time == "23:59") {
+            (true, true) => due.format("%b %d").to_string(),
+            (true, false) => format!("{} {}", due.format("%b %d"), time),
+            (false, true) => String::new(),
+            (false, false) => time,
+        }
+    }
+}
+
+/// The items that fit in `max` lines and how many are left out. When some
+/// are, the last line goes to saying so.
+fn visible(items: &[AgendaItem], max: usize) -> (&[AgendaItem], usize) {
+    if items.len() > max {
+        (&items[..max - 1], items.len() - max + 1)
+    } else {
+        (items, 0)
+    }
+}
+
+/// Runs `agenda [--week YYYY-Www] [--format pdf-ready-text|md] [--output
+/// FILE]` and returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let zone = zone::current();
+    let today = zone::now().date_naive();
+    let mut monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let mut format = AgendaFormat::Text;
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--week" | "--format" | "--output" => match args.next() {
+                Some(value) => value,
+                None => return cli::usage(format!("{} needs a value", arg)),
+            },
+            _ => return cli::usage(USAGE),
+        };
+        match arg.as_str() {
+            "--week" => match parse_week(value) {
+                Some(first) => monday = first,
+                None => return cli::usage("--week takes an ISO week like 2024-W21"),
+            },
+            "--format" => {
+                format = match value.as_str() {
+                    "pdf-ready-text" | "text" => AgendaFormat::Text,
+                    "md" | "markdown" => AgendaFormat::Markdown,
+                    _ => return cli::usage("--format takes pdf-ready-text or md"),
+                }
+            }
+            _ => output = Some(value),
+        }
+    }
+
+    let config = match config::load() {
+        Ok(config) => config,
+        Err(err) => return cli::fail(&err),
+    };
+    let todos = match read_db() {
+        Ok(todos) => todos,
+        Err(err) => return cli::fail(&err),
+    };
+    let agenda = Agenda::build(
+        &todos,
+        monday,
+        &config.priority_escalation,
+        zone,
+        Utc::now(),
+    );
+    let rendered = match format {
+        AgendaFormat::Text => agenda.text(),
+        AgendaFormat::Markdown => agenda.markdown(),
+    };
+    match output {
+        Some(path) => {
+            if let Err(err) = fs::write(path, rendered) {
+                return cli::fail(&Error::WriteFileError(path.clone(), err));
+            }
+        }
+        None => print!("{}", rendered),
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, day, hour, minute, 0).unwrap()
+    }
+
+    fn todo(id: usize, title: &str, due: DateTime<Utc>, priority: Option<Priority>) -> Todo {
+        let mut todo = Todo::new(title.to_string(), "work".to_string(), at(1, 9, 0));
+        todo.id = id;
+        todo.due = Some(due);
+        todo.priority = priority;
+        todo
+    }
+
+    /// 2024-W21: one todo overdue, two on Monday, nothing on Tuesday, nine
+    /// on Wednesday, a long title on Thursday and on Friday a parent due
+    /// by its subtask; a done todo and one due Saturday stay off.
+    fn week() -> Agenda {
+        let mut todos = vec![
+            todo(1, "Renew passport", at(17, 10, 0), Some(Priority::High)),
+            todo(2, "Standup notes", at(20, 9, 30), None),
+            todo(3, "Submit expenses", at(20, 23, 59), Some(Priority::Urgent)),
+        ];
+        for hour in 8..17 {
+            let id = hour as usize + 2;
+            let priority = if id == 18 { Some(Priority::Low) } else { None };
+            todos.push(todo(
+                id,
+                &format!("Review PR {}", id),
+                at(22, hour, 0),
+                priority,
+            ));
+        }
+        todos.push(todo(
+            20,
+            "Write the quarterly planning document for the platform team and circulate it",
+            at(23, 16, 0),
+            Some(Priority::Medium),
+        ));
+        let mut parent = todo(21, "Launch", at(30, 12, 0), None);
+        parent.due = None;
+        let mut subtask = todo(22, "Launch checklist", at(24, 11, 0), None);
+        subtask.parent = Some(21);
+        todos.push(parent);
+        todos.push(subtask);
+        let mut finished = todo(23, "Already done", at(21, 12, 0), None);
+        finished.set_status(TodoStatus::Done, at(20, 12, 0));
+        todos.push(finished);
+        todos.push(todo(24, "Weekend chores", at(25, 10, 0), None));
+
+        let monday = parse_week("2024-W21").unwrap();
+        Agenda::build(
+            &todos,
+            monday,
+            &[],
+            Zone::Named(chrono_tz::UTC),
+            at(20, 8, 0),
+        )
+    }
+
+    #[test]
+    fn weeks_go_by_iso_numbers() {
+        assert_eq!(parse_week("2024-W21"), NaiveDate::from_ymd_opt(2024, 5, 20));
+        assert_eq!(parse_week("2021-W01"), NaiveDate::from_ymd_opt(2021, 1, 4));
+        assert_eq!(parse_week("2024-W54"), None);
+        assert_eq!(parse_week("2024-21"), None);
+        assert_eq!(parse_week("W21"), None);
+    }
+
+    #[test]
+    fn the_text_fits_a_page_of_80_columns_and_cuts_a_full_day() {
+        let text = week().text();
+        let lines: Vec<&str> = text.lines().collect();
+        let expected = [
+            "Agenda 2024-W21, May 20 - May 24 2024",
+            &"=".repeat(80),
+            "Overdue",
+            "  !!  #1 Renew passport                                             May 17 10:00",
+            &"-".repeat(80),
+            "Monday, May 20",
+            "  !!! #3 Submit expenses",
+            "      #2 Standup notes                                                     09:30",
+            "",
+            "Tuesday, May 21",
+            "  -",
+            "",
+            "Wednesday, May 22",
+            "  ·   #18 Review PR 18                                                     16:00",
+            "      #10 Review PR 10                                                     08:00",
+            "      #11 Review PR 11                                                     09:00",
+            "      #12 Review PR 12                                                     10:00",
+            "      #13 Review PR 13                                                     11:00",
+            "  +4 more",
+            "",
+            "Thursday, May 23",
+            "  !   #20 Write the quarterly planning document for the platform team a... 16:00",
+            "",
+            "Friday, May 24",
+            "      #21 Launch                                                           11:00",
+            "      #22 Launch checklist                                                 11:00",
+            "",
+            "Notes",
+        ];
+        assert_eq!(lines[..expected.len()], expected);
+        // the rest of the page is ruled for notes, the zone at the foot
+        for pair in lines[expected.len()..PAGE_LINES - 2].chunks(2) {
+            assert_eq!(pair, ["", &"_".repeat(80)]);
+        }
+        assert_eq!(lines[PAGE_LINES - 2], "");
+        assert_eq!(lines[PAGE_LINES - 1], format!("{:>80}", "UTC"));
+        assert_eq!(lines.len(), PAGE_LINES);
+        assert!(text.ends_with('\n'));
+        assert!(lines.iter().all(|line| line.chars().count() <= WIDTH));
+    }
+
+    #[test]
+    fn the_markdown_has_a_checkbox_per_todo_and_the_same_cuts() {
+        assert_eq!(
+            week().markdown(),
+            "# Agenda 2024-W21, May 20 - May 24 2024\n\
+             \n\
+             ## Overdue\n\
+             \n\
+             - [ ] !! #1 Renew passport (May 17 10:00)\n\
+             \n\
+             ## Monday, May 20\n\
+             \n\
+             - [ ] !!! #3 Submit expenses\n\
+             - [ ] #2 Standup notes (09:30)\n\
+             \n\
+             ## Tuesday, May 21\n\
+             \n\
+             Nothing due.\n\
+             \n\
+             ## Wednesday, May 22\n\
+             \n\
+             - [ ] · #18 Review PR 18 (16:00)\n\
+             - [ ] #10 Review PR 10 (08:00)\n\
+             - [ ] #11 Review PR 11 (09:00)\n\
+             - [ ] #12 Review PR 12 (10:00)\n\
+             - [ ] #13 Review PR 13 (11:00)\n\
+             \n\
+             +4 more\n\
+             \n\
+             ## Thursday, May 23\n\
+             \n\
+             - [ ] ! #20 Write the quarterly planning document for the platform team \
+             and circulate it (16:00)\n\
+             \n\
+             ## Friday, May 24\n\
+             \n\
+             - [ ] #21 Launch (11:00)\n\
+             - [ ] #22 Launch checklist (11:00)\n\
+             \n\
+             ## Notes\n\n\n\n"
+        );
+    }
+
+    #[test]
+    fn escalated_items_sort_by_their_raised_priority() {
+        let todos = [
+            todo(1, "Plain", at(22, 20, 0), Some(Priority::Medium)),
+            todo(2, "Raised", at(22, 7, 0), None),
+        ];
+        let rules = [EscalationRule {
+            to: Priority::High,
+            hours_before: 48.0,
+        }];
+        let wednesday = |rules: &[EscalationRule]| -> Vec<(usize, Option<&str>)> {
+            let monday = parse_week("2024-W21").unwrap();
+            let utc = Zone::Named(chrono_tz::UTC);
+            Agenda::build(&todos, monday, rules, utc, at(20, 8, 0)).days[2]
+                .1
+                .iter()
+                .map(|item| (item.id, item.priority.marker()))
+                .collect()
+        };
+        assert_eq!(wednesday(&rules), [(2, Some("◇◇")), (1, Some("!"))]);
+        assert_eq!(wednesday(&[]), [(1, Some("!")), (2, None)]);
+    }
+}
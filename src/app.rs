@@ -0,0 +1,4099 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Instant;
+use tui::widgets::{ListState, TableState};
+
+use crate::actions::{self, Action, ActionId, Needs, Palette, PaletteAction, Scope};
+use crate::activity;
+use crate::add;
+use crate::attachments;
+use crate::autosave;
+use crate::badges;
+use crate::batch::{self, BatchForm, FormAction};
+use crate::breakdown::{Breakdown, Granularity};
+use crate::bulk;
+use crate::calendar::{self, DaysOff};
+use crate::carryover::{Carryover, CarryoverAction};
+use crate::cmdline::{CommandLine, LineAction};
+use crate::commands;
+use crate::complete::{self, Vocabulary};
+use crate::config::{self, Config, ConfigWatch, QuitPolicy};
+use crate::cursor;
+use crate::dashboard;
+use crate::daynotes::{self, NoteKind};
+use crate::description::{self, DescriptionEditor, EditorAction};
+use crate::estimate::{self, Remaining};
+use crate::export;
+use crate::fields::Field;
+use crate::filter::{DueWindow, Filter};
+use crate::flextime::{self, Overrides};
+use crate::follow::FilterFollow;
+use crate::glyph::Glyphs;
+use crate::highlight::{Highlights, Target};
+use crate::history;
+use crate::integrity::{self, Fix, Problem, Relink};
+use crate::keymap::{self, ConflictAction, KeyConflicts};
+use crate::label;
+use crate::macros::{self, MacroAction, Macros, Step};
+use crate::maintenance;
+use crate::menu::{column_title, Choice, Menu, MenuAction};
+use crate::month::{self, DayAction, DayDetail};
+use crate::notify::{Bell, Desktop, Dispatcher, StatusBar};
+use crate::pomodoro::Pomodoro;
+use crate::power::Activity;
+use crate::preview::{Preview, PreviewAction};
+use crate::quit::QuitCheck;
+use crate::refs::{self, RefIndex};
+use crate::reminders::DueWatch;
+use crate::reports::Reports;
+use crate::schema;
+use crate::scroll::{ScrollStack, Section};
+use crate::search::{Hit, Location, Search, SearchAction};
+use crate::session::{Draft, Session};
+use crate::sort;
+use crate::sparkline;
+use crate::storage;
+use crate::timeline;
+use crate::timerform::{TimerForm, TimerFormAction};
+use crate::timers;
+use crate::timesheet;
+use crate::timetracking::{self, BudgetWatch, EntryClock, ReportFilter, TimeEntry};
+use crate::toast::{self, ToastAction, Toasts};
+use crate::todoform::{TodoForm, TodoFormAction};
+use crate::undo::UndoStack;
+use crate::urgency::{self, Urgency};
+use crate::widgets::{Prompt, PromptAction};
+use crate::zone::{self, Zone};
+use crate::{
+    clamp_selection, column_todos, flush_db, read_db, read_db_by_todo_status, read_db_file,
+    todo_ids, toggle_pin, update_db, write_atomic, Error, MenuItem, Timer, Todo, TodoStatus,
+};
+
+/// The reference index over the DB as it is now.
+fn load_references() -> RefIndex {
+    RefIndex::build(
+        &read_db().unwrap_or_default(),
+        &timetracking::read_time_entries().unwrap_or_default(),
+    )
+}
+
+/// The file the todos that can't be saved are offered to go to on quit,
+/// in the temp directory.
+const RESCUE_FILE: &str = "pws-rescue.json";
+
+/// Writes the todos as this process has them, saved or not, to `path`,
+/// in the DB file's format.
+fn write_rescue(path: &str) -> Result<(), Error> {
+    let content = schema::db_json(&read_db()?)?;
+    fs::write(path, content).map_err(|err| Error::WriteFileError(path.to_string(), err))
+}
+
+const COLUMNS: [TodoStatus; 3] = [TodoStatus::Todo, TodoStatus::Doing, TodoStatus::Done];
+
+/// The todo id on each row of a board column of `all`, None for Done's day
+/// separators.
+fn column_rows(all: &[Todo], status: TodoStatus, filter: &Filter) -> Vec<Option<usize>> {
+    let done = status == TodoStatus::Done;
+    let todos = column_todos(all, status, filter);
+    if !done {
+        return todos.iter().map(|t| Some(t.id)).collect();
+    }
+    sort::done_rows(&todos, zone::now().date_naive())
+        .iter()
+        .map(|row| match row {
+            sort::DoneRow::Todo(todo) => Some(todo.id),
+            sort::DoneRow::Day(_) => None,
+        })
+        .collect()
+}
+
+/// The data files as of the last key or tick. A frame draws from these
+/// instead of reading the files itself, so redrawing reads nothing from
+/// disk.
+pub struct Snapshot {
+    pub todos: Result<Vec<Todo>, Error>,
+    pub entries: Result<Vec<TimeEntry>, Error>,
+    pub overrides: Result<Overrides, Error>,
+    pub calendar: Result<Vec<DaysOff>, Error>,
+    pub timers: Result<Vec<Timer>, Error>,
+    /// The Home tab, empty when the todos or the entries can't be read.
+    pub home: Vec<Section<'static>>,
+}
+
+impl Snapshot {
+    fn read(config: &Config) -> Snapshot {
+        let todos = read_db();
+        let entries = timetracking::read_time_entries();
+        let home = match (&todos, &entries) {
+            (Ok(todos), Ok(entries)) => dashboard::sections(todos, entries, config, Utc::now()),
+            _ => vec![],
+        };
+        Snapshot {
+            todos,
+            entries,
+            overrides: flextime::read_overrides(),
+            calendar: calendar::read_calendar(),
+            timers: timers::read_timers(),
+            home,
+        }
+    }
+}
+
+/// Ticks of 200ms the Done column stays lit after a completion.
+const DONE_FLASH_TICKS: u32 = 5;
+
+/// A popup that takes all key input while it is open.
+pub enum Modal {
+    BatchEdit(Box<BatchForm>),
+    TodoForm(Box<TodoForm>),
+    TimerForm(Box<TimerForm>),
+    KeyConflicts(Box<KeyConflicts>),
+    EntryNote {
+        entry_id: usize,
+        prompt: Prompt,
+    },
+    EntryTags {
+        entry_id: usize,
+        prompt: Prompt,
+    },
+    /// The tag the time reports keep, see `App::report_filter`.
+    ReportTag(Prompt),
+    Attachment {
+        todo_id: usize,
+        prompt: Prompt,
+    },
+    /// The intention or reflection of the day `date`, quitting after it
+    /// with `quit`.
+    DayNote {
+        kind: NoteKind,
+        date: NaiveDate,
+        prompt: Prompt,
+        quit: bool,
+    },
+    Command(Box<CommandLine>),
+    /// An import waiting for confirmation.
+    Preview(Box<Preview>),
+    Palette(Box<Palette>),
+    Carryover(Box<Carryover>),
+    Description(Box<DescriptionEditor>),
+    /// Asks before throwing away what was typed into the editor.
+    DiscardDraft(Box<DescriptionEditor>),
+    /// Offers the draft left from an earlier edit before the editor opens.
+    ResumeDraft {
+        editor: Box<DescriptionEditor>,
+        draft: Draft,
+    },
+    /// Asks before quitting with a timer running or changes unsaved.
+    ConfirmQuit(QuitCheck),
+    /// Asks where to write the todos that can't be saved, before quitting.
+    Rescue(Prompt),
+    /// Asks what to do with a break that is ending.
+    EndBreak {
+        length: Duration,
+    },
+    /// Picks the todo a dangling reference is relinked to.
+    Relink(Box<Relink>),
+    Search(Box<Search>),
+    /// Asks before bringing an archived todo found by a search back.
+    Unarchive(Hit),
+    /// Every todo due on a day picked in the month view.
+    Day(Box<DayDetail>),
+    /// The todo menu or the column menu.
+    Menu(Box<Menu>),
+    /// Asks how many days ago the Done todos to archive were completed.
+    ArchiveOld(Prompt),
+    /// The WIP limit of the column.
+    WipLimit {
+        column: usize,
+        prompt: Prompt,
+    },
+    /// Asks before deleting the todo, by id with its title.
+    ConfirmDelete(usize, String),
+    /// Asks before removing an attachment, by todo id, index and path.
+    ConfirmDetach(usize, usize, PathBuf),
+}
+
+/// A full screen report shown in place of the active tab.
+pub enum View {
+    Today(NaiveDate),
+    /// One todo with its work history, by id.
+    Todo(usize),
+    /// The data files measured when the view opened; any key closes it.
+    Storage(Result<storage::Report, Error>),
+    /// Pomodoro statistics, over the history read when the view opened.
+    Stats(Result<Vec<Pomodoro>, Error>),
+    /// Every action and its keys.
+    Help,
+    /// The tracked hours per category and day of the month starting on
+    /// that day.
+    Timesheet(NaiveDate),
+    /// Dangling references with their fixes.
+    Inconsistencies,
+    /// The month around the selected day with the todos due each day.
+    Month(NaiveDate),
+}
+
+/// Everything the main loop draws from and the key handler mutates.
+pub struct App {
+    pub config: Config,
+    pub session: Session,
+    pub active_menu_item: MenuItem,
+    pub todo_list_state: ListState,
+    pub doing_list_state: ListState,
+    pub done_list_state: ListState,
+    pub entries_table_state: TableState,
+    pub timers_table_state: TableState,
+    pub marked_todos: HashSet<usize>,
+    /// Narrows every board column, set with `:filter` and `:due`.
+    pub board_filter: Filter,
+    /// Decorations after each board item's title, from the config until
+    /// `:set fields` changes them.
+    pub board_fields: Vec<Field>,
+    /// The category glyphs before titles, worked out once at startup.
+    pub glyphs: Glyphs,
+    /// The board column h/l moved to, which `z` collapses.
+    pub focused_column: usize,
+    /// The day the TimeTracking tab shows as a timeline, None for the
+    /// entries table.
+    pub timeline: Option<NaiveDate>,
+    pub timeline_selected: usize,
+    /// Which clock the entries list shows times by.
+    pub entry_clock: EntryClock,
+    /// Which entries the TimeTracking tab's week summary and the
+    /// timesheet view count, and whether their rows are tags.
+    pub report_filter: ReportFilter,
+    /// Compact mode as `:set compact` forced it, None to follow the
+    /// terminal size.
+    pub compact: Option<bool>,
+    pub modal: Option<Modal>,
+    pub view: Option<View>,
+    pub status_message: Option<String>,
+    pub should_quit: bool,
+    /// Where the todos that couldn't be saved were written on quit.
+    pub rescued: Option<String>,
+    /// When the first `q` of a `qq` quit was pressed.
+    quit_armed: Option<Instant>,
+    pub highlights: Highlights,
+    pub toasts: Toasts,
+    pub home_scroll: ScrollStack,
+    pub stats_scroll: ScrollStack,
+    pub help_scroll: u16,
+    /// Scrolls the work history of the todo view.
+    pub history_state: TableState,
+    /// Reports being built for the views, see `Reports`.
+    pub reports: Reports,
+    /// The totals the TimeTracking tab's breakdown draws, see
+    /// `Breakdown`.
+    pub breakdown: Option<Breakdown>,
+    /// What a bar of the breakdown chart covers.
+    pub breakdown_scale: Granularity,
+    /// What the next frame draws, see `Snapshot`.
+    pub snapshot: Snapshot,
+    /// The attachment of the todo view `o` opens.
+    pub attachment_selected: usize,
+    /// The problem of the inconsistencies view a fix applies to.
+    pub problem_selected: usize,
+    /// The reference or backlink of the todo view Enter follows, an index
+    /// into `todo_links`.
+    pub link_selected: Option<usize>,
+    /// Which todos refer to which, for backlinks.
+    pub references: RefIndex,
+    /// The todo under each column's cursor, ToDo, Doing and Done, so the
+    /// cursor stays on it when the data changes under the board.
+    selected_ids: [Option<usize>; 3],
+    undo_stack: UndoStack,
+    budget_watch: BudgetWatch,
+    filter_follow: FilterFollow,
+    pub due_watch: DueWatch,
+    config_watch: ConfigWatch,
+    notifier: Dispatcher,
+    /// What the notifier last put on the status bar, shown on the next tick.
+    notices: Rc<RefCell<Option<String>>>,
+    macros: Macros,
+    /// Whether `f` waits for the 1, 2 or 3 of a due window, see `due_key`.
+    due_leader: bool,
+}
+
+impl App {
+    pub fn new(config: Config) -> App {
+        let mut todo_list_state = ListState::default();
+        todo_list_state.select(Some(0));
+        // doing_list_state.select(None(0));
+        // done_list_state.select(Some(0));
+
+        let mut board_filter = Filter::default();
+        board_filter.week_start = Some(config.week_start);
+        let board_fields = config.board_item_fields.clone();
+        let glyphs = Glyphs::from_config(&config);
+        let session = Session::load();
+        let snapshot = Snapshot::read(&config);
+        let notices = Rc::new(RefCell::new(None));
+        let mut notifier = Dispatcher::default();
+        notifier.register(Box::new(StatusBar(Rc::clone(&notices))));
+        notifier.register(Box::new(Bell));
+        if config.due_desktop_notifications {
+            notifier.register(Box::new(Desktop));
+        }
+        let focused_column = session
+            .collapsed_columns
+            .iter()
+            .position(|collapsed| !collapsed)
+            .unwrap_or(0);
+
+        App {
+            config,
+            session,
+            active_menu_item: MenuItem::Home,
+            todo_list_state,
+            doing_list_state: ListState::default(),
+            done_list_state: ListState::default(),
+            entries_table_state: TableState::default(),
+            timers_table_state: TableState::default(),
+            marked_todos: HashSet::new(),
+            board_filter,
+            board_fields,
+            glyphs,
+            focused_column,
+            timeline: None,
+            timeline_selected: 0,
+            entry_clock: EntryClock::Current,
+            report_filter: ReportFilter::default(),
+            compact: None,
+            modal: None,
+            view: None,
+            status_message: None,
+            should_quit: false,
+            rescued: None,
+            quit_armed: None,
+            highlights: Highlights::default(),
+            toasts: Toasts::default(),
+            home_scroll: ScrollStack::default(),
+            stats_scroll: ScrollStack::default(),
+            help_scroll: 0,
+            history_state: TableState::default(),
+            reports: Reports::default(),
+            breakdown: None,
+            breakdown_scale: Granularity::Day,
+            snapshot,
+            attachment_selected: 0,
+            problem_selected: 0,
+            link_selected: None,
+            references: load_references(),
+            selected_ids: [None; 3],
+            undo_stack: UndoStack::default(),
+            budget_watch: BudgetWatch::default(),
+            filter_follow: FilterFollow::default(),
+            due_watch: DueWatch::default(),
+            config_watch: ConfigWatch::new(),
+            notifier,
+            notices,
+            macros: Macros::default(),
+            due_leader: false,
+        }
+    }
+
+    pub fn recording_macro(&self) -> Option<char> {
+        self.macros.recording()
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        self.follow_selection();
+        self.handle_key_at_selection(key);
+        self.remember_selection();
+        self.take_snapshot();
+    }
+
+    /// Reads the data the next frame draws, see `Snapshot`.
+    pub fn take_snapshot(&mut self) {
+        self.snapshot = Snapshot::read(&self.config);
+    }
+
+    fn handle_key_at_selection(&mut self, key: KeyEvent) {
+        if self.modal.is_some() {
+            self.handle_modal_key(key);
+            return;
+        }
+        // an undo toast's key beats every binding, and isn't recorded; one
+        // that ran out since the last tick no longer takes it
+        self.toasts.expire(Instant::now());
+        if let Some(id) = self.toasts.action_for(&key) {
+            self.perform(id);
+            return;
+        }
+
+        // the key after the due filter's `f` is its own, digits included,
+        // which would otherwise count a macro replay
+        let intercepted = match self.due_leader {
+            true => MacroAction::Pass,
+            false => self.macros.intercept(key),
+        };
+        match intercepted {
+            MacroAction::Pass => {
+                let step = self.dispatch_key(key);
+                self.macros.record(step);
+                if self.modal.is_some() {
+                    if let Some(register) = self.macros.abort_recording() {
+                        self.status_message = Some(format!(
+                            "recording @{} cancelled, macros can't include text prompts",
+                            register
+                        ));
+                    }
+                }
+            }
+            MacroAction::Consumed => {}
+            MacroAction::Quit => {
+                let window = std::time::Duration::from_millis(self.config.quit_window_ms);
+                match self.quit_armed.take() {
+                    Some(armed) if armed.elapsed() <= window => self.request_quit(),
+                    _ => {
+                        self.macros.await_register();
+                        self.quit_armed = Some(Instant::now());
+                        self.status_message = Some("press q again to quit".to_string());
+                    }
+                }
+            }
+            MacroAction::AwaitRegister => {
+                self.quit_armed = Some(Instant::now());
+                self.status_message =
+                    Some("q: press a letter to record a macro, q again to quit".to_string())
+            }
+            MacroAction::StartedRecording(register) => {
+                self.status_message = Some(format!("recording @{}", register))
+            }
+            MacroAction::StoppedRecording(register, keys) => {
+                self.status_message =
+                    Some(format!("recorded {} keys into @{}", keys.len(), register));
+                self.session.macros.insert(register, keys);
+                self.save_session();
+            }
+            MacroAction::Replay(register, count) => self.replay(register, count),
+            MacroAction::Cancelled => self.status_message = None,
+        }
+    }
+
+    /// Runs a register's steps again, a key through the normal key handler
+    /// and an action as the keymap ran it then, stopping early if a step
+    /// opens a text prompt or quits.
+    fn replay(&mut self, register: char, count: usize) {
+        let steps = match self.session.macros.get(&register) {
+            Some(steps) => steps.clone(),
+            None => {
+                self.status_message = Some(format!("register @{} is empty", register));
+                return;
+            }
+        };
+        for _ in 0..count.min(macros::MAX_COUNT) {
+            for step in &steps {
+                match *step {
+                    Step::Key(key) => {
+                        self.dispatch_key(key);
+                    }
+                    Step::Action(id) => {
+                        self.status_message = None;
+                        self.view = None;
+                        self.perform(id);
+                    }
+                }
+                if self.modal.is_some() {
+                    self.status_message =
+                        Some(format!("replay of @{} stopped at a text prompt", register));
+                    return;
+                }
+                if self.should_quit {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn handle_modal_key(&mut self, key: KeyEvent) {
+        match self.modal.take() {
+            Some(Modal::TimerForm(mut form)) => match form.handle_key(key) {
+                TimerFormAction::Continue => self.modal = Some(Modal::TimerForm(form)),
+                TimerFormAction::Cancel => {}
+                TimerFormAction::Submit { name, category } => {
+                    match timers::add(&name, &category, Utc::now()) {
+                        Ok(_) => {
+                            let count = timers::read_timers().map_or(0, |timers| timers.len());
+                            self.timers_table_state.select(count.checked_sub(1));
+                            self.toasts.push(
+                                format!("added timer {}", name),
+                                None,
+                                toast::INFO_FOR,
+                                Instant::now(),
+                            );
+                        }
+                        Err(err) => self.status_message = Some(err.to_string()),
+                    }
+                }
+            },
+            Some(Modal::TodoForm(mut form)) => match form.handle_key(key) {
+                TodoFormAction::Continue => self.modal = Some(Modal::TodoForm(form)),
+                TodoFormAction::Cancel => {}
+                TodoFormAction::Save(todo) => self.update_todo(*todo),
+                TodoFormAction::Submit(todo) => match add::insert(*todo) {
+                    Ok(id) => {
+                        self.refresh_references(id);
+                        if let Err(err) = self.goto_todo(id) {
+                            self.status_message = Some(err);
+                        }
+                        self.remember_selection();
+                        self.toasts.push(
+                            format!("added todo {}", id),
+                            None,
+                            toast::INFO_FOR,
+                            Instant::now(),
+                        );
+                    }
+                    Err(err) => self.status_message = Some(err.to_string()),
+                },
+            },
+            Some(Modal::BatchEdit(mut form)) => match form.handle_key(key) {
+                FormAction::Continue => self.modal = Some(Modal::BatchEdit(form)),
+                FormAction::Cancel => {}
+                FormAction::Submit(patch) => {
+                    match batch::apply_batch_edit(&form.ids, &patch, &mut self.undo_stack) {
+                        Ok(outcome) => {
+                            let mut message = format!("updated {} todos", outcome.updated);
+                            if !outcome.missing.is_empty() {
+                                message.push_str(&format!(
+                                    ", {} no longer exist ({:?})",
+                                    outcome.missing.len(),
+                                    outcome.missing
+                                ));
+                            }
+                            if outcome.updated > 0 {
+                                self.toast_undo(message);
+                            } else {
+                                self.status_message = Some(message);
+                            }
+                            self.marked_todos.clear();
+                            if patch.status == Some(TodoStatus::Done)
+                                && outcome.updated > 0
+                                && self.config.flash_on_done
+                            {
+                                self.highlights.flash(Target::DoneColumn, DONE_FLASH_TICKS);
+                            }
+                        }
+                        Err(err) => self.status_message = Some(err.to_string()),
+                    }
+                    clamp_selection(
+                        &mut self.todo_list_state,
+                        TodoStatus::Todo,
+                        &self.board_filter,
+                    );
+                }
+            },
+            Some(Modal::Carryover(mut carryover)) => match carryover.handle_key(key) {
+                CarryoverAction::Continue => self.modal = Some(Modal::Carryover(carryover)),
+                CarryoverAction::Cancel => {
+                    self.status_message = Some("carry-over cancelled".to_string())
+                }
+                CarryoverAction::Apply => {
+                    match carryover.apply(&mut self.undo_stack) {
+                        Ok(outcome) if outcome.changed() > 0 => {
+                            for id in carryover.dropped() {
+                                self.references.remove(id);
+                            }
+                            self.toast_undo(outcome.summary())
+                        }
+                        Ok(outcome) => self.status_message = Some(outcome.summary()),
+                        Err(err) => self.status_message = Some(err.to_string()),
+                    }
+                    self.clamp_board_selection();
+                }
+            },
+            Some(Modal::Description(mut editor)) => match editor.handle_key(key) {
+                EditorAction::Continue => self.modal = Some(Modal::Description(editor)),
+                EditorAction::Cancel if editor.changed() => {
+                    self.modal = Some(Modal::DiscardDraft(editor))
+                }
+                EditorAction::Cancel => self.drop_draft(&editor.draft_key()),
+                EditorAction::Save(text) => {
+                    match description::set_description(editor.todo_id, text) {
+                        Ok(true) => {
+                            self.drop_draft(&editor.draft_key());
+                            self.refresh_references(editor.todo_id)
+                        }
+                        Ok(false) => {
+                            self.status_message =
+                                Some(format!("todo {} no longer exists", editor.todo_id))
+                        }
+                        Err(err) => self.status_message = Some(err),
+                    }
+                }
+            },
+            Some(Modal::DiscardDraft(editor)) => match key.code {
+                KeyCode::Char('y') => self.drop_draft(&editor.draft_key()),
+                KeyCode::Char('n') | KeyCode::Esc => self.modal = Some(Modal::Description(editor)),
+                _ => self.modal = Some(Modal::DiscardDraft(editor)),
+            },
+            Some(Modal::ResumeDraft { mut editor, draft }) => match key.code {
+                KeyCode::Char('y') => {
+                    editor.resume(&draft.text);
+                    self.modal = Some(Modal::Description(editor));
+                }
+                KeyCode::Char('n') => {
+                    self.drop_draft(&editor.draft_key());
+                    self.modal = Some(Modal::Description(editor));
+                }
+                // the draft stays for next time
+                KeyCode::Esc => {}
+                _ => self.modal = Some(Modal::ResumeDraft { editor, draft }),
+            },
+            Some(Modal::EntryNote {
+                entry_id,
+                mut prompt,
+            }) => match prompt.handle_key(key) {
+                PromptAction::Continue => self.modal = Some(Modal::EntryNote { entry_id, prompt }),
+                PromptAction::Cancel => {}
+                PromptAction::Submit(note) => {
+                    match todo_ids().and_then(|ids| timetracking::set_note(entry_id, &note, &ids)) {
+                        Ok(()) => self.refresh_note_references(entry_id),
+                        Err(err) => self.status_message = Some(err.to_string()),
+                    }
+                }
+            },
+            Some(Modal::EntryTags {
+                entry_id,
+                mut prompt,
+            }) => match prompt.handle_key(key) {
+                PromptAction::Continue => self.modal = Some(Modal::EntryTags { entry_id, prompt }),
+                PromptAction::Cancel => {}
+                PromptAction::Submit(tags) => {
+                    let tags = timetracking::parse_tags(&tags);
+                    match timetracking::set_tags(entry_id, tags, &mut self.undo_stack) {
+                        Ok(true) => self.toast_undo(format!("tagged entry {}", entry_id)),
+                        Ok(false) => {
+                            self.status_message =
+                                Some(format!("time entry {} no longer exists", entry_id))
+                        }
+                        Err(err) => self.status_message = Some(err.to_string()),
+                    }
+                }
+            },
+            Some(Modal::ReportTag(mut prompt)) => match prompt.handle_key(key) {
+                PromptAction::Continue => self.modal = Some(Modal::ReportTag(prompt)),
+                PromptAction::Cancel => {}
+                PromptAction::Submit(tag) => {
+                    self.report_filter.tag = Some(tag.trim().to_string()).filter(|t| !t.is_empty());
+                }
+            },
+            Some(Modal::DayNote {
+                kind,
+                date,
+                mut prompt,
+                quit,
+            }) => match prompt.handle_key(key) {
+                PromptAction::Continue => {
+                    self.modal = Some(Modal::DayNote {
+                        kind,
+                        date,
+                        prompt,
+                        quit,
+                    })
+                }
+                PromptAction::Cancel if quit => self.confirm_quit(),
+                PromptAction::Cancel => {}
+                PromptAction::Submit(text) => {
+                    if let Err(err) = daynotes::record(date, kind, &text) {
+                        self.status_message = Some(err.to_string());
+                        return;
+                    }
+                    if quit {
+                        self.confirm_quit();
+                    }
+                }
+            },
+            Some(Modal::Preview(mut preview)) => match preview.handle_key(key) {
+                PreviewAction::Continue => self.modal = Some(Modal::Preview(preview)),
+                PreviewAction::Cancel => self.status_message = Some("import aborted".to_string()),
+                PreviewAction::Apply => match preview.changes.apply(&mut self.undo_stack) {
+                    Ok(()) => {
+                        self.toast_undo(format!(
+                            "applied {} changes from {}",
+                            preview.changes.changes.len(),
+                            preview.changes.source
+                        ));
+                        self.clamp_board_selection();
+                        self.check_integrity();
+                    }
+                    Err(err) => self.status_message = Some(err.to_string()),
+                },
+            },
+            Some(Modal::Attachment {
+                todo_id,
+                mut prompt,
+            }) => match prompt.handle_key(key) {
+                PromptAction::Continue => self.modal = Some(Modal::Attachment { todo_id, prompt }),
+                PromptAction::Cancel => {}
+                PromptAction::Submit(input) => {
+                    self.status_message = Some(match attachments::resolve(&input) {
+                        Ok(path) => match attachments::attach(todo_id, path.clone()) {
+                            Ok(true) => format!("attached {}", attachments::label(&path)),
+                            Ok(false) => format!("{} is attached already", path.display()),
+                            Err(err) => err.to_string(),
+                        },
+                        Err(err) => err,
+                    });
+                }
+            },
+            Some(Modal::Relink(mut relink)) => match key.code {
+                KeyCode::Enter => {
+                    let target = relink.target();
+                    self.apply_fix(&relink.problem, Fix::Relink, Some(target));
+                }
+                KeyCode::Esc => {}
+                _ => {
+                    relink.picker.handle_key(key);
+                    self.modal = Some(Modal::Relink(relink));
+                }
+            },
+            Some(Modal::ConfirmQuit(check)) => match key.code {
+                KeyCode::Char('y') => self.quit_now(),
+                KeyCode::Char('s') if !check.running.is_empty() => {
+                    match check.stop_running(Utc::now()) {
+                        Ok(_) => self.quit_now(),
+                        Err(err) => self.status_message = Some(err.to_string()),
+                    }
+                }
+                KeyCode::Esc | KeyCode::Char('n') => {}
+                _ => self.modal = Some(Modal::ConfirmQuit(check)),
+            },
+            Some(Modal::EndBreak { length }) => {
+                let keep = match key.code {
+                    KeyCode::Char('y') => true,
+                    KeyCode::Char('n') => false,
+                    KeyCode::Esc => return,
+                    _ => {
+                        self.modal = Some(Modal::EndBreak { length });
+                        return;
+                    }
+                };
+                self.status_message = Some(match timetracking::end_break(keep, Utc::now()) {
+                    Ok(resumed) => format!(
+                        "break {}, resumed {} timer{}",
+                        if keep { "logged" } else { "discarded" },
+                        resumed,
+                        if resumed == 1 { "" } else { "s" }
+                    ),
+                    Err(err) => err.to_string(),
+                });
+            }
+            Some(Modal::Search(mut search)) => match search.handle_key(key) {
+                SearchAction::Continue => self.modal = Some(Modal::Search(search)),
+                SearchAction::Cancel => {}
+                SearchAction::Open(hit) if hit.location == Location::Archived => {
+                    self.modal = Some(Modal::Unarchive(hit))
+                }
+                SearchAction::Open(hit) => {
+                    if let Err(err) = self.goto_todo(hit.id) {
+                        self.status_message = Some(err);
+                    }
+                }
+            },
+            Some(Modal::KeyConflicts(mut screen)) => match screen.handle_key(key) {
+                ConflictAction::Continue => self.modal = Some(Modal::KeyConflicts(screen)),
+                ConflictAction::Confirm(keys, note) => {
+                    let saved =
+                        config::set(vec![("keys", keymap::to_json(actions::ACTIONS, &keys))]);
+                    actions::set_keymap(keys.clone());
+                    self.config.keys = keys;
+                    // the config is as this session has it, no reload needed
+                    self.config_watch.changed();
+                    match saved {
+                        Ok(()) => {
+                            self.toasts.push(
+                                "key conflicts settled, saved to the config",
+                                None,
+                                toast::INFO_FOR,
+                                Instant::now(),
+                            );
+                            self.status_message = Some(note);
+                        }
+                        Err(err) => {
+                            self.status_message =
+                                Some(format!("keys set for this session only, {}", err))
+                        }
+                    }
+                }
+                ConflictAction::Decline(keys) => {
+                    let defaults: Vec<&str> = actions::ACTIONS
+                        .iter()
+                        .filter(|a| {
+                            self.config.keys.contains_key(&a.id) && !keys.contains_key(&a.id)
+                        })
+                        .map(|a| a.name)
+                        .collect();
+                    actions::set_keymap(keys);
+                    self.status_message = Some(format!(
+                        "key conflicts left in the config, default keys for {}",
+                        defaults.join(", ")
+                    ));
+                }
+            },
+            Some(Modal::Menu(mut menu)) => match menu.handle_key(key) {
+                MenuAction::Continue => self.modal = Some(Modal::Menu(menu)),
+                MenuAction::Cancel => {}
+                MenuAction::Pick(Choice::Move(id, status)) => self.move_todo(id, status),
+                MenuAction::Pick(Choice::Run(id)) => self.perform(id),
+                MenuAction::Pick(Choice::Command(line)) => self.run_command(&line),
+                MenuAction::Unavailable(name, reason) => {
+                    self.status_message = Some(format!("can't {} here: {}", name, reason))
+                }
+            },
+            Some(Modal::Rescue(mut prompt)) => match prompt.handle_key(key) {
+                PromptAction::Continue => self.modal = Some(Modal::Rescue(prompt)),
+                PromptAction::Cancel => {}
+                PromptAction::Submit(path) => match path.trim() {
+                    "" => self.should_quit = true,
+                    path => match write_rescue(path) {
+                        Ok(()) => {
+                            self.rescued = Some(path.to_string());
+                            self.should_quit = true;
+                        }
+                        Err(err) => {
+                            self.status_message = Some(err.to_string());
+                            self.modal = Some(Modal::Rescue(prompt));
+                        }
+                    },
+                },
+            },
+            Some(Modal::ArchiveOld(mut prompt)) => match prompt.handle_key(key) {
+                PromptAction::Continue => self.modal = Some(Modal::ArchiveOld(prompt)),
+                PromptAction::Cancel => {}
+                PromptAction::Submit(days) => match days.trim().parse::<i64>() {
+                    Ok(days) if days >= 0 => self.archive_done_before(days),
+                    _ => self.status_message = Some(format!("'{}' isn't a number of days", days)),
+                },
+            },
+            Some(Modal::WipLimit { column, mut prompt }) => match prompt.handle_key(key) {
+                PromptAction::Continue => self.modal = Some(Modal::WipLimit { column, prompt }),
+                PromptAction::Cancel => {}
+                PromptAction::Submit(limit) => match limit.trim() {
+                    "" => self.set_wip_limit(column, None),
+                    limit => match limit.parse::<usize>() {
+                        Ok(limit) => self.set_wip_limit(column, Some(limit)),
+                        Err(_) => {
+                            self.status_message =
+                                Some(format!("'{}' isn't a number of todos", limit))
+                        }
+                    },
+                },
+            },
+            Some(Modal::ConfirmDelete(id, title)) => match key.code {
+                KeyCode::Char('y') | KeyCode::Enter => self.delete_todo(id),
+                KeyCode::Esc | KeyCode::Char('n') => {}
+                _ => self.modal = Some(Modal::ConfirmDelete(id, title)),
+            },
+            Some(Modal::ConfirmDetach(id, index, path)) => match key.code {
+                KeyCode::Char('y') | KeyCode::Enter => self.detach(id, index, &path),
+                KeyCode::Esc | KeyCode::Char('n') => {}
+                _ => self.modal = Some(Modal::ConfirmDetach(id, index, path)),
+            },
+            Some(Modal::Day(mut detail)) => match detail.handle_key(key) {
+                DayAction::Continue => self.modal = Some(Modal::Day(detail)),
+                DayAction::Close => {}
+                DayAction::Open(id) => {
+                    if let Err(err) = self.goto_todo(id) {
+                        self.status_message = Some(err);
+                    }
+                }
+            },
+            Some(Modal::Unarchive(hit)) => match key.code {
+                KeyCode::Char('y') => {
+                    let result = match bulk::unarchive(hit.id, &mut self.undo_stack) {
+                        Ok(Some(id)) => self.goto_todo(id).map(|()| {
+                            if id == hit.id {
+                                format!("unarchived todo {}", id)
+                            } else {
+                                format!("unarchived todo {} as {}", hit.id, id)
+                            }
+                        }),
+                        Ok(None) => Err(format!("todo {} is no longer archived", hit.id)),
+                        Err(err) => Err(err.to_string()),
+                    };
+                    match result {
+                        Ok(text) => self.toast_undo(text),
+                        Err(err) => self.status_message = Some(err),
+                    }
+                }
+                KeyCode::Esc | KeyCode::Char('n') => {}
+                _ => self.modal = Some(Modal::Unarchive(hit)),
+            },
+            Some(Modal::Palette(mut palette)) => match palette.handle_key(key) {
+                PaletteAction::Continue => self.modal = Some(Modal::Palette(palette)),
+                PaletteAction::Cancel => {}
+                PaletteAction::Run(id) => self.perform(id),
+                PaletteAction::Unavailable(name, reason) => {
+                    self.status_message = Some(format!("can't {} here: {}", name, reason))
+                }
+            },
+            Some(Modal::Command(mut line)) => match line.handle_key(key) {
+                LineAction::Continue => self.modal = Some(Modal::Command(line)),
+                LineAction::Cancel => {}
+                LineAction::Run(line) => {
+                    if !line.trim().is_empty() {
+                        self.session.remember_command(&line);
+                        self.save_session();
+                    }
+                    if let Err(err) = commands::execute(self, &line) {
+                        self.status_message = Some(err);
+                    }
+                }
+            },
+            None => {}
+        }
+    }
+
+    /// Runs `event` as a key of the open view or else of the keymap, and
+    /// returns it as a macro records it: the action the keymap found, so a
+    /// remap doesn't change what a replay does, or the key itself.
+    fn dispatch_key(&mut self, event: KeyEvent) -> Step {
+        self.status_message = None;
+        if self.view_key(event) {
+            return Step::Key(event);
+        }
+        match actions::lookup(&event, self.active_menu_item) {
+            Some(id) => {
+                // a view still open took no part in it, so it's replayed as
+                // the key, for the view to see again
+                let step = match self.view {
+                    None => Step::Action(id),
+                    Some(_) => Step::Key(event),
+                };
+                self.perform(id);
+                step
+            }
+            None => Step::Key(event),
+        }
+    }
+
+    /// Toggles the board's due window by the key after `f`: 1 today, 2
+    /// this week, 3 overdue. Esc only drops the `f`; any other key is not
+    /// taken and acts as usual.
+    fn due_key(&mut self, event: KeyEvent) -> bool {
+        let window = match event.code {
+            KeyCode::Char('1') => DueWindow::Today,
+            KeyCode::Char('2') => DueWindow::Week,
+            KeyCode::Char('3') => DueWindow::Overdue,
+            KeyCode::Esc => return true,
+            _ => return false,
+        };
+        self.board_filter.due = match self.board_filter.due {
+            Some(due) if due == window => None,
+            _ => Some(window),
+        };
+        self.clamp_board_selection();
+        true
+    }
+
+    /// Whether the open view took `event`. A key it has no use for leaves
+    /// most views, to act as usual.
+    fn view_key(&mut self, event: KeyEvent) -> bool {
+        if std::mem::take(&mut self.due_leader) && self.due_key(event) {
+            return true;
+        }
+        if let Some(View::Today(date)) = self.view {
+            let today = zone::now().date_naive();
+            match event.code {
+                KeyCode::Char('h') | KeyCode::Left => {
+                    self.view = Some(View::Today(date - Duration::days(1)));
+                    return true;
+                }
+                KeyCode::Char('l') | KeyCode::Right => {
+                    self.view = Some(View::Today((date + Duration::days(1)).min(today)));
+                    return true;
+                }
+                KeyCode::Char('m') => {
+                    self.view = Some(View::Month(date));
+                    return true;
+                }
+                KeyCode::Esc => {
+                    self.view = None;
+                    return true;
+                }
+                // anything else leaves the view and acts as usual
+                _ => self.view = None,
+            }
+        }
+        if let Some(View::Todo(id)) = self.view {
+            match event.code {
+                KeyCode::Char('j') | KeyCode::Down => {
+                    self.scroll_history(id, true);
+                    return true;
+                }
+                KeyCode::Char('k') | KeyCode::Up => {
+                    self.scroll_history(id, false);
+                    return true;
+                }
+                KeyCode::Char(c @ ('h' | 'l' | 'o' | 'd')) => {
+                    self.on_attachment(id, c);
+                    return true;
+                }
+                KeyCode::Char('a') => {
+                    self.perform(ActionId::Attach);
+                    return true;
+                }
+                KeyCode::Char('e') => {
+                    self.perform(ActionId::EditDescription);
+                    return true;
+                }
+                KeyCode::Tab => {
+                    let links = self.todo_links(id).len();
+                    self.link_selected = match self.link_selected {
+                        _ if links == 0 => None,
+                        Some(selected) => Some((selected + 1) % links),
+                        None => Some(0),
+                    };
+                    return true;
+                }
+                KeyCode::Enter => {
+                    self.follow_link(id);
+                    return true;
+                }
+                KeyCode::Esc => {
+                    self.view = None;
+                    return true;
+                }
+                KeyCode::Char(':') => {}
+                KeyCode::Char('p') if event.modifiers.contains(KeyModifiers::CONTROL) => {}
+                _ => self.view = None,
+            }
+        }
+        if let Some(View::Stats(_)) = self.view {
+            match event.code {
+                KeyCode::Char('j') | KeyCode::Down => {
+                    self.stats_scroll.focus_next();
+                    return true;
+                }
+                KeyCode::Char('k') | KeyCode::Up => {
+                    self.stats_scroll.focus_previous();
+                    return true;
+                }
+                KeyCode::Enter => {
+                    self.stats_scroll.toggle_expanded();
+                    return true;
+                }
+                KeyCode::Esc => {
+                    self.view = None;
+                    return true;
+                }
+                KeyCode::Char(':') => {}
+                KeyCode::Char('p') if event.modifiers.contains(KeyModifiers::CONTROL) => {}
+                _ => self.view = None,
+            }
+        }
+        if let Some(View::Help) = self.view {
+            match event.code {
+                KeyCode::Char('j') | KeyCode::Down => {
+                    self.help_scroll = self.help_scroll.saturating_add(1);
+                    return true;
+                }
+                KeyCode::Char('k') | KeyCode::Up => {
+                    self.help_scroll = self.help_scroll.saturating_sub(1);
+                    return true;
+                }
+                KeyCode::Esc => {
+                    self.view = None;
+                    return true;
+                }
+                _ => self.view = None,
+            }
+        }
+        if let Some(View::Month(selected)) = self.view {
+            match event.code {
+                code @ (KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down) => {
+                    self.view = month::step(selected, code).map(View::Month);
+                    return true;
+                }
+                KeyCode::Char(c @ ('h' | 'l')) => {
+                    self.view = Some(View::Month(month::step_month(selected, c == 'l')));
+                    return true;
+                }
+                KeyCode::Enter => {
+                    match read_db() {
+                        Ok(todos) => {
+                            let detail = DayDetail::new(selected, &todos, zone::current());
+                            self.modal = Some(Modal::Day(Box::new(detail)));
+                        }
+                        Err(err) => self.status_message = Some(err.to_string()),
+                    }
+                    return true;
+                }
+                KeyCode::Esc => {
+                    self.view = None;
+                    return true;
+                }
+                KeyCode::Char(':') => {}
+                KeyCode::Char('p') if event.modifiers.contains(KeyModifiers::CONTROL) => {}
+                _ => self.view = None,
+            }
+        }
+        if let Some(View::Timesheet(month)) = self.view {
+            match event.code {
+                KeyCode::Char('h') | KeyCode::Left => {
+                    self.view = Some(View::Timesheet(timesheet::previous_month(month)));
+                    return true;
+                }
+                KeyCode::Char('l') | KeyCode::Right => {
+                    self.view = Some(View::Timesheet(timesheet::next_month(month)));
+                    return true;
+                }
+                KeyCode::Char('f') => {
+                    self.perform(ActionId::ReportTag);
+                    return true;
+                }
+                KeyCode::Char('B') => {
+                    self.perform(ActionId::ReportBy);
+                    return true;
+                }
+                KeyCode::Esc => {
+                    self.view = None;
+                    return true;
+                }
+                _ => self.view = None,
+            }
+        }
+        if let Some(View::Inconsistencies) = self.view {
+            if let KeyCode::Char(key) = event.code {
+                self.on_problem(key);
+                return true;
+            }
+            if event.code == KeyCode::Esc {
+                self.view = None;
+                return true;
+            }
+        }
+        if let Some(View::Storage(_)) = self.view {
+            self.view = None;
+            if event.code == KeyCode::Esc {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Why `action` can't run right now, None when it can.
+    pub fn blocked(&self, action: &Action) -> Option<String> {
+        if let Scope::Tab(tab) = action.scope {
+            if tab != self.active_menu_item {
+                return Some(format!(
+                    "only on the {} tab",
+                    actions::scope_title(action.scope)
+                ));
+            }
+        }
+        let met = match action.needs {
+            Needs::Nothing => true,
+            Needs::Todo => {
+                matches!(self.view, Some(View::Todo(_))) || self.selected_todo().is_some()
+            }
+            Needs::MarkedTodos => !self.marked_todos.is_empty(),
+            Needs::Entry => self.entries_table_state.selected().is_some(),
+            Needs::Timer => self.selected_timer().is_some(),
+            Needs::Timeline => self.timeline.is_some(),
+            Needs::RunningTodo => timetracking::read_time_entries()
+                .ok()
+                .and_then(|entries| timetracking::running_todo(&entries))
+                .is_some(),
+            Needs::Undo => !self.undo_stack.is_empty(),
+            Needs::DoneColumn => COLUMNS[self.focused_column] == TodoStatus::Done,
+        };
+        (!met).then(|| action.needs.reason().to_string())
+    }
+
+    /// Runs an action, the same way for its keys and the palette.
+    pub fn perform(&mut self, id: ActionId) {
+        match id {
+            ActionId::CommandLine => {
+                self.modal = Some(Modal::Command(Box::new(CommandLine::new(
+                    self.vocabulary(),
+                    self.session.command_history.clone(),
+                ))))
+            }
+            ActionId::Palette => {
+                let actions = actions::ACTIONS
+                    .iter()
+                    .map(|action| (action, self.blocked(action)))
+                    .collect();
+                self.modal = Some(Modal::Palette(Box::new(Palette::new(actions))));
+            }
+            ActionId::Search => {
+                let todos = read_db().unwrap_or_default();
+                let now = zone::now();
+                let (board, hidden) = todos
+                    .iter()
+                    .cloned()
+                    .partition(|todo| self.board_filter.matches(todo, &todos, now));
+                match bulk::read_archived() {
+                    Ok(archived) => {
+                        self.modal = Some(Modal::Search(Box::new(Search::new(
+                            board,
+                            hidden,
+                            archived,
+                            &self.glyphs,
+                        ))))
+                    }
+                    Err(err) => self.status_message = Some(err.to_string()),
+                }
+            }
+            ActionId::Help => {
+                self.help_scroll = 0;
+                self.view = Some(View::Help);
+            }
+            ActionId::Quit => self.request_quit(),
+            ActionId::HomeTab => self.switch_tab(MenuItem::Home),
+            ActionId::TodosTab => self.switch_tab(MenuItem::Todos),
+            ActionId::TimersTab => self.switch_tab(MenuItem::Timers),
+            ActionId::TimeTrackingTab => self.switch_tab(MenuItem::TimeTracking),
+            ActionId::NextTab => self.switch_tab(match self.active_menu_item {
+                MenuItem::Home => MenuItem::Todos,
+                MenuItem::Todos => MenuItem::Timers,
+                MenuItem::Timers => MenuItem::TimeTracking,
+                MenuItem::TimeTracking => MenuItem::Home,
+            }),
+            ActionId::GotoRunning => {
+                let entries = timetracking::read_time_entries().unwrap_or_default();
+                let result = match timetracking::running_todo(&entries) {
+                    Some(id) => self.goto_todo(id),
+                    None => Err("no running timer is linked to a todo".to_string()),
+                };
+                if let Err(err) = result {
+                    self.status_message = Some(err);
+                }
+            }
+            ActionId::ToggleBreak => {
+                let now = Utc::now();
+                let entries = timetracking::read_time_entries().unwrap_or_default();
+                if let Some(current) = timetracking::current_break(&entries) {
+                    self.modal = Some(Modal::EndBreak {
+                        length: now - current.start,
+                    });
+                    return;
+                }
+                self.status_message = Some(match timetracking::start_break(now) {
+                    Ok(0) => "on a break".to_string(),
+                    Ok(paused) => format!(
+                        "on a break, paused {} timer{}",
+                        paused,
+                        if paused == 1 { "" } else { "s" }
+                    ),
+                    Err(err) => err.to_string(),
+                });
+            }
+            ActionId::Undo => {
+                self.toasts.dismiss_newest(ActionId::Undo);
+                match self.undo_stack.pop() {
+                    Some(entry) => match entry.apply() {
+                        Ok(()) => {
+                            self.references = load_references();
+                            self.status_message = Some(format!("undid {}", entry.label))
+                        }
+                        Err(err) => self.status_message = Some(err.to_string()),
+                    },
+                    None => self.status_message = Some("nothing to undo".to_string()),
+                }
+                // the undone change may have touched any column
+                self.clamp_board_selection();
+                for (state, status) in [
+                    (&mut self.doing_list_state, TodoStatus::Doing),
+                    (&mut self.done_list_state, TodoStatus::Done),
+                ] {
+                    if state.selected().is_some() {
+                        clamp_selection(state, status, &self.board_filter);
+                    }
+                }
+            }
+            ActionId::NextSection => self.home_scroll.focus_next(),
+            ActionId::PreviousSection => self.home_scroll.focus_previous(),
+            ActionId::ToggleSection => self.home_scroll.toggle_expanded(),
+            ActionId::NextTodo => self.step_selection(true),
+            ActionId::PreviousTodo => self.step_selection(false),
+            ActionId::FocusLeft => self.move_focus(false),
+            ActionId::FocusRight => self.move_focus(true),
+            ActionId::ToggleBadges => {
+                self.session.badges_visible = !self.session.badges_visible;
+                self.save_session();
+            }
+            ActionId::TrackedWeek => {
+                let days = sparkline::daily_totals(
+                    &timetracking::read_time_entries().unwrap_or_default(),
+                    zone::now().date_naive(),
+                    sparkline::SPARKLINE_DAYS,
+                    zone::current(),
+                    Utc::now(),
+                );
+                self.toasts.push(
+                    sparkline::figures(&days, zone::now().date_naive()),
+                    None,
+                    toast::INFO_FOR,
+                    Instant::now(),
+                );
+            }
+            ActionId::DueFilter => {
+                self.due_leader = true;
+                self.status_message =
+                    Some("due: 1 today, 2 this week, 3 overdue, again to show all".to_string());
+            }
+            ActionId::BadgeFilter => {
+                let badges = badges::badges(&read_db().unwrap_or_default());
+                let next = badges::next_category(&badges, self.board_filter.category());
+                self.board_filter.set_category(next.map(str::to_string));
+                self.clamp_board_selection();
+                self.status_message = Some(match next {
+                    Some(category) => format!("showing category {}", category),
+                    None => "category filter cleared".to_string(),
+                });
+            }
+            ActionId::CollapseColumn => {
+                if !self.session.toggle_column(self.focused_column) {
+                    self.status_message = Some("one column always stays expanded".to_string());
+                    return;
+                }
+                if self.session.collapsed_columns[self.focused_column] {
+                    // the focus goes to the nearest column still expanded
+                    let column = self.focused_column;
+                    self.move_focus(true);
+                    if self.focused_column == column {
+                        self.move_focus(false);
+                    }
+                }
+                self.save_session();
+            }
+            ActionId::FollowTodoReference => {
+                if let Some(todo) = self.selected_todo() {
+                    self.follow_reference(None, todo.description());
+                }
+            }
+            ActionId::OpenTodo => {
+                if let Some(todo) = self.selected_todo() {
+                    self.open_todo_view(todo.id);
+                }
+            }
+            ActionId::TodoMenu => {
+                if let Some(todo) = self.selected_todo() {
+                    let menu = Menu::todo(&todo, |action| self.blocked(action));
+                    self.modal = Some(Modal::Menu(Box::new(menu)));
+                }
+            }
+            ActionId::MoveTodo => {
+                if let Some(todo) = self.selected_todo() {
+                    let next = match todo.status() {
+                        TodoStatus::Todo => TodoStatus::Doing,
+                        _ => TodoStatus::Done,
+                    };
+                    self.move_todo(todo.id, next);
+                }
+            }
+            ActionId::ColumnMenu => {
+                let menu =
+                    Menu::column(&COLUMNS[self.focused_column], |action| self.blocked(action));
+                self.modal = Some(Modal::Menu(Box::new(menu)));
+            }
+            ActionId::ColumnSort => {
+                self.modal = Some(Modal::Menu(Box::new(Menu::new(
+                    "Sort the columns",
+                    vec![
+                        (
+                            "by urgency".to_string(),
+                            Choice::Command("set sort urgency".to_string()),
+                        ),
+                        (
+                            "pinned first, then oldest".to_string(),
+                            Choice::Command("set sort manual".to_string()),
+                        ),
+                    ],
+                ))))
+            }
+            ActionId::ColumnArchive => {
+                if COLUMNS[self.focused_column] == TodoStatus::Done {
+                    self.modal = Some(Modal::ArchiveOld(Prompt::new(
+                        "Archive the todos done more than how many days ago?",
+                        "30",
+                    )));
+                } else {
+                    self.status_message = Some(Needs::DoneColumn.reason().to_string());
+                }
+            }
+            ActionId::ColumnWipLimit => {
+                let column = self.focused_column;
+                let title = column_title(&COLUMNS[column]);
+                let current = self
+                    .config
+                    .wip_limits
+                    .get(title)
+                    .map_or(String::new(), usize::to_string);
+                self.modal = Some(Modal::WipLimit {
+                    column,
+                    prompt: Prompt::new(
+                        format!("At most how many todos in {}? (empty: no limit)", title),
+                        &current,
+                    ),
+                });
+            }
+            ActionId::ColumnExport => self.export_column(),
+            ActionId::ColumnClearSubtasks => self.clear_done_subtasks(),
+            ActionId::MoveTodoLeft => self.shift_todo(false),
+            ActionId::MoveTodoRight => self.shift_todo(true),
+            ActionId::StartTimer => {
+                if let Some(todo) = self.selected_todo() {
+                    self.start_timer(&todo);
+                }
+            }
+            ActionId::DeleteTodo => {
+                if let Some(todo) = self.selected_todo() {
+                    self.modal = Some(Modal::ConfirmDelete(todo.id, todo.title));
+                }
+            }
+            ActionId::MarkTodo => {
+                if let Some(todo) = self.selected_todo() {
+                    if !self.marked_todos.remove(&todo.id) {
+                        self.marked_todos.insert(todo.id);
+                    }
+                }
+            }
+            ActionId::TogglePin => {
+                // pins order the ToDo column only
+                if let (0, Some(selected)) = (self.focused_column, self.todo_list_state.selected())
+                {
+                    match toggle_pin(selected, &self.board_filter, &mut self.undo_stack) {
+                        Ok(Some(index)) => {
+                            self.toasts.dismiss_every(ActionId::Undo);
+                            self.todo_list_state.select(Some(index));
+                        }
+                        Ok(None) => {}
+                        Err(err) => self.status_message = Some(err.to_string()),
+                    }
+                }
+            }
+            ActionId::CycleLabel => {
+                let id = match self.view {
+                    Some(View::Todo(id)) => Some(id),
+                    _ => self.selected_todo().map(|todo| todo.id),
+                };
+                if let Some(id) = id {
+                    match label::cycle(id, &mut self.undo_stack) {
+                        Ok(Some(label)) => self.toast_undo(format!("labeled {}", label)),
+                        Ok(None) => self.toast_undo("label cleared".to_string()),
+                        Err(err) => self.status_message = Some(err.to_string()),
+                    }
+                }
+            }
+            ActionId::NewTodo => match read_db() {
+                Ok(todos) => {
+                    let form = TodoForm::new(&todos, Vocabulary::build(&todos));
+                    self.modal = Some(Modal::TodoForm(Box::new(form)));
+                }
+                Err(err) => self.status_message = Some(err.to_string()),
+            },
+            ActionId::EditTodo => {
+                let id = self.selected_todo().map(|todo| todo.id);
+                match read_db() {
+                    Ok(todos) => {
+                        if let Some(todo) = id.and_then(|id| todos.iter().find(|t| t.id == id)) {
+                            let form = TodoForm::edit(todo, &todos, Vocabulary::build(&todos));
+                            self.modal = Some(Modal::TodoForm(Box::new(form)));
+                        }
+                    }
+                    Err(err) => self.status_message = Some(err.to_string()),
+                }
+            }
+            ActionId::BatchEdit => {
+                if self.marked_todos.is_empty() {
+                    self.status_message = Some("mark todos with space first".to_string());
+                } else {
+                    self.modal = Some(Modal::BatchEdit(Box::new(BatchForm::new(
+                        self.marked_todos.clone(),
+                        self.vocabulary(),
+                    ))));
+                }
+            }
+            ActionId::Attach => {
+                let id = match self.view {
+                    Some(View::Todo(id)) => Some(id),
+                    _ => self.selected_todo().map(|todo| todo.id),
+                };
+                if let Some(todo_id) = id {
+                    self.modal = Some(Modal::Attachment {
+                        todo_id,
+                        prompt: Prompt::new(format!("Attach a file to todo {}", todo_id), ""),
+                    });
+                }
+            }
+            ActionId::EditDescription => {
+                let id = match self.view {
+                    Some(View::Todo(id)) => Some(id),
+                    _ => self.selected_todo().map(|todo| todo.id),
+                };
+                let todo = id.and_then(|id| read_db().ok()?.into_iter().find(|t| t.id == id));
+                if let Some(todo) = todo {
+                    let editor = Box::new(DescriptionEditor::new(
+                        todo.id,
+                        todo.description(),
+                        self.config.description_soft_limit,
+                    ));
+                    let key = editor.draft_key();
+                    self.modal = match self.session.drafts.get(&key) {
+                        Some(draft) if draft.text.trim() != todo.description().trim() => {
+                            Some(Modal::ResumeDraft {
+                                editor,
+                                draft: draft.clone(),
+                            })
+                        }
+                        Some(_) => {
+                            self.drop_draft(&key);
+                            Some(Modal::Description(editor))
+                        }
+                        None => Some(Modal::Description(editor)),
+                    };
+                }
+            }
+            ActionId::ToggleDetail => {
+                self.session.detail_visible = !self.session.detail_visible;
+                self.save_session();
+            }
+            ActionId::GrowDetail | ActionId::ShrinkDetail => {
+                self.session.resize_detail(id == ActionId::GrowDetail);
+                self.save_session();
+            }
+            ActionId::ToggleTimeline => {
+                self.timeline = match self.timeline {
+                    Some(_) => None,
+                    None => Some(zone::now().date_naive()),
+                };
+                self.timeline_selected = 0;
+            }
+            ActionId::NextTimer | ActionId::PreviousTimer => {
+                self.scroll_timers(id == ActionId::NextTimer)
+            }
+            ActionId::NewTimer => {
+                self.modal = Some(Modal::TimerForm(Box::new(TimerForm::new(
+                    self.vocabulary(),
+                ))))
+            }
+            ActionId::TimerStart | ActionId::TimerPause | ActionId::TimerStop => self.run_timer(id),
+            ActionId::NextEntry | ActionId::PreviousEntry => {
+                let down = id == ActionId::NextEntry;
+                match self.timeline {
+                    Some(day) => self.scroll_timeline(day, if down { 'j' } else { 'k' }),
+                    None => self.scroll_entries(down),
+                }
+            }
+            ActionId::EarlierDay | ActionId::LaterDay => {
+                if let Some(day) = self.timeline {
+                    let later = id == ActionId::LaterDay;
+                    self.scroll_timeline(day, if later { 'l' } else { 'h' });
+                }
+            }
+            ActionId::FollowEntryReference => {
+                if let Some(entry) = self.selected_entry() {
+                    self.follow_reference(entry.todo_id, entry.note.as_deref().unwrap_or(""));
+                }
+            }
+            ActionId::EntryNote => {
+                if let Some(entry) = self.selected_entry() {
+                    self.modal = Some(Modal::EntryNote {
+                        entry_id: entry.id,
+                        prompt: Prompt::new(
+                            format!("Note for {} entry", entry.category),
+                            entry.note.as_deref().unwrap_or(""),
+                        ),
+                    });
+                }
+            }
+            ActionId::EntryTags => {
+                if let Some(entry) = self.selected_entry() {
+                    let current = match &entry.tags {
+                        Some(tags) if tags.is_empty() => "-".to_string(),
+                        Some(tags) => tags.join(", "),
+                        None => String::new(),
+                    };
+                    self.modal = Some(Modal::EntryTags {
+                        entry_id: entry.id,
+                        prompt: Prompt::new(
+                            "Tags (comma separated, empty: the todo's, -: none)",
+                            &current,
+                        )
+                        .with_completion(self.vocabulary(), complete::locate_tag),
+                    });
+                }
+            }
+            ActionId::Today => self.run_command("today"),
+            ActionId::Yesterday => self.run_command("yesterday"),
+            ActionId::Calendar => self.run_command("calendar"),
+            ActionId::EntryClock => {
+                self.entry_clock = match self.entry_clock {
+                    EntryClock::Current => EntryClock::Recorded,
+                    EntryClock::Recorded => EntryClock::Current,
+                };
+            }
+            ActionId::Timesheet => self.run_command("timesheet"),
+            ActionId::BreakdownScale => self.breakdown_scale = self.breakdown_scale.next(),
+            ActionId::ReportTag => {
+                let current = self.report_filter.tag.clone().unwrap_or_default();
+                self.modal = Some(Modal::ReportTag(
+                    Prompt::new("Count only time tagged (empty: all of it)", &current)
+                        .with_completion(self.vocabulary(), complete::locate_tag),
+                ));
+            }
+            ActionId::ReportBy => self.report_filter.by_tag = !self.report_filter.by_tag,
+            ActionId::Stats => self.run_command("stats"),
+            ActionId::Storage => self.run_command("storage"),
+            ActionId::Inconsistencies => self.run_command("inconsistencies"),
+            ActionId::Share => self.run_command("share"),
+            ActionId::ExportItem => self.run_command("export-item"),
+            ActionId::Snooze => self.run_command("snooze"),
+            ActionId::Refresh => self.run_command("refresh"),
+            ActionId::SaveNow => self.save_now(),
+        }
+    }
+
+    fn run_command(&mut self, line: &str) {
+        if let Err(err) = commands::execute(self, line) {
+            self.status_message = Some(err);
+        }
+    }
+
+    /// Shows another tab, saving any changes still held back first.
+    fn switch_tab(&mut self, item: MenuItem) {
+        if let Err(err) = flush_db(true) {
+            self.status_message = Some(err.to_string());
+        }
+        self.active_menu_item = item;
+        if item == MenuItem::Timers && self.timers_table_state.selected().is_none() {
+            self.scroll_timers(true);
+        }
+        if item == MenuItem::TimeTracking {
+            self.refresh_breakdown();
+        }
+    }
+
+    /// What is moving on screen, for the tick rate.
+    pub fn activity(&self) -> Activity {
+        Activity {
+            running_entry: timetracking::read_time_entries()
+                .map(|entries| entries.iter().any(|e| e.end.is_none()))
+                .unwrap_or(false),
+            running_timer: timers::read_timers()
+                .map(|timers| timers.iter().any(Timer::is_running))
+                .unwrap_or(false),
+            toast: !self.toasts.is_empty(),
+            highlight: self.highlights.any_lit(),
+            pending_alert: self.notifier.has_queued(),
+        }
+    }
+
+    pub fn on_tick(&mut self) {
+        self.reload_config();
+        self.highlights.on_tick();
+        self.toasts.expire(Instant::now());
+        self.save_draft();
+        if let Err(err) = flush_db(false) {
+            self.status_message = Some(err.to_string());
+        }
+        self.follow_selection();
+        let snapshot = Snapshot::read(&self.config);
+        if let Ok(entries) = &snapshot.entries {
+            if let Some(warning) = self.budget_watch.check(entries, &self.config, Utc::now()) {
+                self.status_message = Some(warning);
+            }
+            self.follow_timer(entries);
+            if self.active_menu_item == MenuItem::TimeTracking
+                && self
+                    .breakdown
+                    .as_ref()
+                    .is_none_or(|b| b.is_stale(Instant::now()))
+            {
+                self.breakdown = Some(Breakdown::compute(
+                    entries,
+                    zone::now(),
+                    self.config.week_start,
+                ));
+            }
+        }
+        let now = Utc::now();
+        if let Ok(todos) = &snapshot.todos {
+            for alert in self.due_watch.check(todos, &self.config, now) {
+                self.notifier.push(alert, now);
+            }
+        }
+        self.notifier.flush(now);
+        if let Some(notice) = self.notices.borrow_mut().take() {
+            self.status_message = Some(notice);
+        }
+        if let Some(warning) = activity::take_warning() {
+            self.status_message = Some(warning);
+        }
+        if let Some(notice) = autosave::take_notice() {
+            self.status_message = Some(format!("saved over another process's changes, {}", notice));
+        }
+        self.snapshot = snapshot;
+    }
+
+    fn list_state(&mut self, column: usize) -> &mut ListState {
+        match column {
+            0 => &mut self.todo_list_state,
+            1 => &mut self.doing_list_state,
+            _ => &mut self.done_list_state,
+        }
+    }
+
+    fn column_selected(&self, column: usize) -> Option<usize> {
+        match column {
+            0 => self.todo_list_state.selected(),
+            1 => self.doing_list_state.selected(),
+            _ => self.done_list_state.selected(),
+        }
+    }
+
+    /// Moves the cursor of the focused column to the next todo down or up,
+    /// wrapping around within the column and past the Done day headers.
+    fn step_selection(&mut self, forward: bool) {
+        let column = self.focused_column;
+        let rows = column_rows(
+            &read_db().unwrap_or_default(),
+            COLUMNS[column],
+            &self.board_filter,
+        );
+        let len = rows.len();
+        let next = match self.column_selected(column) {
+            Some(from) => (1..=len)
+                .map(|step| {
+                    if forward {
+                        (from + step) % len
+                    } else {
+                        (from + len - step % len) % len
+                    }
+                })
+                .find(|i| rows[*i].is_some()),
+            None => rows.iter().position(Option::is_some),
+        };
+        self.list_state(column).select(next);
+        self.remember_selection();
+    }
+
+    /// Records which todo each column's cursor is on.
+    fn remember_selection(&mut self) {
+        let all = read_db().unwrap_or_default();
+        for (column, status) in COLUMNS.iter().enumerate() {
+            let rows = column_rows(&all, *status, &self.board_filter);
+            self.selected_ids[column] = self
+                .list_state(column)
+                .selected()
+                .and_then(|i| rows.get(i).cloned().flatten());
+        }
+    }
+
+    /// Puts each cursor back on its todo after the columns changed. A todo
+    /// that left its column is followed to the new one when
+    /// `focus_follows_item` is set, and flashed there; otherwise, or when it
+    /// is gone, the cursor stays at its index.
+    fn follow_selection(&mut self) {
+        let all = read_db().unwrap_or_default();
+        let rows: Vec<Vec<Option<usize>>> = COLUMNS
+            .iter()
+            .map(|status| column_rows(&all, *status, &self.board_filter))
+            .collect();
+        for column in 0..COLUMNS.len() {
+            let id = match self.selected_ids[column] {
+                Some(id) => id,
+                None => continue,
+            };
+            if let Some(index) = rows[column].iter().position(|row| *row == Some(id)) {
+                self.list_state(column).select(Some(index));
+                continue;
+            }
+            let moved_to = (0..COLUMNS.len()).find_map(|other| {
+                rows[other]
+                    .iter()
+                    .position(|row| *row == Some(id))
+                    .map(|index| (other, index))
+            });
+            if let (true, Some((other, index))) = (self.config.focus_follows_item, moved_to) {
+                self.list_state(other).select(Some(index));
+                self.selected_ids[other] = Some(id);
+                self.highlights.flash(Target::Todo(id), DONE_FLASH_TICKS);
+            }
+            let len = rows[column].len();
+            let state = self.list_state(column);
+            if len == 0 {
+                state.select(None);
+            } else if let Some(selected) = state.selected() {
+                state.select(Some(selected.min(len - 1)));
+            }
+            self.selected_ids[column] = state
+                .selected()
+                .and_then(|i| rows[column].get(i).cloned().flatten());
+        }
+    }
+
+    /// Shows the Todos tab with the todo `id` selected in its column. A board
+    /// filter that hides it is cleared, with a notice.
+    pub fn goto_todo(&mut self, id: usize) -> Result<(), String> {
+        let todos = read_db().map_err(|err| err.to_string())?;
+        let todo = match todos.iter().find(|t| t.id == id) {
+            Some(todo) => todo,
+            None => return Err("linked todo no longer exists".to_string()),
+        };
+        if !self.board_filter.matches(todo, &todos, zone::now()) {
+            let mut cleared = Filter::default();
+            cleared.week_start = self.board_filter.week_start;
+            self.board_filter = cleared;
+            self.status_message = Some(format!("filter cleared to show todo {}", id));
+        }
+        self.view = None;
+        self.active_menu_item = MenuItem::Todos;
+
+        let index = COLUMNS
+            .iter()
+            .position(|s| *s == todo.status())
+            .unwrap_or(0);
+        if self.session.collapsed_columns[index] {
+            self.session.toggle_column(index);
+            self.save_session();
+        }
+        self.focused_column = index;
+        let column = read_db_by_todo_status(todo.status(), &self.board_filter)
+            .map_err(|err| err.to_string())?;
+        match todo.status() {
+            TodoStatus::Todo => self
+                .todo_list_state
+                .select(column.iter().position(|t| t.id == id)),
+            TodoStatus::Doing => self
+                .doing_list_state
+                .select(column.iter().position(|t| t.id == id)),
+            TodoStatus::Done => self.done_list_state.select(
+                sort::done_rows(&column, zone::now().date_naive())
+                    .iter()
+                    .position(|row| matches!(row, sort::DoneRow::Todo(t) if t.id == id)),
+            ),
+        }
+        Ok(())
+    }
+
+    /// The todo in the detail pane: the one selected in the focused column.
+    pub fn selected_todo(&self) -> Option<Todo> {
+        self.selected_in(&read_db().ok()?)
+    }
+
+    /// The todo under the cursor among `all`, for drawing from the snapshot.
+    pub fn selected_in(&self, all: &[Todo]) -> Option<Todo> {
+        let column = self.focused_column;
+        let rows = column_rows(all, COLUMNS[column], &self.board_filter);
+        let id = (*rows.get(self.column_selected(column)?)?)?;
+        all.iter().find(|t| t.id == id).cloned()
+    }
+
+    /// h/l pick an attachment in the todo view, o opens it and d asks before removing it.
+    fn on_attachment(&mut self, id: usize, key: char) {
+        let paths = read_db()
+            .ok()
+            .and_then(|todos| todos.into_iter().find(|t| t.id == id))
+            .map(|todo| todo.attachments)
+            .unwrap_or_default();
+        let selected = match paths.get(self.attachment_selected) {
+            Some(path) => path,
+            None => return,
+        };
+        match key {
+            'h' => self.attachment_selected = self.attachment_selected.saturating_sub(1),
+            'l' => self.attachment_selected = (self.attachment_selected + 1).min(paths.len() - 1),
+            'o' => {
+                self.status_message = Some(match attachments::open(selected) {
+                    Ok(()) => format!("opened {}", attachments::label(selected)),
+                    Err(err) => err,
+                })
+            }
+            _ => {
+                self.modal = Some(Modal::ConfirmDetach(
+                    id,
+                    self.attachment_selected,
+                    selected.clone(),
+                ))
+            }
+        }
+    }
+
+    fn detach(&mut self, id: usize, index: usize, path: &Path) {
+        self.status_message = Some(match attachments::detach(id, index) {
+            Ok(_) => format!("removed {}", attachments::label(path)),
+            Err(err) => err.to_string(),
+        });
+        self.attachment_selected = self.attachment_selected.saturating_sub(1);
+    }
+
+    fn scroll_history(&mut self, id: usize, down: bool) {
+        let sessions = timetracking::read_time_entries()
+            .map(|entries| {
+                history::work_history(&entries, id, Utc::now())
+                    .sessions
+                    .len()
+            })
+            .unwrap_or(0);
+        if sessions == 0 {
+            return;
+        }
+        self.history_state
+            .select(Some(match self.history_state.selected() {
+                None => 0,
+                Some(i) if down => (i + 1).min(sessions - 1),
+                Some(i) => i.saturating_sub(1),
+            }));
+    }
+
+    /// Quits, asking for the day's reflection first when it is due.
+    fn request_quit(&mut self) {
+        let notes = daynotes::read_day_notes().unwrap_or_default();
+        let now = zone::now().naive_local();
+        if !daynotes::asks_reflection(&self.config, &notes, self.session.reflection_offered, now) {
+            self.confirm_quit();
+            return;
+        }
+        self.session.reflection_offered = Some(now.date());
+        self.save_session();
+        self.modal = Some(Modal::DayNote {
+            kind: NoteKind::Reflection,
+            date: now.date(),
+            prompt: Prompt::new(NoteKind::Reflection.question(), ""),
+            quit: true,
+        });
+    }
+
+    /// Quits, or asks first as the quit policy says.
+    fn confirm_quit(&mut self) {
+        let check = QuitCheck::new(
+            &timetracking::read_time_entries().unwrap_or_default(),
+            autosave::pending().is_some(),
+        );
+        match self.config.quit_policy {
+            QuitPolicy::Immediate => self.quit_now(),
+            QuitPolicy::Guarded if check.is_clear() => self.quit_now(),
+            QuitPolicy::Guarded | QuitPolicy::Confirm => {
+                self.modal = Some(Modal::ConfirmQuit(check))
+            }
+        }
+    }
+
+    /// Quits, unless the todos can't be saved: then it asks where to
+    /// write them instead, after one more try.
+    fn quit_now(&mut self) {
+        if autosave::failure().is_some() {
+            if let Err(err) = flush_db(true) {
+                self.status_message = Some(err.to_string());
+            }
+        }
+        match autosave::failure() {
+            Some(reason) if autosave::pending().is_some() => {
+                let path = std::env::temp_dir().join(RESCUE_FILE);
+                self.modal = Some(Modal::Rescue(Prompt::new(
+                    format!(
+                        "Cannot save: {}. Write the todos to (empty: quit without them, Esc: back)",
+                        reason
+                    ),
+                    &path.display().to_string(),
+                )));
+            }
+            _ => self.should_quit = true,
+        }
+    }
+
+    /// Saves the changes held in memory now, which is how a failed save
+    /// is tried again without waiting for the retry.
+    fn save_now(&mut self) {
+        let pending = autosave::pending().is_some();
+        if let Err(err) = flush_db(true) {
+            self.status_message = Some(err.to_string());
+            return;
+        }
+        match autosave::failure() {
+            Some(reason) => self.status_message = Some(format!("still cannot save: {}", reason)),
+            None => self.toasts.push(
+                if pending { "saved" } else { "nothing to save" }.to_string(),
+                None,
+                toast::INFO_FOR,
+                Instant::now(),
+            ),
+        }
+    }
+
+    fn open_todo_view(&mut self, id: usize) {
+        self.history_state = TableState::default();
+        self.attachment_selected = 0;
+        self.link_selected = None;
+        self.view = Some(View::Todo(id));
+    }
+
+    /// What the todo view of `id` links to: the todos it refers to, then
+    /// the todos referring to it.
+    pub fn todo_links(&self, id: usize) -> Vec<usize> {
+        let mut links = self.references.refers_to(id).to_vec();
+        for source in self.references.referenced_by(id) {
+            if !links.contains(source) {
+                links.push(*source);
+            }
+        }
+        links
+    }
+
+    /// Jumps from the todo view of `id` to the selected link, or the only
+    /// one, keeping the view open on the todo jumped to.
+    fn follow_link(&mut self, id: usize) {
+        let links = self.todo_links(id);
+        let target = match (self.link_selected, links.as_slice()) {
+            (Some(selected), _) => links.get(selected).copied(),
+            (None, [only]) => Some(*only),
+            (None, []) => {
+                self.status_message = Some("no references to or from this todo".to_string());
+                return;
+            }
+            (None, _) => {
+                self.status_message = Some("Tab to pick a link to follow".to_string());
+                return;
+            }
+        };
+        if let Some(target) = target {
+            match self.goto_todo(target) {
+                Ok(()) => self.open_todo_view(target),
+                Err(err) => {
+                    self.view = Some(View::Todo(id));
+                    self.status_message = Some(err);
+                }
+            }
+        }
+    }
+
+    /// Rescans what todo `id` refers to after its description changed.
+    fn refresh_references(&mut self, id: usize) {
+        let todos = read_db().unwrap_or_default();
+        let entries = timetracking::read_time_entries().unwrap_or_default();
+        match todos.iter().find(|t| t.id == id) {
+            Some(todo) => self.references.update(id, refs::references(todo, &entries)),
+            None => self.references.remove(id),
+        }
+    }
+
+    /// Rescans the todo a time entry was spent on after its note changed.
+    fn refresh_note_references(&mut self, entry_id: usize) {
+        let todo_id = timetracking::read_time_entries()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|e| e.id == entry_id)
+            .and_then(|e| e.todo_id);
+        if let Some(todo_id) = todo_id {
+            self.refresh_references(todo_id);
+        }
+    }
+
+    /// Jumps to `todo_id`, or else to the todo `text` refers to.
+    fn follow_reference(&mut self, todo_id: Option<usize>, text: &str) {
+        let target = match todo_id {
+            Some(id) => Some(id),
+            None => match todo_ids() {
+                Ok(ids) => refs::linked_todo(text, &ids),
+                Err(err) => {
+                    self.status_message = Some(err.to_string());
+                    return;
+                }
+            },
+        };
+        match target {
+            Some(id) => {
+                if let Err(err) = self.goto_todo(id) {
+                    self.status_message = Some(err);
+                }
+            }
+            None => self.status_message = Some("no todo referenced here".to_string()),
+        }
+    }
+
+    /// The categories and tags in the DB as it is now, for a form opening.
+    fn vocabulary(&self) -> Vocabulary {
+        Vocabulary::build(&read_db().unwrap_or_default())
+    }
+
+    /// Todos moved to Done since local midnight.
+    pub fn completed_today(&self) -> usize {
+        let today = zone::now().date_naive();
+        self.snapshot
+            .todos
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .filter(|t| t.status() == TodoStatus::Done)
+            .filter_map(|t| t.completed_at)
+            .filter(|at| at.with_timezone(&zone::current()).date_naive() == today)
+            .count()
+    }
+
+    pub fn clamp_board_selection(&mut self) {
+        clamp_selection(
+            &mut self.todo_list_state,
+            TodoStatus::Todo,
+            &self.board_filter,
+        );
+    }
+
+    /// The remaining work shown under the Todo column, over the todos the
+    /// board filter lets through and limited to todos due before the end of
+    /// the current week when `:estimate week` is on. None while the DB
+    /// can't be read.
+    pub fn remaining_estimate(&self) -> Option<Remaining> {
+        let now = zone::now();
+        let all = self.snapshot.todos.as_ref().ok()?;
+        let todos: Vec<Todo> = all
+            .iter()
+            .filter(|todo| self.board_filter.matches(todo, all, now))
+            .cloned()
+            .collect();
+        let due_before = if self.session.estimate_this_week {
+            Some(timetracking::week_bounds(now, self.config.week_start).1)
+        } else {
+            None
+        };
+        Some(estimate::remaining(&todos, due_before))
+    }
+
+    fn listed_time_entries(&self) -> Result<Vec<timetracking::TimeEntry>, Error> {
+        Ok(timetracking::week_entries(
+            &timetracking::read_time_entries()?,
+            &self.config,
+            Utc::now(),
+        ))
+    }
+
+    /// The entry selected on the TimeTracking tab. None, with the error in
+    /// the status line, when the entries cannot be read.
+    fn selected_entry(&mut self) -> Option<timetracking::TimeEntry> {
+        let selected = self.entries_table_state.selected()?;
+        match self.listed_time_entries() {
+            Ok(listed) => listed.into_iter().nth(selected),
+            Err(err) => {
+                self.status_message = Some(err.to_string());
+                None
+            }
+        }
+    }
+
+    /// The timer selected on the Timers tab, as it is on disk now.
+    fn selected_timer(&self) -> Option<Timer> {
+        let selected = self.timers_table_state.selected()?;
+        timers::read_timers().ok()?.into_iter().nth(selected)
+    }
+
+    fn scroll_timers(&mut self, down: bool) {
+        let count = timers::read_timers().map_or(0, |timers| timers.len());
+        self.timers_table_state
+            .select(match self.timers_table_state.selected() {
+                _ if count == 0 => None,
+                None => Some(0),
+                Some(selected) if down => Some((selected + 1) % count),
+                Some(selected) => Some((selected + count - 1) % count),
+            });
+    }
+
+    /// Starts, pauses or stops the selected timer, as `id` says.
+    fn run_timer(&mut self, id: ActionId) {
+        let timer = match self.selected_timer() {
+            Some(timer) => timer,
+            None => return,
+        };
+        let now = Utc::now();
+        let done = match id {
+            ActionId::TimerStart if timer.is_running() => Ok(format!("{} is running", timer.name)),
+            ActionId::TimerStart => {
+                timers::start(timer.id, now).map(|_| format!("started {}", timer.name))
+            }
+            ActionId::TimerPause if !timer.is_running() => {
+                Ok(format!("{} isn't running", timer.name))
+            }
+            ActionId::TimerPause => timers::pause(timer.id, now).map(|_| {
+                format!(
+                    "paused {} at {}",
+                    timer.name,
+                    timetracking::format_clock(timer.elapsed_at(now))
+                )
+            }),
+            _ => timers::stop(timer.id, now).map(|counted| {
+                format!(
+                    "stopped {} after {}",
+                    timer.name,
+                    timetracking::format_clock(counted.unwrap_or_else(|| timer.elapsed_at(now)))
+                )
+            }),
+        };
+        match done {
+            Ok(message) => self
+                .toasts
+                .push(message, None, toast::INFO_FOR, Instant::now()),
+            Err(err) => self.status_message = Some(err.to_string()),
+        }
+    }
+
+    /// Works out the TimeTracking tab's breakdown afresh, for when the
+    /// tab opens; ticks keep it current from there.
+    fn refresh_breakdown(&mut self) {
+        match timetracking::read_time_entries() {
+            Ok(entries) => {
+                self.breakdown = Some(Breakdown::compute(
+                    &entries,
+                    zone::now(),
+                    self.config.week_start,
+                ))
+            }
+            Err(err) => self.status_message = Some(err.to_string()),
+        }
+    }
+
+    fn scroll_entries(&mut self, down: bool) {
+        let listed = match self.listed_time_entries() {
+            Ok(listed) => listed,
+            Err(err) => {
+                self.status_message = Some(err.to_string());
+                return;
+            }
+        };
+        self.entries_table_state
+            .select(match self.entries_table_state.selected() {
+                _ if listed.is_empty() => None,
+                None => Some(0),
+                Some(selected) if down => Some((selected + 1) % listed.len()),
+                Some(selected) => Some((selected + listed.len() - 1) % listed.len()),
+            });
+    }
+
+    /// j/k pick an entry on the timeline, h/l change the day but never past
+    /// today.
+    fn scroll_timeline(&mut self, day: NaiveDate, key: char) {
+        let today = zone::now().date_naive();
+        match key {
+            'h' => self.timeline = Some(day - Duration::days(1)),
+            'l' => self.timeline = Some((day + Duration::days(1)).min(today)),
+            _ => {
+                let count = timeline::day_entries(
+                    &timetracking::read_time_entries().unwrap_or_default(),
+                    day,
+                    zone::current(),
+                    Utc::now(),
+                )
+                .len();
+                self.timeline_selected = if key == 'j' {
+                    (self.timeline_selected + 1).min(count.saturating_sub(1))
+                } else {
+                    self.timeline_selected.saturating_sub(1)
+                };
+                return;
+            }
+        }
+        self.timeline_selected = 0;
+    }
+
+    /// j/k pick a problem in the inconsistencies view, r, c and d fix it.
+    fn on_problem(&mut self, key: char) {
+        let problems = integrity::problems();
+        let problem = match problems.get(self.problem_selected) {
+            Some(problem) => problem.clone(),
+            None => return,
+        };
+        let fix = match key {
+            'j' => {
+                self.problem_selected = (self.problem_selected + 1).min(problems.len() - 1);
+                return;
+            }
+            'k' => {
+                self.problem_selected = self.problem_selected.saturating_sub(1);
+                return;
+            }
+            'r' => Fix::Relink,
+            'c' => Fix::Clear,
+            'd' => Fix::DeleteEntry,
+            _ => return,
+        };
+        if !problem.fixes().contains(&fix) {
+            self.status_message = Some("that fix doesn't apply here".to_string());
+            return;
+        }
+        if fix == Fix::Relink {
+            match Relink::new(problem, &read_db().unwrap_or_default()) {
+                Some(relink) => self.modal = Some(Modal::Relink(Box::new(relink))),
+                None => self.status_message = Some("no todo to relink to".to_string()),
+            }
+            return;
+        }
+        self.apply_fix(&problem, fix, None);
+    }
+
+    fn apply_fix(&mut self, problem: &Problem, fix: Fix, target: Option<usize>) {
+        match integrity::apply(problem, fix, target, &mut self.undo_stack) {
+            Ok(true) => {
+                let left = integrity::problems().len();
+                self.problem_selected = self.problem_selected.min(left.saturating_sub(1));
+                self.toast_undo(format!("fixed, {} left", left));
+            }
+            Ok(false) => self.status_message = Some("nothing to fix any more".to_string()),
+            Err(err) => self.status_message = Some(err.to_string()),
+        }
+    }
+
+    /// Binds the keys from the config. Keys that conflict open the screen
+    /// to settle them; with a popup already open, the conflicting actions
+    /// keep their defaults and the status line says so.
+    pub fn apply_keymap(&mut self) {
+        let screen = match keymap::apply(&self.config.keys) {
+            Some(screen) => screen,
+            None => return,
+        };
+        if self.modal.is_none() {
+            self.modal = Some(Modal::KeyConflicts(Box::new(screen)));
+        } else {
+            self.status_message =
+                Some("the config's keys conflict, default keys for those actions".to_string());
+        }
+    }
+
+    /// Looks for dangling references, on load and after an import, and
+    /// points to the inconsistencies view when it finds any.
+    pub fn check_integrity(&mut self) {
+        let found = integrity::problems().len();
+        if found == 0 {
+            return;
+        }
+        let note = format!("{} inconsistencies, see :inconsistencies", found);
+        self.status_message = Some(match self.status_message.take() {
+            Some(message) => format!("{}; {}", message, note),
+            None => note,
+        });
+    }
+
+    /// Runs `on_startup` from the config, a failure showing in a toast.
+    pub fn run_startup(&mut self) {
+        let script = self.config.on_startup.clone();
+        if let Err(err) = self.run_script("on_startup", &script) {
+            self.toasts.push(err, None, toast::INFO_FOR, Instant::now());
+        }
+    }
+
+    /// Runs the `:` command lines of the config's `name` script in order.
+    /// The first that fails skips the rest, as does one that opens a
+    /// popup, which the script couldn't answer. A popup open before is
+    /// left as it was.
+    pub fn run_script(&mut self, name: &str, lines: &[String]) -> Result<(), String> {
+        let waiting = self.modal.take();
+        let mut result = Ok(());
+        for line in lines {
+            let ran = commands::execute(self, line).and_then(|()| match self.modal.take() {
+                Some(_) => Err("it opens a popup, which a script can't answer".to_string()),
+                None => Ok(()),
+            });
+            if let Err(err) = ran {
+                result = Err(format!(
+                    "{}: ':{}' failed, the rest skipped: {}",
+                    name,
+                    line.trim(),
+                    err
+                ));
+                break;
+            }
+        }
+        self.modal = waiting;
+        result
+    }
+
+    /// Asks for the day's intention at the first launch of the day. Waits
+    /// for the next launch while another popup is up.
+    pub fn offer_intention(&mut self) {
+        let today = zone::now().date_naive();
+        if self.modal.is_some() || self.session.last_launch == Some(today) {
+            return;
+        }
+        let notes = daynotes::read_day_notes().unwrap_or_default();
+        if daynotes::asks_intention(&self.config, &notes, self.session.last_launch, today) {
+            self.modal = Some(Modal::DayNote {
+                kind: NoteKind::Intention,
+                date: today,
+                prompt: Prompt::new(NoteKind::Intention.question(), ""),
+                quit: false,
+            });
+        }
+        self.session.last_launch = Some(today);
+        self.save_session();
+    }
+
+    /// The once a day pass over the data, run when the TUI starts. Its
+    /// findings go to the status line.
+    pub fn run_daily_maintenance(&mut self) {
+        let today = zone::now().date_naive();
+        if self.session.last_maintenance == Some(today) {
+            return;
+        }
+        if let Some(policy) = self.config.stale_doing {
+            match maintenance::run(&policy, Utc::now()) {
+                Ok(Some(message)) => self.status_message = Some(message),
+                Ok(None) => {}
+                Err(err) => {
+                    self.status_message = Some(err.to_string());
+                    return;
+                }
+            }
+        }
+        self.session.last_maintenance = Some(today);
+        self.save_session();
+    }
+
+    /// Moves the column focus to the next expanded column to the right or
+    /// left, selecting its first todo if nothing is selected there yet.
+    fn move_focus(&mut self, right: bool) {
+        let mut column = self.focused_column;
+        loop {
+            column = match (right, column) {
+                (true, 2) | (false, 0) => return,
+                (true, column) => column + 1,
+                (false, column) => column - 1,
+            };
+            if !self.session.collapsed_columns[column] {
+                break;
+            }
+        }
+        self.focused_column = column;
+        if self.list_state(column).selected().is_none() {
+            let todos =
+                read_db_by_todo_status(COLUMNS[column], &self.board_filter).unwrap_or_default();
+            let first = if COLUMNS[column] == TodoStatus::Done {
+                let rows = sort::done_rows(&todos, zone::now().date_naive());
+                sort::next_selectable(&rows, None, true)
+            } else if todos.is_empty() {
+                None
+            } else {
+                Some(0)
+            };
+            self.list_state(column).select(first);
+        }
+    }
+
+    /// The todos of the focused column as the board shows them, filtered.
+    fn focused_todos(&self) -> Result<Vec<Todo>, Error> {
+        read_db_by_todo_status(COLUMNS[self.focused_column], &self.board_filter)
+    }
+
+    /// Archives the todos of the focused Done column completed more than
+    /// `days` days ago.
+    fn archive_done_before(&mut self, days: i64) {
+        let cutoff = match days
+            .checked_mul(24 * 3600)
+            .and_then(timetracking::checked_seconds)
+            .and_then(|back| Utc::now().checked_sub_signed(back))
+        {
+            Some(cutoff) => cutoff,
+            None => {
+                self.status_message = Some(format!("{} days is too far back", days));
+                return;
+            }
+        };
+        let ids: Vec<usize> = match self.focused_todos() {
+            Ok(todos) => todos
+                .iter()
+                .filter(|t| t.status() == TodoStatus::Done)
+                .filter(|t| t.completed_at.is_some_and(|at| at < cutoff))
+                .map(|t| t.id)
+                .collect(),
+            Err(err) => {
+                self.status_message = Some(err.to_string());
+                return;
+            }
+        };
+        match bulk::archive(&ids, &mut self.undo_stack) {
+            Ok(0) => {
+                self.status_message = Some(format!("nothing done more than {} days ago", days))
+            }
+            Ok(archived) => {
+                self.marked_todos.retain(|id| !ids.contains(id));
+                self.references = load_references();
+                self.clamp_board_selection();
+                self.toast_undo(format!(
+                    "archived {} todos done more than {} days ago",
+                    archived, days
+                ));
+            }
+            Err(err) => self.status_message = Some(err.to_string()),
+        }
+    }
+
+    /// Sets the WIP limit of `column` in the config, or clears it.
+    fn set_wip_limit(&mut self, column: usize, limit: Option<usize>) {
+        let title = column_title(&COLUMNS[column]);
+        let mut limits = self.config.wip_limits.clone();
+        match limit {
+            Some(limit) => limits.insert(title.to_string(), limit),
+            None => limits.remove(title),
+        };
+        let value = serde_json::to_value(&limits).unwrap_or_default();
+        match config::set(vec![("wip_limits", value)]) {
+            Ok(()) => {
+                self.config.wip_limits = limits;
+                // the config is as this session has it, no reload needed
+                self.config_watch.changed();
+                self.status_message = Some(match limit {
+                    Some(limit) => format!("{} holds at most {} todos", title, limit),
+                    None => format!("{} has no WIP limit", title),
+                });
+            }
+            Err(err) => self.status_message = Some(err.to_string()),
+        }
+    }
+
+    /// Writes the focused column, as filtered, to a Markdown file. An
+    /// export from before is replaced whole or not at all.
+    fn export_column(&mut self) {
+        let title = column_title(&COLUMNS[self.focused_column]);
+        let path = format!("column-{}.md", title.to_lowercase());
+        let written = self.focused_todos().and_then(|todos| {
+            write_atomic(&path, &export::column_markdown(title, &todos, Utc::now()))
+                .map(|()| todos.len())
+        });
+        self.status_message = Some(match written {
+            Ok(count) => format!("exported {} todos of {} to {}", count, title, path),
+            Err(err) => err.to_string(),
+        });
+    }
+
+    /// Detaches the done subtasks of the focused column's todos, so their
+    /// progress counts the open ones only, as one undo step.
+    fn clear_done_subtasks(&mut self) {
+        let title = column_title(&COLUMNS[self.focused_column]);
+        let parents: Vec<usize> = match self.focused_todos() {
+            Ok(todos) => todos.iter().map(|t| t.id).collect(),
+            Err(err) => {
+                self.status_message = Some(err.to_string());
+                return;
+            }
+        };
+        let cleared = update_db(|todos| {
+            let before = todos.clone();
+            let mut cleared = 0;
+            for todo in todos.iter_mut().filter(|t| t.status() == TodoStatus::Done) {
+                if todo.parent.is_some_and(|parent| parents.contains(&parent)) {
+                    todo.parent = None;
+                    cleared += 1;
+                }
+            }
+            (before, cleared)
+        });
+        match cleared {
+            Ok((_, 0)) => self.status_message = Some(format!("no done subtasks in {}", title)),
+            Ok((before, cleared)) => {
+                self.undo_stack.push("clear done subtasks", before, 0);
+                self.toast_undo(format!("detached {} done subtasks in {}", cleared, title));
+            }
+            Err(err) => self.status_message = Some(err.to_string()),
+        }
+    }
+
+    /// Moves the todo selected in the focused column to the next column
+    /// on the right or left, where it stays selected so moves can chain.
+    fn shift_todo(&mut self, right: bool) {
+        let from = self.focused_column;
+        let to = match (right, from) {
+            (true, 2) | (false, 0) => return,
+            (true, column) => column + 1,
+            (false, column) => column - 1,
+        };
+        let rows = column_rows(
+            &read_db().unwrap_or_default(),
+            COLUMNS[from],
+            &self.board_filter,
+        );
+        let id = match self.list_state(from).selected().and_then(|i| rows.get(i)) {
+            Some(Some(id)) => *id,
+            _ => {
+                self.status_message = Some(Needs::Todo.reason().to_string());
+                return;
+            }
+        };
+        self.move_todo(id, COLUMNS[to]);
+        let rows = column_rows(
+            &read_db().unwrap_or_default(),
+            COLUMNS[to],
+            &self.board_filter,
+        );
+        if let Some(index) = rows.iter().position(|row| *row == Some(id)) {
+            if self.session.collapsed_columns[to] {
+                self.session.toggle_column(to);
+                self.save_session();
+            }
+            self.list_state(to).select(Some(index));
+            self.focused_column = to;
+        }
+        self.remember_selection();
+    }
+
+    /// Keeps the selection of `column` on a todo after one left it: the
+    /// todo before, when it was the last or a day header is left without
+    /// todos, and none in an empty column.
+    fn clamp_column(&mut self, column: usize) {
+        let rows = column_rows(
+            &read_db().unwrap_or_default(),
+            COLUMNS[column],
+            &self.board_filter,
+        );
+        let state = self.list_state(column);
+        match state.selected() {
+            _ if rows.is_empty() => state.select(None),
+            Some(selected) => {
+                let index = selected.min(rows.len() - 1);
+                let index = (0..=index)
+                    .rev()
+                    .chain(index + 1..rows.len())
+                    .find(|i| rows[*i].is_some());
+                state.select(index);
+            }
+            None => {}
+        }
+    }
+
+    /// Moves the todo `id` to the `status` column, as one undo step.
+    pub fn move_todo(&mut self, id: usize, status: TodoStatus) {
+        let moved = update_db(|todos| {
+            let before = todos.clone();
+            let todo = todos.iter_mut().find(|t| t.id == id)?;
+            todo.set_status(status, Utc::now());
+            Some(before)
+        });
+        match moved {
+            Ok(Some(before)) => {
+                self.undo_stack.push("move", before, 0);
+                for column in 0..COLUMNS.len() {
+                    self.clamp_column(column);
+                }
+                self.toast_undo(format!("moved todo {} to {}", id, column_title(&status)));
+            }
+            Ok(None) => self.status_message = Some(format!("todo {} no longer exists", id)),
+            Err(err) => self.status_message = Some(err.to_string()),
+        }
+    }
+
+    /// Deletes the todo `id`, as one undo step.
+    fn delete_todo(&mut self, id: usize) {
+        let deleted = update_db(|todos| {
+            let before = todos.clone();
+            let column = todos.iter().find(|t| t.id == id)?.status();
+            todos.retain(|t| t.id != id);
+            Some((before, column))
+        });
+        match deleted {
+            Ok(Some((before, column))) => {
+                self.undo_stack.push("delete", before, 0);
+                self.marked_todos.remove(&id);
+                self.references = load_references();
+                if let Some(column) = COLUMNS.iter().position(|c| *c == column) {
+                    self.clamp_column(column);
+                }
+                self.remember_selection();
+                self.toast_undo(format!("deleted todo {}", id));
+            }
+            Ok(None) => self.status_message = Some(format!("todo {} no longer exists", id)),
+            Err(err) => self.status_message = Some(err.to_string()),
+        }
+    }
+
+    /// Saves the title, description and category of `edited` over the
+    /// todo with its id, as one undo step. Nothing else of it changes.
+    fn update_todo(&mut self, edited: Todo) {
+        let updated = update_db(|todos| {
+            let before = todos.clone();
+            let todo = todos.iter_mut().find(|t| t.id == edited.id)?;
+            Some(
+                todo.set_description(edited.description().to_string())
+                    .map(|()| {
+                        todo.title = edited.title.clone();
+                        todo.category = edited.category.clone();
+                        before
+                    }),
+            )
+        });
+        match updated {
+            Ok(Some(Err(err))) => self.status_message = Some(err),
+            Ok(Some(Ok(before))) => {
+                self.undo_stack.push("edit", before, 0);
+                self.references = load_references();
+                self.toast_undo(format!("saved todo {}", edited.id));
+            }
+            Ok(None) => self.status_message = Some(format!("todo {} no longer exists", edited.id)),
+            Err(err) => self.status_message = Some(err.to_string()),
+        }
+    }
+
+    /// Whether the board filter is the one `filter_follows_timer` set.
+    pub fn following_timer(&self) -> bool {
+        self.config.filter_follows_timer && self.filter_follow.is_following()
+    }
+
+    /// Puts the filter for the timers in `entries` on the board, with
+    /// `filter_follows_timer` on.
+    fn follow_timer(&mut self, entries: &[TimeEntry]) {
+        if !self.config.filter_follows_timer {
+            return;
+        }
+        if let Some(filter) = self.filter_follow.step(entries, &self.board_filter) {
+            self.board_filter = filter;
+            self.clamp_board_selection();
+        }
+    }
+
+    /// Starts a timer on `todo` in its category, unless one already runs
+    /// on it.
+    fn start_timer(&mut self, todo: &Todo) {
+        let entries = timetracking::read_time_entries().unwrap_or_default();
+        let running = entries
+            .iter()
+            .any(|e| e.end.is_none() && e.todo_id == Some(todo.id));
+        self.status_message = Some(if running {
+            format!("a timer already runs on todo {}", todo.id)
+        } else {
+            match timetracking::start_entry(&todo.category, Utc::now(), None, Some(todo.id), None) {
+                Ok(_) => format!("timer started on todo {}", todo.id),
+                Err(err) => err.to_string(),
+            }
+        });
+        if let Ok(entries) = timetracking::read_time_entries() {
+            self.follow_timer(&entries);
+        }
+    }
+
+    /// Reloads the DB another process wrote, keeping unsaved changes on
+    /// top of it, the cursors on their todos, the filter and the scroll.
+    pub fn refresh(&mut self) -> Result<String, String> {
+        // the selection was remembered after the last key; remembering it
+        // now would take the rows as the other process left them
+        let disk = read_db_file().map_err(|err| err.to_string())?;
+        let rebased = autosave::rebase(disk);
+        self.references = load_references();
+        integrity::invalidate();
+        self.reports.clear();
+        self.follow_selection();
+        Ok(match rebased.describe() {
+            Some(notice) => format!("refreshed, {}", notice),
+            None => "refreshed".to_string(),
+        })
+    }
+
+    /// Reports a change that just went onto the undo stack with a toast
+    /// offering to take it back. Undo toasts stack in the same order as
+    /// the undo stack, so the shown one always reverts its own change.
+    fn toast_undo(&mut self, text: String) {
+        self.toasts.push(
+            text,
+            Some(ToastAction {
+                key: actions::undo_key(),
+                id: ActionId::Undo,
+                verb: "undo",
+            }),
+            toast::UNDO_FOR,
+            Instant::now(),
+        );
+    }
+
+    /// Reloads the config once its file changes. A valid one replaces the
+    /// old, along with everything drawn from it, in one go between two
+    /// frames; an invalid one leaves the old in place and says why.
+    fn reload_config(&mut self) {
+        if !self.config_watch.changed() {
+            return;
+        }
+        let config = match config::load() {
+            Ok(config) => config,
+            Err(err) => {
+                let at = match &err {
+                    Error::ParseConfigError(parse) => config::line(parse.line())
+                        .map(|line| format!(" in `{}`", line))
+                        .unwrap_or_default(),
+                    _ => String::new(),
+                };
+                self.status_message = Some(format!("config not reloaded, {}{}", err, at));
+                return;
+            }
+        };
+        // the notifiers are only set up at startup
+        let restart = config.due_desktop_notifications != self.config.due_desktop_notifications;
+        // fields set with :set fields stay until the next :set
+        if self.board_fields == self.config.board_item_fields {
+            self.board_fields = config.board_item_fields.clone();
+        }
+        self.glyphs = Glyphs::from_config(&config);
+        self.board_filter.week_start = Some(config.week_start);
+        zone::set(config.timezone.unwrap_or(Zone::System));
+        cursor::set(config.cursor_style);
+        autosave::set_interval(std::time::Duration::from_millis(
+            config.autosave_interval_ms,
+        ));
+        if urgency::board().is_some() {
+            urgency::set_board(Some(Urgency {
+                weights: config.urgency_weights,
+                escalation: config.priority_escalation.clone(),
+            }));
+        }
+        self.config = config;
+        self.apply_keymap();
+        self.toasts.push(
+            if restart {
+                "config reloaded, due_desktop_notifications takes effect on restart"
+            } else {
+                "config reloaded"
+            },
+            None,
+            toast::INFO_FOR,
+            Instant::now(),
+        );
+    }
+
+    /// Keeps what the open description editor holds in the session, or
+    /// drops the draft once the text is back to the stored one.
+    fn save_draft(&mut self) {
+        let (key, text, changed) = match &mut self.modal {
+            Some(Modal::Description(editor)) => match editor.new_draft(Instant::now()) {
+                Some(text) => (editor.draft_key(), text, editor.changed()),
+                None => return,
+            },
+            _ => return,
+        };
+        if changed {
+            self.session.drafts.insert(
+                key,
+                Draft {
+                    text,
+                    saved_at: Utc::now(),
+                },
+            );
+            self.save_session();
+        } else {
+            self.drop_draft(&key);
+        }
+    }
+
+    fn drop_draft(&mut self, key: &str) {
+        if self.session.drafts.remove(key).is_some() {
+            self.save_session();
+        }
+    }
+
+    fn save_session(&mut self) {
+        if let Err(err) = self.session.save() {
+            self.status_message = Some(err.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    /// The data paths are relative, so tests that go through the app take
+    /// turns in a directory of their own.
+    static WORKDIR: Mutex<()> = Mutex::new(());
+
+    struct Scratch {
+        dir: PathBuf,
+        previous: PathBuf,
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            // nothing left pending for the next test to read
+            let _ = flush_db(true);
+            let _ = env::set_current_dir(&self.previous);
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// Runs `test` on an app over `./data/db.json` holding `todos`, in a
+    /// fresh directory.
+    pub(crate) fn with_app(name: &str, todos: serde_json::Value, test: impl FnOnce(&mut App)) {
+        let _turn = WORKDIR.lock().unwrap_or_else(|p| p.into_inner());
+        let dir = env::temp_dir().join(format!("app-{}-{}", name, std::process::id()));
+        fs::create_dir_all(dir.join("data")).unwrap();
+        fs::write(dir.join("data/db.json"), todos.to_string()).unwrap();
+        let _scratch = Scratch {
+            previous: env::current_dir().unwrap(),
+            dir: dir.clone(),
+        };
+        env::set_current_dir(&dir).unwrap();
+        test(&mut App::new(Config::default()));
+    }
+
+    fn todo(id: usize, category: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "title": format!("todo {}", id),
+            "description": "",
+            "category": category,
+            "status": "Todo",
+            "created_at": "2024-05-01T09:00:00Z",
+        })
+    }
+
+    fn done(id: usize, days_ago: i64, parent: Option<usize>) -> serde_json::Value {
+        let mut todo = todo(id, "home");
+        todo["status"] = "Done".into();
+        todo["completed_at"] = (Utc::now() - Duration::days(days_ago)).to_rfc3339().into();
+        todo["parent"] = serde_json::json!(parent);
+        todo
+    }
+
+    fn press(app: &mut App, keys: &[KeyCode]) {
+        for code in keys {
+            app.handle_key(KeyEvent::new(*code, KeyModifiers::NONE));
+        }
+    }
+
+    fn ids() -> Vec<usize> {
+        let mut ids: Vec<usize> = read_db().unwrap().iter().map(|todo| todo.id).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// The Todos tab with column `column` focused.
+    fn on_column(app: &mut App, column: usize) {
+        app.perform(ActionId::TodosTab);
+        app.focused_column = column;
+    }
+
+    fn script(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn every_way_to_add_a_todo_stamps_where_it_came_from() {
+        let mut kept = todo(1, "home");
+        kept["source"] = "stdin".into();
+        with_app("sources", serde_json::json!([kept]), |app| {
+            let source = |title: &str| {
+                let todos = read_db().unwrap();
+                let todo = todos.iter().find(|t| t.title == title).unwrap();
+                todo.source.clone()
+            };
+
+            assert_eq!(add::run(&script(&["Pay", "rent", "--category", "home"])), 0);
+            assert_eq!(source("Pay rent").as_deref(), Some("cli"));
+
+            on_column(app, 0);
+            app.todo_list_state.select(Some(0));
+            assert_eq!(
+                app.run_script("test", &script(&["subtask Call the bank"])),
+                Ok(())
+            );
+            assert_eq!(source("Call the bank").as_deref(), Some("tui"));
+
+            app.perform(ActionId::NewTodo);
+            let title: Vec<KeyCode> = "Water plants".chars().map(KeyCode::Char).collect();
+            press(app, &title);
+            press(app, &[KeyCode::Enter]);
+            assert_eq!(source("Water plants").as_deref(), Some("tui"));
+
+            // an edit keeps whatever the todo had
+            app.todo_list_state.select(Some(0));
+            app.perform(ActionId::EditTodo);
+            press(app, &[KeyCode::Char('!'), KeyCode::Enter]);
+            assert_eq!(source("todo 1!").as_deref(), Some("stdin"));
+        });
+    }
+
+    #[test]
+    fn a_script_switches_the_tab_and_filters_the_board() {
+        let todos = serde_json::json!([todo(1, "home"), todo(2, "work"), todo(3, "work")]);
+        with_app("script", todos, |app| {
+            app.todo_list_state.select(Some(2));
+            let lines = script(&["tab todos", "filter category:work"]);
+            assert_eq!(app.run_script("on_startup", &lines), Ok(()));
+            assert_eq!(app.active_menu_item, MenuItem::Todos);
+
+            let all = read_db().unwrap();
+            let shown: Vec<usize> = all
+                .iter()
+                .filter(|todo| app.board_filter.matches(todo, &all, zone::now()))
+                .map(|todo| todo.id)
+                .collect();
+            assert_eq!(shown, [2, 3]);
+            // the selection stays in the shorter column
+            assert_eq!(app.todo_list_state.selected(), Some(1));
+            assert_eq!(app.board_filter.week_start, Some(app.config.week_start));
+        });
+    }
+
+    #[test]
+    fn a_failing_line_skips_the_rest_of_the_script() {
+        with_app("script-fails", serde_json::json!([]), |app| {
+            let lines = script(&["tab timers", "tab nowhere", "tab todos"]);
+            assert_eq!(
+                app.run_script("on_startup", &lines),
+                Err("on_startup: ':tab nowhere' failed, the rest skipped: \
+                     usage: :tab home|todos|timers|time"
+                    .to_string())
+            );
+            assert_eq!(app.active_menu_item, MenuItem::Timers);
+
+            let lines = script(&["filter nosuchkey:1", "tab todos"]);
+            assert!(app.run_script("on_quit", &lines).is_err());
+            assert_eq!(app.active_menu_item, MenuItem::Timers);
+        });
+    }
+
+    #[test]
+    fn removing_an_attachment_asks_first() {
+        let mut attached = todo(1, "home");
+        attached["attachments"] = serde_json::json!(["notes.txt", "plan.txt"]);
+        with_app("detach-confirm", serde_json::json!([attached]), |app| {
+            let attachments = || read_db().unwrap()[0].attachments.len();
+            app.view = Some(View::Todo(1));
+            press(app, &[KeyCode::Char('d')]);
+            assert!(matches!(app.modal, Some(Modal::ConfirmDetach(1, 0, _))));
+            press(app, &[KeyCode::Char('n')]);
+            assert!(app.modal.is_none());
+            assert_eq!(attachments(), 2);
+
+            press(app, &[KeyCode::Char('d'), KeyCode::Char('y')]);
+            assert_eq!(
+                read_db().unwrap()[0].attachments,
+                [PathBuf::from("plan.txt")]
+            );
+        });
+    }
+
+    #[test]
+    fn a_popup_stops_the_script_and_one_open_before_stays() {
+        let mut deferred = todo(1, "home");
+        deferred["deferred_until"] = "2024-01-01T00:00:00Z".into();
+        with_app("script-popup", serde_json::json!([deferred]), |app| {
+            app.modal = Some(Modal::ConfirmDelete(7, "seven".to_string()));
+            let lines = script(&["tab todos", "carryover", "tab time"]);
+            assert_eq!(
+                app.run_script("on_startup", &lines),
+                Err("on_startup: ':carryover' failed, the rest skipped: \
+                     it opens a popup, which a script can't answer"
+                    .to_string())
+            );
+            assert_eq!(app.active_menu_item, MenuItem::Todos);
+            assert!(matches!(app.modal, Some(Modal::ConfirmDelete(7, _))));
+        });
+    }
+
+    #[test]
+    fn archiving_old_todos_is_grayed_out_on_the_todo_column() {
+        let todos = serde_json::json!([todo(1, "home"), done(2, 60, None)]);
+        with_app("column-archive-todo", todos, |app| {
+            on_column(app, 0);
+            // sort, collapse, then archive
+            press(app, &[KeyCode::Char('.'), KeyCode::Down, KeyCode::Down]);
+            press(app, &[KeyCode::Enter]);
+            assert!(app.modal.is_none());
+            assert_eq!(
+                app.status_message.as_deref(),
+                Some("can't column: archive old here: only on the Done column")
+            );
+            // nor does it run from the palette or a macro
+            app.perform(ActionId::ColumnArchive);
+            assert!(app.modal.is_none());
+            assert_eq!(ids(), [1, 2]);
+        });
+    }
+
+    #[test]
+    fn archiving_old_todos_takes_the_done_ones_past_the_days() {
+        let todos = serde_json::json!([todo(1, "home"), done(2, 60, None), done(3, 1, None)]);
+        with_app("column-archive-done", todos, |app| {
+            on_column(app, 2);
+            press(app, &[KeyCode::Char('.'), KeyCode::Down, KeyCode::Down]);
+            press(app, &[KeyCode::Enter]);
+            assert!(matches!(app.modal, Some(Modal::ArchiveOld(_))));
+            // the suggested 30 days
+            press(app, &[KeyCode::Enter]);
+            assert_eq!(ids(), [1, 3]);
+            assert_eq!(bulk::read_archived().unwrap()[0].id, 2);
+
+            app.perform(ActionId::Undo);
+            assert_eq!(ids(), [1, 2, 3]);
+        });
+    }
+
+    #[test]
+    fn the_export_writes_the_filtered_column() {
+        let todos = serde_json::json!([todo(1, "home"), todo(2, "work"), done(3, 1, None)]);
+        with_app("column-export", todos, |app| {
+            app.board_filter = Filter::parse("category:work").unwrap();
+            on_column(app, 0);
+            app.perform(ActionId::ColumnExport);
+            assert_eq!(
+                app.status_message.as_deref(),
+                Some("exported 1 todos of ToDo to column-todo.md")
+            );
+            let markdown = fs::read_to_string("column-todo.md").unwrap();
+            assert!(markdown.starts_with("# ToDo (1)\n"));
+            assert!(markdown.contains("#2 todo 2 `work`"));
+            assert!(!markdown.contains("#1 "));
+            assert!(!std::path::Path::new("column-todo.md.tmp").exists());
+        });
+    }
+
+    #[test]
+    fn clearing_done_subtasks_keeps_the_open_ones_and_undoes() {
+        let mut open = todo(3, "home");
+        open["parent"] = 1.into();
+        let todos = serde_json::json!([todo(1, "home"), done(2, 1, Some(1)), open]);
+        with_app("column-subtasks", todos, |app| {
+            on_column(app, 0);
+            app.perform(ActionId::ColumnClearSubtasks);
+            let parents: Vec<Option<usize>> = read_db().unwrap().iter().map(|t| t.parent).collect();
+            assert!(parents.contains(&Some(1)));
+            assert_eq!(parents.iter().filter(|p| p.is_some()).count(), 1);
+
+            // the Done column's todos have no subtasks of their own
+            on_column(app, 2);
+            app.perform(ActionId::ColumnClearSubtasks);
+            assert_eq!(
+                app.status_message.as_deref(),
+                Some("no done subtasks in Done")
+            );
+
+            app.perform(ActionId::Undo);
+            let parents: Vec<Option<usize>> = read_db().unwrap().iter().map(|t| t.parent).collect();
+            assert_eq!(parents.iter().filter(|p| p.is_some()).count(), 2);
+        });
+    }
+
+    #[test]
+    fn the_wip_limit_goes_to_the_config_and_clears_when_empty() {
+        with_app("column-wip", serde_json::json!([]), |app| {
+            on_column(app, 1);
+            app.perform(ActionId::ColumnWipLimit);
+            press(app, &[KeyCode::Char('3'), KeyCode::Enter]);
+            assert_eq!(app.config.wip_limits.get("Doing"), Some(&3));
+            let saved = config::load().unwrap();
+            assert_eq!(saved.wip_limits.get("Doing"), Some(&3));
+
+            app.perform(ActionId::ColumnWipLimit);
+            press(app, &[KeyCode::Backspace, KeyCode::Enter]);
+            assert!(app.config.wip_limits.is_empty());
+            assert!(config::load().unwrap().wip_limits.is_empty());
+        });
+    }
+
+    #[test]
+    fn the_sort_picker_runs_the_sort_command() {
+        with_app("column-sort", serde_json::json!([]), |app| {
+            on_column(app, 0);
+            app.perform(ActionId::ColumnSort);
+            press(app, &[KeyCode::Enter]);
+            assert!(app.modal.is_none());
+            assert!(crate::urgency::board().is_some());
+            crate::urgency::set_board(None);
+        });
+    }
+
+    /// An app following timers, with `done:false` on the board.
+    fn following(app: &mut App) -> Filter {
+        app.config.filter_follows_timer = true;
+        app.board_filter = Filter::parse("done:false").unwrap();
+        on_column(app, 0);
+        app.board_filter.clone()
+    }
+
+    fn running() -> Vec<usize> {
+        let entries = timetracking::read_time_entries().unwrap();
+        entries
+            .iter()
+            .filter(|e| e.end.is_none() && e.kind == timetracking::EntryKind::Work)
+            .map(|e| e.id)
+            .collect()
+    }
+
+    #[test]
+    fn back_to_back_timers_restore_the_filter_from_before_the_first() {
+        let todos = serde_json::json!([todo(1, "work"), todo(2, "work")]);
+        with_app("follow-back-to-back", todos, |app| {
+            let before = following(app);
+            app.perform(ActionId::StartTimer);
+            assert_eq!(app.board_filter.category(), Some("work"));
+            assert!(app.following_timer());
+
+            // a second one on the next todo while the first runs
+            press(app, &[KeyCode::Down]);
+            app.perform(ActionId::StartTimer);
+            assert_eq!(running().len(), 2);
+            // and one in another category started by `timer`
+            timetracking::start_entry("email", Utc::now(), None, None, None).unwrap();
+            app.on_tick();
+            assert_eq!(app.board_filter.category(), Some("email"));
+
+            for id in running() {
+                timetracking::finish_entry(id, Utc::now()).unwrap();
+                app.on_tick();
+            }
+            assert_eq!(app.board_filter.label(), before.label());
+            assert!(!app.following_timer());
+        });
+    }
+
+    #[test]
+    fn a_manual_break_keeps_following_the_timer() {
+        let todos = serde_json::json!([todo(1, "work")]);
+        with_app("follow-break", todos, |app| {
+            let before = following(app);
+            app.perform(ActionId::StartTimer);
+            let ctrl_b = KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL);
+            app.handle_key(ctrl_b);
+            assert!(running().is_empty());
+            app.on_tick();
+            assert_eq!(app.board_filter.category(), Some("work"));
+            assert!(app.following_timer());
+
+            app.handle_key(ctrl_b);
+            press(app, &[KeyCode::Char('y')]);
+            assert_eq!(running().len(), 1);
+            app.on_tick();
+            assert_eq!(app.board_filter.category(), Some("work"));
+
+            timetracking::finish_entry(running()[0], Utc::now()).unwrap();
+            app.on_tick();
+            assert_eq!(app.board_filter.label(), before.label());
+        });
+    }
+
+    #[test]
+    fn a_break_is_kept_in_the_entries_and_survives_a_restart() {
+        with_app(
+            "break-restart",
+            serde_json::json!([todo(1, "work")]),
+            |app| {
+                let ctrl_b = KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL);
+                app.perform(ActionId::StartTimer);
+                let timer = running()[0];
+                app.handle_key(ctrl_b);
+
+                let entries = timetracking::read_time_entries().unwrap();
+                assert!(entries
+                    .iter()
+                    .find(|e| e.id == timer)
+                    .unwrap()
+                    .end
+                    .is_some());
+                let current = timetracking::current_break(&entries).unwrap();
+                assert_eq!(current.paused, [timer]);
+                let break_id = current.id;
+
+                // a fresh app finds the break and ends it
+                let mut restarted = App::new(Config::default());
+                restarted.handle_key(ctrl_b);
+                assert!(matches!(restarted.modal, Some(Modal::EndBreak { .. })));
+                press(&mut restarted, &[KeyCode::Char('y')]);
+
+                let entries = timetracking::read_time_entries().unwrap();
+                let logged = entries.iter().find(|e| e.id == break_id).unwrap();
+                assert_eq!(logged.kind, timetracking::EntryKind::Break);
+                assert!(logged.end.is_some() && logged.paused.is_empty());
+                let resumed = running();
+                assert_eq!(resumed.len(), 1);
+                assert_ne!(resumed[0], timer);
+                let resumed = entries.iter().find(|e| e.id == resumed[0]).unwrap();
+                assert_eq!(resumed.todo_id, Some(1));
+            },
+        );
+    }
+
+    #[test]
+    fn a_discarded_break_leaves_no_entry() {
+        with_app(
+            "break-discard",
+            serde_json::json!([todo(1, "work")]),
+            |app| {
+                let ctrl_b = KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL);
+                app.perform(ActionId::StartTimer);
+                app.handle_key(ctrl_b);
+                // a key other than y, n or Esc keeps asking
+                app.handle_key(ctrl_b);
+                press(app, &[KeyCode::Char('x')]);
+                assert!(matches!(app.modal, Some(Modal::EndBreak { .. })));
+                press(app, &[KeyCode::Char('n')]);
+
+                let entries = timetracking::read_time_entries().unwrap();
+                assert!(entries
+                    .iter()
+                    .all(|e| e.kind == timetracking::EntryKind::Work));
+                assert_eq!(entries.len(), 2);
+                assert_eq!(running().len(), 1);
+            },
+        );
+    }
+
+    #[test]
+    fn a_timer_forgotten_during_a_break_is_not_resumed() {
+        with_app(
+            "break-forget",
+            serde_json::json!([todo(1, "work")]),
+            |app| {
+                app.perform(ActionId::StartTimer);
+                let timer = running()[0];
+                assert_eq!(timetracking::start_break(Utc::now()).unwrap(), 1);
+                // a second Ctrl+b from elsewhere while on the break changes nothing
+                assert_eq!(timetracking::start_break(Utc::now()).unwrap(), 0);
+                assert!(timetracking::forget_paused(timer).unwrap());
+                assert!(!timetracking::forget_paused(timer).unwrap());
+                assert_eq!(timetracking::end_break(true, Utc::now()).unwrap(), 0);
+                assert!(running().is_empty());
+                assert_eq!(timetracking::end_break(true, Utc::now()).unwrap(), 0);
+            },
+        );
+    }
+
+    fn ctrl_q(app: &mut App) {
+        app.handle_key(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL));
+    }
+
+    fn asks_to_quit(app: &App) -> bool {
+        matches!(app.modal, Some(Modal::ConfirmQuit(_)))
+    }
+
+    #[test]
+    fn the_immediate_policy_quits_on_ctrl_q_even_with_a_timer() {
+        with_app(
+            "quit-immediate",
+            serde_json::json!([todo(1, "work")]),
+            |app| {
+                app.config.quit_policy = QuitPolicy::Immediate;
+                app.perform(ActionId::StartTimer);
+                ctrl_q(app);
+                assert!(app.should_quit);
+                assert_eq!(running().len(), 1);
+            },
+        );
+    }
+
+    #[test]
+    fn a_single_q_only_arms_the_quit() {
+        with_app("quit-qq", serde_json::json!([todo(1, "work")]), |app| {
+            press(app, &[KeyCode::Char('q')]);
+            assert!(!app.should_quit);
+            assert!(app.status_message.is_some());
+            press(app, &[KeyCode::Char('q')]);
+            assert!(app.should_quit);
+        });
+        with_app(
+            "quit-qq-late",
+            serde_json::json!([todo(1, "work")]),
+            |app| {
+                app.config.quit_window_ms = 0;
+                press(app, &[KeyCode::Char('q')]);
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                press(app, &[KeyCode::Char('q')]);
+                assert!(!app.should_quit);
+                assert_eq!(app.status_message.as_deref(), Some("press q again to quit"));
+            },
+        );
+    }
+
+    #[test]
+    fn the_guarded_policy_asks_only_with_something_left_behind() {
+        with_app(
+            "quit-guarded",
+            serde_json::json!([todo(1, "work")]),
+            |app| {
+                assert_eq!(app.config.quit_policy, QuitPolicy::Guarded);
+                app.perform(ActionId::StartTimer);
+                ctrl_q(app);
+                assert!(asks_to_quit(app));
+                // another key leaves the question up, Esc takes it down
+                press(app, &[KeyCode::Char('x')]);
+                assert!(asks_to_quit(app));
+                press(app, &[KeyCode::Esc]);
+                assert!(app.modal.is_none());
+                assert!(!app.should_quit);
+
+                timetracking::finish_entry(running()[0], Utc::now()).unwrap();
+                ctrl_q(app);
+                assert!(app.modal.is_none());
+                assert!(app.should_quit);
+            },
+        );
+    }
+
+    #[test]
+    fn quitting_can_stop_and_record_the_running_timer() {
+        with_app("quit-stop", serde_json::json!([todo(1, "work")]), |app| {
+            app.perform(ActionId::StartTimer);
+            let timer = running()[0];
+            ctrl_q(app);
+            press(app, &[KeyCode::Char('s')]);
+            assert!(app.should_quit);
+            assert!(running().is_empty());
+            let entries = timetracking::read_time_entries().unwrap();
+            let stopped = entries.iter().find(|e| e.id == timer).unwrap();
+            assert_eq!(stopped.todo_id, Some(1));
+            assert!(stopped.end.is_some());
+        });
+    }
+
+    #[test]
+    fn the_confirm_policy_always_asks_and_y_leaves_the_timer_running() {
+        with_app(
+            "quit-confirm",
+            serde_json::json!([todo(1, "work")]),
+            |app| {
+                app.config.quit_policy = QuitPolicy::Confirm;
+                ctrl_q(app);
+                assert!(asks_to_quit(app));
+                // nothing is running, so there is nothing to stop
+                press(app, &[KeyCode::Char('s')]);
+                assert!(asks_to_quit(app));
+                press(app, &[KeyCode::Char('n')]);
+                assert!(!app.should_quit);
+
+                app.perform(ActionId::StartTimer);
+                press(app, &[KeyCode::Char('q'), KeyCode::Char('q')]);
+                assert!(asks_to_quit(app));
+                press(app, &[KeyCode::Char('y')]);
+                assert!(app.should_quit);
+                assert_eq!(running().len(), 1);
+            },
+        );
+    }
+
+    #[test]
+    fn undo_keeps_every_column_selection_in_range() {
+        let todos = serde_json::json!([
+            todo(1, "work"),
+            done(2, 1, None),
+            done(3, 1, None),
+            done(4, 1, None),
+        ]);
+        with_app("undo-clamp", todos, |app| {
+            let before: Vec<Todo> = read_db().unwrap().into_iter().take(2).collect();
+            app.undo_stack.push("import", before, 4);
+            on_column(app, 2);
+            app.done_list_state.select(Some(2));
+            app.perform(ActionId::Undo);
+            assert_eq!(ids(), [1, 2]);
+            assert_eq!(app.done_list_state.selected(), Some(0));
+            // a column without a selection keeps none
+            assert_eq!(app.doing_list_state.selected(), None);
+        });
+    }
+
+    #[test]
+    fn the_reference_index_follows_adds_edits_and_deletes() {
+        let mut todos = serde_json::json!([todo(1, "work"), todo(2, "work"), todo(3, "work")]);
+        todos[0]["description"] = "after #3".into();
+        todos[1]["description"] = "see #3 and #1".into();
+        with_app("references", todos, |app| {
+            assert_eq!(app.references.referenced_by(3), [1, 2]);
+            assert_eq!(app.references.referenced_by(1), [2]);
+
+            // edited in the description editor
+            app.modal = Some(Modal::Description(Box::new(DescriptionEditor::new(
+                1, "after #3", None,
+            ))));
+            for c in " and #2".chars() {
+                press(app, &[KeyCode::Char(c)]);
+            }
+            app.handle_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+            assert_eq!(app.references.refers_to(1), [2, 3]);
+            assert_eq!(app.references.referenced_by(2), [1]);
+
+            // edited in the form, dropping both
+            app.perform(ActionId::TodosTab);
+            app.goto_todo(1).unwrap();
+            app.perform(ActionId::EditTodo);
+            press(app, &[KeyCode::Tab]);
+            for _ in 0.."after #3 and #2".len() {
+                press(app, &[KeyCode::Backspace]);
+            }
+            press(app, &[KeyCode::Enter]);
+            assert_eq!(app.references.refers_to(1), [] as [usize; 0]);
+            assert_eq!(app.references.referenced_by(3), [2]);
+
+            // added with a reference
+            app.perform(ActionId::NewTodo);
+            for c in "new".chars() {
+                press(app, &[KeyCode::Char(c)]);
+            }
+            press(app, &[KeyCode::Tab]);
+            for c in "blocks #2".chars() {
+                press(app, &[KeyCode::Char(c)]);
+            }
+            press(app, &[KeyCode::Enter]);
+            assert_eq!(app.references.referenced_by(2), [4]);
+
+            // deleted: its own references go, the ones to it stay dangling
+            app.delete_todo(2);
+            assert_eq!(app.references.refers_to(2), [] as [usize; 0]);
+            assert_eq!(app.references.referenced_by(3), [] as [usize; 0]);
+            assert_eq!(app.references.referenced_by(1), [] as [usize; 0]);
+            assert_eq!(app.references.referenced_by(2), [4]);
+        });
+    }
+
+    #[test]
+    fn a_filter_set_while_following_stays_after_the_timer() {
+        let todos = serde_json::json!([todo(1, "work")]);
+        with_app("follow-by-hand", todos, |app| {
+            following(app);
+            app.perform(ActionId::StartTimer);
+            app.run_command("filter tag:deep");
+            app.on_tick();
+            assert!(!app.following_timer());
+
+            timetracking::finish_entry(running()[0], Utc::now()).unwrap();
+            app.on_tick();
+            assert_eq!(app.board_filter.label().as_deref(), Some("#deep"));
+        });
+    }
+
+    #[test]
+    fn the_snapshot_is_taken_after_the_key() {
+        with_app("snapshot", serde_json::json!([todo(1, "work")]), |app| {
+            let on_break = |app: &App| {
+                let entries = app.snapshot.entries.as_ref().unwrap();
+                timetracking::current_break(entries).is_some()
+            };
+            assert!(!on_break(app));
+            app.handle_key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL));
+            assert!(on_break(app));
+        });
+    }
+
+    #[test]
+    fn unreadable_time_entries_show_in_the_status_line() {
+        with_app("entries-broken", serde_json::json!([]), |app| {
+            fs::write(timetracking::TIME_ENTRIES_PATH, "[{").unwrap();
+            app.perform(ActionId::TimeTrackingTab);
+            app.status_message = None;
+            app.perform(ActionId::NextEntry);
+            assert!(app.status_message.is_some());
+
+            app.status_message = None;
+            app.entries_table_state.select(Some(0));
+            app.perform(ActionId::EntryNote);
+            assert!(app.status_message.is_some());
+            assert!(app.modal.is_none());
+        });
+    }
+
+    /// The status of each todo, by id.
+    fn board_state() -> Vec<(usize, TodoStatus)> {
+        let mut state: Vec<_> = read_db()
+            .unwrap()
+            .iter()
+            .map(|t| (t.id, t.status()))
+            .collect();
+        state.sort_unstable_by_key(|(id, _)| *id);
+        state
+    }
+
+    #[test]
+    fn a_replay_ends_where_the_keys_by_hand_do_whatever_the_remap() {
+        let board = || serde_json::json!([todo(1, "work"), todo(2, "work"), todo(3, "work")]);
+        let mut by_hand = vec![];
+        with_app("macro-by-hand", board(), |app| {
+            on_column(app, 0);
+            for _ in 0..3 {
+                press(app, &[KeyCode::Char('L'), KeyCode::Char('h')]);
+                by_hand.push(board_state());
+            }
+        });
+        let doing = |state: &[(usize, TodoStatus)]| {
+            state
+                .iter()
+                .filter(|(_, status)| *status == TodoStatus::Doing)
+                .count()
+        };
+        assert_eq!(
+            by_hand.iter().map(|s| doing(s)).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+
+        with_app("macro-replay", board(), |app| {
+            on_column(app, 0);
+            let keys = ['q', 'a', 'L', 'h', 'q'].map(KeyCode::Char);
+            press(app, &keys);
+            assert_eq!(board_state(), by_hand[0]);
+            assert_eq!(
+                app.session.macros.get(&'a').map(Vec::as_slice),
+                Some(
+                    &[
+                        Step::Action(ActionId::MoveTodoRight),
+                        Step::Action(ActionId::FocusLeft)
+                    ][..]
+                )
+            );
+
+            press(app, &[KeyCode::Char('@'), KeyCode::Char('a')]);
+            assert_eq!(board_state(), by_hand[1]);
+
+            // L now runs nothing, the replay still moves the todo
+            let mut keymap = actions::Keymap::new();
+            keymap.insert(ActionId::MoveTodoRight, vec![actions::key('M')]);
+            actions::set_keymap(keymap);
+            press(app, &[KeyCode::Char('@'), KeyCode::Char('@')]);
+            actions::set_keymap(actions::Keymap::new());
+            assert_eq!(board_state(), by_hand[2]);
+        });
+    }
+
+    #[test]
+    fn a_huge_replay_count_is_capped() {
+        with_app("macro-count", serde_json::json!([todo(1, "work")]), |app| {
+            on_column(app, 0);
+            app.session
+                .macros
+                .insert('a', vec![Step::Action(ActionId::ToggleDetail)]);
+            let visible = app.session.detail_visible;
+            let keys = ['9', '9', '9', '9', '9', '@', 'a'].map(KeyCode::Char);
+            press(app, &keys);
+            // MAX_COUNT is even, so the detail pane ends as it was
+            assert_eq!(macros::MAX_COUNT % 2, 0);
+            assert_eq!(app.session.detail_visible, visible);
+        });
+    }
+
+    /// What another process does to add a todo: read the file under the
+    /// lock, add, write it back, knowing nothing of this one's changes.
+    /// Changes the DB file the way another process would, under the lock
+    /// and without going through this one's autosave state.
+    fn change_in_another_process<T>(change: impl FnOnce(&mut Vec<Todo>) -> T) -> T {
+        let _lock = crate::lock::DbLock::acquire().unwrap();
+        let mut disk =
+            crate::schema::parse_db(&fs::read_to_string(crate::DB_PATH).unwrap()).unwrap();
+        let result = change(&mut disk);
+        fs::write(crate::DB_PATH, crate::schema::db_json(&disk).unwrap()).unwrap();
+        result
+    }
+
+    fn add_from_another_process(title: &str) -> usize {
+        change_in_another_process(|disk| {
+            crate::db::push_todo(
+                disk,
+                Todo::new(title.to_string(), "errands".to_string(), Utc::now()),
+            )
+        })
+    }
+
+    #[test]
+    fn a_cli_add_between_a_change_and_its_save_survives_the_save() {
+        with_app(
+            "interleaved-add",
+            serde_json::json!([todo(1, "work")]),
+            |_| {
+                // saves now, so the change after it waits for the interval
+                autosave::set_interval(std::time::Duration::from_secs(3600));
+                update_db(|_| ()).unwrap();
+                update_db(|todos| todos[0].title = "renamed here".to_string()).unwrap();
+                assert!(autosave::pending().is_some());
+
+                let added = add_from_another_process("added there");
+                flush_db(true).unwrap();
+                autosave::set_interval(std::time::Duration::from_millis(1000));
+
+                let saved = read_db_file().unwrap();
+                let titles: Vec<(usize, &str)> =
+                    saved.iter().map(|t| (t.id, t.title.as_str())).collect();
+                assert_eq!(titles, [(1, "renamed here"), (added, "added there")]);
+            },
+        );
+    }
+
+    #[test]
+    fn adds_here_and_there_in_turn_all_survive_the_save() {
+        with_app(
+            "interleaved-adds",
+            serde_json::json!([todo(1, "work")]),
+            |_| {
+                autosave::set_interval(std::time::Duration::from_secs(3600));
+                update_db(|_| ()).unwrap();
+                let add_here = |title: &str| {
+                    update_db(|todos| {
+                        let todo = Todo::new(title.to_string(), "work".to_string(), Utc::now());
+                        crate::db::push_todo(todos, todo)
+                    })
+                    .unwrap()
+                };
+                assert_eq!(add_here("here a"), 2);
+                assert_eq!(add_from_another_process("there a"), 2);
+                add_here("here b");
+                // the file still has no todo of this process, so 3 again
+                assert_eq!(add_from_another_process("there b"), 3);
+                flush_db(true).unwrap();
+                autosave::set_interval(std::time::Duration::from_millis(1000));
+
+                let saved = read_db_file().unwrap();
+                let mut titles: Vec<(usize, &str)> =
+                    saved.iter().map(|t| (t.id, t.title.as_str())).collect();
+                titles.sort_unstable();
+                assert_eq!(
+                    titles,
+                    [
+                        (1, "todo 1"),
+                        (2, "there a"),
+                        (3, "there b"),
+                        (4, "here b"),
+                        (5, "here a")
+                    ]
+                );
+                let notice = autosave::take_notice().unwrap();
+                assert!(notice.contains("#3 to #5"), "{}", notice);
+            },
+        );
+    }
+
+    #[test]
+    fn every_mutation_path_bumps_updated_at() {
+        let stale = |id: usize, category: &str| {
+            let mut todo = todo(id, category);
+            todo["updated_at"] = "2024-05-01T09:00:00Z".into();
+            todo
+        };
+        let todos = serde_json::json!([stale(1, "work"), stale(2, "home"), stale(3, "work")]);
+        with_app("updated-at", todos, |app| {
+            let since = Utc::now();
+            let bumped = |id: usize| {
+                let todos = read_db().unwrap();
+                let todo = todos.iter().find(|t| t.id == id).unwrap();
+                todo.modified_at() >= since
+            };
+
+            // the TUI
+            on_column(app, 0);
+            press(app, &[KeyCode::Char('L')]);
+            assert!(bumped(1), "TUI move");
+
+            // the CLI, changing a todo and adding one
+            let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+            let code = bulk::run(&args(&[
+                "--filter",
+                "category:home",
+                "--action",
+                "set-priority",
+                "high",
+                "--yes",
+            ]));
+            assert_eq!(code, 0);
+            assert!(bumped(2), "CLI bulk");
+            assert_eq!(add::run(&args(&["added", "--category", "work"])), 0);
+            let added = read_db().unwrap().iter().map(|t| t.id).max().unwrap();
+            assert!(bumped(added), "CLI add");
+
+            // the library
+            crate::Storage::transaction(&crate::JsonStorage, |db| {
+                Ok(crate::move_todo(db, 3, TodoStatus::Done, Utc::now()))
+            })
+            .unwrap();
+            assert!(bumped(3), "Storage");
+
+            // an import merged into the board
+            let mut elsewhere = stale(1, "old");
+            elsewhere["created_at"] = "2024-04-01T09:00:00Z".into();
+            let archived = serde_json::json!({"version": 1, "todos": [elsewhere]});
+            let archive = serde_json::json!({
+                "format": "pws-archive",
+                "version": 1,
+                "created_at": "2024-05-01T09:00:00Z",
+                "files": {"db.json": archived.to_string()},
+            });
+            fs::write("backup.json", archive.to_string()).unwrap();
+            let code = crate::archive::run_import(&args(&[
+                "--format",
+                "pws-archive",
+                "backup.json",
+                "--merge",
+            ]));
+            assert_eq!(code, 0);
+            let imported = read_db().unwrap().iter().map(|t| t.id).max().unwrap();
+            assert!(imported > added && bumped(imported), "import");
+        });
+    }
+
+    #[test]
+    fn the_cursor_stays_on_its_todo_when_the_rows_shift() {
+        let todos = serde_json::json!([todo(1, "work"), todo(2, "work"), todo(3, "work")]);
+        with_app("rows-shift", todos, |app| {
+            on_column(app, 0);
+            app.todo_list_state.select(Some(2));
+            app.remember_selection();
+            assert_eq!(app.selected_ids[0], Some(3));
+
+            change_in_another_process(|disk| disk.retain(|t| t.id != 1));
+            app.refresh().unwrap();
+            assert_eq!(app.todo_list_state.selected(), Some(1));
+            assert_eq!(app.selected_ids[0], Some(3));
+
+            // gone altogether, the cursor keeps its place as far as it can
+            change_in_another_process(|disk| disk.retain(|t| t.id != 3));
+            app.on_tick();
+            assert_eq!(app.todo_list_state.selected(), Some(0));
+            assert_eq!(app.selected_ids[0], Some(2));
+        });
+    }
+
+    #[test]
+    fn a_todo_moved_by_another_process_is_followed_only_when_configured() {
+        for follow in [false, true] {
+            let todos = serde_json::json!([todo(1, "work"), todo(2, "work"), todo(3, "work")]);
+            with_app("moved-away", todos, |app| {
+                app.config.focus_follows_item = follow;
+                on_column(app, 0);
+                app.todo_list_state.select(Some(1));
+                app.remember_selection();
+
+                change_in_another_process(|disk| {
+                    let todo = disk.iter_mut().find(|t| t.id == 2).unwrap();
+                    todo.set_status(TodoStatus::Doing, Utc::now());
+                });
+                app.refresh().unwrap();
+                // the Todo column's cursor falls to the todo now at its index
+                assert_eq!(app.todo_list_state.selected(), Some(1));
+                assert_eq!(app.selected_ids[0], Some(3));
+                if follow {
+                    assert_eq!(app.doing_list_state.selected(), Some(0));
+                    assert_eq!(app.selected_ids[1], Some(2));
+                } else {
+                    assert_eq!(app.selected_ids[1], None);
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn z_collapses_columns_and_h_l_skip_them() {
+        let todos = serde_json::json!([todo(1, "work")]);
+        with_app("collapse", todos, |app| {
+            on_column(app, 1);
+            let z = [KeyCode::Char('z')];
+            press(app, &z);
+            assert_eq!(app.session.collapsed_columns, [false, true, false]);
+            // the focus leaves for the next expanded column
+            assert_eq!(app.focused_column, 2);
+            press(app, &[KeyCode::Char('h')]);
+            assert_eq!(app.focused_column, 0);
+            press(app, &[KeyCode::Char('l')]);
+            assert_eq!(app.focused_column, 2);
+
+            press(app, &z);
+            assert_eq!(app.session.collapsed_columns, [false, true, true]);
+            assert_eq!(app.focused_column, 0);
+            // the last expanded column stays
+            press(app, &z);
+            assert_eq!(app.session.collapsed_columns, [false, true, true]);
+            assert_eq!(
+                app.status_message.as_deref(),
+                Some("one column always stays expanded")
+            );
+            press(app, &[KeyCode::Char('l')]);
+            assert_eq!(app.focused_column, 0);
+
+            // and the collapsed columns are kept for the next session
+            assert_eq!(Session::load().collapsed_columns, [false, true, true]);
+        });
+    }
+
+    #[test]
+    fn stale_doing_todos_are_demoted_with_a_note_and_logged() {
+        let mut stale = todo(1, "work");
+        stale["status"] = "Doing".into();
+        stale["doing_at"] = "2024-05-01T09:00:00Z".into();
+        stale["description"] = "draft".into();
+        let mut fresh = todo(2, "work");
+        fresh["status"] = "Doing".into();
+        fresh["doing_at"] = Utc::now().to_rfc3339().into();
+        with_app("stale-doing", serde_json::json!([stale, fresh]), |_| {
+            let policy = crate::config::StalePolicy {
+                days: 14,
+                action: crate::config::StaleAction::Warn,
+            };
+            let warned = maintenance::run(&policy, Utc::now()).unwrap().unwrap();
+            assert!(warned.ends_with("without activity: todo 1"), "{}", warned);
+            assert_eq!(read_db().unwrap()[0].status(), TodoStatus::Doing);
+
+            let policy = crate::config::StalePolicy {
+                action: crate::config::StaleAction::Demote,
+                ..policy
+            };
+            let demoted = maintenance::run(&policy, Utc::now()).unwrap().unwrap();
+            assert_eq!(demoted, "moved back to ToDo: todo 1");
+            let todos = read_db().unwrap();
+            assert_eq!(todos[0].status(), TodoStatus::Todo);
+            assert!(todos[0]
+                .description()
+                .starts_with("draft\n\nauto-demoted after "));
+            assert_eq!(todos[1].status(), TodoStatus::Doing);
+            let logged = crate::activity::read_activity().unwrap();
+            assert!(logged.iter().any(|e| e.todo_id == 1
+                && e.kind
+                    == crate::activity::ActivityKind::Moved {
+                        from: TodoStatus::Doing,
+                        to: TodoStatus::Todo
+                    }));
+        });
+    }
+
+    #[test]
+    fn digits_after_f_pick_the_due_window_and_count_replays_otherwise() {
+        with_app("due-keys", serde_json::json!([todo(1, "work")]), |app| {
+            on_column(app, 0);
+            let f = |digit| [KeyCode::Char('f'), KeyCode::Char(digit)];
+            press(app, &f('1'));
+            assert_eq!(app.board_filter.due, Some(DueWindow::Today));
+            press(app, &f('3'));
+            assert_eq!(app.board_filter.due, Some(DueWindow::Overdue));
+            press(app, &f('3'));
+            assert_eq!(app.board_filter.due, None);
+            press(app, &[KeyCode::Char('f'), KeyCode::Esc, KeyCode::Char('2')]);
+            assert_eq!(app.board_filter.due, None);
+
+            // a key other than a digit drops the f and runs as usual
+            let visible = app.session.detail_visible;
+            press(app, &[KeyCode::Char('f'), KeyCode::Char('x')]);
+            assert_eq!(app.session.detail_visible, !visible);
+
+            app.session
+                .macros
+                .insert('a', vec![Step::Action(ActionId::ToggleDetail)]);
+            press(app, &['3', '@', 'a'].map(KeyCode::Char));
+            assert_eq!(app.session.detail_visible, visible);
+            assert_eq!(app.board_filter.due, None);
+        });
+    }
+
+    /// Opens the description editor on the first todo of the ToDo column.
+    fn edit_description(app: &mut App) {
+        on_column(app, 0);
+        app.todo_list_state.select(Some(0));
+        app.perform(ActionId::EditDescription);
+    }
+
+    fn drafts() -> Vec<(String, String)> {
+        Session::load()
+            .drafts
+            .into_iter()
+            .map(|(key, draft)| (key, draft.text))
+            .collect()
+    }
+
+    fn ctrl_s(app: &mut App) {
+        app.handle_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+    }
+
+    #[test]
+    fn a_draft_outlives_the_app_and_is_offered_back_on_the_next_edit() {
+        with_app(
+            "draft-resume",
+            serde_json::json!([todo(1, "work")]),
+            |app| {
+                edit_description(app);
+                press(
+                    app,
+                    &"half a thought"
+                        .chars()
+                        .map(KeyCode::Char)
+                        .collect::<Vec<_>>(),
+                );
+                app.on_tick();
+                let kept = [("description:1".to_string(), "half a thought".to_string())];
+                assert_eq!(drafts(), kept);
+
+                // a new app stands in for the one that crashed
+                let mut app = App::new(Config::default());
+                edit_description(&mut app);
+                assert!(matches!(app.modal, Some(Modal::ResumeDraft { .. })));
+                // Esc leaves the draft for later
+                press(&mut app, &[KeyCode::Esc]);
+                assert!(app.modal.is_none());
+                assert_eq!(drafts(), kept);
+
+                edit_description(&mut app);
+                press(&mut app, &[KeyCode::Char('y')]);
+                assert!(matches!(app.modal, Some(Modal::Description(_))));
+                ctrl_s(&mut app);
+                assert_eq!(read_db().unwrap()[0].description(), "half a thought");
+                assert_eq!(drafts(), []);
+            },
+        );
+    }
+
+    #[test]
+    fn declining_or_discarding_a_draft_drops_it() {
+        with_app("draft-drop", serde_json::json!([todo(1, "work")]), |app| {
+            edit_description(app);
+            press(app, &[KeyCode::Char('x')]);
+            app.on_tick();
+            press(app, &[KeyCode::Esc]);
+            assert!(matches!(app.modal, Some(Modal::DiscardDraft(_))));
+            // n goes back to the editor, the text still there
+            press(app, &[KeyCode::Char('n')]);
+            assert!(matches!(app.modal, Some(Modal::Description(_))));
+            press(app, &[KeyCode::Esc]);
+            assert!(matches!(app.modal, Some(Modal::DiscardDraft(_))));
+            // the terminal closes with the question open
+            app.modal = None;
+            assert_eq!(drafts().len(), 1);
+
+            edit_description(app);
+            press(app, &[KeyCode::Char('n')]);
+            assert!(matches!(app.modal, Some(Modal::Description(_))));
+            assert_eq!(drafts(), []);
+            // the editor starts from the stored description
+            ctrl_s(app);
+            assert_eq!(read_db().unwrap()[0].description(), "");
+
+            edit_description(app);
+            press(app, &[KeyCode::Char('y')]);
+            app.on_tick();
+            press(app, &[KeyCode::Esc]);
+            press(app, &[KeyCode::Char('y')]);
+            assert!(app.modal.is_none());
+            assert_eq!(drafts(), []);
+
+            // Esc on text no different from the stored one doesn't ask
+            edit_description(app);
+            press(app, &[KeyCode::Enter, KeyCode::Esc]);
+            assert!(app.modal.is_none());
+        });
+    }
+
+    #[test]
+    fn the_timers_tab_adds_a_timer_then_starts_pauses_and_stops_it_by_key() {
+        with_app("timers-tab", serde_json::json!([]), |app| {
+            app.perform(ActionId::TimersTab);
+            press(app, &[KeyCode::Char('a')]);
+            assert!(matches!(app.modal, Some(Modal::TimerForm(_))));
+            press(app, &"focus".chars().map(KeyCode::Char).collect::<Vec<_>>());
+            press(app, &[KeyCode::Tab]);
+            press(app, &"deep".chars().map(KeyCode::Char).collect::<Vec<_>>());
+            press(app, &[KeyCode::Enter]);
+            assert!(app.modal.is_none());
+            let timer = || timers::read_timers().unwrap().remove(0);
+            assert_eq!(
+                (timer().name, timer().category),
+                ("focus".into(), "deep".into())
+            );
+            assert_eq!(app.timers_table_state.selected(), Some(0));
+
+            let entries = || timetracking::read_time_entries().unwrap();
+            press(app, &[KeyCode::Char('s')]);
+            assert!(timer().is_running());
+            press(app, &[KeyCode::Char('p')]);
+            assert!(!timer().is_running());
+            assert_eq!(entries().len(), 1);
+            press(app, &[KeyCode::Char('s'), KeyCode::Char('x')]);
+            assert!(!timer().is_running());
+            assert_eq!(timer().elapsed, Duration::zero());
+            assert_eq!(entries().len(), 2);
+            assert!(entries().iter().all(|e| e.end.is_some()
+                && e.category == "deep"
+                && e.note.as_deref() == Some("focus")));
+        });
+    }
+}
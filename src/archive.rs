@@ -0,0 +1,503 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::reid::{self, IdMap, IdMaps};
+use crate::schema;
+use crate::timetracking::TimeEntry;
+use crate::txn::transaction;
+use crate::{cli, write_atomic, Error, Todo};
+
+const FORMAT: &str = "pws-archive";
+/// Bumped whenever the archive layout changes; newer archives are refused.
+const VERSION: u32 = 1;
+const DATA_DIR: &str = "./data";
+/// Everything under `./data` that is user data. The session file is left
+/// out, it only holds UI state for one machine.
+const ARCHIVED_FILES: [&str; 10] = [
+    "db.json",
+    "archived_todos.json",
+    "config.json",
+    "time_entries.json",
+    "activity.json",
+    "target_overrides.json",
+    "pomodoros.json",
+    "calendar.json",
+    "day_notes.json",
+    "timers.json",
+];
+
+/// A single JSON document holding the data files verbatim, so a restore
+/// reproduces them byte for byte.
+#[derive(Serialize, Deserialize)]
+struct Archive {
+    format: String,
+    version: u32,
+    created_at: DateTime<Utc>,
+    files: BTreeMap<String, String>,
+}
+
+/// Writes every data file that exists into one archive at `path` and
+/// returns the process exit code.
+pub fn export(path: &str) -> i32 {
+    let mut files = BTreeMap::new();
+    for name in ARCHIVED_FILES.iter() {
+        let source = format!("{}/{}", DATA_DIR, name);
+        match fs::read_to_string(&source) {
+            Ok(content) => {
+                files.insert(name.to_string(), content);
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return cli::fail(&Error::ReadFileError(source, err)),
+        }
+    }
+    let archive = Archive {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        created_at: Utc::now(),
+        files,
+    };
+    let content = match serde_json::to_string_pretty(&archive) {
+        Ok(content) => content,
+        Err(err) => return cli::fail(&err.into()),
+    };
+    if let Err(err) = fs::write(path, content) {
+        return cli::fail(&Error::WriteFileError(path.to_string(), err));
+    }
+    println!("archived {} files to {}", archive.files.len(), path);
+    0
+}
+
+/// Runs `import --format pws-archive FILE [--into DIR | --merge [--id-map
+/// OUT]]` and returns the process exit code.
+pub fn run_import(args: &[String]) -> i32 {
+    let mut format = None;
+    let mut into = None;
+    let mut merging = false;
+    let mut id_map = None;
+    let mut path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" | "--into" | "--id-map" => match args.next() {
+                Some(value) if arg == "--format" => format = Some(value.as_str()),
+                Some(value) if arg == "--into" => into = Some(value.clone()),
+                Some(value) => id_map = Some(value.as_str()),
+                None => return cli::usage(format!("{} needs a value", arg)),
+            },
+            "--merge" => merging = true,
+            _ if path.is_none() => path = Some(arg.as_str()),
+            _ => return cli::usage(format!("unknown import argument '{}'", arg)),
+        }
+    }
+    let path = match (format, path) {
+        (Some(FORMAT), Some(path)) => path,
+        _ => {
+            return cli::usage(
+                "usage: import --format pws-archive FILE [--into DIR | --merge [--id-map OUT]]",
+            )
+        }
+    };
+    if merging && into.is_some() {
+        return cli::usage("--merge adds to ./data, it doesn't take --into");
+    }
+    if id_map.is_some() && !merging {
+        return cli::usage("--id-map needs --merge");
+    }
+    if merging {
+        return match merge(path) {
+            Ok(merged) => merged.report(path, id_map),
+            Err(err) => cli::fail(&err),
+        };
+    }
+    let into = into.unwrap_or_else(|| DATA_DIR.to_string());
+
+    match import(path, &into) {
+        Ok(restored) => {
+            println!("restored {} files into {}", restored, into);
+            0
+        }
+        Err(err) => cli::fail(&err),
+    }
+}
+
+/// Reads and checks the archive at `path`.
+fn read_archive(path: &str) -> Result<Archive, Error> {
+    let content =
+        fs::read_to_string(path).map_err(|err| Error::ReadFileError(path.to_string(), err))?;
+    let archive: Archive = serde_json::from_str(&content)
+        .map_err(|err| Error::ArchiveError(format!("{} is not a {}: {}", path, FORMAT, err)))?;
+    if archive.format != FORMAT {
+        return Err(Error::ArchiveError(format!(
+            "{} has format '{}', expected '{}'",
+            path, archive.format, FORMAT
+        )));
+    }
+    if archive.version > VERSION {
+        return Err(Error::ArchiveError(format!(
+            "{} is version {}, this build reads up to {}; update before importing",
+            path, archive.version, VERSION
+        )));
+    }
+    for name in archive.files.keys() {
+        if !ARCHIVED_FILES.contains(&name.as_str()) {
+            return Err(Error::ArchiveError(format!(
+                "{} contains an unexpected file '{}'",
+                path, name
+            )));
+        }
+    }
+    Ok(archive)
+}
+
+/// The todos an archive holds, for restoring them into the live DB.
+pub fn read_todos(path: &str) -> Result<Vec<Todo>, Error> {
+    match read_archive(path)?.files.get("db.json") {
+        Some(content) => schema::parse_db(content),
+        None => Err(Error::ArchiveError(format!("{} holds no db.json", path))),
+    }
+}
+
+/// Restores an archive into `dir`, which must not hold a DB yet so that
+/// nothing is overwritten. Returns the number of files written. Every
+/// file comes back byte for byte but the DB, whose todos are touched so
+/// `list --modified-since` picks up the import.
+fn import(path: &str, dir: &str) -> Result<usize, Error> {
+    let archive = read_archive(path)?;
+    if Path::new(dir).join("db.json").exists() {
+        return Err(Error::ArchiveError(format!(
+            "{} already has a db.json, import into an empty directory with --into",
+            dir
+        )));
+    }
+
+    // read before anything is written, so a DB too new leaves `dir` alone
+    let mut todos = match archive.files.get("db.json") {
+        Some(content) => Some(schema::parse_db(content)?),
+        None => None,
+    };
+
+    fs::create_dir_all(dir).map_err(|err| Error::WriteFileError(dir.to_string(), err))?;
+    let now = Utc::now();
+    for (name, content) in &archive.files {
+        let path = format!("{}/{}", dir, name);
+        match todos.as_mut().filter(|_| name == "db.json") {
+            Some(todos) => {
+                for todo in todos.iter_mut() {
+                    todo.touch(now);
+                }
+                write_atomic(&path, &schema::db_json(todos)?)?;
+            }
+            None => write_atomic(&path, content)?,
+        }
+    }
+    Ok(archive.files.len())
+}
+
+/// What `import --merge` added to the live data.
+struct Merged {
+    todos: usize,
+    entries: usize,
+    /// Records the data already had, same id and same creation.
+    skipped: usize,
+    todos_map: IdMap,
+    entries_map: IdMap,
+}
+
+impl Merged {
+    /// Prints the summary with the ids that moved, writes them to
+    /// `id_map` as JSON when given, and returns the exit code.
+    fn report(&self, path: &str, id_map: Option<&str>) -> i32 {
+        println!(
+            "merged {} todos and {} time entries from {}, {} already here",
+            self.todos, self.entries, path, self.skipped
+        );
+        if !self.todos_map.is_empty() || !self.entries_map.is_empty() {
+            println!("ids taken here, moved:");
+            for line in reid::table("todo", &self.todos_map)
+                .into_iter()
+                .chain(reid::table("entry", &self.entries_map))
+            {
+                println!("{}", line);
+            }
+        }
+        if let Some(out) = id_map {
+            let maps = IdMaps {
+                todos: &self.todos_map,
+                time_entries: &self.entries_map,
+            };
+            let written = serde_json::to_string_pretty(&maps)
+                .map_err(Error::from)
+                .and_then(|json| {
+                    fs::write(out, json).map_err(|err| Error::WriteFileError(out.to_string(), err))
+                });
+            if let Err(err) = written {
+                return cli::fail(&err);
+            }
+        }
+        0
+    }
+}
+
+/// Adds the todos and time entries of the archive at `path` to the live
+/// data. Records already here are skipped; incoming ones whose id is
+/// taken get new ids by `IdMap::plan`, and the links among them follow.
+/// Todos that don't know their source get "merge:" and the archive's file
+/// stem, e.g. "merge:laptop".
+fn merge(path: &str) -> Result<Merged, Error> {
+    let archive = read_archive(path)?;
+    let source = format!(
+        "merge:{}",
+        Path::new(path)
+            .file_stem()
+            .map_or(path.into(), |stem| stem.to_string_lossy())
+    );
+    let incoming_todos = match archive.files.get("db.json") {
+        Some(content) => schema::parse_db(content)?,
+        None => vec![],
+    };
+    let incoming_entries: Vec<TimeEntry> = match archive.files.get("time_entries.json") {
+        Some(content) => serde_json::from_str(content)?,
+        None => vec![],
+    };
+
+    // todos and entries go in together, so a failure never leaves the
+    // todos merged without their time
+    transaction(|txn| {
+        let known: HashSet<(usize, DateTime<Utc>)> =
+            txn.todos.iter().map(|t| (t.id, t.created_at)).collect();
+        let total = incoming_todos.len();
+        let mut incoming: Vec<Todo> = incoming_todos
+            .into_iter()
+            .filter(|t| !known.contains(&(t.id, t.created_at)))
+            .collect();
+        let ids: Vec<usize> = incoming.iter().map(|t| t.id).collect();
+        let todos_map = IdMap::plan(&ids, &txn.todos.iter().map(|t| t.id).collect());
+        reid::rewrite_todos(&mut incoming, &todos_map);
+        let now = Utc::now();
+        for todo in incoming.iter_mut() {
+            todo.source.get_or_insert_with(|| source.clone());
+            todo.touch(now);
+        }
+        let todos = incoming.len();
+        let mut skipped = total - todos;
+        txn.todos.extend(incoming);
+        let batch: HashSet<usize> = ids.into_iter().collect();
+
+        let known: HashSet<(usize, DateTime<Utc>)> =
+            txn.entries.iter().map(|e| (e.id, e.start)).collect();
+        let total = incoming_entries.len();
+        let mut incoming: Vec<TimeEntry> = incoming_entries
+            .into_iter()
+            .filter(|e| !known.contains(&(e.id, e.start)))
+            .collect();
+        let ids: Vec<usize> = incoming.iter().map(|e| e.id).collect();
+        let entries_map = IdMap::plan(&ids, &txn.entries.iter().map(|e| e.id).collect());
+        reid::rewrite_entries(&mut incoming, &entries_map, &todos_map, &batch);
+        let entries = incoming.len();
+        skipped += total - entries;
+        txn.entries.extend(incoming);
+
+        Ok(Merged {
+            todos,
+            entries,
+            skipped,
+            todos_map,
+            entries_map,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TodoStatus;
+    use serde_json::json;
+
+    #[test]
+    fn an_import_touches_the_todos_and_keeps_the_other_files() {
+        let scratch = std::env::temp_dir().join(format!("archive-import-{}", std::process::id()));
+        let dir = scratch.join("data");
+        let path = scratch.join("backup.json");
+        let todos = json!([{
+            "id": 1,
+            "title": "restored",
+            "description": "",
+            "category": "home",
+            "status": "Todo",
+            "created_at": "2024-05-01T09:00:00Z",
+            "updated_at": "2024-05-02T09:00:00Z",
+        }]);
+        let notes = "{ \"2024-05-01\": \"kept as is\" }";
+        let archive = Archive {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            created_at: Utc::now(),
+            files: BTreeMap::from([
+                ("db.json".to_string(), todos.to_string()),
+                ("day_notes.json".to_string(), notes.to_string()),
+            ]),
+        };
+        fs::create_dir_all(&scratch).unwrap();
+        fs::write(&path, serde_json::to_string(&archive).unwrap()).unwrap();
+
+        let before = Utc::now();
+        let restored = import(path.to_str().unwrap(), dir.to_str().unwrap());
+        let db = fs::read_to_string(dir.join("db.json")).map(|db| schema::parse_db(&db));
+        let kept = fs::read_to_string(dir.join("day_notes.json"));
+        let _ = fs::remove_dir_all(&scratch);
+
+        assert_eq!(restored.unwrap(), 2);
+        let todos = db.unwrap().unwrap();
+        assert_eq!(todos[0].title, "restored");
+        assert!(todos[0].modified_at() >= before);
+        assert_eq!(kept.unwrap(), notes);
+    }
+
+    /// A board of `count` todos with every kind of field filled in turn.
+    fn fixture(count: usize) -> Vec<Todo> {
+        let created = Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap();
+        (1..=count)
+            .map(|id| {
+                let at = created + chrono::Duration::hours(id as i64);
+                let mut todo = Todo::new(
+                    format!("todo {} \"quoted\" — ünïcode", id),
+                    ["work", "home", "side"][id % 3].to_string(),
+                    at,
+                );
+                todo.id = id;
+                todo.set_description(format!("line one\nline {}", id))
+                    .unwrap();
+                todo.tags = (0..id % 3).map(|t| format!("tag{}", t)).collect();
+                todo.estimate = (id % 2 == 0).then_some(id as f64 / 4.0);
+                todo.due = (id % 4 == 0).then(|| at + chrono::Duration::days(3));
+                todo.parent = (id > 5 && id % 5 == 0).then_some(id - 1);
+                todo.set_status(
+                    [TodoStatus::Todo, TodoStatus::Doing, TodoStatus::Done][id % 3],
+                    at,
+                );
+                todo
+            })
+            .collect()
+    }
+
+    fn scratch(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("archive-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_archive(path: &Path, version: u32, files: &[(&str, String)]) {
+        let archive = Archive {
+            format: FORMAT.to_string(),
+            version,
+            created_at: Utc::now(),
+            files: files
+                .iter()
+                .map(|(name, content)| (name.to_string(), content.clone()))
+                .collect(),
+        };
+        fs::write(path, serde_json::to_string_pretty(&archive).unwrap()).unwrap();
+    }
+
+    /// The DB as JSON without the stamps an import sets.
+    fn unstamped(db: &str) -> serde_json::Value {
+        let mut value: serde_json::Value = serde_json::from_str(db).unwrap();
+        for todo in value["todos"].as_array_mut().unwrap() {
+            todo.as_object_mut().unwrap().remove("updated_at");
+        }
+        value
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn a_merge_stamps_the_archive_on_todos_that_dont_know_their_source() {
+        let live = json!([{
+            "id": 1, "title": "here already", "description": "", "category": "home",
+            "status": "Todo", "created_at": "2024-05-01T09:00:00Z",
+        }]);
+        crate::app::tests::with_app("merge-source", live, |_| {
+            let mut todos = fixture(2);
+            todos[1].source = Some("cli".to_string());
+            let path = std::env::current_dir().unwrap().join("laptop.json");
+            write_archive(
+                &path,
+                VERSION,
+                &[("db.json", schema::db_json(&todos).unwrap())],
+            );
+
+            let merged = merge(path.to_str().unwrap()).unwrap();
+            assert_eq!(merged.todos, 2);
+            let todos = crate::read_db().unwrap();
+            let source = |title: &str| {
+                let todo = todos.iter().find(|t| t.title.starts_with(title)).unwrap();
+                todo.source.as_deref()
+            };
+            assert_eq!(source("here already"), None);
+            // todo 1 moved to a free id, the stamp follows it
+            assert_eq!(source("todo 1 "), Some("merge:laptop"));
+            assert_eq!(source("todo 2 "), Some("cli"));
+        });
+    }
+
+    #[test]
+    fn a_round_trip_gives_back_every_file_byte_for_byte() {
+        let scratch = scratch("round-trip");
+        let path = scratch.join("backup.json");
+        let db = schema::db_json(&fixture(40)).unwrap();
+        // the DB itself reads back and writes out the same
+        assert_eq!(
+            schema::db_json(&schema::parse_db(&db).unwrap()).unwrap(),
+            db
+        );
+
+        let entries = json!([{"id": 1, "category": "work", "start": "2024-05-01T09:00:00Z"}]);
+        let files = [
+            ("db.json", db.clone()),
+            ("time_entries.json", entries.to_string()),
+            ("calendar.json", "[]\n".to_string()),
+        ];
+        write_archive(&path, VERSION, &files);
+        let read = read_archive(path.to_str().unwrap()).unwrap();
+        for (name, content) in &files {
+            assert_eq!(&read.files[*name], content, "{}", name);
+        }
+
+        let dir = scratch.join("data");
+        assert_eq!(
+            import(path.to_str().unwrap(), dir.to_str().unwrap()).unwrap(),
+            3
+        );
+        let restored = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(restored("time_entries.json"), files[1].1);
+        assert_eq!(restored("calendar.json"), files[2].1);
+        assert_eq!(unstamped(&restored("db.json")), unstamped(&db));
+        let _ = fs::remove_dir_all(&scratch);
+    }
+
+    #[test]
+    fn newer_archives_and_dbs_are_refused_before_writing_anything() {
+        let scratch = scratch("too-new");
+        let path = scratch.join("backup.json");
+        let dir = scratch.join("data");
+        let (path, dir_path) = (path.to_str().unwrap(), dir.to_str().unwrap());
+
+        write_archive(Path::new(path), VERSION + 1, &[]);
+        let refused = import(path, dir_path);
+        assert!(
+            matches!(refused, Err(Error::ArchiveError(ref m)) if m.contains("update before importing"))
+        );
+
+        let newer_db = json!({"version": schema::DB_VERSION + 1, "todos": []}).to_string();
+        let notes = ("calendar.json", "[]".to_string());
+        write_archive(Path::new(path), VERSION, &[("db.json", newer_db), notes]);
+        assert!(matches!(import(path, dir_path), Err(Error::SchemaError(_))));
+        assert!(!dir.exists());
+        let _ = fs::remove_dir_all(&scratch);
+    }
+}
@@ -0,0 +1,93 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::{update_db, Error, Todo};
+
+/// `~` or `~/...` made absolute with `$HOME`; anything else as it is.
+pub fn expand(input: &str) -> PathBuf {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    match (input.strip_prefix('~'), home) {
+        (Some(""), Some(home)) => home,
+        (Some(rest), Some(home)) if rest.starts_with('/') => home.join(&rest[1..]),
+        _ => PathBuf::from(input),
+    }
+}
+
+/// The path a prompt's text refers to, if there is a file or directory
+/// there. Relative paths stay relative, just as typed.
+pub fn resolve(input: &str) -> Result<PathBuf, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("no path given".to_string());
+    }
+    let path = expand(input);
+    if !path.exists() {
+        return Err(format!("no such file: {}", path.display()));
+    }
+    Ok(path)
+}
+
+/// Adds `path` to the todo `id`, unless it is attached already. Returns
+/// false when it was.
+pub fn attach(id: usize, path: PathBuf) -> Result<bool, Error> {
+    update_db(|todos| match todos.iter_mut().find(|t| t.id == id) {
+        Some(todo) if !todo.attachments.contains(&path) => {
+            todo.attachments.push(path);
+            true
+        }
+        _ => false,
+    })
+}
+
+/// Removes the `index`th attachment of the todo `id`.
+pub fn detach(id: usize, index: usize) -> Result<Option<PathBuf>, Error> {
+    update_db(|todos| {
+        let todo = todos.iter_mut().find(|t| t.id == id)?;
+        (index < todo.attachments.len()).then(|| todo.attachments.remove(index))
+    })
+}
+
+/// Every attachment whose file is gone, with its todo id.
+pub fn dangling(todos: &[Todo]) -> Vec<(usize, &Path)> {
+    todos
+        .iter()
+        .flat_map(|t| t.attachments.iter().map(move |path| (t.id, path.as_path())))
+        .filter(|(_, path)| !path.exists())
+        .collect()
+}
+
+/// Hands `path` to the desktop's opener without waiting for it. Its output
+/// is discarded so it can't draw over the board.
+pub fn open(path: &Path) -> Result<(), String> {
+    if !path.exists() {
+        return Err(format!("{} no longer exists", path.display()));
+    }
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    let mut child = command
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("can't open {}: {}", path.display(), err))?;
+    // reaped in the background so the opener doesn't linger as a zombie
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// The file name, or the whole path when it has none.
+pub fn label(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
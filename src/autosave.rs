@@ -0,0 +1,434 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::reid::{self, IdMap};
+use crate::{Todo, DB_PATH};
+
+static INTERVAL_MS: AtomicU64 = AtomicU64::new(1000);
+static HOLD: AtomicBool = AtomicBool::new(false);
+
+/// How long after a failed save the next try comes, short of Ctrl+s.
+const RETRY: Duration = Duration::from_secs(10);
+
+/// The DB as this process last changed it, when that isn't saved yet, and
+/// when the DB was last saved.
+struct State {
+    pending: Option<Vec<Todo>>,
+    /// The todos on disk `pending` started from.
+    base: Option<Vec<Todo>>,
+    last_save: Option<Instant>,
+    /// When the DB file this process last read or wrote was modified.
+    seen: Option<SystemTime>,
+    /// Why the last save failed, until one succeeds.
+    failure: Option<String>,
+    last_failure: Option<Instant>,
+    /// The changes made since the last save.
+    changes: usize,
+    /// What the last save over another process's had to settle, until
+    /// `take_notice` reports it.
+    notice: Option<String>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    pending: None,
+    base: None,
+    last_save: None,
+    seen: None,
+    failure: None,
+    last_failure: None,
+    changes: 0,
+    notice: None,
+});
+
+pub fn set_interval(interval: Duration) {
+    INTERVAL_MS.store(interval.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// Keeps changes a save fails on in memory instead of failing them, for
+/// the TUI, which retries and offers a rescue file on quit. The CLI runs
+/// without it, so a command that couldn't save says so.
+pub fn hold_failed_saves() {
+    HOLD.store(true, Ordering::Relaxed);
+}
+
+pub fn holds_failed_saves() -> bool {
+    HOLD.load(Ordering::Relaxed)
+}
+
+fn interval() -> Duration {
+    Duration::from_millis(INTERVAL_MS.load(Ordering::Relaxed))
+}
+
+fn state() -> std::sync::MutexGuard<'static, State> {
+    STATE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The unsaved todos, which readers see in place of the file.
+pub fn pending() -> Option<Vec<Todo>> {
+    state().pending.clone()
+}
+
+/// Whether a change from `before` to `after` must be saved right away:
+/// deletions and status changes are never left waiting.
+pub fn significant(before: &[Todo], after: &[Todo]) -> bool {
+    before
+        .iter()
+        .any(|old| match after.iter().find(|t| t.id == old.id) {
+            Some(new) => new.status() != old.status(),
+            None => true,
+        })
+}
+
+/// Whether a save now stays within one per interval.
+pub fn save_allowed() -> bool {
+    state()
+        .last_save
+        .is_none_or(|saved| saved.elapsed() >= interval())
+}
+
+/// Keeps `todos` in memory until the next flush. `before` is what they
+/// were changed from, the todos on disk when nothing was pending yet.
+pub fn defer(todos: Vec<Todo>, before: &[Todo]) {
+    let mut state = state();
+    if state.pending.is_none() {
+        state.base = Some(before.to_vec());
+    }
+    state.pending = Some(todos);
+    state.changes += 1;
+}
+
+/// Records a save of everything there was.
+pub fn saved() {
+    let mut state = state();
+    state.pending = None;
+    state.base = None;
+    state.last_save = Some(Instant::now());
+    state.seen = modified();
+    state.failure = None;
+    state.changes = 0;
+}
+
+/// Records a save that failed with `err`, e.g. on a full disk or a data
+/// directory mounted read-only. The changes stay pending until a retry
+/// gets through.
+pub fn failed(err: &io::Error) {
+    let mut state = state();
+    state.failure = Some(reason(err));
+    state.last_failure = Some(Instant::now());
+}
+
+/// `err` as the status line shows it: "No space left on device (os error
+/// 28)" reads better without the code.
+fn reason(err: &io::Error) -> String {
+    let reason = err.to_string();
+    reason
+        .split(" (os error")
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Why saving fails, while it does.
+pub fn failure() -> Option<String> {
+    state().failure.clone()
+}
+
+/// Whether it is time to try a failed save again.
+pub fn retry_due() -> bool {
+    state()
+        .last_failure
+        .is_none_or(|failed| failed.elapsed() >= RETRY)
+}
+
+/// How many changes were made since the last save.
+pub fn changes() -> usize {
+    state().changes
+}
+
+fn modified() -> Option<SystemTime> {
+    fs::metadata(DB_PATH).and_then(|meta| meta.modified()).ok()
+}
+
+/// Takes the DB file as it is now as the one this process has seen.
+pub fn seen() {
+    state().seen = modified();
+}
+
+/// Whether another process wrote the DB since this one last read or
+/// wrote it, so caches built from it and unsaved changes are behind.
+pub fn stale() -> bool {
+    let state = state();
+    state.seen.is_some() && modified() != state.seen
+}
+
+/// What laying the unsaved changes over another process's save came to.
+#[derive(Debug, Default, PartialEq)]
+pub struct Rebased {
+    /// The ids both changed, where the unsaved change won.
+    pub conflicts: Vec<usize>,
+    /// Todos added here under an id the other process gave one of its own
+    /// meanwhile, by old and new id.
+    pub moved: Vec<(usize, usize)>,
+}
+
+impl Rebased {
+    /// What the status line says about it, None when nothing needs saying.
+    pub fn describe(&self) -> Option<String> {
+        let mut parts = vec![];
+        if !self.conflicts.is_empty() {
+            let ids: Vec<String> = self.conflicts.iter().map(|id| format!("#{}", id)).collect();
+            parts.push(format!(
+                "kept the unsaved changes to {} also changed on disk",
+                ids.join(", ")
+            ));
+        }
+        if !self.moved.is_empty() {
+            let moves: Vec<String> = self
+                .moved
+                .iter()
+                .map(|(old, new)| format!("#{} to #{}", old, new))
+                .collect();
+            parts.push(format!(
+                "moved new todos whose id was taken on disk: {}",
+                moves.join(", ")
+            ));
+        }
+        match parts.is_empty() {
+            true => None,
+            false => Some(parts.join("; ")),
+        }
+    }
+}
+
+/// Lays the unsaved changes over `disk`, the DB file as another process
+/// left it: a todo this process changed, added or deleted takes its
+/// change along, every other one comes from disk. A todo added here under
+/// an id also added there moves to a free id.
+pub fn rebase(disk: Vec<Todo>) -> Rebased {
+    let mut state = state();
+    state.seen = modified();
+    let (pending, base) = match (state.pending.take(), state.base.take()) {
+        (Some(pending), Some(base)) => (pending, base),
+        _ => return Rebased::default(),
+    };
+    let (merged, rebased) = merge(pending, &base, &disk);
+    state.base = Some(disk);
+    state.pending = Some(merged);
+    rebased
+}
+
+/// Keeps `notice` for `take_notice`, for a rebase no one was there to
+/// report, one right before a save.
+pub fn report(notice: String) {
+    state().notice = Some(notice);
+}
+
+pub fn take_notice() -> Option<String> {
+    state().notice.take()
+}
+
+/// `pending`, changed from `base`, laid over `disk` as `rebase` does.
+fn merge(mut pending: Vec<Todo>, base: &[Todo], disk: &[Todo]) -> (Vec<Todo>, Rebased) {
+    // ids given out on either side since `base`, and before it, are taken
+    let taken: HashSet<usize> = base.iter().chain(disk).map(|t| t.id).collect();
+    let base_ids: HashSet<usize> = base.iter().map(|t| t.id).collect();
+    let added: Vec<usize> = pending
+        .iter()
+        .map(|t| t.id)
+        .filter(|id| !base_ids.contains(id))
+        .collect();
+    let map = IdMap::plan(&added, &taken);
+    reid::rewrite_todos(&mut pending, &map);
+
+    let value = |todo: &Todo| serde_json::to_value(todo).unwrap_or_default();
+    let base: HashMap<usize, serde_json::Value> = base.iter().map(|t| (t.id, value(t))).collect();
+    let on_disk: HashMap<usize, serde_json::Value> =
+        disk.iter().map(|t| (t.id, value(t))).collect();
+    let kept: Vec<usize> = pending.iter().map(|t| t.id).collect();
+
+    let mut merged: Vec<Todo> = disk
+        .iter()
+        .filter(|t| !base.contains_key(&t.id) || kept.contains(&t.id))
+        .cloned()
+        .collect();
+    let mut conflicts = vec![];
+    for todo in pending {
+        let ours = value(&todo);
+        if base.get(&todo.id) == Some(&ours) {
+            continue;
+        }
+        if on_disk.get(&todo.id) != base.get(&todo.id) {
+            conflicts.push(todo.id);
+        }
+        match merged.iter_mut().find(|t| t.id == todo.id) {
+            Some(slot) => *slot = todo,
+            None => merged.push(todo),
+        }
+    }
+    let moved = map.pairs().collect();
+    (merged, Rebased { conflicts, moved })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn todo(id: usize, title: &str, status: &str) -> Todo {
+        serde_json::from_value(json!({
+            "id": id,
+            "title": title,
+            "description": "",
+            "category": "home",
+            "status": status,
+            "created_at": "2024-05-01T09:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    fn titles(todos: &[Todo]) -> Vec<(usize, &str)> {
+        let mut titles: Vec<_> = todos.iter().map(|t| (t.id, t.title.as_str())).collect();
+        titles.sort_unstable();
+        titles
+    }
+
+    #[test]
+    fn deletions_and_status_changes_are_significant() {
+        let before = [todo(1, "a", "Todo"), todo(2, "b", "Todo")];
+        let retitled = [todo(1, "renamed", "Todo"), todo(2, "b", "Todo")];
+        assert!(!significant(&before, &retitled));
+        let added = [
+            todo(1, "a", "Todo"),
+            todo(2, "b", "Todo"),
+            todo(3, "c", "Todo"),
+        ];
+        assert!(!significant(&before, &added));
+
+        let finished = [todo(1, "a", "Done"), todo(2, "b", "Todo")];
+        assert!(significant(&before, &finished));
+        assert!(significant(&before, &before[..1]));
+    }
+
+    #[test]
+    fn a_merge_keeps_the_changes_of_both_sides() {
+        let base = [
+            todo(1, "a", "Todo"),
+            todo(2, "b", "Todo"),
+            todo(3, "c", "Todo"),
+        ];
+        // here 1 is renamed, 3 deleted and 4 added
+        let pending = vec![
+            todo(1, "ours", "Todo"),
+            todo(2, "b", "Todo"),
+            todo(4, "d", "Todo"),
+        ];
+        // there 2 is renamed and 5 added
+        let disk = [
+            todo(1, "a", "Todo"),
+            todo(2, "theirs", "Todo"),
+            todo(3, "c", "Todo"),
+            todo(5, "e", "Todo"),
+        ];
+        let (merged, rebased) = merge(pending, &base, &disk);
+        assert_eq!(
+            titles(&merged),
+            [(1, "ours"), (2, "theirs"), (4, "d"), (5, "e")]
+        );
+        assert_eq!(rebased, Rebased::default());
+        assert_eq!(rebased.describe(), None);
+    }
+
+    #[test]
+    fn a_todo_changed_on_both_sides_is_a_conflict_ours_wins() {
+        let base = [todo(1, "a", "Todo")];
+        let pending = vec![todo(1, "ours", "Todo")];
+        let disk = [todo(1, "theirs", "Done")];
+        let (merged, rebased) = merge(pending, &base, &disk);
+        assert_eq!(titles(&merged), [(1, "ours")]);
+        assert_eq!(rebased.conflicts, [1]);
+        assert_eq!(
+            rebased.describe().unwrap(),
+            "kept the unsaved changes to #1 also changed on disk"
+        );
+
+        // deleted there but changed here comes back
+        let (merged, rebased) = merge(vec![todo(1, "ours", "Todo")], &base, &[]);
+        assert_eq!(titles(&merged), [(1, "ours")]);
+        assert_eq!(rebased.conflicts, [1]);
+    }
+
+    #[test]
+    fn a_todo_added_on_both_sides_under_one_id_moves_to_a_free_id() {
+        let base = [todo(1, "a", "Todo")];
+        let mut child = todo(3, "child", "Todo");
+        child.parent = Some(2);
+        let pending = vec![todo(1, "a", "Todo"), todo(2, "ours", "Todo"), child];
+        let disk = [todo(1, "a", "Todo"), todo(2, "theirs", "Todo")];
+
+        let (merged, rebased) = merge(pending, &base, &disk);
+        assert_eq!(
+            titles(&merged),
+            [(1, "a"), (2, "theirs"), (3, "child"), (4, "ours")]
+        );
+        // the subtask follows its parent, the disk's todo 2 keeps its id
+        assert_eq!(merged.iter().find(|t| t.id == 3).unwrap().parent, Some(4));
+        assert_eq!(rebased.moved, [(2, 4)]);
+        assert!(rebased.conflicts.is_empty());
+        assert_eq!(
+            rebased.describe().unwrap(),
+            "moved new todos whose id was taken on disk: #2 to #4"
+        );
+    }
+
+    #[test]
+    fn a_burst_of_changes_merges_as_one() {
+        let base: Vec<Todo> = (1..=5).map(|id| todo(id, "old", "Todo")).collect();
+        // here every other todo renamed, 5 deleted and three added
+        let mut pending: Vec<Todo> = base[..4].to_vec();
+        for todo in pending.iter_mut().step_by(2) {
+            todo.title = "ours".to_string();
+        }
+        pending.extend((6..=8).map(|id| todo(id, "new here", "Todo")));
+        // there 2 finished, 3 renamed too and 6 and 7 added
+        let mut disk = base.clone();
+        disk[1] = todo(2, "old", "Done");
+        disk[2] = todo(3, "theirs", "Todo");
+        disk.extend((6..=7).map(|id| todo(id, "new there", "Todo")));
+
+        let (merged, rebased) = merge(pending, &base, &disk);
+        assert_eq!(
+            titles(&merged),
+            [
+                (1, "ours"),
+                (2, "old"),
+                (3, "ours"),
+                (4, "old"),
+                (6, "new there"),
+                (7, "new there"),
+                (8, "new here"),
+                (9, "new here"),
+                (10, "new here"),
+            ]
+        );
+        assert_eq!(
+            merged.iter().find(|t| t.id == 2).unwrap().status(),
+            crate::TodoStatus::Done
+        );
+        assert_eq!(rebased.conflicts, [3]);
+        assert_eq!(rebased.moved, [(6, 9), (7, 10)]);
+    }
+
+    #[test]
+    fn a_failure_reads_without_the_os_error_code() {
+        let err = io::Error::from_raw_os_error(28);
+        assert_eq!(reason(&err), "No space left on device");
+        let err = io::Error::other("disk on fire");
+        assert_eq!(reason(&err), "disk on fire");
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{Todo, TodoStatus};
+
+const BADGE_GAP: &str = "  ";
+
+/// How much is open in one category, for the status bar.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Badge {
+    pub category: String,
+    pub doing: usize,
+    /// Every todo not done yet, Doing included.
+    pub open: usize,
+}
+
+impl Badge {
+    /// e.g. `work: 3▶/7`.
+    pub fn label(&self) -> String {
+        format!("{}: {}▶/{}", self.category, self.doing, self.open)
+    }
+}
+
+/// A badge per category with anything not done, most Doing first, then
+/// most open, then by name.
+pub fn badges(todos: &[Todo]) -> Vec<Badge> {
+    let mut counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for todo in todos.iter().filter(|t| t.status() != TodoStatus::Done) {
+        let (doing, open) = counts.entry(&todo.category).or_insert((0, 0));
+        if todo.status() == TodoStatus::Doing {
+            *doing += 1;
+        }
+        *open += 1;
+    }
+    let mut badges: Vec<Badge> = counts
+        .into_iter()
+        .map(|(category, (doing, open))| Badge {
+            category: category.to_string(),
+            doing,
+            open,
+        })
+        .collect();
+    // the sort is stable, so ties stay in name order
+    badges.sort_by(|a, b| b.doing.cmp(&a.doing).then(b.open.cmp(&a.open)));
+    badges
+}
+
+/// The badges that fit in `width` columns, in order, and how many were
+/// left out. When some are left out a `+N more` has to fit as well.
+pub fn fit(badges: &[Badge], width: usize) -> (Vec<&Badge>, usize) {
+    let labels: Vec<String> = badges.iter().map(Badge::label).collect();
+    let mut shown = 0;
+    let mut used = 0;
+    for label in &labels {
+        let needed = used + gap(shown) + label.width();
+        let rest = badges.len() - shown - 1;
+        let more = match rest {
+            0 => 0,
+            rest => BADGE_GAP.width() + more_label(rest).width(),
+        };
+        if needed + more > width {
+            break;
+        }
+        used = needed;
+        shown += 1;
+    }
+    (badges[..shown].iter().collect(), badges.len() - shown)
+}
+
+/// The badges as one line of at most `width` columns, e.g.
+/// `work: 3▶/7  home: 1▶/4  +3 more`.
+pub fn badge_line(badges: &[Badge], width: usize) -> String {
+    let (shown, hidden) = fit(badges, width);
+    let mut parts: Vec<String> = shown.iter().map(|b| b.label()).collect();
+    if hidden > 0 {
+        let more = more_label(hidden);
+        // not even one badge fits, the count alone may still
+        if parts.is_empty() && more.width() > width {
+            return String::new();
+        }
+        parts.push(more);
+    }
+    parts.join(BADGE_GAP)
+}
+
+fn gap(shown: usize) -> usize {
+    if shown == 0 {
+        0
+    } else {
+        BADGE_GAP.width()
+    }
+}
+
+fn more_label(hidden: usize) -> String {
+    format!("+{} more", hidden)
+}
+
+/// The category after `current` among the badges, for cycling the board
+/// filter through them; None after the last one, to clear the filter.
+pub fn next_category<'a>(badges: &'a [Badge], current: Option<&str>) -> Option<&'a str> {
+    let next = match current.and_then(|c| badges.iter().position(|b| b.category == c)) {
+        Some(index) => index + 1,
+        None if current.is_some() => return None,
+        None => 0,
+    };
+    badges.get(next).map(|b| b.category.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn todo(category: &str, status: TodoStatus) -> Todo {
+        let mut todo = Todo::new("title".to_string(), category.to_string(), Utc::now());
+        todo.set_status(status, Utc::now());
+        todo
+    }
+
+    fn badge(category: &str, doing: usize, open: usize) -> Badge {
+        Badge {
+            category: category.to_string(),
+            doing,
+            open,
+        }
+    }
+
+    /// `count` categories with one open todo each, `cat00` to `catNN`.
+    fn many(count: usize) -> Vec<Badge> {
+        (0..count)
+            .map(|i| badge(&format!("cat{:02}", i), 0, 1))
+            .collect()
+    }
+
+    #[test]
+    fn counts_open_work_per_category_most_doing_first() {
+        use TodoStatus::*;
+        let todos = [
+            todo("home", Todo),
+            todo("work", Doing),
+            todo("work", Todo),
+            todo("work", Done),
+            todo("errands", Todo),
+            todo("errands", Todo),
+            todo("garden", Done),
+            todo("admin", Todo),
+            todo("admin", Doing),
+        ];
+        assert_eq!(
+            badges(&todos),
+            [
+                // a tie in Doing goes to the one with more open, then by name
+                badge("admin", 1, 2),
+                badge("work", 1, 2),
+                badge("errands", 0, 2),
+                badge("home", 0, 1),
+            ]
+        );
+        assert!(badges(&[todo("garden", Done)]).is_empty());
+        assert_eq!(badge("work", 3, 7).label(), "work: 3▶/7");
+    }
+
+    #[test]
+    fn everything_shows_when_it_fits() {
+        let badges = [badge("work", 3, 7), badge("home", 1, 4)];
+        assert_eq!(badge_line(&badges, 22), "work: 3▶/7  home: 1▶/4");
+        assert_eq!(badge_line(&badges, 80), "work: 3▶/7  home: 1▶/4");
+        assert_eq!(badge_line(&[], 80), "");
+    }
+
+    #[test]
+    fn overflow_shows_the_top_ones_and_how_many_more() {
+        let badges = many(10);
+        // "cat00: 0▶/1" is 11 wide
+        assert_eq!(badge_line(&badges, 40), "cat00: 0▶/1  cat01: 0▶/1  +8 more");
+        let (shown, hidden) = fit(&badges, 40);
+        assert_eq!((shown.len(), hidden), (2, 8));
+        // one column short of all of them and the last gives way
+        let all = badge_line(&badges, 200);
+        assert!(!all.contains("more"));
+        assert!(badge_line(&badges, all.width() - 1).ends_with("cat08: 0▶/1  +1 more"));
+    }
+
+    #[test]
+    fn the_line_never_overflows_its_width() {
+        let mut badges = many(12);
+        badges.push(badge("日本語", 2, 3));
+        for width in 0..=200 {
+            let line = badge_line(&badges, width);
+            assert!(line.width() <= width, "{} at {}", line, width);
+        }
+        // too narrow for a badge, the count alone may still fit
+        assert_eq!(badge_line(&badges, 10), "+13 more");
+        assert_eq!(badge_line(&badges, 7), "");
+        assert_eq!(badge_line(&[badge("日本語", 2, 3)], 13), "日本語: 2▶/3");
+    }
+
+    #[test]
+    fn the_filter_cycles_through_the_badges_then_clears() {
+        let badges = [badge("work", 1, 2), badge("home", 0, 1)];
+        assert_eq!(next_category(&badges, None), Some("work"));
+        assert_eq!(next_category(&badges, Some("work")), Some("home"));
+        assert_eq!(next_category(&badges, Some("home")), None);
+        // a filter on a category without a badge clears too
+        assert_eq!(next_category(&badges, Some("garden")), None);
+        assert_eq!(next_category(&[], None), None);
+    }
+}
@@ -0,0 +1,450 @@
+use chrono::prelude::*;
+use crossterm::event::{KeyCode, KeyEvent};
+use std::collections::HashSet;
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::complete::{Completer, Kind, Vocabulary};
+use crate::dates::parse_due;
+use crate::estimate::parse_estimate;
+use crate::undo::UndoStack;
+use crate::widgets::{centered_rect, suggestions_line, Input, Picker};
+use crate::{update_db, Error, Priority, Todo, TodoStatus};
+
+const KEEP: &str = "— keep —";
+
+/// The fields to change across every marked todo. `None` means "keep".
+#[derive(Clone, Debug, Default)]
+pub struct TodoPatch {
+    pub category: Option<String>,
+    pub priority: Option<Option<Priority>>,
+    pub due: Option<Option<DateTime<Utc>>>,
+    pub estimate: Option<Option<f64>>,
+    pub add_tag: Option<String>,
+    pub remove_tag: Option<String>,
+    pub status: Option<TodoStatus>,
+}
+
+impl TodoPatch {
+    pub fn is_empty(&self) -> bool {
+        self.category.is_none()
+            && self.priority.is_none()
+            && self.due.is_none()
+            && self.estimate.is_none()
+            && self.add_tag.is_none()
+            && self.remove_tag.is_none()
+            && self.status.is_none()
+    }
+
+    fn apply_to(&self, todo: &mut Todo) {
+        if let Some(category) = &self.category {
+            todo.category = category.clone();
+        }
+        if let Some(priority) = self.priority {
+            todo.priority = priority;
+        }
+        if let Some(due) = self.due {
+            todo.due = due;
+        }
+        if let Some(estimate) = self.estimate {
+            todo.estimate = estimate;
+        }
+        if let Some(tag) = &self.add_tag {
+            if !todo.tags.contains(tag) {
+                todo.tags.push(tag.clone());
+            }
+        }
+        if let Some(tag) = &self.remove_tag {
+            todo.tags.retain(|t| t != tag);
+        }
+        if let Some(status) = &self.status {
+            todo.set_status(*status, Utc::now());
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BatchOutcome {
+    pub updated: usize,
+    /// Marked ids that no longer exist, e.g. deleted by hand in the DB file.
+    pub missing: Vec<usize>,
+}
+
+pub fn apply_patch(todos: &mut [Todo], ids: &HashSet<usize>, patch: &TodoPatch) -> BatchOutcome {
+    let mut outcome = BatchOutcome::default();
+    for todo in todos.iter_mut().filter(|t| ids.contains(&t.id)) {
+        patch.apply_to(todo);
+        outcome.updated += 1;
+    }
+
+    let present: HashSet<usize> = todos.iter().map(|t| t.id).collect();
+    outcome.missing = ids.difference(&present).cloned().collect();
+    outcome.missing.sort_unstable();
+    outcome
+}
+
+/// Re-reads the DB, applies the patch and saves once, as a single undo entry.
+pub fn apply_batch_edit(
+    ids: &HashSet<usize>,
+    patch: &TodoPatch,
+    undo: &mut UndoStack,
+) -> Result<BatchOutcome, Error> {
+    let (outcome, before) = update_db(|todos| {
+        let before = todos.clone();
+        (apply_patch(todos, ids, patch), before)
+    })?;
+    if outcome.updated > 0 {
+        undo.push(
+            format!("batch edit of {} todos", outcome.updated),
+            before,
+            0,
+        );
+    }
+    Ok(outcome)
+}
+
+pub enum FormAction {
+    Continue,
+    Cancel,
+    Submit(TodoPatch),
+}
+
+const FIELD_LABELS: [&str; 7] = [
+    "Category",
+    "Priority",
+    "Due date",
+    "Estimate",
+    "Add tag",
+    "Remove tag",
+    "Status",
+];
+const PRIORITIES: [Priority; 4] = [
+    Priority::Low,
+    Priority::Medium,
+    Priority::High,
+    Priority::Urgent,
+];
+const STATUSES: [TodoStatus; 3] = [TodoStatus::Todo, TodoStatus::Doing, TodoStatus::Done];
+
+/// Form opened with `Shift+E` over the marked todos. Empty inputs and the
+/// first picker option mean "keep the current value".
+pub struct BatchForm {
+    pub ids: HashSet<usize>,
+    focus: usize,
+    category: Input,
+    priority: Picker,
+    due: Input,
+    estimate: Input,
+    add_tag: Input,
+    remove_tag: Input,
+    status: Picker,
+    error: Option<String>,
+    vocabulary: Vocabulary,
+    completer: Completer,
+}
+
+impl BatchForm {
+    pub fn new(ids: HashSet<usize>, vocabulary: Vocabulary) -> BatchForm {
+        let mut priorities = vec![KEEP.to_string(), "none".to_string()];
+        priorities.extend(PRIORITIES.iter().map(|p| format!("{:?}", p)));
+        let mut statuses = vec![KEEP.to_string()];
+        statuses.extend(STATUSES.iter().map(|s| format!("{:?}", s)));
+
+        BatchForm {
+            ids,
+            focus: 0,
+            category: Input::default(),
+            priority: Picker::new(priorities),
+            due: Input::default(),
+            estimate: Input::default(),
+            add_tag: Input::default(),
+            remove_tag: Input::default(),
+            status: Picker::new(statuses),
+            error: None,
+            vocabulary,
+            completer: Completer::default(),
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> FormAction {
+        match key.code {
+            KeyCode::Esc => return FormAction::Cancel,
+            KeyCode::Enter => match self.patch() {
+                Ok(patch) if patch.is_empty() => {
+                    self.error = Some("nothing to change, every field is kept".to_string())
+                }
+                Ok(patch) => return FormAction::Submit(patch),
+                Err(err) => self.error = Some(err),
+            },
+            KeyCode::Tab | KeyCode::Down => {
+                let vocabulary = &self.vocabulary;
+                let completed = match self.focus {
+                    0 => self.completer.cycle(
+                        &mut self.category,
+                        0,
+                        vocabulary.candidates(Kind::Category),
+                    ),
+                    4 => {
+                        self.completer
+                            .cycle(&mut self.add_tag, 0, vocabulary.candidates(Kind::Tag))
+                    }
+                    5 => self.completer.cycle(
+                        &mut self.remove_tag,
+                        0,
+                        vocabulary.candidates(Kind::Tag),
+                    ),
+                    _ => false,
+                };
+                if !completed {
+                    self.completer.reset();
+                    self.focus = (self.focus + 1) % FIELD_LABELS.len();
+                }
+            }
+            KeyCode::BackTab | KeyCode::Up => {
+                self.completer.reset();
+                self.focus = (self.focus + FIELD_LABELS.len() - 1) % FIELD_LABELS.len()
+            }
+            _ => {
+                self.completer.reset();
+                match self.focus {
+                    0 => self.category.handle_key(key),
+                    1 => self.priority.handle_key(key),
+                    2 => self.due.handle_key(key),
+                    3 => self.estimate.handle_key(key),
+                    4 => self.add_tag.handle_key(key),
+                    5 => self.remove_tag.handle_key(key),
+                    _ => self.status.handle_key(key),
+                };
+            }
+        }
+        FormAction::Continue
+    }
+
+    /// Matches for the focused input when it takes a category or tag.
+    fn suggestions(&self) -> Vec<&str> {
+        let (input, kind) = match self.focus {
+            0 => (&self.category, Kind::Category),
+            4 => (&self.add_tag, Kind::Tag),
+            5 => (&self.remove_tag, Kind::Tag),
+            _ => return vec![],
+        };
+        self.completer
+            .suggestions(input, 0, self.vocabulary.candidates(kind))
+    }
+
+    fn patch(&self) -> Result<TodoPatch, String> {
+        let text = |input: &Input| {
+            let value = input.value().trim();
+            if value.is_empty() {
+                None
+            } else {
+                Some(value.to_string())
+            }
+        };
+
+        let due = match text(&self.due).as_deref() {
+            None => None,
+            Some("none") | Some("-") => Some(None),
+            Some(value) => Some(Some(parse_due(value).ok_or_else(|| {
+                format!("invalid due date '{}', use YYYY-MM-DD [HH:MM]", value)
+            })?)),
+        };
+
+        let estimate = match text(&self.estimate).as_deref() {
+            None => None,
+            Some("none") | Some("-") => Some(None),
+            Some(value) => Some(Some(parse_estimate(value).ok_or_else(|| {
+                format!(
+                    "invalid estimate '{}', use hours or minutes like 1.5 or 90m",
+                    value
+                )
+            })?)),
+        };
+
+        Ok(TodoPatch {
+            category: text(&self.category),
+            priority: match self.priority.selected() {
+                0 => None,
+                1 => Some(None),
+                i => Some(Some(PRIORITIES[i - 2])),
+            },
+            due,
+            estimate,
+            add_tag: text(&self.add_tag),
+            remove_tag: text(&self.remove_tag),
+            status: match self.status.selected() {
+                0 => None,
+                i => Some(STATUSES[i - 1]),
+            },
+        })
+    }
+
+    fn field_value(&self, field: usize) -> (&str, Option<&Input>) {
+        let input = match field {
+            0 => &self.category,
+            1 => return (self.priority.label(), None),
+            2 => &self.due,
+            3 => &self.estimate,
+            4 => &self.add_tag,
+            5 => &self.remove_tag,
+            _ => return (self.status.label(), None),
+        };
+        if input.value().is_empty() && self.focus != field {
+            (KEEP, None)
+        } else {
+            (input.value(), Some(input))
+        }
+    }
+}
+
+pub fn render_batch_form<B: Backend>(rect: &mut Frame<B>, form: &BatchForm, area: Rect) {
+    let popup = centered_rect(60, 50, area);
+    let mut lines = vec![Spans::from(vec![Span::raw("")])];
+    let mut cursor = None;
+
+    for (i, label) in FIELD_LABELS.iter().enumerate() {
+        let (value, input) = form.field_value(i);
+        let style = if i == form.focus {
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::White)
+        };
+        let prefix = format!(" {:<12}", format!("{}:", label));
+        if let (true, Some(input)) = (i == form.focus, input) {
+            cursor = Some((
+                popup.x + 1 + prefix.chars().count() as u16 + input.cursor() as u16,
+                popup.y + 1 + lines.len() as u16,
+            ));
+        }
+        lines.push(Spans::from(vec![
+            Span::styled(prefix, style),
+            Span::styled(value.to_string(), Style::default().fg(Color::White)),
+        ]));
+        let found = form.suggestions();
+        if i == form.focus && !found.is_empty() {
+            let mut line = suggestions_line(&found, form.completer.current());
+            line.0.insert(0, Span::raw(" ".repeat(13)));
+            lines.push(line);
+        }
+    }
+
+    lines.push(Spans::from(vec![Span::raw("")]));
+    lines.push(Spans::from(vec![Span::styled(
+        match &form.error {
+            Some(err) => err.clone(),
+            None => " Tab/Up/Down: field  Left/Right: pick  Enter: apply  Esc: cancel".to_string(),
+        },
+        Style::default().fg(if form.error.is_some() {
+            Color::Red
+        } else {
+            Color::DarkGray
+        }),
+    )]));
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(format!("Batch edit {} todos", form.ids.len()))
+            .border_type(BorderType::Plain),
+    );
+
+    rect.render_widget(Clear, popup);
+    rect.render_widget(paragraph, popup);
+    if let Some((x, y)) = cursor {
+        rect.set_cursor(x, y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(id: usize) -> Todo {
+        let mut todo = Todo::new(format!("todo {}", id), "work".to_string(), Utc::now());
+        todo.id = id;
+        todo.tags = vec!["old".to_string()];
+        todo
+    }
+
+    #[test]
+    fn an_empty_patch_keeps_everything() {
+        assert!(TodoPatch::default().is_empty());
+        let mut todos = vec![todo(1)];
+        let ids = [1].iter().cloned().collect();
+        let outcome = apply_patch(&mut todos, &ids, &TodoPatch::default());
+        assert_eq!(outcome.updated, 1);
+        assert_eq!(todos[0].category, "work");
+        assert_eq!(todos[0].tags, ["old"]);
+    }
+
+    #[test]
+    fn only_the_marked_todos_change() {
+        let mut todos = vec![todo(1), todo(2), todo(3)];
+        let ids = [1, 3].iter().cloned().collect();
+        let patch = TodoPatch {
+            category: Some("home".to_string()),
+            priority: Some(Some(Priority::High)),
+            estimate: Some(Some(1.5)),
+            add_tag: Some("new".to_string()),
+            remove_tag: Some("old".to_string()),
+            status: Some(TodoStatus::Done),
+            ..TodoPatch::default()
+        };
+        let outcome = apply_patch(&mut todos, &ids, &patch);
+        assert_eq!(outcome.updated, 2);
+        assert!(outcome.missing.is_empty());
+        for todo in [&todos[0], &todos[2]].iter() {
+            assert_eq!(todo.category, "home");
+            assert_eq!(todo.priority, Some(Priority::High));
+            assert_eq!(todo.estimate, Some(1.5));
+            assert_eq!(todo.tags, ["new"]);
+            assert_eq!(todo.status(), TodoStatus::Done);
+            assert!(todo.completed_at.is_some());
+        }
+        assert_eq!(todos[1].category, "work");
+        assert_eq!(todos[1].status(), TodoStatus::Todo);
+    }
+
+    #[test]
+    fn a_patch_can_clear_a_field() {
+        let mut todos = vec![todo(1)];
+        todos[0].priority = Some(Priority::Low);
+        todos[0].due = Some(Utc::now());
+        let ids = [1].iter().cloned().collect();
+        let patch = TodoPatch {
+            priority: Some(None),
+            due: Some(None),
+            add_tag: Some("old".to_string()),
+            ..TodoPatch::default()
+        };
+        apply_patch(&mut todos, &ids, &patch);
+        assert_eq!(todos[0].priority, None);
+        assert_eq!(todos[0].due, None);
+        assert_eq!(todos[0].tags, ["old"]);
+    }
+
+    #[test]
+    fn ids_gone_since_marking_are_reported() {
+        // 2 and 5 were deleted from another terminal after being marked
+        let mut todos = vec![todo(1), todo(3)];
+        let ids = [1, 2, 5].iter().cloned().collect();
+        let patch = TodoPatch {
+            category: Some("home".to_string()),
+            ..TodoPatch::default()
+        };
+        let outcome = apply_patch(&mut todos, &ids, &patch);
+        assert_eq!(outcome.updated, 1);
+        assert_eq!(outcome.missing, [2, 5]);
+        assert_eq!(todos[0].category, "home");
+        assert_eq!(todos[1].category, "work");
+    }
+}
@@ -0,0 +1,387 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use std::collections::BTreeMap;
+use std::time::Instant;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Span,
+    widgets::{BarChart, Cell, Row, Table},
+    Frame,
+};
+
+use crate::chrome::Chrome;
+use crate::timesheet::{next_month, previous_month};
+use crate::timetracking::{
+    format_duration, local_midnight, time_per_category, week_bounds, TimeEntry,
+};
+use crate::zone::Zone;
+
+/// How long the totals shown stay before a tick computes them again.
+pub const REFRESH: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// How many bars the chart has at each granularity, the current one
+/// included.
+const DAYS: i64 = 14;
+const WEEKS: i64 = 8;
+const MONTHS: usize = 6;
+
+const BAR_WIDTH: u16 = 6;
+const BAR_GAP: u16 = 1;
+
+/// What one bar of the breakdown chart covers, switched with s or
+/// `:breakdown`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Granularity {
+    Day,
+    Week,
+    Month,
+}
+
+impl Granularity {
+    pub fn parse(value: &str) -> Option<Granularity> {
+        match value {
+            "d" | "day" => Some(Granularity::Day),
+            "w" | "week" => Some(Granularity::Week),
+            "m" | "month" => Some(Granularity::Month),
+            _ => None,
+        }
+    }
+
+    /// The one after, back to days after months.
+    pub fn next(self) -> Granularity {
+        match self {
+            Granularity::Day => Granularity::Week,
+            Granularity::Week => Granularity::Month,
+            Granularity::Month => Granularity::Day,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Granularity::Day => "day",
+            Granularity::Week => "week",
+            Granularity::Month => "month",
+        }
+    }
+}
+
+/// The tracked time of one category today, this week and this month.
+pub struct CategoryTotals {
+    pub category: String,
+    pub today: Duration,
+    pub week: Duration,
+    pub month: Duration,
+}
+
+/// The tracked time per category and per day, week and month, worked out
+/// when the TimeTracking tab opens and again every `REFRESH` rather than
+/// each frame.
+/// Entries over a boundary count on both sides of it, each period only
+/// getting its own part.
+pub struct Breakdown {
+    pub categories: Vec<CategoryTotals>,
+    /// The bars of each granularity, oldest first: the period's label and
+    /// its tracked time.
+    days: Vec<(String, Duration)>,
+    weeks: Vec<(String, Duration)>,
+    months: Vec<(String, Duration)>,
+    computed_at: Instant,
+}
+
+fn total(
+    entries: &[TimeEntry],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Duration {
+    time_per_category(entries, from, to, now)
+        .values()
+        .fold(Duration::zero(), |sum, total| sum + *total)
+}
+
+impl Breakdown {
+    /// The breakdown of `entries` at `now` in its zone, running entries
+    /// counting up to `now`.
+    pub fn compute(entries: &[TimeEntry], now: DateTime<Zone>, week_start: Weekday) -> Breakdown {
+        let zone = now.timezone();
+        let today = now.date_naive();
+        let utc = now.with_timezone(&Utc);
+        let day_bounds = |first: NaiveDate, days: i64| {
+            (
+                local_midnight(first, zone),
+                local_midnight(first + Duration::days(days), zone),
+            )
+        };
+        let month_bounds = |first: NaiveDate| {
+            (
+                local_midnight(first, zone),
+                local_midnight(next_month(first), zone),
+            )
+        };
+
+        let (today_from, today_to) = day_bounds(today, 1);
+        let (week_from, week_to) = week_bounds(now, week_start);
+        let this_month = today.with_day(1).expect("every month has a first day");
+        let (month_from, month_to) = month_bounds(this_month);
+
+        let mut categories: BTreeMap<String, CategoryTotals> = BTreeMap::new();
+        let periods = [
+            time_per_category(entries, today_from, today_to, utc),
+            time_per_category(entries, week_from, week_to, utc),
+            time_per_category(entries, month_from, month_to, utc),
+        ];
+        for (period, totals) in periods.iter().enumerate() {
+            for (category, time) in totals {
+                let row = categories
+                    .entry(category.clone())
+                    .or_insert_with(|| CategoryTotals {
+                        category: category.clone(),
+                        today: Duration::zero(),
+                        week: Duration::zero(),
+                        month: Duration::zero(),
+                    });
+                match period {
+                    0 => row.today = *time,
+                    1 => row.week = *time,
+                    _ => row.month = *time,
+                }
+            }
+        }
+
+        let days = (0..DAYS)
+            .rev()
+            .map(|ago| {
+                let day = today - Duration::days(ago);
+                let (from, to) = day_bounds(day, 1);
+                (
+                    day.format("%a %d").to_string(),
+                    total(entries, from, to, utc),
+                )
+            })
+            .collect();
+        let first_day = week_from.with_timezone(&zone).date_naive();
+        let weeks = (0..WEEKS)
+            .rev()
+            .map(|ago| {
+                let first = first_day - Duration::days(7 * ago);
+                let (from, to) = day_bounds(first, 7);
+                (
+                    first.format("%b %d").to_string(),
+                    total(entries, from, to, utc),
+                )
+            })
+            .collect();
+        let mut months: Vec<(String, Duration)> =
+            std::iter::successors(Some(this_month), |month| Some(previous_month(*month)))
+                .take(MONTHS)
+                .map(|first| {
+                    let (from, to) = month_bounds(first);
+                    (
+                        first.format("%b %y").to_string(),
+                        total(entries, from, to, utc),
+                    )
+                })
+                .collect();
+        months.reverse();
+
+        Breakdown {
+            categories: categories
+                .into_values()
+                .filter(|row| {
+                    row.today > Duration::zero()
+                        || row.week > Duration::zero()
+                        || row.month > Duration::zero()
+                })
+                .collect(),
+            days,
+            weeks,
+            months,
+            computed_at: Instant::now(),
+        }
+    }
+
+    /// Whether the totals are older than `REFRESH` at `now`.
+    pub fn is_stale(&self, now: Instant) -> bool {
+        now.duration_since(self.computed_at) >= REFRESH
+    }
+
+    fn bars(&self, granularity: Granularity) -> &[(String, Duration)] {
+        match granularity {
+            Granularity::Day => &self.days,
+            Granularity::Week => &self.weeks,
+            Granularity::Month => &self.months,
+        }
+    }
+}
+
+/// The time per category next to the TimeTracking tab's entries, above a
+/// chart of the latest periods that fit.
+pub fn render_breakdown<B: Backend>(
+    rect: &mut Frame<B>,
+    breakdown: &Breakdown,
+    granularity: Granularity,
+    chrome: Chrome,
+    area: Rect,
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(breakdown.categories.len() as u16 + 5),
+                Constraint::Min(6),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let sum = |pick: fn(&CategoryTotals) -> Duration| {
+        breakdown
+            .categories
+            .iter()
+            .fold(Duration::zero(), |sum, row| sum + pick(row))
+    };
+    let mut rows: Vec<Row> = breakdown
+        .categories
+        .iter()
+        .map(|row| {
+            Row::new(vec![
+                Cell::from(Span::raw(row.category.clone())),
+                Cell::from(Span::raw(format_duration(row.today))),
+                Cell::from(Span::raw(format_duration(row.week))),
+                Cell::from(Span::raw(format_duration(row.month))),
+            ])
+        })
+        .collect();
+    rows.push(Row::new(vec![
+        Cell::from(Span::styled("Total", bold)),
+        Cell::from(Span::styled(format_duration(sum(|row| row.today)), bold)),
+        Cell::from(Span::styled(format_duration(sum(|row| row.week)), bold)),
+        Cell::from(Span::styled(format_duration(sum(|row| row.month)), bold)),
+    ]));
+    let table = Table::new(rows)
+        .header(Row::new(
+            ["Category", "Today", "This week", "This month"]
+                .iter()
+                .map(|title| Cell::from(Span::styled(*title, bold)))
+                .collect::<Vec<_>>(),
+        ))
+        .block(chrome.block().title("Time per category"))
+        .widths(&[
+            Constraint::Percentage(40), // category
+            Constraint::Percentage(20), // today
+            Constraint::Percentage(20), // week
+            Constraint::Percentage(20), // month
+        ]);
+    rect.render_widget(table, chunks[0]);
+
+    // the chart cuts off on the right, so the current period comes last
+    let fit = (chunks[1].width.saturating_sub(2) + BAR_GAP) / (BAR_WIDTH + BAR_GAP);
+    let all = breakdown.bars(granularity);
+    let bars: Vec<(&str, u64)> = all[all.len().saturating_sub(fit.max(1) as usize)..]
+        .iter()
+        .map(|(label, time)| (label.as_str(), time.num_minutes().max(0) as u64))
+        .collect();
+    let chart = BarChart::default()
+        .block(chrome.block().title(format!(
+            "Minutes per {} (s: day, week, month)",
+            granularity.name()
+        )))
+        .data(&bars)
+        .bar_width(BAR_WIDTH)
+        .bar_gap(BAR_GAP)
+        .bar_style(Style::default().fg(Color::Cyan))
+        .value_style(Style::default().fg(Color::Black).bg(Color::Cyan))
+        .label_style(Style::default().fg(Color::White));
+    rect.render_widget(chart, chunks[1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn berlin() -> Zone {
+        Zone::parse("Europe/Berlin").unwrap()
+    }
+
+    fn local(value: &str) -> DateTime<Zone> {
+        let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap();
+        berlin().from_local_datetime(&naive).unwrap()
+    }
+
+    fn entry(id: usize, category: &str, start: &str, end: &str) -> TimeEntry {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "category": category,
+            "start": local(start).with_timezone(&Utc),
+            "end": local(end).with_timezone(&Utc),
+        }))
+        .unwrap()
+    }
+
+    fn minutes(bars: &[(String, Duration)], last: usize) -> Vec<(&str, i64)> {
+        bars[bars.len() - last..]
+            .iter()
+            .map(|(label, time)| (label.as_str(), time.num_minutes()))
+            .collect()
+    }
+
+    #[test]
+    fn entries_over_midnight_split_at_the_day_boundary() {
+        let entries = [
+            entry(1, "dev", "2024-05-14 22:30", "2024-05-15 01:00"),
+            // over the end of April
+            entry(2, "dev", "2024-04-30 23:00", "2024-05-01 01:00"),
+            // over the start of the week, on a Monday
+            entry(3, "ops", "2024-05-12 23:00", "2024-05-13 00:30"),
+        ];
+        let breakdown = Breakdown::compute(&entries, local("2024-05-15 10:00"), Weekday::Mon);
+
+        let totals: Vec<(&str, i64, i64, i64)> = breakdown
+            .categories
+            .iter()
+            .map(|row| {
+                (
+                    row.category.as_str(),
+                    row.today.num_minutes(),
+                    row.week.num_minutes(),
+                    row.month.num_minutes(),
+                )
+            })
+            .collect();
+        assert_eq!(totals, [("dev", 60, 150, 210), ("ops", 0, 30, 90)]);
+
+        assert_eq!(
+            minutes(&breakdown.days, 4),
+            [
+                ("Sun 12", 60),
+                ("Mon 13", 30),
+                ("Tue 14", 90),
+                ("Wed 15", 60)
+            ]
+        );
+        assert_eq!(
+            minutes(&breakdown.weeks, 2),
+            [("May 06", 60), ("May 13", 180)]
+        );
+        assert_eq!(
+            minutes(&breakdown.months, 2),
+            [("Apr 24", 60), ("May 24", 300)]
+        );
+        assert_eq!(breakdown.days.len(), DAYS as usize);
+    }
+
+    #[test]
+    fn a_running_entry_counts_up_to_now() {
+        let mut running = entry(1, "dev", "2024-05-14 23:00", "2024-05-15 00:00");
+        running.end = None;
+        let breakdown = Breakdown::compute(&[running], local("2024-05-15 00:45"), Weekday::Mon);
+        assert_eq!(
+            minutes(&breakdown.days, 2),
+            [("Tue 14", 60), ("Wed 15", 45)]
+        );
+        assert_eq!(breakdown.categories[0].today.num_minutes(), 45);
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+
+use crate::cli;
+use crate::config;
+use crate::filter::Filter;
+use crate::reid::{self, IdMap};
+use crate::txn::{transaction, Txn};
+use crate::undo::UndoStack;
+use crate::zone;
+use crate::{read_db, Error, Priority, Todo};
+
+const USAGE: &str = "usage: bulk --filter EXPR --action archive|delete|set-priority P|add-tag TAG \
+                     [--dry-run] [--yes]";
+pub const ARCHIVED_TODOS_PATH: &str = "./data/archived_todos.json";
+/// Archiving or deleting more todos than this needs `--yes`.
+const CONFIRM_ABOVE: usize = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Action {
+    /// Moves the todos out of the DB into `ARCHIVED_TODOS_PATH`.
+    Archive,
+    Delete,
+    SetPriority(Option<Priority>),
+    AddTag(String),
+}
+
+impl Action {
+    fn destructive(&self) -> bool {
+        matches!(self, Action::Archive | Action::Delete)
+    }
+
+    fn done(&self) -> &'static str {
+        match self {
+            Action::Archive => "archived",
+            Action::Delete => "deleted",
+            Action::SetPriority(_) => "changed the priority of",
+            Action::AddTag(_) => "tagged",
+        }
+    }
+
+    /// Applies a non destructive action to one todo.
+    fn apply(&self, todo: &mut Todo) {
+        match self {
+            Action::SetPriority(priority) => todo.priority = *priority,
+            Action::AddTag(tag) if !todo.tags.contains(tag) => todo.tags.push(tag.clone()),
+            _ => {}
+        }
+    }
+}
+
+/// Moves the todos `ids` out of the transaction's todos into its archive.
+fn archive_from(txn: &mut Txn, ids: &[usize]) -> Result<(), Error> {
+    let moved: Vec<Todo> = txn
+        .todos
+        .iter()
+        .filter(|t| ids.contains(&t.id))
+        .cloned()
+        .collect();
+    txn.archived()?.extend(moved);
+    txn.todos.retain(|t| !ids.contains(&t.id));
+    Ok(())
+}
+
+/// Moves the todos `ids` out of the DB into the archive, as `bulk --action
+/// archive` does, both saved together, as one undo step. Returns how many
+/// there were.
+pub fn archive(ids: &[usize], undo: &mut UndoStack) -> Result<usize, Error> {
+    let (before, archived, count) = transaction(|txn| {
+        let before = txn.todos.clone();
+        let archived = txn.archived()?.clone();
+        archive_from(txn, ids)?;
+        let count = before.len() - txn.todos.len();
+        Ok((before, archived, count))
+    })?;
+    if count > 0 {
+        undo.push_archive("archive", before, archived, 0);
+    }
+    Ok(count)
+}
+
+/// The todos `bulk --action archive` moved out of the DB.
+pub fn read_archived() -> Result<Vec<Todo>, Error> {
+    match fs::read_to_string(ARCHIVED_TODOS_PATH) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(Error::ReadFileError(ARCHIVED_TODOS_PATH.to_string(), err)),
+    }
+}
+
+/// Moves archived todo `id` back into the DB, both saved together, as one
+/// undo step, and returns its id there, a new one when a todo added since
+/// took its old one. None when it isn't archived.
+pub fn unarchive(id: usize, undo: &mut UndoStack) -> Result<Option<usize>, Error> {
+    let unarchived = transaction(|txn| {
+        let before = txn.todos.clone();
+        let archived = txn.archived()?;
+        let snapshot = archived.clone();
+        let index = match archived.iter().position(|t| t.id == id) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let todo = archived.remove(index);
+        // the archived ids count as taken, so a later unarchive can't collide
+        let mut taken: HashSet<usize> = archived.iter().map(|t| t.id).collect();
+        taken.extend(txn.todos.iter().map(|t| t.id));
+        let map = IdMap::plan(&[todo.id], &taken);
+        let mut restored = [todo];
+        reid::rewrite_todos(&mut restored, &map);
+        let [todo] = restored;
+        let restored = todo.id;
+        txn.todos.push(todo);
+        Ok(Some((before, snapshot, restored)))
+    })?;
+    Ok(unarchived.map(|(before, archived, restored)| {
+        undo.push_archive("unarchive", before, archived, restored);
+        restored
+    }))
+}
+
+/// Runs `bulk --filter EXPR --action ACTION [--dry-run] [--yes]` and
+/// returns the process exit code. The filter takes everything `:filter`
+/// does, plain terms or an expression.
+pub fn run(args: &[String]) -> i32 {
+    let mut filter = None;
+    let mut action = None;
+    let mut dry_run = false;
+    let mut yes = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--yes" => yes = true,
+            "--filter" => match args.next() {
+                Some(spec) => match Filter::parse(spec) {
+                    Ok(parsed) => filter = Some(parsed),
+                    Err(err) => return cli::usage(format!("invalid --filter: {}", err)),
+                },
+                None => return cli::usage("--filter needs a value"),
+            },
+            "--action" => {
+                action = Some(match args.next().map(String::as_str) {
+                    Some("archive") => Action::Archive,
+                    Some("delete") => Action::Delete,
+                    Some("set-priority") => match args.next().map(String::as_str) {
+                        Some("none") => Action::SetPriority(None),
+                        Some(name) => match Priority::parse(name) {
+                            Some(priority) => Action::SetPriority(Some(priority)),
+                            None => {
+                                return cli::usage(format!(
+                                    "invalid priority '{}', use low, medium, high, urgent or none",
+                                    name
+                                ))
+                            }
+                        },
+                        None => return cli::usage("set-priority needs a priority"),
+                    },
+                    Some("add-tag") => match args.next() {
+                        Some(tag) if !tag.is_empty() => Action::AddTag(tag.clone()),
+                        _ => return cli::usage("add-tag needs a tag"),
+                    },
+                    _ => return cli::usage(USAGE),
+                })
+            }
+            _ => return cli::usage(USAGE),
+        }
+    }
+    let (mut filter, action) = match (filter, action) {
+        (Some(filter), Some(action)) => (filter, action),
+        _ => return cli::usage(USAGE),
+    };
+    match config::load() {
+        Ok(config) => filter.week_start = Some(config.week_start),
+        Err(err) => return cli::fail(&err),
+    }
+
+    let now = zone::now();
+    let matched = |todos: &[Todo]| -> Vec<usize> {
+        todos
+            .iter()
+            .filter(|t| filter.matches(t, todos, now))
+            .map(|t| t.id)
+            .collect()
+    };
+    let todos = match read_db() {
+        Ok(todos) => todos,
+        Err(err) => return cli::fail(&err),
+    };
+    let ids = matched(&todos);
+    if dry_run {
+        for todo in todos.iter().filter(|t| ids.contains(&t.id)) {
+            println!(
+                "{:>4} {:<6} {}",
+                todo.id,
+                format!("{:?}", todo.status()),
+                todo.title
+            );
+        }
+        println!("{} todos would be {}", ids.len(), action.done());
+        return 0;
+    }
+    if action.destructive() && ids.len() > CONFIRM_ABOVE && !yes {
+        return cli::usage(format!(
+            "{} todos match, pass --yes to {} them or --dry-run to list them",
+            ids.len(),
+            if action == Action::Archive {
+                "archive"
+            } else {
+                "delete"
+            }
+        ));
+    }
+
+    // matched again under the lock in case the DB changed since the count
+    let changed = transaction(|txn| {
+        let ids = matched(&txn.todos);
+        if action == Action::Archive {
+            archive_from(txn, &ids)?;
+        } else if action.destructive() {
+            txn.todos.retain(|t| !ids.contains(&t.id));
+        } else {
+            for todo in txn.todos.iter_mut().filter(|t| ids.contains(&t.id)) {
+                action.apply(todo);
+            }
+        }
+        Ok(ids.len())
+    });
+    match changed {
+        Ok(count) => {
+            println!("{} {} todos", action.done(), count);
+            0
+        }
+        Err(err) => cli::fail(&err),
+    }
+}
@@ -0,0 +1,290 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+
+use crate::config::Config;
+use crate::{write_atomic, Error};
+
+const CALENDAR_PATH: &str = "./data/calendar.json";
+
+/// How far the working day searches look before giving up, so a calendar
+/// marking every day off can't hang them.
+const SEARCH_DAYS: i64 = 366;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum OffKind {
+    Holiday,
+    Vacation,
+}
+
+impl OffKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            OffKind::Holiday => "holiday",
+            OffKind::Vacation => "vacation",
+        }
+    }
+}
+
+/// Days off from `from` through `to`, both included.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DaysOff {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub kind: OffKind,
+}
+
+pub fn read_calendar() -> Result<Vec<DaysOff>, Error> {
+    match fs::read_to_string(CALENDAR_PATH) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn write_calendar(calendar: &[DaysOff]) -> Result<(), Error> {
+    write_atomic(CALENDAR_PATH, &serde_json::to_string_pretty(calendar)?)
+}
+
+/// Reads `YYYY-MM-DD..YYYY-MM-DD`, or a single `YYYY-MM-DD` for one day.
+pub fn parse_range(spec: &str) -> Option<(NaiveDate, NaiveDate)> {
+    let date = |value: &str| NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").ok();
+    let (from, to) = match spec.split_once("..") {
+        Some((from, to)) => (date(from)?, date(to)?),
+        None => (date(spec)?, date(spec)?),
+    };
+    (from <= to).then_some((from, to))
+}
+
+/// What `date` is off as. Vacation wins where ranges of both kinds overlap.
+pub fn day_off(calendar: &[DaysOff], date: NaiveDate) -> Option<OffKind> {
+    let mut kinds = calendar
+        .iter()
+        .filter(|off| off.from <= date && date <= off.to)
+        .map(|off| off.kind);
+    let first = kinds.next()?;
+    Some(if kinds.any(|kind| kind == OffKind::Vacation) {
+        OffKind::Vacation
+    } else {
+        first
+    })
+}
+
+/// A weekday with a daily target, or Monday to Friday when no targets are
+/// configured, that isn't a day off.
+pub fn is_working_day(config: &Config, calendar: &[DaysOff], date: NaiveDate) -> bool {
+    let weekday = date.weekday();
+    let scheduled = if config.daily_targets.is_empty() {
+        !matches!(weekday, Weekday::Sat | Weekday::Sun)
+    } else {
+        config.daily_targets.get(&weekday).is_some_and(|h| *h > 0.0)
+    };
+    scheduled && day_off(calendar, date).is_none()
+}
+
+/// The first working day after `date`.
+pub fn next_working_day(config: &Config, calendar: &[DaysOff], date: NaiveDate) -> NaiveDate {
+    (1..=SEARCH_DAYS)
+        .map(|days| date + Duration::days(days))
+        .find(|day| is_working_day(config, calendar, *day))
+        .unwrap_or(date + Duration::days(1))
+}
+
+/// The last working day before `date`, e.g. what "yesterday" means at a
+/// Monday standup.
+pub fn previous_working_day(config: &Config, calendar: &[DaysOff], date: NaiveDate) -> NaiveDate {
+    (1..=SEARCH_DAYS)
+        .map(|days| date - Duration::days(days))
+        .find(|day| is_working_day(config, calendar, *day))
+        .unwrap_or(date - Duration::days(1))
+}
+
+/// Working days after `from` up to and including `to`, 0 when `to` isn't
+/// later.
+pub fn working_days_between(
+    config: &Config,
+    calendar: &[DaysOff],
+    from: NaiveDate,
+    to: NaiveDate,
+) -> i64 {
+    from.iter_days()
+        .skip(1)
+        .take_while(|day| *day <= to)
+        .filter(|day| is_working_day(config, calendar, *day))
+        .count() as i64
+}
+
+/// Marks `from` through `to` as `kind`.
+pub fn add(kind: OffKind, from: NaiveDate, to: NaiveDate) -> Result<(), Error> {
+    let mut calendar = read_calendar()?;
+    calendar.push(DaysOff { from, to, kind });
+    calendar.sort_by_key(|off| off.from);
+    write_calendar(&calendar)
+}
+
+/// Clears every day off from `from` through `to`, cutting ranges that stick
+/// out on either side. Returns how many days were off.
+pub fn remove(from: NaiveDate, to: NaiveDate) -> Result<usize, Error> {
+    let calendar = read_calendar()?;
+    let cleared = from
+        .iter_days()
+        .take_while(|day| *day <= to)
+        .filter(|day| day_off(&calendar, *day).is_some())
+        .count();
+    write_calendar(&clear(calendar, from, to))?;
+    Ok(cleared)
+}
+
+/// `calendar` without the days from `from` through `to`.
+fn clear(calendar: Vec<DaysOff>, from: NaiveDate, to: NaiveDate) -> Vec<DaysOff> {
+    let mut kept = vec![];
+    for off in calendar {
+        if off.to < from || off.from > to {
+            kept.push(off);
+            continue;
+        }
+        if off.from < from {
+            kept.push(DaysOff {
+                to: from - Duration::days(1),
+                ..off.clone()
+            });
+        }
+        if off.to > to {
+            kept.push(DaysOff {
+                from: to + Duration::days(1),
+                ..off
+            });
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn off(from: &str, to: &str, kind: OffKind) -> DaysOff {
+        DaysOff {
+            from: date(from),
+            to: date(to),
+            kind,
+        }
+    }
+
+    /// Christmas break across the new year, with a holiday inside it.
+    fn winter() -> Vec<DaysOff> {
+        vec![
+            off("2024-12-23", "2025-01-02", OffKind::Vacation),
+            off("2024-12-25", "2024-12-26", OffKind::Holiday),
+            off("2025-01-06", "2025-01-06", OffKind::Holiday),
+        ]
+    }
+
+    #[test]
+    fn parses_ranges_and_single_days() {
+        assert_eq!(
+            parse_range("2024-12-23..2025-01-02"),
+            Some((date("2024-12-23"), date("2025-01-02")))
+        );
+        assert_eq!(
+            parse_range("2024-12-25"),
+            Some((date("2024-12-25"), date("2024-12-25")))
+        );
+        assert_eq!(parse_range("2025-01-02..2024-12-23"), None);
+        assert_eq!(parse_range("2024-12-23.."), None);
+        assert_eq!(parse_range("christmas"), None);
+    }
+
+    #[test]
+    fn vacation_wins_where_ranges_overlap() {
+        let calendar = winter();
+        assert_eq!(
+            day_off(&calendar, date("2024-12-25")),
+            Some(OffKind::Vacation)
+        );
+        assert_eq!(
+            day_off(&calendar, date("2025-01-06")),
+            Some(OffKind::Holiday)
+        );
+        assert_eq!(day_off(&calendar, date("2025-01-03")), None);
+        // a holiday listed after a vacation is still off as vacation
+        let reversed: Vec<_> = calendar.into_iter().rev().collect();
+        assert_eq!(
+            day_off(&reversed, date("2024-12-26")),
+            Some(OffKind::Vacation)
+        );
+    }
+
+    #[test]
+    fn working_days_skip_weekends_and_days_off_across_the_year() {
+        let config = Config::default();
+        let calendar = winter();
+        // Friday before the break, Friday after it
+        assert_eq!(
+            next_working_day(&config, &calendar, date("2024-12-20")),
+            date("2025-01-03")
+        );
+        assert_eq!(
+            previous_working_day(&config, &calendar, date("2025-01-03")),
+            date("2024-12-20")
+        );
+        // the Monday holiday makes "yesterday" on Tuesday the Friday before
+        assert_eq!(
+            previous_working_day(&config, &calendar, date("2025-01-07")),
+            date("2025-01-03")
+        );
+        assert_eq!(
+            working_days_between(&config, &calendar, date("2024-12-20"), date("2025-01-07")),
+            2
+        );
+        assert_eq!(
+            working_days_between(&config, &[], date("2024-12-20"), date("2025-01-07")),
+            12
+        );
+        assert_eq!(
+            working_days_between(&config, &calendar, date("2025-01-07"), date("2024-12-20")),
+            0
+        );
+    }
+
+    #[test]
+    fn daily_targets_decide_the_working_weekdays() {
+        let mut config = Config::default();
+        config.daily_targets.insert(Weekday::Sat, 4.0);
+        config.daily_targets.insert(Weekday::Mon, 0.0);
+        assert!(is_working_day(&config, &[], date("2025-01-04")));
+        assert!(!is_working_day(&config, &[], date("2025-01-06")));
+        assert!(!is_working_day(&config, &[], date("2025-01-07")));
+    }
+
+    #[test]
+    fn a_calendar_of_nothing_but_days_off_gives_up() {
+        let config = Config::default();
+        let always = [off("2000-01-01", "2100-01-01", OffKind::Vacation)];
+        assert_eq!(
+            next_working_day(&config, &always, date("2025-01-01")),
+            date("2025-01-02")
+        );
+    }
+
+    #[test]
+    fn clearing_cuts_ranges_that_stick_out() {
+        let kept = clear(winter(), date("2024-12-25"), date("2024-12-31"));
+        assert_eq!(
+            kept,
+            [
+                off("2024-12-23", "2024-12-24", OffKind::Vacation),
+                off("2025-01-01", "2025-01-02", OffKind::Vacation),
+                off("2025-01-06", "2025-01-06", OffKind::Holiday),
+            ]
+        );
+        assert!(clear(winter(), date("2024-12-01"), date("2025-01-31")).is_empty());
+    }
+}
@@ -0,0 +1,406 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::timetracking::{local_midnight, week_bounds};
+use crate::undo::UndoStack;
+use crate::widgets::centered_rect;
+use crate::zone::{self, Zone};
+use crate::{update_db, Error, Priority, Todo, TodoStatus};
+
+/// The todos `:carryover` goes through, none of them done: those due last
+/// week by their own due date and not deferred past `now`, and those a
+/// past carry-over deferred until `now` or before, which resurface
+/// whenever they were due. A todo rescheduled by hand has left last
+/// week's range, so it isn't offered again.
+pub fn candidates(todos: &[Todo], now: DateTime<Zone>, week_start: Weekday) -> Vec<&Todo> {
+    let (this_week, _) = week_bounds(now, week_start);
+    let last_week = this_week - Duration::days(7);
+    let mut candidates: Vec<&Todo> = todos
+        .iter()
+        .filter(|t| t.status() != TodoStatus::Done)
+        .filter(|t| match t.deferred_until {
+            Some(until) => until <= now,
+            None => t.due.is_some_and(|due| due >= last_week && due < this_week),
+        })
+        .collect();
+    candidates.sort_by_key(|t| (t.due, t.id));
+    candidates
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Decision {
+    /// Due on that day of this week, at the time it was due last week.
+    Reschedule(NaiveDate),
+    /// Left out of the carry-over until next week.
+    Defer,
+    /// Low priority and no due date.
+    Deprioritize,
+    /// Deleted.
+    Drop,
+}
+
+impl Decision {
+    fn label(&self) -> String {
+        match self {
+            Decision::Reschedule(day) => format!("reschedule to {}", day.format("%a %Y-%m-%d")),
+            Decision::Defer => "defer".to_string(),
+            Decision::Deprioritize => "deprioritize".to_string(),
+            Decision::Drop => "drop".to_string(),
+        }
+    }
+
+    /// Applies the decision to `todo`; all but another deferral end the
+    /// one it may have had.
+    fn apply(&self, todo: &mut Todo, next_week: DateTime<Utc>) {
+        todo.deferred_until = None;
+        match self {
+            Decision::Reschedule(day) => todo.due = todo.due.map(|due| moved_to(due, *day)),
+            Decision::Defer => todo.deferred_until = Some(next_week),
+            Decision::Deprioritize => {
+                todo.priority = Some(Priority::Low);
+                todo.due = None;
+            }
+            Decision::Drop => {}
+        }
+    }
+}
+
+/// `due` on `day` at the same local time, or the end of `day` when that
+/// time doesn't exist there.
+fn moved_to(due: DateTime<Utc>, day: NaiveDate) -> DateTime<Utc> {
+    let zone = zone::current();
+    let time = due.with_timezone(&zone).time();
+    zone.from_local_datetime(&day.and_time(time))
+        .earliest()
+        .map(|moved| moved.with_timezone(&Utc))
+        .unwrap_or_else(|| local_midnight(day + Duration::days(1), zone) - Duration::seconds(1))
+}
+
+pub enum CarryoverAction {
+    Continue,
+    Cancel,
+    Apply,
+}
+
+/// What `:carryover` applied, for the status bar.
+#[derive(Debug, Default)]
+pub struct CarryoverOutcome {
+    pub rescheduled: usize,
+    pub deferred: usize,
+    pub deprioritized: usize,
+    pub dropped: usize,
+    pub kept: usize,
+    /// Todos deleted by someone else while they were being triaged.
+    pub missing: usize,
+}
+
+impl CarryoverOutcome {
+    pub fn summary(&self) -> String {
+        let mut parts = vec![];
+        for (count, label) in [
+            (self.rescheduled, "rescheduled"),
+            (self.deferred, "deferred"),
+            (self.deprioritized, "deprioritized"),
+            (self.dropped, "dropped"),
+            (self.kept, "kept"),
+            (self.missing, "gone meanwhile"),
+        ] {
+            if count > 0 {
+                parts.push(format!("{} {}", count, label));
+            }
+        }
+        format!("carry-over: {}", parts.join(", "))
+    }
+
+    pub fn changed(&self) -> usize {
+        self.rescheduled + self.deferred + self.deprioritized + self.dropped
+    }
+}
+
+/// The `:carryover` walk through last week's unfinished todos, one per
+/// screen. Nothing is saved until Enter on the closing summary, which
+/// writes every decision at once.
+pub struct Carryover {
+    todos: Vec<Todo>,
+    /// None keeps the todo as it is.
+    decisions: Vec<Option<Decision>>,
+    /// The todo on screen, `todos.len()` for the summary.
+    index: usize,
+    /// The days of this week, starting with the configured first day.
+    days: Vec<NaiveDate>,
+    today: NaiveDate,
+    next_week: DateTime<Utc>,
+    error: Option<String>,
+}
+
+impl Carryover {
+    pub fn new(todos: Vec<Todo>, now: DateTime<Zone>, week_start: Weekday) -> Carryover {
+        let (this_week, next_week) = week_bounds(now, week_start);
+        let first = this_week.with_timezone(&zone::current()).date_naive();
+        Carryover {
+            decisions: vec![None; todos.len()],
+            todos,
+            index: 0,
+            days: (0..7).map(|i| first + Duration::days(i)).collect(),
+            today: now.date_naive(),
+            next_week,
+            error: None,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> CarryoverAction {
+        self.error = None;
+        if self.index == self.todos.len() {
+            match key.code {
+                KeyCode::Esc => return CarryoverAction::Cancel,
+                KeyCode::Enter => return CarryoverAction::Apply,
+                KeyCode::Left | KeyCode::Backspace => self.index = self.index.saturating_sub(1),
+                _ => {}
+            }
+            return CarryoverAction::Continue;
+        }
+        let decision = match key.code {
+            KeyCode::Esc => return CarryoverAction::Cancel,
+            KeyCode::Left | KeyCode::Backspace => {
+                self.index = self.index.saturating_sub(1);
+                return CarryoverAction::Continue;
+            }
+            KeyCode::Char(c @ '1'..='7') => {
+                let day = self.days[c as usize - '1' as usize];
+                if day < self.today {
+                    self.error = Some(format!("{} is already over", day.format("%a")));
+                    return CarryoverAction::Continue;
+                }
+                Some(Decision::Reschedule(day))
+            }
+            KeyCode::Char('d') => Some(Decision::Defer),
+            KeyCode::Char('p') => Some(Decision::Deprioritize),
+            KeyCode::Char('x') => Some(Decision::Drop),
+            KeyCode::Char('k') | KeyCode::Char(' ') | KeyCode::Right => None,
+            _ => return CarryoverAction::Continue,
+        };
+        self.decisions[self.index] = decision;
+        self.index += 1;
+        CarryoverAction::Continue
+    }
+
+    /// Re-reads the DB and applies every decision in one save, as a single
+    /// undo entry.
+    pub fn apply(&self, undo: &mut UndoStack) -> Result<CarryoverOutcome, Error> {
+        let (outcome, before) = update_db(|todos| {
+            let before = todos.clone();
+            let mut outcome = CarryoverOutcome::default();
+            for (todo, decision) in self.todos.iter().zip(&self.decisions) {
+                let decision = match decision {
+                    Some(decision) => decision,
+                    None => {
+                        outcome.kept += 1;
+                        continue;
+                    }
+                };
+                let current = match todos.iter_mut().find(|t| t.id == todo.id) {
+                    Some(current) => current,
+                    None => {
+                        outcome.missing += 1;
+                        continue;
+                    }
+                };
+                decision.apply(current, self.next_week);
+                match decision {
+                    Decision::Reschedule(_) => outcome.rescheduled += 1,
+                    Decision::Defer => outcome.deferred += 1,
+                    Decision::Deprioritize => outcome.deprioritized += 1,
+                    Decision::Drop => outcome.dropped += 1,
+                }
+            }
+            let dropped = self.dropped();
+            todos.retain(|t| !dropped.contains(&t.id));
+            (outcome, before)
+        })?;
+        if outcome.changed() > 0 {
+            undo.push("carry-over", before, 0);
+        }
+        Ok(outcome)
+    }
+
+    /// The ids of the todos decided to drop.
+    pub fn dropped(&self) -> Vec<usize> {
+        self.todos
+            .iter()
+            .zip(&self.decisions)
+            .filter(|(_, decision)| **decision == Some(Decision::Drop))
+            .map(|(todo, _)| todo.id)
+            .collect()
+    }
+
+    fn todo_lines(&self, todo: &Todo) -> Vec<Spans<'static>> {
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let mut lines = vec![
+            Spans::from(vec![Span::raw("")]),
+            Spans::from(vec![Span::styled(format!(" {}", todo.title), bold)]),
+            Spans::from(vec![Span::raw(format!(
+                " {} · {:?}{}",
+                todo.category,
+                todo.status(),
+                match todo.priority {
+                    Some(priority) => format!(" · {:?}", priority),
+                    None => String::new(),
+                }
+            ))]),
+        ];
+        if let Some(due) = todo.due {
+            lines.push(Spans::from(vec![Span::styled(
+                format!(
+                    " was due {}",
+                    due.with_timezone(&zone::current())
+                        .format("%a %Y-%m-%d %H:%M")
+                ),
+                Style::default().fg(Color::Red),
+            )]));
+        }
+        if let Some(decision) = self.decisions[self.index] {
+            lines.push(Spans::from(vec![Span::styled(
+                format!(" decided: {}", decision.label()),
+                Style::default().fg(Color::Yellow),
+            )]));
+        }
+        lines.push(Spans::from(vec![Span::raw("")]));
+        let days: Vec<String> = self
+            .days
+            .iter()
+            .enumerate()
+            .filter(|(_, day)| **day >= self.today)
+            .map(|(i, day)| format!("{}: {}", i + 1, day.format("%a")))
+            .collect();
+        lines.push(Spans::from(vec![Span::raw(format!(
+            " reschedule  {}",
+            days.join("  ")
+        ))]));
+        lines.push(Spans::from(vec![Span::raw(
+            " d: defer  p: deprioritize  x: drop  k: keep  Left: back  Esc: cancel",
+        )]));
+        lines
+    }
+
+    fn summary_lines(&self) -> Vec<Spans<'static>> {
+        let mut lines = vec![Spans::from(vec![Span::raw("")])];
+        for (todo, decision) in self.todos.iter().zip(&self.decisions) {
+            lines.push(Spans::from(vec![
+                Span::styled(
+                    format!(
+                        " {:<24}",
+                        decision.map_or("keep".to_string(), |d| d.label())
+                    ),
+                    Style::default().fg(match decision {
+                        Some(Decision::Drop) => Color::Red,
+                        Some(_) => Color::Yellow,
+                        None => Color::DarkGray,
+                    }),
+                ),
+                Span::raw(todo.title.clone()),
+            ]));
+        }
+        lines.push(Spans::from(vec![Span::raw("")]));
+        lines.push(Spans::from(vec![Span::raw(
+            " Enter: save  Left: back  Esc: cancel",
+        )]));
+        lines
+    }
+}
+
+pub fn render_carryover<B: Backend>(rect: &mut Frame<B>, carryover: &Carryover, area: Rect) {
+    let popup = centered_rect(70, 60, area);
+    let (mut lines, title) = match carryover.todos.get(carryover.index) {
+        Some(todo) => (
+            carryover.todo_lines(todo),
+            format!(
+                "Carry-over {}/{}",
+                carryover.index + 1,
+                carryover.todos.len()
+            ),
+        ),
+        None => (carryover.summary_lines(), "Carry-over summary".to_string()),
+    };
+    if let Some(err) = &carryover.error {
+        lines.push(Spans::from(vec![Span::styled(
+            format!(" {}", err),
+            Style::default().fg(Color::Red),
+        )]));
+    }
+    let paragraph = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(title)
+            .border_type(BorderType::Plain),
+    );
+    rect.render_widget(Clear, popup);
+    rect.render_widget(paragraph, popup);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(id: usize, due: &str, deferred_until: Option<&str>) -> Todo {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "title": format!("todo {}", id),
+            "description": "",
+            "category": "work",
+            "status": "Todo",
+            "created_at": "2024-04-01T09:00:00Z",
+            "due": due,
+            "deferred_until": deferred_until,
+        }))
+        .unwrap()
+    }
+
+    /// Wednesday 2024-05-15 noon in UTC, weeks starting on Monday.
+    fn now() -> DateTime<Zone> {
+        let zone = Zone::parse("UTC").unwrap();
+        zone.with_ymd_and_hms(2024, 5, 15, 12, 0, 0).unwrap()
+    }
+
+    fn ids(todos: Vec<&Todo>) -> Vec<usize> {
+        todos.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn offers_last_weeks_todos_and_ended_deferrals() {
+        let todos = [
+            // due last week
+            todo(1, "2024-05-08T10:00:00Z", None),
+            // due this week
+            todo(2, "2024-05-16T10:00:00Z", None),
+            // due two weeks ago, deferred until this Monday
+            todo(3, "2024-05-01T10:00:00Z", Some("2024-05-13T00:00:00Z")),
+            // due last week, deferred until next Monday
+            todo(4, "2024-05-09T10:00:00Z", Some("2024-05-20T00:00:00Z")),
+        ];
+        assert_eq!(ids(candidates(&todos, now(), Weekday::Mon)), [3, 1]);
+    }
+
+    #[test]
+    fn deciding_ends_a_deferral_unless_deferring_again() {
+        let next_week = Utc.with_ymd_and_hms(2024, 5, 20, 0, 0, 0).unwrap();
+        let deferred = todo(3, "2024-05-01T10:00:00Z", Some("2024-05-13T00:00:00Z"));
+
+        let mut todo = deferred.clone();
+        Decision::Deprioritize.apply(&mut todo, next_week);
+        assert_eq!(todo.deferred_until, None);
+
+        let mut todo = deferred.clone();
+        Decision::Defer.apply(&mut todo, next_week);
+        assert_eq!(todo.deferred_until, Some(next_week));
+    }
+}
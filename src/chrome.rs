@@ -0,0 +1,69 @@
+use tui::{
+    layout::Rect,
+    style::{Color, Style},
+    widgets::{Block, BorderType, Borders},
+};
+
+/// Below this many columns or rows the screen switches to compact mode by
+/// itself.
+pub const COMPACT_BELOW: (u16, u16) = (80, 24);
+
+/// How much decoration a frame gets. Normally every pane is boxed and the
+/// screen has a margin; compact mode trades that for content, leaving a
+/// single rule above each pane and one between side by side panes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Chrome {
+    pub compact: bool,
+}
+
+impl Chrome {
+    /// The chrome for a screen of `size`, where `forced` is what
+    /// `:set compact` chose, or None to decide by the size.
+    pub fn new(size: Rect, forced: Option<bool>) -> Chrome {
+        Chrome {
+            compact: forced
+                .unwrap_or(size.width < COMPACT_BELOW.0 || size.height < COMPACT_BELOW.1),
+        }
+    }
+
+    /// Space kept around the whole screen.
+    pub fn margin(self) -> u16 {
+        if self.compact {
+            0
+        } else {
+            2
+        }
+    }
+
+    /// The block a pane is drawn in.
+    pub fn block<'a>(self) -> Block<'a> {
+        self.pane(false)
+    }
+
+    /// The block of a pane with another one to its right, which in compact
+    /// mode draws the line between them.
+    pub fn pane<'a>(self, beside: bool) -> Block<'a> {
+        Block::default()
+            .borders(self.borders(beside))
+            .style(Style::default().fg(Color::White))
+            .border_type(BorderType::Plain)
+    }
+
+    /// Columns and rows the borders of a `pane(beside)` take.
+    pub fn frame(self, beside: bool) -> (u16, u16) {
+        let borders = self.borders(beside);
+        let count = |sides: [Borders; 2]| sides.iter().filter(|s| borders.contains(**s)).count();
+        (
+            count([Borders::LEFT, Borders::RIGHT]) as u16,
+            count([Borders::TOP, Borders::BOTTOM]) as u16,
+        )
+    }
+
+    fn borders(self, beside: bool) -> Borders {
+        match (self.compact, beside) {
+            (false, _) => Borders::ALL,
+            (true, false) => Borders::TOP,
+            (true, true) => Borders::TOP | Borders::RIGHT,
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use serde_json::json;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::Error;
+
+pub const USAGE_EXIT: i32 = 2;
+const OTHER_EXIT: i32 = 1;
+
+pub const HELP: &str = "\
+usage: work_time_cli [--format json] [COMMAND]
+
+Without a command the TUI starts.
+
+commands:
+  add TITLE [--description TEXT] [--category NAME] [--parent ID]
+  agenda [--week YYYY-Www] [--format pdf-ready-text|md] [--output FILE]
+  bulk --filter EXPR --action archive|delete|set-priority P|add-tag TAG
+       [--dry-run] [--yes]
+  doctor [--fix]
+  export --format html [--include FILTER] [--output FILE]
+  export --format pws-archive FILE
+  export-time --format grid [--month YYYY-MM] [--round DURATION] [--text]
+       [--width N] [--output FILE] [--tag TAG] [--by category|tag]
+  import --format pws-archive FILE [--into DIR | --merge [--id-map OUT]]
+  list [--format json|text] [--sort KEYS] [--modified-since TIMESTAMP]
+       [--include FILTER]
+  migrate [--dry-run] [FILE]
+  quick [--workspace DIR] [TEXT]
+  show ID [--format md|text]
+  storage
+  timer DURATION [LABEL] [--category NAME] [--todo ID] [--tag TAG]...
+  help
+
+list prints todos with their stable id, their board position as `order`
+and `updated_at`. --sort takes comma separated keys, each ascending:
+status (ToDo, Doing, Done), rank (board position), priority (Urgent
+first, none last), due (earliest first, none last), urgency (most urgent
+first, by overdue days, time to the due date and priority, weighted by
+urgency_weights in the config), created (oldest first) and id; ties
+always fall back to the id. The default is status,rank. --modified-since
+keeps todos changed at or after an RFC 3339 timestamp.
+
+bulk changes every todo the filter matches. The filter is what :filter
+takes: key:value terms like done:false,tag:home, or an expression such as
+\"status=done and completed<2024-01-01\". Expressions compare status,
+category, tag, priority, due, created, completed, pinned, source and id
+with = != < <= > >=, combine them with and, or, not and parentheses, and
+take dates as YYYY-MM-DD, today, yesterday, tomorrow, -30d or +2w. source
+is where a todo was made (cli, stdin, tui, merge:NAME, or unknown), and *
+in it matches anything, e.g. source=merge:*. archive moves the todos to
+./data/archived_todos.json. --dry-run lists the matches only; archiving
+or deleting more than 10 todos needs --yes.
+
+add --parent makes the todo a subtask. A todo's effective due date is
+the earliest due date of its subtasks not done yet when that comes
+before its own; the board, due sorting and due alerts go by it.
+
+agenda prints a week, the current one by default, for paper: Monday to
+Friday with the todos due each day, highest effective priority first,
+and above them the ones due before the week that aren't done. A day
+with too many ends in \"+N more\". pdf-ready-text, the default, fills one
+80 column page of 66 lines for lpr, the rest ruled for notes; md writes
+Markdown with a checkbox per todo.
+
+import --merge adds the todos and time entries of an archive to ./data
+instead of filling an empty directory. Ones already here, same id and
+same creation, are skipped. An incoming one whose id is taken gets the
+next free id, the lowest colliding id first, so the same data always
+maps the same way; subtask and time entry links follow it. The summary
+lists every id that moved, --id-map writes them to OUT as JSON.
+
+timer counts down and tracks the time. Ctrl+C stops it and asks whether
+to record the time, throw it away or continue; every run is kept in
+./data/pomodoros.json, where continued stops count as interruptions. The
+TUI shows the totals on Home and in :stats. A break started in the TUI
+with Ctrl+b ends the run as stopped at the break. With auto_break in the
+config, e.g. \"5m\", a recorded run goes straight into a break countdown
+that rings when it is over; starting another timer or taking auto_break
+out of the config ends it early, and log_breaks: true records it as a
+break entry.
+
+export-time prints the tracked hours of a month, the current one by
+default, as a grid: a row per category, a column per day, totals last
+both ways. Days with nothing tracked are blank. It writes CSV, or with
+--text a fixed-width table that abbreviates the day headers to fit
+--width columns (160). --round 15m rounds every cell to the nearest 15
+minutes before the totals add up; the step is at most 24h. --tag TAG
+keeps only entries with that tag, and --by tag makes a row per tag
+instead, where an entry with two tags counts in both. An entry has the
+tags set on it with a in the TimeTracking tab or timer --tag, otherwise
+those of its todo. The TimeTracking tab previews the same grid with g;
+f there keeps one tag and B makes a row per tag, in the preview and the
+week summary.
+
+quick adds one todo and prints its id. Without TEXT it prompts on a
+terminal, otherwise it reads one line from stdin. In the text, @NAME
+sets the category, +TAG adds a tag, ! !! !!! (or !low .. !urgent) set
+the priority, due:YYYY-MM-DD the due date and ~2h or ~30m the estimate.
+--workspace DIR uses DIR/data instead of ./data.
+
+./data/db.json is a versioned object. The original format, a bare array
+of todos, is migrated in place by the first run that finds it, which
+keeps the original next to it as db.json.v0-TIMESTAMP.bak and reports the
+todos migrated, the fields defaulted and anything suspicious. migrate
+does the same for FILE, the DB by default; with --dry-run it only
+prints the report.
+
+--format json prints errors on stderr as one JSON object per line,
+e.g. {\"error\":\"locked\",\"message\":\"...\",\"path\":\"...\"}.
+
+exit codes:
+  0  success
+  1  other failure
+  2  usage         bad arguments
+  3  read_error    a data file can't be read
+  4  parse_error   a data file isn't valid JSON for its format
+  5  write_error   a data or output file can't be written
+  6  config_error  ./data/config.json is invalid
+  7  locked        another instance holds the DB lock
+  8  archive_error an archive is invalid, too new, or the target isn't empty
+  9  schema_error  the DB is newer than this build
+  10 protected_field a change would rewrite a todo's id or created_at
+";
+
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json_errors(json: bool) {
+    JSON_ERRORS.store(json, Ordering::Relaxed);
+}
+
+/// Prints `err` in the selected format and returns its exit code.
+pub fn fail(err: &Error) -> i32 {
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        let mut object = json!({
+            "error": err.code(),
+            "message": err.to_string(),
+        });
+        match err {
+            Error::DBLockedError(path) => object["path"] = json!(path),
+            Error::ParseDBError(parse) | Error::ParseConfigError(parse) => {
+                object["line"] = json!(parse.line());
+                object["column"] = json!(parse.column());
+            }
+            Error::WriteFileError(path, _) | Error::ReadFileError(path, _) => {
+                object["path"] = json!(path)
+            }
+            Error::ProtectedFieldError(id, field) => {
+                object["id"] = json!(id);
+                object["field"] = json!(field);
+            }
+            _ => {}
+        }
+        eprintln!("{}", object);
+    } else {
+        eprintln!("{}", err);
+    }
+    err.exit_code()
+}
+
+/// Reports bad arguments and returns the usage exit code.
+pub fn usage(message: impl Display) -> i32 {
+    report("usage", message);
+    USAGE_EXIT
+}
+
+/// Reports a failure that isn't one of the `Error` classes.
+pub fn other(message: impl Display) -> i32 {
+    report("other", message);
+    OTHER_EXIT
+}
+
+fn report(code: &str, message: impl Display) {
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        eprintln!(
+            "{}",
+            json!({ "error": code, "message": message.to_string() })
+        );
+    } else {
+        eprintln!("{}", message);
+    }
+}
@@ -0,0 +1,331 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::commands;
+use crate::complete::{Completer, Vocabulary};
+use crate::widgets::{centered_rect, suggestions_line, Input};
+
+pub enum LineAction {
+    Continue,
+    Cancel,
+    Run(String),
+}
+
+/// A Ctrl+r search back through the history.
+struct Search {
+    query: Input,
+    /// The history line shown, None when nothing matches.
+    found: Option<usize>,
+}
+
+/// The `:` command line: Tab completes commands and their arguments, Up
+/// and Down go through the lines run before and Ctrl+r searches them.
+pub struct CommandLine {
+    input: Input,
+    vocabulary: Vocabulary,
+    completer: Completer,
+    /// Oldest first, as the session keeps it.
+    history: Vec<String>,
+    /// The history line Up and Down are on, None on the line being typed.
+    recalled: Option<usize>,
+    /// The line being typed, kept while Up and Down show others.
+    draft: String,
+    search: Option<Search>,
+}
+
+impl CommandLine {
+    pub fn new(vocabulary: Vocabulary, history: Vec<String>) -> CommandLine {
+        CommandLine {
+            input: Input::default(),
+            vocabulary,
+            completer: Completer::listing(),
+            history,
+            recalled: None,
+            draft: String::new(),
+            search: None,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> LineAction {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if let Some(search) = &mut self.search {
+            match key.code {
+                KeyCode::Esc => self.search = None,
+                KeyCode::Char('g') if ctrl => self.search = None,
+                KeyCode::Enter => {
+                    let line = match search.found {
+                        Some(i) => self.history[i].clone(),
+                        None => self.input.value().to_string(),
+                    };
+                    return LineAction::Run(line);
+                }
+                KeyCode::Char('r') if ctrl => {
+                    let before = search.found.unwrap_or(self.history.len());
+                    if let Some(older) = find(&self.history[..before], search.query.value()) {
+                        search.found = Some(older);
+                    }
+                }
+                KeyCode::Tab | KeyCode::Left | KeyCode::Right => {
+                    if let Some(i) = search.found {
+                        self.input.set_value(&self.history[i]);
+                    }
+                    self.search = None;
+                }
+                _ => {
+                    if search.query.handle_key(key) {
+                        search.found = find(&self.history, search.query.value());
+                    }
+                }
+            }
+            return LineAction::Continue;
+        }
+
+        match key.code {
+            KeyCode::Esc => return LineAction::Cancel,
+            KeyCode::Enter => return LineAction::Run(self.input.value().to_string()),
+            KeyCode::Char('r') if ctrl => {
+                self.completer.reset();
+                self.search = Some(Search {
+                    query: Input::default(),
+                    found: None,
+                });
+            }
+            KeyCode::Tab => {
+                let typed = self.completer.typed().map(str::to_string);
+                if let Some((start, candidates)) =
+                    commands::completions(self.input.value(), &self.vocabulary, typed.as_deref())
+                {
+                    self.completer.cycle(&mut self.input, start, &candidates);
+                }
+            }
+            KeyCode::Up => {
+                self.completer.reset();
+                let at = match self.recalled {
+                    Some(at) => at.saturating_sub(1),
+                    None if self.history.is_empty() => return LineAction::Continue,
+                    None => {
+                        self.draft = self.input.value().to_string();
+                        self.history.len() - 1
+                    }
+                };
+                self.recalled = Some(at);
+                self.input.set_value(&self.history[at]);
+            }
+            KeyCode::Down => {
+                self.completer.reset();
+                match self.recalled {
+                    Some(at) if at + 1 < self.history.len() => {
+                        self.recalled = Some(at + 1);
+                        self.input.set_value(&self.history[at + 1]);
+                    }
+                    Some(_) => {
+                        self.recalled = None;
+                        self.input.set_value(&self.draft);
+                    }
+                    None => {}
+                }
+            }
+            _ => {
+                self.completer.reset();
+                self.input.handle_key(key);
+            }
+        }
+        LineAction::Continue
+    }
+
+    /// The matches under the line, while Tab cycles through them.
+    fn suggestions(&self) -> (Vec<String>, Option<usize>) {
+        let typed = match (&self.search, self.completer.typed()) {
+            (None, Some(typed)) => typed,
+            _ => return (vec![], None),
+        };
+        match commands::completions(self.input.value(), &self.vocabulary, Some(typed)) {
+            Some((start, candidates)) => (
+                self.completer
+                    .suggestions(&self.input, start, &candidates)
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+                self.completer.current(),
+            ),
+            None => (vec![], None),
+        }
+    }
+}
+
+/// The newest line of `history` containing `query`.
+fn find(history: &[String], query: &str) -> Option<usize> {
+    if query.is_empty() {
+        return None;
+    }
+    history.iter().rposition(|line| line.contains(query))
+}
+
+pub fn render_command_line<B: Backend>(rect: &mut Frame<B>, line: &CommandLine, area: Rect) {
+    let popup = Rect {
+        y: area.y + area.height.saturating_sub(4) / 2,
+        height: area.height.min(4),
+        ..centered_rect(60, 20, area)
+    };
+    let (text, title, cursor) = match &line.search {
+        Some(search) => {
+            let found = search.found.map_or("", |i| line.history[i].as_str());
+            (
+                found.to_string(),
+                format!("(reverse-i-search)`{}'", search.query.value()),
+                found.chars().count(),
+            )
+        }
+        None => (
+            line.input.value().to_string(),
+            ":".to_string(),
+            line.input.cursor(),
+        ),
+    };
+    let (found, current) = line.suggestions();
+    let hint = if line.search.is_some() {
+        "Ctrl+r: older  Enter: run  Tab: edit  Esc: back"
+    } else {
+        "Tab: complete  Up/Down: history  Ctrl+r: search  Enter: run  Esc: cancel"
+    };
+    let paragraph = Paragraph::new(vec![
+        Spans::from(vec![Span::raw(text)]),
+        if found.is_empty() {
+            Spans::from(vec![Span::styled(
+                hint,
+                Style::default().fg(Color::DarkGray),
+            )])
+        } else {
+            suggestions_line(
+                &found.iter().map(String::as_str).collect::<Vec<_>>(),
+                current,
+            )
+        },
+    ])
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(title)
+            .border_type(BorderType::Plain),
+    );
+
+    rect.render_widget(Clear, popup);
+    rect.render_widget(paragraph, popup);
+    rect.set_cursor(popup.x + 1 + cursor as u16, popup.y + 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Todo;
+
+    fn vocabulary() -> Vocabulary {
+        let todos: Vec<Todo> = serde_json::from_value(serde_json::json!([{
+            "id": 1, "title": "a", "description": "", "category": "work",
+            "status": "Todo", "created_at": "2024-05-01T09:00:00Z",
+            "tags": ["deep", "call"],
+        }]))
+        .unwrap();
+        Vocabulary::build(&todos)
+    }
+
+    fn press(line: &mut CommandLine, keys: &[KeyCode]) {
+        for code in keys {
+            line.handle_key(KeyEvent::new(*code, KeyModifiers::NONE));
+        }
+    }
+
+    fn typed(line: &mut CommandLine, text: &str) {
+        let keys: Vec<KeyCode> = text.chars().map(KeyCode::Char).collect();
+        press(line, &keys);
+    }
+
+    /// What Enter would run.
+    fn buffer(line: &mut CommandLine) -> String {
+        match line.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)) {
+            LineAction::Run(line) => line,
+            _ => panic!("Enter runs the line"),
+        }
+    }
+
+    #[test]
+    fn tab_cycles_through_the_matches_prefixes_first() {
+        let mut line = CommandLine::new(vocabulary(), vec![]);
+        typed(&mut line, "ca");
+        press(&mut line, &[KeyCode::Tab]);
+        assert_eq!(buffer(&mut line), "calendar");
+        press(&mut line, &[KeyCode::Tab]);
+        assert_eq!(buffer(&mut line), "carryover");
+        press(&mut line, &[KeyCode::Tab]);
+        assert_eq!(buffer(&mut line), "vacation");
+        press(&mut line, &[KeyCode::Tab]);
+        assert_eq!(buffer(&mut line), "calendar");
+
+        // typing ends the cycle, the next Tab starts from the new word
+        press(&mut line, &[KeyCode::Backspace; 6]);
+        typed(&mut line, "r");
+        press(&mut line, &[KeyCode::Tab]);
+        assert_eq!(buffer(&mut line), "carryover");
+    }
+
+    #[test]
+    fn tab_completes_arguments_in_place() {
+        let mut line = CommandLine::new(vocabulary(), vec![]);
+        typed(&mut line, "view ca");
+        press(&mut line, &[KeyCode::Tab]);
+        assert_eq!(buffer(&mut line), "view calendar");
+
+        let mut line = CommandLine::new(vocabulary(), vec![]);
+        typed(&mut line, "filter done:false,tag:c");
+        press(&mut line, &[KeyCode::Tab]);
+        assert_eq!(buffer(&mut line), "filter done:false,tag:call");
+
+        let mut line = CommandLine::new(vocabulary(), vec![]);
+        typed(&mut line, "mo");
+        press(&mut line, &[KeyCode::Tab, KeyCode::Char(' '), KeyCode::Tab]);
+        assert_eq!(buffer(&mut line), "move todo");
+        press(&mut line, &[KeyCode::Tab]);
+        assert_eq!(buffer(&mut line), "move doing");
+    }
+
+    #[test]
+    fn up_and_down_recall_the_history_and_keep_the_draft() {
+        let history = vec!["stats".to_string(), "tab todos".to_string()];
+        let mut line = CommandLine::new(vocabulary(), history);
+        typed(&mut line, "fil");
+        press(&mut line, &[KeyCode::Up]);
+        assert_eq!(buffer(&mut line), "tab todos");
+        press(&mut line, &[KeyCode::Up, KeyCode::Up]);
+        assert_eq!(buffer(&mut line), "stats");
+        press(&mut line, &[KeyCode::Down, KeyCode::Down]);
+        assert_eq!(buffer(&mut line), "fil");
+    }
+
+    #[test]
+    fn ctrl_r_finds_older_matches() {
+        let history = vec![
+            "filter tag:deep".to_string(),
+            "stats".to_string(),
+            "filter tag:call".to_string(),
+        ];
+        let mut line = CommandLine::new(vocabulary(), history);
+        let ctrl_r = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL);
+        line.handle_key(ctrl_r);
+        typed(&mut line, "filter");
+        assert_eq!(buffer(&mut line), "filter tag:call");
+        line.handle_key(ctrl_r);
+        assert_eq!(buffer(&mut line), "filter tag:deep");
+
+        // Tab takes the match for editing
+        press(&mut line, &[KeyCode::Tab, KeyCode::Backspace]);
+        assert_eq!(buffer(&mut line), "filter tag:dee");
+    }
+}
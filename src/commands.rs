@@ -0,0 +1,554 @@
+use chrono::prelude::*;
+use std::fs;
+
+use crate::actions::ActionId;
+use crate::add;
+use crate::app::{App, Modal, View};
+use crate::archive;
+use crate::breakdown::Granularity;
+use crate::calendar::{self, OffKind};
+use crate::carryover::{self, Carryover};
+use crate::complete::{self, Arg, Kind, Vocabulary};
+use crate::expr;
+use crate::fields;
+use crate::filter::{DueWindow, Filter};
+use crate::history::{self, SnippetFormat};
+use crate::pomodoro;
+use crate::preview::{ChangeSet, Preview};
+use crate::quick;
+use crate::share;
+use crate::storage;
+use crate::subtasks;
+use crate::timesheet;
+use crate::timetracking;
+use crate::urgency::{self, Urgency};
+use crate::zone;
+use crate::{read_db, update_db, Todo, TodoStatus};
+
+const ADD_REMOVE: &[&str] = &["add", "remove"];
+
+/// The commands `:view` opens by name, each a view of its own.
+const VIEWS: &[&str] = &[
+    "calendar",
+    "help",
+    "inconsistencies",
+    "stats",
+    "storage",
+    "timesheet",
+    "today",
+    "yesterday",
+];
+
+/// Every `:` command with what its arguments are, in order, the last one
+/// standing for any further ones. Completion reads this, so a new command
+/// gets it by being listed here.
+pub const COMMANDS: &[(&str, &[Arg])] = &[
+    ("breakdown", &[Arg::Word(&["day", "week", "month"])]),
+    ("calendar", &[Arg::Free]),
+    ("carryover", &[]),
+    ("commands", &[]),
+    ("due", &[Arg::Word(&["today", "week", "overdue", "off"])]),
+    ("estimate", &[Arg::Word(&["week", "all"])]),
+    ("export-item", &[Arg::Path]),
+    ("filter", &[Arg::Filter]),
+    ("help", &[]),
+    ("holiday", &[Arg::Word(ADD_REMOVE), Arg::Free]),
+    ("inconsistencies", &[]),
+    ("move", &[Arg::Word(&["todo", "doing", "done"])]),
+    ("parent", &[Arg::Free]),
+    ("refresh", &[]),
+    ("restore", &[Arg::Path]),
+    (
+        "set",
+        &[
+            Arg::Word(&["fields", "compact", "badges", "sort"]),
+            Arg::Word(&["on", "off", "auto", "urgency", "manual"]),
+        ],
+    ),
+    ("share", &[Arg::Word(&["md", "text"])]),
+    ("snooze", &[Arg::Free]),
+    ("stats", &[]),
+    ("storage", &[]),
+    ("subtask", &[Arg::Free]),
+    ("tab", &[Arg::Word(&["home", "todos", "timers", "time"])]),
+    ("timesheet", &[Arg::Free]),
+    ("today", &[]),
+    ("vacation", &[Arg::Word(ADD_REMOVE), Arg::Free]),
+    ("view", &[Arg::Word(VIEWS), Arg::Free]),
+    ("yesterday", &[]),
+];
+
+/// The word being typed at the end of a `:` line and what it could be:
+/// the char it starts at and the candidates. The first word is a command,
+/// the others are what `COMMANDS` says they are. `typed` is the word as
+/// it was before a Tab cycle replaced it, for paths, whose candidates
+/// depend on it.
+pub fn completions(
+    line: &str,
+    vocabulary: &Vocabulary,
+    typed: Option<&str>,
+) -> Option<(usize, Vec<String>)> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let open = !line.is_empty() && !line.ends_with(char::is_whitespace);
+    let word = if open { words.last().copied() } else { None }.unwrap_or("");
+    let start = line.chars().count() - word.chars().count();
+    let position = words.len() - usize::from(open);
+    if position == 0 {
+        let names = COMMANDS.iter().map(|(name, _)| name.to_string()).collect();
+        return Some((start, names));
+    }
+    let (_, args) = COMMANDS.iter().find(|(name, _)| *name == words[0])?;
+    match args.get(position - 1).or(args.last())? {
+        Arg::Word(choices) => Some((start, choices.iter().map(|c| c.to_string()).collect())),
+        Arg::Filter => {
+            let (start, kind) = locate_completion(line)?;
+            Some((start, vocabulary.candidates(kind).to_vec()))
+        }
+        Arg::Path => Some((start, complete::path_candidates(typed.unwrap_or(word)))),
+        Arg::Free => None,
+    }
+}
+
+/// Finds a `category:` or `tag:` value at the end of a `:filter` line, for
+/// completion.
+pub fn locate_completion(line: &str) -> Option<(usize, Kind)> {
+    let spec = line.strip_prefix("filter ")?;
+    let term = spec
+        .rsplit(|c: char| c == ',' || c.is_whitespace())
+        .next()?;
+    let (key, value) = term.split_once(':')?;
+    let kind = match key {
+        "category" => Kind::Category,
+        "tag" => Kind::Tag,
+        _ => return None,
+    };
+    Some((line.chars().count() - value.chars().count(), kind))
+}
+
+/// The open todo, or else the selected one.
+fn target_todo(app: &App, action: &str) -> Result<Todo, String> {
+    let id = match app.view {
+        Some(View::Todo(id)) => id,
+        _ => app
+            .selected_todo()
+            .map(|todo| todo.id)
+            .ok_or_else(|| format!("select or open a todo to {}", action))?,
+    };
+    read_db()
+        .map_err(|err| err.to_string())?
+        .into_iter()
+        .find(|t| t.id == id)
+        .ok_or_else(|| format!("todo {} no longer exists", id))
+}
+
+/// Runs a line typed after `:`, returning a message for the status line
+/// when it fails.
+pub fn execute(app: &mut App, line: &str) -> Result<(), String> {
+    let mut words = line.split_whitespace();
+    let command = match words.next() {
+        Some(command) => command,
+        None => return Ok(()),
+    };
+    match command {
+        "commands" => {
+            app.perform(ActionId::Palette);
+            Ok(())
+        }
+        "help" => {
+            app.perform(ActionId::Help);
+            Ok(())
+        }
+        "stats" => {
+            app.view = Some(View::Stats(pomodoro::read_history()));
+            Ok(())
+        }
+        "view" => {
+            let rest: Vec<&str> = words.collect();
+            match rest.first() {
+                Some(name) if VIEWS.contains(name) => execute(app, &rest.join(" ")),
+                _ => Err(format!("usage: :view {} [ARG]", VIEWS.join("|"))),
+            }
+        }
+        "timesheet" => {
+            let month = match words.next() {
+                Some(value) => timesheet::parse_month(value)
+                    .ok_or_else(|| "usage: timesheet [YYYY-MM]".to_string())?,
+                None => zone::now().date_naive().with_day(1).expect("every month has a first day"),
+            };
+            app.view = Some(View::Timesheet(month));
+            Ok(())
+        }
+        "breakdown" => {
+            if let Some(value) = words.next() {
+                app.breakdown_scale = Granularity::parse(value)
+                    .ok_or_else(|| "usage: :breakdown [day|week|month]".to_string())?;
+            }
+            app.perform(ActionId::TimeTrackingTab);
+            Ok(())
+        }
+        "calendar" => {
+            let day = match words.next() {
+                Some(value) => timesheet::parse_month(value)
+                    .ok_or_else(|| "usage: calendar [YYYY-MM]".to_string())?,
+                None => zone::now().date_naive(),
+            };
+            app.view = Some(View::Month(day));
+            Ok(())
+        }
+        "storage" => {
+            app.view = Some(View::Storage(storage::load_report(Utc::now())));
+            Ok(())
+        }
+        "inconsistencies" => {
+            app.problem_selected = 0;
+            app.view = Some(View::Inconsistencies);
+            Ok(())
+        }
+        "refresh" => {
+            app.status_message = Some(app.refresh()?);
+            Ok(())
+        }
+        "today" => {
+            app.view = Some(View::Today(zone::now().date_naive()));
+            Ok(())
+        }
+        "estimate" => {
+            app.session.estimate_this_week = match words.next() {
+                Some("week") => true,
+                Some("all") => false,
+                _ => return Err("usage: :estimate week|all".to_string()),
+            };
+            app.session.save().map_err(|err| err.to_string())
+        }
+        "filter" => {
+            let words: Vec<&str> = words.collect();
+            // plain terms may be space separated, an expression needs its spaces
+            let spec = words.join(" ");
+            let mut filter = if expr::is_expression(&spec) {
+                Filter::parse(&spec)?
+            } else {
+                Filter::parse(&words.join(","))?
+            };
+            filter.week_start = app.board_filter.week_start;
+            app.board_filter = filter;
+            app.clamp_board_selection();
+            Ok(())
+        }
+        "carryover" => {
+            let now = zone::now();
+            let todos = read_db().map_err(|err| err.to_string())?;
+            let candidates: Vec<Todo> = carryover::candidates(&todos, now, app.config.week_start)
+                .into_iter()
+                .cloned()
+                .collect();
+            if candidates.is_empty() {
+                return Err("nothing due last week or deferred until now is left open".to_string());
+            }
+            app.modal = Some(Modal::Carryover(Box::new(Carryover::new(
+                candidates,
+                now,
+                app.config.week_start,
+            ))));
+            Ok(())
+        }
+        "snooze" => {
+            let minutes = match words.next() {
+                Some(value) => value
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|minutes| *minutes > 0)
+                    .ok_or_else(|| "usage: :snooze [MINUTES]".to_string())?,
+                None => app.config.due_snooze_minutes,
+            };
+            match app.due_watch.snooze(minutes, Utc::now()) {
+                0 => Err("no due alert to snooze".to_string()),
+                count => {
+                    app.status_message = Some(format!(
+                        "snoozed {} due alert{} for {}m",
+                        count,
+                        if count == 1 { "" } else { "s" },
+                        minutes
+                    ));
+                    Ok(())
+                }
+            }
+        }
+        "set" => match words.next() {
+            Some("fields") => {
+                let spec = words.collect::<Vec<_>>().join(" ");
+                if spec.is_empty() {
+                    return Err("usage: :set fields title|FIELD,...".to_string());
+                }
+                app.board_fields = fields::parse_list(&spec)?;
+                Ok(())
+            }
+            Some("compact") => {
+                app.compact = match words.next() {
+                    None | Some("on") => Some(true),
+                    Some("off") => Some(false),
+                    Some("auto") => None,
+                    Some(_) => return Err("usage: :set compact [on|off|auto]".to_string()),
+                };
+                Ok(())
+            }
+            Some("badges") => {
+                app.session.badges_visible = match words.next() {
+                    None | Some("on") => true,
+                    Some("off") => false,
+                    Some(_) => return Err("usage: :set badges [on|off]".to_string()),
+                };
+                app.session.save().map_err(|err| err.to_string())
+            }
+            Some("sort") => {
+                urgency::set_board(match words.next() {
+                    Some("urgency") => Some(Urgency {
+                        weights: app.config.urgency_weights,
+                        escalation: app.config.priority_escalation.clone(),
+                    }),
+                    Some("manual") => None,
+                    _ => return Err("usage: :set sort urgency|manual".to_string()),
+                });
+                app.clamp_board_selection();
+                Ok(())
+            }
+            _ => Err(
+                "usage: :set fields title|FIELD,..., :set compact [on|off|auto], :set badges [on|off] \
+                 or :set sort urgency|manual"
+                    .to_string(),
+            ),
+        },
+        "due" => {
+            app.board_filter.due = match words.next() {
+                Some("off") => None,
+                Some(value) => Some(
+                    DueWindow::parse(value)
+                        .ok_or_else(|| "usage: :due today|week|overdue|off".to_string())?,
+                ),
+                None => return Err("usage: :due today|week|overdue|off".to_string()),
+            };
+            app.clamp_board_selection();
+            Ok(())
+        }
+        "restore" => {
+            let path = words
+                .next()
+                .ok_or_else(|| "usage: :restore ARCHIVE".to_string())?;
+            let incoming = archive::read_todos(path).map_err(|err| err.to_string())?;
+            let current = read_db().map_err(|err| err.to_string())?;
+            app.modal = Some(Modal::Preview(Box::new(Preview::new(ChangeSet::new(
+                path, &current, incoming,
+            )))));
+            Ok(())
+        }
+        "share" => {
+            let format = match words.next() {
+                Some(value) => share::parse_format(value)
+                    .ok_or_else(|| "usage: :share [md|text]".to_string())?,
+                None => SnippetFormat::Markdown,
+            };
+            let todo = target_todo(app, "share")?;
+            let snippet = share::todo_snippet(&todo, format).map_err(|err| err.to_string())?;
+            share::copy_to_clipboard(&snippet).map_err(|err| err.to_string())?;
+            app.status_message = Some(format!("copied todo {} to the clipboard", todo.id));
+            Ok(())
+        }
+        "export-item" => {
+            let todo = target_todo(app, "export")?;
+            let id = todo.id;
+            let path = words
+                .next()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("todo-{}.md", id));
+            let now = Utc::now();
+            let entries = timetracking::read_time_entries().map_err(|err| err.to_string())?;
+            let history = history::work_history(&entries, id, now);
+            fs::write(&path, history::markdown(&todo, &history, now))
+                .map_err(|err| format!("can't write {}: {}", path, err))?;
+            app.status_message = Some(format!("exported todo {} to {}", id, path));
+            Ok(())
+        }
+        "holiday" | "vacation" => {
+            let usage = format!(
+                "usage: :{0} add FROM[..TO], :{0} remove FROM[..TO] or :holiday DATE",
+                command
+            );
+            let kind = if command == "vacation" {
+                OffKind::Vacation
+            } else {
+                OffKind::Holiday
+            };
+            let (action, spec) = match (words.next(), words.next()) {
+                (Some(action @ ("add" | "remove")), Some(spec)) => (action, spec),
+                // `:holiday DATE` toggles a single day
+                (Some(spec), None) if command == "holiday" => ("toggle", spec),
+                _ => return Err(usage),
+            };
+            let (from, to) = calendar::parse_range(spec).ok_or(usage)?;
+            let days_off = calendar::read_calendar().map_err(|err| err.to_string())?;
+            let add = match action {
+                "add" => true,
+                "remove" => false,
+                _ => calendar::day_off(&days_off, from).is_none(),
+            };
+            let span = if from == to {
+                from.to_string()
+            } else {
+                format!("{} to {}", from, to)
+            };
+            app.status_message = Some(if add {
+                calendar::add(kind, from, to).map_err(|err| err.to_string())?;
+                format!("{} is {}, target 0h", span, kind.label())
+            } else {
+                match calendar::remove(from, to).map_err(|err| err.to_string())? {
+                    0 => format!("{} had no days off", span),
+                    _ => format!("{} is a normal working time again", span),
+                }
+            });
+            Ok(())
+        }
+        "move" => {
+            let status = match words.next() {
+                Some("todo") => TodoStatus::Todo,
+                Some("doing") => TodoStatus::Doing,
+                Some("done") => TodoStatus::Done,
+                _ => return Err("usage: :move todo|doing|done".to_string()),
+            };
+            let todo = target_todo(app, "move")?;
+            app.move_todo(todo.id, status);
+            Ok(())
+        }
+        "tab" => {
+            let tab = match words.next() {
+                Some("home") => ActionId::HomeTab,
+                Some("todos") => ActionId::TodosTab,
+                Some("timers") => ActionId::TimersTab,
+                Some("time") => ActionId::TimeTrackingTab,
+                _ => return Err("usage: :tab home|todos|timers|time".to_string()),
+            };
+            app.perform(tab);
+            Ok(())
+        }
+        "yesterday" => {
+            let days_off = calendar::read_calendar().map_err(|err| err.to_string())?;
+            let day =
+                calendar::previous_working_day(&app.config, &days_off, zone::now().date_naive());
+            app.view = Some(View::Today(day));
+            Ok(())
+        }
+        "subtask" => {
+            let parent = target_todo(app, "add a subtask to")?;
+            let mut subtask = quick::parse(&words.collect::<Vec<_>>().join(" "))?;
+            if subtask.category.is_empty() {
+                subtask.category = parent.category.clone();
+            }
+            subtask.parent = Some(parent.id);
+            subtask.source = Some("tui".to_string());
+            let id = add::insert(subtask).map_err(|err| err.to_string())?;
+            app.status_message = Some(format!("added subtask {} to todo {}", id, parent.id));
+            Ok(())
+        }
+        "parent" => {
+            let todo = target_todo(app, "move")?;
+            let parent = match words.next() {
+                Some("off") => None,
+                Some(value) => Some(
+                    value
+                        .trim_start_matches('#')
+                        .parse::<usize>()
+                        .map_err(|_| "usage: :parent ID|off".to_string())?,
+                ),
+                None => return Err("usage: :parent ID|off".to_string()),
+            };
+            update_db(|todos| {
+                if let Some(parent) = parent {
+                    subtasks::check_parent(todo.id, parent, todos)?;
+                }
+                match todos.iter_mut().find(|t| t.id == todo.id) {
+                    Some(todo) => {
+                        todo.parent = parent;
+                        Ok(())
+                    }
+                    None => Err(format!("todo {} no longer exists", todo.id)),
+                }
+            })
+            .map_err(|err| err.to_string())??;
+            app.status_message = Some(match parent {
+                Some(parent) => format!("todo {} is a subtask of todo {}", todo.id, parent),
+                None => format!("todo {} is no longer a subtask", todo.id),
+            });
+            Ok(())
+        }
+        _ => Err(format!("unknown command ':{}'", command)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn vocabulary() -> Vocabulary {
+        let todos: Vec<Todo> = serde_json::from_value(json!([
+            {
+                "id": 1, "title": "a", "description": "", "category": "work",
+                "status": "Todo", "created_at": "2024-05-01T09:00:00Z",
+                "tags": ["deep", "call"],
+            },
+            {
+                "id": 2, "title": "b", "description": "", "category": "home",
+                "status": "Todo", "created_at": "2024-05-01T09:00:00Z",
+                "tags": ["deep"],
+            },
+            {
+                "id": 3, "title": "c", "description": "", "category": "work",
+                "status": "Done", "created_at": "2024-05-01T09:00:00Z",
+            },
+        ]))
+        .unwrap();
+        Vocabulary::build(&todos)
+    }
+
+    fn candidates(line: &str) -> Option<(usize, Vec<String>)> {
+        completions(line, &vocabulary(), None)
+    }
+
+    #[test]
+    fn completes_command_names_then_their_arguments() {
+        let (start, names) = candidates("").unwrap();
+        assert_eq!(start, 0);
+        assert_eq!(names.len(), COMMANDS.len());
+        assert_eq!(candidates("sta").unwrap().0, 0);
+
+        let (start, statuses) = candidates("move d").unwrap();
+        assert_eq!(start, 5);
+        assert_eq!(statuses, ["todo", "doing", "done"]);
+        assert_eq!(candidates("move ").unwrap().0, 5);
+
+        // the last argument kind stands for any further ones
+        let (start, views) = candidates("view ").unwrap();
+        assert_eq!(start, 5);
+        assert_eq!(views, VIEWS);
+        assert_eq!(candidates("view timesheet 2024-0"), None);
+        assert_eq!(candidates("set badges o").unwrap().1[0], "on");
+    }
+
+    #[test]
+    fn completes_filter_values_by_their_key() {
+        assert_eq!(
+            candidates("filter done:false,category:w"),
+            Some((27, vec!["work".to_string(), "home".to_string()]))
+        );
+        assert_eq!(
+            candidates("filter tag:"),
+            Some((11, vec!["deep".to_string(), "call".to_string()]))
+        );
+        assert_eq!(candidates("filter due:w"), None);
+    }
+
+    #[test]
+    fn no_completion_after_an_unknown_command_or_free_text() {
+        assert_eq!(candidates("nosuch a"), None);
+        assert_eq!(candidates("snooze 1"), None);
+        assert_eq!(candidates("help "), None);
+    }
+}
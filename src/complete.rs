@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crate::widgets::Input;
+use crate::Todo;
+
+/// Suggestions shown below an input at most.
+pub const MAX_MATCHES: usize = 5;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Kind {
+    Category,
+    Tag,
+}
+
+/// Every category and tag in use, most used first, the source of completions.
+#[derive(Clone, Debug, Default)]
+pub struct Vocabulary {
+    categories: Vec<String>,
+    tags: Vec<String>,
+}
+
+impl Vocabulary {
+    pub fn build(todos: &[Todo]) -> Vocabulary {
+        let mut categories = HashMap::new();
+        let mut tags = HashMap::new();
+        for todo in todos {
+            if !todo.category.is_empty() {
+                *categories.entry(todo.category.clone()).or_insert(0) += 1;
+            }
+            for tag in &todo.tags {
+                *tags.entry(tag.clone()).or_insert(0) += 1;
+            }
+        }
+        Vocabulary {
+            categories: by_usage(categories),
+            tags: by_usage(tags),
+        }
+    }
+
+    pub fn candidates(&self, kind: Kind) -> &[String] {
+        match kind {
+            Kind::Category => &self.categories,
+            Kind::Tag => &self.tags,
+        }
+    }
+}
+
+fn by_usage(counts: HashMap<String, usize>) -> Vec<String> {
+    let mut ranked: Vec<(String, usize)> = counts.into_iter().collect();
+    ranked.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+    ranked.into_iter().map(|(value, _)| value).collect()
+}
+
+/// What an argument of a `:` command is, for completion.
+#[derive(Clone, Copy, Debug)]
+pub enum Arg {
+    /// One of these words.
+    Word(&'static [&'static str]),
+    /// `category:` and `tag:` terms of a filter.
+    Filter,
+    /// A file or directory.
+    Path,
+    /// Anything, with nothing to offer.
+    Free,
+}
+
+/// The files and directories `typed` could go on to name, directories
+/// with a trailing `/`. Hidden ones only once a dot is typed.
+pub fn path_candidates(typed: &str) -> Vec<String> {
+    let (dir, name) = match typed.rfind('/') {
+        Some(at) => typed.split_at(at + 1),
+        None => ("", typed),
+    };
+    let entries = fs::read_dir(if dir.is_empty() { "." } else { dir });
+    let mut found: Vec<String> = entries
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let file = entry.file_name().into_string().ok()?;
+            if file.starts_with('.') && !name.starts_with('.') {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, file, slash))
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+/// Finds the tag being typed at the end of a list of tags, for completion.
+pub fn locate_tag(line: &str) -> Option<(usize, Kind)> {
+    let start = line
+        .rfind(|c: char| c == ',' || c.is_whitespace())
+        .map_or(0, |at| at + 1);
+    Some((line[..start].chars().count(), Kind::Tag))
+}
+
+/// Candidates for `typed`, ignoring case: those starting with it first, then
+/// those containing it, each in usage order. Nothing is offered for an
+/// empty word or one that already is a complete candidate on its own.
+pub fn matches<'a>(candidates: &'a [String], typed: &str) -> Vec<&'a str> {
+    let typed = typed.to_lowercase();
+    if typed.is_empty() {
+        return vec![];
+    }
+    let lowered: Vec<String> = candidates.iter().map(|c| c.to_lowercase()).collect();
+    let prefixed = lowered.iter().map(|c| c.starts_with(&typed));
+    let contained = lowered
+        .iter()
+        .map(|c| !c.starts_with(&typed) && c.contains(&typed));
+    let found: Vec<&str> = prefixed
+        .enumerate()
+        .chain(contained.enumerate())
+        .filter(|(_, matched)| *matched)
+        .map(|(i, _)| candidates[i].as_str())
+        .take(MAX_MATCHES)
+        .collect();
+    if found.len() == 1 && found[0].to_lowercase() == typed {
+        return vec![];
+    }
+    found
+}
+
+/// Cycles an input through the matches for what was typed before the first
+/// Tab. The word being completed runs from char `start` to the end.
+#[derive(Clone, Debug, Default)]
+pub struct Completer {
+    typed: Option<String>,
+    next: usize,
+    /// Offers the first candidates for an empty word too.
+    list_empty: bool,
+}
+
+impl Completer {
+    /// A completer that offers candidates before anything is typed, for
+    /// short fixed lists like command names.
+    pub fn listing() -> Completer {
+        Completer {
+            list_empty: true,
+            ..Completer::default()
+        }
+    }
+
+    fn found<'a>(&self, candidates: &'a [String], typed: &str) -> Vec<&'a str> {
+        if typed.is_empty() && self.list_empty {
+            candidates
+                .iter()
+                .take(MAX_MATCHES)
+                .map(String::as_str)
+                .collect()
+        } else {
+            matches(candidates, typed)
+        }
+    }
+
+    /// The word as it was before the first Tab, while cycling.
+    pub fn typed(&self) -> Option<&str> {
+        self.typed.as_deref()
+    }
+
+    /// The matches below the input: for the typed word while cycling, so
+    /// they don't change under the user, else for the current value.
+    pub fn suggestions<'a>(
+        &self,
+        input: &Input,
+        start: usize,
+        candidates: &'a [String],
+    ) -> Vec<&'a str> {
+        match &self.typed {
+            Some(typed) => self.found(candidates, typed),
+            None => self.found(candidates, &word(input.value(), start)),
+        }
+    }
+
+    /// Replaces the word with the next match. False when there is none, or
+    /// the only one is already in, so the key can do what it normally does.
+    pub fn cycle(&mut self, input: &mut Input, start: usize, candidates: &[String]) -> bool {
+        let typed = self
+            .typed
+            .get_or_insert_with(|| word(input.value(), start))
+            .clone();
+        let found = self.found(candidates, &typed);
+        // a lone match has nothing to cycle to once accepted
+        if found.is_empty() || (found.len() == 1 && self.next > 0) {
+            self.reset();
+            return false;
+        }
+        let kept: String = input.value().chars().take(start).collect();
+        input.set_value(&format!("{}{}", kept, found[self.next % found.len()]));
+        self.next += 1;
+        true
+    }
+
+    /// Forgets the cycle, after any other key.
+    pub fn reset(&mut self) {
+        self.typed = None;
+        self.next = 0;
+    }
+
+    /// The match the cycle currently shows, to highlight it.
+    pub fn current(&self) -> Option<usize> {
+        self.typed.as_ref().map(|_| self.next.saturating_sub(1))
+    }
+}
+
+fn word(value: &str, start: usize) -> String {
+    value.chars().skip(start).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    fn todo(category: &str, tags: &[&str]) -> Todo {
+        let mut todo = Todo::new("todo".to_string(), category.to_string(), Utc::now());
+        todo.tags = tags.iter().map(|t| t.to_string()).collect();
+        todo
+    }
+
+    fn owned(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn the_vocabulary_ranks_by_usage_then_name() {
+        let vocabulary = Vocabulary::build(&[
+            todo("work", &["urgent"]),
+            todo("home", &["calls", "urgent"]),
+            todo("work", &["admin"]),
+            todo("", &[]),
+        ]);
+        assert_eq!(vocabulary.candidates(Kind::Category), ["work", "home"]);
+        assert_eq!(
+            vocabulary.candidates(Kind::Tag),
+            ["urgent", "admin", "calls"]
+        );
+    }
+
+    #[test]
+    fn prefixes_come_before_substrings() {
+        let candidates = owned(&["backlog", "Work", "homework", "worship"]);
+        assert_eq!(matches(&candidates, "wor"), ["Work", "worship", "homework"]);
+        assert_eq!(matches(&candidates, "LOG"), ["backlog"]);
+        assert_eq!(matches(&candidates, ""), Vec::<&str>::new());
+        assert_eq!(matches(&candidates, "zzz"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn at_most_five_matches_and_none_for_a_finished_word() {
+        let candidates = owned(&["a1", "a2", "a3", "a4", "a5", "a6"]);
+        assert_eq!(matches(&candidates, "a").len(), MAX_MATCHES);
+        assert_eq!(matches(&owned(&["work"]), "WORK"), Vec::<&str>::new());
+        assert_eq!(
+            matches(&owned(&["work", "workout"]), "work"),
+            ["work", "workout"]
+        );
+    }
+
+    #[test]
+    fn the_tag_being_typed_is_the_last_of_the_list() {
+        assert_eq!(locate_tag("urgent, cal").unwrap().0, 8);
+        assert_eq!(locate_tag("urgent,cal").unwrap().0, 7);
+        assert_eq!(locate_tag("ünï cal").unwrap().0, 4);
+        assert_eq!(locate_tag("").unwrap().0, 0);
+    }
+
+    #[test]
+    fn tab_cycles_through_the_matches_after_unicode_text() {
+        let candidates = owned(&["café", "calls"]);
+        let mut input = Input::new("über, ca");
+        let mut completer = Completer::default();
+        assert!(completer.cycle(&mut input, 6, &candidates));
+        assert_eq!(input.value(), "über, café");
+        assert_eq!(input.cursor(), 10);
+        assert!(completer.cycle(&mut input, 6, &candidates));
+        assert_eq!(input.value(), "über, calls");
+        assert_eq!(completer.current(), Some(1));
+        // the suggestions stay those for what was typed
+        assert_eq!(
+            completer.suggestions(&input, 6, &candidates),
+            ["café", "calls"]
+        );
+        assert!(completer.cycle(&mut input, 6, &candidates));
+        assert_eq!(input.value(), "über, café");
+
+        // typing on after accepting edits at the cursor, after the é
+        completer.reset();
+        input.handle_key(KeyEvent::new(KeyCode::Char('!'), KeyModifiers::NONE));
+        assert_eq!(input.value(), "über, café!");
+        assert_eq!(completer.current(), None);
+    }
+
+    #[test]
+    fn a_lone_match_is_accepted_once() {
+        let candidates = owned(&["work"]);
+        let mut input = Input::new("wo");
+        let mut completer = Completer::default();
+        assert!(completer.cycle(&mut input, 0, &candidates));
+        assert_eq!(input.value(), "work");
+        assert!(!completer.cycle(&mut input, 0, &candidates));
+        assert_eq!(completer.typed(), None);
+        assert!(!Completer::default().cycle(&mut Input::new(""), 0, &candidates));
+    }
+
+    #[test]
+    fn a_listing_offers_candidates_before_typing() {
+        let candidates = owned(&["add", "agenda", "export"]);
+        let input = Input::new("");
+        assert_eq!(
+            Completer::listing().suggestions(&input, 0, &candidates),
+            ["add", "agenda", "export"]
+        );
+        assert!(Completer::default()
+            .suggestions(&input, 0, &candidates)
+            .is_empty());
+    }
+
+    #[test]
+    fn paths_list_directories_with_a_slash_and_hide_dot_files() {
+        let dir = std::env::temp_dir().join(format!("complete-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("notes")).unwrap();
+        fs::write(dir.join("notes.md"), "").unwrap();
+        fs::write(dir.join(".hidden"), "").unwrap();
+        let base = format!("{}/", dir.display());
+
+        assert_eq!(
+            path_candidates(&format!("{}no", base)),
+            [format!("{}notes.md", base), format!("{}notes/", base)]
+        );
+        assert_eq!(path_candidates(&base).len(), 2);
+        assert_eq!(path_candidates(&format!("{}.", base)).len(), 3);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,430 @@
+use chrono::Weekday;
+use serde::{de, Deserialize, Deserializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::time::SystemTime;
+#[cfg(feature = "tui")]
+use tui::style::Color;
+
+#[cfg(feature = "tui")]
+use crate::actions::Keymap;
+#[cfg(feature = "tui")]
+use crate::cursor::CursorStyle;
+use crate::escalation::EscalationRule;
+#[cfg(feature = "tui")]
+use crate::fields::{Field, DEFAULT_FIELDS};
+use crate::timetracking::ConfigDuration;
+use crate::urgency::UrgencyWeights;
+use crate::zone::Zone;
+use crate::Error;
+
+const CONFIG_PATH: &str = "./data/config.json";
+
+/// User settings from `./data/config.json`. Every field is optional and a
+/// missing file means all defaults.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct Config {
+    /// Hours allowed per week, by category.
+    pub weekly_budgets: BTreeMap<String, f64>,
+    pub week_start: Weekday,
+    /// Hours to work per weekday, e.g. `{"Mon": 8}`. Missing days are 0.
+    pub daily_targets: HashMap<Weekday, f64>,
+    /// Flash the Done column when todos are moved into it.
+    pub flash_on_done: bool,
+    /// Move the cursor along when the selected todo changes column.
+    pub focus_follows_item: bool,
+    /// Least time between two saves of the DB, changes in between are
+    /// saved together.
+    pub autosave_interval_ms: u64,
+    #[cfg(feature = "tui")]
+    /// Decorations shown after each board item's title, in order, e.g.
+    /// `["priority", "due"]`. The title is always shown.
+    pub board_item_fields: Vec<Field>,
+    /// How long before a todo's due time the TUI announces it.
+    pub due_lead_minutes: i64,
+    /// How long `:snooze` holds a due alert back by default.
+    pub due_snooze_minutes: i64,
+    /// Also send due alerts as desktop notifications.
+    pub due_desktop_notifications: bool,
+    /// What the daily maintenance pass does about todos left in Doing
+    /// without activity. Off when missing.
+    pub stale_doing: Option<StalePolicy>,
+    /// The hours a day the timeline spans, start and end, e.g. `[9, 17]`.
+    pub working_hours: [u32; 2],
+    /// Description length in chars past which the editor warns, e.g. for
+    /// exports into Jira comments. No warning when missing.
+    pub description_soft_limit: Option<usize>,
+    /// The tick, and so the save and alert checks, while no timer, toast
+    /// or highlight needs the screen redrawn. The screen then only redraws
+    /// on input.
+    pub idle_tick_ms: u64,
+    /// Raises priorities as due dates come closer, e.g. High two days
+    /// before and Urgent once overdue. The stored priority stays as it is.
+    /// Nothing is raised when empty.
+    pub priority_escalation: Vec<EscalationRule>,
+    /// When `qq` and Ctrl+q ask before quitting.
+    pub quit_policy: QuitPolicy,
+    /// How quickly the second `q` of `qq` has to follow the first to quit.
+    pub quit_window_ms: u64,
+    /// The IANA zone days, weeks and due dates are reckoned in, e.g.
+    /// "Europe/Berlin"; the system's when unset.
+    pub timezone: Option<Zone>,
+    #[cfg(feature = "tui")]
+    /// The color each todo label shows in, by label name, e.g.
+    /// `{"red": "Magenta"}` for a colorblind palette. Labels not listed
+    /// keep their own color.
+    pub label_colors: BTreeMap<String, Color>,
+    /// A short prefix or Nerd Font glyph shown before the titles of each
+    /// category's todos, e.g. `{"home": "⌂"}`. Categories not listed get
+    /// one from a fixed pool; no glyphs at all when empty.
+    pub category_glyphs: BTreeMap<String, String>,
+    /// What shows instead of `category_glyphs` in ASCII mode, e.g.
+    /// `{"home": "H"}`.
+    pub category_glyphs_ascii: BTreeMap<String, String>,
+    /// Draws glyphs in plain ASCII even when the terminal looks like it
+    /// could do better.
+    pub ascii_only: bool,
+    /// Multipliers for the parts of the urgency score `:set sort urgency`
+    /// and `list --sort urgency` order by.
+    pub urgency_weights: UrgencyWeights,
+    /// A break countdown `timer` starts by itself when a work interval
+    /// ends or is stopped, e.g. "5m". Off when missing.
+    pub auto_break: Option<ConfigDuration>,
+    /// Records those breaks as break entries.
+    pub log_breaks: bool,
+    #[cfg(feature = "tui")]
+    /// How the selected row stands out: "block", "prefix" for a marker
+    /// before it, or "underline+bold".
+    pub cursor_style: CursorStyle,
+    /// Shows the subtask and time progress of the todos in every column,
+    /// not only in Doing.
+    pub progress_everywhere: bool,
+    #[cfg(feature = "tui")]
+    /// Keys in place of an action's defaults, by the action's name as
+    /// help shows it, e.g. `{"collapse column": ["c"], "palette":
+    /// ["Ctrl+p", "F2"]}`.
+    pub keys: Keymap,
+    /// Narrows the board to the category of a timer that starts, until
+    /// it stops.
+    pub filter_follows_timer: bool,
+    /// Asks for the one thing to get done at the first launch of a day.
+    pub intention_prompt: bool,
+    /// The local hour from which the first quit asks how the day went,
+    /// e.g. 17. Off when missing.
+    #[serde(deserialize_with = "hour")]
+    pub reflection_hour: Option<u32>,
+    /// How many todos a column should hold at most, by the column's
+    /// title, e.g. `{"Doing": 3}`. The column's title turns red past it.
+    pub wip_limits: BTreeMap<String, usize>,
+    /// `:` commands run in order once the app has loaded, e.g. `["tab
+    /// todos", "filter category:work"]`. The first that fails stops the
+    /// rest.
+    pub on_startup: Vec<String>,
+    /// `:` commands run in order on quit, the same way.
+    pub on_quit: Vec<String>,
+}
+
+/// An hour of the day, 0 to 23. A later one would never come.
+fn hour<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    match Option::<u32>::deserialize(deserializer)? {
+        Some(hour) if hour > 23 => Err(de::Error::custom(format!(
+            "hour {} is out of range, use 0 to 23",
+            hour
+        ))),
+        hour => Ok(hour),
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum QuitPolicy {
+    /// Quit without asking.
+    Immediate,
+    /// Ask only while a timer runs or changes aren't saved yet.
+    Guarded,
+    /// Always ask.
+    Confirm,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StaleAction {
+    /// List them when the TUI starts.
+    Warn,
+    /// Move them back to ToDo.
+    Demote,
+}
+
+/// e.g. `{"days": 14, "action": "demote"}`.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct StalePolicy {
+    pub days: i64,
+    pub action: StaleAction,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            weekly_budgets: BTreeMap::new(),
+            week_start: Weekday::Mon,
+            daily_targets: HashMap::new(),
+            flash_on_done: true,
+            focus_follows_item: false,
+            autosave_interval_ms: 1000,
+            #[cfg(feature = "tui")]
+            board_item_fields: DEFAULT_FIELDS.to_vec(),
+            due_lead_minutes: 15,
+            due_snooze_minutes: 10,
+            due_desktop_notifications: false,
+            stale_doing: None,
+            working_hours: [9, 17],
+            description_soft_limit: None,
+            idle_tick_ms: 2000,
+            priority_escalation: vec![],
+            quit_policy: QuitPolicy::Guarded,
+            quit_window_ms: 1000,
+            timezone: None,
+            #[cfg(feature = "tui")]
+            label_colors: BTreeMap::new(),
+            category_glyphs: BTreeMap::new(),
+            category_glyphs_ascii: BTreeMap::new(),
+            ascii_only: false,
+            urgency_weights: UrgencyWeights::default(),
+            auto_break: None,
+            log_breaks: false,
+            #[cfg(feature = "tui")]
+            cursor_style: CursorStyle::Block,
+            progress_everywhere: false,
+            #[cfg(feature = "tui")]
+            keys: Keymap::new(),
+            filter_follows_timer: false,
+            intention_prompt: false,
+            reflection_hour: None,
+            wip_limits: BTreeMap::new(),
+            on_startup: vec![],
+            on_quit: vec![],
+        }
+    }
+}
+
+/// Notices when `./data/config.json` is written, by its modification
+/// time, for the TUI to reload it.
+pub struct ConfigWatch {
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatch {
+    /// Watches from the config as it is now.
+    pub fn new() -> ConfigWatch {
+        ConfigWatch {
+            modified: modified(),
+        }
+    }
+
+    /// Whether the file changed, appeared or went away since the last
+    /// call.
+    pub fn changed(&mut self) -> bool {
+        let modified = modified();
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+fn modified() -> Option<SystemTime> {
+    fs::metadata(CONFIG_PATH)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// The line of the config file a parse error points at, trimmed.
+pub fn line(number: usize) -> Option<String> {
+    let content = fs::read_to_string(CONFIG_PATH).ok()?;
+    let line = content.lines().nth(number.checked_sub(1)?)?.trim();
+    Some(line.to_string())
+}
+
+/// Sets top-level entries of the config file and creates it when there
+/// is none. The rest of the file stays as it was written: an entry
+/// already there gets its value replaced in place, a new one goes last.
+pub fn set(entries: Vec<(&str, serde_json::Value)>) -> Result<(), Error> {
+    let mut content = match fs::read_to_string(CONFIG_PATH) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => "{}\n".to_string(),
+        Err(err) => return Err(err.into()),
+    };
+    // a broken file is reported rather than spliced into
+    serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&content)
+        .map_err(Error::ParseConfigError)?;
+    for (name, value) in entries {
+        content = splice(&content, name, &value)?;
+    }
+    crate::write_atomic(CONFIG_PATH, &content)
+}
+
+/// `content`, a JSON object, with its top-level entry `name` set to
+/// `value`.
+fn splice(content: &str, name: &str, value: &serde_json::Value) -> Result<String, Error> {
+    let (entries, close) = layout(content);
+    let indent = match entries.first() {
+        Some((_, value)) => line_indent(content, value.start),
+        None => "  ",
+    };
+    let mut spliced = content.to_string();
+    match entries.iter().find(|(key, _)| key == name) {
+        Some((_, range)) => {
+            let indent = line_indent(content, range.start);
+            spliced.replace_range(range.clone(), &pretty(value, indent)?);
+        }
+        None => {
+            let before = content[..close].trim_end();
+            let separator = if entries.is_empty() { "" } else { "," };
+            spliced = format!(
+                "{}{}\n{}{}: {}\n{}",
+                before,
+                separator,
+                indent,
+                serde_json::to_string(name)?,
+                pretty(value, indent)?,
+                &content[close..]
+            );
+        }
+    }
+    Ok(spliced)
+}
+
+/// The top-level entries of the JSON object `content`, by name and the
+/// byte range of their value, and where its closing brace is.
+fn layout(content: &str) -> (Vec<(String, Range<usize>)>, usize) {
+    let mut entries = vec![];
+    let mut depth = 0;
+    let mut string_start = None;
+    let mut escaped = false;
+    let mut key = None;
+    let mut value_start = None;
+    let mut close = content.len();
+    for (i, c) in content.char_indices() {
+        if let Some(start) = string_start {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => {
+                    string_start = None;
+                    if depth == 1 && value_start.is_none() {
+                        key = serde_json::from_str::<String>(&content[start..=i]).ok();
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => string_start = Some(i),
+            ':' if depth == 1 => value_start = Some(i + 1),
+            ',' | '}' if depth == 1 => {
+                if let (Some(name), Some(start)) = (key.take(), value_start.take()) {
+                    let value = &content[start..i];
+                    let start = start + (value.len() - value.trim_start().len());
+                    entries.push((name, start..start + value.trim().len()));
+                }
+                if c == '}' {
+                    close = i;
+                    depth -= 1;
+                }
+            }
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    (entries, close)
+}
+
+/// The whitespace the line holding byte `at` of `content` starts with.
+fn line_indent(content: &str, at: usize) -> &str {
+    let line = &content[content[..at].rfind('\n').map_or(0, |n| n + 1)..];
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// `value` formatted over several lines, each after the first indented
+/// by `indent`.
+fn pretty(value: &serde_json::Value, indent: &str) -> Result<String, Error> {
+    let text = serde_json::to_string_pretty(value)?;
+    Ok(text.replace('\n', &format!("\n{}", indent)))
+}
+
+/// The config in ./data/config.json, the defaults when there is none.
+pub fn load() -> Result<Config, Error> {
+    match fs::read_to_string(CONFIG_PATH) {
+        Ok(content) => serde_json::from_str(&content).map_err(Error::ParseConfigError),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const CONFIG: &str = "{\n    \"week_start\": \"Sun\",\n    \"keys\": {\"hide\": [\"x\"]},\n    \"ascii_only\": true\n}\n";
+
+    #[test]
+    fn the_reflection_hour_is_an_hour_of_the_day() {
+        let hour = |value: serde_json::Value| {
+            serde_json::from_value::<Config>(json!({ "reflection_hour": value }))
+                .map(|config| config.reflection_hour)
+                .map_err(|err| err.to_string())
+        };
+        assert_eq!(hour(json!(0)), Ok(Some(0)));
+        assert_eq!(hour(json!(23)), Ok(Some(23)));
+        assert_eq!(hour(json!(null)), Ok(None));
+        assert_eq!(
+            hour(json!(24)),
+            Err("hour 24 is out of range, use 0 to 23".to_string())
+        );
+        let missing: Config = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(missing.reflection_hour, None);
+    }
+
+    #[test]
+    fn an_entry_is_replaced_in_place() {
+        let spliced = splice(CONFIG, "keys", &json!({ "focus left": ["h"] })).unwrap();
+        assert_eq!(
+            spliced,
+            "{\n    \"week_start\": \"Sun\",\n    \"keys\": {\n      \"focus left\": [\n        \"h\"\n      ]\n    },\n    \"ascii_only\": true\n}\n"
+        );
+    }
+
+    #[test]
+    fn a_new_entry_goes_last_with_the_file_indent() {
+        let spliced = splice(CONFIG, "wip_limits", &json!(3)).unwrap();
+        assert_eq!(
+            spliced,
+            "{\n    \"week_start\": \"Sun\",\n    \"keys\": {\"hide\": [\"x\"]},\n    \"ascii_only\": true,\n    \"wip_limits\": 3\n}\n"
+        );
+        let spliced = splice("{}\n", "ascii_only", &json!(true)).unwrap();
+        assert_eq!(spliced, "{\n  \"ascii_only\": true\n}\n");
+    }
+
+    #[test]
+    fn strings_and_nested_objects_do_not_confuse_the_layout() {
+        let content = "{\"a\": \"x, } \\\" y\", \"b\": {\"a\": [1, {\"c\": 2}]}, \"c\": 3}";
+        let spliced = splice(content, "c", &json!(4)).unwrap();
+        assert_eq!(
+            spliced,
+            "{\"a\": \"x, } \\\" y\", \"b\": {\"a\": [1, {\"c\": 2}]}, \"c\": 4}"
+        );
+        let parsed: serde_json::Value =
+            serde_json::from_str(&splice(content, "a", &json!(1)).unwrap()).unwrap();
+        assert_eq!(
+            parsed,
+            json!({ "a": 1, "b": { "a": [1, { "c": 2 }] }, "c": 3 })
+        );
+    }
+}
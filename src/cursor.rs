@@ -0,0 +1,80 @@
+use serde::Deserialize;
+use std::sync::Mutex;
+use tui::style::{Color, Modifier, Style};
+
+/// How the selected row stands out, `cursor_style` in the config. Every
+/// list, table and picker asks [`current`] for it.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum CursorStyle {
+    /// Black on yellow, bold.
+    #[default]
+    #[serde(rename = "block")]
+    Block,
+    /// A "▶ " before the row, which keeps its colors.
+    #[serde(rename = "prefix")]
+    Prefix,
+    /// The row underlined and bold.
+    #[serde(rename = "underline+bold")]
+    UnderlineBold,
+}
+
+const PREFIX: &str = "▶ ";
+
+impl CursorStyle {
+    /// The `highlight_style` of a list or table.
+    pub fn highlight(self) -> Style {
+        match self {
+            CursorStyle::Block => Style::default()
+                .bg(Color::Yellow)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            CursorStyle::Prefix => Style::default(),
+            CursorStyle::UnderlineBold => Style::default()
+                .add_modifier(Modifier::UNDERLINED)
+                .add_modifier(Modifier::BOLD),
+        }
+    }
+
+    /// The `highlight_symbol` of a list or table.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            CursorStyle::Prefix => PREFIX,
+            _ => "",
+        }
+    }
+
+    /// The cells the symbol takes from every row of a list with a
+    /// selection, the unselected ones padded to match.
+    pub fn width(self) -> u16 {
+        match self {
+            CursorStyle::Prefix => 2,
+            _ => 0,
+        }
+    }
+
+    /// For a row drawn by hand: what goes before it and its style on top
+    /// of `base`.
+    pub fn row(self, selected: bool, base: Style) -> (&'static str, Style) {
+        match (self, selected) {
+            (CursorStyle::Prefix, true) => (PREFIX, base),
+            (CursorStyle::Prefix, false) => ("  ", base),
+            (_, true) => ("", base.patch(self.highlight())),
+            (_, false) => ("", base),
+        }
+    }
+}
+
+static CURRENT: Mutex<CursorStyle> = Mutex::new(CursorStyle::Block);
+
+pub fn set(style: CursorStyle) {
+    *CURRENT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = style;
+}
+
+/// The cursor style in use.
+pub fn current() -> CursorStyle {
+    *CURRENT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,181 @@
+use chrono::prelude::*;
+use tui::{
+    style::{Color, Style},
+    text::{Span, Spans},
+};
+
+use crate::calendar;
+use crate::config::Config;
+use crate::daynotes;
+use crate::estimate;
+use crate::filter::{due_in, DueWindow};
+use crate::flextime;
+use crate::pomodoro;
+use crate::scroll::Section;
+use crate::subtasks::effective_due;
+use crate::timetracking::{
+    format_duration, format_signed_duration, time_per_category, week_bounds, TimeEntry,
+};
+use crate::zone;
+use crate::{Todo, TodoStatus};
+
+fn line<'a>(text: impl Into<String>) -> Spans<'a> {
+    Spans::from(vec![Span::raw(text.into())])
+}
+
+fn dim<'a>(text: impl Into<String>) -> Spans<'a> {
+    Spans::from(vec![Span::styled(
+        text.into(),
+        Style::default().fg(Color::DarkGray),
+    )])
+}
+
+/// The sections of the Home tab, from the current data.
+pub fn sections<'a>(
+    todos: &[Todo],
+    entries: &[TimeEntry],
+    config: &Config,
+    now: DateTime<Utc>,
+) -> Vec<Section<'a>> {
+    let local = now.with_timezone(&zone::current());
+    let mut sections = vec![Section {
+        title: "Personal Work Suit CLI".to_string(),
+        lines: vec![
+            line("Press 't' to access To-Do, 'i' to access timers and 'm' to check time tracking."),
+            dim("':' opens the command line, 'qq' quits."),
+        ],
+    }];
+
+    let open: Vec<&Todo> = todos
+        .iter()
+        .filter(|t| t.status() != TodoStatus::Done)
+        .collect();
+    let days_off = calendar::read_calendar().unwrap_or_default();
+    let mut today = vec![];
+    let notes = daynotes::read_day_notes().unwrap_or_default();
+    if let Some(intention) =
+        daynotes::note_for(&notes, local.date_naive()).and_then(|n| n.intention.clone())
+    {
+        today.push(Spans::from(vec![Span::styled(
+            format!("→ {}", intention),
+            Style::default().fg(Color::Yellow),
+        )]));
+    }
+    for window in [DueWindow::Overdue, DueWindow::Today] {
+        for todo in open
+            .iter()
+            .filter(|t| due_in(window, t, todos, local, config.week_start))
+        {
+            let label = match (window, effective_due(todo, todos)) {
+                (DueWindow::Overdue, Some(due)) => {
+                    // days off and weekends don't make a todo later
+                    match calendar::working_days_between(
+                        config,
+                        &days_off,
+                        due.with_timezone(&zone::current()).date_naive(),
+                        local.date_naive(),
+                    ) {
+                        0 => "overdue".to_string(),
+                        1 => "overdue 1 working day".to_string(),
+                        days => format!("overdue {} working days", days),
+                    }
+                }
+                _ => "due today".to_string(),
+            };
+            today.push(line(format!("{} ({})", todo.title, label)));
+        }
+    }
+    if today.is_empty() {
+        today.push(dim("nothing due today"));
+    }
+    let completed = todos
+        .iter()
+        .filter(|t| t.status() == TodoStatus::Done)
+        .filter_map(|t| t.completed_at)
+        .filter(|at| at.with_timezone(&zone::current()).date_naive() == local.date_naive())
+        .count();
+    today.push(dim(format!("✔ {} completed today", completed)));
+    sections.push(Section {
+        title: "Today".to_string(),
+        lines: today,
+    });
+
+    sections.push(Section {
+        title: "Remaining work".to_string(),
+        lines: vec![line(estimate::remaining(todos, None).label())],
+    });
+
+    let (from, to) = week_bounds(local, config.week_start);
+    let totals = time_per_category(entries, from, to, now);
+    let mut week: Vec<Spans> = totals
+        .iter()
+        .map(|(category, tracked)| line(format!("{:<16} {}", category, format_duration(*tracked))))
+        .collect();
+    if week.is_empty() {
+        week.push(dim("nothing tracked yet"));
+    }
+    sections.push(Section {
+        title: "Tracked this week".to_string(),
+        lines: week,
+    });
+
+    let balance = flextime::read_overrides().and_then(|overrides| {
+        let calendar = calendar::read_calendar()?;
+        Ok(flextime::month_balance(
+            entries, config, &overrides, &calendar, now,
+        ))
+    });
+    sections.push(Section {
+        title: "Flex time".to_string(),
+        lines: vec![match balance {
+            Ok(balance) => line(format!("{} this month", format_signed_duration(balance))),
+            Err(err) => dim(err.to_string()),
+        }],
+    });
+
+    let today = local.date_naive();
+    let mut off = vec![];
+    if let Some(kind) = calendar::day_off(&days_off, today) {
+        off.push(line(format!(
+            "today is a {}, back on {}",
+            kind.label(),
+            calendar::next_working_day(config, &days_off, today).format("%a %Y-%m-%d")
+        )));
+    }
+    for range in days_off
+        .iter()
+        .filter(|r| r.from > today && r.from <= today + chrono::Duration::days(30))
+    {
+        off.push(line(if range.from == range.to {
+            format!(
+                "{} {}",
+                range.kind.label(),
+                range.from.format("%a %Y-%m-%d")
+            )
+        } else {
+            format!(
+                "{} {} to {}",
+                range.kind.label(),
+                range.from.format("%a %Y-%m-%d"),
+                range.to.format("%a %Y-%m-%d")
+            )
+        }));
+    }
+    if !off.is_empty() {
+        sections.push(Section {
+            title: "Days off".to_string(),
+            lines: off,
+        });
+    }
+
+    let pomodoros =
+        pomodoro::read_history().map(|history| pomodoro::stats(&history, config.week_start, now));
+    sections.push(Section {
+        title: "Pomodoros".to_string(),
+        lines: vec![match pomodoros {
+            Ok(stats) => line(pomodoro::summary(&stats)),
+            Err(err) => dim(err.to_string()),
+        }],
+    });
+    sections
+}
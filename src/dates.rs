@@ -0,0 +1,116 @@
+use chrono::prelude::*;
+use chrono::Days;
+
+use crate::zone;
+
+/// Parses a due date typed by the user: `YYYY-MM-DD HH:MM`, or a bare
+/// `YYYY-MM-DD` meaning the end of that day. Both are in the configured
+/// zone.
+///
+/// ```
+/// use work_time_cli::{parse_due, set_zone, Zone};
+///
+/// set_zone(Zone::parse("Europe/Berlin").unwrap());
+/// let due = parse_due("2024-05-01").unwrap();
+/// assert_eq!(due.to_rfc3339(), "2024-05-01T21:59:59+00:00");
+/// assert!(parse_due("May 1st").is_none());
+/// ```
+pub fn parse_due(input: &str) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+    let naive = match NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M") {
+        Ok(naive) => naive,
+        Err(_) => NaiveDate::parse_from_str(input, "%Y-%m-%d")
+            .ok()?
+            .and_hms_opt(23, 59, 59)?,
+    };
+    zone::current()
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|due| due.with_timezone(&Utc))
+}
+
+/// Parses a day in a filter expression: `YYYY-MM-DD`, `today`, `yesterday`,
+/// `tomorrow`, or a signed count of days or weeks from `today` such as
+/// `-30d` or `+2w`.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use work_time_cli::parse_day;
+///
+/// let today = NaiveDate::from_ymd_opt(2024, 5, 15).unwrap();
+/// assert_eq!(parse_day("+2w", today), NaiveDate::from_ymd_opt(2024, 5, 29));
+/// assert_eq!(parse_day("yesterday", today), NaiveDate::from_ymd_opt(2024, 5, 14));
+/// assert_eq!(parse_day("soon", today), None);
+/// ```
+pub fn parse_day(input: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match input {
+        "today" => return Some(today),
+        "yesterday" => return today.pred_opt(),
+        "tomorrow" => return today.succ_opt(),
+        _ => {}
+    }
+    if let Ok(day) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Some(day);
+    }
+    let sign = match input.chars().next()? {
+        '-' => -1,
+        '+' => 1,
+        _ => return None,
+    };
+    let rest = &input[1..];
+    let (at, unit) = rest.char_indices().last()?;
+    let count: i64 = rest[..at].parse().ok()?;
+    let days = match unit {
+        'd' => count,
+        'w' => count.checked_mul(7)?,
+        _ => return None,
+    };
+    match days.checked_mul(sign)? {
+        days if days < 0 => today.checked_sub_days(Days::new(days.unsigned_abs())),
+        days => today.checked_add_days(Days::new(days as u64)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn parses_names_dates_and_offsets() {
+        let today = day("2024-05-15");
+        assert_eq!(parse_day("today", today), Some(today));
+        assert_eq!(parse_day("yesterday", today), Some(day("2024-05-14")));
+        assert_eq!(parse_day("tomorrow", today), Some(day("2024-05-16")));
+        assert_eq!(parse_day("2023-01-02", today), Some(day("2023-01-02")));
+        assert_eq!(parse_day("-30d", today), Some(day("2024-04-15")));
+        assert_eq!(parse_day("+2w", today), Some(day("2024-05-29")));
+        assert_eq!(parse_day("+0d", today), Some(today));
+    }
+
+    #[test]
+    fn rejects_malformed_offsets() {
+        let today = day("2024-05-15");
+        for input in ["", "+", "-", "+d", "3d", "+3x", "+1é", "-é", "+3dd", "soon"] {
+            assert_eq!(parse_day(input, today), None, "{}", input);
+        }
+    }
+
+    #[test]
+    fn huge_offsets_are_none_instead_of_panicking() {
+        let today = day("2024-05-15");
+        for input in [
+            "+9223372036854775807d",
+            "-9223372036854775807d",
+            "+-9223372036854775808d",
+            "-2000000000000000000w",
+            "+106751991167300d",
+            "+100000000d",
+        ] {
+            assert_eq!(parse_day(input, today), None, "{}", input);
+        }
+    }
+}
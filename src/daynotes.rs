@@ -0,0 +1,227 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+
+use crate::config::Config;
+use crate::{write_atomic, Error};
+
+const DAY_NOTES_PATH: &str = "./data/day_notes.json";
+
+/// What was written down for a local day: the one thing meant to get done
+/// and, in the evening, how it went.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DayNote {
+    pub date: NaiveDate,
+    #[serde(default)]
+    pub intention: Option<String>,
+    #[serde(default)]
+    pub reflection: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoteKind {
+    Intention,
+    Reflection,
+}
+
+impl NoteKind {
+    pub fn question(self) -> &'static str {
+        match self {
+            NoteKind::Intention => "What's the one thing today? (Esc: skip)",
+            NoteKind::Reflection => "How did today go? (Esc: skip)",
+        }
+    }
+
+    fn text(self, note: &DayNote) -> Option<&String> {
+        match self {
+            NoteKind::Intention => note.intention.as_ref(),
+            NoteKind::Reflection => note.reflection.as_ref(),
+        }
+    }
+}
+
+pub fn read_day_notes() -> Result<Vec<DayNote>, Error> {
+    match fs::read_to_string(DAY_NOTES_PATH) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// The note of the local day `date`, if anything was written for it.
+pub fn note_for(notes: &[DayNote], date: NaiveDate) -> Option<&DayNote> {
+    notes.iter().find(|n| n.date == date)
+}
+
+/// Whether `kind` was written for `date` already.
+pub fn has(notes: &[DayNote], date: NaiveDate, kind: NoteKind) -> bool {
+    note_for(notes, date).is_some_and(|note| kind.text(note).is_some())
+}
+
+/// Stores `text` as the `kind` of the day `date`, replacing what was
+/// there. An empty text stores nothing.
+pub fn record(date: NaiveDate, kind: NoteKind, text: &str) -> Result<(), Error> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(());
+    }
+    let mut notes = read_day_notes()?;
+    let index = match notes.iter().position(|n| n.date == date) {
+        Some(index) => index,
+        None => {
+            notes.push(DayNote {
+                date,
+                intention: None,
+                reflection: None,
+            });
+            notes.sort_by_key(|n| n.date);
+            notes.iter().position(|n| n.date == date).unwrap_or(0)
+        }
+    };
+    match kind {
+        NoteKind::Intention => notes[index].intention = Some(text.to_string()),
+        NoteKind::Reflection => notes[index].reflection = Some(text.to_string()),
+    }
+    write_atomic(DAY_NOTES_PATH, &serde_json::to_string_pretty(&notes)?)
+}
+
+/// Whether the first launch on `today` asks for the intention: with
+/// `intention_prompt` on, the last launch on another day, however long
+/// ago, and none written for today yet.
+pub fn asks_intention(
+    config: &Config,
+    notes: &[DayNote],
+    last_launch: Option<NaiveDate>,
+    today: NaiveDate,
+) -> bool {
+    config.intention_prompt && last_launch != Some(today) && !has(notes, today, NoteKind::Intention)
+}
+
+/// Whether quitting at the local time `now` asks for the reflection: at
+/// or past `reflection_hour`, not asked yet that day and none written.
+pub fn asks_reflection(
+    config: &Config,
+    notes: &[DayNote],
+    last_offered: Option<NaiveDate>,
+    now: NaiveDateTime,
+) -> bool {
+    let today = now.date();
+    config
+        .reflection_hour
+        .is_some_and(|hour| now.hour() >= hour)
+        && last_offered != Some(today)
+        && !has(notes, today, NoteKind::Reflection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn note(date: &str, intention: Option<&str>, reflection: Option<&str>) -> DayNote {
+        DayNote {
+            date: day(date),
+            intention: intention.map(str::to_string),
+            reflection: reflection.map(str::to_string),
+        }
+    }
+
+    fn prompts(intention_prompt: bool, reflection_hour: Option<u32>) -> Config {
+        Config {
+            intention_prompt,
+            reflection_hour,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn the_intention_is_asked_once_on_a_new_day() {
+        let config = prompts(true, None);
+        let today = day("2024-05-15");
+        // launched at 23:59 and again just past midnight
+        assert!(asks_intention(&config, &[], Some(day("2024-05-14")), today));
+        assert!(!asks_intention(&config, &[], Some(today), today));
+        assert!(asks_intention(&config, &[], None, today));
+        assert!(!asks_intention(&prompts(false, None), &[], None, today));
+    }
+
+    #[test]
+    fn the_intention_is_asked_after_skipped_days_unless_written() {
+        let config = prompts(true, None);
+        let today = day("2024-05-20");
+        let last_launch = Some(day("2024-05-16"));
+        assert!(asks_intention(&config, &[], last_launch, today));
+
+        // a note from the last launch is not today's
+        let notes = [note("2024-05-16", Some("ship it"), None)];
+        assert!(asks_intention(&config, &notes, last_launch, today));
+        let notes = [note("2024-05-20", Some("ship it"), None)];
+        assert!(!asks_intention(&config, &notes, last_launch, today));
+        // a reflection alone doesn't count
+        let notes = [note("2024-05-20", None, Some("fine"))];
+        assert!(asks_intention(&config, &notes, last_launch, today));
+    }
+
+    #[test]
+    fn the_reflection_is_asked_from_its_hour_until_midnight() {
+        let config = prompts(false, Some(17));
+        assert!(!asks_reflection(&config, &[], None, at("2024-05-15 16:59")));
+        assert!(asks_reflection(&config, &[], None, at("2024-05-15 17:00")));
+        assert!(asks_reflection(&config, &[], None, at("2024-05-15 23:59")));
+        // past midnight it is the next day, before the hour
+        let offered = Some(day("2024-05-15"));
+        assert!(!asks_reflection(
+            &config,
+            &[],
+            offered,
+            at("2024-05-16 00:00")
+        ));
+        assert!(!asks_reflection(
+            &config,
+            &[],
+            offered,
+            at("2024-05-15 18:00")
+        ));
+        assert!(asks_reflection(
+            &config,
+            &[],
+            offered,
+            at("2024-05-16 17:00")
+        ));
+
+        // an hour of 0 asks all day
+        let midnight = prompts(false, Some(0));
+        assert!(asks_reflection(
+            &midnight,
+            &[],
+            None,
+            at("2024-05-16 00:00")
+        ));
+        assert!(!asks_reflection(
+            &prompts(false, None),
+            &[],
+            None,
+            at("2024-05-16 23:00")
+        ));
+    }
+
+    #[test]
+    fn the_reflection_is_asked_after_skipped_days_unless_written() {
+        let config = prompts(false, Some(17));
+        let now = at("2024-05-20 18:30");
+        let offered = Some(day("2024-05-16"));
+        assert!(asks_reflection(&config, &[], offered, now));
+        let notes = [note("2024-05-16", None, Some("fine"))];
+        assert!(asks_reflection(&config, &notes, offered, now));
+        let notes = [note("2024-05-20", None, Some("fine"))];
+        assert!(!asks_reflection(&config, &notes, offered, now));
+    }
+}
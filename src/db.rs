@@ -0,0 +1,270 @@
+use chrono::prelude::*;
+use std::cell::RefCell;
+use std::mem;
+
+use crate::timetracking::{self, TimeEntry};
+use crate::txn;
+use crate::{check_protected, next_todo_id, read_db, stamp_updates, Error, Todo, TodoStatus};
+
+/// The todos and time entries together, as a `Storage` hands them out.
+#[derive(Clone, Debug, Default)]
+pub struct Db {
+    pub todos: Vec<Todo>,
+    pub entries: Vec<TimeEntry>,
+}
+
+/// Where a `Db` is kept. Every change goes through `transaction`, which
+/// saves all of it or nothing and holds the same rules the TUI does: a
+/// todo keeps its id and `created_at`, a status only changes through
+/// `Todo::set_status`, and every todo that changed gets `updated_at`.
+pub trait Storage {
+    /// The todos and time entries as they are now.
+    fn load(&self) -> Result<Db, Error>;
+
+    /// Lets `change` edit the `Db` and saves it, unless `change` or the
+    /// checks fail, which leaves it as it was.
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use work_time_cli::{add_todo, Db, MemoryStorage, Storage, Todo};
+    ///
+    /// let storage = MemoryStorage::new(Db::default());
+    /// let id = storage.transaction(|db| {
+    ///     let todo = Todo::new("Water the plants".to_string(), "home".to_string(), Utc::now());
+    ///     Ok(add_todo(db, todo))
+    /// })?;
+    /// assert_eq!(storage.load()?.todos[0].id, id);
+    /// # Ok::<(), work_time_cli::Error>(())
+    /// ```
+    fn transaction<T>(&self, change: impl FnOnce(&mut Db) -> Result<T, Error>) -> Result<T, Error>;
+}
+
+/// The files under ./data the TUI and the commands use, relative to the
+/// working directory. A transaction takes the DB lock, waiting about two
+/// seconds for another instance to release it before it fails with
+/// `Error::DBLockedError`.
+///
+/// ```no_run
+/// use work_time_cli::{JsonStorage, Storage};
+///
+/// let db = JsonStorage.load()?;
+/// println!("{} todos, {} time entries", db.todos.len(), db.entries.len());
+/// # Ok::<(), work_time_cli::Error>(())
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonStorage;
+
+impl Storage for JsonStorage {
+    fn load(&self) -> Result<Db, Error> {
+        Ok(Db {
+            todos: read_db()?,
+            entries: timetracking::read_time_entries()?,
+        })
+    }
+
+    fn transaction<T>(&self, change: impl FnOnce(&mut Db) -> Result<T, Error>) -> Result<T, Error> {
+        txn::transaction(|txn| {
+            let mut db = Db {
+                todos: mem::take(&mut txn.todos),
+                entries: mem::take(&mut txn.entries),
+            };
+            let result = change(&mut db);
+            txn.todos = db.todos;
+            txn.entries = db.entries;
+            result
+        })
+    }
+}
+
+/// A `Db` kept in memory only, for tests and previews.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    db: RefCell<Db>,
+}
+
+impl MemoryStorage {
+    pub fn new(db: Db) -> MemoryStorage {
+        MemoryStorage {
+            db: RefCell::new(db),
+        }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load(&self) -> Result<Db, Error> {
+        Ok(self.db.borrow().clone())
+    }
+
+    fn transaction<T>(&self, change: impl FnOnce(&mut Db) -> Result<T, Error>) -> Result<T, Error> {
+        let before = self.load()?;
+        let mut db = before.clone();
+        let result = change(&mut db)?;
+        check_protected(&before.todos, &db.todos)?;
+        stamp_updates(&before.todos, &mut db.todos, Utc::now());
+        *self.db.borrow_mut() = db;
+        Ok(result)
+    }
+}
+
+/// Adds `todo` under the next free id and returns that id. Ids are never
+/// reused, whatever id `todo` had is replaced.
+///
+/// ```
+/// use chrono::Utc;
+/// use work_time_cli::{add_todo, Db, Todo};
+///
+/// let mut db = Db::default();
+/// let first = add_todo(&mut db, Todo::new("Call the bank".into(), "errands".into(), Utc::now()));
+/// let second = add_todo(&mut db, Todo::new("Pay rent".into(), "errands".into(), Utc::now()));
+/// assert_eq!((first, second), (1, 2));
+/// ```
+pub fn add_todo(db: &mut Db, todo: Todo) -> usize {
+    push_todo(&mut db.todos, todo)
+}
+
+/// `add_todo` on the todos alone.
+pub fn push_todo(todos: &mut Vec<Todo>, mut todo: Todo) -> usize {
+    todo.id = next_todo_id(todos);
+    let id = todo.id;
+    todos.push(todo);
+    id
+}
+
+/// Moves the todo `id` to the column of `status` at `now`. Returns false
+/// when there is no such todo.
+///
+/// ```
+/// use chrono::Utc;
+/// use work_time_cli::{add_todo, move_todo, Db, Todo, TodoStatus};
+///
+/// let mut db = Db::default();
+/// let id = add_todo(&mut db, Todo::new("Pay rent".into(), "home".into(), Utc::now()));
+/// assert!(move_todo(&mut db, id, TodoStatus::Done, Utc::now()));
+/// assert_eq!(db.todos[0].status(), TodoStatus::Done);
+/// assert!(db.todos[0].completed_at.is_some());
+/// assert!(!move_todo(&mut db, id + 1, TodoStatus::Done, Utc::now()));
+/// ```
+pub fn move_todo(db: &mut Db, id: usize, status: TodoStatus, now: DateTime<Utc>) -> bool {
+    match db.todos.iter_mut().find(|t| t.id == id) {
+        Some(todo) => {
+            todo.set_status(status, now);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Deletes the todo `id` and returns it, None when there is no such todo.
+/// Its subtasks and time entries stay, pointing at an id no todo has,
+/// as after a delete in the TUI.
+///
+/// ```
+/// use chrono::Utc;
+/// use work_time_cli::{add_todo, delete_todo, Db, Todo};
+///
+/// let mut db = Db::default();
+/// let id = add_todo(&mut db, Todo::new("Pay rent".into(), "home".into(), Utc::now()));
+/// assert_eq!(delete_todo(&mut db, id).map(|todo| todo.title), Some("Pay rent".to_string()));
+/// assert!(db.todos.is_empty());
+/// ```
+pub fn delete_todo(db: &mut Db, id: usize) -> Option<Todo> {
+    let index = db.todos.iter().position(|t| t.id == id)?;
+    Some(db.todos.remove(index))
+}
+
+/// Starts tracking time on the todo `id` at `now`, in its category, and
+/// returns the id of the running entry. None when there is no such todo
+/// or a timer already runs on it.
+///
+/// ```
+/// use chrono::Utc;
+/// use work_time_cli::{add_todo, start_timer, Db, Todo};
+///
+/// let mut db = Db::default();
+/// let id = add_todo(&mut db, Todo::new("Write report".into(), "work".into(), Utc::now()));
+/// let entry = start_timer(&mut db, id, Utc::now()).unwrap();
+/// assert_eq!(db.entries[0].id, entry);
+/// assert_eq!(db.entries[0].todo_id, Some(id));
+/// assert_eq!(start_timer(&mut db, id, Utc::now()), None);
+/// ```
+pub fn start_timer(db: &mut Db, id: usize, now: DateTime<Utc>) -> Option<usize> {
+    let category = db.todos.iter().find(|t| t.id == id)?.category.clone();
+    if db
+        .entries
+        .iter()
+        .any(|e| e.end.is_none() && e.todo_id == Some(id))
+    {
+        return None;
+    }
+    Some(timetracking::push_running(
+        &mut db.entries,
+        &category,
+        now,
+        None,
+        Some(id),
+        None,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(title: &str) -> Todo {
+        Todo::new(title.to_string(), "work".to_string(), Utc::now())
+    }
+
+    #[test]
+    fn a_failed_transaction_saves_nothing() {
+        let storage = MemoryStorage::default();
+        storage
+            .transaction(|db| Ok(add_todo(db, todo("kept"))))
+            .unwrap();
+        let failed = storage.transaction(|db| {
+            add_todo(db, todo("dropped"));
+            Err::<(), _>(Error::SchemaError("no".to_string()))
+        });
+        assert!(failed.is_err());
+        let titles: Vec<String> = storage
+            .load()
+            .unwrap()
+            .todos
+            .into_iter()
+            .map(|t| t.title)
+            .collect();
+        assert_eq!(titles, ["kept"]);
+    }
+
+    #[test]
+    fn a_transaction_keeps_ids_and_stamps_changes() {
+        let storage = MemoryStorage::default();
+        let id = storage
+            .transaction(|db| Ok(add_todo(db, todo("report"))))
+            .unwrap();
+        let renumbered = storage.transaction(|db| {
+            db.todos[0].id = id + 1;
+            Ok(())
+        });
+        assert!(matches!(renumbered, Err(Error::ProtectedFieldError(..))));
+
+        let before = storage.load().unwrap().todos[0].modified_at();
+        storage
+            .transaction(|db| Ok(move_todo(db, id, TodoStatus::Doing, Utc::now())))
+            .unwrap();
+        let after = storage.load().unwrap().todos[0].clone();
+        assert_eq!(after.status(), TodoStatus::Doing);
+        assert!(after.modified_at() >= before);
+    }
+
+    #[test]
+    fn a_timer_starts_once_per_todo() {
+        let mut db = Db::default();
+        let first = add_todo(&mut db, todo("first"));
+        let second = add_todo(&mut db, todo("second"));
+        assert_eq!(start_timer(&mut db, first, Utc::now()), Some(1));
+        assert_eq!(start_timer(&mut db, first, Utc::now()), None);
+        assert_eq!(start_timer(&mut db, second, Utc::now()), Some(2));
+        assert_eq!(start_timer(&mut db, second + 1, Utc::now()), None);
+        assert!(db.entries.iter().all(|e| e.end.is_none()));
+    }
+}
@@ -0,0 +1,320 @@
+use chrono::prelude::*;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::time::{Duration, Instant};
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::todo::MAX_DESCRIPTION_CHARS;
+use crate::update_db;
+use crate::widgets::{centered_rect, render_text_area, TextArea};
+
+/// Least time between two saves of the draft while typing.
+const DRAFT_EVERY: Duration = Duration::from_secs(2);
+
+pub enum EditorAction {
+    Continue,
+    Cancel,
+    Save(String),
+}
+
+/// The popup `e` opens to edit a todo's description, counting chars and
+/// words as they are typed. What is typed goes to a draft in the session
+/// every few seconds until it is saved.
+pub struct DescriptionEditor {
+    pub todo_id: usize,
+    text: TextArea,
+    /// The description when the editor opened.
+    original: String,
+    /// The text last handed out as a draft, and when.
+    drafted: String,
+    drafted_at: Option<Instant>,
+    /// Past this many chars the border turns red, from the config.
+    soft_limit: Option<usize>,
+    error: Option<String>,
+}
+
+impl DescriptionEditor {
+    pub fn new(todo_id: usize, description: &str, soft_limit: Option<usize>) -> DescriptionEditor {
+        DescriptionEditor {
+            todo_id,
+            text: TextArea::new(description, Some(MAX_DESCRIPTION_CHARS)),
+            original: description.to_string(),
+            drafted: description.to_string(),
+            drafted_at: None,
+            soft_limit,
+            error: None,
+        }
+    }
+
+    /// Continues from a draft instead of the stored description.
+    pub fn resume(&mut self, draft: &str) {
+        self.text = TextArea::new(draft, Some(MAX_DESCRIPTION_CHARS));
+        self.drafted = draft.to_string();
+    }
+
+    /// Where the session keeps this editor's draft.
+    pub fn draft_key(&self) -> String {
+        draft_key(self.todo_id)
+    }
+
+    /// Whether the text differs from the stored description by more than
+    /// whitespace at its ends.
+    pub fn changed(&self) -> bool {
+        self.text.value().trim() != self.original.trim()
+    }
+
+    /// The text to keep as the draft, when it changed since the last one
+    /// and that is `DRAFT_EVERY` ago.
+    pub fn new_draft(&mut self, now: Instant) -> Option<String> {
+        if self.text.value() == self.drafted
+            || self
+                .drafted_at
+                .is_some_and(|at| now.duration_since(at) < DRAFT_EVERY)
+        {
+            return None;
+        }
+        self.drafted = self.text.value().to_string();
+        self.drafted_at = Some(now);
+        Some(self.drafted.clone())
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> EditorAction {
+        self.error = None;
+        match key.code {
+            KeyCode::Esc => return EditorAction::Cancel,
+            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return EditorAction::Save(self.text.value().to_string())
+            }
+            KeyCode::Char(_) | KeyCode::Enter if self.text.is_full() => {
+                self.error = Some(format!(
+                    "a description holds at most {} chars",
+                    MAX_DESCRIPTION_CHARS
+                ))
+            }
+            _ => {
+                self.text.handle_key(key);
+            }
+        }
+        EditorAction::Continue
+    }
+
+    fn over_limit(&self) -> bool {
+        self.soft_limit
+            .is_some_and(|limit| self.text.chars() > limit)
+    }
+}
+
+pub fn draft_key(todo_id: usize) -> String {
+    format!("description:{}", todo_id)
+}
+
+/// The question asked before an editor opens on a draft, e.g. "Resume
+/// unsaved draft from 2 minutes ago?".
+pub fn resume_question(todo_id: usize, saved_at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let minutes = (now - saved_at).num_minutes().max(0);
+    let ago = match minutes {
+        0 => "just now".to_string(),
+        1 => "1 minute ago".to_string(),
+        2..=59 => format!("{} minutes ago", minutes),
+        60..=119 => "1 hour ago".to_string(),
+        _ if minutes < 24 * 60 => format!("{} hours ago", minutes / 60),
+        _ => format!("{} days ago", minutes / (24 * 60)),
+    };
+    format!(
+        "Resume the unsaved description draft of todo {} from {}? y/n",
+        todo_id, ago
+    )
+}
+
+/// Replaces the description of the todo `id`. Returns false when the todo
+/// no longer exists.
+pub fn set_description(id: usize, description: String) -> Result<bool, String> {
+    update_db(|todos| match todos.iter_mut().find(|t| t.id == id) {
+        Some(todo) => todo.set_description(description).map(|()| true),
+        None => Ok(false),
+    })
+    .map_err(|err| err.to_string())?
+}
+
+pub fn render_description_editor<B: Backend>(
+    rect: &mut Frame<B>,
+    editor: &DescriptionEditor,
+    area: Rect,
+) {
+    let popup = centered_rect(70, 60, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::White))
+        .border_style(Style::default().fg(if editor.over_limit() {
+            Color::Red
+        } else {
+            Color::White
+        }))
+        .title(format!("Description of todo {}", editor.todo_id))
+        .border_type(BorderType::Plain);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+        .split(block.inner(popup));
+
+    let count = format!(
+        " {} chars{} · {} words  ",
+        editor.text.chars(),
+        match editor.soft_limit {
+            Some(limit) => format!(" of {}", limit),
+            None => String::new(),
+        },
+        editor.text.words()
+    );
+    let footer = Spans::from(vec![
+        Span::styled(
+            count,
+            Style::default().fg(if editor.over_limit() {
+                Color::Red
+            } else {
+                Color::Gray
+            }),
+        ),
+        match &editor.error {
+            Some(err) => Span::styled(err.clone(), Style::default().fg(Color::Red)),
+            None => Span::styled(
+                "Ctrl+s: save  Esc: cancel",
+                Style::default().fg(Color::DarkGray),
+            ),
+        },
+    ]);
+
+    rect.render_widget(Clear, popup);
+    rect.render_widget(block, popup);
+    rect.render_widget(Paragraph::new(footer), chunks[1]);
+    render_text_area(rect, &editor.text, chunks[0]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Todo;
+
+    fn press(editor: &mut DescriptionEditor, code: KeyCode) -> EditorAction {
+        editor.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn save(editor: &mut DescriptionEditor) -> String {
+        match editor.handle_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL)) {
+            EditorAction::Save(text) => text,
+            _ => panic!("Ctrl+s didn't save"),
+        }
+    }
+
+    #[test]
+    fn typing_stops_at_the_model_limit_with_a_message() {
+        let full = "x".repeat(MAX_DESCRIPTION_CHARS - 1);
+        let mut editor = DescriptionEditor::new(1, &full, None);
+        press(&mut editor, KeyCode::Char('y'));
+        assert!(editor.error.is_none());
+        for code in [KeyCode::Char('z'), KeyCode::Enter] {
+            press(&mut editor, code);
+            assert_eq!(
+                editor.error.as_deref(),
+                Some("a description holds at most 10000 chars")
+            );
+        }
+        // deleting still works and clears the message
+        press(&mut editor, KeyCode::Backspace);
+        assert!(editor.error.is_none());
+        press(&mut editor, KeyCode::Char('z'));
+
+        let text = save(&mut editor);
+        assert_eq!(text.chars().count(), MAX_DESCRIPTION_CHARS);
+        assert!(text.ends_with('z'));
+        // what the editor hands out fits the todo, one more char doesn't
+        let mut todo = Todo::new("title".to_string(), "work".to_string(), Utc::now());
+        assert_eq!(todo.set_description(text.clone()), Ok(()));
+        assert!(todo.set_description(format!("{}!", text)).is_err());
+        assert_eq!(todo.description(), text);
+    }
+
+    #[test]
+    fn the_soft_limit_only_warns() {
+        let mut editor = DescriptionEditor::new(1, "four", Some(5));
+        assert!(!editor.over_limit());
+        press(&mut editor, KeyCode::Char('!'));
+        assert!(!editor.over_limit());
+        press(&mut editor, KeyCode::Char('!'));
+        assert!(editor.over_limit());
+        assert!(editor.error.is_none());
+        assert_eq!(save(&mut editor), "four!!");
+        assert!(!DescriptionEditor::new(1, &"x".repeat(50), None).over_limit());
+    }
+
+    #[test]
+    fn enter_starts_a_line_and_the_counts_follow() {
+        let mut editor = DescriptionEditor::new(1, "one two", None);
+        press(&mut editor, KeyCode::Enter);
+        for c in "three".chars() {
+            press(&mut editor, KeyCode::Char(c));
+        }
+        assert_eq!(editor.text.value(), "one two\nthree");
+        assert_eq!((editor.text.chars(), editor.text.words()), (13, 3));
+        assert!(matches!(
+            press(&mut editor, KeyCode::Esc),
+            EditorAction::Cancel
+        ));
+    }
+
+    #[test]
+    fn only_changes_beyond_the_ends_count() {
+        let mut editor = DescriptionEditor::new(1, "notes", None);
+        assert!(!editor.changed());
+        press(&mut editor, KeyCode::Enter);
+        assert!(!editor.changed());
+        press(&mut editor, KeyCode::Char('x'));
+        assert!(editor.changed());
+    }
+
+    #[test]
+    fn drafts_go_out_at_most_every_few_seconds() {
+        let start = Instant::now();
+        let mut editor = DescriptionEditor::new(1, "notes", None);
+        assert_eq!(editor.new_draft(start), None);
+        press(&mut editor, KeyCode::Char('a'));
+        assert_eq!(editor.new_draft(start).as_deref(), Some("notesa"));
+        press(&mut editor, KeyCode::Char('b'));
+        assert_eq!(editor.new_draft(start + Duration::from_secs(1)), None);
+        assert_eq!(
+            editor.new_draft(start + DRAFT_EVERY).as_deref(),
+            Some("notesab")
+        );
+        assert_eq!(editor.new_draft(start + 2 * DRAFT_EVERY), None);
+
+        editor.resume("from a draft");
+        assert_eq!(editor.text.value(), "from a draft");
+        assert_eq!(editor.draft_key(), "description:1");
+    }
+
+    #[test]
+    fn the_resume_question_says_how_old_the_draft_is() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 3, 12, 0, 0).unwrap();
+        let ago = |minutes: i64| {
+            let question = resume_question(7, now - chrono::Duration::minutes(minutes), now);
+            question
+                .strip_prefix("Resume the unsaved description draft of todo 7 from ")
+                .and_then(|rest| rest.strip_suffix("? y/n"))
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(ago(-5), "just now");
+        assert_eq!(ago(0), "just now");
+        assert_eq!(ago(1), "1 minute ago");
+        assert_eq!(ago(59), "59 minutes ago");
+        assert_eq!(ago(119), "1 hour ago");
+        assert_eq!(ago(23 * 60 + 59), "23 hours ago");
+        assert_eq!(ago(3 * 24 * 60), "3 days ago");
+    }
+}
@@ -0,0 +1,343 @@
+use crossterm::terminal;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::attachments;
+use crate::lock::LOCK_PATH;
+use crate::schema;
+use crate::{update_db, Todo, TodoStatus, DB_PATH};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Level {
+    Pass,
+    Warn,
+    Fail,
+}
+
+pub struct Check {
+    pub name: &'static str,
+    pub level: Level,
+    pub message: String,
+    pub remedy: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, message: impl Into<String>) -> Check {
+        Check {
+            name,
+            level: Level::Pass,
+            message: message.into(),
+            remedy: None,
+        }
+    }
+
+    fn problem(
+        name: &'static str,
+        level: Level,
+        message: impl Into<String>,
+        remedy: impl Into<String>,
+    ) -> Check {
+        Check {
+            name,
+            level,
+            message: message.into(),
+            remedy: Some(remedy.into()),
+        }
+    }
+}
+
+pub fn check_parses(content: &str) -> (Check, Option<Vec<Todo>>) {
+    match schema::parse_db(content) {
+        Ok(todos) => (Check::pass("db parses", DB_PATH), Some(todos)),
+        Err(err) => (
+            Check::problem(
+                "db parses",
+                Level::Fail,
+                err.to_string(),
+                format!("fix the JSON at the reported line of {}", DB_PATH),
+            ),
+            None,
+        ),
+    }
+}
+
+pub fn check_schema(content: &str, todos: &[Todo]) -> Check {
+    let count = |status: TodoStatus| todos.iter().filter(|t| t.status() == status).count();
+    let counts = format!(
+        "{} todos ({} todo, {} doing, {} done)",
+        todos.len(),
+        count(TodoStatus::Todo),
+        count(TodoStatus::Doing),
+        count(TodoStatus::Done)
+    );
+    if schema::is_legacy(content) {
+        return Check::problem(
+            "schema",
+            Level::Warn,
+            format!("unversioned todo array, {}", counts),
+            "run `migrate --dry-run` to preview the migration, then `migrate`",
+        );
+    }
+    Check::pass(
+        "schema",
+        format!("version {}, {}", schema::DB_VERSION, counts),
+    )
+}
+
+pub fn check_duplicate_ids(todos: &[Todo]) -> Check {
+    let duplicates = duplicate_ids(todos);
+    if duplicates.is_empty() {
+        Check::pass("unique ids", "no duplicate ids")
+    } else {
+        Check::problem(
+            "unique ids",
+            Level::Fail,
+            format!("duplicate ids: {:?}", duplicates),
+            "run `doctor --fix` to give the later copies new ids",
+        )
+    }
+}
+
+pub fn check_attachments(todos: &[Todo]) -> Check {
+    let dangling = attachments::dangling(todos);
+    if dangling.is_empty() {
+        return Check::pass("attachments", "every attached file exists");
+    }
+    let listed: Vec<String> = dangling
+        .iter()
+        .map(|(id, path)| format!("todo {}: {}", id, path.display()))
+        .collect();
+    Check::problem(
+        "attachments",
+        Level::Warn,
+        format!("{} missing: {}", dangling.len(), listed.join(", ")),
+        "restore the files, or remove them with d in the todo view",
+    )
+}
+
+pub fn check_leftover_tmp(db_path: &str) -> Check {
+    let tmp_path = format!("{}.tmp", db_path);
+    if Path::new(&tmp_path).exists() {
+        Check::problem(
+            "interrupted save",
+            Level::Warn,
+            format!("{} exists, a save was interrupted", tmp_path),
+            format!("compare it with {} and delete it", db_path),
+        )
+    } else {
+        Check::pass("interrupted save", "no leftover temp file")
+    }
+}
+
+pub fn check_lock() -> Check {
+    if Path::new(LOCK_PATH).exists() {
+        Check::problem(
+            "DB lock",
+            Level::Warn,
+            format!("{} exists, saves will fail while it does", LOCK_PATH),
+            "delete it if no other instance is running",
+        )
+    } else {
+        Check::pass("DB lock", "not held")
+    }
+}
+
+pub fn check_terminal() -> Check {
+    let colors = match env::var("COLORTERM").as_deref() {
+        Ok("truecolor") | Ok("24bit") => "truecolor",
+        _ => match env::var("TERM") {
+            Ok(term) if term.contains("256color") => "256 colors",
+            Ok(term) if term == "dumb" => "no colors",
+            Ok(_) => "16 colors",
+            Err(_) => "unknown",
+        },
+    };
+    match terminal::size() {
+        Ok((width, height)) if width < 80 || height < 24 => Check::problem(
+            "terminal",
+            Level::Warn,
+            format!("{}x{}, {}", width, height, colors),
+            "use a terminal of at least 80x24",
+        ),
+        Ok((width, height)) => Check::pass("terminal", format!("{}x{}, {}", width, height, colors)),
+        Err(_) => Check::pass("terminal", format!("not a tty, {}", colors)),
+    }
+}
+
+fn duplicate_ids(todos: &[Todo]) -> Vec<usize> {
+    let mut seen = HashSet::new();
+    let mut duplicates: Vec<usize> = todos
+        .iter()
+        .filter(|t| !seen.insert(t.id))
+        .map(|t| t.id)
+        .collect();
+    duplicates.sort_unstable();
+    duplicates.dedup();
+    duplicates
+}
+
+/// Keeps the first todo with each id and gives later copies fresh ids.
+/// Returns the (old, new) ids that were assigned.
+pub fn fix_duplicate_ids(todos: &mut [Todo]) -> Vec<(usize, usize)> {
+    let mut next_id = todos.iter().map(|t| t.id).max().unwrap_or(0) + 1;
+    let mut seen = HashSet::new();
+    let mut changed = vec![];
+    for todo in todos.iter_mut() {
+        if !seen.insert(todo.id) {
+            changed.push((todo.id, next_id));
+            todo.id = next_id;
+            next_id += 1;
+        }
+    }
+    changed
+}
+
+/// Runs `doctor [--fix]` and returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let fix = args.iter().any(|a| a == "--fix");
+    let mut checks = vec![];
+
+    match fs::read_to_string(DB_PATH) {
+        Ok(content) => {
+            let (parsed, todos) = check_parses(&content);
+            checks.push(parsed);
+            if let Some(todos) = todos {
+                checks.push(check_schema(&content, &todos));
+                checks.push(check_attachments(&todos));
+                let duplicates = check_duplicate_ids(&todos);
+                if fix && duplicates.level == Level::Fail {
+                    match update_db(|todos| fix_duplicate_ids(todos)) {
+                        Ok(changed) => checks.push(Check::pass(
+                            "unique ids",
+                            format!("fixed, re-ided (old, new): {:?}", changed),
+                        )),
+                        Err(err) => checks.push(Check::problem(
+                            "unique ids",
+                            Level::Fail,
+                            err.to_string(),
+                            "check the permissions of the data directory",
+                        )),
+                    }
+                } else {
+                    checks.push(duplicates);
+                }
+            }
+        }
+        Err(err) => checks.push(Check::problem(
+            "db parses",
+            Level::Fail,
+            format!("cannot read {}: {}", DB_PATH, err),
+            "run from the directory containing data/db.json",
+        )),
+    }
+    checks.push(check_leftover_tmp(DB_PATH));
+    checks.push(check_lock());
+    checks.push(check_terminal());
+
+    for check in &checks {
+        let tag = match check.level {
+            Level::Pass => "pass",
+            Level::Warn => "warn",
+            Level::Fail => "FAIL",
+        };
+        println!("[{}] {}: {}", tag, check.name, check.message);
+        if let Some(remedy) = &check.remedy {
+            println!("       -> {}", remedy);
+        }
+    }
+
+    if checks.iter().any(|c| c.level == Level::Fail) {
+        1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn todo(id: usize, status: TodoStatus) -> Todo {
+        let mut todo = Todo::new(format!("todo {}", id), "work".to_string(), Utc::now());
+        todo.id = id;
+        todo.set_status(status, Utc::now());
+        todo
+    }
+
+    fn ids(todos: &[Todo]) -> Vec<usize> {
+        todos.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn a_broken_db_fails_to_parse() {
+        let (check, todos) = check_parses("{\"version\": 1, \"todos\": [");
+        assert_eq!(check.level, Level::Fail);
+        assert!(check.remedy.is_some());
+        assert!(todos.is_none());
+
+        let (check, todos) = check_parses("{\"version\": 1, \"todos\": []}");
+        assert_eq!(check.level, Level::Pass);
+        assert_eq!(todos.map(|t| t.len()), Some(0));
+    }
+
+    #[test]
+    fn the_schema_check_counts_each_column_and_flags_legacy_files() {
+        let todos = vec![
+            todo(1, TodoStatus::Todo),
+            todo(2, TodoStatus::Doing),
+            todo(3, TodoStatus::Done),
+            todo(4, TodoStatus::Done),
+        ];
+        let check = check_schema("{\"version\": 1}", &todos);
+        assert_eq!(check.level, Level::Pass);
+        assert_eq!(
+            check.message,
+            format!(
+                "version {}, 4 todos (1 todo, 1 doing, 2 done)",
+                schema::DB_VERSION
+            )
+        );
+
+        let check = check_schema("[]", &[]);
+        assert_eq!(check.level, Level::Warn);
+        assert!(check.message.starts_with("unversioned"));
+    }
+
+    #[test]
+    fn duplicate_ids_fail_until_fixed() {
+        let mut todos = vec![
+            todo(1, TodoStatus::Todo),
+            todo(2, TodoStatus::Todo),
+            todo(1, TodoStatus::Doing),
+            todo(2, TodoStatus::Done),
+            todo(2, TodoStatus::Done),
+        ];
+        let check = check_duplicate_ids(&todos);
+        assert_eq!(check.level, Level::Fail);
+        assert_eq!(check.message, "duplicate ids: [1, 2]");
+
+        // the first copy keeps its id, later ones go past the highest
+        assert_eq!(fix_duplicate_ids(&mut todos), [(1, 3), (2, 4), (2, 5)]);
+        assert_eq!(ids(&todos), [1, 2, 3, 4, 5]);
+        assert_eq!(todos[2].status(), TodoStatus::Doing);
+        assert_eq!(check_duplicate_ids(&todos).level, Level::Pass);
+        assert!(fix_duplicate_ids(&mut todos).is_empty());
+    }
+
+    #[test]
+    fn a_leftover_temp_file_warns() {
+        let dir = std::env::temp_dir().join(format!("doctor-tmp-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("db.json").to_string_lossy().into_owned();
+
+        assert_eq!(check_leftover_tmp(&db).level, Level::Pass);
+        fs::write(format!("{}.tmp", db), "half a save").unwrap();
+        let check = check_leftover_tmp(&db);
+        assert_eq!(check.level, Level::Warn);
+        assert!(check.remedy.unwrap().contains(&db));
+    }
+}
@@ -0,0 +1,222 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use serde::Deserialize;
+
+use crate::subtasks::effective_due;
+use crate::timetracking::format_duration;
+use crate::{Priority, Todo, TodoStatus};
+
+/// Raises a todo's priority as its due date comes closer, e.g.
+/// `{"to": "High", "hours_before": 48}`. With 0 hours the rule applies once
+/// the todo is overdue.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct EscalationRule {
+    pub to: Priority,
+    pub hours_before: f64,
+}
+
+/// A todo's priority once the escalation rules are applied. The stored
+/// priority never changes, this is only how it shows and sorts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EffectivePriority {
+    pub priority: Option<Priority>,
+    /// The due date that raised the priority above the stored one, None
+    /// when it wasn't raised.
+    pub escalated_by: Option<DateTime<Utc>>,
+}
+
+impl EffectivePriority {
+    pub fn is_escalated(&self) -> bool {
+        self.escalated_by.is_some()
+    }
+
+    /// Why the priority was raised, e.g. "escalated: due in 5h 00m".
+    pub fn reason(&self, now: DateTime<Utc>) -> Option<String> {
+        let due = self.escalated_by?;
+        Some(if due > now {
+            format!("escalated: due in {}", format_duration(due - now))
+        } else {
+            format!("escalated: overdue by {}", format_duration(now - due))
+        })
+    }
+
+    /// The marker the board shows, hollow when escalated.
+    pub fn marker(&self) -> Option<&'static str> {
+        let priority = self.priority?;
+        Some(if self.is_escalated() {
+            priority.hollow_marker()
+        } else {
+            priority.marker()
+        })
+    }
+}
+
+/// The priority `todo` shows at `now`: the highest one a rule reached by
+/// its effective due date, when that is above its own. Done todos and
+/// todos without a due date keep theirs. `todos` is every todo, for due
+/// dates that roll up from subtasks.
+pub fn effective_priority(
+    todo: &Todo,
+    todos: &[Todo],
+    now: DateTime<Utc>,
+    rules: &[EscalationRule],
+) -> EffectivePriority {
+    let stored = EffectivePriority {
+        priority: todo.priority,
+        escalated_by: None,
+    };
+    if todo.status() == TodoStatus::Done {
+        return stored;
+    }
+    let due = match effective_due(todo, todos) {
+        Some(due) => due,
+        None => return stored,
+    };
+    let reached = rules
+        .iter()
+        .filter(|rule| now >= due - Duration::seconds((rule.hours_before * 3600.0) as i64))
+        .map(|rule| rule.to)
+        .max();
+    match reached {
+        Some(priority) if Some(priority) > todo.priority => EffectivePriority {
+            priority: Some(priority),
+            escalated_by: Some(due),
+        },
+        _ => stored,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 10, 0, 0, 0).unwrap() + Duration::hours(hour as i64)
+    }
+
+    fn todo(id: usize, priority: Option<Priority>, due: Option<DateTime<Utc>>) -> Todo {
+        let mut todo = Todo::new(format!("todo {}", id), "work".to_string(), at(0));
+        todo.id = id;
+        todo.priority = priority;
+        todo.due = due;
+        todo
+    }
+
+    /// High two days before, Urgent once overdue.
+    fn rules() -> Vec<EscalationRule> {
+        vec![
+            EscalationRule {
+                to: Priority::High,
+                hours_before: 48.0,
+            },
+            EscalationRule {
+                to: Priority::Urgent,
+                hours_before: 0.0,
+            },
+        ]
+    }
+
+    fn shown(todo: &Todo, now: DateTime<Utc>) -> (Option<Priority>, bool) {
+        let effective = effective_priority(todo, &[], now, &rules());
+        (effective.priority, effective.is_escalated())
+    }
+
+    #[test]
+    fn without_rules_nothing_changes() {
+        let todo = todo(1, Some(Priority::Low), Some(at(100)));
+        assert_eq!(
+            effective_priority(&todo, &[], at(200), &[]),
+            EffectivePriority {
+                priority: Some(Priority::Low),
+                escalated_by: None,
+            }
+        );
+    }
+
+    #[test]
+    fn the_priority_rises_as_the_due_date_comes_closer() {
+        let due = at(100);
+        for stored in [None, Some(Priority::Low), Some(Priority::Medium)] {
+            let todo = todo(1, stored, Some(due));
+            assert_eq!(shown(&todo, at(51)), (stored, false));
+            // the window starts exactly at hours_before
+            assert_eq!(shown(&todo, at(52)), (Some(Priority::High), true));
+            assert_eq!(shown(&todo, at(99)), (Some(Priority::High), true));
+            assert_eq!(shown(&todo, due), (Some(Priority::Urgent), true));
+            assert_eq!(shown(&todo, at(300)), (Some(Priority::Urgent), true));
+        }
+    }
+
+    #[test]
+    fn a_priority_at_or_above_the_rule_is_not_escalated() {
+        let high = todo(1, Some(Priority::High), Some(at(100)));
+        assert_eq!(shown(&high, at(60)), (Some(Priority::High), false));
+        assert_eq!(shown(&high, at(100)), (Some(Priority::Urgent), true));
+        let urgent = todo(2, Some(Priority::Urgent), Some(at(100)));
+        assert_eq!(shown(&urgent, at(0)), (Some(Priority::Urgent), false));
+        assert_eq!(shown(&urgent, at(300)), (Some(Priority::Urgent), false));
+    }
+
+    #[test]
+    fn the_highest_rule_reached_wins_whatever_the_order() {
+        let mut reversed = rules();
+        reversed.reverse();
+        let todo = todo(1, None, Some(at(100)));
+        let effective = effective_priority(&todo, &[], at(120), &reversed);
+        assert_eq!(effective.priority, Some(Priority::Urgent));
+        // fractions of hours count
+        let half = [EscalationRule {
+            to: Priority::Medium,
+            hours_before: 0.5,
+        }];
+        let before = at(100) - Duration::minutes(31);
+        let within = at(100) - Duration::minutes(30);
+        assert!(!effective_priority(&todo, &[], before, &half).is_escalated());
+        assert!(effective_priority(&todo, &[], within, &half).is_escalated());
+    }
+
+    #[test]
+    fn done_and_undated_todos_keep_their_priority() {
+        let mut finished = todo(1, Some(Priority::Low), Some(at(100)));
+        finished.set_status(TodoStatus::Done, at(1));
+        assert_eq!(shown(&finished, at(300)), (Some(Priority::Low), false));
+        let undated = todo(2, Some(Priority::Low), None);
+        assert_eq!(shown(&undated, at(300)), (Some(Priority::Low), false));
+    }
+
+    #[test]
+    fn a_subtask_due_date_escalates_its_parent() {
+        let parent = todo(1, None, None);
+        let mut subtask = todo(2, None, Some(at(100)));
+        subtask.parent = Some(1);
+        let todos = [parent.clone(), subtask];
+        let effective = effective_priority(&parent, &todos, at(60), &rules());
+        assert_eq!(effective.priority, Some(Priority::High));
+        assert_eq!(effective.escalated_by, Some(at(100)));
+        // alone, it has no due date to go by
+        assert!(!effective_priority(&parent, &[], at(60), &rules()).is_escalated());
+    }
+
+    #[test]
+    fn the_reason_and_marker_tell_an_escalation_apart() {
+        let todo = todo(1, Some(Priority::Low), Some(at(100)));
+        let soon = effective_priority(&todo, &[], at(95), &rules());
+        assert_eq!(
+            soon.reason(at(95)).as_deref(),
+            Some("escalated: due in 5h 00m")
+        );
+        assert_eq!(soon.marker(), Some("◇◇"));
+        let late = effective_priority(&todo, &[], at(102), &rules());
+        assert_eq!(
+            late.reason(at(102)).as_deref(),
+            Some("escalated: overdue by 2h 00m")
+        );
+        assert_eq!(late.marker(), Some("◇◇◇"));
+
+        let calm = effective_priority(&todo, &[], at(0), &rules());
+        assert_eq!(calm.reason(at(0)), None);
+        assert_eq!(calm.marker(), Some("·"));
+        let none = effective_priority(&self::todo(2, None, None), &[], at(0), &rules());
+        assert_eq!(none.marker(), None);
+    }
+}
@@ -0,0 +1,133 @@
+use chrono::prelude::*;
+
+use crate::subtasks::effective_due;
+use crate::{Todo, TodoStatus};
+
+/// Estimated hours of unfinished work, with the todos lacking an estimate
+/// counted apart so a low total isn't mistaken for a light week.
+#[derive(Debug, Default, PartialEq)]
+pub struct Remaining {
+    pub hours: f64,
+    pub unestimated: usize,
+}
+
+impl Remaining {
+    pub fn label(&self) -> String {
+        let rounded = (self.hours * 10.0).round() / 10.0;
+        let mut label = format!("{}h remaining", rounded);
+        if self.unestimated > 0 {
+            label.push_str(&format!(" +{} unestimated", self.unestimated));
+        }
+        label
+    }
+}
+
+/// Sums the estimates of every todo not done yet. With `due_before`, only
+/// todos due before it are counted, which leaves out undated ones.
+pub fn remaining(todos: &[Todo], due_before: Option<DateTime<Utc>>) -> Remaining {
+    let mut remaining = Remaining::default();
+    for todo in todos.iter().filter(|t| t.status() != TodoStatus::Done) {
+        if let Some(limit) = due_before {
+            if effective_due(todo, todos).is_none_or(|due| due >= limit) {
+                continue;
+            }
+        }
+        match todo.estimate {
+            Some(hours) => remaining.hours += hours,
+            None => remaining.unestimated += 1,
+        }
+    }
+    remaining
+}
+
+/// Parses "1.5", "1.5h" or "90m" into hours.
+pub fn parse_estimate(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let hours = match value.strip_suffix('m') {
+        Some(minutes) => minutes.trim().parse::<f64>().ok()? / 60.0,
+        None => value
+            .strip_suffix('h')
+            .unwrap_or(value)
+            .trim()
+            .parse()
+            .ok()?,
+    };
+    if hours.is_finite() && hours >= 0.0 {
+        Some(hours)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn todo(id: usize, estimate: Option<f64>, due_in_days: Option<i64>) -> Todo {
+        let now = Utc::now();
+        let mut todo = Todo::new(format!("todo {}", id), "work".to_string(), now);
+        todo.id = id;
+        todo.estimate = estimate;
+        todo.due = due_in_days.map(|days| now + Duration::days(days));
+        todo
+    }
+
+    #[test]
+    fn unestimated_todos_are_counted_apart() {
+        let mut done = todo(4, Some(8.0), None);
+        done.set_status(TodoStatus::Done, Utc::now());
+        let mut doing = todo(2, Some(0.5), None);
+        doing.set_status(TodoStatus::Doing, Utc::now());
+        let todos = [todo(1, Some(1.5), None), doing, todo(3, None, None), done];
+        let remaining = remaining(&todos, None);
+        assert_eq!(
+            remaining,
+            Remaining {
+                hours: 2.0,
+                unestimated: 1
+            }
+        );
+        assert_eq!(remaining.label(), "2h remaining +1 unestimated");
+        assert_eq!(super::remaining(&[], None).label(), "0h remaining");
+    }
+
+    #[test]
+    fn a_due_limit_leaves_out_later_and_undated_todos() {
+        let todos = [
+            todo(1, Some(1.0), Some(1)),
+            todo(2, None, Some(2)),
+            todo(3, Some(4.0), Some(10)),
+            todo(4, Some(2.0), None),
+            todo(5, Some(0.25), Some(-3)),
+        ];
+        let limit = Utc::now() + Duration::days(7);
+        assert_eq!(
+            remaining(&todos, Some(limit)),
+            Remaining {
+                hours: 1.25,
+                unestimated: 1
+            }
+        );
+    }
+
+    #[test]
+    fn labels_round_to_a_tenth() {
+        let remaining = Remaining {
+            hours: 1.0 / 3.0,
+            unestimated: 0,
+        };
+        assert_eq!(remaining.label(), "0.3h remaining");
+    }
+
+    #[test]
+    fn estimates_parse_as_hours_or_minutes() {
+        assert_eq!(parse_estimate("1.5"), Some(1.5));
+        assert_eq!(parse_estimate(" 2h "), Some(2.0));
+        assert_eq!(parse_estimate("90m"), Some(1.5));
+        assert_eq!(parse_estimate("0"), Some(0.0));
+        for value in ["", "-1", "1.5d", "NaN", "inf", "m", "h"] {
+            assert_eq!(parse_estimate(value), None, "{}", value);
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use chrono::prelude::*;
+use std::fs;
+
+use crate::archive;
+use crate::cli;
+use crate::config;
+use crate::filter::Filter;
+use crate::zone;
+use crate::{read_db, sort, Error, Todo, TodoStatus};
+
+const COLUMNS: [(TodoStatus, &str); 3] = [
+    (TodoStatus::Todo, "ToDo"),
+    (TodoStatus::Doing, "Doing"),
+    (TodoStatus::Done, "Done"),
+];
+
+const STYLE: &str = "body{font-family:sans-serif;background:#f4f4f4;margin:2em}\
+.board{display:flex;gap:1em}\
+.column{flex:1;background:#fff;border:1px solid #ccc;border-radius:4px;padding:0 1em 1em}\
+.item{border-bottom:1px solid #eee;padding:.5em 0}\
+.category{background:#def;border-radius:3px;padding:0 .4em;font-size:.8em}\
+.priority{color:#c00;font-weight:bold}\
+.age{color:#888;font-size:.8em}\
+.label{border:1px solid;border-radius:3px;padding:0 .4em;font-size:.8em}\
+footer{color:#888;font-size:.8em;margin-top:1em}";
+
+/// Runs `export --format html [--include FILTER] [--output FILE]` or
+/// `export --format pws-archive FILE` and returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let mut format = None;
+    let mut filter = Filter::default();
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--format" | "--include" | "--output" => match args.next() {
+                Some(value) => value,
+                None => return cli::usage(format!("{} needs a value", arg)),
+            },
+            _ if output.is_none() => {
+                output = Some(arg);
+                continue;
+            }
+            _ => return cli::usage(format!("unknown export argument '{}'", arg)),
+        };
+        match arg.as_str() {
+            "--format" => format = Some(value.as_str()),
+            "--include" => match Filter::parse(value) {
+                Ok(parsed) => filter = parsed,
+                Err(err) => return cli::usage(format!("invalid --include: {}", err)),
+            },
+            _ => output = Some(value),
+        }
+    }
+
+    match (format, output) {
+        (Some("html"), _) => {}
+        (Some("pws-archive"), Some(path)) => return archive::export(path),
+        (Some("pws-archive"), None) => {
+            return cli::usage("usage: export --format pws-archive FILE")
+        }
+        _ => return cli::usage("unsupported export format, use --format html or pws-archive"),
+    }
+
+    match config::load() {
+        Ok(config) => filter.week_start = Some(config.week_start),
+        Err(err) => return cli::fail(&err),
+    }
+    let todos = match read_db() {
+        Ok(todos) => todos,
+        Err(err) => return cli::fail(&err),
+    };
+    let html = board_html(&todos, &filter, Utc::now());
+    match output {
+        Some(path) => {
+            if let Err(err) = fs::write(path, html) {
+                return cli::fail(&Error::WriteFileError(path.clone(), err));
+            }
+        }
+        None => print!("{}", html),
+    }
+    0
+}
+
+/// Renders the board as a single self-contained HTML page, without scripts.
+pub fn board_html(todos: &[Todo], filter: &Filter, now: DateTime<Utc>) -> String {
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>Personal Work Suit board</title>\n");
+    html.push_str(&format!("<style>{}</style>\n</head>\n<body>\n", STYLE));
+    html.push_str("<div class=\"board\">\n");
+
+    for (status, title) in COLUMNS.iter() {
+        if filter.hides_status(status) {
+            continue;
+        }
+        let mut column: Vec<Todo> = todos
+            .iter()
+            .filter(|t| {
+                t.status() == *status
+                    && filter.matches(t, todos, now.with_timezone(&zone::current()))
+            })
+            .cloned()
+            .collect();
+        sort::sort_column(&mut column);
+
+        html.push_str(&format!(
+            "<div class=\"column\">\n<h2>{} ({})</h2>\n",
+            title,
+            column.len()
+        ));
+        for todo in &column {
+            html.push_str("<div class=\"item\">");
+            if let Some(priority) = todo.priority {
+                html.push_str(&format!(
+                    "<span class=\"priority\">{}</span> ",
+                    escape(priority.marker())
+                ));
+            }
+            html.push_str(&escape(&todo.title));
+            html.push_str(&format!(
+                " <span class=\"category\">{}</span> <span class=\"age\">{}</span>",
+                escape(&todo.category),
+                age(todo.created_at, now)
+            ));
+            if let Some(label) = &todo.color {
+                html.push_str(&format!(" <span class=\"label\">{}</span>", escape(label)));
+            }
+            html.push_str("</div>\n");
+        }
+        html.push_str("</div>\n");
+    }
+
+    html.push_str("</div>\n");
+    html.push_str(&format!(
+        "<footer>Generated at {}, due dates in {}</footer>\n</body>\n</html>\n",
+        now.format("%Y-%m-%d %H:%M UTC"),
+        escape(&zone::current().describe(now))
+    ));
+    html
+}
+
+/// A board column as a Markdown checklist, its todos in the order given,
+/// for the column menu's export.
+pub fn column_markdown(title: &str, column: &[Todo], now: DateTime<Utc>) -> String {
+    let mut out = format!("# {} ({})\n\n", title, column.len());
+    for todo in column {
+        out.push_str(if todo.status() == TodoStatus::Done {
+            "- [x] "
+        } else {
+            "- [ ] "
+        });
+        if let Some(priority) = todo.priority {
+            out.push_str(&format!("{} ", priority.marker()));
+        }
+        out.push_str(&format!(
+            "#{} {} `{}` {}\n",
+            todo.id,
+            todo.title,
+            todo.category,
+            age(todo.created_at, now)
+        ));
+    }
+    out
+}
+
+fn age(created_at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let days = (now - created_at).num_days().max(0);
+    if days >= 365 {
+        format!("{}y", days / 365)
+    } else if days >= 30 {
+        format!("{}mo", days / 30)
+    } else {
+        format!("{}d", days)
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+
+    fn at(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap()
+    }
+
+    fn board() -> Vec<Todo> {
+        let mut todos = vec![];
+        for (id, title, category, status, created) in [
+            (
+                1,
+                "Write <script>alert(1)</script>",
+                "work",
+                TodoStatus::Todo,
+                1,
+            ),
+            (2, "Fish & chips", "home \"kitchen\"", TodoStatus::Doing, 9),
+            (3, "Pay rent", "home", TodoStatus::Done, 10),
+        ] {
+            let mut todo = Todo::new(title.to_string(), category.to_string(), at(created));
+            todo.id = id;
+            todo.set_status(status, at(10));
+            todos.push(todo);
+        }
+        todos[0].priority = Some(Priority::High);
+        todos[1].color = Some("it's red".to_string());
+        todos
+    }
+
+    #[test]
+    fn the_board_renders_to_a_fixed_page() {
+        let now = at(10);
+        let expected = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+<title>Personal Work Suit board</title>\n\
+<style>{}</style>\n</head>\n<body>\n\
+<div class=\"board\">\n\
+<div class=\"column\">\n<h2>ToDo (1)</h2>\n\
+<div class=\"item\"><span class=\"priority\">!!</span> Write &lt;script&gt;alert(1)&lt;/script&gt; \
+<span class=\"category\">work</span> <span class=\"age\">9d</span></div>\n\
+</div>\n\
+<div class=\"column\">\n<h2>Doing (1)</h2>\n\
+<div class=\"item\">Fish &amp; chips <span class=\"category\">home &quot;kitchen&quot;</span> \
+<span class=\"age\">1d</span> <span class=\"label\">it&#39;s red</span></div>\n\
+</div>\n\
+<div class=\"column\">\n<h2>Done (1)</h2>\n\
+<div class=\"item\">Pay rent <span class=\"category\">home</span> <span class=\"age\">0d</span></div>\n\
+</div>\n\
+</div>\n\
+<footer>Generated at 2024-05-10 12:00 UTC, due dates in {}</footer>\n</body>\n</html>\n",
+            STYLE,
+            escape(&zone::current().describe(now))
+        );
+        assert_eq!(board_html(&board(), &Filter::default(), now), expected);
+    }
+
+    #[test]
+    fn an_include_filter_drops_columns_and_todos() {
+        let filter = Filter::parse("done:false,category:work").unwrap();
+        let html = board_html(&board(), &filter, at(10));
+        assert!(html.contains("<h2>ToDo (1)</h2>"));
+        assert!(html.contains("<h2>Doing (0)</h2>"));
+        assert!(!html.contains("Fish"));
+        assert!(!html.contains("Done ("));
+        assert!(!html.contains("Pay rent"));
+    }
+
+    #[test]
+    fn user_content_never_reaches_the_page_as_markup() {
+        let html = board_html(&board(), &Filter::default(), at(10));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("<alert"));
+        assert_eq!(
+            escape("<a href='x'>\"&\"</a>"),
+            "&lt;a href=&#39;x&#39;&gt;&quot;&amp;&quot;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn a_column_exports_as_a_checklist() {
+        let todos = board();
+        assert_eq!(
+            column_markdown("Board", &todos, at(10)),
+            "# Board (3)\n\n\
+- [ ] !! #1 Write <script>alert(1)</script> `work` 9d\n\
+- [ ] #2 Fish & chips `home \"kitchen\"` 1d\n\
+- [x] #3 Pay rent `home` 0d\n"
+        );
+    }
+
+    #[test]
+    fn ages_round_down_to_days_months_and_years() {
+        let now = at(10);
+        assert_eq!(age(now, now), "0d");
+        assert_eq!(age(now + chrono::Duration::days(1), now), "0d");
+        assert_eq!(age(now - chrono::Duration::days(29), now), "29d");
+        assert_eq!(age(now - chrono::Duration::days(30), now), "1mo");
+        assert_eq!(age(now - chrono::Duration::days(364), now), "12mo");
+        assert_eq!(age(now - chrono::Duration::days(730), now), "2y");
+    }
+}
@@ -0,0 +1,578 @@
+use chrono::prelude::*;
+use std::cmp::Ordering;
+
+use crate::dates::parse_day;
+use crate::filter::{has_category, has_tag, Filter};
+use crate::subtasks::effective_due;
+use crate::zone::{self, Zone};
+use crate::{Priority, Todo, TodoStatus};
+
+const FIELDS: &str =
+    "status, category, tag, priority, due, created, completed, pinned, source or id";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn label(self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        }
+    }
+
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+        }
+    }
+
+    /// Compares a value that may be missing, like a todo without a due
+    /// date, with one that may be `none`. Missing only equals `none` and
+    /// is neither before nor after anything.
+    fn compare<T: Ord>(self, left: Option<T>, right: Option<T>) -> bool {
+        match (left, right) {
+            (Some(left), Some(right)) => self.holds(left.cmp(&right)),
+            (None, None) => self == Op::Eq,
+            _ => self == Op::Ne,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DateField {
+    Due,
+    Created,
+    Completed,
+}
+
+/// One comparison of a todo's field with a value.
+#[derive(Clone, Debug)]
+pub enum Test {
+    Status(Op, TodoStatus),
+    Category(Op, String),
+    Tag(Op, String),
+    Priority(Op, Option<Priority>),
+    Date(DateField, Op, Option<NaiveDate>),
+    Pinned(Op, bool),
+    Id(Op, usize),
+    /// A pattern where `*` stands for any text, e.g. `import:*`; `unknown`
+    /// matches todos without a source.
+    Source(Op, String),
+    /// A `key:value` term of the plain filter syntax, e.g. `due:week`.
+    Term(Box<Filter>),
+}
+
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Test(Test),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Whether `todo` matches. `todos` is every todo, for due dates that
+    /// roll up from subtasks, and `week_start` is where `due:week` weeks
+    /// start.
+    pub fn eval(
+        &self,
+        todo: &Todo,
+        todos: &[Todo],
+        now: DateTime<Zone>,
+        week_start: Weekday,
+    ) -> bool {
+        let eval = |expr: &Expr| expr.eval(todo, todos, now, week_start);
+        match self {
+            Expr::Test(test) => test.eval(todo, todos, now, week_start),
+            Expr::Not(expr) => !eval(expr),
+            Expr::And(left, right) => eval(left) && eval(right),
+            Expr::Or(left, right) => eval(left) || eval(right),
+        }
+    }
+}
+
+impl Test {
+    fn eval(&self, todo: &Todo, todos: &[Todo], now: DateTime<Zone>, week_start: Weekday) -> bool {
+        let equal = |op: Op, holds: bool| holds == (op == Op::Eq);
+        match self {
+            Test::Status(op, status) => equal(*op, todo.status() == *status),
+            Test::Category(op, category) => equal(*op, has_category(todo, category)),
+            Test::Tag(op, tag) => equal(*op, has_tag(todo, tag)),
+            Test::Priority(op, priority) => op.compare(todo.priority, *priority),
+            Test::Date(field, op, day) => {
+                let at = match field {
+                    DateField::Due => effective_due(todo, todos),
+                    DateField::Created => Some(todo.created_at),
+                    DateField::Completed => todo.completed_at,
+                };
+                op.compare(
+                    at.map(|at| at.with_timezone(&zone::current()).date_naive()),
+                    *day,
+                )
+            }
+            Test::Pinned(op, pinned) => equal(*op, todo.pinned == *pinned),
+            Test::Id(op, id) => op.holds(todo.id.cmp(id)),
+            Test::Source(op, pattern) => equal(
+                *op,
+                match &todo.source {
+                    Some(source) => glob_match(pattern, source),
+                    None => pattern == "unknown",
+                },
+            ),
+            Test::Term(filter) => filter.matches_in_week(todo, todos, now, week_start),
+        }
+    }
+}
+
+/// Whether `text` matches `pattern` whole, `*` in it matching any run of
+/// chars.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match text.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let last = match parts.pop() {
+        Some(last) => last,
+        // no `*` at all
+        None => return rest.is_empty(),
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Whether `spec` reads as an expression rather than plain `key:value`
+/// terms: it compares with an operator, groups or combines.
+pub fn is_expression(spec: &str) -> bool {
+    spec.contains(['=', '<', '>', '(', ')', '"'])
+        || spec
+            .split_whitespace()
+            .any(|word| matches!(word, "and" | "or" | "not"))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    /// A value in double quotes, never a keyword.
+    Quoted(String),
+    Op(Op),
+    Open,
+    Close,
+}
+
+fn tokenize(spec: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = spec.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            ',' => tokens.push(Token::Word("and".to_string())),
+            '=' => tokens.push(Token::Op(Op::Eq)),
+            '!' if chars.peek() == Some(&'=') => {
+                chars.next();
+                tokens.push(Token::Op(Op::Ne));
+            }
+            '<' | '>' => {
+                let or_equal = chars.next_if_eq(&'=').is_some();
+                tokens.push(Token::Op(match (c, or_equal) {
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    _ => Op::Ge,
+                }));
+            }
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => text.push(c),
+                        None => return Err("unterminated quote".to_string()),
+                    }
+                }
+                tokens.push(Token::Quoted(text));
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| {
+                    !c.is_whitespace()
+                        && !matches!(c, '(' | ')' | ',' | '=' | '!' | '<' | '>' | '"')
+                }) {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Reads a filter expression: comparisons like `status=done`,
+/// `priority>=high` or `completed<2024-01-01` joined with `and`, `or` and
+/// `not`, in that order of precedence from loosest, with parentheses to
+/// group. A comma is another `and`, and plain `key:value` filter terms
+/// work as comparisons. Dates are `YYYY-MM-DD`, `today`, `yesterday`,
+/// `tomorrow` or days and weeks from `today` like `-30d` and `+2w`; `none`
+/// matches a missing date or priority.
+pub fn parse(spec: &str, today: NaiveDate) -> Result<Expr, String> {
+    let tokens = tokenize(spec)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        at: 0,
+        today,
+    };
+    let expr = parser.or()?;
+    match parser.tokens.get(parser.at) {
+        None => Ok(expr),
+        Some(Token::Close) => Err("unmatched ')'".to_string()),
+        Some(token) => Err(format!("unexpected {}", describe(token))),
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) | Token::Quoted(word) => format!("'{}'", word),
+        Token::Op(op) => format!("'{}'", op.label()),
+        Token::Open => "'('".to_string(),
+        Token::Close => "')'".to_string(),
+    }
+}
+
+struct Parser<'t> {
+    tokens: &'t [Token],
+    at: usize,
+    today: NaiveDate,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.at);
+        self.at += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.tokens.get(self.at), Some(Token::Word(word)) if word == keyword);
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("missing ')'".to_string()),
+                }
+            }
+            Some(Token::Word(word)) => {
+                let word = word.clone();
+                if word.contains(':') {
+                    return Ok(Expr::Test(Test::Term(Box::new(Filter::parse(&word)?))));
+                }
+                let op = match self.next() {
+                    Some(Token::Op(op)) => *op,
+                    _ => return Err(format!("expected a comparison after '{}'", word)),
+                };
+                let value = match self.next() {
+                    Some(Token::Word(value) | Token::Quoted(value)) => value.clone(),
+                    _ => return Err(format!("expected a value after '{}{}'", word, op.label())),
+                };
+                self.test(&word, op, &value).map(Expr::Test)
+            }
+            Some(token) => Err(format!("unexpected {}", describe(token))),
+            None => Err("the expression ends too early".to_string()),
+        }
+    }
+
+    fn test(&self, field: &str, op: Op, value: &str) -> Result<Test, String> {
+        let equality = |test: Test| match op {
+            Op::Eq | Op::Ne => Ok(test),
+            _ => Err(format!("{} can only use = or !=", field)),
+        };
+        let date = |field: DateField| match value {
+            "none" => Ok(Test::Date(field, op, None)),
+            _ => parse_day(value, self.today)
+                .map(|day| Test::Date(field, op, Some(day)))
+                .ok_or_else(|| {
+                    format!(
+                        "invalid date '{}', use YYYY-MM-DD, today, yesterday, tomorrow or -7d",
+                        value
+                    )
+                }),
+        };
+        match field {
+            "status" => equality(Test::Status(
+                op,
+                match value {
+                    "todo" => TodoStatus::Todo,
+                    "doing" => TodoStatus::Doing,
+                    "done" => TodoStatus::Done,
+                    _ => {
+                        return Err(format!(
+                            "invalid status '{}', use todo, doing or done",
+                            value
+                        ))
+                    }
+                },
+            )),
+            "category" => equality(Test::Category(op, value.to_string())),
+            "tag" => equality(Test::Tag(op, value.to_string())),
+            "priority" => match value {
+                "none" => Ok(Test::Priority(op, None)),
+                _ => Priority::parse(value)
+                    .map(|priority| Test::Priority(op, Some(priority)))
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid priority '{}', use low, medium, high, urgent or none",
+                            value
+                        )
+                    }),
+            },
+            "due" => date(DateField::Due),
+            "created" => date(DateField::Created),
+            "completed" => date(DateField::Completed),
+            "pinned" => match value {
+                "true" => equality(Test::Pinned(op, true)),
+                "false" => equality(Test::Pinned(op, false)),
+                _ => Err(format!(
+                    "expected true or false for pinned, got '{}'",
+                    value
+                )),
+            },
+            "source" => equality(Test::Source(op, value.to_string())),
+            "id" => value
+                .parse()
+                .map(|id| Test::Id(op, id))
+                .map_err(|_| format!("invalid id '{}'", value)),
+            _ => Err(format!("unknown field '{}', use {}", field, FIELDS)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, 15).unwrap()
+    }
+
+    /// Noon keeps the dates the same in whatever zone the tests run in.
+    fn noon(date: &str) -> DateTime<Utc> {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+            .and_utc()
+    }
+
+    fn todo(id: usize, category: &str) -> Todo {
+        let mut todo = Todo::new(
+            format!("todo {}", id),
+            category.to_string(),
+            noon("2024-05-01"),
+        );
+        todo.id = id;
+        todo
+    }
+
+    fn board() -> Vec<Todo> {
+        let mut todos = vec![
+            todo(1, "work"),
+            todo(2, "home"),
+            todo(3, "work"),
+            todo(4, "and"),
+        ];
+        todos[0].set_status(TodoStatus::Done, noon("2023-12-20"));
+        todos[0].priority = Some(Priority::Low);
+        todos[1].tags = vec!["errand".to_string()];
+        todos[1].priority = Some(Priority::Urgent);
+        todos[1].due = Some(noon("2024-05-20"));
+        todos[2].set_status(TodoStatus::Doing, noon("2024-05-10"));
+        todos[2].priority = Some(Priority::High);
+        todos[2].pinned = true;
+        todos[2].source = Some("import:tasks.csv".to_string());
+        todos[3].created_at = noon("2024-04-10");
+        todos
+    }
+
+    /// The ids of the todos `spec` matches.
+    fn matching(spec: &str) -> Vec<usize> {
+        let todos = board();
+        let expr = parse(spec, today()).unwrap_or_else(|err| panic!("{}: {}", spec, err));
+        todos
+            .iter()
+            .filter(|todo| expr.eval(todo, &todos, zone::now(), Weekday::Mon))
+            .map(|todo| todo.id)
+            .collect()
+    }
+
+    fn error(spec: &str) -> String {
+        parse(spec, today())
+            .err()
+            .unwrap_or_else(|| panic!("{} parsed", spec))
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or_and_not_tightest() {
+        // work or (home and doing)
+        assert_eq!(
+            matching("category=work or category=home and status=doing"),
+            [1, 3]
+        );
+        assert_eq!(
+            matching("(category=work or category=home) and status=doing"),
+            [3]
+        );
+        // (not work) and not done
+        assert_eq!(matching("not category=work and status!=done"), [2, 4]);
+        assert_eq!(matching("not (category=work and status=done)"), [2, 3, 4]);
+        assert_eq!(matching("not not status=done"), [1]);
+        // a comma is another and, chains read left to right
+        assert_eq!(matching("category=work, status=done"), [1]);
+        assert_eq!(matching("id=1 or id=2 or id=4"), [1, 2, 4]);
+    }
+
+    #[test]
+    fn each_operator_compares_as_written() {
+        assert_eq!(matching("id=2"), [2]);
+        assert_eq!(matching("id!=2"), [1, 3, 4]);
+        assert_eq!(matching("id<2"), [1]);
+        assert_eq!(matching("id<=2"), [1, 2]);
+        assert_eq!(matching("id>2"), [3, 4]);
+        assert_eq!(matching("id>=2"), [2, 3, 4]);
+        assert_eq!(matching("id >= 2 and id < 4"), [2, 3]);
+    }
+
+    #[test]
+    fn a_missing_priority_or_date_only_equals_none() {
+        assert_eq!(matching("priority>=high"), [2, 3]);
+        assert_eq!(matching("priority<medium"), [1]);
+        assert_eq!(matching("priority=none"), [4]);
+        assert_eq!(matching("priority!=none"), [1, 2, 3]);
+        assert_eq!(matching("due=none"), [1, 3, 4]);
+        assert_eq!(matching("due>2024-01-01"), [2]);
+        assert_eq!(matching("completed<2024-01-01"), [1]);
+        assert_eq!(matching("completed>2024-01-01"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn dates_may_be_relative_to_today() {
+        assert_eq!(matching("created<-30d"), [4]);
+        assert_eq!(matching("created<-40d"), Vec::<usize>::new());
+        assert_eq!(matching("created<=-14d"), [1, 2, 3, 4]);
+        assert_eq!(matching("created<-2w"), [4]);
+        assert_eq!(matching("due<=+1w"), [2]);
+        assert_eq!(matching("due<+5d"), Vec::<usize>::new());
+        assert_eq!(matching("due>today and due<+6d"), [2]);
+        assert_eq!(matching("created>yesterday"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn other_fields_and_plain_terms() {
+        assert_eq!(matching("tag=errand"), [2]);
+        assert_eq!(matching("tag!=errand"), [1, 3, 4]);
+        assert_eq!(matching("pinned=true"), [3]);
+        assert_eq!(matching("source=import:*"), [3]);
+        assert_eq!(matching("source=*.csv"), [3]);
+        assert_eq!(matching("source=import"), Vec::<usize>::new());
+        assert_eq!(matching("source=unknown"), [1, 2, 4]);
+        // a quoted value is never a keyword
+        assert_eq!(matching("category=\"and\""), [4]);
+        // a key:value term of the plain filter syntax
+        assert_eq!(matching("category:home or id=1"), [1, 2]);
+    }
+
+    #[test]
+    fn bad_fields_values_and_syntax_are_explained() {
+        assert_eq!(
+            error("owner=me"),
+            format!("unknown field 'owner', use {}", FIELDS)
+        );
+        assert!(error("status=open").starts_with("invalid status 'open'"));
+        assert!(error("priority=huge").starts_with("invalid priority 'huge'"));
+        assert!(error("due<2024-13-01").starts_with("invalid date '2024-13-01'"));
+        assert!(error("created>-3x").starts_with("invalid date '-3x'"));
+        assert_eq!(
+            error("pinned=yes"),
+            "expected true or false for pinned, got 'yes'"
+        );
+        assert_eq!(error("id=first"), "invalid id 'first'");
+        assert_eq!(error("status<done"), "status can only use = or !=");
+        assert_eq!(error("tag>=x"), "tag can only use = or !=");
+    }
+
+    #[test]
+    fn broken_expressions_say_where() {
+        assert_eq!(error("(status=done"), "missing ')'");
+        assert_eq!(error("status=done)"), "unmatched ')'");
+        assert_eq!(error("status=done id=1"), "unexpected 'id'");
+        assert_eq!(error("status"), "expected a comparison after 'status'");
+        assert_eq!(error("status="), "expected a value after 'status='");
+        assert_eq!(error("status=done and"), "the expression ends too early");
+        assert_eq!(error("category=\"home"), "unterminated quote");
+        assert_eq!(
+            error("and status=done"),
+            "expected a comparison after 'and'"
+        );
+        assert_eq!(error("= done"), "unexpected '='");
+    }
+
+    #[test]
+    fn tells_expressions_from_plain_terms() {
+        assert!(is_expression("status=done"));
+        assert!(is_expression("tag:a or tag:b"));
+        assert!(is_expression("(tag:a)"));
+        assert!(!is_expression("tag:a category:work"));
+        assert!(!is_expression("android"));
+    }
+}
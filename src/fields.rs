@@ -0,0 +1,275 @@
+use chrono::prelude::*;
+use serde::Deserialize;
+use tui::{
+    style::{Color, Style},
+    text::Span,
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::escalation::{effective_priority, EscalationRule};
+use crate::progress::{self, Progress};
+use crate::subtasks::effective_due;
+use crate::urgency;
+use crate::zone::{self, Zone};
+use crate::Todo;
+
+/// A decoration a board item can show after its title, see
+/// `board_item_fields` in the config.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Priority,
+    Due,
+    /// The time left until the due date, e.g. "in 3d 4h", in place of the
+    /// date.
+    DueIn,
+    Tags,
+    Estimate,
+}
+
+pub const DEFAULT_FIELDS: [Field; 3] = [Field::Priority, Field::Due, Field::Tags];
+
+impl Field {
+    pub fn parse(name: &str) -> Option<Field> {
+        match name {
+            "priority" => Some(Field::Priority),
+            "due" => Some(Field::Due),
+            "due_in" => Some(Field::DueIn),
+            "tags" => Some(Field::Tags),
+            "estimate" => Some(Field::Estimate),
+            _ => None,
+        }
+    }
+
+    /// The decoration for `todo`, if it has anything to show. `todos` is
+    /// every todo, for due dates that roll up from subtasks and raise the
+    /// priority by the `escalation` rules.
+    fn decorate<'a>(
+        &self,
+        todo: &Todo,
+        todos: &[Todo],
+        escalation: &[EscalationRule],
+        now: DateTime<Zone>,
+    ) -> Option<Span<'a>> {
+        match self {
+            Field::Priority => effective_priority(todo, todos, now.with_timezone(&Utc), escalation)
+                .marker()
+                .map(|marker| Span::styled(marker, Style::default().fg(Color::Red))),
+            Field::Due => effective_due(todo, todos).map(|due| {
+                let due = due.with_timezone(&zone::current());
+                let (label, color) = if due < now {
+                    ("overdue".to_string(), Color::Red)
+                } else if due.date_naive() == now.date_naive() {
+                    ("due today".to_string(), Color::Yellow)
+                } else {
+                    (format!("due {}", due.format("%b %d")), Color::Gray)
+                };
+                Span::styled(label, Style::default().fg(color))
+            }),
+            Field::DueIn => effective_due(todo, todos).map(|due| {
+                let now = now.with_timezone(&Utc);
+                let color = if due < now {
+                    Color::Red
+                } else if due - now < chrono::Duration::days(1) {
+                    Color::Yellow
+                } else {
+                    Color::Gray
+                };
+                let left = urgency::due_in(due, now);
+                let label = if due < now {
+                    left
+                } else {
+                    format!("in {}", left)
+                };
+                Span::styled(label, Style::default().fg(color))
+            }),
+            Field::Tags if !todo.tags.is_empty() => Some(Span::styled(
+                todo.tags
+                    .iter()
+                    .map(|tag| format!("[{}]", tag))
+                    .collect::<Vec<_>>()
+                    .join(""),
+                Style::default().fg(Color::Blue),
+            )),
+            Field::Tags => None,
+            Field::Estimate => todo.estimate.map(|hours| {
+                Span::styled(format!("~{}h", hours), Style::default().fg(Color::Gray))
+            }),
+        }
+    }
+}
+
+/// Parses a comma or space separated field list, as typed after
+/// `:set fields`. `title` is accepted and ignored, it is always shown.
+pub fn parse_list(spec: &str) -> Result<Vec<Field>, String> {
+    spec.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|name| !name.is_empty() && *name != "title")
+        .map(|name| {
+            Field::parse(name).ok_or_else(|| {
+                format!(
+                    "unknown field '{}', use priority, due, due_in, tags or estimate",
+                    name
+                )
+            })
+        })
+        .collect()
+}
+
+/// Columns `text` takes, two for wide chars such as CJK and most glyphs.
+fn width(text: &str) -> usize {
+    UnicodeWidthStr::width(text)
+}
+
+/// Fits an item into `width` columns: the `prefix` markers and title
+/// first, then the `progress` readout and each of `fields` in order while
+/// they still fit. The title only gets cut once no decoration is left.
+#[allow(clippy::too_many_arguments)]
+pub fn fit<'a>(
+    prefix: Vec<Span<'a>>,
+    title: Span<'a>,
+    todo: &Todo,
+    todos: &[Todo],
+    progress: Option<&Progress>,
+    fields: &[Field],
+    escalation: &[EscalationRule],
+    now: DateTime<Zone>,
+    width_left: usize,
+) -> Vec<Span<'a>> {
+    let mut left = prefix.iter().fold(width_left, |left, span| {
+        left.saturating_sub(width(&span.content))
+    });
+    let mut spans = prefix;
+
+    let title_width = width(&title.content);
+    if title_width > left {
+        let mut room = left.saturating_sub(1);
+        let cut: String = title
+            .content
+            .chars()
+            .take_while(|c| match room.checked_sub(c.width().unwrap_or(0)) {
+                Some(rest) => {
+                    room = rest;
+                    true
+                }
+                None => false,
+            })
+            .collect();
+        spans.push(Span::styled(format!("{}…", cut), title.style));
+        return spans;
+    }
+    left -= title_width;
+    spans.push(title);
+
+    if let Some(readout) =
+        progress.and_then(|progress| progress::annotation(progress, left.saturating_sub(1)))
+    {
+        left -= 1 + width(&readout.content);
+        spans.push(Span::raw(" "));
+        spans.push(readout);
+    }
+
+    for field in fields {
+        if let Some(decoration) = field.decorate(todo, todos, escalation, now) {
+            let needed = 1 + width(&decoration.content);
+            if needed <= left {
+                left -= needed;
+                spans.push(Span::raw(" "));
+                spans.push(decoration);
+            }
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(title: &str) -> Todo {
+        let mut todo = Todo::new(title.to_string(), "work".to_string(), Utc::now());
+        todo.tags = vec!["ops".to_string(), "q2".to_string()];
+        todo.estimate = Some(2.5);
+        todo
+    }
+
+    /// The text `fit` gives for `todo` in `width` columns.
+    fn fitted(todo: &Todo, fields: &[Field], width: usize) -> String {
+        let spans = fit(
+            vec![Span::raw("> ")],
+            Span::raw(todo.title.clone()),
+            todo,
+            std::slice::from_ref(todo),
+            None,
+            fields,
+            &[],
+            zone::now(),
+            width,
+        );
+        spans.iter().map(|s| s.content.as_ref()).collect()
+    }
+
+    #[test]
+    fn a_list_parses_with_commas_or_spaces_and_title_is_implied() {
+        assert_eq!(
+            parse_list("title, due_in tags,,estimate").unwrap(),
+            [Field::DueIn, Field::Tags, Field::Estimate]
+        );
+        assert_eq!(parse_list("").unwrap(), []);
+        assert!(parse_list("priority colour")
+            .unwrap_err()
+            .starts_with("unknown field 'colour'"));
+    }
+
+    #[test]
+    fn fields_show_in_the_configured_order() {
+        let todo = todo("Write report");
+        let fields = [Field::Estimate, Field::Tags];
+        assert_eq!(fitted(&todo, &fields, 80), "> Write report ~2.5h [ops][q2]");
+        let fields = [Field::Tags, Field::Estimate];
+        assert_eq!(fitted(&todo, &fields, 80), "> Write report [ops][q2] ~2.5h");
+        // a field with nothing to show takes no room
+        let mut bare = todo.clone();
+        bare.tags.clear();
+        assert_eq!(fitted(&bare, &fields, 80), "> Write report ~2.5h");
+    }
+
+    #[test]
+    fn decorations_give_way_before_the_title() {
+        let todo = todo("Write report");
+        let fields = [Field::Tags, Field::Estimate];
+        // the prefix and title take 14 columns, the tags 9 more
+        assert_eq!(fitted(&todo, &fields, 29), "> Write report [ops][q2]");
+        // a later field that still fits shows where an earlier one didn't
+        assert_eq!(fitted(&todo, &fields, 20), "> Write report ~2.5h");
+        assert_eq!(fitted(&todo, &fields, 14), "> Write report");
+        assert_eq!(fitted(&todo, &fields, 10), "> Write r…");
+    }
+
+    #[test]
+    fn wide_chars_are_cut_by_their_columns() {
+        let todo = todo("報告書を書く");
+        // 12 columns of title, 8 left after the prefix: 3 chars and the …
+        assert_eq!(fitted(&todo, &[Field::Tags], 10), "> 報告書…");
+        assert_eq!(fitted(&todo, &[Field::Tags], 14), "> 報告書を書く");
+    }
+
+    #[test]
+    fn a_wide_glyph_before_the_title_takes_two_columns() {
+        let todo = todo("Write report");
+        let spans = fit(
+            vec![Span::raw("> "), Span::raw("🏠 ")],
+            Span::raw(todo.title.clone()),
+            &todo,
+            std::slice::from_ref(&todo),
+            None,
+            &[],
+            &[],
+            zone::now(),
+            10,
+        );
+        let text: String = spans.iter().map(|s| s.content.as_ref()).collect();
+        // 5 columns of prefix leave 5: 4 of the title and the …
+        assert_eq!(text, "> 🏠 Writ…");
+        assert_eq!(width(&text), 10);
+    }
+}
@@ -0,0 +1,339 @@
+use chrono::prelude::*;
+use chrono::Duration;
+
+use crate::expr::{self, Expr};
+use crate::subtasks::effective_due;
+use crate::timetracking::{local_midnight, week_bounds};
+use crate::zone::{self, Zone};
+use crate::{Todo, TodoStatus};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DueWindow {
+    Today,
+    Week,
+    Overdue,
+}
+
+impl DueWindow {
+    pub fn parse(value: &str) -> Option<DueWindow> {
+        match value {
+            "today" => Some(DueWindow::Today),
+            "week" => Some(DueWindow::Week),
+            "overdue" => Some(DueWindow::Overdue),
+            _ => None,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            DueWindow::Today => "due today",
+            DueWindow::Week => "due this week",
+            DueWindow::Overdue => "overdue",
+        }
+    }
+}
+
+/// Whether `todo`, going by its effective due date among `todos`, falls in
+/// the window at `now`. Days and weeks are local; done todos are never
+/// overdue.
+pub fn due_in(
+    window: DueWindow,
+    todo: &Todo,
+    todos: &[Todo],
+    now: DateTime<Zone>,
+    week_start: Weekday,
+) -> bool {
+    let due = match effective_due(todo, todos) {
+        Some(due) => due,
+        None => return false,
+    };
+    match window {
+        DueWindow::Today => {
+            let today = now.date_naive();
+            local_midnight(today, now.timezone()) <= due
+                && due < local_midnight(today + Duration::days(1), now.timezone())
+        }
+        DueWindow::Week => {
+            let (from, to) = week_bounds(now, week_start);
+            from <= due && due < to
+        }
+        DueWindow::Overdue => due < now && todo.status() != TodoStatus::Done,
+    }
+}
+
+pub fn has_category(todo: &Todo, category: &str) -> bool {
+    todo.category == category
+}
+
+pub fn has_tag(todo: &Todo, tag: &str) -> bool {
+    todo.tags.iter().any(|t| t == tag)
+}
+
+/// Restricts which todos are shown or exported. Built from comma separated
+/// `key:value` terms, e.g. `done:false,category:work,due:week`, or from an
+/// expression, see `expr::parse`.
+///
+/// ```
+/// use chrono::Utc;
+/// use work_time_cli::{parse_quick_add, Filter, Zone};
+///
+/// let todos = vec![parse_quick_add("Pay rent @home +bills").unwrap()];
+/// let now = Utc::now().with_timezone(&Zone::System);
+///
+/// let bills = Filter::parse("done:false,tag:bills").unwrap();
+/// assert!(bills.matches(&todos[0], &todos, now));
+/// let work = Filter::parse("category = work or priority = urgent").unwrap();
+/// assert!(!work.matches(&todos[0], &todos, now));
+/// assert!(Filter::parse("colour:red").is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    hidden_statuses: Vec<TodoStatus>,
+    category: Option<String>,
+    tag: Option<String>,
+    pub due: Option<DueWindow>,
+    /// Where "this week" starts for `due:week`, from the config.
+    pub week_start: Option<Weekday>,
+    /// The expression as typed and parsed.
+    expression: Option<(String, Expr)>,
+}
+
+impl Filter {
+    pub fn parse(spec: &str) -> Result<Filter, String> {
+        let mut filter = Filter::default();
+        if expr::is_expression(spec) {
+            let parsed = expr::parse(spec, zone::now().date_naive())?;
+            filter.expression = Some((spec.trim().to_string(), parsed));
+            return Ok(filter);
+        }
+        for term in spec.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let (key, value) = term
+                .split_once(':')
+                .ok_or_else(|| format!("expected key:value, got '{}'", term))?;
+            match key {
+                "todo" | "doing" | "done" => {
+                    let status = match key {
+                        "todo" => TodoStatus::Todo,
+                        "doing" => TodoStatus::Doing,
+                        _ => TodoStatus::Done,
+                    };
+                    match value {
+                        "false" => filter.hidden_statuses.push(status),
+                        "true" => filter.hidden_statuses.retain(|s| *s != status),
+                        _ => return Err(format!("expected true or false in '{}'", term)),
+                    }
+                }
+                "category" => filter.category = Some(value.to_string()),
+                "tag" => filter.tag = Some(value.to_string()),
+                "due" => {
+                    filter.due =
+                        Some(DueWindow::parse(value).ok_or_else(|| {
+                            format!("expected today, week or overdue in '{}'", term)
+                        })?)
+                }
+                _ => return Err(format!("unknown filter key '{}'", key)),
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+
+    /// Narrows to `category`, or drops the category term for None, keeping
+    /// the other terms.
+    pub fn set_category(&mut self, category: Option<String>) {
+        self.category = category;
+    }
+
+    pub fn hides_status(&self, status: &TodoStatus) -> bool {
+        self.hidden_statuses.contains(status)
+    }
+
+    /// Whether `todo` passes. `todos` is every todo, for due dates that
+    /// roll up from subtasks.
+    pub fn matches(&self, todo: &Todo, todos: &[Todo], now: DateTime<Zone>) -> bool {
+        self.matches_in_week(todo, todos, now, self.week_start.unwrap_or(Weekday::Mon))
+    }
+
+    /// `matches` with the week starting on `week_start`, for terms inside
+    /// an expression, which go by the week of the whole filter.
+    pub fn matches_in_week(
+        &self,
+        todo: &Todo,
+        todos: &[Todo],
+        now: DateTime<Zone>,
+        week_start: Weekday,
+    ) -> bool {
+        !self.hidden_statuses.contains(&todo.status())
+            && self
+                .category
+                .as_ref()
+                .is_none_or(|category| has_category(todo, category))
+            && self.tag.as_ref().is_none_or(|tag| has_tag(todo, tag))
+            && self
+                .due
+                .is_none_or(|window| due_in(window, todo, todos, now, week_start))
+            && self
+                .expression
+                .as_ref()
+                .is_none_or(|(_, expr)| expr.eval(todo, todos, now, week_start))
+    }
+
+    /// A short description for titles, or None when nothing is filtered.
+    pub fn label(&self) -> Option<String> {
+        let mut parts = vec![];
+        if let Some(window) = self.due {
+            parts.push(window.label().to_string());
+        }
+        if let Some(category) = &self.category {
+            parts.push(format!("category {}", category));
+        }
+        if let Some(tag) = &self.tag {
+            parts.push(format!("#{}", tag));
+        }
+        for status in &self.hidden_statuses {
+            parts.push(format!("no {:?}", status));
+        }
+        if let Some((source, _)) = &self.expression {
+            parts.push(source.clone());
+        }
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn berlin() -> Zone {
+        Zone::parse("Europe/Berlin").unwrap()
+    }
+
+    /// `h:m` local time in Berlin on a day of June 2024, which starts on
+    /// a Saturday; the 2nd is a Sunday, the 3rd a Monday.
+    fn local(day: u32, h: u32, m: u32) -> DateTime<Zone> {
+        berlin().with_ymd_and_hms(2024, 6, day, h, m, 0).unwrap()
+    }
+
+    fn todo(id: usize, due: Option<DateTime<Zone>>) -> Todo {
+        let mut todo = Todo::new(format!("todo {}", id), "work".to_string(), Utc::now());
+        todo.id = id;
+        todo.due = due.map(|due| due.with_timezone(&Utc));
+        todo
+    }
+
+    fn due(
+        window: DueWindow,
+        due: DateTime<Zone>,
+        now: DateTime<Zone>,
+        week_start: Weekday,
+    ) -> bool {
+        let todo = todo(1, Some(due));
+        due_in(window, &todo, &[], now, week_start)
+    }
+
+    #[test]
+    fn today_runs_between_local_midnights() {
+        let now = local(4, 23, 59);
+        assert!(due(DueWindow::Today, local(4, 0, 0), now, Weekday::Mon));
+        assert!(due(DueWindow::Today, local(4, 23, 59), now, Weekday::Mon));
+        assert!(!due(DueWindow::Today, local(5, 0, 0), now, Weekday::Mon));
+        assert!(!due(DueWindow::Today, local(3, 23, 59), now, Weekday::Mon));
+        // just after midnight yesterday's todos are no longer today's
+        assert!(!due(
+            DueWindow::Today,
+            local(4, 12, 0),
+            local(5, 0, 1),
+            Weekday::Mon
+        ));
+    }
+
+    #[test]
+    fn the_week_follows_the_configured_start() {
+        let sunday_night = local(2, 23, 30);
+        let monday = local(3, 0, 0);
+        // on Monday weeks, Sunday night still belongs to the week before
+        assert!(!due(DueWindow::Week, monday, sunday_night, Weekday::Mon));
+        assert!(due(
+            DueWindow::Week,
+            local(1, 9, 0),
+            sunday_night,
+            Weekday::Mon
+        ));
+        assert!(due(DueWindow::Week, local(9, 23, 59), monday, Weekday::Mon));
+        assert!(!due(DueWindow::Week, local(10, 0, 0), monday, Weekday::Mon));
+        // on Sunday weeks, Sunday night starts the week Monday is in
+        assert!(due(DueWindow::Week, monday, sunday_night, Weekday::Sun));
+        assert!(!due(
+            DueWindow::Week,
+            local(1, 9, 0),
+            sunday_night,
+            Weekday::Sun
+        ));
+    }
+
+    #[test]
+    fn overdue_is_before_now_and_never_done() {
+        let now = local(4, 12, 0);
+        assert!(due(DueWindow::Overdue, local(4, 11, 59), now, Weekday::Mon));
+        assert!(!due(DueWindow::Overdue, now, now, Weekday::Mon));
+        assert!(!due(DueWindow::Overdue, local(5, 9, 0), now, Weekday::Mon));
+
+        let mut done = todo(1, Some(local(1, 9, 0)));
+        done.set_status(TodoStatus::Done, Utc::now());
+        assert!(!due_in(DueWindow::Overdue, &done, &[], now, Weekday::Mon));
+
+        let undated = todo(2, None);
+        for window in [DueWindow::Today, DueWindow::Week, DueWindow::Overdue] {
+            assert!(!due_in(window, &undated, &[], now, Weekday::Mon));
+        }
+    }
+
+    #[test]
+    fn terms_combine_and_bad_ones_are_errors() {
+        let filter = Filter::parse("done:false, category:work,tag:deep,due:today").unwrap();
+        assert!(filter.hides_status(&TodoStatus::Done));
+        assert!(!filter.hides_status(&TodoStatus::Todo));
+        assert_eq!(filter.category(), Some("work"));
+        assert_eq!(filter.due, Some(DueWindow::Today));
+        assert_eq!(
+            filter.label().as_deref(),
+            Some("due today, category work, #deep, no Done")
+        );
+        assert_eq!(Filter::parse("").unwrap().label(), None);
+        assert!(!Filter::parse("done:false,done:true")
+            .unwrap()
+            .hides_status(&TodoStatus::Done));
+
+        for (spec, error) in [
+            ("done", "expected key:value, got 'done'"),
+            ("done:maybe", "expected true or false in 'done:maybe'"),
+            ("due:soon", "expected today, week or overdue in 'due:soon'"),
+            ("colour:red", "unknown filter key 'colour'"),
+        ] {
+            assert_eq!(Filter::parse(spec).err().as_deref(), Some(error));
+        }
+    }
+
+    #[test]
+    fn a_todo_must_pass_every_term() {
+        let now = local(4, 12, 0);
+        let mut todos = vec![
+            todo(1, Some(local(4, 18, 0))),
+            todo(2, Some(local(4, 18, 0))),
+        ];
+        todos[0].tags = vec!["deep".to_string()];
+        todos[1].category = "home".to_string();
+        let filter = Filter::parse("category:work,due:today").unwrap();
+        assert!(filter.matches(&todos[0], &todos, now));
+        assert!(!filter.matches(&todos[1], &todos, now));
+        let filter = Filter::parse("tag:deep,due:overdue").unwrap();
+        assert!(!filter.matches(&todos[0], &todos, now));
+    }
+}
@@ -0,0 +1,320 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use tui::{
+    layout::Constraint,
+    style::{Color, Modifier, Style},
+    text::Span,
+    widgets::{Cell, Row, Table},
+};
+
+use crate::calendar::{self, DaysOff};
+use crate::chrome::Chrome;
+use crate::config::Config;
+use crate::timetracking::{
+    format_duration, format_signed_duration, local_midnight, time_per_category, week_bounds,
+    TimeEntry,
+};
+use crate::zone::{self, Zone};
+use crate::Error;
+
+const OVERRIDES_PATH: &str = "./data/target_overrides.json";
+
+/// Hours to work on specific dates, replacing the weekday target.
+pub type Overrides = BTreeMap<NaiveDate, f64>;
+
+pub fn read_overrides() -> Result<Overrides, Error> {
+    match fs::read_to_string(OVERRIDES_PATH) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Overrides::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Nothing on days off, else the date's override or its weekday's target.
+pub fn target(
+    config: &Config,
+    overrides: &Overrides,
+    calendar: &[DaysOff],
+    date: NaiveDate,
+) -> Duration {
+    if calendar::day_off(calendar, date).is_some() {
+        return Duration::zero();
+    }
+    let hours = overrides
+        .get(&date)
+        .or_else(|| config.daily_targets.get(&date.weekday()))
+        .cloned()
+        .unwrap_or(0.0);
+    Duration::seconds((hours * 3600.0).round() as i64)
+}
+
+pub struct DayBalance {
+    pub date: NaiveDate,
+    pub tracked: Duration,
+    pub target: Duration,
+}
+
+impl DayBalance {
+    pub fn delta(&self) -> Duration {
+        self.tracked - self.target
+    }
+}
+
+/// One row per day of `zone` in `[from, to]`, including days nothing was
+/// tracked.
+#[allow(clippy::too_many_arguments)]
+pub fn days(
+    entries: &[TimeEntry],
+    config: &Config,
+    overrides: &Overrides,
+    calendar: &[DaysOff],
+    from: NaiveDate,
+    to: NaiveDate,
+    zone: Zone,
+    now: DateTime<Utc>,
+) -> Vec<DayBalance> {
+    from.iter_days()
+        .take_while(|date| *date <= to)
+        .map(|date| DayBalance {
+            date,
+            tracked: time_per_category(
+                entries,
+                local_midnight(date, zone),
+                local_midnight(date + Duration::days(1), zone),
+                now,
+            )
+            .values()
+            .fold(Duration::zero(), |sum, d| sum + *d),
+            target: target(config, overrides, calendar, date),
+        })
+        .collect()
+}
+
+/// The flex-time balance: the sum of every day's tracked minus target.
+pub fn balance(days: &[DayBalance]) -> Duration {
+    days.iter()
+        .fold(Duration::zero(), |balance, day| balance + day.delta())
+}
+
+/// The balance from the 1st of the current month through today.
+pub fn month_balance(
+    entries: &[TimeEntry],
+    config: &Config,
+    overrides: &Overrides,
+    calendar: &[DaysOff],
+    now: DateTime<Utc>,
+) -> Duration {
+    let zone = zone::current();
+    let today = now.with_timezone(&zone).date_naive();
+    balance(&days(
+        entries,
+        config,
+        overrides,
+        calendar,
+        today.with_day(1).expect("every month has a first day"),
+        today,
+        zone,
+        now,
+    ))
+}
+
+/// The current week day by day, days off grayed, titled with the balance
+/// of the month so far.
+pub fn render_days<'a>(
+    entries: &[TimeEntry],
+    overrides: &Overrides,
+    calendar: &[DaysOff],
+    config: &Config,
+    now: DateTime<Utc>,
+    chrome: Chrome,
+) -> Table<'a> {
+    let zone = zone::current();
+    let today = now.with_timezone(&zone).date_naive();
+    let week_start = week_bounds(now.with_timezone(&zone), config.week_start)
+        .0
+        .with_timezone(&zone)
+        .date_naive();
+    let week = days(
+        entries, config, overrides, calendar, week_start, today, zone, now,
+    );
+
+    let rows: Vec<Row> = week
+        .iter()
+        .map(|day| {
+            let color = if day.delta() < Duration::zero() {
+                Color::Red
+            } else {
+                Color::Green
+            };
+            let off = calendar::day_off(calendar, day.date);
+            let label = match off {
+                Some(_) => format!("{} off", day.date.format("%a %d")),
+                None => day.date.format("%a %d").to_string(),
+            };
+            let row = Row::new(vec![
+                Cell::from(Span::raw(label)),
+                Cell::from(Span::raw(format_duration(day.tracked))),
+                Cell::from(Span::raw(format_duration(day.target))),
+                Cell::from(Span::styled(
+                    format_signed_duration(day.delta()),
+                    Style::default().fg(color),
+                )),
+            ]);
+            match off {
+                Some(_) => row.style(Style::default().fg(Color::DarkGray)),
+                None => row,
+            }
+        })
+        .collect();
+
+    Table::new(rows)
+        .header(Row::new(
+            ["Day", "Tracked", "Target", "Delta"]
+                .iter()
+                .map(|title| {
+                    Cell::from(Span::styled(
+                        *title,
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ))
+                })
+                .collect::<Vec<_>>(),
+        ))
+        .block(chrome.block().title(format!(
+            "Days - {} this month",
+            format_signed_duration(month_balance(entries, config, overrides, calendar, now))
+        )))
+        .widths(&[
+            Constraint::Percentage(25), // day
+            Constraint::Percentage(25), // tracked
+            Constraint::Percentage(25), // target
+            Constraint::Percentage(25), // delta
+        ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::OffKind;
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        for weekday in [
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+        ] {
+            config.daily_targets.insert(weekday, 8.0);
+        }
+        config
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        // 2024-12-02 is a Monday
+        NaiveDate::from_ymd_opt(2024, 12, day).unwrap()
+    }
+
+    /// `hours` tracked from 9:00 UTC on `day`.
+    fn entry(id: usize, day: u32, hours: f64) -> TimeEntry {
+        let start = Utc.from_utc_datetime(&date(day).and_hms_opt(9, 0, 0).unwrap());
+        let end = start + Duration::seconds((hours * 3600.0) as i64);
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "category": "work",
+            "start": start,
+            "end": end,
+        }))
+        .unwrap()
+    }
+
+    fn utc() -> Zone {
+        Zone::parse("UTC").unwrap()
+    }
+
+    fn deltas(days: &[DayBalance]) -> Vec<i64> {
+        days.iter().map(|day| day.delta().num_minutes()).collect()
+    }
+
+    #[test]
+    fn weekends_have_no_target_and_holidays_take_their_override() {
+        let config = config();
+        let mut overrides = Overrides::new();
+        overrides.insert(date(4), 4.0);
+        let targets: Vec<i64> = (2..=8)
+            .map(|day| target(&config, &overrides, &[], date(day)).num_hours())
+            .collect();
+        assert_eq!(targets, [8, 8, 4, 8, 8, 0, 0]);
+
+        let calendar = [DaysOff {
+            from: date(5),
+            to: date(6),
+            kind: OffKind::Vacation,
+        }];
+        assert_eq!(
+            target(&config, &overrides, &calendar, date(5)),
+            Duration::zero()
+        );
+        assert_eq!(
+            target(&config, &overrides, &calendar, date(4)),
+            Duration::hours(4)
+        );
+    }
+
+    #[test]
+    fn days_with_nothing_tracked_still_count_against_the_target() {
+        let entries = [entry(1, 2, 9.0), entry(2, 4, 4.5), entry(3, 7, 1.0)];
+        let mut overrides = Overrides::new();
+        overrides.insert(date(4), 4.0);
+        let now = Utc.from_utc_datetime(&date(8).and_hms_opt(23, 0, 0).unwrap());
+        let week = days(
+            &entries,
+            &config(),
+            &overrides,
+            &[],
+            date(2),
+            date(8),
+            utc(),
+            now,
+        );
+        assert_eq!(week.len(), 7);
+        // Tuesday and Friday tracked nothing, Saturday was a bonus
+        assert_eq!(deltas(&week), [60, -480, 30, -480, -480, 60, 0]);
+        assert_eq!(balance(&week), Duration::minutes(60 + 30 + 60 - 3 * 480));
+    }
+
+    #[test]
+    fn a_running_entry_counts_up_to_now_and_an_empty_range_is_even() {
+        let mut running = entry(1, 2, 0.0);
+        running.end = None;
+        let now = Utc.from_utc_datetime(&date(2).and_hms_opt(12, 30, 0).unwrap());
+        let today = days(
+            &[running],
+            &config(),
+            &Overrides::new(),
+            &[],
+            date(2),
+            date(2),
+            utc(),
+            now,
+        );
+        assert_eq!(today[0].tracked, Duration::minutes(210));
+        assert_eq!(balance(&today), Duration::minutes(210 - 480));
+
+        let none = days(
+            &[],
+            &config(),
+            &Overrides::new(),
+            &[],
+            date(3),
+            date(2),
+            utc(),
+            now,
+        );
+        assert!(none.is_empty());
+        assert_eq!(balance(&none), Duration::zero());
+    }
+}
@@ -0,0 +1,181 @@
+use crate::filter::Filter;
+use crate::timetracking::{self, EntryKind, TimeEntry};
+
+struct Following {
+    /// The filter from before the first of timers started back to back.
+    before: Filter,
+    /// The label of the filter set, to notice it was changed by hand.
+    applied: Option<String>,
+}
+
+/// The board filter following the category of the running timer, with
+/// `filter_follows_timer` on: a timer that starts narrows the board to
+/// its category and the filter from before comes back once it stops.
+#[derive(Default)]
+pub struct FilterFollow {
+    following: Option<Following>,
+    /// The running entry last seen, so each timer is followed once, when
+    /// it starts.
+    seen: Option<usize>,
+}
+
+impl FilterFollow {
+    pub fn is_following(&self) -> bool {
+        self.following.is_some()
+    }
+
+    /// Catches up with the timers in `entries` given the board's filter
+    /// now, returning the filter to put on the board if it changes.
+    ///
+    /// A timer started while another was followed keeps the filter from
+    /// before the first to restore. A filter changed by hand breaks the
+    /// link: it stays when the timer stops, and the next timer to start
+    /// is followed from it. A break changes nothing.
+    pub fn step(&mut self, entries: &[TimeEntry], current: &Filter) -> Option<Filter> {
+        if let Some(following) = &self.following {
+            if current.label() != following.applied {
+                self.following = None;
+            }
+        }
+        if timetracking::current_break(entries).is_some() {
+            return None;
+        }
+        let running = entries
+            .iter()
+            .filter(|e| e.end.is_none() && e.kind == EntryKind::Work)
+            .max_by_key(|e| e.start);
+        match running {
+            Some(entry) if self.seen == Some(entry.id) => None,
+            Some(entry) => {
+                self.seen = Some(entry.id);
+                let before = match self.following.take() {
+                    Some(following) => following.before,
+                    None => current.clone(),
+                };
+                let mut applied = before.clone();
+                applied.set_category(Some(entry.category.clone()));
+                self.following = Some(Following {
+                    before,
+                    applied: applied.label(),
+                });
+                Some(applied)
+            }
+            None => {
+                self.seen = None;
+                self.following.take().map(|following| following.before)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone, Utc};
+    use serde_json::json;
+
+    fn entry(id: usize, category: &str, minute: i64, kind: &str) -> TimeEntry {
+        let start = Utc.with_ymd_and_hms(2024, 5, 15, 9, 0, 0).unwrap() + Duration::minutes(minute);
+        serde_json::from_value(json!({
+            "id": id,
+            "category": category,
+            "start": start,
+            "kind": kind,
+        }))
+        .unwrap()
+    }
+
+    fn running(id: usize, category: &str, minute: i64) -> TimeEntry {
+        entry(id, category, minute, "work")
+    }
+
+    fn stopped(mut entry: TimeEntry) -> TimeEntry {
+        entry.end = Some(entry.start + Duration::minutes(5));
+        entry
+    }
+
+    fn category(filter: &Option<Filter>) -> Option<&str> {
+        filter.as_ref().and_then(Filter::category)
+    }
+
+    #[test]
+    fn a_timer_narrows_the_board_and_stopping_restores_it() {
+        let before = Filter::parse("tag:home").unwrap();
+        let mut follow = FilterFollow::default();
+        let applied = follow.step(&[running(1, "work", 0)], &before);
+        assert_eq!(category(&applied), Some("work"));
+        assert!(follow.is_following());
+
+        // the same timer is followed once
+        let applied = applied.unwrap();
+        assert!(follow.step(&[running(1, "work", 0)], &applied).is_none());
+
+        let restored = follow.step(&[stopped(running(1, "work", 0))], &applied);
+        assert_eq!(restored.unwrap().label(), before.label());
+        assert!(!follow.is_following());
+    }
+
+    #[test]
+    fn back_to_back_timers_restore_the_filter_from_before_the_first() {
+        let before = Filter::parse("tag:home").unwrap();
+        let mut follow = FilterFollow::default();
+        let first = follow.step(&[running(1, "work", 0)], &before).unwrap();
+
+        let entries = [stopped(running(1, "work", 0)), running(2, "email", 5)];
+        let second = follow.step(&entries, &first);
+        assert_eq!(category(&second), Some("email"));
+
+        let entries = [
+            stopped(running(1, "work", 0)),
+            stopped(running(2, "email", 5)),
+        ];
+        let restored = follow.step(&entries, &second.unwrap()).unwrap();
+        assert_eq!(restored.label(), before.label());
+    }
+
+    #[test]
+    fn a_filter_changed_by_hand_stays_when_the_timer_stops() {
+        let mut follow = FilterFollow::default();
+        follow.step(&[running(1, "work", 0)], &Filter::default());
+        let by_hand = Filter::parse("tag:urgent").unwrap();
+        assert!(follow.step(&[running(1, "work", 0)], &by_hand).is_none());
+        assert!(!follow.is_following());
+        assert!(follow
+            .step(&[stopped(running(1, "work", 0))], &by_hand)
+            .is_none());
+
+        // the next timer is followed from it
+        let entries = [stopped(running(1, "work", 0)), running(2, "email", 5)];
+        let applied = follow.step(&entries, &by_hand).unwrap();
+        assert_eq!(applied.category(), Some("email"));
+        let restored = follow
+            .step(&[stopped(running(2, "email", 5))], &applied)
+            .unwrap();
+        assert_eq!(restored.label(), by_hand.label());
+    }
+
+    #[test]
+    fn a_break_changes_nothing() {
+        let mut follow = FilterFollow::default();
+        let applied = follow
+            .step(&[running(1, "work", 0)], &Filter::default())
+            .unwrap();
+        let entries = [
+            stopped(running(1, "work", 0)),
+            entry(2, "break", 5, "break"),
+        ];
+        assert!(follow.step(&entries, &applied).is_none());
+        assert!(follow.is_following());
+
+        // resuming the same timer keeps following it
+        let entries = [
+            stopped(running(1, "work", 0)),
+            stopped(entry(2, "break", 5, "break")),
+            running(3, "work", 10),
+        ];
+        let applied = follow.step(&entries, &applied).unwrap();
+        assert_eq!(applied.category(), Some("work"));
+        let restored = follow.step(&[stopped(running(3, "work", 10))], &applied);
+        assert_eq!(restored.unwrap().label(), None);
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::env;
+
+use crate::config::Config;
+
+/// Glyphs for categories the config doesn't map, picked by a hash of the
+/// name so a category keeps its glyph from run to run.
+const POOL: [&str; 8] = ["◆", "●", "▲", "■", "★", "♦", "♣", "♠"];
+const ASCII_POOL: [&str; 8] = ["*", "+", "#", "%", "&", "=", "~", "^"];
+
+/// The glyph shown before a todo's title for its category, e.g. "⌂" for
+/// home. Off until `category_glyphs` in the config maps some category;
+/// from then on every category gets one.
+pub struct Glyphs {
+    glyphs: BTreeMap<String, String>,
+    ascii_glyphs: BTreeMap<String, String>,
+    ascii: bool,
+}
+
+impl Glyphs {
+    /// ASCII only when the config says so with `ascii_only` or the
+    /// terminal doesn't look like it draws Unicode.
+    pub fn from_config(config: &Config) -> Glyphs {
+        Glyphs {
+            glyphs: config.category_glyphs.clone(),
+            ascii_glyphs: config.category_glyphs_ascii.clone(),
+            ascii: config.ascii_only || !unicode_terminal(),
+        }
+    }
+
+    /// The glyph for `category`: the configured one, in ASCII mode the
+    /// configured ASCII one or the configured one if it is ASCII already,
+    /// else one from the pool. None when glyphs are off or the todo has
+    /// no category.
+    pub fn for_category(&self, category: &str) -> Option<String> {
+        if self.glyphs.is_empty() || category.is_empty() {
+            return None;
+        }
+        let configured = if self.ascii {
+            self.ascii_glyphs
+                .get(category)
+                .or_else(|| self.glyphs.get(category).filter(|g| g.is_ascii()))
+        } else {
+            self.glyphs.get(category)
+        };
+        if let Some(glyph) = configured {
+            return Some(glyph.clone());
+        }
+        let pool = if self.ascii { &ASCII_POOL } else { &POOL };
+        Some(pool[(fnv1a(category) % pool.len() as u64) as usize].to_string())
+    }
+
+    /// The glyph and a space, to put before a title; empty without one.
+    pub fn prefix(&self, category: &str) -> String {
+        self.for_category(category)
+            .map(|glyph| format!("{} ", glyph))
+            .unwrap_or_default()
+    }
+}
+
+/// FNV-1a, which unlike the std hasher is the same on every build.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Whether the terminal seems to draw Unicode: not the Linux console or a
+/// dumb terminal, with a UTF-8 locale.
+pub fn unicode_terminal() -> bool {
+    if matches!(env::var("TERM").as_deref(), Ok("linux") | Ok("dumb")) {
+        return false;
+    }
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()));
+    match locale {
+        Some(locale) => {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        }
+        // nothing set, as on Windows, which draws Unicode
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyphs(mapped: &[(&str, &str)], ascii_mapped: &[(&str, &str)], ascii: bool) -> Glyphs {
+        let map = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(category, glyph)| (category.to_string(), glyph.to_string()))
+                .collect()
+        };
+        Glyphs {
+            glyphs: map(mapped),
+            ascii_glyphs: map(ascii_mapped),
+            ascii,
+        }
+    }
+
+    #[test]
+    fn glyphs_are_off_until_a_category_is_mapped() {
+        let off = glyphs(&[], &[("home", "H")], false);
+        assert_eq!(off.for_category("home"), None);
+        assert_eq!(off.prefix("home"), "");
+        let on = glyphs(&[("home", "⌂")], &[], false);
+        assert_eq!(on.for_category(""), None);
+        assert_eq!(on.prefix("home"), "⌂ ");
+    }
+
+    #[test]
+    fn an_unmapped_category_gets_the_same_pool_glyph_every_time() {
+        let on = glyphs(&[("home", "⌂")], &[], false);
+        let work = on.for_category("work").unwrap();
+        assert!(POOL.contains(&work.as_str()));
+        assert_eq!(on.for_category("work"), Some(work));
+        // the hash is fixed, not seeded per run
+        assert_eq!(fnv1a(""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a("a"), 0xaf63dc4c8601ec8c);
+        let categories = ["work", "errands", "health", "finance", "garden", "music"];
+        let distinct: std::collections::HashSet<String> = categories
+            .iter()
+            .filter_map(|category| on.for_category(category))
+            .collect();
+        assert!(distinct.len() > 1);
+    }
+
+    #[test]
+    fn ascii_mode_uses_the_stand_ins_then_ascii_glyphs_then_the_ascii_pool() {
+        let ascii = glyphs(
+            &[("home", "⌂"), ("work", "W"), ("gym", "🏋")],
+            &[("home", "h")],
+            true,
+        );
+        assert_eq!(ascii.for_category("home").as_deref(), Some("h"));
+        assert_eq!(ascii.for_category("work").as_deref(), Some("W"));
+        for category in ["gym", "errands"] {
+            let glyph = ascii.for_category(category).unwrap();
+            assert!(ASCII_POOL.contains(&glyph.as_str()), "{}", glyph);
+        }
+        // the stand-ins are only for ASCII mode
+        let unicode = glyphs(&[("home", "⌂")], &[("home", "h")], false);
+        assert_eq!(unicode.for_category("home").as_deref(), Some("⌂"));
+    }
+
+    #[test]
+    fn the_config_can_force_ascii() {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "category_glyphs": {"home": "⌂"},
+            "category_glyphs_ascii": {"home": "h"},
+            "ascii_only": true,
+        }))
+        .unwrap();
+        assert_eq!(
+            Glyphs::from_config(&config).for_category("home").as_deref(),
+            Some("h")
+        );
+    }
+}
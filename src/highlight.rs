@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+
+/// Something on screen that can be briefly highlighted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Target {
+    DoneColumn,
+    /// A todo's row on the board, by id.
+    Todo(usize),
+}
+
+/// Short-lived visual emphasis that expires after a number of ticks, so
+/// features can draw attention to a spot without tracking timers themselves.
+#[derive(Default)]
+pub struct Highlights {
+    remaining_ticks: HashMap<Target, u32>,
+}
+
+impl Highlights {
+    /// Highlights `target` for the next `ticks` ticks, restarting it if it
+    /// is already lit.
+    pub fn flash(&mut self, target: Target, ticks: u32) {
+        self.remaining_ticks.insert(target, ticks);
+    }
+
+    pub fn is_lit(&self, target: Target) -> bool {
+        self.remaining_ticks.contains_key(&target)
+    }
+
+    pub fn any_lit(&self) -> bool {
+        !self.remaining_ticks.is_empty()
+    }
+
+    pub fn on_tick(&mut self) {
+        self.remaining_ticks.retain(|_, ticks| {
+            *ticks -= 1;
+            *ticks > 0
+        });
+    }
+}
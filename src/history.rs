@@ -0,0 +1,547 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use std::collections::BTreeMap;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Cell, Paragraph, Row, Table, TableState},
+    Frame,
+};
+
+use crate::attachments;
+use crate::chrome::Chrome;
+use crate::cursor;
+use crate::escalation::{effective_priority, EscalationRule};
+use crate::label;
+use crate::refs;
+use crate::subtasks;
+use crate::timetracking::{format_duration, TimeEntry};
+use crate::zone;
+use crate::{Todo, TodoStatus};
+
+/// The time entries spent on one todo, newest first, and their total.
+/// Running entries count up to `now`.
+pub struct WorkHistory {
+    pub sessions: Vec<TimeEntry>,
+    pub total: Duration,
+}
+
+pub fn work_history(entries: &[TimeEntry], todo_id: usize, now: DateTime<Utc>) -> WorkHistory {
+    let mut sessions: Vec<TimeEntry> = entries
+        .iter()
+        .filter(|e| e.todo_id == Some(todo_id))
+        .cloned()
+        .collect();
+    sessions.sort_by_key(|e| std::cmp::Reverse(e.start));
+    let total = sessions.iter().fold(Duration::zero(), |sum, e| {
+        sum + (e.end.unwrap_or(now) - e.start)
+    });
+    WorkHistory { sessions, total }
+}
+
+/// The total tracked on one todo, as shown in the detail pane.
+pub fn time_spent(entries: &[TimeEntry], todo_id: usize, now: DateTime<Utc>) -> Duration {
+    work_history(entries, todo_id, now).total
+}
+
+fn session_cells(entry: &TimeEntry, now: DateTime<Utc>) -> [String; 3] {
+    [
+        entry
+            .start
+            .with_timezone(&zone::current())
+            .format("%a %Y-%m-%d %H:%M")
+            .to_string(),
+        match entry.end {
+            Some(end) => format_duration(end - entry.start),
+            None => format!("{} (running)", format_duration(now - entry.start)),
+        },
+        entry.note.clone().unwrap_or_default(),
+    ]
+}
+
+/// The full screen view of one todo: its fields above, then its
+/// attachments with `selected_attachment` marked, and the sessions spent
+/// on it below, scrolled with `state`. `todos` is every todo, for its
+/// subtasks and the due date they roll up to. `referenced_by` lists the
+/// todos referring to this one, and `selected_link` the reference or
+/// backlink Enter follows. A color label shows after the title, in the
+/// color `label_colors` gives it.
+#[allow(clippy::too_many_arguments)]
+pub fn render_todo_view<B: Backend>(
+    rect: &mut Frame<B>,
+    todo: &Todo,
+    todos: &[Todo],
+    history: &WorkHistory,
+    state: &mut TableState,
+    selected_attachment: usize,
+    escalation: &[EscalationRule],
+    label_colors: &BTreeMap<String, Color>,
+    referenced_by: &[usize],
+    selected_link: Option<usize>,
+    now: DateTime<Utc>,
+    chrome: Chrome,
+    area: Rect,
+) {
+    let attachment_rows = todo.attachments.len().clamp(1, 5) as u16;
+    let (_, frame) = chrome.frame(false);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(12 + frame),
+                Constraint::Length(attachment_rows + frame),
+                Constraint::Min(4),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let label = |label: &str| {
+        Span::styled(
+            format!("{:<12}", label),
+            Style::default().add_modifier(Modifier::BOLD),
+        )
+    };
+    let field = |name: &str, value: String| Spans::from(vec![label(name), Span::raw(value)]);
+    let rollup = subtasks::rollup(todo, todos);
+    let due = match (rollup.due, rollup.driver) {
+        (None, _) => "none".to_string(),
+        (Some(due), driver) => {
+            let mut due = due
+                .with_timezone(&zone::current())
+                .format("%Y-%m-%d %H:%M")
+                .to_string();
+            if let Some(subtask) = driver.and_then(|id| todos.iter().find(|t| t.id == id)) {
+                due.push_str(&format!(", from subtask #{} {}", subtask.id, subtask.title));
+            }
+            due
+        }
+    };
+    let effective = effective_priority(todo, todos, now, escalation);
+    let priority = match (effective.priority, effective.reason(now)) {
+        (None, _) => "none".to_string(),
+        (Some(priority), None) => format!("{:?}", priority),
+        (Some(priority), Some(reason)) => format!(
+            "{:?} ({}, set {})",
+            priority,
+            reason,
+            todo.priority
+                .map_or("none".to_string(), |own| format!("{:?}", own))
+        ),
+    };
+    let subtasks: Vec<String> = subtasks::subtasks(todo.id, todos)
+        .iter()
+        .map(|t| match t.status() {
+            TodoStatus::Done => format!("✔ #{} {}", t.id, t.title),
+            _ => format!("#{} {}", t.id, t.title),
+        })
+        .collect();
+    let link_style = |id: usize, style: Style| {
+        if selected_link == Some(id) {
+            style.add_modifier(Modifier::REVERSED)
+        } else {
+            style
+        }
+    };
+    let ids = todos.iter().map(|t| t.id).collect();
+    let mut description = refs::spans(todo.description(), &ids, Style::default());
+    if let Some(selected) = selected_link {
+        let reference = format!("#{}", selected);
+        for span in description.0.iter_mut().filter(|s| s.content == reference) {
+            span.style = link_style(selected, span.style);
+        }
+    }
+    description.0.insert(0, label("Description"));
+    let mut backlinks = vec![label("Referenced by")];
+    for (i, source) in referenced_by.iter().enumerate() {
+        if i > 0 {
+            backlinks.push(Span::raw(", "));
+        }
+        backlinks.push(match todos.iter().find(|t| t.id == *source) {
+            Some(source) => Span::styled(
+                format!("#{} {}", source.id, source.title),
+                link_style(source.id, Style::default().fg(Color::Cyan)),
+            ),
+            None => Span::styled(
+                format!("#{}", source),
+                link_style(
+                    *source,
+                    Style::default()
+                        .fg(Color::DarkGray)
+                        .add_modifier(Modifier::CROSSED_OUT),
+                ),
+            ),
+        });
+    }
+    if referenced_by.is_empty() {
+        backlinks.push(Span::raw("none"));
+    }
+    let mut title = field(
+        "Title",
+        match todo.parent {
+            Some(parent) => format!("{} (subtask of #{})", todo.title, parent),
+            None => todo.title.clone(),
+        },
+    );
+    if let Some(name) = &todo.color {
+        let style = match label::color(name, label_colors) {
+            Some(color) => Style::default().fg(color).add_modifier(Modifier::BOLD),
+            None => Style::default().fg(Color::DarkGray),
+        };
+        title.0.push(Span::styled(format!("  ■ {}", name), style));
+    }
+    let fields = Paragraph::new(vec![
+        title,
+        description,
+        field("Category", todo.category.clone()),
+        field("Priority", priority),
+        field("Status", format!("{:?}", todo.status())),
+        field("Tags", todo.tags.join(", ")),
+        field("Due", due),
+        field(
+            "Subtasks",
+            if subtasks.is_empty() {
+                "none".to_string()
+            } else {
+                subtasks.join(", ")
+            },
+        ),
+        field(
+            "Created",
+            todo.created_at
+                .with_timezone(&zone::current())
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+        ),
+        field(
+            "Source",
+            todo.source.clone().unwrap_or_else(|| "unknown".to_string()),
+        ),
+        field("Spent", format_duration(history.total)),
+        Spans::from(backlinks),
+    ])
+    .block(chrome.block().title(format!(
+        "Todo {} (j/k: scroll history, Tab: next link, Enter: follow, Esc: close)",
+        todo.id
+    )));
+    rect.render_widget(fields, chunks[0]);
+
+    let mut lines: Vec<Spans> = todo
+        .attachments
+        .iter()
+        .enumerate()
+        .map(|(i, path)| {
+            let style = if i == selected_attachment {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            let mut spans = vec![Span::styled(path.display().to_string(), style)];
+            if !path.exists() {
+                spans.insert(0, Span::styled("! ", Style::default().fg(Color::Red)));
+                spans.push(Span::styled(" (missing)", Style::default().fg(Color::Red)));
+            }
+            Spans::from(spans)
+        })
+        .collect();
+    if lines.is_empty() {
+        lines.push(Spans::from(vec![Span::styled(
+            "no files attached",
+            Style::default().fg(Color::DarkGray),
+        )]));
+    }
+    let offset = (selected_attachment + 1).saturating_sub(attachment_rows as usize) as u16;
+    let attachments = Paragraph::new(lines).scroll((offset, 0)).block(
+        chrome
+            .block()
+            .title("Attachments - a: attach, h/l: select, o: open, d: remove"),
+    );
+    rect.render_widget(attachments, chunks[1]);
+
+    let rows: Vec<Row> = history
+        .sessions
+        .iter()
+        .map(|entry| Row::new(session_cells(entry, now).to_vec()))
+        .collect();
+    let table = Table::new(rows)
+        .header(Row::new(
+            ["Date", "Duration", "Note"]
+                .iter()
+                .map(|title| {
+                    Cell::from(Span::styled(
+                        *title,
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ))
+                })
+                .collect::<Vec<_>>(),
+        ))
+        .block(chrome.block().title(format!(
+            "Work history - {} sessions, {}",
+            history.sessions.len(),
+            format_duration(history.total)
+        )))
+        .highlight_style(cursor::current().highlight())
+        .highlight_symbol(cursor::current().symbol())
+        .widths(&[
+            Constraint::Percentage(30), // date
+            Constraint::Percentage(20), // duration
+            Constraint::Percentage(50), // note
+        ]);
+    rect.render_stateful_widget(table, chunks[2], state);
+}
+
+/// How much was tracked and in how many sessions, e.g. "1h 05m over 3
+/// sessions".
+fn spent_summary(history: &WorkHistory) -> String {
+    match history.sessions.len() {
+        0 => "nothing tracked yet".to_string(),
+        1 => format!("{} in 1 session", format_duration(history.total)),
+        count => format!("{} over {} sessions", format_duration(history.total), count),
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum SnippetFormat {
+    Markdown,
+    Text,
+}
+
+/// One todo in a few lines to paste elsewhere: the title, a metadata line,
+/// the description as it is, its `subtasks` as checkboxes and what was
+/// tracked on it.
+pub fn snippet(
+    todo: &Todo,
+    subtasks: &[&Todo],
+    history: &WorkHistory,
+    format: SnippetFormat,
+) -> String {
+    let mut meta = vec![];
+    if !todo.category.is_empty() {
+        meta.push(todo.category.clone());
+    }
+    if let Some(priority) = todo.priority {
+        meta.push(format!("{:?}", priority));
+    }
+    if let Some(label) = &todo.color {
+        meta.push(format!("label {}", label));
+    }
+    if let Some(due) = todo.due {
+        meta.push(format!(
+            "due {}",
+            due.with_timezone(&zone::current()).format("%Y-%m-%d %H:%M")
+        ));
+    }
+    meta.push(format!("#{}", todo.id));
+
+    let mut out = match format {
+        SnippetFormat::Markdown => format!("## {}\n\n", todo.title),
+        SnippetFormat::Text => format!("{}\n", todo.title),
+    };
+    out.push_str(&format!("{}\n\n", meta.join(" · ")));
+    if !todo.description().is_empty() {
+        out.push_str(&format!("{}\n\n", todo.description()));
+    }
+    if !subtasks.is_empty() {
+        for subtask in subtasks {
+            let check = if subtask.status() == TodoStatus::Done {
+                "x"
+            } else {
+                " "
+            };
+            let bullet = match format {
+                SnippetFormat::Markdown => "- ",
+                SnippetFormat::Text => "",
+            };
+            out.push_str(&format!(
+                "{}[{}] {} (#{})\n",
+                bullet, check, subtask.title, subtask.id
+            ));
+        }
+        out.push('\n');
+    }
+    if !todo.attachments.is_empty() {
+        out.push_str(&format!("{}\n", attachment_links(todo, format).join("\n")));
+    }
+    out.push_str(&format!("Time spent: {}\n", spent_summary(history)));
+    out
+}
+
+/// A line per attachment: a Markdown link, or just the path for text.
+fn attachment_links(todo: &Todo, format: SnippetFormat) -> Vec<String> {
+    todo.attachments
+        .iter()
+        .map(|path| match format {
+            // <...> keeps paths with spaces in one link
+            SnippetFormat::Markdown => {
+                format!("- [{}](<{}>)", attachments::label(path), path.display())
+            }
+            SnippetFormat::Text => format!("- {}", path.display()),
+        })
+        .collect()
+}
+
+/// One todo and its work history as a Markdown document.
+pub fn markdown(todo: &Todo, history: &WorkHistory, now: DateTime<Utc>) -> String {
+    let mut out = format!("# {}\n\n", todo.title);
+    if !todo.description().is_empty() {
+        out.push_str(&format!("{}\n\n", todo.description()));
+    }
+    out.push_str(&format!("- Status: {:?}\n", todo.status()));
+    if !todo.category.is_empty() {
+        out.push_str(&format!("- Category: {}\n", todo.category));
+    }
+    if !todo.tags.is_empty() {
+        out.push_str(&format!("- Tags: {}\n", todo.tags.join(", ")));
+    }
+    if let Some(label) = &todo.color {
+        out.push_str(&format!("- Label: {}\n", label));
+    }
+    out.push_str(&format!(
+        "- Created: {}\n",
+        todo.created_at
+            .with_timezone(&zone::current())
+            .format("%Y-%m-%d %H:%M")
+    ));
+    out.push_str(&format!(
+        "- Source: {}\n",
+        todo.source.as_deref().unwrap_or("unknown")
+    ));
+
+    if !todo.attachments.is_empty() {
+        out.push_str("\n## Attachments\n\n");
+        out.push_str(&format!(
+            "{}\n",
+            attachment_links(todo, SnippetFormat::Markdown).join("\n")
+        ));
+    }
+
+    out.push_str("\n## Work history\n\n");
+    if history.sessions.is_empty() {
+        out.push_str("Nothing tracked yet.\n");
+        return out;
+    }
+    out.push_str("| Date | Duration | Note |\n|---|---|---|\n");
+    for entry in &history.sessions {
+        let [date, duration, note] = session_cells(entry, now);
+        out.push_str(&format!(
+            "| {} | {} | {} |\n",
+            date,
+            duration,
+            note.replace('|', "\\|")
+        ));
+    }
+    out.push_str(&format!("\n**Total:** {}\n", spent_summary(history)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetracking::push_running;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 6, hour, minute, 0).unwrap()
+    }
+
+    fn entry(
+        entries: &mut Vec<TimeEntry>,
+        todo_id: Option<usize>,
+        from: DateTime<Utc>,
+        minutes: Option<i64>,
+        note: &str,
+    ) {
+        let note = Some(note.to_string()).filter(|n| !n.is_empty());
+        let id = push_running(entries, "work", from, note, todo_id, None);
+        let entry = entries.iter_mut().find(|e| e.id == id).unwrap();
+        entry.end = minutes.map(|m| from + Duration::minutes(m));
+    }
+
+    fn local(at: DateTime<Utc>) -> String {
+        at.with_timezone(&zone::current())
+            .format("%a %Y-%m-%d %H:%M")
+            .to_string()
+    }
+
+    #[test]
+    fn the_history_holds_the_todos_sessions_newest_first() {
+        let mut entries = vec![];
+        entry(&mut entries, Some(1), at(9, 0), Some(25), "first");
+        entry(&mut entries, Some(2), at(10, 0), Some(60), "");
+        entry(&mut entries, Some(1), at(11, 0), None, "running");
+        entry(&mut entries, Some(1), at(8, 0), Some(5), "");
+
+        let history = work_history(&entries, 1, at(11, 30));
+        let starts: Vec<_> = history.sessions.iter().map(|e| e.start).collect();
+        assert_eq!(starts, [at(11, 0), at(9, 0), at(8, 0)]);
+        assert_eq!(history.total, Duration::minutes(25 + 30 + 5));
+        assert_eq!(time_spent(&entries, 1, at(11, 30)), history.total);
+    }
+
+    #[test]
+    fn orphaned_entries_belong_to_no_todo() {
+        let mut entries = vec![];
+        entry(&mut entries, None, at(9, 0), Some(30), "no todo");
+        // the todo it was spent on has been deleted
+        entry(&mut entries, Some(9), at(10, 0), Some(30), "deleted");
+
+        let history = work_history(&entries, 1, at(12, 0));
+        assert!(history.sessions.is_empty());
+        assert_eq!(history.total, Duration::zero());
+        // ids aren't reused, so they still add up under the deleted id only
+        assert_eq!(time_spent(&entries, 9, at(12, 0)), Duration::minutes(30));
+    }
+
+    #[test]
+    fn the_markdown_export_lists_every_session_and_the_total() {
+        let mut todo = Todo::new("Write report".to_string(), "work".to_string(), at(8, 0));
+        todo.id = 1;
+        todo.tags = vec!["q2".to_string()];
+        let mut entries = vec![];
+        entry(&mut entries, Some(1), at(9, 0), Some(25), "draft | outline");
+        entry(&mut entries, Some(1), at(10, 0), Some(70), "");
+
+        let history = work_history(&entries, 1, at(12, 0));
+        let expected = format!(
+            "# Write report\n\n\
+             - Status: Todo\n\
+             - Category: work\n\
+             - Tags: q2\n\
+             - Created: {}\n\
+             - Source: unknown\n\
+             \n## Work history\n\n\
+             | Date | Duration | Note |\n|---|---|---|\n\
+             | {} | 1h 10m |  |\n\
+             | {} | 25m | draft \\| outline |\n\
+             \n**Total:** 1h 35m over 2 sessions\n",
+            at(8, 0)
+                .with_timezone(&zone::current())
+                .format("%Y-%m-%d %H:%M"),
+            local(at(10, 0)),
+            local(at(9, 0)),
+        );
+        assert_eq!(markdown(&todo, &history, at(12, 0)), expected);
+
+        let empty = work_history(&[], 1, at(12, 0));
+        assert!(markdown(&todo, &empty, at(12, 0))
+            .ends_with("## Work history\n\nNothing tracked yet.\n"));
+    }
+
+    #[test]
+    fn the_snippet_sums_up_the_time_spent() {
+        let mut todo = Todo::new("Call".to_string(), "".to_string(), at(8, 0));
+        todo.id = 4;
+        let mut entries = vec![];
+        entry(&mut entries, Some(4), at(9, 0), None, "");
+        let history = work_history(&entries, 4, at(9, 40));
+        assert_eq!(
+            snippet(&todo, &[], &history, SnippetFormat::Text),
+            "Call\n#4\n\nTime spent: 40m in 1 session\n"
+        );
+        assert_eq!(
+            session_cells(&history.sessions[0], at(9, 40))[1],
+            "40m (running)"
+        );
+    }
+}
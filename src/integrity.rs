@@ -0,0 +1,506 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+#[cfg(feature = "tui")]
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::Paragraph,
+    Frame,
+};
+
+use crate::attachments;
+#[cfg(feature = "tui")]
+use crate::chrome::Chrome;
+#[cfg(feature = "tui")]
+use crate::cursor;
+use crate::subtasks::check_parent;
+use crate::timetracking::{self, update_time_entries, TimeEntry};
+use crate::undo::UndoStack;
+#[cfg(feature = "tui")]
+use crate::widgets::Picker;
+use crate::{read_db, update_db, Error, Todo};
+
+/// A reference left dangling by a deletion, a merge or a hand edit.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Problem {
+    /// A time entry linked to a todo that is gone.
+    EntryTodo { entry_id: usize, todo_id: usize },
+    /// A subtask whose parent is gone.
+    Parent { todo_id: usize, parent: usize },
+    /// An attached file that is gone.
+    Attachment { todo_id: usize, path: PathBuf },
+}
+
+/// What can be done about a problem.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fix {
+    /// Point the reference at another todo.
+    Relink,
+    /// Drop the reference, keeping what holds it.
+    Clear,
+    /// Delete the time entry.
+    DeleteEntry,
+}
+
+impl Problem {
+    pub fn describe(&self) -> String {
+        match self {
+            Problem::EntryTodo { entry_id, todo_id } => {
+                format!(
+                    "time entry {} is linked to missing todo #{}",
+                    entry_id, todo_id
+                )
+            }
+            Problem::Parent { todo_id, parent } => {
+                format!("todo #{} is a subtask of missing todo #{}", todo_id, parent)
+            }
+            Problem::Attachment { todo_id, path } => {
+                format!("todo #{} has a missing file {}", todo_id, path.display())
+            }
+        }
+    }
+
+    /// The fixes that apply to it.
+    pub fn fixes(&self) -> &'static [Fix] {
+        match self {
+            Problem::EntryTodo { .. } => &[Fix::Relink, Fix::Clear, Fix::DeleteEntry],
+            Problem::Parent { .. } => &[Fix::Relink, Fix::Clear],
+            Problem::Attachment { .. } => &[Fix::Clear],
+        }
+    }
+}
+
+/// Time entries linked to todos that aren't there.
+pub fn orphaned_entries(todos: &[Todo], entries: &[TimeEntry]) -> Vec<Problem> {
+    let ids: HashSet<usize> = todos.iter().map(|t| t.id).collect();
+    entries
+        .iter()
+        .filter_map(|e| {
+            let todo_id = e.todo_id.filter(|id| !ids.contains(id))?;
+            Some(Problem::EntryTodo {
+                entry_id: e.id,
+                todo_id,
+            })
+        })
+        .collect()
+}
+
+/// Subtasks whose parent isn't there.
+pub fn orphaned_subtasks(todos: &[Todo]) -> Vec<Problem> {
+    let ids: HashSet<usize> = todos.iter().map(|t| t.id).collect();
+    todos
+        .iter()
+        .filter_map(|t| {
+            let parent = t.parent.filter(|id| !ids.contains(id))?;
+            Some(Problem::Parent {
+                todo_id: t.id,
+                parent,
+            })
+        })
+        .collect()
+}
+
+/// Attachments whose file is gone.
+pub fn missing_attachments(todos: &[Todo]) -> Vec<Problem> {
+    attachments::dangling(todos)
+        .into_iter()
+        .map(|(todo_id, path)| Problem::Attachment {
+            todo_id,
+            path: path.to_path_buf(),
+        })
+        .collect()
+}
+
+/// Every problem in the data, entries first.
+pub fn detect(todos: &[Todo], entries: &[TimeEntry]) -> Vec<Problem> {
+    let mut problems = orphaned_entries(todos, entries);
+    problems.extend(orphaned_subtasks(todos));
+    problems.extend(missing_attachments(todos));
+    problems
+}
+
+/// Applies `fix` to the todos, with `target` the todo to relink to. Returns
+/// whether anything changed.
+pub fn fix_todos(problem: &Problem, fix: Fix, target: Option<usize>, todos: &mut [Todo]) -> bool {
+    match (problem, fix) {
+        (Problem::Parent { todo_id, parent }, Fix::Relink | Fix::Clear) => {
+            let new = if fix == Fix::Relink { target } else { None };
+            if let Some(target) = new {
+                if check_parent(*todo_id, target, todos).is_err() {
+                    return false;
+                }
+            }
+            match todos
+                .iter_mut()
+                .find(|t| t.id == *todo_id && t.parent == Some(*parent))
+            {
+                Some(todo) => {
+                    todo.parent = new;
+                    true
+                }
+                None => false,
+            }
+        }
+        (Problem::Attachment { todo_id, path }, Fix::Clear) => {
+            match todos.iter_mut().find(|t| t.id == *todo_id) {
+                Some(todo) => {
+                    let before = todo.attachments.len();
+                    todo.attachments.retain(|p| p != path);
+                    todo.attachments.len() != before
+                }
+                None => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Applies `fix` to the time entries, with `target` the todo to relink
+/// to. Returns whether anything changed.
+pub fn fix_entries(
+    problem: &Problem,
+    fix: Fix,
+    target: Option<usize>,
+    entries: &mut Vec<TimeEntry>,
+) -> bool {
+    let (entry_id, todo_id) = match problem {
+        Problem::EntryTodo { entry_id, todo_id } => (*entry_id, *todo_id),
+        _ => return false,
+    };
+    let index = match entries
+        .iter()
+        .position(|e| e.id == entry_id && e.todo_id == Some(todo_id))
+    {
+        Some(index) => index,
+        None => return false,
+    };
+    match fix {
+        Fix::Relink if target.is_some() => entries[index].todo_id = target,
+        Fix::Relink => return false,
+        Fix::Clear => entries[index].todo_id = None,
+        Fix::DeleteEntry => {
+            entries.remove(index);
+        }
+    }
+    true
+}
+
+/// Applies `fix` to the data on disk right away. Todo changes can be
+/// undone. Returns whether anything changed.
+pub fn apply(
+    problem: &Problem,
+    fix: Fix,
+    target: Option<usize>,
+    undo: &mut UndoStack,
+) -> Result<bool, Error> {
+    if let Problem::EntryTodo { .. } = problem {
+        return update_time_entries(|entries| fix_entries(problem, fix, target, entries));
+    }
+    let (before, changed) = update_db(|todos| {
+        let before = todos.clone();
+        (before, fix_todos(problem, fix, target, todos))
+    })?;
+    if changed {
+        undo.push("fix", before, 0);
+    }
+    Ok(changed)
+}
+
+/// The problems as last detected, None once a change made them stale.
+static CACHE: Mutex<Option<Vec<Problem>>> = Mutex::new(None);
+
+fn cache() -> std::sync::MutexGuard<'static, Option<Vec<Problem>>> {
+    CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Forgets the detected problems, for every write to the data.
+pub fn invalidate() {
+    *cache() = None;
+}
+
+/// The problems in the current data, detected again only after a change.
+pub fn problems() -> Vec<Problem> {
+    if let Some(problems) = cache().as_ref() {
+        return problems.clone();
+    }
+    let problems = detect(
+        &read_db().unwrap_or_default(),
+        &timetracking::read_time_entries().unwrap_or_default(),
+    );
+    *cache() = Some(problems.clone());
+    problems
+}
+
+#[cfg(feature = "tui")]
+/// Picks the todo a dangling reference points at instead.
+pub struct Relink {
+    pub problem: Problem,
+    ids: Vec<usize>,
+    pub picker: Picker,
+}
+
+#[cfg(feature = "tui")]
+impl Relink {
+    /// None when there is no todo to relink to. A subtask is only offered
+    /// parents that don't make a loop.
+    pub fn new(problem: Problem, todos: &[Todo]) -> Option<Relink> {
+        let candidates: Vec<&Todo> = todos
+            .iter()
+            .filter(|t| match &problem {
+                Problem::Parent { todo_id, .. } => check_parent(*todo_id, t.id, todos).is_ok(),
+                _ => true,
+            })
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(Relink {
+            problem,
+            ids: candidates.iter().map(|t| t.id).collect(),
+            picker: Picker::new(
+                candidates
+                    .iter()
+                    .map(|t| format!("#{} {}", t.id, t.title))
+                    .collect(),
+            ),
+        })
+    }
+
+    pub fn target(&self) -> usize {
+        self.ids[self.picker.selected()]
+    }
+
+    pub fn question(&self) -> String {
+        format!(
+            "Relink to: < {} > Left/Right: pick, Enter: relink, Esc: cancel",
+            self.picker.label()
+        )
+    }
+}
+
+#[cfg(feature = "tui")]
+/// The inconsistencies view: a line per problem with `selected` marked,
+/// and the fixes it takes.
+pub fn render_inconsistencies<B: Backend>(
+    rect: &mut Frame<B>,
+    problems: &[Problem],
+    selected: usize,
+    chrome: Chrome,
+    area: Rect,
+) {
+    let mut lines: Vec<Spans> = problems
+        .iter()
+        .enumerate()
+        .map(|(i, problem)| {
+            let (marker, style) = cursor::current().row(i == selected, Style::default());
+            let keys: Vec<&str> = problem
+                .fixes()
+                .iter()
+                .map(|fix| match fix {
+                    Fix::Relink => "r: relink",
+                    Fix::Clear => "c: clear",
+                    Fix::DeleteEntry => "d: delete entry",
+                })
+                .collect();
+            Spans::from(vec![
+                Span::styled(format!("{}{}", marker, problem.describe()), style),
+                Span::styled(
+                    format!("  {}", keys.join(", ")),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ])
+        })
+        .collect();
+    if lines.is_empty() {
+        lines.push(Spans::from(vec![Span::styled(
+            "no inconsistencies found",
+            Style::default().fg(Color::DarkGray),
+        )]));
+    }
+    let paragraph = Paragraph::new(lines)
+        .scroll((
+            (selected + 1).saturating_sub(area.height.saturating_sub(2) as usize) as u16,
+            0,
+        ))
+        .block(chrome.block().title(format!(
+            "Inconsistencies ({}) - j/k: select, Esc: close",
+            problems.len()
+        )));
+    rect.render_widget(paragraph, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(id: usize, parent: Option<usize>) -> Todo {
+        let mut todo: Todo = serde_json::from_value(serde_json::json!({
+            "id": id,
+            "title": format!("todo {}", id),
+            "description": "",
+            "category": "work",
+            "status": "Todo",
+            "created_at": "2024-05-01T09:00:00Z",
+        }))
+        .unwrap();
+        todo.parent = parent;
+        todo
+    }
+
+    fn entry(id: usize, todo_id: Option<usize>) -> TimeEntry {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "category": "work",
+            "start": "2024-05-01T09:00:00Z",
+            "end": "2024-05-01T10:00:00Z",
+            "todo_id": todo_id,
+        }))
+        .unwrap()
+    }
+
+    fn missing_file() -> PathBuf {
+        std::env::temp_dir().join(format!("integrity-gone-{}", std::process::id()))
+    }
+
+    /// Todo 3 is a subtask of the missing 9, entry 2 is linked to the
+    /// missing 8 and todo 1 has a file that is gone next to one that is
+    /// there.
+    fn broken() -> (Vec<Todo>, Vec<TimeEntry>) {
+        let mut first = todo(1, None);
+        first.attachments = vec![std::env::temp_dir(), missing_file()];
+        let todos = vec![first, todo(2, Some(1)), todo(3, Some(9))];
+        let entries = vec![entry(1, Some(1)), entry(2, Some(8)), entry(3, None)];
+        (todos, entries)
+    }
+
+    #[test]
+    fn detects_each_dangling_reference_entries_first() {
+        let (todos, entries) = broken();
+        let problems = detect(&todos, &entries);
+        assert_eq!(
+            problems,
+            [
+                Problem::EntryTodo {
+                    entry_id: 2,
+                    todo_id: 8
+                },
+                Problem::Parent {
+                    todo_id: 3,
+                    parent: 9
+                },
+                Problem::Attachment {
+                    todo_id: 1,
+                    path: missing_file()
+                },
+            ]
+        );
+        assert_eq!(
+            problems[0].describe(),
+            "time entry 2 is linked to missing todo #8"
+        );
+        assert_eq!(
+            problems[1].describe(),
+            "todo #3 is a subtask of missing todo #9"
+        );
+        assert_eq!(problems[2].fixes(), [Fix::Clear]);
+        assert!(detect(&[todo(1, None), todo(2, Some(1))], &entries[..1]).is_empty());
+        assert!(detect(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn a_subtask_is_relinked_only_where_it_makes_no_loop() {
+        let (mut todos, _) = broken();
+        todos.push(todo(4, Some(3)));
+        let problem = orphaned_subtasks(&todos).remove(0);
+        // not under itself, nor under its own subtask
+        for target in [3, 4] {
+            assert!(!fix_todos(&problem, Fix::Relink, Some(target), &mut todos));
+        }
+        assert!(!fix_todos(&problem, Fix::Relink, Some(99), &mut todos));
+        assert_eq!(todos[2].parent, Some(9));
+
+        assert!(fix_todos(&problem, Fix::Relink, Some(2), &mut todos));
+        assert_eq!(todos[2].parent, Some(2));
+        // the problem is stale now
+        assert!(!fix_todos(&problem, Fix::Clear, None, &mut todos));
+        assert_eq!(todos[2].parent, Some(2));
+    }
+
+    #[test]
+    fn clearing_drops_only_the_dangling_reference() {
+        let (mut todos, _) = broken();
+        let problems = detect(&todos, &[]);
+        assert!(fix_todos(&problems[0], Fix::Clear, None, &mut todos));
+        assert_eq!(todos[2].parent, None);
+        assert_eq!(todos[1].parent, Some(1));
+
+        assert!(fix_todos(&problems[1], Fix::Clear, None, &mut todos));
+        assert_eq!(todos[0].attachments, [std::env::temp_dir()]);
+        assert!(!fix_todos(&problems[1], Fix::Clear, None, &mut todos));
+        // a fix that doesn't apply to the problem changes nothing
+        assert!(!fix_todos(&problems[1], Fix::DeleteEntry, None, &mut todos));
+        let (_, entries) = broken();
+        let entry_problem = orphaned_entries(&todos, &entries).remove(0);
+        assert!(!fix_todos(&entry_problem, Fix::Clear, None, &mut todos));
+    }
+
+    #[test]
+    fn each_fix_of_an_entry() {
+        let (todos, entries) = broken();
+        let problem = orphaned_entries(&todos, &entries).remove(0);
+
+        let mut relinked = entries.clone();
+        assert!(!fix_entries(&problem, Fix::Relink, None, &mut relinked));
+        assert!(fix_entries(&problem, Fix::Relink, Some(1), &mut relinked));
+        assert_eq!(relinked[1].todo_id, Some(1));
+        assert!(!fix_entries(&problem, Fix::Relink, Some(2), &mut relinked));
+
+        let mut cleared = entries.clone();
+        assert!(fix_entries(&problem, Fix::Clear, None, &mut cleared));
+        assert_eq!(cleared[1].todo_id, None);
+        assert_eq!(cleared.len(), 3);
+
+        let mut deleted = entries.clone();
+        assert!(fix_entries(&problem, Fix::DeleteEntry, None, &mut deleted));
+        let ids: Vec<usize> = deleted.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [1, 3]);
+
+        let parent = orphaned_subtasks(&todos).remove(0);
+        let mut untouched = entries.clone();
+        assert!(!fix_entries(&parent, Fix::Clear, None, &mut untouched));
+        assert_eq!(untouched.len(), 3);
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn apply_saves_the_fix_and_only_todo_fixes_can_be_undone() {
+        let (todos, entries) = broken();
+        let json = serde_json::to_value(&todos).unwrap();
+        crate::app::tests::with_app("integrity-apply", json, |_| {
+            update_time_entries(|saved| *saved = entries).unwrap();
+            invalidate();
+            let found = problems();
+            assert_eq!(found.len(), 3);
+            let mut undo = UndoStack::default();
+
+            assert!(apply(&found[0], Fix::DeleteEntry, None, &mut undo).unwrap());
+            assert!(undo.is_empty());
+            assert_eq!(timetracking::read_time_entries().unwrap().len(), 2);
+
+            assert!(apply(&found[1], Fix::Relink, Some(1), &mut undo).unwrap());
+            assert_eq!(read_db().unwrap()[2].parent, Some(1));
+            // writing forgot what was detected
+            assert_eq!(problems(), [found[2].clone()]);
+            assert!(!apply(&found[1], Fix::Clear, None, &mut undo).unwrap());
+
+            undo.pop().unwrap().apply().unwrap();
+            assert!(undo.is_empty());
+            assert_eq!(read_db().unwrap()[2].parent, Some(9));
+        });
+    }
+}
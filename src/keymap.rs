@@ -0,0 +1,487 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::actions::{self, key, scope_title, Action, ActionId, Key, Keymap, Scope, ACTIONS};
+use crate::cursor;
+
+/// A key bound to more than one action of the same scope.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    pub scope: Scope,
+    pub key: Key,
+    /// In registry order, so the first is the one the key runs.
+    pub actions: Vec<ActionId>,
+}
+
+impl Conflict {
+    /// e.g. "Todos: h is bound to both focus left and hide".
+    #[cfg(test)]
+    pub fn describe(&self, actions: &[Action]) -> String {
+        let names: Vec<&str> = self.actions.iter().map(|id| name(actions, *id)).collect();
+        let bound = match names.split_last() {
+            Some((last, [first])) => format!("both {} and {}", first, last),
+            Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+            None => String::new(),
+        };
+        format!(
+            "{}: {} is bound to {}",
+            scope_title(self.scope),
+            self.key.label(),
+            bound
+        )
+    }
+}
+
+fn find(actions: &[Action], id: ActionId) -> Option<&Action> {
+    actions.iter().find(|a| a.id == id)
+}
+
+fn name(actions: &[Action], id: ActionId) -> &'static str {
+    find(actions, id).map_or("action", |a| a.name)
+}
+
+/// The keys of `keymap` bound to more than one action of a scope, once
+/// per scope and key.
+pub fn conflicts(actions: &[Action], keymap: &Keymap) -> Vec<Conflict> {
+    let mut conflicts: Vec<Conflict> = vec![];
+    for (i, first) in actions.iter().enumerate() {
+        for key in first.keys_in(keymap) {
+            if conflicts
+                .iter()
+                .any(|c| c.scope == first.scope && c.key == *key)
+            {
+                continue;
+            }
+            let mut ids = vec![first.id];
+            ids.extend(
+                actions[i + 1..]
+                    .iter()
+                    .filter(|a| a.scope == first.scope && a.keys_in(keymap).contains(key))
+                    .map(|a| a.id),
+            );
+            if ids.len() > 1 {
+                conflicts.push(Conflict {
+                    scope: first.scope,
+                    key: *key,
+                    actions: ids,
+                });
+            }
+        }
+    }
+    conflicts
+}
+
+/// The keys tried for a free one, in order. q, @ and the digits are the
+/// macro keys.
+const POOL: &str = "abcdefghijklmnoprstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// The first key in the pool that no action working alongside `scope`
+/// has in `keymap`, so binding it neither conflicts with nor hides
+/// another action's key.
+pub fn free_key(actions: &[Action], keymap: &Keymap, scope: Scope) -> Option<Key> {
+    let alongside =
+        |other: Scope| other == scope || other == Scope::Global || scope == Scope::Global;
+    POOL.chars().map(key).find(|candidate| {
+        !actions
+            .iter()
+            .any(|a| alongside(a.scope) && a.keys_in(keymap).contains(candidate))
+    })
+}
+
+/// `keymap` with each conflict's key left to one action, by its index in
+/// the conflict's actions in `winners`. The others lose the key and get
+/// the first free key in its place, when there is one.
+pub fn resolve(
+    actions: &[Action],
+    keymap: &Keymap,
+    conflicts: &[Conflict],
+    winners: &[usize],
+) -> Keymap {
+    let mut resolved = keymap.clone();
+    for (conflict, winner) in conflicts.iter().zip(winners) {
+        for (_, id) in conflict
+            .actions
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i != winner)
+        {
+            let action = match find(actions, *id) {
+                Some(action) => action,
+                None => continue,
+            };
+            let kept: Vec<Key> = action
+                .keys_in(&resolved)
+                .iter()
+                .filter(|k| **k != conflict.key)
+                .copied()
+                .collect();
+            resolved.insert(*id, kept.clone());
+            let mut keys = kept;
+            keys.extend(free_key(actions, &resolved, action.scope));
+            resolved.insert(*id, keys);
+        }
+    }
+    resolved
+}
+
+/// `keymap` without the actions that conflict in it, which go back to
+/// their default keys, until that brings up no new conflict with another
+/// remapped key.
+pub fn fallback(actions: &[Action], keymap: &Keymap) -> Keymap {
+    let mut keymap = keymap.clone();
+    loop {
+        let before = keymap.len();
+        for conflict in conflicts(actions, &keymap) {
+            for id in conflict.actions {
+                keymap.remove(&id);
+            }
+        }
+        // what is left conflicts among the defaults, which the startup
+        // check rules out
+        if keymap.len() == before {
+            return keymap;
+        }
+    }
+}
+
+/// The `keys` entry of the config for `keymap`.
+pub fn to_json(actions: &[Action], keymap: &Keymap) -> serde_json::Value {
+    keymap
+        .iter()
+        .map(|(id, keys)| {
+            let labels = keys.iter().map(|k| serde_json::Value::from(k.label()));
+            (name(actions, *id).to_string(), labels.collect())
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+pub enum ConflictAction {
+    Continue,
+    /// Save the keymap, with a note on what changed for the status line.
+    Confirm(Keymap, String),
+    /// Leave the config as it is and run with this keymap meanwhile.
+    Decline(Keymap),
+}
+
+/// The screen that opens when the keys from the config conflict, to pick
+/// which action keeps each key before the board takes any.
+pub struct KeyConflicts {
+    keymap: Keymap,
+    conflicts: Vec<Conflict>,
+    winners: Vec<usize>,
+    selected: usize,
+}
+
+impl KeyConflicts {
+    /// Suggests the actions that run today keep their keys, and the
+    /// others move to the first free key.
+    pub fn new(keymap: Keymap, conflicts: Vec<Conflict>) -> KeyConflicts {
+        KeyConflicts {
+            winners: vec![0; conflicts.len()],
+            keymap,
+            conflicts,
+            selected: 0,
+        }
+    }
+
+    fn resolved(&self) -> Keymap {
+        resolve(ACTIONS, &self.keymap, &self.conflicts, &self.winners)
+    }
+
+    /// e.g. "Todos h: focus left kept it, hide got a".
+    fn note(&self, resolved: &Keymap) -> String {
+        let settled: Vec<String> = self
+            .conflicts
+            .iter()
+            .zip(&self.winners)
+            .map(|(conflict, winner)| {
+                let mut parts = vec![format!(
+                    "{} kept it",
+                    name(ACTIONS, conflict.actions[*winner])
+                )];
+                for (_, id) in conflict
+                    .actions
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| i != winner)
+                {
+                    parts.push(format!(
+                        "{} got {}",
+                        name(ACTIONS, *id),
+                        keys_label(resolved, *id)
+                    ));
+                }
+                format!(
+                    "{} {}: {}",
+                    scope_title(conflict.scope),
+                    conflict.key.label(),
+                    parts.join(", ")
+                )
+            })
+            .collect();
+        format!("keys settled: {}", settled.join("; "))
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> ConflictAction {
+        match key.code {
+            KeyCode::Esc => return ConflictAction::Decline(fallback(ACTIONS, &self.keymap)),
+            KeyCode::Enter => {
+                let resolved = self.resolved();
+                let note = self.note(&resolved);
+                return ConflictAction::Confirm(resolved, note);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.conflicts.len().saturating_sub(1))
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            code @ (KeyCode::Left | KeyCode::Right | KeyCode::Char('h' | 'l')) => {
+                if let Some(conflict) = self.conflicts.get(self.selected) {
+                    let count = conflict.actions.len();
+                    let winner = &mut self.winners[self.selected];
+                    *winner = match code {
+                        KeyCode::Right | KeyCode::Char('l') => (*winner + 1) % count,
+                        _ => (*winner + count - 1) % count,
+                    };
+                }
+            }
+            _ => {}
+        }
+        ConflictAction::Continue
+    }
+}
+
+/// The keys of `id` in `keymap`, e.g. "a/F7", or "no key" when it has
+/// none left.
+fn keys_label(keymap: &Keymap, id: ActionId) -> String {
+    match find(ACTIONS, id).map(|a| a.keys_in(keymap)) {
+        Some(keys) if !keys.is_empty() => keys.iter().map(Key::label).collect::<Vec<_>>().join("/"),
+        _ => "no key".to_string(),
+    }
+}
+
+pub fn render_key_conflicts<B: Backend>(rect: &mut Frame<B>, screen: &KeyConflicts, area: Rect) {
+    let resolved = screen.resolved();
+    let cursor = cursor::current();
+    let mut lines = vec![
+        Spans::from(vec![Span::styled(
+            "The config binds these keys to more than one action each.",
+            Style::default().fg(Color::Gray),
+        )]),
+        Spans::from(vec![Span::styled(
+            "The marked action keeps the key, the others move to the keys shown.",
+            Style::default().fg(Color::DarkGray),
+        )]),
+    ];
+    // the line of the selected conflict's title, kept in view
+    let mut selected_line = 0;
+    for (row, (conflict, winner)) in screen.conflicts.iter().zip(&screen.winners).enumerate() {
+        lines.push(Spans::from(vec![Span::raw("")]));
+        let (marker, style) = cursor.row(
+            row == screen.selected,
+            Style::default()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::BOLD),
+        );
+        if row == screen.selected {
+            selected_line = lines.len();
+        }
+        lines.push(Spans::from(vec![Span::styled(
+            format!(
+                "{}{}: {}",
+                marker,
+                scope_title(conflict.scope),
+                conflict.key.label()
+            ),
+            style,
+        )]));
+        for (i, id) in conflict.actions.iter().enumerate() {
+            let (mark, keys, color) = if i == *winner {
+                (
+                    "(x)",
+                    format!("keeps {}", conflict.key.label()),
+                    Color::White,
+                )
+            } else {
+                (
+                    "( )",
+                    format!("→ {}", keys_label(&resolved, *id)),
+                    Color::Gray,
+                )
+            };
+            lines.push(Spans::from(vec![
+                Span::styled(
+                    format!("    {} {:<22}", mark, name(ACTIONS, *id)),
+                    Style::default().fg(color),
+                ),
+                Span::styled(keys, Style::default().fg(Color::Yellow)),
+            ]));
+        }
+    }
+    let rows = area.height.saturating_sub(2) as usize;
+    let offset = (selected_line + 4).saturating_sub(rows);
+    let paragraph = Paragraph::new(lines)
+        .scroll((offset as u16, 0))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title("Key conflicts (Up/Down: conflict, Left/Right: who keeps the key, Enter: save, Esc: defaults)")
+                .border_type(BorderType::Plain),
+        );
+    rect.render_widget(Clear, area);
+    rect.render_widget(paragraph, area);
+}
+
+/// Puts the keys from the config in place, for `actions::lookup`. When
+/// they conflict, the conflicting actions keep their defaults and the
+/// conflicts come back as a screen to settle them, None otherwise.
+pub fn apply(keymap: &Keymap) -> Option<KeyConflicts> {
+    let found = conflicts(ACTIONS, keymap);
+    if found.is_empty() {
+        actions::set_keymap(keymap.clone());
+        return None;
+    }
+    actions::set_keymap(fallback(ACTIONS, keymap));
+    Some(KeyConflicts::new(keymap.clone(), found))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::Needs;
+    use crate::MenuItem;
+
+    const A: &[Key] = &[key('a')];
+    const B: &[Key] = &[key('b')];
+    const C: &[Key] = &[key('c')];
+    const H: &[Key] = &[key('h')];
+    const HX: &[Key] = &[key('h'), key('x')];
+
+    fn bound(id: ActionId, name: &'static str, keys: &'static [Key], scope: Scope) -> Action {
+        Action {
+            id,
+            name,
+            description: "",
+            default_keys: keys,
+            scope,
+            needs: Needs::Nothing,
+            hint: false,
+        }
+    }
+
+    #[test]
+    fn a_conflict_comes_once_per_scope_and_key_in_registry_order() {
+        let todos = Scope::Tab(MenuItem::Todos);
+        let actions = [
+            bound(ActionId::Search, "find todo", H, todos),
+            bound(ActionId::Palette, "command palette", HX, todos),
+            bound(ActionId::Help, "help", B, todos),
+            // the same key in another scope is no conflict
+            bound(ActionId::Quit, "quit", H, Scope::Global),
+        ];
+        assert_eq!(conflicts(&actions, &Keymap::new()).len(), 1);
+
+        let mut keymap = Keymap::new();
+        keymap.insert(ActionId::Help, vec![key('h'), key('x')]);
+        assert_eq!(
+            conflicts(&actions, &keymap),
+            [
+                Conflict {
+                    scope: todos,
+                    key: key('h'),
+                    actions: vec![ActionId::Search, ActionId::Palette, ActionId::Help],
+                },
+                Conflict {
+                    scope: todos,
+                    key: key('x'),
+                    actions: vec![ActionId::Palette, ActionId::Help],
+                },
+            ]
+        );
+        assert_eq!(
+            conflicts(&actions, &keymap)[0].describe(&actions),
+            "Todos: h is bound to find todo, command palette and help"
+        );
+    }
+
+    #[test]
+    fn the_free_key_is_one_no_action_alongside_has() {
+        let todos = Scope::Tab(MenuItem::Todos);
+        let timers = Scope::Tab(MenuItem::Timers);
+        let actions = [
+            bound(ActionId::Search, "find todo", A, todos),
+            bound(ActionId::Help, "help", B, Scope::Global),
+            bound(ActionId::TimerStart, "start timer", C, timers),
+        ];
+        let keymap = Keymap::new();
+        assert_eq!(free_key(&actions, &keymap, todos), Some(key('c')));
+        // another tab's keys are free to take
+        assert_eq!(free_key(&actions, &keymap, timers), Some(key('a')));
+        // a global key must not hide any tab's
+        assert_eq!(free_key(&actions, &keymap, Scope::Global), Some(key('d')));
+
+        // the keymap's keys count, not the defaults they replace
+        let mut keymap = Keymap::new();
+        keymap.insert(ActionId::Search, vec![key('d')]);
+        assert_eq!(free_key(&actions, &keymap, todos), Some(key('a')));
+    }
+
+    #[test]
+    fn no_free_key_when_the_pool_is_taken() {
+        let keys: Vec<Key> = POOL.chars().map(key).collect();
+        let mut keymap = Keymap::new();
+        keymap.insert(ActionId::Help, keys);
+        let actions = [bound(ActionId::Help, "help", B, Scope::Global)];
+        assert_eq!(free_key(&actions, &keymap, Scope::Global), None);
+    }
+
+    #[test]
+    fn the_losers_of_a_conflict_move_to_free_keys() {
+        let todos = Scope::Tab(MenuItem::Todos);
+        let actions = [
+            bound(ActionId::Search, "find todo", HX, todos),
+            bound(ActionId::Palette, "command palette", A, todos),
+            bound(ActionId::Help, "help", B, Scope::Global),
+        ];
+        let mut keymap = Keymap::new();
+        keymap.insert(ActionId::Palette, vec![key('h')]);
+        let found = conflicts(&actions, &keymap);
+
+        // the remapped action keeps the key, find todo keeps x
+        // and gets the a command palette left
+        let resolved = resolve(&actions, &keymap, &found, &[1]);
+        assert_eq!(resolved[&ActionId::Search], [key('x'), key('a')]);
+        assert_eq!(resolved[&ActionId::Palette], [key('h')]);
+        assert!(conflicts(&actions, &resolved).is_empty());
+
+        let resolved = resolve(&actions, &keymap, &found, &[0]);
+        assert!(!resolved.contains_key(&ActionId::Search));
+        assert_eq!(resolved[&ActionId::Palette], [key('a')]);
+        assert!(conflicts(&actions, &resolved).is_empty());
+    }
+
+    #[test]
+    fn the_fallback_drops_the_conflicting_keys_only() {
+        let todos = Scope::Tab(MenuItem::Todos);
+        let actions = [
+            bound(ActionId::Search, "find todo", H, todos),
+            bound(ActionId::Palette, "command palette", A, todos),
+            bound(ActionId::Help, "help", B, todos),
+        ];
+        let mut keymap = Keymap::new();
+        keymap.insert(ActionId::Palette, vec![key('h')]);
+        keymap.insert(ActionId::Help, vec![key('z')]);
+        let fallback = fallback(&actions, &keymap);
+        assert!(!fallback.contains_key(&ActionId::Palette));
+        assert_eq!(fallback[&ActionId::Help], [key('z')]);
+        assert!(conflicts(&actions, &fallback).is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use tui::style::{Color, Modifier, Style};
+
+use crate::undo::UndoStack;
+use crate::{update_db, Error, Priority};
+
+/// The color labels Shift+C cycles through, in order. A label is only a
+/// name; the color it shows in comes from the config.
+pub const LABELS: [&str; 6] = ["red", "orange", "yellow", "green", "blue", "purple"];
+
+/// The label after `current`, None after the last one to clear it. No
+/// label or an unknown one starts the cycle over.
+pub fn next(current: Option<&str>) -> Option<&'static str> {
+    match current.and_then(|label| LABELS.iter().position(|l| *l == label)) {
+        Some(index) => LABELS.get(index + 1).copied(),
+        None => LABELS.first().copied(),
+    }
+}
+
+/// The color `label` shows in: the one `label_colors` in the config maps it
+/// to, e.g. a colorblind friendly one, or else its own. None for a label
+/// neither knows.
+pub fn color(label: &str, overrides: &BTreeMap<String, Color>) -> Option<Color> {
+    if let Some(color) = overrides.get(label) {
+        return Some(*color);
+    }
+    match label {
+        "red" => Some(Color::Red),
+        "orange" => Some(Color::Indexed(208)),
+        "yellow" => Some(Color::Yellow),
+        "green" => Some(Color::Green),
+        "blue" => Some(Color::Blue),
+        "purple" => Some(Color::Magenta),
+        _ => None,
+    }
+}
+
+/// What besides a label can color a todo's title.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Cues {
+    pub overdue: bool,
+    /// In Doing without activity for longer than `stale_doing` allows.
+    pub stale: bool,
+    /// The priority after escalation.
+    pub priority: Option<Priority>,
+}
+
+/// The style of a todo's title. The first that applies wins: its label,
+/// overdue, stale, then an Urgent or High priority; the default style
+/// otherwise.
+pub fn title_style(label: Option<&str>, cues: Cues, overrides: &BTreeMap<String, Color>) -> Style {
+    if let Some(color) = label.and_then(|label| color(label, overrides)) {
+        return Style::default().fg(color).add_modifier(Modifier::BOLD);
+    }
+    if cues.overdue {
+        return Style::default().fg(Color::Red);
+    }
+    if cues.stale {
+        return Style::default().fg(Color::DarkGray);
+    }
+    match cues.priority {
+        Some(Priority::Urgent) => Style::default().fg(Color::LightRed),
+        Some(Priority::High) => Style::default().fg(Color::LightYellow),
+        _ => Style::default(),
+    }
+}
+
+/// Moves todo `id` on to the next label, see [`next`]. Returns the new
+/// label, None when it was cleared.
+pub fn cycle(id: usize, undo: &mut UndoStack) -> Result<Option<&'static str>, Error> {
+    let (before, label) = update_db(|todos| {
+        let before = todos.clone();
+        let mut label = None;
+        for todo in todos.iter_mut().filter(|t| t.id == id) {
+            label = next(todo.color.as_deref());
+            todo.color = label.map(str::to_string);
+        }
+        (before, label)
+    })?;
+    undo.push("label", before, 0);
+    Ok(label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::tests::with_app;
+    use crate::read_db;
+
+    fn bold(color: Color) -> Style {
+        Style::default().fg(color).add_modifier(Modifier::BOLD)
+    }
+
+    #[test]
+    fn the_labels_cycle_and_then_clear() {
+        let mut label = None;
+        let mut seen = vec![];
+        for _ in 0..=LABELS.len() {
+            label = next(label);
+            seen.push(label);
+        }
+        let mut expected: Vec<Option<&str>> = LABELS.iter().map(|l| Some(*l)).collect();
+        expected.push(None);
+        assert_eq!(seen, expected);
+        assert_eq!(next(Some("teal")), Some("red"));
+    }
+
+    #[test]
+    fn the_config_can_remap_a_label_color() {
+        let none = BTreeMap::new();
+        assert_eq!(color("red", &none), Some(Color::Red));
+        assert_eq!(color("orange", &none), Some(Color::Indexed(208)));
+        assert_eq!(color("teal", &none), None);
+        assert!(LABELS.iter().all(|label| color(label, &none).is_some()));
+
+        let overrides = BTreeMap::from([
+            ("red".to_string(), Color::Indexed(33)),
+            ("teal".to_string(), Color::Cyan),
+        ]);
+        assert_eq!(color("red", &overrides), Some(Color::Indexed(33)));
+        assert_eq!(color("green", &overrides), Some(Color::Green));
+        assert_eq!(color("teal", &overrides), Some(Color::Cyan));
+    }
+
+    #[test]
+    fn the_title_style_goes_label_overdue_stale_priority() {
+        let none = BTreeMap::new();
+        let every = Cues {
+            overdue: true,
+            stale: true,
+            priority: Some(Priority::Urgent),
+        };
+        assert_eq!(title_style(Some("blue"), every, &none), bold(Color::Blue));
+        // a label no one knows is no label
+        assert_eq!(
+            title_style(Some("teal"), every, &none),
+            Style::default().fg(Color::Red)
+        );
+        assert_eq!(
+            title_style(None, every, &none),
+            Style::default().fg(Color::Red)
+        );
+        let stale = Cues {
+            overdue: false,
+            ..every
+        };
+        assert_eq!(
+            title_style(None, stale, &none),
+            Style::default().fg(Color::DarkGray)
+        );
+        let urgent = Cues {
+            stale: false,
+            ..stale
+        };
+        assert_eq!(
+            title_style(None, urgent, &none),
+            Style::default().fg(Color::LightRed)
+        );
+        let high = Cues {
+            priority: Some(Priority::High),
+            ..urgent
+        };
+        assert_eq!(
+            title_style(None, high, &none),
+            Style::default().fg(Color::LightYellow)
+        );
+        for priority in [Some(Priority::Medium), Some(Priority::Low), None] {
+            let calm = Cues { priority, ..high };
+            assert_eq!(title_style(None, calm, &none), Style::default());
+        }
+        let overrides = BTreeMap::from([("blue".to_string(), Color::Yellow)]);
+        assert_eq!(
+            title_style(Some("blue"), Cues::default(), &overrides),
+            bold(Color::Yellow)
+        );
+    }
+
+    #[test]
+    fn cycling_labels_only_the_todo_and_can_be_undone() {
+        let todo = |id: usize| {
+            serde_json::json!({
+                "id": id,
+                "title": format!("todo {}", id),
+                "description": "",
+                "category": "work",
+                "status": "Todo",
+                "created_at": "2024-05-01T09:00:00Z",
+            })
+        };
+        with_app("label-cycle", serde_json::json!([todo(1), todo(2)]), |_| {
+            let colors = || -> Vec<Option<String>> {
+                read_db().unwrap().into_iter().map(|t| t.color).collect()
+            };
+            let mut undo = UndoStack::default();
+            assert_eq!(cycle(2, &mut undo).unwrap(), Some("red"));
+            assert_eq!(cycle(2, &mut undo).unwrap(), Some("orange"));
+            assert_eq!(colors(), [None, Some("orange".to_string())]);
+            assert_eq!(cycle(3, &mut undo).unwrap(), None);
+
+            let undone = undo.pop().unwrap();
+            assert_eq!(undone.label, "label");
+            undone.apply().unwrap();
+            undo.pop().unwrap().apply().unwrap();
+            assert_eq!(colors(), [None, Some("red".to_string())]);
+        });
+    }
+}
@@ -0,0 +1,794 @@
+//! The todos and time entries of Personal Work Suit, and what the TUI
+//! does with them, for other frontends to build on.
+//!
+//! A frontend reads and changes the data through a [`Storage`]:
+//! [`JsonStorage`] is the files under ./data the TUI uses, so both see the
+//! same todos, and [`MemoryStorage`] keeps them in memory. The quick
+//! operations, [`add_todo`], [`move_todo`], [`delete_todo`] and
+//! [`start_timer`], are plain functions over a [`Db`], to run inside
+//! [`Storage::transaction`] or on a copy. [`Filter`], [`column_todos`],
+//! [`sort_todos`], [`Agenda`] and [`time_per_category`] pick and order what
+//! to show, and [`parse_quick_add`], [`parse_due`] and [`parse_day`] read
+//! what the user typed the way the TUI does. `examples/agenda.rs` prints
+//! today's agenda with nothing else.
+//!
+//! ```
+//! use chrono::Utc;
+//! use work_time_cli::{add_todo, move_todo, parse_quick_add, MemoryStorage, Storage, TodoStatus};
+//!
+//! let storage = MemoryStorage::default();
+//! storage.transaction(|db| {
+//!     let todo = parse_quick_add("Renew passport @errands !!").unwrap();
+//!     let id = add_todo(db, todo);
+//!     move_todo(db, id, TodoStatus::Doing, Utc::now());
+//!     Ok(())
+//! })?;
+//! let db = storage.load()?;
+//! assert_eq!(db.todos[0].category, "errands");
+//! assert_eq!(db.todos[0].status(), TodoStatus::Doing);
+//! # Ok::<(), work_time_cli::Error>(())
+//! ```
+//!
+//! Days, weeks and bare due dates are reckoned in the zone [`set_zone`]
+//! last set, the system's until then; the TUI sets the config's
+//! `timezone`.
+//!
+//! The terminal UI and the `work_time_cli` binary sit behind the default
+//! `tui` feature. Without it, `default-features = false`, the library
+//! builds without tui and crossterm.
+
+// without the TUI, most of the commands and the plumbing they share are
+// only compiled for the library's sake
+#![cfg_attr(not(feature = "tui"), allow(dead_code))]
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
+use thiserror::Error;
+#[cfg(feature = "tui")]
+use tui::widgets::ListState;
+
+#[cfg(feature = "tui")]
+mod actions;
+mod activity;
+mod add;
+mod agenda;
+#[cfg(feature = "tui")]
+mod app;
+mod archive;
+mod attachments;
+mod autosave;
+#[cfg(feature = "tui")]
+mod badges;
+#[cfg(feature = "tui")]
+mod batch;
+#[cfg(feature = "tui")]
+mod breakdown;
+mod bulk;
+mod calendar;
+#[cfg(feature = "tui")]
+mod carryover;
+#[cfg(feature = "tui")]
+mod chrome;
+mod cli;
+#[cfg(feature = "tui")]
+mod cmdline;
+#[cfg(feature = "tui")]
+mod commands;
+#[cfg(feature = "tui")]
+mod complete;
+mod config;
+#[cfg(feature = "tui")]
+mod cursor;
+#[cfg(feature = "tui")]
+mod dashboard;
+mod dates;
+mod daynotes;
+mod db;
+#[cfg(feature = "tui")]
+mod description;
+#[cfg(feature = "tui")]
+mod doctor;
+mod escalation;
+mod estimate;
+mod export;
+mod expr;
+#[cfg(feature = "tui")]
+mod fields;
+mod filter;
+#[cfg(feature = "tui")]
+mod flextime;
+#[cfg(feature = "tui")]
+mod follow;
+#[cfg(feature = "tui")]
+mod glyph;
+#[cfg(feature = "tui")]
+mod highlight;
+#[cfg(feature = "tui")]
+mod history;
+mod integrity;
+#[cfg(feature = "tui")]
+mod keymap;
+#[cfg(feature = "tui")]
+mod label;
+mod list;
+mod lock;
+#[cfg(feature = "tui")]
+mod macros;
+mod maintenance;
+#[cfg(feature = "tui")]
+mod menu;
+#[cfg(feature = "tui")]
+mod month;
+#[cfg(feature = "tui")]
+mod notify;
+#[cfg(feature = "tui")]
+mod pomodoro;
+#[cfg(feature = "tui")]
+mod power;
+#[cfg(feature = "tui")]
+mod preview;
+#[cfg(feature = "tui")]
+mod progress;
+mod quick;
+#[cfg(feature = "tui")]
+mod quit;
+mod refs;
+mod reid;
+#[cfg(feature = "tui")]
+mod reminders;
+#[cfg(feature = "tui")]
+mod reports;
+#[cfg(feature = "tui")]
+mod review;
+mod schema;
+#[cfg(feature = "tui")]
+mod scroll;
+#[cfg(feature = "tui")]
+mod search;
+#[cfg(feature = "tui")]
+mod session;
+#[cfg(feature = "tui")]
+mod share;
+mod sort;
+#[cfg(feature = "tui")]
+mod sparkline;
+#[cfg(feature = "tui")]
+mod storage;
+mod subtasks;
+mod suggest;
+#[cfg(feature = "tui")]
+mod timeline;
+#[cfg(feature = "tui")]
+mod timer;
+#[cfg(feature = "tui")]
+mod timerform;
+mod timers;
+#[cfg(feature = "tui")]
+mod timesheet;
+mod timetracking;
+#[cfg(feature = "tui")]
+mod toast;
+mod todo;
+#[cfg(feature = "tui")]
+mod todoform;
+mod txn;
+#[cfg(feature = "tui")]
+mod ui;
+mod undo;
+mod urgency;
+#[cfg(feature = "tui")]
+mod widgets;
+mod zone;
+
+pub use agenda::{Agenda, AgendaItem};
+pub use config::{load as load_config, Config};
+pub use dates::{parse_day, parse_due};
+pub use db::{
+    add_todo, delete_todo, move_todo, start_timer, Db, JsonStorage, MemoryStorage, Storage,
+};
+pub use escalation::EffectivePriority;
+pub use estimate::{remaining as remaining_work, Remaining};
+pub use filter::{due_in, DueWindow, Filter};
+pub use list::sort_todos;
+pub use quick::parse as parse_quick_add;
+pub use timetracking::{local_midnight, time_per_category, week_bounds, EntryKind, TimeEntry};
+pub use todo::{Todo, MAX_DESCRIPTION_CHARS};
+#[cfg(feature = "tui")]
+pub use ui::run;
+pub use zone::{set as set_zone, Zone};
+
+#[cfg(feature = "tui")]
+use undo::UndoStack;
+
+const DB_PATH: &str = "./data/db.json";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("error reading the DB file: {0}")]
+    ReadDBError(#[from] io::Error),
+    #[error("error parsing the DB file: {0}")]
+    ParseDBError(#[from] serde_json::Error),
+    #[error("error writing the DB file: {0}")]
+    WriteDBError(io::Error),
+    #[error("error parsing the config file: {0}")]
+    ParseConfigError(serde_json::Error),
+    #[error("error writing the session file: {0}")]
+    WriteSessionError(io::Error),
+    #[error("the DB is locked by another instance, delete {0} if none is running")]
+    DBLockedError(&'static str),
+    #[error("error writing {0}: {1}")]
+    WriteFileError(String, io::Error),
+    #[error("error reading {0}: {1}")]
+    ReadFileError(String, io::Error),
+    #[error("{0}")]
+    ArchiveError(String),
+    #[error("{0}")]
+    SchemaError(String),
+    #[error("todo {0}: {1} can't be changed that way")]
+    ProtectedFieldError(usize, &'static str),
+}
+
+impl Error {
+    /// A stable short name for scripts, see `cli::HELP`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ReadDBError(_) | Error::ReadFileError(..) => "read_error",
+            Error::ParseDBError(_) => "parse_error",
+            Error::WriteDBError(_) | Error::WriteSessionError(_) | Error::WriteFileError(..) => {
+                "write_error"
+            }
+            Error::ParseConfigError(_) => "config_error",
+            Error::DBLockedError(_) => "locked",
+            Error::ArchiveError(_) => "archive_error",
+            Error::SchemaError(_) => "schema_error",
+            Error::ProtectedFieldError(..) => "protected_field",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self.code() {
+            "read_error" => 3,
+            "parse_error" => 4,
+            "write_error" => 5,
+            "config_error" => 6,
+            "locked" => 7,
+            "archive_error" => 8,
+            "schema_error" => 9,
+            "protected_field" => 10,
+            _ => 1,
+        }
+    }
+}
+
+/// A named stopwatch of the Timers tab, see `timers`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Timer {
+    id: usize,
+    name: String,
+    category: String,
+    created_at: DateTime<Utc>,
+    /// Time counted before `started_at`, since the timer was last stopped.
+    #[serde(default = "chrono::Duration::zero", with = "timers::seconds")]
+    elapsed: chrono::Duration,
+    /// When the stretch running now began, None while paused or stopped.
+    /// Kept on disk, so a running timer goes on counting across restarts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    started_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(feature = "tui")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum MenuItem {
+    Home,
+    Todos,
+    Timers,
+    TimeTracking,
+}
+
+/// The board column a todo is in.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub enum TodoStatus {
+    Todo,
+    Done,
+    Doing,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+    Urgent,
+}
+
+impl Priority {
+    /// `low`, `medium`, `high` or `urgent`.
+    fn parse(name: &str) -> Option<Priority> {
+        match name {
+            "low" => Some(Priority::Low),
+            "medium" => Some(Priority::Medium),
+            "high" => Some(Priority::High),
+            "urgent" => Some(Priority::Urgent),
+            _ => None,
+        }
+    }
+
+    fn marker(&self) -> &'static str {
+        match self {
+            Priority::Low => "·",
+            Priority::Medium => "!",
+            Priority::High => "!!",
+            Priority::Urgent => "!!!",
+        }
+    }
+
+    /// The marker of a priority a due date raised it to.
+    fn hollow_marker(&self) -> &'static str {
+        match self {
+            Priority::Low => "∘",
+            Priority::Medium => "◇",
+            Priority::High => "◇◇",
+            Priority::Urgent => "◇◇◇",
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl From<MenuItem> for usize {
+    fn from(input: MenuItem) -> usize {
+        match input {
+            MenuItem::Home => 0,
+            MenuItem::Todos => 1,
+            MenuItem::Timers => 2,
+            MenuItem::TimeTracking => 3,
+        }
+    }
+}
+
+/// The todos as this process sees them, including changes not saved yet.
+fn read_db() -> Result<Vec<Todo>, Error> {
+    match autosave::pending() {
+        Some(todos) => Ok(todos),
+        None => read_db_file(),
+    }
+}
+
+/// The todos on disk. With no unsaved changes to keep apart, the file as
+/// read is the one this process has seen, see `autosave::stale`.
+fn read_db_file() -> Result<Vec<Todo>, Error> {
+    let content = fs::read_to_string(DB_PATH)?;
+    if autosave::pending().is_none() {
+        autosave::seen();
+    }
+    schema::parse_db(&content)
+}
+
+/// Lays the unsaved changes over the DB file when another process wrote
+/// it since this one last saw it, so saving them keeps its records. Runs
+/// under the DB lock, right before a save. What had to be settled waits
+/// in `autosave::take_notice`.
+fn catch_up() -> Result<(), Error> {
+    if autosave::pending().is_some() && autosave::stale() {
+        if let Some(notice) = autosave::rebase(read_db_file()?).describe() {
+            autosave::report(notice);
+        }
+    }
+    Ok(())
+}
+
+/// The ids of every todo, to tell `#<id>` references that resolve.
+fn todo_ids() -> Result<HashSet<usize>, Error> {
+    Ok(read_db()?.iter().map(|t| t.id).collect())
+}
+
+/// Ids are never reused, so a new todo takes the largest id plus one.
+fn next_todo_id(todos: &[Todo]) -> usize {
+    todos.iter().map(|t| t.id).max().unwrap_or(0) + 1
+}
+
+/// Saves the todos and logs what changed since the previous save, so every
+/// mutation path feeds the activity log. A log that can't be written
+/// doesn't fail the save, see `activity::record`.
+fn write_db(todos: &[Todo]) -> Result<(), Error> {
+    let before = read_db_file().unwrap_or_default();
+    // a WriteDBError, which `update_db` and `flush_db` hold in memory
+    write_through_tmp(DB_PATH, &schema::db_json(todos)?).map_err(Error::WriteDBError)?;
+    autosave::saved();
+    activity::record(&activity::changes(&before, todos, Utc::now()));
+    Ok(())
+}
+
+/// Re-reads the DB under the lock, lets `change` edit it and saves it
+/// before releasing the lock. Every mutation goes through here so a TUI
+/// and a CLI running side by side never overwrite each other's records.
+///
+/// Saves are rate limited: a change within the autosave interval of the
+/// last save stays in memory for `flush_db`, unless it deletes a todo or
+/// changes a status. Until then this process reads its own unsaved state.
+///
+/// While the data directory can't be written, e.g. on a full disk or a
+/// read-only mount, changes stay in memory too, see `autosave::failure`,
+/// and `flush_db` keeps trying to save them.
+///
+/// A change that breaks `check_protected` is thrown away with an error.
+fn update_db<T>(change: impl FnOnce(&mut Vec<Todo>) -> T) -> Result<T, Error> {
+    // a directory that takes no writes takes no lock file either, and
+    // no other process can save to it meanwhile
+    let lock = match lock::DbLock::acquire() {
+        Ok(lock) => Some(lock),
+        Err(Error::WriteDBError(err)) if autosave::holds_failed_saves() => {
+            autosave::failed(&err);
+            None
+        }
+        Err(err) => return Err(err),
+    };
+    if lock.is_some() {
+        catch_up()?;
+    }
+    let mut todos = read_db()?;
+    let before = todos.clone();
+    let result = change(&mut todos);
+    check_protected(&before, &todos)?;
+    stamp_updates(&before, &mut todos, Utc::now());
+    integrity::invalidate();
+    let save = lock.is_some()
+        && autosave::failure().is_none()
+        && (autosave::save_allowed() || autosave::significant(&before, &todos));
+    if !save {
+        autosave::defer(todos, &before);
+    } else if let Err(err) = write_db(&todos) {
+        match err {
+            Error::WriteDBError(err) if autosave::holds_failed_saves() => {
+                autosave::failed(&err);
+                autosave::defer(todos, &before);
+            }
+            err => return Err(err),
+        }
+    }
+    Ok(result)
+}
+
+/// Checks that a change from `before` to `after` kept what links, dedupe
+/// and history rely on: a todo keeps its id and `created_at`, no id is
+/// taken twice that wasn't already, and a status change went through
+/// `Todo::set_status`, which keeps `completed_at` and `doing_at` in step.
+/// `updated_at` needs no check, `stamp_updates` overwrites it anyway.
+fn check_protected(before: &[Todo], after: &[Todo]) -> Result<(), Error> {
+    let mut old: HashMap<usize, &Todo> = HashMap::new();
+    let mut counts: HashMap<usize, (usize, usize)> = HashMap::new();
+    for todo in before {
+        old.entry(todo.id).or_insert(todo);
+        counts.entry(todo.id).or_default().0 += 1;
+    }
+    for todo in after {
+        counts.entry(todo.id).or_default().1 += 1;
+    }
+    if let Some((id, _)) = counts
+        .iter()
+        .find(|(_, (before, after))| *after > 1 && after > before)
+    {
+        return Err(Error::ProtectedFieldError(*id, "id"));
+    }
+    // a todo that left and one that came with its created_at is the same
+    // todo under another id
+    let new_ids: HashSet<usize> = after.iter().map(|t| t.id).collect();
+    for gone in before.iter().filter(|t| !new_ids.contains(&t.id)) {
+        if after
+            .iter()
+            .any(|t| !old.contains_key(&t.id) && t.created_at == gone.created_at)
+        {
+            return Err(Error::ProtectedFieldError(gone.id, "id"));
+        }
+    }
+    for todo in after {
+        let previous = match old.get(&todo.id) {
+            Some(previous) => previous,
+            None => continue,
+        };
+        if todo.created_at != previous.created_at {
+            return Err(Error::ProtectedFieldError(todo.id, "created_at"));
+        }
+        if todo.status() == previous.status() {
+            continue;
+        }
+        // todos in Doing from before doing_at existed have none, and undo
+        // can bring one back that way, so only leaving Doing is checked
+        let done = todo.status() == TodoStatus::Done;
+        let left_doing = previous.status() == TodoStatus::Doing;
+        if done != todo.completed_at.is_some() || (left_doing && todo.doing_at.is_some()) {
+            return Err(Error::ProtectedFieldError(todo.id, "status"));
+        }
+    }
+    Ok(())
+}
+
+/// Sets `updated_at` on every todo that is new or differs from `before`.
+fn stamp_updates(before: &[Todo], after: &mut [Todo], now: DateTime<Utc>) {
+    let before: HashMap<usize, serde_json::Value> = before
+        .iter()
+        .map(|t| (t.id, serde_json::to_value(t).unwrap_or_default()))
+        .collect();
+    for todo in after.iter_mut() {
+        if before.get(&todo.id) != Some(&serde_json::to_value(&*todo).unwrap_or_default()) {
+            todo.touch(now);
+        }
+    }
+}
+
+/// The todos changed at or after `since`.
+fn read_db_modified_since(since: DateTime<Utc>) -> Result<Vec<Todo>, Error> {
+    Ok(read_db()?
+        .into_iter()
+        .filter(|t| t.modified_at() >= since)
+        .collect())
+}
+
+/// Saves changes `update_db` held back, over what another process saved
+/// meanwhile, see `catch_up`. Unless `force`d, only once the autosave
+/// interval has passed since the last save, or after a failed save once
+/// it is time to retry. A save that fails again keeps the changes in
+/// memory where failed saves are held, see `autosave::failure`.
+fn flush_db(force: bool) -> Result<(), Error> {
+    let due = match autosave::failure() {
+        Some(_) => autosave::retry_due(),
+        None => autosave::save_allowed(),
+    };
+    if (!force && !due) || autosave::pending().is_none() {
+        return Ok(());
+    }
+    let saved = lock::DbLock::acquire().and_then(|_lock| {
+        catch_up()?;
+        match autosave::pending() {
+            Some(todos) => write_db(&todos),
+            None => Ok(()),
+        }
+    });
+    match saved {
+        Err(Error::WriteDBError(err)) if autosave::holds_failed_saves() => {
+            autosave::failed(&err);
+            Ok(())
+        }
+        saved => saved,
+    }
+}
+
+/// Writes through a temp file and a rename, so a failed write never
+/// leaves a half-written data file behind.
+fn write_atomic(path: &str, content: &str) -> Result<(), Error> {
+    write_through_tmp(path, content).map_err(|err| Error::WriteFileError(path.to_string(), err))
+}
+
+fn write_through_tmp(path: &str, content: &str) -> io::Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)
+}
+
+#[cfg(feature = "tui")]
+/// Toggles `pinned` on the todo at `selected` in the Todo column and
+/// returns its new index, since pinning moves it within the column.
+fn toggle_pin(
+    selected: usize,
+    filter: &Filter,
+    undo: &mut UndoStack,
+) -> Result<Option<usize>, Error> {
+    let id = match read_db_by_todo_status(TodoStatus::Todo, filter)?.get(selected) {
+        Some(todo) => todo.id,
+        None => return Ok(None),
+    };
+    let before = update_db(|todos| {
+        let before = todos.clone();
+        for todo in todos.iter_mut().filter(|t| t.id == id) {
+            todo.pinned = !todo.pinned;
+        }
+        before
+    })?;
+    undo.push("pin", before, 0);
+    Ok(read_db_by_todo_status(TodoStatus::Todo, filter)?
+        .iter()
+        .position(|t| t.id == id))
+}
+
+#[cfg(feature = "tui")]
+/// Keeps a column selection inside the column after its contents changed.
+fn clamp_selection(state: &mut ListState, status: TodoStatus, filter: &Filter) {
+    let len = read_db_by_todo_status(status, filter)
+        .map(|todos| todos.len())
+        .unwrap_or(0);
+    if len == 0 {
+        state.select(None);
+    } else {
+        state.select(Some(state.selected().unwrap_or(0).min(len - 1)));
+    }
+}
+
+fn read_db_by_todo_status(status: TodoStatus, filter: &Filter) -> Result<Vec<Todo>, Error> {
+    Ok(column_todos(&read_db()?, status, filter))
+}
+
+/// The todos of `parsed` in the column for `status` that `filter` lets
+/// through, in board order.
+///
+/// ```
+/// use work_time_cli::{add_todo, column_todos, parse_quick_add, Db, Filter, TodoStatus};
+///
+/// let mut db = Db::default();
+/// for line in ["Pay rent @home", "Write report @work"] {
+///     add_todo(&mut db, parse_quick_add(line).unwrap());
+/// }
+/// let work = Filter::parse("category:work").unwrap();
+/// let column = column_todos(&db.todos, TodoStatus::Todo, &work);
+/// assert_eq!(column.len(), 1);
+/// assert_eq!(column[0].title, "Write report");
+/// ```
+pub fn column_todos(parsed: &[Todo], status: TodoStatus, filter: &Filter) -> Vec<Todo> {
+    let now = zone::now();
+    let mut filtered: Vec<Todo> = parsed
+        .iter()
+        .filter(|s| s.status() == status && filter.matches(s, parsed, now))
+        .cloned()
+        .collect();
+    if status == TodoStatus::Done {
+        sort::sort_done(&mut filtered);
+    } else if let Some(board) = urgency::board() {
+        urgency::sort(&mut filtered, parsed, now.with_timezone(&Utc), &board);
+    } else {
+        sort::sort_column(&mut filtered);
+    }
+    filtered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(id: usize, created_at: &str) -> Todo {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "title": format!("todo {}", id),
+            "description": "",
+            "category": "work",
+            "status": "Todo",
+            "created_at": created_at,
+        }))
+        .unwrap()
+    }
+
+    fn rejected(before: &[Todo], after: &[Todo]) -> Option<(usize, &'static str)> {
+        match check_protected(before, after) {
+            Err(Error::ProtectedFieldError(id, field)) => Some((id, field)),
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(()) => None,
+        }
+    }
+
+    #[test]
+    fn changing_created_at_is_rejected() {
+        let before = [todo(1, "2024-05-01T09:00:00Z")];
+        let after = [todo(1, "2024-05-02T09:00:00Z")];
+        assert_eq!(rejected(&before, &after), Some((1, "created_at")));
+    }
+
+    #[test]
+    fn changing_an_id_is_rejected() {
+        let before = [
+            todo(1, "2024-05-01T09:00:00Z"),
+            todo(2, "2024-05-02T09:00:00Z"),
+        ];
+        // 2 taken twice
+        let mut taken = before.clone();
+        taken[0].id = 2;
+        assert_eq!(rejected(&before, &taken), Some((2, "id")));
+        // 2 renumbered to 3
+        let mut renumbered = before.clone();
+        renumbered[1].id = 3;
+        assert_eq!(rejected(&before, &renumbered), Some((2, "id")));
+    }
+
+    #[test]
+    fn a_status_change_without_its_timestamps_is_rejected() {
+        let before = [todo(1, "2024-05-01T09:00:00Z")];
+        // what an import or a hand-edited file could bring in
+        let mut after: Vec<Todo> = serde_json::from_value(serde_json::json!([{
+            "id": 1,
+            "title": "todo 1",
+            "description": "",
+            "category": "work",
+            "status": "Done",
+            "created_at": "2024-05-01T09:00:00Z",
+        }]))
+        .unwrap();
+        assert_eq!(rejected(&before, &after), Some((1, "status")));
+
+        after[0] = before[0].clone();
+        after[0].set_status(TodoStatus::Done, Utc::now());
+        assert_eq!(rejected(&before, &after), None);
+    }
+
+    #[test]
+    fn adding_and_removing_todos_is_allowed() {
+        let before = [todo(1, "2024-05-01T09:00:00Z")];
+        let after = [todo(2, "2024-05-02T09:00:00Z")];
+        assert_eq!(rejected(&before, &after), None);
+    }
+
+    #[test]
+    fn stamping_marks_only_the_todos_that_changed() {
+        let before = [
+            todo(1, "2024-05-01T09:00:00Z"),
+            todo(2, "2024-05-02T09:00:00Z"),
+        ];
+        let mut after = before.to_vec();
+        after[1].title = "renamed".to_string();
+        after.push(todo(3, "2024-05-03T09:00:00Z"));
+        let now = Utc.with_ymd_and_hms(2024, 5, 4, 9, 0, 0).unwrap();
+        stamp_updates(&before, &mut after, now);
+        let modified: Vec<DateTime<Utc>> = after.iter().map(Todo::modified_at).collect();
+        assert_eq!(modified[0], before[0].created_at);
+        assert_eq!(modified[1..], [now, now]);
+    }
+
+    #[test]
+    fn a_failed_write_names_the_file() {
+        let path = std::env::temp_dir()
+            .join(format!("no-such-dir-{}", std::process::id()))
+            .join("notes.json");
+        let path = path.to_str().unwrap();
+        match write_atomic(path, "{}") {
+            Err(err @ Error::WriteFileError(..)) => {
+                assert!(err
+                    .to_string()
+                    .starts_with(&format!("error writing {}", path)))
+            }
+            other => panic!("expected a WriteFileError, got {:?}", other),
+        }
+    }
+
+    /// The `exit codes` table of the help, as (code, name) rows.
+    fn documented_exit_codes() -> Vec<(i32, String)> {
+        cli::HELP
+            .split("exit codes:\n")
+            .nth(1)
+            .unwrap()
+            .lines()
+            .filter_map(|line| {
+                let mut words = line.split_whitespace();
+                Some((words.next()?.parse().ok()?, words.next()?.to_string()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_error_exits_with_its_documented_code() {
+        let io = || io::Error::other("disk on fire");
+        let json = || serde_json::from_str::<Todo>("{").unwrap_err();
+        let errors = [
+            Error::ReadDBError(io()),
+            Error::ReadFileError("notes.json".to_string(), io()),
+            Error::ParseDBError(json()),
+            Error::WriteDBError(io()),
+            Error::WriteSessionError(io()),
+            Error::WriteFileError("notes.json".to_string(), io()),
+            Error::ParseConfigError(json()),
+            Error::DBLockedError("./data/db.json.lock"),
+            Error::ArchiveError("too new".to_string()),
+            Error::SchemaError("version 9".to_string()),
+            Error::ProtectedFieldError(1, "id"),
+        ];
+        let documented = documented_exit_codes();
+        for err in &errors {
+            let row = (err.exit_code(), err.code().to_string());
+            assert!(documented.contains(&row), "{:?} isn't documented", row);
+        }
+        // and every documented error class has an error behind it
+        for (code, name) in &documented {
+            if *code > cli::USAGE_EXIT {
+                assert!(errors.iter().any(|e| e.exit_code() == *code), "{}", name);
+            }
+        }
+    }
+}
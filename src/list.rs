@@ -0,0 +1,272 @@
+use chrono::prelude::*;
+use serde_json::json;
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
+
+use crate::cli;
+use crate::config::{self, Config};
+use crate::escalation::effective_priority;
+use crate::filter::Filter;
+use crate::subtasks::effective_due;
+use crate::urgency;
+use crate::zone;
+use crate::{read_db, read_db_modified_since, sort, Priority, Todo, TodoStatus};
+
+const USAGE: &str = "usage: list [--format json|text] [--sort KEYS] [--modified-since TIMESTAMP] \
+                     [--include FILTER]";
+const DEFAULT_SORT: &str = "status,rank";
+
+/// What sorting needs from the whole board: each todo's rank, its
+/// effective due date and its priority after escalation.
+struct Board {
+    order: HashMap<usize, usize>,
+    due: HashMap<usize, DateTime<Utc>>,
+    priority: HashMap<usize, Priority>,
+    urgency: HashMap<usize, f64>,
+}
+
+/// One key of `--sort`. Each orders ascending; see `compare`.
+#[derive(Clone, Copy, Debug)]
+enum SortKey {
+    Status,
+    Rank,
+    Priority,
+    Due,
+    Urgency,
+    Created,
+    Id,
+}
+
+impl Board {
+    /// The ranks, due dates and priorities of `all` at `now`.
+    fn build(all: &[Todo], config: &Config, now: DateTime<Utc>) -> Board {
+        Board {
+            order: sort::board_order(all),
+            due: all
+                .iter()
+                .filter_map(|t| effective_due(t, all).map(|due| (t.id, due)))
+                .collect(),
+            priority: all
+                .iter()
+                .filter_map(|t| {
+                    effective_priority(t, all, now, &config.priority_escalation)
+                        .priority
+                        .map(|priority| (t.id, priority))
+                })
+                .collect(),
+            urgency: all
+                .iter()
+                .map(|t| {
+                    let score = urgency::score(
+                        t,
+                        all,
+                        now,
+                        &config.priority_escalation,
+                        &config.urgency_weights,
+                    );
+                    (t.id, score)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl SortKey {
+    fn parse(value: &str) -> Option<SortKey> {
+        match value {
+            "status" => Some(SortKey::Status),
+            "rank" => Some(SortKey::Rank),
+            "priority" => Some(SortKey::Priority),
+            "due" => Some(SortKey::Due),
+            "urgency" => Some(SortKey::Urgency),
+            "created" => Some(SortKey::Created),
+            "id" => Some(SortKey::Id),
+            _ => None,
+        }
+    }
+
+    /// status: ToDo, Doing, Done. rank: position on the unfiltered board.
+    /// priority: Urgent to Low as escalated, then none. due: earliest effective due date
+    /// first, then none. urgency: most urgent first, see `urgency::score`.
+    /// created: oldest first. id: lowest first.
+    fn compare(&self, a: &Todo, b: &Todo, board: &Board) -> Ordering {
+        let status = |todo: &Todo| match todo.status() {
+            TodoStatus::Todo => 0,
+            TodoStatus::Doing => 1,
+            TodoStatus::Done => 2,
+        };
+        match self {
+            SortKey::Status => status(a).cmp(&status(b)),
+            SortKey::Rank => board.order.get(&a.id).cmp(&board.order.get(&b.id)),
+            SortKey::Priority => {
+                let (a, b) = (board.priority.get(&a.id), board.priority.get(&b.id));
+                (a.is_none(), a.map(Reverse)).cmp(&(b.is_none(), b.map(Reverse)))
+            }
+            SortKey::Due => {
+                let (a, b) = (board.due.get(&a.id), board.due.get(&b.id));
+                (a.is_none(), a).cmp(&(b.is_none(), b))
+            }
+            SortKey::Urgency => {
+                let score = |todo: &Todo| board.urgency.get(&todo.id).copied().unwrap_or(0.0);
+                score(b).total_cmp(&score(a))
+            }
+            SortKey::Created => a.created_at.cmp(&b.created_at),
+            SortKey::Id => a.id.cmp(&b.id),
+        }
+    }
+}
+
+/// The keys of `--sort`, comma separated.
+fn parse_keys(value: &str) -> Result<Vec<SortKey>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .map(|name| {
+            SortKey::parse(name).ok_or_else(|| {
+                format!(
+                    "unknown sort key '{}', use status, rank, priority, due, urgency, created \
+                     or id",
+                    name
+                )
+            })
+        })
+        .collect()
+}
+
+fn sort(todos: &mut [Todo], keys: &[SortKey], board: &Board) {
+    todos.sort_by(|a, b| {
+        keys.iter()
+            .map(|key| key.compare(a, b, board))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or_else(|| a.id.cmp(&b.id))
+    });
+}
+
+/// Orders `todos` by `keys` as `list --sort` does, e.g. "priority,due".
+/// Ranks, effective due dates and escalated priorities are those of
+/// `all`, the whole board, so they don't shift with a filter; ties on
+/// every key fall back to the id.
+///
+/// ```
+/// use chrono::Utc;
+/// use work_time_cli::{add_todo, parse_quick_add, sort_todos, Config, Db};
+///
+/// let mut db = Db::default();
+/// add_todo(&mut db, parse_quick_add("Sort mail !low").unwrap());
+/// add_todo(&mut db, parse_quick_add("Renew passport !urgent").unwrap());
+///
+/// let mut todos = db.todos.clone();
+/// sort_todos(&mut todos, "priority", &db.todos, &Config::default(), Utc::now()).unwrap();
+/// assert_eq!(todos[0].title, "Renew passport");
+/// assert!(sort_todos(&mut todos, "size", &db.todos, &Config::default(), Utc::now()).is_err());
+/// ```
+pub fn sort_todos(
+    todos: &mut [Todo],
+    keys: &str,
+    all: &[Todo],
+    config: &Config,
+    now: DateTime<Utc>,
+) -> Result<(), String> {
+    let keys = parse_keys(keys)?;
+    sort(todos, &keys, &Board::build(all, config, now));
+    Ok(())
+}
+
+/// Runs `list [--format json|text] [--sort KEYS] [--modified-since TIMESTAMP]
+/// [--include FILTER]` and returns the process exit code. The output is
+/// always fully ordered: ties on every key fall back to the id.
+pub fn run(args: &[String]) -> i32 {
+    let mut json_output = false;
+    let mut keys = vec![];
+    let mut since = None;
+    let mut filter = Filter::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = match (arg.as_str(), args.next()) {
+            ("--format" | "--sort" | "--modified-since" | "--include", Some(value)) => value,
+            ("--format" | "--sort" | "--modified-since" | "--include", None) => {
+                return cli::usage(format!("{} needs a value", arg))
+            }
+            _ => return cli::usage(USAGE),
+        };
+        match arg.as_str() {
+            "--format" => match value.as_str() {
+                "json" => json_output = true,
+                "text" => json_output = false,
+                _ => return cli::usage("--format takes json or text"),
+            },
+            "--sort" => match parse_keys(value) {
+                Ok(parsed) => keys = parsed,
+                Err(err) => return cli::usage(err),
+            },
+            "--modified-since" => match DateTime::parse_from_rfc3339(value) {
+                Ok(parsed) => since = Some(parsed.with_timezone(&Utc)),
+                Err(_) => {
+                    return cli::usage(format!(
+                        "invalid --modified-since '{}', use RFC 3339 like 2024-05-01T09:00:00Z",
+                        value
+                    ))
+                }
+            },
+            _ => match Filter::parse(value) {
+                Ok(parsed) => filter = parsed,
+                Err(err) => return cli::usage(format!("invalid --include: {}", err)),
+            },
+        }
+    }
+    if keys.is_empty() {
+        keys = DEFAULT_SORT.split(',').filter_map(SortKey::parse).collect();
+    }
+
+    let config = match config::load() {
+        Ok(config) => config,
+        Err(err) => return cli::fail(&err),
+    };
+    filter.week_start = Some(config.week_start);
+    // ranks and due dates come from the whole board so they don't shift
+    // with the filters
+    let all = match read_db() {
+        Ok(todos) => todos,
+        Err(err) => return cli::fail(&err),
+    };
+    let board = Board::build(&all, &config, Utc::now());
+    let order = &board.order;
+    let todos = match since {
+        Some(since) => read_db_modified_since(since),
+        None => read_db(),
+    };
+    let now = zone::now();
+    let mut todos: Vec<Todo> = match todos {
+        Ok(todos) => todos
+            .into_iter()
+            .filter(|t| filter.matches(t, &all, now))
+            .collect(),
+        Err(err) => return cli::fail(&err),
+    };
+    sort(&mut todos, &keys, &board);
+
+    if json_output {
+        let objects: Vec<_> = todos
+            .iter()
+            .map(|todo| {
+                let mut object = json!(todo);
+                object["order"] = json!(order.get(&todo.id));
+                object["updated_at"] = json!(todo.modified_at());
+                object
+            })
+            .collect();
+        println!("{}", json!(objects));
+    } else {
+        for todo in &todos {
+            println!(
+                "{:>4} {:>4} {:<6} {}",
+                order.get(&todo.id).copied().unwrap_or_default(),
+                todo.id,
+                format!("{:?}", todo.status()),
+                todo.title
+            );
+        }
+    }
+    0
+}
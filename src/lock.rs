@@ -0,0 +1,47 @@
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::Error;
+
+pub const LOCK_PATH: &str = "./data/db.json.lock";
+
+const MAX_WAIT: Duration = Duration::from_secs(2);
+const FIRST_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Exclusive right to read-modify-write the DB, shared by the TUI and the
+/// CLI so neither overwrites a save the other has in flight. Released on drop.
+pub struct DbLock;
+
+impl DbLock {
+    /// Takes the lock, backing off while another instance holds it and
+    /// giving up after about two seconds.
+    pub fn acquire() -> Result<DbLock, Error> {
+        let started = Instant::now();
+        let mut backoff = FIRST_BACKOFF;
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(LOCK_PATH)
+            {
+                Ok(_) => return Ok(DbLock),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    if started.elapsed() >= MAX_WAIT {
+                        return Err(Error::DBLockedError(LOCK_PATH));
+                    }
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_WAIT / 4);
+                }
+                Err(err) => return Err(Error::WriteDBError(err)),
+            }
+        }
+    }
+}
+
+impl Drop for DbLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(LOCK_PATH);
+    }
+}
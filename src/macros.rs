@@ -0,0 +1,193 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+use crate::actions::ActionId;
+
+/// The most times `[count]@<letter>` replays a register, so a stray
+/// `99999@a` can't hang the UI.
+pub const MAX_COUNT: usize = 100;
+
+#[derive(Copy, Clone, PartialEq, Default)]
+enum Pending {
+    #[default]
+    Nothing,
+    Register,
+    Replay,
+}
+
+/// One recorded step: the action a key ran through the keymap, or the key
+/// itself when an open view took it or it ran nothing. Registers saved as
+/// plain keys still load, as `Key` steps.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Step {
+    Key(KeyEvent),
+    Action(ActionId),
+}
+
+pub enum MacroAction {
+    /// Not a macro key, handle it normally.
+    Pass,
+    Consumed,
+    /// `qq`, since `q` alone now waits for a register. Whether the second
+    /// `q` came soon enough is up to the caller.
+    Quit,
+    AwaitRegister,
+    StartedRecording(char),
+    StoppedRecording(char, Vec<Step>),
+    Replay(char, usize),
+    Cancelled,
+}
+
+/// Vim style keyboard macros: `q<letter>` records the following keys, as
+/// steps the caller hands to `record`, `q` stops, `[count]@<letter>` replays a register and `@@` the last one.
+/// The registers themselves live in the session.
+#[derive(Default)]
+pub struct Macros {
+    pending: Pending,
+    count: usize,
+    recording: Option<(char, Vec<Step>)>,
+    last_replayed: Option<char>,
+}
+
+impl Macros {
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _)| *register)
+    }
+
+    pub fn intercept(&mut self, key: KeyEvent) -> MacroAction {
+        let register = match key.code {
+            KeyCode::Char(c) if c.is_ascii_lowercase() => Some(c),
+            _ => None,
+        };
+        match std::mem::take(&mut self.pending) {
+            Pending::Register => {
+                return match (key.code, register) {
+                    (KeyCode::Char('q'), _) => MacroAction::Quit,
+                    (_, Some(register)) => {
+                        self.recording = Some((register, vec![]));
+                        MacroAction::StartedRecording(register)
+                    }
+                    _ => MacroAction::Cancelled,
+                };
+            }
+            Pending::Replay => {
+                let count = std::mem::take(&mut self.count).max(1);
+                let register = match key.code {
+                    KeyCode::Char('@') => self.last_replayed,
+                    _ => register,
+                };
+                return match register {
+                    Some(register) => {
+                        self.last_replayed = Some(register);
+                        MacroAction::Replay(register, count)
+                    }
+                    None => MacroAction::Cancelled,
+                };
+            }
+            Pending::Nothing => {}
+        }
+
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            self.count = 0;
+            return MacroAction::Pass;
+        }
+        match key.code {
+            KeyCode::Char('q') => match self.recording.take() {
+                Some((register, keys)) => MacroAction::StoppedRecording(register, keys),
+                None => {
+                    self.pending = Pending::Register;
+                    MacroAction::AwaitRegister
+                }
+            },
+            KeyCode::Char('@') if self.recording.is_none() => {
+                self.pending = Pending::Replay;
+                MacroAction::Consumed
+            }
+            KeyCode::Char(digit @ '0'..='9')
+                if self.recording.is_none() && (self.count > 0 || digit != '0') =>
+            {
+                let digit = digit.to_digit(10).expect("is a digit") as usize;
+                self.count = (self.count * 10 + digit).min(MAX_COUNT);
+                MacroAction::Consumed
+            }
+            _ => {
+                self.count = 0;
+                MacroAction::Pass
+            }
+        }
+    }
+
+    /// Adds what a passed key did to the recording, if there is one.
+    pub fn record(&mut self, step: Step) {
+        if let Some((_, steps)) = &mut self.recording {
+            steps.push(step);
+        }
+    }
+
+    /// Waits for a register again, as after a first `q`.
+    pub fn await_register(&mut self) {
+        self.pending = Pending::Register;
+    }
+
+    /// Drops an in-progress recording, returning its register if there was one.
+    pub fn abort_recording(&mut self) -> Option<char> {
+        self.recording.take().map(|(register, _)| register)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(macros: &mut Macros, keys: &str) -> Vec<MacroAction> {
+        keys.chars()
+            .map(|c| macros.intercept(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)))
+            .collect()
+    }
+
+    #[test]
+    fn counts_stop_at_the_maximum() {
+        let mut macros = Macros::default();
+        let replayed = press(&mut macros, "99999999999999999999999@a").pop();
+        assert!(matches!(
+            replayed,
+            Some(MacroAction::Replay('a', MAX_COUNT))
+        ));
+        let replayed = press(&mut macros, "3@@").pop();
+        assert!(matches!(replayed, Some(MacroAction::Replay('a', 3))));
+        let replayed = press(&mut macros, "@b").pop();
+        assert!(matches!(replayed, Some(MacroAction::Replay('b', 1))));
+    }
+
+    #[test]
+    fn only_steps_while_recording_are_kept() {
+        let mut macros = Macros::default();
+        macros.record(Step::Action(ActionId::NextTodo));
+        press(&mut macros, "qa");
+        assert_eq!(macros.recording(), Some('a'));
+        macros.record(Step::Action(ActionId::NextTodo));
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        macros.record(Step::Key(enter));
+        match press(&mut macros, "q").pop() {
+            Some(MacroAction::StoppedRecording('a', steps)) => {
+                assert_eq!(steps, [Step::Action(ActionId::NextTodo), Step::Key(enter)])
+            }
+            _ => panic!("recording didn't stop"),
+        }
+        assert_eq!(macros.recording(), None);
+    }
+
+    #[test]
+    fn registers_saved_as_keys_still_load() {
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        let saved = serde_json::to_string(&vec![enter]).unwrap();
+        let steps: Vec<Step> = serde_json::from_str(&saved).unwrap();
+        assert_eq!(steps, [Step::Key(enter)]);
+
+        let steps = vec![Step::Action(ActionId::MoveTodoRight), Step::Key(enter)];
+        let saved = serde_json::to_string(&steps).unwrap();
+        assert!(saved.starts_with("[\"move todo right\","));
+        assert_eq!(serde_json::from_str::<Vec<Step>>(&saved).unwrap(), steps);
+    }
+}
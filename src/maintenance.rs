@@ -0,0 +1,157 @@
+use chrono::prelude::*;
+use chrono::Duration;
+
+use crate::config::{StaleAction, StalePolicy};
+use crate::timetracking::{self, TimeEntry};
+use crate::{read_db, update_db, Error, Todo, TodoStatus};
+
+/// The latest sign of work on a todo: entering Doing, an edit, or a time
+/// entry on it. Running entries count as activity `now`.
+pub fn last_activity(todo: &Todo, entries: &[TimeEntry], now: DateTime<Utc>) -> DateTime<Utc> {
+    entries
+        .iter()
+        .filter(|e| e.todo_id == Some(todo.id))
+        .map(|e| e.end.unwrap_or(now))
+        .chain(todo.doing_at)
+        .chain(Some(todo.modified_at()))
+        .max()
+        .unwrap_or(todo.created_at)
+}
+
+/// Todos in Doing with no activity for more than `days`.
+pub fn stale_doing<'a>(
+    todos: &'a [Todo],
+    entries: &[TimeEntry],
+    days: i64,
+    now: DateTime<Utc>,
+) -> Vec<&'a Todo> {
+    todos
+        .iter()
+        .filter(|t| t.status() == TodoStatus::Doing)
+        .filter(|t| now - last_activity(t, entries, now) > Duration::days(days))
+        .collect()
+}
+
+/// Applies the stale Doing policy and returns a message for the status
+/// line when it found anything.
+pub fn run(policy: &StalePolicy, now: DateTime<Utc>) -> Result<Option<String>, Error> {
+    let entries = timetracking::read_time_entries()?;
+    let todos = read_db()?;
+    let stale: Vec<(usize, String, i64)> = stale_doing(&todos, &entries, policy.days, now)
+        .iter()
+        .map(|t| {
+            let idle = (now - last_activity(t, &entries, now)).num_days();
+            (t.id, t.title.clone(), idle)
+        })
+        .collect();
+    if stale.is_empty() {
+        return Ok(None);
+    }
+    let titles: Vec<&str> = stale.iter().map(|(_, title, _)| title.as_str()).collect();
+    match policy.action {
+        StaleAction::Warn => Ok(Some(format!(
+            "in Doing for over {}d without activity: {}",
+            policy.days,
+            titles.join(", ")
+        ))),
+        StaleAction::Demote => {
+            update_db(|todos| {
+                for (id, _, idle) in &stale {
+                    if let Some(todo) = todos.iter_mut().find(|t| t.id == *id) {
+                        todo.set_status(TodoStatus::Todo, now);
+                        let note = format!("auto-demoted after {}d inactive", idle);
+                        let description = if todo.description().is_empty() {
+                            note
+                        } else {
+                            format!("{}\n\n{}", todo.description(), note)
+                        };
+                        // one already at the limit goes without the note
+                        let _ = todo.set_description(description);
+                    }
+                }
+            })?;
+            Ok(Some(format!("moved back to ToDo: {}", titles.join(", "))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetracking::push_running;
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, day, 9, 0, 0).unwrap()
+    }
+
+    /// A todo created on the 1st and in Doing since `doing`, last edited
+    /// on `edited`.
+    fn doing(id: usize, doing: u32, edited: Option<u32>) -> Todo {
+        let mut todo = Todo::new(format!("todo {}", id), "work".to_string(), day(1));
+        todo.id = id;
+        todo.set_status(TodoStatus::Doing, day(doing));
+        if let Some(edited) = edited {
+            todo.touch(day(edited));
+        }
+        todo
+    }
+
+    fn entry(entries: &mut Vec<TimeEntry>, todo_id: usize, from: u32, to: Option<u32>) {
+        let id = push_running(entries, "work", day(from), None, Some(todo_id), None);
+        entries[id - 1].end = to.map(day);
+    }
+
+    fn stale_ids(todos: &[Todo], entries: &[TimeEntry], now: DateTime<Utc>) -> Vec<usize> {
+        stale_doing(todos, entries, 14, now)
+            .iter()
+            .map(|t| t.id)
+            .collect()
+    }
+
+    #[test]
+    fn the_latest_of_doing_edits_and_entries_counts() {
+        let todo = doing(1, 2, Some(5));
+        assert_eq!(last_activity(&todo, &[], day(30)), day(5));
+        let mut entries = vec![];
+        entry(&mut entries, 1, 6, Some(8));
+        entry(&mut entries, 2, 9, Some(10));
+        assert_eq!(last_activity(&todo, &entries, day(30)), day(8));
+        entry(&mut entries, 1, 20, None);
+        assert_eq!(last_activity(&todo, &entries, day(30)), day(30));
+        // no doing_at, from before it was kept, falls back to the edits
+        let mut legacy = doing(3, 2, None);
+        legacy.doing_at = None;
+        assert_eq!(last_activity(&legacy, &[], day(30)), day(1));
+    }
+
+    #[test]
+    fn each_sign_of_work_keeps_a_todo_from_going_stale() {
+        let now = day(30);
+        let todos = [
+            doing(1, 2, None),     // nothing since entering Doing
+            doing(2, 20, None),    // entered Doing lately
+            doing(3, 2, Some(20)), // edited lately
+            doing(4, 2, None),     // time tracked lately
+            doing(5, 2, None),     // a timer still runs
+            doing(6, 2, None),     // time tracked only on another todo
+        ];
+        let mut entries = vec![];
+        entry(&mut entries, 4, 19, Some(20));
+        entry(&mut entries, 5, 3, None);
+        entry(&mut entries, 2, 25, Some(26));
+        assert_eq!(stale_ids(&todos, &entries, now), [1, 6]);
+    }
+
+    #[test]
+    fn only_doing_todos_past_the_threshold_are_stale() {
+        let mut todo_column = doing(1, 2, None);
+        todo_column.set_status(TodoStatus::Todo, day(2));
+        let mut done = doing(2, 2, None);
+        done.set_status(TodoStatus::Done, day(2));
+        let exactly = doing(3, 2, None);
+        let todos = [todo_column, done, exactly];
+        // fourteen days to the minute is not over the threshold yet
+        assert_eq!(stale_ids(&todos, &[], day(16)), Vec::<usize>::new());
+        assert_eq!(stale_ids(&todos, &[], day(16) + Duration::minutes(1)), [3]);
+    }
+}
@@ -0,0 +1,180 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::actions::{self, Action, ActionId};
+use crate::cursor;
+use crate::widgets::centered_rect;
+use crate::{Todo, TodoStatus};
+
+/// A column's name as the board titles it.
+pub fn column_title(status: &TodoStatus) -> &'static str {
+    match status {
+        TodoStatus::Todo => "ToDo",
+        TodoStatus::Doing => "Doing",
+        TodoStatus::Done => "Done",
+    }
+}
+
+/// What a row of a menu does.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Choice {
+    /// Moves the todo to the column.
+    Move(usize, TodoStatus),
+    Run(ActionId),
+    /// Runs the `:` command line.
+    Command(String),
+}
+
+pub enum MenuAction {
+    Continue,
+    Cancel,
+    Pick(Choice),
+    /// Enter on an action that can't run here, with the reason.
+    Unavailable(&'static str, String),
+}
+
+/// A popup of choices picked with the arrow keys and Enter. Rows that
+/// can't run are shown grayed out with the reason.
+pub struct Menu {
+    title: String,
+    /// Each row with the reason it can't run, None when it can.
+    rows: Vec<(String, Choice, Option<String>)>,
+    selected: usize,
+}
+
+/// The rows running `ids`, each grayed out when `blocked` tells why it
+/// can't run right now, like the palette.
+fn action_rows(
+    ids: &[ActionId],
+    blocked: impl Fn(&Action) -> Option<String>,
+) -> Vec<(String, Choice, Option<String>)> {
+    ids.iter()
+        .filter_map(|id| actions::ACTIONS.iter().find(|a| a.id == *id))
+        .map(|action| {
+            (
+                action.name.to_string(),
+                Choice::Run(action.id),
+                blocked(action),
+            )
+        })
+        .collect()
+}
+
+impl Menu {
+    pub fn new(title: impl Into<String>, rows: Vec<(String, Choice)>) -> Menu {
+        Menu {
+            title: title.into(),
+            rows: rows
+                .into_iter()
+                .map(|(label, choice)| (label, choice, None))
+                .collect(),
+            selected: 0,
+        }
+    }
+
+    /// The popup Enter opens on a todo, so moving, editing, timing and
+    /// deleting it all work from the arrow keys: the other columns to
+    /// move it to, then the actions of `actions::MENU`.
+    pub fn todo(todo: &Todo, blocked: impl Fn(&Action) -> Option<String>) -> Menu {
+        let mut rows: Vec<(String, Choice, Option<String>)> =
+            [TodoStatus::Todo, TodoStatus::Doing, TodoStatus::Done]
+                .into_iter()
+                .filter(|status| *status != todo.status())
+                .map(|status| {
+                    (
+                        format!("move to {}", column_title(&status)),
+                        Choice::Move(todo.id, status),
+                        None,
+                    )
+                })
+                .collect();
+        rows.extend(action_rows(actions::MENU, blocked));
+        Menu {
+            title: format!("#{} {}", todo.id, todo.title),
+            rows,
+            selected: 0,
+        }
+    }
+
+    /// The popup `.` opens on the focused column, with the actions of
+    /// `actions::COLUMN_MENU`.
+    pub fn column(status: &TodoStatus, blocked: impl Fn(&Action) -> Option<String>) -> Menu {
+        Menu {
+            title: format!("{} column", column_title(status)),
+            rows: action_rows(actions::COLUMN_MENU, blocked),
+            selected: 0,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> MenuAction {
+        match key.code {
+            KeyCode::Esc => return MenuAction::Cancel,
+            KeyCode::Enter => {
+                return match self.rows.get(self.selected) {
+                    Some((_, choice, None)) => MenuAction::Pick(choice.clone()),
+                    Some((_, Choice::Run(id), Some(reason))) => {
+                        let name = actions::ACTIONS
+                            .iter()
+                            .find(|a| a.id == *id)
+                            .map_or("action", |a| a.name);
+                        MenuAction::Unavailable(name, reason.clone())
+                    }
+                    _ => MenuAction::Continue,
+                }
+            }
+            KeyCode::Down | KeyCode::Tab | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.rows.len().saturating_sub(1))
+            }
+            KeyCode::Up | KeyCode::BackTab | KeyCode::Char('k') => {
+                self.selected = self.selected.saturating_sub(1)
+            }
+            _ => {}
+        }
+        MenuAction::Continue
+    }
+}
+
+pub fn render_menu<B: Backend>(rect: &mut Frame<B>, menu: &Menu, area: Rect) {
+    let popup = centered_rect(40, 60, area);
+    let rows = popup.height.saturating_sub(2) as usize;
+    let offset = (menu.selected + 1).saturating_sub(rows);
+    let cursor = cursor::current();
+    let lines: Vec<Spans> = menu
+        .rows
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(rows)
+        .map(|(row, (label, _, blocked))| {
+            let base = match blocked {
+                Some(_) => Style::default().fg(Color::DarkGray),
+                None => Style::default().fg(Color::White),
+            };
+            let (marker, style) = cursor.row(row == menu.selected, base);
+            let mut spans = vec![Span::styled(format!("{}{}", marker, label), style)];
+            if let Some(reason) = blocked {
+                spans.push(Span::styled(
+                    format!(" ({})", reason),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            Spans::from(spans)
+        })
+        .collect();
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(format!("{} (Enter: pick, Esc: close)", menu.title))
+            .border_type(BorderType::Plain),
+    );
+    rect.render_widget(Clear, popup);
+    rect.render_widget(paragraph, popup);
+}
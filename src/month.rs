@@ -0,0 +1,438 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use crossterm::event::{KeyCode, KeyEvent};
+use std::collections::BTreeMap;
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+use unicode_width::UnicodeWidthChar;
+
+use crate::calendar::{self, DaysOff};
+use crate::chrome::Chrome;
+use crate::cursor;
+use crate::subtasks::effective_due;
+use crate::timesheet::{next_month, previous_month};
+use crate::widgets::centered_rect;
+use crate::zone::Zone;
+use crate::{Todo, TodoStatus};
+
+/// Titles a day cell lists at most, the rest are counted as "+n".
+const MAX_TITLES: usize = 2;
+/// Cells narrower than this only count their todos.
+const MIN_TITLE_WIDTH: u16 = 8;
+/// Cells narrower than this don't fit the cursor and the day number, so
+/// the view lists the days instead.
+const MIN_CELL_WIDTH: u16 = 3;
+
+/// The weeks `month` touches, each starting on `week_start`, with None
+/// for the days of the months around it.
+pub fn weeks(month: NaiveDate, week_start: Weekday) -> Vec<[Option<NaiveDate>; 7]> {
+    let first = month.with_day(1).expect("every month has a first day");
+    let end = next_month(first);
+    let lead = (first.weekday().num_days_from_monday() + 7 - week_start.num_days_from_monday()) % 7;
+    let mut day = first - Duration::days(lead as i64);
+    let mut weeks = vec![];
+    while day < end {
+        let mut week = [None; 7];
+        for slot in week.iter_mut() {
+            if day >= first && day < end {
+                *slot = Some(day);
+            }
+            day += Duration::days(1);
+        }
+        weeks.push(week);
+    }
+    weeks
+}
+
+/// How big a day cell is and what fits in it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CellSize {
+    pub width: u16,
+    pub height: u16,
+    /// Titles listed under the day number, 0 when the cell only counts.
+    pub titles: usize,
+}
+
+/// Shares `width` by `height` cells, what is left below the weekday
+/// names, among seven columns and `weeks` rows. A cell is the day number
+/// line and as many title lines as fit, up to `MAX_TITLES`; too narrow or
+/// too low for one, it shows counts only.
+pub fn cell_size(width: u16, height: u16, weeks: usize) -> CellSize {
+    let cell_width = width / 7;
+    let cell_height = (height / weeks.max(1) as u16).max(1);
+    let titles = if cell_width < MIN_TITLE_WIDTH {
+        0
+    } else {
+        (cell_height as usize - 1).min(MAX_TITLES)
+    };
+    CellSize {
+        width: cell_width,
+        height: cell_height,
+        titles,
+    }
+}
+
+/// How the month view fits the area inside its border.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MonthLayout {
+    /// A cell per day under the weekday names.
+    Grid(CellSize),
+    /// Too small for a line per week or a day number per column: the days
+    /// one after another, each with the count of todos due.
+    Counts,
+}
+
+/// The layout of `weeks` weeks in `width` by `height` cells, the line of
+/// weekday names included.
+pub fn layout(width: u16, height: u16, weeks: usize) -> MonthLayout {
+    let rows = height.saturating_sub(1);
+    if (rows as usize) < weeks || width / 7 < MIN_CELL_WIDTH {
+        return MonthLayout::Counts;
+    }
+    MonthLayout::Grid(cell_size(width, rows, weeks))
+}
+
+/// The todos not done yet by the local day of their effective due date,
+/// highest priority first, then earliest due and lowest id.
+pub fn due_by_day(todos: &[Todo], zone: Zone) -> BTreeMap<NaiveDate, Vec<&Todo>> {
+    let mut days: BTreeMap<NaiveDate, Vec<(DateTime<Utc>, &Todo)>> = BTreeMap::new();
+    for todo in todos.iter().filter(|t| t.status() != TodoStatus::Done) {
+        if let Some(due) = effective_due(todo, todos) {
+            days.entry(due.with_timezone(&zone).date_naive())
+                .or_default()
+                .push((due, todo));
+        }
+    }
+    days.into_iter()
+        .map(|(day, mut due)| {
+            due.sort_by(|(a_due, a), (b_due, b)| {
+                b.priority
+                    .cmp(&a.priority)
+                    .then(a_due.cmp(b_due))
+                    .then(a.id.cmp(&b.id))
+            });
+            (day, due.into_iter().map(|(_, todo)| todo).collect())
+        })
+        .collect()
+}
+
+/// The day `selected` moves to for an arrow key, None for other keys.
+pub fn step(selected: NaiveDate, key: KeyCode) -> Option<NaiveDate> {
+    let days = match key {
+        KeyCode::Left => -1,
+        KeyCode::Right => 1,
+        KeyCode::Up => -7,
+        KeyCode::Down => 7,
+        _ => return None,
+    };
+    Some(selected + Duration::days(days))
+}
+
+/// The same day of the month before or after, the month's last day when
+/// it is shorter.
+pub fn step_month(selected: NaiveDate, forward: bool) -> NaiveDate {
+    let month = selected.with_day(1).expect("every month has a first day");
+    let month = if forward {
+        next_month(month)
+    } else {
+        previous_month(month)
+    };
+    let last = next_month(month) - Duration::days(1);
+    month.with_day(selected.day()).unwrap_or(last)
+}
+
+/// `text` cut to `width` columns, with "…" where it was cut.
+fn cut(text: &str, width: usize) -> String {
+    let full: usize = text.chars().map(|c| c.width().unwrap_or(0)).sum();
+    if full <= width {
+        return text.to_string();
+    }
+    let mut room = width.saturating_sub(1);
+    let kept: String = text
+        .chars()
+        .take_while(|c| match room.checked_sub(c.width().unwrap_or(0)) {
+            Some(rest) => {
+                room = rest;
+                true
+            }
+            None => false,
+        })
+        .collect();
+    format!("{}…", kept)
+}
+
+/// What the month view needs besides the selected day.
+pub struct MonthData<'a> {
+    pub todos: &'a [Todo],
+    pub days_off: &'a [DaysOff],
+    pub week_start: Weekday,
+    pub today: NaiveDate,
+    pub zone: Zone,
+}
+
+pub fn render_month<B: Backend>(
+    rect: &mut Frame<B>,
+    selected: NaiveDate,
+    data: &MonthData,
+    chrome: Chrome,
+    area: Rect,
+) {
+    let block = chrome.block().title(format!(
+        "{} (arrows: day, h/l: month, Enter: day's todos, Esc: close)",
+        selected.format("%B %Y")
+    ));
+    let inner = block.inner(area);
+    rect.render_widget(block, area);
+
+    let weeks = weeks(selected, data.week_start);
+    let due = due_by_day(data.todos, data.zone);
+    let size = match layout(inner.width, inner.height, weeks.len()) {
+        MonthLayout::Grid(size) => size,
+        MonthLayout::Counts => {
+            render_counts(rect, selected, &weeks, &due, data, inner);
+            return;
+        }
+    };
+    let cursor = cursor::current();
+
+    let names: String = (0..7)
+        .map(|i| {
+            let weekday = (0..i).fold(data.week_start, |day, _| day.succ());
+            format!("{:<1$}", weekday.to_string(), size.width as usize)
+        })
+        .collect();
+    rect.render_widget(
+        Paragraph::new(Span::styled(
+            names,
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Rect { height: 1, ..inner },
+    );
+
+    for (row, week) in weeks.iter().enumerate() {
+        for (column, day) in week.iter().enumerate() {
+            let day = match day {
+                Some(day) => *day,
+                None => continue,
+            };
+            let cell = Rect {
+                x: inner.x + column as u16 * size.width,
+                y: inner.y + 1 + row as u16 * size.height,
+                width: size.width,
+                height: size.height,
+            };
+            let todos = due.get(&day).map(Vec::as_slice).unwrap_or_default();
+            let (marker, style) = cursor.row(day == selected, day_style(day, todos.len(), data));
+            let shown = size.titles.min(todos.len());
+            let count = match (todos.len(), shown) {
+                (0, _) => String::new(),
+                (all, 0) => format!(" ({})", all),
+                (all, shown) if all > shown => format!(" +{}", all - shown),
+                _ => String::new(),
+            };
+            let mut lines = vec![Spans::from(vec![Span::styled(
+                format!("{}{:>2}{}", marker, day.day(), count),
+                style,
+            )])];
+            for todo in &todos[..shown] {
+                lines.push(Spans::from(vec![Span::styled(
+                    cut(&todo.title, size.width.saturating_sub(1) as usize),
+                    Style::default().fg(Color::Gray),
+                )]));
+            }
+            rect.render_widget(Paragraph::new(lines), cell);
+        }
+    }
+}
+
+/// Days off shaded, past days with todos still due in red and today in
+/// bold yellow.
+fn day_style(day: NaiveDate, due: usize, data: &MonthData) -> Style {
+    let mut style = Style::default();
+    if calendar::day_off(data.days_off, day).is_some() {
+        style = style.bg(Color::DarkGray);
+    }
+    if day < data.today && due > 0 {
+        style = style.fg(Color::Red);
+    }
+    if day == data.today {
+        style = style.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    }
+    style
+}
+
+/// The month as `MonthLayout::Counts`, e.g. "1 2 3:2 4", wrapped.
+fn render_counts<B: Backend>(
+    rect: &mut Frame<B>,
+    selected: NaiveDate,
+    weeks: &[[Option<NaiveDate>; 7]],
+    due: &BTreeMap<NaiveDate, Vec<&Todo>>,
+    data: &MonthData,
+    area: Rect,
+) {
+    let cursor = cursor::current();
+    let mut spans = vec![];
+    for day in weeks.iter().flatten().flatten() {
+        let todos = due.get(day).map_or(0, Vec::len);
+        let (marker, style) = cursor.row(*day == selected, day_style(*day, todos, data));
+        let text = match todos {
+            0 => format!("{}{}", marker, day.day()),
+            todos => format!("{}{}:{}", marker, day.day(), todos),
+        };
+        spans.push(Span::styled(text, style));
+        spans.push(Span::raw(" "));
+    }
+    rect.render_widget(
+        Paragraph::new(Spans::from(spans)).wrap(Wrap { trim: true }),
+        area,
+    );
+}
+
+pub enum DayAction {
+    Continue,
+    Close,
+    Open(usize),
+}
+
+/// The popup Enter opens on a day of the month view, listing every todo
+/// due that day to jump to.
+pub struct DayDetail {
+    pub day: NaiveDate,
+    todos: Vec<(usize, String)>,
+    selected: usize,
+}
+
+impl DayDetail {
+    pub fn new(day: NaiveDate, todos: &[Todo], zone: Zone) -> DayDetail {
+        let todos = due_by_day(todos, zone)
+            .remove(&day)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|todo| (todo.id, todo.title.clone()))
+            .collect();
+        DayDetail {
+            day,
+            todos,
+            selected: 0,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> DayAction {
+        match key.code {
+            KeyCode::Esc => return DayAction::Close,
+            KeyCode::Enter => {
+                if let Some((id, _)) = self.todos.get(self.selected) {
+                    return DayAction::Open(*id);
+                }
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.selected = (self.selected + 1).min(self.todos.len().saturating_sub(1))
+            }
+            KeyCode::Char('k') | KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            _ => {}
+        }
+        DayAction::Continue
+    }
+}
+
+pub fn render_day_detail<B: Backend>(rect: &mut Frame<B>, detail: &DayDetail, area: Rect) {
+    let popup = centered_rect(60, 50, area);
+    let rows = popup.height.saturating_sub(2) as usize;
+    let offset = (detail.selected + 1).saturating_sub(rows);
+    let cursor = cursor::current();
+    let mut lines: Vec<Spans> = detail
+        .todos
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(rows)
+        .map(|(row, (id, title))| {
+            let (marker, style) = cursor.row(row == detail.selected, Style::default());
+            Spans::from(vec![Span::styled(
+                format!("{}#{} {}", marker, id, title),
+                style,
+            )])
+        })
+        .collect();
+    if lines.is_empty() {
+        lines.push(Spans::from(vec![Span::styled(
+            "nothing due",
+            Style::default().fg(Color::DarkGray),
+        )]));
+    }
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(format!(
+                "Due {} (Enter: go to todo, Esc: close)",
+                detail.day.format("%a %Y-%m-%d")
+            ))
+            .border_type(BorderType::Plain),
+    );
+    rect.render_widget(Clear, popup);
+    rect.render_widget(paragraph, popup);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_share_the_area_and_list_what_fits() {
+        // 6 weeks of 4 lines: the day number and two titles
+        assert_eq!(
+            cell_size(70, 24, 6),
+            CellSize {
+                width: 10,
+                height: 4,
+                titles: 2
+            }
+        );
+        // two lines a week leave room for one title
+        assert_eq!(cell_size(70, 10, 5).titles, 1);
+        // narrow cells only count
+        assert_eq!(cell_size(49, 24, 6).titles, 0);
+        // a line a week is the day number alone
+        assert_eq!(cell_size(70, 6, 6).titles, 0);
+    }
+
+    #[test]
+    fn a_month_that_does_not_fit_falls_back_to_counts() {
+        // the weekday names take the first line
+        assert_eq!(
+            layout(70, 7, 6),
+            MonthLayout::Grid(CellSize {
+                width: 10,
+                height: 1,
+                titles: 0
+            })
+        );
+        assert_eq!(layout(70, 6, 6), MonthLayout::Counts);
+        assert_eq!(layout(70, 0, 6), MonthLayout::Counts);
+        assert_eq!(layout(21, 25, 5), MonthLayout::Grid(cell_size(21, 24, 5)));
+        assert_eq!(layout(20, 25, 5), MonthLayout::Counts);
+    }
+
+    #[test]
+    fn weeks_cover_the_month_from_the_week_start() {
+        let june = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        // June 2024 starts on a Saturday and ends on a Sunday
+        let monday = weeks(june, Weekday::Mon);
+        assert_eq!(monday.len(), 5);
+        assert_eq!(monday[0][5], Some(june));
+        assert_eq!(monday[0][4], None);
+        assert_eq!(monday[4][6], NaiveDate::from_ymd_opt(2024, 6, 30));
+        let sunday = weeks(june, Weekday::Sun);
+        assert_eq!(sunday.len(), 6);
+        assert_eq!(sunday[0][6], Some(june));
+        assert_eq!(sunday[5][0], NaiveDate::from_ymd_opt(2024, 6, 30));
+        assert_eq!(sunday[5][1], None);
+    }
+}
@@ -0,0 +1,297 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::timer;
+
+/// How long alerts of one kind wait for others to arrive before going out
+/// together.
+const COALESCE_WINDOW: i64 = 5;
+/// Most notifications delivered per minute; the rest wait their turn.
+const PER_MINUTE: usize = 3;
+/// Seconds an alert identical to one queued or delivered is dropped for.
+const DUPLICATE_WINDOW: i64 = 60;
+
+/// Something to tell the user about.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alert {
+    /// Heading for a lone alert of this kind, e.g. "Todo due".
+    pub title: &'static str,
+    /// What several of them add up to, e.g. "todos became due".
+    pub plural: &'static str,
+    pub text: String,
+    /// Advice only worth the status bar's room, e.g. a command to run.
+    pub hint: &'static str,
+}
+
+/// One or more alerts of a kind, as delivered.
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    pub hint: &'static str,
+}
+
+/// Somewhere notifications go: the bell, the desktop, the status bar.
+pub trait Sink {
+    fn deliver(&mut self, notification: &Notification);
+}
+
+pub struct Bell;
+
+impl Sink for Bell {
+    fn deliver(&mut self, _: &Notification) {
+        print!("\x07");
+        let _ = io::stdout().flush();
+    }
+}
+
+pub struct Desktop;
+
+impl Sink for Desktop {
+    fn deliver(&mut self, notification: &Notification) {
+        timer::send_notification(&notification.title, &notification.body);
+    }
+}
+
+/// Keeps the latest notification for the app to show on its status line.
+pub struct StatusBar(pub Rc<RefCell<Option<String>>>);
+
+impl Sink for StatusBar {
+    fn deliver(&mut self, notification: &Notification) {
+        *self.0.borrow_mut() = Some(if notification.hint.is_empty() {
+            notification.body.clone()
+        } else {
+            format!("{} ({})", notification.body, notification.hint)
+        });
+    }
+}
+
+/// Queues alerts and hands them to every sink: alerts of one kind that
+/// arrive close together become one notification, exact duplicates are
+/// dropped and at most `PER_MINUTE` go out in any minute.
+#[derive(Default)]
+pub struct Dispatcher {
+    sinks: Vec<Box<dyn Sink>>,
+    queue: Vec<(DateTime<Utc>, Alert)>,
+    /// Alerts handed out, for the duplicate check.
+    recent: VecDeque<(DateTime<Utc>, Alert)>,
+    /// When each notification in the last minute went out.
+    sent: VecDeque<DateTime<Utc>>,
+}
+
+impl Dispatcher {
+    pub fn register(&mut self, sink: Box<dyn Sink>) {
+        self.sinks.push(sink);
+    }
+
+    pub fn push(&mut self, alert: Alert, now: DateTime<Utc>) {
+        let duplicate = self
+            .queue
+            .iter()
+            .chain(self.recent.iter())
+            .any(|(_, queued)| *queued == alert);
+        if !duplicate {
+            self.queue.push((now, alert));
+        }
+    }
+
+    /// Whether alerts wait to go out.
+    pub fn has_queued(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    /// Delivers what is due by `now` and returns it.
+    pub fn flush(&mut self, now: DateTime<Utc>) -> Vec<Notification> {
+        while self
+            .recent
+            .front()
+            .is_some_and(|(at, _)| now - *at >= Duration::seconds(DUPLICATE_WINDOW))
+        {
+            self.recent.pop_front();
+        }
+        while self
+            .sent
+            .front()
+            .is_some_and(|at| now - *at >= Duration::minutes(1))
+        {
+            self.sent.pop_front();
+        }
+
+        let mut delivered = vec![];
+        while self.sent.len() < PER_MINUTE {
+            // the kind that has been waiting longest, once its window closed
+            let first = match self.queue.first() {
+                Some((at, _)) if now - *at >= Duration::seconds(COALESCE_WINDOW) => {
+                    self.queue[0].1.title
+                }
+                _ => break,
+            };
+            let (group, rest): (Vec<_>, Vec<_>) = self
+                .queue
+                .drain(..)
+                .partition(|(_, alert)| alert.title == first);
+            self.queue = rest;
+            let notification = combine(&group.iter().map(|(_, a)| a).collect::<Vec<_>>());
+            for sink in &mut self.sinks {
+                sink.deliver(&notification);
+            }
+            self.sent.push_back(now);
+            self.recent.extend(group.into_iter().map(|(_, a)| (now, a)));
+            delivered.push(notification);
+        }
+        delivered
+    }
+}
+
+fn combine(alerts: &[&Alert]) -> Notification {
+    match alerts {
+        [alert] => Notification {
+            title: alert.title.to_string(),
+            body: alert.text.clone(),
+            hint: alert.hint,
+        },
+        _ => Notification {
+            title: alerts[0].title.to_string(),
+            body: format!(
+                "{} {}: {}",
+                alerts.len(),
+                alerts[0].plural,
+                alerts
+                    .iter()
+                    .map(|a| a.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
+            hint: alerts[0].hint,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap() + Duration::seconds(seconds)
+    }
+
+    fn due(text: &str) -> Alert {
+        Alert {
+            title: "Todo due",
+            plural: "todos became due",
+            text: text.to_string(),
+            hint: "",
+        }
+    }
+
+    fn timer(text: &str) -> Alert {
+        Alert {
+            title: "Timer",
+            plural: "timers ran long",
+            text: text.to_string(),
+            hint: "press t",
+        }
+    }
+
+    fn bodies(notifications: &[Notification]) -> Vec<&str> {
+        notifications.iter().map(|n| n.body.as_str()).collect()
+    }
+
+    #[test]
+    fn alerts_of_a_kind_within_the_window_go_out_as_one() {
+        let mut dispatcher = Dispatcher::default();
+        dispatcher.push(due("#1 Pay rent"), at(0));
+        dispatcher.push(due("#2 Call the bank"), at(2));
+        dispatcher.push(due("#3 Water the plants"), at(4));
+        assert!(dispatcher.flush(at(COALESCE_WINDOW - 1)).is_empty());
+
+        let sent = dispatcher.flush(at(COALESCE_WINDOW));
+        assert_eq!(
+            bodies(&sent),
+            ["3 todos became due: #1 Pay rent; #2 Call the bank; #3 Water the plants"]
+        );
+        assert_eq!(sent[0].title, "Todo due");
+        assert!(!dispatcher.has_queued());
+    }
+
+    #[test]
+    fn a_lone_alert_keeps_its_own_text_and_hint() {
+        let mut dispatcher = Dispatcher::default();
+        dispatcher.push(timer("Report ran past 2h"), at(0));
+        let sent = dispatcher.flush(at(COALESCE_WINDOW));
+        assert_eq!(bodies(&sent), ["Report ran past 2h"]);
+        assert_eq!(sent[0].hint, "press t");
+    }
+
+    #[test]
+    fn kinds_go_out_separately_oldest_first() {
+        let mut dispatcher = Dispatcher::default();
+        dispatcher.push(timer("Report ran past 2h"), at(0));
+        dispatcher.push(due("#1 Pay rent"), at(1));
+        dispatcher.push(timer("Review ran past 1h"), at(2));
+        let sent = dispatcher.flush(at(10));
+        assert_eq!(
+            bodies(&sent),
+            [
+                "2 timers ran long: Report ran past 2h; Review ran past 1h",
+                "#1 Pay rent"
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicates_are_dropped_queued_or_recently_sent() {
+        let mut dispatcher = Dispatcher::default();
+        dispatcher.push(due("#1 Pay rent"), at(0));
+        dispatcher.push(due("#1 Pay rent"), at(1));
+        assert_eq!(bodies(&dispatcher.flush(at(10))), ["#1 Pay rent"]);
+
+        dispatcher.push(due("#1 Pay rent"), at(20));
+        assert!(!dispatcher.has_queued());
+
+        // once the duplicate window is over it may go out again
+        dispatcher.flush(at(10 + DUPLICATE_WINDOW));
+        dispatcher.push(due("#1 Pay rent"), at(10 + DUPLICATE_WINDOW));
+        assert!(dispatcher.has_queued());
+    }
+
+    #[test]
+    fn at_most_the_cap_goes_out_per_minute_and_the_rest_waits() {
+        assert_eq!(PER_MINUTE, 3);
+        let mut dispatcher = Dispatcher::default();
+        let titles: [&'static str; 5] = ["a", "b", "c", "d", "e"];
+        for (i, title) in titles.into_iter().enumerate() {
+            dispatcher.push(
+                Alert {
+                    title,
+                    plural: "alerts",
+                    text: title.to_string(),
+                    hint: "",
+                },
+                at(i as i64),
+            );
+        }
+        assert_eq!(bodies(&dispatcher.flush(at(10))), ["a", "b", "c"]);
+        assert!(dispatcher.flush(at(69)).is_empty());
+        assert_eq!(bodies(&dispatcher.flush(at(70))), ["d", "e"]);
+        assert!(!dispatcher.has_queued());
+    }
+
+    #[test]
+    fn every_sink_gets_each_notification() {
+        let status = Rc::new(RefCell::new(None));
+        let other = Rc::new(RefCell::new(None));
+        let mut dispatcher = Dispatcher::default();
+        dispatcher.register(Box::new(StatusBar(status.clone())));
+        dispatcher.register(Box::new(StatusBar(other.clone())));
+        dispatcher.push(timer("Report ran past 2h"), at(0));
+        dispatcher.flush(at(COALESCE_WINDOW));
+        let shown = Some("Report ran past 2h (press t)".to_string());
+        assert_eq!(*status.borrow(), shown);
+        assert_eq!(*other.borrow(), shown);
+    }
+}
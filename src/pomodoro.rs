@@ -0,0 +1,292 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use tui::{
+    style::{Color, Style},
+    text::{Span, Spans},
+};
+
+use crate::scroll::Section;
+use crate::timetracking::week_bounds;
+use crate::zone::{self, Zone};
+use crate::{write_atomic, Error};
+
+const HISTORY_PATH: &str = "./data/pomodoros.json";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    /// Ran to the end.
+    Completed,
+    /// Stopped early and recorded.
+    Stopped,
+    /// Stopped early and thrown away.
+    Abandoned,
+}
+
+/// One run of the `timer` command, however it ended.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Pomodoro {
+    pub start: DateTime<Utc>,
+    pub planned_secs: i64,
+    pub outcome: Outcome,
+    /// Times Ctrl+C was pressed during the run.
+    pub interruptions: u32,
+}
+
+pub fn read_history() -> Result<Vec<Pomodoro>, Error> {
+    match fs::read_to_string(HISTORY_PATH) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub fn append(pomodoro: Pomodoro) -> Result<(), Error> {
+    let mut history = read_history()?;
+    history.push(pomodoro);
+    write_atomic(HISTORY_PATH, &serde_json::to_string_pretty(&history)?)
+}
+
+pub struct Stats {
+    pub today: usize,
+    pub this_week: usize,
+    /// The local day with the most completions, the latest on a tie.
+    pub best_day: Option<(NaiveDate, usize)>,
+    pub streak: usize,
+    /// Interruptions per run, abandoned runs included.
+    pub interruptions: f64,
+}
+
+/// Completed pomodoros per start day in `zone`.
+fn completed_per_day(history: &[Pomodoro], zone: Zone) -> BTreeMap<NaiveDate, usize> {
+    let mut days = BTreeMap::new();
+    for pomodoro in history.iter().filter(|p| p.outcome == Outcome::Completed) {
+        *days
+            .entry(pomodoro.start.with_timezone(&zone).date_naive())
+            .or_insert(0) += 1;
+    }
+    days
+}
+
+/// Consecutive local days with a completed pomodoro, ending today, or
+/// yesterday while today has none yet.
+pub fn streak(days: &BTreeMap<NaiveDate, usize>, today: NaiveDate) -> usize {
+    let mut day = if days.contains_key(&today) {
+        today
+    } else {
+        today - Duration::days(1)
+    };
+    let mut streak = 0;
+    while days.contains_key(&day) {
+        streak += 1;
+        day -= Duration::days(1);
+    }
+    streak
+}
+
+pub fn stats(history: &[Pomodoro], week_start: Weekday, now: DateTime<Utc>) -> Stats {
+    stats_in(history, week_start, now, zone::current())
+}
+
+/// `stats` with the days reckoned in `zone`.
+fn stats_in(history: &[Pomodoro], week_start: Weekday, now: DateTime<Utc>, zone: Zone) -> Stats {
+    let local = now.with_timezone(&zone);
+    let today = local.date_naive();
+    let days = completed_per_day(history, zone);
+    let (from, to) = week_bounds(local, week_start);
+    let this_week = history
+        .iter()
+        .filter(|p| p.outcome == Outcome::Completed && from <= p.start && p.start < to)
+        .count();
+    let interruptions = if history.is_empty() {
+        0.0
+    } else {
+        history.iter().map(|p| p.interruptions as f64).sum::<f64>() / history.len() as f64
+    };
+    Stats {
+        today: days.get(&today).cloned().unwrap_or(0),
+        this_week,
+        best_day: days
+            .iter()
+            .max_by_key(|(day, count)| (**count, **day))
+            .map(|(day, count)| (*day, *count)),
+        streak: streak(&days, today),
+        interruptions,
+    }
+}
+
+fn days(count: usize) -> String {
+    if count == 1 {
+        "1 day".to_string()
+    } else {
+        format!("{} days", count)
+    }
+}
+
+/// The one line version for the Home dashboard.
+pub fn summary(stats: &Stats) -> String {
+    format!(
+        "{} completed today · {} this week · {} streak",
+        stats.today,
+        stats.this_week,
+        days(stats.streak)
+    )
+}
+
+/// The Pomodoros section of the Stats view.
+pub fn section<'a>(history: &[Pomodoro], stats: &Stats) -> Section<'a> {
+    let line = |label: &str, value: String| {
+        Spans::from(vec![
+            Span::styled(format!("{:<24}", label), Style::default().fg(Color::Gray)),
+            Span::raw(value),
+        ])
+    };
+    let lines = if history.is_empty() {
+        vec![Spans::from(vec![Span::styled(
+            "no pomodoros yet, start one with `timer 25m`",
+            Style::default().fg(Color::DarkGray),
+        )])]
+    } else {
+        vec![
+            line("Completed today", stats.today.to_string()),
+            line("Completed this week", stats.this_week.to_string()),
+            line(
+                "Best day",
+                match stats.best_day {
+                    Some((day, count)) => format!("{} ({})", day.format("%a %Y-%m-%d"), count),
+                    None => "-".to_string(),
+                },
+            ),
+            line("Daily streak", days(stats.streak)),
+            line(
+                "Interruptions per run",
+                format!("{:.1}", stats.interruptions),
+            ),
+        ]
+    };
+    Section {
+        title: "Pomodoros".to_string(),
+        lines,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(start: DateTime<Utc>, outcome: Outcome, interruptions: u32) -> Pomodoro {
+        Pomodoro {
+            start,
+            planned_secs: 25 * 60,
+            outcome,
+            interruptions,
+        }
+    }
+
+    fn utc(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, day, hour, 0, 0).unwrap()
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, day).unwrap()
+    }
+
+    fn berlin() -> Zone {
+        Zone::parse("Europe/Berlin").unwrap()
+    }
+
+    fn days(list: &[u32]) -> BTreeMap<NaiveDate, usize> {
+        list.iter().map(|day| (date(*day), 1)).collect()
+    }
+
+    #[test]
+    fn a_gap_ends_the_streak() {
+        assert_eq!(streak(&days(&[6, 7, 8]), date(8)), 3);
+        assert_eq!(streak(&days(&[4, 6, 7, 8]), date(8)), 3);
+        assert_eq!(streak(&days(&[6, 7]), date(9)), 0);
+        assert_eq!(streak(&BTreeMap::new(), date(9)), 0);
+    }
+
+    #[test]
+    fn today_without_a_pomodoro_yet_keeps_yesterdays_streak() {
+        assert_eq!(streak(&days(&[6, 7]), date(8)), 2);
+        assert_eq!(streak(&days(&[6, 7, 8]), date(8)), 3);
+    }
+
+    #[test]
+    fn days_are_the_local_ones() {
+        // 23:30 UTC is already the next day in Berlin, 00:30 in May
+        let late = Utc.with_ymd_and_hms(2024, 5, 7, 22, 30, 0).unwrap();
+        let history = [run(late, Outcome::Completed, 0)];
+        assert_eq!(
+            completed_per_day(&history, Zone::Named(chrono_tz::UTC)),
+            days(&[7])
+        );
+        assert_eq!(completed_per_day(&history, berlin()), days(&[8]));
+
+        // after the switch to summer time the same UTC hour falls a day later
+        let history: Vec<Pomodoro> = [30, 31]
+            .iter()
+            .map(|day| {
+                let start = Utc.with_ymd_and_hms(2024, 3, *day, 22, 30, 0).unwrap();
+                run(start, Outcome::Completed, 0)
+            })
+            .collect();
+        let now = Utc.with_ymd_and_hms(2024, 4, 1, 8, 0, 0).unwrap();
+        let stats = stats_in(&history, Weekday::Mon, now, berlin());
+        // 23:30 on the 30th and 00:30 on April 1st leave out the 31st
+        assert_eq!(stats.streak, 1);
+        assert_eq!(stats.today, 1);
+    }
+
+    #[test]
+    fn only_completed_runs_count_but_every_run_has_interruptions() {
+        let history = [
+            run(utc(6, 9), Outcome::Completed, 1),
+            run(utc(6, 10), Outcome::Abandoned, 3),
+            run(utc(7, 9), Outcome::Completed, 0),
+            run(utc(7, 10), Outcome::Completed, 0),
+            run(utc(8, 9), Outcome::Stopped, 2),
+            run(utc(8, 10), Outcome::Completed, 0),
+        ];
+        // Wednesday the 8th, the week from Monday the 6th
+        let stats = stats_in(
+            &history,
+            Weekday::Mon,
+            utc(8, 12),
+            Zone::Named(chrono_tz::UTC),
+        );
+        assert_eq!(stats.today, 1);
+        assert_eq!(stats.this_week, 4);
+        assert_eq!(stats.best_day, Some((date(7), 2)));
+        assert_eq!(stats.streak, 3);
+        assert!((stats.interruptions - 1.0).abs() < 1e-9);
+        assert_eq!(
+            summary(&stats),
+            "1 completed today · 4 this week · 3 days streak"
+        );
+    }
+
+    #[test]
+    fn the_best_day_is_the_latest_on_a_tie_and_no_history_reads_zero() {
+        let history = [
+            run(utc(6, 9), Outcome::Completed, 0),
+            run(utc(7, 9), Outcome::Completed, 0),
+        ];
+        let stats = stats_in(
+            &history,
+            Weekday::Mon,
+            utc(9, 9),
+            Zone::Named(chrono_tz::UTC),
+        );
+        assert_eq!(stats.best_day, Some((date(7), 1)));
+        assert_eq!(stats.streak, 0);
+
+        let empty = stats_in(&[], Weekday::Mon, utc(9, 9), Zone::Named(chrono_tz::UTC));
+        assert_eq!((empty.today, empty.this_week, empty.best_day), (0, 0, None));
+        assert_eq!(empty.interruptions, 0.0);
+    }
+}
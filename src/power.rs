@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+/// The tick while anything on screen counts by itself.
+pub const FAST_TICK: Duration = Duration::from_millis(200);
+
+/// What on screen changes without input, each of which needs the fast
+/// tick to look right.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Activity {
+    /// A timer or a break, whose clock counts up.
+    pub running_entry: bool,
+    /// A named timer on the Timers tab, whose elapsed time counts up.
+    pub running_timer: bool,
+    /// A toast, whose countdown runs.
+    pub toast: bool,
+    /// A highlight waiting to fade.
+    pub highlight: bool,
+    /// An alert queued for the notifier.
+    pub pending_alert: bool,
+}
+
+impl Activity {
+    pub fn is_idle(&self) -> bool {
+        !(self.running_entry
+            || self.running_timer
+            || self.toast
+            || self.highlight
+            || self.pending_alert)
+    }
+}
+
+/// The fast tick while anything is active, else `idle_tick`, which is
+/// never faster than the fast tick.
+pub fn tick_rate(activity: Activity, idle_tick: Duration) -> Duration {
+    if activity.is_idle() {
+        idle_tick.max(FAST_TICK)
+    } else {
+        FAST_TICK
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDLE: Duration = Duration::from_secs(2);
+
+    /// Every combination of the five kinds of activity.
+    fn every_activity() -> impl Iterator<Item = Activity> {
+        (0..32u8).map(|bits| Activity {
+            running_entry: bits & 1 != 0,
+            running_timer: bits & 2 != 0,
+            toast: bits & 4 != 0,
+            highlight: bits & 8 != 0,
+            pending_alert: bits & 16 != 0,
+        })
+    }
+
+    #[test]
+    fn anything_active_keeps_the_fast_tick() {
+        for activity in every_activity() {
+            let expected = if activity == Activity::default() {
+                IDLE
+            } else {
+                FAST_TICK
+            };
+            assert_eq!(tick_rate(activity, IDLE), expected, "{:?}", activity);
+            assert_eq!(activity.is_idle(), activity == Activity::default());
+        }
+    }
+
+    #[test]
+    fn the_idle_tick_is_never_faster_than_the_fast_one() {
+        let idle = Activity::default();
+        assert_eq!(tick_rate(idle, Duration::from_millis(50)), FAST_TICK);
+        assert_eq!(tick_rate(idle, Duration::ZERO), FAST_TICK);
+        assert_eq!(
+            tick_rate(idle, Duration::from_secs(30)),
+            Duration::from_secs(30)
+        );
+    }
+}
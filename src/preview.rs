@@ -0,0 +1,308 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use std::collections::{BTreeSet, HashMap};
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+
+use crate::cursor;
+use crate::undo::UndoStack;
+use crate::widgets::centered_rect;
+use crate::{update_db, Error, Todo};
+
+pub enum ChangeKind {
+    New,
+    /// The fields that differ.
+    Modified(Vec<String>),
+    /// Like `Modified`, but the todo here changed after the incoming one,
+    /// so applying loses those edits.
+    Conflict(Vec<String>),
+    Removed,
+}
+
+pub struct Change {
+    pub id: usize,
+    pub title: String,
+    pub kind: ChangeKind,
+}
+
+/// What an import would do to the DB. Importers build the todos they want
+/// the DB to hold and this works out the difference, so every one of them
+/// gets the same preview.
+pub struct ChangeSet {
+    /// Where the todos come from, e.g. the archive path.
+    pub source: String,
+    pub changes: Vec<Change>,
+    result: Vec<Todo>,
+}
+
+/// Field names whose values differ, `updated_at` aside since saving sets it.
+fn changed_fields(before: &Todo, after: &Todo) -> Vec<String> {
+    let object = |todo: &Todo| match serde_json::to_value(todo) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    let (before, after) = (object(before), object(after));
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    keys.into_iter()
+        .filter(|key| *key != "updated_at" && before.get(*key) != after.get(*key))
+        .cloned()
+        .collect()
+}
+
+impl ChangeSet {
+    /// The changes from `current` to `result`, by todo id.
+    pub fn new(source: impl Into<String>, current: &[Todo], result: Vec<Todo>) -> ChangeSet {
+        let existing: HashMap<usize, &Todo> = current.iter().map(|t| (t.id, t)).collect();
+        let mut changes = vec![];
+        for todo in &result {
+            let kind = match existing.get(&todo.id) {
+                None => ChangeKind::New,
+                Some(old) => {
+                    let fields = changed_fields(old, todo);
+                    if fields.is_empty() {
+                        continue;
+                    } else if old.modified_at() > todo.modified_at() {
+                        ChangeKind::Conflict(fields)
+                    } else {
+                        ChangeKind::Modified(fields)
+                    }
+                }
+            };
+            changes.push(Change {
+                id: todo.id,
+                title: todo.title.clone(),
+                kind,
+            });
+        }
+        for old in current {
+            if !result.iter().any(|t| t.id == old.id) {
+                changes.push(Change {
+                    id: old.id,
+                    title: old.title.clone(),
+                    kind: ChangeKind::Removed,
+                });
+            }
+        }
+        changes.sort_by_key(|change| change.id);
+        ChangeSet {
+            source: source.into(),
+            changes,
+            result,
+        }
+    }
+
+    /// Makes the listed changes to the DB as it is now, as one undo step:
+    /// todos changed or added since the preview was built and not listed
+    /// are left alone.
+    pub fn apply(&self, undo: &mut UndoStack) -> Result<(), Error> {
+        let (before, newest) = update_db(|todos| {
+            let before = todos.clone();
+            self.apply_to(todos);
+            (before, todos.iter().map(|t| t.id).max().unwrap_or(0))
+        })?;
+        undo.push("import", before, newest);
+        Ok(())
+    }
+
+    fn apply_to(&self, todos: &mut Vec<Todo>) {
+        for change in &self.changes {
+            let incoming = self.result.iter().find(|t| t.id == change.id);
+            let slot = todos.iter().position(|t| t.id == change.id);
+            match (&change.kind, incoming, slot) {
+                (ChangeKind::Removed, _, Some(slot)) => {
+                    todos.remove(slot);
+                }
+                (ChangeKind::Removed, _, None) | (_, None, _) => {}
+                (_, Some(todo), Some(slot)) => todos[slot] = todo.clone(),
+                (_, Some(todo), None) => todos.push(todo.clone()),
+            }
+        }
+    }
+
+    fn summary(&self) -> String {
+        let count = |matches: fn(&ChangeKind) -> bool| {
+            self.changes.iter().filter(|c| matches(&c.kind)).count()
+        };
+        format!(
+            "{} new, {} modified, {} conflicting, {} removed",
+            count(|k| matches!(k, ChangeKind::New)),
+            count(|k| matches!(k, ChangeKind::Modified(_))),
+            count(|k| matches!(k, ChangeKind::Conflict(_))),
+            count(|k| matches!(k, ChangeKind::Removed)),
+        )
+    }
+}
+
+pub enum PreviewAction {
+    Continue,
+    Cancel,
+    Apply,
+}
+
+/// The popup listing a change set before it is applied.
+pub struct Preview {
+    pub changes: ChangeSet,
+    state: ListState,
+}
+
+impl Preview {
+    pub fn new(changes: ChangeSet) -> Preview {
+        let mut state = ListState::default();
+        if !changes.changes.is_empty() {
+            state.select(Some(0));
+        }
+        Preview { changes, state }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> PreviewAction {
+        let len = self.changes.changes.len();
+        match key.code {
+            KeyCode::Esc => return PreviewAction::Cancel,
+            KeyCode::Enter => return PreviewAction::Apply,
+            KeyCode::Char('j') | KeyCode::Down if len > 0 => self
+                .state
+                .select(self.state.selected().map(|i| (i + 1).min(len - 1))),
+            KeyCode::Char('k') | KeyCode::Up => self
+                .state
+                .select(self.state.selected().map(|i| i.saturating_sub(1))),
+            _ => {}
+        }
+        PreviewAction::Continue
+    }
+}
+
+fn change_item<'a>(change: &Change) -> ListItem<'a> {
+    let (sign, detail, color) = match &change.kind {
+        ChangeKind::New => ("+", String::new(), Color::Green),
+        ChangeKind::Modified(fields) => ("~", fields.join(", "), Color::Yellow),
+        ChangeKind::Conflict(fields) => (
+            "!",
+            format!("{}, overwrites newer edits", fields.join(", ")),
+            Color::Red,
+        ),
+        ChangeKind::Removed => ("-", "removed".to_string(), Color::Red),
+    };
+    let mut spans = vec![Span::styled(
+        format!("{} {:>4} {}", sign, change.id, change.title),
+        Style::default().fg(color),
+    )];
+    if !detail.is_empty() {
+        spans.push(Span::styled(
+            format!("  {}", detail),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    ListItem::new(Spans::from(spans))
+}
+
+pub fn render_preview<B: Backend>(rect: &mut Frame<B>, preview: &Preview, area: Rect) {
+    let popup = centered_rect(70, 60, area);
+    let mut items: Vec<ListItem> = preview.changes.changes.iter().map(change_item).collect();
+    if items.is_empty() {
+        items.push(ListItem::new(Spans::from(vec![Span::styled(
+            "nothing would change",
+            Style::default().fg(Color::DarkGray),
+        )])));
+    }
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!(
+                    "Import {} - {} (j/k: scroll, Enter: apply, Esc: abort)",
+                    preview.changes.source,
+                    preview.changes.summary()
+                ))
+                .border_type(BorderType::Plain),
+        )
+        .highlight_style(cursor::current().highlight())
+        .highlight_symbol(cursor::current().symbol());
+
+    rect.render_widget(Clear, popup);
+    rect.render_stateful_widget(list, popup, &mut preview.state.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(id: usize, title: &str) -> Todo {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "title": title,
+            "description": "",
+            "category": "work",
+            "status": "Todo",
+            "created_at": "2024-05-01T09:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    fn titles(todos: &[Todo]) -> Vec<(usize, &str)> {
+        todos.iter().map(|t| (t.id, t.title.as_str())).collect()
+    }
+
+    #[test]
+    fn lists_new_modified_and_removed() {
+        let current = [todo(1, "keep"), todo(2, "old"), todo(3, "gone")];
+        let changes = ChangeSet::new(
+            "a.zip",
+            &current,
+            vec![todo(1, "keep"), todo(2, "new"), todo(4, "added")],
+        );
+        assert_eq!(
+            changes.summary(),
+            "1 new, 1 modified, 0 conflicting, 1 removed"
+        );
+    }
+
+    #[test]
+    fn applies_onto_the_db_as_it_is_now() {
+        let current = [todo(1, "keep"), todo(2, "old"), todo(3, "gone")];
+        let changes = ChangeSet::new(
+            "a.zip",
+            &current,
+            vec![todo(1, "keep"), todo(2, "new"), todo(4, "added")],
+        );
+        // since the preview, 1 was edited and 5 added from elsewhere
+        let mut todos = vec![
+            todo(1, "edited"),
+            todo(2, "old"),
+            todo(3, "gone"),
+            todo(5, "later"),
+        ];
+        changes.apply_to(&mut todos);
+        assert_eq!(
+            titles(&todos),
+            [(1, "edited"), (2, "new"), (5, "later"), (4, "added")]
+        );
+    }
+
+    #[test]
+    fn undoing_an_import_drops_the_todos_it_added() {
+        let current = vec![todo(1, "a"), todo(2, "b"), todo(3, "c")];
+        let changes = ChangeSet::new(
+            "a.zip",
+            &current,
+            vec![todo(1, "a"), todo(2, "b"), todo(3, "c"), todo(4, "added")],
+        );
+        let mut todos = current.clone();
+        changes.apply_to(&mut todos);
+        let mut undo = UndoStack::default();
+        undo.push("import", current, 4);
+        // 5 came from elsewhere after the import and stays
+        todos.push(todo(5, "later"));
+
+        let restored = undo.pop().unwrap().restore(&todos).unwrap();
+        assert_eq!(
+            titles(&restored),
+            [(1, "a"), (2, "b"), (3, "c"), (5, "later")]
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use tui::{
+    style::{Color, Style},
+    text::Span,
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::history;
+use crate::subtasks;
+use crate::timetracking::TimeEntry;
+use crate::{Todo, TodoStatus};
+
+/// How far along a todo is, by its subtasks and by the time tracked on it
+/// against its estimate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    /// Subtasks done and in all, None without subtasks.
+    pub subtasks: Option<(usize, usize)>,
+    pub spent: Duration,
+    /// In hours, as `Todo::estimate`.
+    pub estimate: Option<f64>,
+}
+
+impl Progress {
+    pub fn of(todo: &Todo, todos: &[Todo], entries: &[TimeEntry], now: DateTime<Utc>) -> Progress {
+        let subtasks = subtasks::subtasks(todo.id, todos);
+        let done = subtasks
+            .iter()
+            .filter(|t| t.status() == TodoStatus::Done)
+            .count();
+        Progress {
+            subtasks: (!subtasks.is_empty()).then_some((done, subtasks.len())),
+            spent: history::time_spent(entries, todo.id, now),
+            estimate: todo.estimate,
+        }
+    }
+
+    /// Green while the subtasks keep up with the time used, yellow once
+    /// the time runs ahead of them, red once the estimate is used up with
+    /// subtasks still open. Gray with only one of the two to go by.
+    fn color(&self) -> Color {
+        let (subtasks, estimate) = match (self.subtasks, self.estimate) {
+            (Some(subtasks), Some(estimate)) if estimate > 0.0 => (subtasks, estimate),
+            _ => return Color::Gray,
+        };
+        let used = self.spent.num_minutes() as f64 / 60.0 / estimate;
+        let done = subtasks.0 as f64 / subtasks.1 as f64;
+        if used > 1.0 && done < 1.0 {
+            Color::Red
+        } else if used > done {
+            Color::Yellow
+        } else {
+            Color::Green
+        }
+    }
+}
+
+/// Hours and minutes as short as they go: "45m", "2h", "2h10".
+fn short(minutes: i64) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, 0) => format!("{}h", hours),
+        (hours, minutes) => format!("{}h{:02}", hours, minutes),
+    }
+}
+
+/// The readout after a board item's title, e.g. "[3/5 · 2h10/4h]", in at
+/// most `width` columns. Without room for both parts it keeps the
+/// subtasks, then the time; None when there is neither to show or no room.
+pub fn annotation<'a>(progress: &Progress, width: usize) -> Option<Span<'a>> {
+    let subtasks = progress
+        .subtasks
+        .map(|(done, all)| format!("{}/{}", done, all));
+    let time = progress.estimate.map(|hours| {
+        format!(
+            "{}/{}",
+            short(progress.spent.num_minutes()),
+            short((hours * 60.0).round() as i64)
+        )
+    });
+    let candidates = match (subtasks, time) {
+        (Some(subtasks), Some(time)) => vec![format!("{} · {}", subtasks, time), subtasks, time],
+        (Some(part), None) | (None, Some(part)) => vec![part],
+        (None, None) => vec![],
+    };
+    candidates
+        .into_iter()
+        .map(|part| format!("[{}]", part))
+        .find(|label| label.width() <= width)
+        .map(|label| Span::styled(label, Style::default().fg(progress.color())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetracking::push_running;
+
+    fn progress(subtasks: Option<(usize, usize)>, minutes: i64, estimate: Option<f64>) -> Progress {
+        Progress {
+            subtasks,
+            spent: Duration::minutes(minutes),
+            estimate,
+        }
+    }
+
+    fn label(progress: &Progress, width: usize) -> Option<String> {
+        annotation(progress, width).map(|span| span.content.into_owned())
+    }
+
+    #[test]
+    fn each_combination_of_subtasks_and_estimate_shows_what_it_has() {
+        let wide = 40;
+        let both = progress(Some((3, 5)), 130, Some(4.0));
+        assert_eq!(label(&both, wide).as_deref(), Some("[3/5 · 2h10/4h]"));
+        let subtasks = progress(Some((3, 5)), 130, None);
+        assert_eq!(label(&subtasks, wide).as_deref(), Some("[3/5]"));
+        let estimate = progress(None, 45, Some(1.5));
+        assert_eq!(label(&estimate, wide).as_deref(), Some("[45m/1h30]"));
+        assert_eq!(label(&progress(None, 130, None), wide), None);
+    }
+
+    #[test]
+    fn a_narrow_item_keeps_the_subtasks_then_the_time() {
+        let both = progress(Some((3, 5)), 130, Some(4.0));
+        assert_eq!(label(&both, 15).as_deref(), Some("[3/5 · 2h10/4h]"));
+        assert_eq!(label(&both, 14).as_deref(), Some("[3/5]"));
+        assert_eq!(label(&both, 5).as_deref(), Some("[3/5]"));
+        assert_eq!(label(&both, 4), None);
+
+        let estimate = progress(None, 0, Some(4.0));
+        assert_eq!(label(&estimate, 7).as_deref(), Some("[0m/4h]"));
+        assert_eq!(label(&estimate, 6), None);
+    }
+
+    #[test]
+    fn the_color_follows_the_time_against_the_subtasks() {
+        let color = |subtasks, minutes, estimate| {
+            annotation(&progress(subtasks, minutes, estimate), 40)
+                .unwrap()
+                .style
+                .fg
+        };
+        // half the subtasks in a quarter of the time
+        assert_eq!(color(Some((2, 4)), 60, Some(4.0)), Some(Color::Green));
+        // a quarter of the subtasks in half the time
+        assert_eq!(color(Some((1, 4)), 120, Some(4.0)), Some(Color::Yellow));
+        // over the estimate with subtasks still open
+        assert_eq!(color(Some((3, 4)), 300, Some(4.0)), Some(Color::Red));
+        // over the estimate with every subtask done
+        assert_eq!(color(Some((4, 4)), 300, Some(4.0)), Some(Color::Yellow));
+        assert_eq!(color(Some((1, 4)), 300, None), Some(Color::Gray));
+        assert_eq!(color(None, 300, Some(4.0)), Some(Color::Gray));
+        assert_eq!(color(Some((1, 4)), 300, Some(0.0)), Some(Color::Gray));
+    }
+
+    #[test]
+    fn times_are_as_short_as_they_go() {
+        assert_eq!(short(0), "0m");
+        assert_eq!(short(45), "45m");
+        assert_eq!(short(120), "2h");
+        assert_eq!(short(130), "2h10");
+        assert_eq!(short(605), "10h05");
+    }
+
+    #[test]
+    fn the_progress_of_a_todo_counts_its_subtasks_and_its_time() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 3, 12, 0, 0).unwrap();
+        let mut todos: Vec<Todo> = (1..=4)
+            .map(|id| {
+                let mut todo = Todo::new(format!("todo {}", id), "work".to_string(), now);
+                todo.id = id;
+                todo.parent = (id > 1).then_some(1);
+                todo
+            })
+            .collect();
+        todos[0].estimate = Some(2.0);
+        todos[1].set_status(TodoStatus::Done, now);
+
+        let mut entries = vec![];
+        push_running(
+            &mut entries,
+            "work",
+            now - Duration::hours(3),
+            None,
+            Some(1),
+            None,
+        );
+        entries[0].end = Some(now - Duration::hours(2));
+        // the running one counts up to now, another todo's doesn't count
+        push_running(
+            &mut entries,
+            "work",
+            now - Duration::minutes(10),
+            None,
+            Some(1),
+            None,
+        );
+        push_running(
+            &mut entries,
+            "work",
+            now - Duration::hours(1),
+            None,
+            Some(2),
+            None,
+        );
+
+        assert_eq!(
+            Progress::of(&todos[0], &todos, &entries, now),
+            progress(Some((1, 3)), 70, Some(2.0))
+        );
+        assert_eq!(
+            Progress::of(&todos[1], &todos, &entries, now),
+            progress(None, 60, None)
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use std::env;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use crate::add::{self, DEFAULT_CATEGORY};
+use crate::cli;
+use crate::dates::parse_due;
+use crate::suggest::CategoryIndex;
+use crate::{read_db, Priority, Todo};
+
+const USAGE: &str = "usage: quick [--workspace DIR] [TEXT]";
+
+/// Reads quick-add text: `@category`, `+tag`, `!`/`!!`/`!!!` or
+/// `!low`..`!urgent` for the priority, `due:YYYY-MM-DD` and `~2h` or
+/// `~30m` for the estimate. Everything else is the title.
+///
+/// ```
+/// use work_time_cli::{parse_quick_add, Priority};
+///
+/// let todo = parse_quick_add("Renew passport @errands +travel !! ~30m").unwrap();
+/// assert_eq!(todo.title, "Renew passport");
+/// assert_eq!(todo.category, "errands");
+/// assert_eq!(todo.tags, ["travel"]);
+/// assert_eq!(todo.priority, Some(Priority::High));
+/// assert_eq!(todo.estimate, Some(0.5));
+/// assert!(parse_quick_add("@errands").is_err());
+/// ```
+pub fn parse(line: &str) -> Result<Todo, String> {
+    let mut title = vec![];
+    let mut todo = add::new_todo(String::new(), String::new());
+    for word in line.split_whitespace() {
+        if let Some(category) = word.strip_prefix('@').filter(|c| !c.is_empty()) {
+            todo.category = category.to_string();
+        } else if let Some(tag) = word.strip_prefix('+').filter(|t| !t.is_empty()) {
+            if !todo.tags.iter().any(|t| t == tag) {
+                todo.tags.push(tag.to_string());
+            }
+        } else if let Some(priority) = word.strip_prefix('!').and_then(parse_priority) {
+            todo.priority = Some(priority);
+        } else if let Some(due) = word.strip_prefix("due:") {
+            todo.due = Some(
+                parse_due(due).ok_or_else(|| format!("invalid due '{}', use YYYY-MM-DD", due))?,
+            );
+        } else if let Some(estimate) = word.strip_prefix('~').and_then(parse_estimate) {
+            todo.estimate = Some(estimate);
+        } else {
+            title.push(word);
+        }
+    }
+    todo.title = title.join(" ");
+    if todo.title.is_empty() {
+        return Err("a quick-add needs a title".to_string());
+    }
+    Ok(todo)
+}
+
+/// The part after the first `!`.
+fn parse_priority(rest: &str) -> Option<Priority> {
+    match rest {
+        "" | "medium" => Some(Priority::Medium),
+        "!" | "high" => Some(Priority::High),
+        "!!" | "urgent" => Some(Priority::Urgent),
+        "low" => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+/// Hours from `2h`, `1.5h` or `30m`.
+fn parse_estimate(value: &str) -> Option<f64> {
+    let hours = if let Some(hours) = value.strip_suffix('h') {
+        hours.parse::<f64>().ok()?
+    } else {
+        value.strip_suffix('m')?.parse::<f64>().ok()? / 60.0
+    };
+    (hours > 0.0).then_some(hours)
+}
+
+/// Moves into the directory of `--workspace DIR` among the arguments of
+/// `quick`, taking both out of them. It runs before anything else reads
+/// ./data, the migration and the config included; the data files are
+/// relative, so a workspace is just a directory holding its own ./data.
+/// Returns the exit code to fail with.
+pub fn enter_workspace(args: &mut Vec<String>) -> Result<(), i32> {
+    let at = match args.iter().position(|arg| arg == "--workspace") {
+        Some(at) => at,
+        None => return Ok(()),
+    };
+    let dir = match args.get(at + 1) {
+        Some(dir) => dir.clone(),
+        None => return Err(cli::usage("--workspace needs a value")),
+    };
+    if let Err(err) = env::set_current_dir(&dir) {
+        return Err(cli::other(format!("can't use workspace {}: {}", dir, err)));
+    }
+    args.drain(at..at + 2);
+    Ok(())
+}
+
+/// Runs `quick [TEXT]`, once `enter_workspace` took `--workspace`: adds
+/// one todo from quick-add text, prompting for it on a terminal and
+/// otherwise reading one line of stdin, then prints its id. Returns the
+/// process exit code.
+pub fn run(args: &[String]) -> i32 {
+    if args.iter().any(|arg| arg.starts_with("--")) {
+        return cli::usage(USAGE);
+    }
+    let text: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let mut source = "cli";
+    let line = if text.is_empty() {
+        let stdin = io::stdin();
+        if stdin.is_terminal() {
+            eprint!("quick add> ");
+            let _ = io::stderr().flush();
+        } else {
+            source = "stdin";
+        }
+        let mut line = String::new();
+        if let Err(err) = stdin.lock().read_line(&mut line) {
+            return cli::other(format!("can't read the todo: {}", err));
+        }
+        line
+    } else {
+        text.join(" ")
+    };
+
+    let mut todo = match parse(&line) {
+        Ok(todo) => todo,
+        Err(err) => return cli::usage(err),
+    };
+    todo.source = Some(source.to_string());
+    if todo.category.is_empty() {
+        // only used for the category guess, the insert re-reads under the lock
+        todo.category = match read_db() {
+            Ok(todos) => CategoryIndex::build(&todos)
+                .suggest(&todo.title)
+                .map(|suggestion| suggestion.category)
+                .unwrap_or_else(|| DEFAULT_CATEGORY.to_string()),
+            Err(err) => return cli::fail(&err),
+        };
+    }
+    match add::insert(todo) {
+        Ok(id) => {
+            println!("{}", id);
+            0
+        }
+        Err(err) => cli::fail(&err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn no_workspace_leaves_the_arguments() {
+        let mut given = args(&["quick", "buy", "milk"]);
+        assert_eq!(enter_workspace(&mut given), Ok(()));
+        assert_eq!(given, args(&["quick", "buy", "milk"]));
+    }
+
+    #[test]
+    fn a_workspace_needs_a_directory_that_exists() {
+        assert!(enter_workspace(&mut args(&["quick", "--workspace"])).is_err());
+        let mut given = args(&["quick", "--workspace", "/nonexistent/quick-test", "x"]);
+        assert!(enter_workspace(&mut given).is_err());
+        assert_eq!(given.len(), 4);
+    }
+
+    #[test]
+    fn parses_quick_add_text() {
+        let todo = parse("call Bob @phone +work !! ~30m").unwrap();
+        assert_eq!(todo.title, "call Bob");
+        assert_eq!(todo.category, "phone");
+        assert_eq!(todo.tags, ["work"]);
+        assert_eq!(todo.priority, Some(Priority::High));
+        assert_eq!(todo.estimate, Some(0.5));
+        assert!(parse("@phone +work").is_err());
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn adds_exactly_one_todo_and_leaves_the_rest() {
+        let todo = |id: usize| {
+            serde_json::json!({
+                "id": id,
+                "title": format!("todo {}", id),
+                "description": "kept as is",
+                "category": "home",
+                "status": "Todo",
+                "created_at": "2024-05-01T09:00:00Z",
+                "tags": ["x"],
+            })
+        };
+        let todos = serde_json::json!([todo(1), todo(3)]);
+        crate::app::tests::with_app("quick", todos, |_| {
+            let before = serde_json::to_value(read_db().unwrap()).unwrap();
+            assert_eq!(run(&args(&["Renew", "passport", "@errands", "+travel"])), 0);
+
+            let after = read_db().unwrap();
+            assert_eq!(after.len(), 3);
+            assert_eq!(serde_json::to_value(&after[..2]).unwrap(), before);
+            let added = &after[2];
+            assert_eq!(added.id, 4);
+            assert_eq!(added.title, "Renew passport");
+            assert_eq!(added.category, "errands");
+            assert_eq!(added.tags, ["travel"]);
+            assert_eq!(added.source.as_deref(), Some("cli"));
+        });
+    }
+}
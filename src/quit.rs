@@ -0,0 +1,127 @@
+use chrono::prelude::*;
+
+use crate::timetracking::{update_time_entries, EntryKind, TimeEntry};
+use crate::Error;
+
+/// What would be left behind by quitting now: running timers and todo
+/// changes autosave is still holding back.
+pub struct QuitCheck {
+    /// The running work entries, ids and labels.
+    pub running: Vec<(usize, String)>,
+    pub unsaved: bool,
+}
+
+impl QuitCheck {
+    pub fn new(entries: &[TimeEntry], unsaved: bool) -> QuitCheck {
+        QuitCheck {
+            running: entries
+                .iter()
+                .filter(|e| e.end.is_none() && e.kind == EntryKind::Work)
+                .map(|e| (e.id, e.note.clone().unwrap_or_else(|| e.category.clone())))
+                .collect(),
+            unsaved,
+        }
+    }
+
+    pub fn is_clear(&self) -> bool {
+        self.running.is_empty() && !self.unsaved
+    }
+
+    /// The confirmation asked before quitting, naming what is left behind.
+    pub fn question(&self) -> String {
+        let mut concerns = vec![];
+        match self.running.as_slice() {
+            [] => {}
+            [(_, label)] => concerns.push(format!("timer '{}' is running", label)),
+            running => concerns.push(format!("{} timers are running", running.len())),
+        }
+        if self.unsaved {
+            concerns.push("changes are waiting to be saved".to_string());
+        }
+        let mut question = if concerns.is_empty() {
+            "Quit?".to_string()
+        } else {
+            format!("{}, quit anyway?", concerns.join(" and "))
+        };
+        if self.running.is_empty() {
+            question.push_str(" y: quit, Esc: cancel");
+        } else {
+            question.push_str(" y: quit, it keeps running, s: stop and record it, Esc: cancel");
+        }
+        question
+    }
+
+    /// Ends the running timers at `now`, recording them. Returns how many
+    /// were stopped.
+    pub fn stop_running(&self, now: DateTime<Utc>) -> Result<usize, Error> {
+        update_time_entries(|entries| {
+            let mut stopped = 0;
+            for entry in entries.iter_mut() {
+                if entry.end.is_none() && self.running.iter().any(|(id, _)| *id == entry.id) {
+                    entry.end = Some(now);
+                    stopped += 1;
+                }
+            }
+            stopped
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: usize, note: Option<&str>, end: bool, kind: &str) -> TimeEntry {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "category": "work",
+            "start": "2024-05-01T09:00:00Z",
+            "end": if end { Some("2024-05-01T10:00:00Z") } else { None },
+            "note": note,
+            "kind": kind,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn only_running_work_counts_named_by_its_note() {
+        let entries = [
+            entry(1, Some("deep work"), false, "work"),
+            entry(2, None, false, "work"),
+            entry(3, Some("done"), true, "work"),
+            entry(4, None, false, "break"),
+        ];
+        let check = QuitCheck::new(&entries, false);
+        assert_eq!(
+            check.running,
+            [(1, "deep work".to_string()), (2, "work".to_string())]
+        );
+        assert!(!check.is_clear());
+        assert!(QuitCheck::new(&entries[2..], false).is_clear());
+        assert!(!QuitCheck::new(&entries[2..], true).is_clear());
+    }
+
+    #[test]
+    fn the_question_names_what_is_left_behind() {
+        let one = [entry(1, Some("deep work"), false, "work")];
+        let two = [entry(1, None, false, "work"), entry(2, None, false, "work")];
+        assert_eq!(
+            QuitCheck::new(&[], false).question(),
+            "Quit? y: quit, Esc: cancel"
+        );
+        assert_eq!(
+            QuitCheck::new(&[], true).question(),
+            "changes are waiting to be saved, quit anyway? y: quit, Esc: cancel"
+        );
+        assert_eq!(
+            QuitCheck::new(&one, false).question(),
+            "timer 'deep work' is running, quit anyway? \
+             y: quit, it keeps running, s: stop and record it, Esc: cancel"
+        );
+        assert_eq!(
+            QuitCheck::new(&two, true).question(),
+            "2 timers are running and changes are waiting to be saved, quit anyway? \
+             y: quit, it keeps running, s: stop and record it, Esc: cancel"
+        );
+    }
+}
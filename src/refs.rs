@@ -0,0 +1,315 @@
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "tui")]
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+use crate::timetracking::TimeEntry;
+use crate::Todo;
+
+/// A piece of free text: plain text or a `#<id>` todo reference.
+#[derive(Debug, PartialEq)]
+pub enum Piece<'a> {
+    Text(&'a str),
+    Ref(usize),
+}
+
+/// Splits `text` on `#<id>` references. A reference must stand on its own:
+/// "(#42)" and "#42," are references, "abc#42" and "#42a" are not.
+pub fn parse(text: &str) -> Vec<Piece<'_>> {
+    let mut pieces = vec![];
+    let mut plain_from = 0;
+    let mut chars = text.char_indices().peekable();
+    let mut previous: Option<char> = None;
+    while let Some((at, c)) = chars.next() {
+        let standalone = previous.is_none_or(|p| !p.is_alphanumeric() && p != '#');
+        previous = Some(c);
+        if c != '#' || !standalone {
+            continue;
+        }
+        let digits_from = at + 1;
+        let mut digits_to = digits_from;
+        while let Some((i, d)) = chars.peek().cloned() {
+            if !d.is_ascii_digit() {
+                break;
+            }
+            digits_to = i + 1;
+            previous = Some(d);
+            chars.next();
+        }
+        let ends = chars.peek().is_none_or(|(_, n)| !n.is_alphanumeric());
+        match text[digits_from..digits_to].parse() {
+            Ok(id) if ends => {
+                if plain_from < at {
+                    pieces.push(Piece::Text(&text[plain_from..at]));
+                }
+                pieces.push(Piece::Ref(id));
+                plain_from = digits_to;
+            }
+            _ => {}
+        }
+    }
+    if plain_from < text.len() {
+        pieces.push(Piece::Text(&text[plain_from..]));
+    }
+    pieces
+}
+
+/// The todo `text` refers to, if it names exactly one existing todo.
+pub fn linked_todo(text: &str, ids: &HashSet<usize>) -> Option<usize> {
+    let mut found = parse(text).into_iter().filter_map(|piece| match piece {
+        Piece::Ref(id) if ids.contains(&id) => Some(id),
+        _ => None,
+    });
+    let first = found.next()?;
+    if found.all(|id| id == first) {
+        Some(first)
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "tui")]
+/// `text` with references to existing todos highlighted. Ids no todo has
+/// any more are struck through; when the text names several todos they all
+/// stay plain with a dim "?".
+pub fn spans<'a>(text: &str, ids: &HashSet<usize>, style: Style) -> Spans<'a> {
+    let pieces = parse(text);
+    let ambiguous = linked_todo(text, ids).is_none();
+    let mut spans = vec![];
+    for piece in pieces {
+        match piece {
+            Piece::Text(plain) => spans.push(Span::styled(plain.to_string(), style)),
+            Piece::Ref(id) if ids.contains(&id) && !ambiguous => spans.push(Span::styled(
+                format!("#{}", id),
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::UNDERLINED),
+            )),
+            Piece::Ref(id) if !ids.contains(&id) => spans.push(Span::styled(
+                format!("#{}", id),
+                Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::CROSSED_OUT),
+            )),
+            Piece::Ref(id) => {
+                spans.push(Span::styled(format!("#{}", id), style));
+                spans.push(Span::styled("?", Style::default().fg(Color::DarkGray)));
+            }
+        }
+    }
+    Spans::from(spans)
+}
+
+/// The todos `todo` refers to, in its description and in the notes of the
+/// time spent on it, in id order and without itself.
+pub fn references(todo: &Todo, entries: &[TimeEntry]) -> Vec<usize> {
+    let notes = entries
+        .iter()
+        .filter(|e| e.todo_id == Some(todo.id))
+        .filter_map(|e| e.note.as_deref());
+    let mut ids: Vec<usize> = std::iter::once(todo.description())
+        .chain(notes)
+        .flat_map(parse)
+        .filter_map(|piece| match piece {
+            Piece::Ref(id) if id != todo.id => Some(id),
+            _ => None,
+        })
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+/// Which todos refer to which, both ways, so a todo can list its
+/// backlinks. Built once on load, then kept current one todo at a time as
+/// descriptions and notes are edited.
+#[derive(Debug, Default)]
+pub struct RefIndex {
+    /// The backlinks: for each todo, the todos referring to it.
+    referenced_by: HashMap<usize, Vec<usize>>,
+    /// For each todo, the todos it refers to.
+    refers_to: HashMap<usize, Vec<usize>>,
+}
+
+impl RefIndex {
+    pub fn build(todos: &[Todo], entries: &[TimeEntry]) -> RefIndex {
+        let mut index = RefIndex::default();
+        for todo in todos {
+            index.update(todo.id, references(todo, entries));
+        }
+        index
+    }
+
+    /// Records that `id` now refers to `targets` and nothing else.
+    pub fn update(&mut self, id: usize, targets: Vec<usize>) {
+        for target in self.refers_to.remove(&id).unwrap_or_default() {
+            if let Some(sources) = self.referenced_by.get_mut(&target) {
+                sources.retain(|source| *source != id);
+                if sources.is_empty() {
+                    self.referenced_by.remove(&target);
+                }
+            }
+        }
+        for target in &targets {
+            let sources = self.referenced_by.entry(*target).or_default();
+            if let Err(at) = sources.binary_search(&id) {
+                sources.insert(at, id);
+            }
+        }
+        if !targets.is_empty() {
+            self.refers_to.insert(id, targets);
+        }
+    }
+
+    /// Forgets what a deleted todo referred to. References to it stay, and
+    /// show as dangling.
+    pub fn remove(&mut self, id: usize) {
+        self.update(id, vec![]);
+    }
+
+    /// The todos `id` refers to, in id order.
+    pub fn refers_to(&self, id: usize) -> &[usize] {
+        self.refers_to.get(&id).map_or(&[], Vec::as_slice)
+    }
+
+    /// The todos referring to `id`, in id order.
+    pub fn referenced_by(&self, id: usize) -> &[usize] {
+        self.referenced_by.get(&id).map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetracking::push_running;
+    use chrono::Utc;
+
+    fn refs(text: &str) -> Vec<usize> {
+        parse(text)
+            .into_iter()
+            .filter_map(|piece| match piece {
+                Piece::Ref(id) => Some(id),
+                Piece::Text(_) => None,
+            })
+            .collect()
+    }
+
+    fn todo(id: usize, description: &str) -> Todo {
+        let mut todo = Todo::new(format!("todo {}", id), "work".to_string(), Utc::now());
+        todo.id = id;
+        todo.set_description(description.to_string()).unwrap();
+        todo
+    }
+
+    #[test]
+    fn references_may_touch_punctuation() {
+        assert_eq!(refs("finished login fix (#42)"), [42]);
+        assert_eq!(refs("#1, #2; #3. #4!"), [1, 2, 3, 4]);
+        assert_eq!(refs("see \"#7\" and [#8]"), [7, 8]);
+        assert_eq!(refs("#5"), [5]);
+    }
+
+    #[test]
+    fn references_must_stand_on_their_own() {
+        assert_eq!(refs("abc#42"), Vec::<usize>::new());
+        assert_eq!(refs("#42a"), Vec::<usize>::new());
+        assert_eq!(refs("##42"), Vec::<usize>::new());
+        assert_eq!(refs("# 42 and #"), Vec::<usize>::new());
+        assert_eq!(refs("#99999999999999999999999"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn parsing_keeps_the_text_around_references() {
+        assert_eq!(
+            parse("é (#3) ü"),
+            [Piece::Text("é ("), Piece::Ref(3), Piece::Text(") ü")]
+        );
+        assert_eq!(parse(""), []);
+    }
+
+    #[test]
+    fn only_one_existing_todo_links() {
+        let ids: HashSet<usize> = [1, 2].into_iter().collect();
+        assert_eq!(linked_todo("about #1", &ids), Some(1));
+        assert_eq!(linked_todo("#1 again #1", &ids), Some(1));
+        assert_eq!(linked_todo("#1 or #2", &ids), None);
+        // ids no todo has don't count either way
+        assert_eq!(linked_todo("#1 not #9", &ids), Some(1));
+        assert_eq!(linked_todo("#9", &ids), None);
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn spans_mark_dangling_and_ambiguous_references() {
+        let ids: HashSet<usize> = [1, 2].into_iter().collect();
+        let text = |spans: Spans| -> Vec<String> {
+            spans.0.iter().map(|s| s.content.to_string()).collect()
+        };
+        let linked = spans("see #1", &ids, Style::default());
+        assert_eq!(text(linked.clone()), ["see ", "#1"]);
+        assert!(linked.0[1]
+            .style
+            .add_modifier
+            .contains(Modifier::UNDERLINED));
+
+        let gone = spans("#9", &ids, Style::default());
+        assert!(gone.0[0].style.add_modifier.contains(Modifier::CROSSED_OUT));
+
+        assert_eq!(
+            text(spans("#1 #2", &ids, Style::default())),
+            ["#1", "?", " ", "#2", "?"]
+        );
+    }
+
+    #[test]
+    fn references_come_from_the_description_and_notes() {
+        let todo = todo(1, "after #3 and #2, not #1");
+        let mut entries = vec![];
+        push_running(
+            &mut entries,
+            "work",
+            Utc::now(),
+            Some("with #5".into()),
+            Some(1),
+            None,
+        );
+        push_running(
+            &mut entries,
+            "work",
+            Utc::now(),
+            Some("#6".into()),
+            Some(4),
+            None,
+        );
+        push_running(
+            &mut entries,
+            "work",
+            Utc::now(),
+            Some("#2".into()),
+            Some(1),
+            None,
+        );
+        assert_eq!(references(&todo, &entries), [2, 3, 5]);
+    }
+
+    #[test]
+    fn the_index_follows_edits_and_deletes() {
+        let todos = [todo(1, "#3"), todo(2, "#3 #1"), todo(3, "")];
+        let mut index = RefIndex::build(&todos, &[]);
+        assert_eq!(index.referenced_by(3), [1, 2]);
+        assert_eq!(index.referenced_by(1), [2]);
+        assert_eq!(index.refers_to(2), [1, 3]);
+
+        index.update(1, vec![2]);
+        assert_eq!(index.referenced_by(3), [2]);
+        assert_eq!(index.referenced_by(2), [1]);
+
+        index.remove(2);
+        assert_eq!(index.referenced_by(3), [] as [usize; 0]);
+        assert_eq!(index.referenced_by(1), [] as [usize; 0]);
+        assert_eq!(index.refers_to(2), [] as [usize; 0]);
+        assert_eq!(index.referenced_by(2), [1]);
+    }
+}
@@ -0,0 +1,170 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::timetracking::TimeEntry;
+use crate::Todo;
+
+/// The new id of every incoming record whose id was taken, by old id.
+/// Records not in it keep theirs.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct IdMap(BTreeMap<usize, usize>);
+
+impl IdMap {
+    /// Maps every id of `incoming` that is in `taken`, lowest first, to the
+    /// next id above both `taken` and `incoming`. The same ids always give
+    /// the same map, so a sync partner can work it out too.
+    pub fn plan(incoming: &[usize], taken: &HashSet<usize>) -> IdMap {
+        let next = taken.iter().chain(incoming).max().map_or(1, |max| max + 1);
+        let mut colliding: Vec<usize> = incoming
+            .iter()
+            .copied()
+            .filter(|id| taken.contains(id))
+            .collect();
+        colliding.sort_unstable();
+        colliding.dedup();
+        IdMap(colliding.into_iter().zip(next..).collect())
+    }
+
+    /// The id `id` goes by after the import.
+    pub fn get(&self, id: usize) -> usize {
+        self.0.get(&id).copied().unwrap_or(id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn pairs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.0.iter().map(|(old, new)| (*old, *new))
+    }
+}
+
+/// Moves `todos` to their new ids, along with the parent links among them,
+/// a todo's link to itself included. Links to todos outside the batch are
+/// left alone.
+pub fn rewrite_todos(todos: &mut [Todo], map: &IdMap) {
+    let batch: HashSet<usize> = todos.iter().map(|t| t.id).collect();
+    for todo in todos.iter_mut() {
+        todo.id = map.get(todo.id);
+        if let Some(parent) = todo.parent.filter(|parent| batch.contains(parent)) {
+            todo.parent = Some(map.get(parent));
+        }
+    }
+}
+
+/// Moves `entries` to their new ids by `entries_map`, with the entries a
+/// break paused, and their todo links by `todos_map`. `todos_in_batch` are
+/// the old ids of the todos imported with them; links to others are left
+/// alone.
+pub fn rewrite_entries(
+    entries: &mut [TimeEntry],
+    entries_map: &IdMap,
+    todos_map: &IdMap,
+    todos_in_batch: &HashSet<usize>,
+) {
+    let batch: HashSet<usize> = entries.iter().map(|e| e.id).collect();
+    for entry in entries.iter_mut() {
+        entry.id = entries_map.get(entry.id);
+        for paused in entry.paused.iter_mut().filter(|id| batch.contains(id)) {
+            *paused = entries_map.get(*paused);
+        }
+        if let Some(todo_id) = entry.todo_id.filter(|id| todos_in_batch.contains(id)) {
+            entry.todo_id = Some(todos_map.get(todo_id));
+        }
+    }
+}
+
+/// The ids of both maps, for `--id-map`.
+#[derive(Serialize)]
+pub struct IdMaps<'a> {
+    pub todos: &'a IdMap,
+    pub time_entries: &'a IdMap,
+}
+
+/// The table the import summary prints, a line per moved id.
+pub fn table(kind: &str, map: &IdMap) -> Vec<String> {
+    map.pairs()
+        .map(|(old, new)| format!("  {} {} -> {}", kind, old, new))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn taken(ids: &[usize]) -> HashSet<usize> {
+        ids.iter().copied().collect()
+    }
+
+    fn todo(id: usize, parent: Option<usize>) -> Todo {
+        serde_json::from_value(json!({
+            "id": id,
+            "title": format!("todo {}", id),
+            "description": "",
+            "category": "work",
+            "status": "Todo",
+            "created_at": "2024-05-01T09:00:00Z",
+            "parent": parent,
+        }))
+        .unwrap()
+    }
+
+    fn entry(id: usize, todo_id: Option<usize>, paused: &[usize]) -> TimeEntry {
+        serde_json::from_value(json!({
+            "id": id,
+            "category": "work",
+            "start": "2024-05-01T09:00:00Z",
+            "todo_id": todo_id,
+            "paused": paused,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn colliding_ids_move_past_every_id_lowest_first() {
+        let map = IdMap::plan(&[7, 2, 3, 12], &taken(&[1, 2, 3, 4]));
+        assert_eq!(map.pairs().collect::<Vec<_>>(), [(2, 13), (3, 14)]);
+        assert_eq!(map.get(2), 13);
+        assert_eq!(map.get(7), 7);
+
+        // the same ids in another order give the same map
+        assert_eq!(IdMap::plan(&[3, 12, 2, 7, 2], &taken(&[4, 3, 2, 1])), map);
+        assert!(IdMap::plan(&[5, 6], &taken(&[1, 2])).is_empty());
+        assert!(IdMap::plan(&[], &taken(&[])).is_empty());
+    }
+
+    #[test]
+    fn parent_links_follow_within_the_batch_only() {
+        let mut todos = vec![
+            todo(1, None),
+            todo(2, Some(1)),
+            todo(3, Some(3)),
+            todo(4, Some(9)),
+        ];
+        let map = IdMap::plan(&[1, 2, 3, 4], &taken(&[1, 3, 9]));
+        rewrite_todos(&mut todos, &map);
+        let ids: Vec<(usize, Option<usize>)> = todos.iter().map(|t| (t.id, t.parent)).collect();
+        // 9 is a todo already here, not the incoming one
+        assert_eq!(
+            ids,
+            [(10, None), (2, Some(10)), (11, Some(11)), (4, Some(9))]
+        );
+    }
+
+    #[test]
+    fn entries_follow_their_own_map_and_the_todos_map() {
+        let mut entries = vec![entry(1, Some(1), &[]), entry(2, Some(5), &[1, 8])];
+        let entries_map = IdMap::plan(&[1, 2], &taken(&[1]));
+        let todos_map = IdMap::plan(&[1], &taken(&[1, 5]));
+        rewrite_entries(&mut entries, &entries_map, &todos_map, &taken(&[1]));
+        assert_eq!(entries[0].id, 3);
+        assert_eq!(entries[0].todo_id, Some(6));
+        assert_eq!(entries[1].id, 2);
+        // todo 5 wasn't imported, entry 8 isn't in the batch
+        assert_eq!(entries[1].todo_id, Some(5));
+        assert_eq!(entries[1].paused, [3, 8]);
+
+        assert_eq!(table("todo", &todos_map), ["  todo 1 -> 6"]);
+    }
+}
@@ -0,0 +1,205 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use std::collections::{HashMap, HashSet};
+
+use crate::config::Config;
+use crate::escalation::{effective_priority, EscalationRule};
+use crate::notify::Alert;
+use crate::subtasks::effective_due;
+use crate::timetracking::format_duration;
+use crate::{Todo, TodoStatus};
+
+/// Open todos due by `now + lead` that haven't been announced yet, going by
+/// their effective due date. That includes todos that fell due while their
+/// alert was held back.
+pub fn sweep<'a>(
+    todos: &'a [Todo],
+    now: DateTime<Utc>,
+    lead: Duration,
+    notified: &HashSet<usize>,
+) -> Vec<&'a Todo> {
+    todos
+        .iter()
+        .filter(|t| t.status() != TodoStatus::Done && !notified.contains(&t.id))
+        .filter(|t| effective_due(t, todos).is_some_and(|due| due <= now + lead))
+        .collect()
+}
+
+/// Watches for todos coming due while the TUI is open. Each is announced
+/// once per session, or again after a snooze runs out.
+#[derive(Default)]
+pub struct DueWatch {
+    notified: HashSet<usize>,
+    /// Todos held back until the time, by id.
+    snoozed: HashMap<usize, DateTime<Utc>>,
+    last_sweep: Option<DateTime<Utc>>,
+    /// The todos named by the latest alert, for `:snooze`.
+    pub announced: Vec<usize>,
+}
+
+impl DueWatch {
+    /// Sweeps at most once a minute and returns an alert for each todo that
+    /// just came due. Todos already overdue at the first sweep are taken
+    /// as known; Home lists them.
+    pub fn check(&mut self, todos: &[Todo], config: &Config, now: DateTime<Utc>) -> Vec<Alert> {
+        match self.last_sweep {
+            Some(last) if now - last < Duration::minutes(1) => return vec![],
+            None => {
+                self.notified = sweep(todos, now, Duration::zero(), &HashSet::new())
+                    .iter()
+                    .map(|t| t.id)
+                    .collect();
+            }
+            Some(_) => {}
+        }
+        self.last_sweep = Some(now);
+
+        let ended: Vec<usize> = self
+            .snoozed
+            .iter()
+            .filter(|(_, until)| **until <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ended {
+            self.snoozed.remove(&id);
+            self.notified.remove(&id);
+        }
+
+        let due = sweep(
+            todos,
+            now,
+            Duration::minutes(config.due_lead_minutes),
+            &self.notified,
+        );
+        if due.is_empty() {
+            return vec![];
+        }
+        self.notified.extend(due.iter().map(|t| t.id));
+        self.announced = due.iter().map(|t| t.id).collect();
+        due.iter()
+            .map(|todo| Alert {
+                title: "Todo due",
+                plural: "todos became due",
+                text: describe(todo, todos, &config.priority_escalation, now),
+                hint: ":snooze to remind again",
+            })
+            .collect()
+    }
+
+    /// Holds the todos of the latest alert back for `minutes`, after which
+    /// they are announced again.
+    pub fn snooze(&mut self, minutes: i64, now: DateTime<Utc>) -> usize {
+        let until = now + Duration::minutes(minutes);
+        let count = self.announced.len();
+        for id in self.announced.drain(..) {
+            self.snoozed.insert(id, until);
+        }
+        count
+    }
+}
+
+fn describe(
+    todo: &Todo,
+    todos: &[Todo],
+    escalation: &[EscalationRule],
+    now: DateTime<Utc>,
+) -> String {
+    let priority = effective_priority(todo, todos, now, escalation);
+    let due = match effective_due(todo, todos) {
+        Some(due) if due > now => format!(
+            "#{} {} due in {}",
+            todo.id,
+            todo.title,
+            format_duration(due - now)
+        ),
+        _ => format!("#{} {} is due", todo.id, todo.title),
+    };
+    match priority.priority {
+        Some(raised) if priority.is_escalated() => format!("{}, now {:?}", due, raised),
+        _ => due,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 6, hour, minute, 0).unwrap()
+    }
+
+    fn todo(id: usize, due: Option<DateTime<Utc>>) -> Todo {
+        let mut todo = Todo::new(format!("todo {}", id), "work".to_string(), at(8, 0));
+        todo.id = id;
+        todo.due = due;
+        todo
+    }
+
+    fn ids(todos: &[&Todo]) -> Vec<usize> {
+        todos.iter().map(|t| t.id).collect()
+    }
+
+    fn alerted(alerts: &[Alert]) -> Vec<String> {
+        alerts.iter().map(|a| a.text.clone()).collect()
+    }
+
+    #[test]
+    fn the_sweep_finds_open_todos_due_within_the_lead() {
+        let mut done = todo(4, Some(at(10, 0)));
+        done.set_status(TodoStatus::Done, at(9, 0));
+        let todos = [
+            todo(1, Some(at(10, 10))),
+            todo(2, Some(at(10, 20))),
+            todo(3, None),
+            done,
+            todo(5, Some(at(9, 0))),
+        ];
+        let lead = Duration::minutes(15);
+        assert_eq!(
+            ids(&sweep(&todos, at(10, 0), lead, &HashSet::new())),
+            [1, 5]
+        );
+        let notified: HashSet<usize> = [5].into_iter().collect();
+        assert_eq!(ids(&sweep(&todos, at(10, 0), lead, &notified)), [1]);
+    }
+
+    #[test]
+    fn a_todo_is_announced_once_and_overdue_ones_at_start_not_at_all() {
+        let config = Config::default();
+        let todos = [todo(1, Some(at(9, 0))), todo(2, Some(at(10, 30)))];
+        let mut watch = DueWatch::default();
+        assert!(watch.check(&todos, &config, at(10, 0)).is_empty());
+
+        let alerts = watch.check(&todos, &config, at(10, 20));
+        assert_eq!(alerted(&alerts), ["#2 todo 2 due in 10m"]);
+        assert_eq!(watch.announced, [2]);
+        assert!(watch.check(&todos, &config, at(10, 40)).is_empty());
+    }
+
+    #[test]
+    fn sweeps_come_at_most_once_a_minute() {
+        let config = Config::default();
+        let todos = [todo(1, Some(at(10, 30)))];
+        let mut watch = DueWatch::default();
+        watch.check(&todos, &config, at(10, 0));
+        let just_after = at(10, 0) + Duration::seconds(59);
+        let soon = vec![todo(1, Some(at(10, 10)))];
+        assert!(watch.check(&soon, &config, just_after).is_empty());
+        assert_eq!(watch.check(&soon, &config, at(10, 1)).len(), 1);
+    }
+
+    #[test]
+    fn a_snoozed_todo_comes_back_even_once_it_fell_due_meanwhile() {
+        let config = Config::default();
+        let todos = [todo(1, Some(at(10, 10)))];
+        let mut watch = DueWatch::default();
+        watch.check(&todos, &config, at(9, 0));
+        assert_eq!(watch.check(&todos, &config, at(10, 0)).len(), 1);
+        assert_eq!(watch.snooze(30, at(10, 0)), 1);
+        assert!(watch.announced.is_empty());
+
+        assert!(watch.check(&todos, &config, at(10, 15)).is_empty());
+        let alerts = watch.check(&todos, &config, at(10, 30));
+        assert_eq!(alerted(&alerts), ["#1 todo 1 is due"]);
+    }
+}
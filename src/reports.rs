@@ -0,0 +1,316 @@
+use chrono::prelude::*;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::timesheet::Timesheet;
+use crate::timetracking::{self, ReportFilter, WeekSummary};
+use crate::{read_db, zone};
+
+/// How long a built report is shown before it is built again in the
+/// background, for the time still being tracked.
+const REFRESH: Duration = Duration::from_secs(30);
+
+/// What a report is of, and which of the entries it counts.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Request {
+    /// The timesheet view of the month starting on the day.
+    Timesheet(NaiveDate, ReportFilter),
+    /// The TimeTracking tab's summary of the current week.
+    Week(ReportFilter),
+}
+
+impl Request {
+    fn filter(&self) -> &ReportFilter {
+        match self {
+            Request::Timesheet(_, filter) | Request::Week(filter) => filter,
+        }
+    }
+}
+
+pub enum Report {
+    Timesheet(Timesheet),
+    Week(WeekSummary),
+}
+
+/// A report built off the UI thread, answering the request `generation`.
+pub struct ReportReady {
+    generation: u64,
+    request: Request,
+    report: Result<Report, String>,
+}
+
+/// Builds the report `request` asks for from the entries on disk, the
+/// week by `week_start` and against `budgets`. Runs on a worker thread.
+pub fn build(
+    generation: u64,
+    request: Request,
+    week_start: Weekday,
+    budgets: &BTreeMap<String, f64>,
+) -> ReportReady {
+    let now = Utc::now();
+    let todos = match request.filter().uses_tags() {
+        true => read_db(),
+        false => Ok(vec![]),
+    };
+    let report = todos
+        .and_then(|todos| Ok((todos, timetracking::read_time_entries()?)))
+        .map(|(todos, entries)| match &request {
+            Request::Timesheet(month, filter) => Report::Timesheet(Timesheet::build(
+                &entries,
+                &todos,
+                filter,
+                *month,
+                None,
+                zone::current(),
+                now,
+            )),
+            Request::Week(filter) => Report::Week(WeekSummary::build(
+                &entries, &todos, filter, week_start, budgets, now,
+            )),
+        })
+        .map_err(|err| err.to_string());
+    ReportReady {
+        generation,
+        request,
+        report,
+    }
+}
+
+/// The reports the views show, built in the background so a large entry
+/// file doesn't hold up input. Every request gets the next generation and
+/// only a result for the latest one is kept, so a report for a month the
+/// user already moved away from never shows.
+#[derive(Default)]
+pub struct Reports {
+    generation: u64,
+    /// The request in flight, by generation.
+    pending: Option<(u64, Request)>,
+    ready: Option<(Request, Instant, Result<Report, String>)>,
+    /// Whether the entries may have changed since the ready report.
+    expired: bool,
+}
+
+impl Reports {
+    /// Asks for the report `request`. Returns the generation to build it
+    /// under, or None when it is ready and fresh or already coming.
+    pub fn request(&mut self, request: Request, now: Instant) -> Option<u64> {
+        if matches!(&self.pending, Some((_, pending)) if *pending == request) {
+            return None;
+        }
+        if let Some((ready, built, _)) = &self.ready {
+            if *ready == request && !self.expired && now.duration_since(*built) < REFRESH {
+                return None;
+            }
+        }
+        self.generation += 1;
+        self.pending = Some((self.generation, request));
+        Some(self.generation)
+    }
+
+    /// Takes a finished report. Returns whether it was the one asked for
+    /// last; any other is dropped.
+    pub fn deliver(&mut self, ready: ReportReady, now: Instant) -> bool {
+        match self.pending {
+            Some((generation, _)) if generation == ready.generation => {
+                self.pending = None;
+                self.ready = Some((ready.request, now, ready.report));
+                self.expired = false;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn report(&self, request: &Request) -> Option<Result<&Report, &str>> {
+        match &self.ready {
+            Some((ready, _, report)) if ready == request => {
+                Some(report.as_ref().map_err(String::as_str))
+            }
+            _ => None,
+        }
+    }
+
+    /// The timesheet of `month` by `filter`, None while it is being
+    /// built. One built before stays up while it is refreshed.
+    pub fn timesheet(
+        &self,
+        month: NaiveDate,
+        filter: &ReportFilter,
+    ) -> Option<Result<&Timesheet, &str>> {
+        match self.report(&Request::Timesheet(month, filter.clone()))? {
+            Ok(Report::Timesheet(sheet)) => Some(Ok(sheet)),
+            Ok(_) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+
+    /// The summary of the current week, like `timesheet`.
+    pub fn week(&self, filter: &ReportFilter) -> Option<Result<&WeekSummary, &str>> {
+        match self.report(&Request::Week(filter.clone()))? {
+            Ok(Report::Week(week)) => Some(Ok(week)),
+            Ok(_) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+
+    /// Has the report built again on the next request, for after a key
+    /// press that may have changed the entries. The one built stays up
+    /// meanwhile, one in flight may have read them before and is dropped.
+    pub fn expire(&mut self) {
+        self.pending = None;
+        self.expired = true;
+    }
+
+    /// Forgets every report, for when the view closes. A request still in
+    /// flight is dropped when it arrives.
+    pub fn clear(&mut self) {
+        self.pending = None;
+        self.ready = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn month(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(&format!("{}-01", value), "%Y-%m-%d").unwrap()
+    }
+
+    fn sheet(month: NaiveDate) -> Request {
+        Request::Timesheet(month, ReportFilter::default())
+    }
+
+    fn week() -> Request {
+        Request::Week(ReportFilter::default())
+    }
+
+    fn ready(generation: u64, request: Request) -> ReportReady {
+        let report = match &request {
+            Request::Timesheet(month, filter) => Report::Timesheet(Timesheet::build(
+                &[],
+                &[],
+                filter,
+                *month,
+                None,
+                zone::current(),
+                Utc::now(),
+            )),
+            Request::Week(filter) => Report::Week(WeekSummary::build(
+                &[],
+                &[],
+                filter,
+                Weekday::Mon,
+                &BTreeMap::new(),
+                Utc::now(),
+            )),
+        };
+        ReportReady {
+            generation,
+            request,
+            report: Ok(report),
+        }
+    }
+
+    fn shown(reports: &Reports, month: NaiveDate) -> Option<NaiveDate> {
+        match reports.timesheet(month, &ReportFilter::default())? {
+            Ok(sheet) => Some(sheet.month),
+            Err(_) => None,
+        }
+    }
+
+    #[test]
+    fn a_superseded_report_never_shows() {
+        let now = Instant::now();
+        let (may, june) = (month("2024-05"), month("2024-06"));
+        let mut reports = Reports::default();
+        let first = reports.request(sheet(may), now).unwrap();
+        let second = reports.request(sheet(june), now).unwrap();
+        assert!(second > first);
+
+        assert!(!reports.deliver(ready(first, sheet(may)), now));
+        assert_eq!(shown(&reports, may), None);
+        assert_eq!(shown(&reports, june), None);
+
+        assert!(reports.deliver(ready(second, sheet(june)), now));
+        assert_eq!(shown(&reports, june), Some(june));
+        assert_eq!(shown(&reports, may), None);
+    }
+
+    #[test]
+    fn a_report_in_flight_or_fresh_is_not_asked_for_again() {
+        let now = Instant::now();
+        let mut reports = Reports::default();
+        let generation = reports.request(week(), now).unwrap();
+        assert_eq!(reports.request(week(), now), None);
+        assert!(reports.deliver(ready(generation, week()), now));
+        assert!(reports.week(&ReportFilter::default()).is_some());
+        assert_eq!(reports.request(week(), now + REFRESH / 2), None);
+
+        // a stale one is rebuilt and stays up meanwhile
+        let refresh = reports.request(week(), now + REFRESH).unwrap();
+        assert!(refresh > generation);
+        assert!(reports.week(&ReportFilter::default()).is_some());
+    }
+
+    #[test]
+    fn a_report_after_clear_is_dropped() {
+        let now = Instant::now();
+        let mut reports = Reports::default();
+        let generation = reports.request(week(), now).unwrap();
+        reports.clear();
+        assert!(!reports.deliver(ready(generation, week()), now));
+        assert!(reports.week(&ReportFilter::default()).is_none());
+
+        // nor does a late answer to an earlier request replace a newer one
+        let newer = reports.request(week(), now).unwrap();
+        assert!(!reports.deliver(ready(generation, week()), now));
+        assert!(reports.deliver(ready(newer, week()), now));
+    }
+
+    #[test]
+    fn an_expired_report_is_rebuilt_and_stays_up() {
+        let now = Instant::now();
+        let mut reports = Reports::default();
+        let generation = reports.request(week(), now).unwrap();
+        assert!(reports.deliver(ready(generation, week()), now));
+        reports.expire();
+        let in_flight = reports.request(week(), now).unwrap();
+        // a change after the worker read the entries
+        reports.expire();
+        assert!(reports.week(&ReportFilter::default()).is_some());
+        let latest = reports.request(week(), now).unwrap();
+        assert!(!reports.deliver(ready(in_flight, week()), now));
+        assert!(reports.deliver(ready(latest, week()), now));
+        assert_eq!(reports.request(week(), now), None);
+    }
+
+    #[test]
+    fn the_week_is_not_a_timesheet() {
+        let now = Instant::now();
+        let may = month("2024-05");
+        let mut reports = Reports::default();
+        let generation = reports.request(week(), now).unwrap();
+        assert!(reports.deliver(ready(generation, week()), now));
+        assert!(reports.timesheet(may, &ReportFilter::default()).is_none());
+    }
+
+    #[test]
+    fn a_report_for_another_filter_never_shows() {
+        let now = Instant::now();
+        let deep = ReportFilter {
+            tag: Some("deep".to_string()),
+            by_tag: false,
+        };
+        let mut reports = Reports::default();
+        let generation = reports.request(week(), now).unwrap();
+        assert!(reports.deliver(ready(generation, week()), now));
+        assert!(reports.week(&deep).is_none());
+
+        let filtered = reports.request(Request::Week(deep.clone()), now).unwrap();
+        assert!(filtered > generation);
+        assert!(reports.deliver(ready(filtered, Request::Week(deep.clone())), now));
+        assert!(reports.week(&deep).is_some());
+        assert!(reports.week(&ReportFilter::default()).is_none());
+    }
+}
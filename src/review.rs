@@ -0,0 +1,306 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Cell, Paragraph, Row, Table},
+    Frame,
+};
+
+use crate::activity::{ActivityEvent, ActivityKind};
+use crate::chrome::Chrome;
+use crate::daynotes::{self, DayNote};
+use crate::timetracking::{
+    format_duration, format_signed_duration, local_midnight, time_per_category, TimeEntry,
+};
+use crate::zone::Zone;
+use crate::TodoStatus;
+
+/// Days before the reviewed one that make up the "daily average".
+const AVERAGE_DAYS: i64 = 28;
+
+pub struct CategoryDay {
+    pub category: String,
+    pub tracked: Duration,
+    pub average: Duration,
+}
+
+/// What a single local day looked like.
+pub struct DaySummary {
+    pub date: NaiveDate,
+    pub created: Vec<ActivityEvent>,
+    pub completed: Vec<ActivityEvent>,
+    pub moved: Vec<ActivityEvent>,
+    pub deleted: Vec<ActivityEvent>,
+    pub time: Vec<CategoryDay>,
+    /// The intention and reflection written that day.
+    pub note: Option<DayNote>,
+}
+
+pub fn day_summary(
+    events: &[ActivityEvent],
+    entries: &[TimeEntry],
+    notes: &[DayNote],
+    date: NaiveDate,
+    zone: Zone,
+    now: DateTime<Utc>,
+) -> DaySummary {
+    // local midnights, so a DST day is 23 or 25 hours long
+    let from = local_midnight(date, zone);
+    let to = local_midnight(date + Duration::days(1), zone);
+
+    let mut summary = DaySummary {
+        date,
+        created: vec![],
+        completed: vec![],
+        moved: vec![],
+        deleted: vec![],
+        time: vec![],
+        note: daynotes::note_for(notes, date).cloned(),
+    };
+    for event in events.iter().filter(|e| e.at >= from && e.at < to) {
+        let bucket = match &event.kind {
+            ActivityKind::Created => &mut summary.created,
+            ActivityKind::Moved {
+                to: TodoStatus::Done,
+                ..
+            } => &mut summary.completed,
+            ActivityKind::Moved { .. } => &mut summary.moved,
+            ActivityKind::Deleted => &mut summary.deleted,
+        };
+        bucket.push(event.clone());
+    }
+
+    let before = time_per_category(
+        entries,
+        local_midnight(date - Duration::days(AVERAGE_DAYS), zone),
+        from,
+        now,
+    );
+    let today = time_per_category(entries, from, to, now);
+    let mut categories: Vec<&String> = today.keys().chain(before.keys()).collect();
+    categories.sort();
+    categories.dedup();
+    summary.time = categories
+        .into_iter()
+        .map(|category| CategoryDay {
+            category: category.clone(),
+            tracked: today.get(category).cloned().unwrap_or_else(Duration::zero),
+            average: before
+                .get(category)
+                .map(|total| *total / AVERAGE_DAYS as i32)
+                .unwrap_or_else(Duration::zero),
+        })
+        .collect();
+    summary
+}
+
+pub fn render_day_summary<B: Backend>(
+    rect: &mut Frame<B>,
+    summary: &DaySummary,
+    chrome: Chrome,
+    area: Rect,
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(area);
+
+    let mut lines = vec![];
+    if let Some(note) = &summary.note {
+        for (label, text) in [
+            ("Intention", &note.intention),
+            ("Reflection", &note.reflection),
+        ] {
+            if let Some(text) = text {
+                lines.push(Spans::from(vec![
+                    Span::styled(
+                        format!("{:<10}      ", label),
+                        Style::default()
+                            .fg(Color::Magenta)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(text.clone()),
+                ]));
+            }
+        }
+    }
+    for (label, events, color) in [
+        ("Created", &summary.created, Color::LightBlue),
+        ("Completed", &summary.completed, Color::Green),
+        ("Moved", &summary.moved, Color::Yellow),
+        ("Deleted", &summary.deleted, Color::Red),
+    ] {
+        let titles: Vec<String> = events
+            .iter()
+            .map(|e| match &e.kind {
+                ActivityKind::Moved { from, to } => {
+                    format!("{} ({:?} -> {:?})", e.title, from, to)
+                }
+                _ => e.title.clone(),
+            })
+            .collect();
+        lines.push(Spans::from(vec![
+            Span::styled(
+                format!("{:<10} {:>3}  ", label, events.len()),
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(titles.join(", ")),
+        ]));
+    }
+
+    let todos = Paragraph::new(lines).block(chrome.block().title(format!(
+        "Today view - {} (h/l: day, Esc: close)",
+        summary.date.format("%a %Y-%m-%d")
+    )));
+
+    let rows: Vec<Row> = summary
+        .time
+        .iter()
+        .map(|day| {
+            let delta = day.tracked - day.average;
+            Row::new(vec![
+                Cell::from(Span::raw(day.category.clone())),
+                Cell::from(Span::raw(format_duration(day.tracked))),
+                Cell::from(Span::raw(format_duration(day.average))),
+                Cell::from(Span::styled(
+                    format_signed_duration(delta),
+                    Style::default().fg(if delta < Duration::zero() {
+                        Color::Red
+                    } else {
+                        Color::Green
+                    }),
+                )),
+            ])
+        })
+        .collect();
+
+    let time = Table::new(rows)
+        .header(Row::new(vec![
+            Cell::from(Span::styled(
+                "Category",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Tracked",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Daily average",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Delta",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+        ]))
+        .block(chrome.block().title("Time tracked"))
+        .widths(&[
+            Constraint::Percentage(30), // category
+            Constraint::Percentage(20), // tracked
+            Constraint::Percentage(20), // average
+            Constraint::Percentage(20), // delta
+        ]);
+
+    rect.render_widget(todos, chunks[0]);
+    rect.render_widget(time, chunks[1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn berlin() -> Zone {
+        Zone::parse("Europe/Berlin").unwrap()
+    }
+
+    fn at(date: NaiveDate, hour: i64) -> DateTime<Utc> {
+        local_midnight(date, berlin()) + Duration::hours(hour)
+    }
+
+    fn event(at: DateTime<Utc>, todo_id: usize, kind: ActivityKind) -> ActivityEvent {
+        ActivityEvent {
+            at,
+            todo_id,
+            title: format!("todo {}", todo_id),
+            kind,
+        }
+    }
+
+    fn entry(id: usize, category: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> TimeEntry {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "category": category,
+            "start": start,
+            "end": end,
+        }))
+        .unwrap()
+    }
+
+    fn ids(events: &[ActivityEvent]) -> Vec<usize> {
+        events.iter().map(|e| e.todo_id).collect()
+    }
+
+    #[test]
+    fn an_empty_day_has_nothing_to_show() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let summary = day_summary(&[], &[], &[], date, berlin(), at(date, 20));
+        assert_eq!(summary.date, date);
+        assert!(summary.created.is_empty() && summary.completed.is_empty());
+        assert!(summary.moved.is_empty() && summary.deleted.is_empty());
+        assert!(summary.time.is_empty());
+        assert!(summary.note.is_none());
+    }
+
+    #[test]
+    fn events_of_the_day_go_to_their_kind() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let moved = |from, to| ActivityKind::Moved { from, to };
+        let events = [
+            event(at(date, -1), 1, ActivityKind::Created),
+            event(at(date, 0), 2, ActivityKind::Created),
+            event(at(date, 9), 3, moved(TodoStatus::Todo, TodoStatus::Doing)),
+            event(at(date, 10), 3, moved(TodoStatus::Doing, TodoStatus::Done)),
+            event(at(date, 11), 4, ActivityKind::Deleted),
+            event(at(date, 24), 5, ActivityKind::Created),
+        ];
+        let summary = day_summary(&events, &[], &[], date, berlin(), at(date, 20));
+        assert_eq!(ids(&summary.created), [2]);
+        assert_eq!(ids(&summary.moved), [3]);
+        assert_eq!(ids(&summary.completed), [3]);
+        assert_eq!(ids(&summary.deleted), [4]);
+    }
+
+    #[test]
+    fn a_dst_day_is_reviewed_from_midnight_to_midnight() {
+        // clocks in Berlin went forward on 2024-03-31, a 23 hour day
+        let date = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        let next = date.succ_opt().unwrap();
+        assert_eq!(at(next, 0) - at(date, 0), Duration::hours(23));
+
+        let entries = [
+            // the whole day and an hour either side
+            entry(1, "work", at(date, -1), at(next, 1)),
+            // 28 hours over the days before, an hour a day on average
+            entry(2, "home", at(date, -28 * 24), at(date, -28 * 24 + 28)),
+        ];
+        let events = [
+            event(at(next, 0) - Duration::minutes(1), 1, ActivityKind::Created),
+            event(at(next, 0), 2, ActivityKind::Created),
+        ];
+        let summary = day_summary(&events, &entries, &[], date, berlin(), at(next, 12));
+        assert_eq!(ids(&summary.created), [1]);
+
+        let time: Vec<(&str, i64, i64)> = summary
+            .time
+            .iter()
+            .map(|day| {
+                let (tracked, average) = (day.tracked.num_hours(), day.average.num_hours());
+                (day.category.as_str(), tracked, average)
+            })
+            .collect();
+        assert_eq!(time, [("home", 0, 1), ("work", 23, 0)]);
+    }
+}
@@ -0,0 +1,425 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+
+use crate::cli;
+use crate::lock::DbLock;
+use crate::zone;
+use crate::{write_atomic, Error, Todo, TodoStatus, DB_PATH};
+
+/// Bumped whenever the layout of `db.json` changes. The original layout, a
+/// bare array of todos, counts as version 0.
+pub const DB_VERSION: u32 = 1;
+
+/// The todo fields added since version 0, which a migration defaults.
+const ADDED_FIELDS: [&str; 13] = [
+    "priority",
+    "due",
+    "tags",
+    "pinned",
+    "estimate",
+    "completed_at",
+    "doing_at",
+    "attachments",
+    "parent",
+    "updated_at",
+    "deferred_until",
+    "color",
+    "source",
+];
+
+/// The top level of `db.json`.
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    version: u32,
+    todos: T,
+}
+
+/// Reads the todos out of a DB file in any known version. A version 0
+/// file still reads, migrating it only happens at startup.
+pub fn parse_db(content: &str) -> Result<Vec<Todo>, Error> {
+    if is_legacy(content) {
+        return Ok(serde_json::from_str(content)?);
+    }
+    let envelope: Envelope<Vec<Todo>> = serde_json::from_str(content)?;
+    if envelope.version > DB_VERSION {
+        return Err(Error::SchemaError(format!(
+            "the DB is version {}, this build reads up to {}; update before running it",
+            envelope.version, DB_VERSION
+        )));
+    }
+    Ok(envelope.todos)
+}
+
+/// The todos as the current version of `db.json`, sorted by id whatever
+/// order they came in, so saving unchanged todos writes the same bytes and
+/// a change only touches its own records in a diff.
+pub fn db_json(todos: &[Todo]) -> Result<String, Error> {
+    let mut todos: Vec<&Todo> = todos.iter().collect();
+    todos.sort_by_key(|todo| todo.id);
+    Ok(serde_json::to_string_pretty(&Envelope {
+        version: DB_VERSION,
+        todos,
+    })?)
+}
+
+/// Whether `content` is a version 0 file, a bare array of todos.
+pub fn is_legacy(content: &str) -> bool {
+    content.trim_start().starts_with('[')
+}
+
+/// What migrating a version 0 file did.
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    pub records: usize,
+    /// How many records got each field's default, by field.
+    pub defaulted: BTreeMap<String, usize>,
+    pub warnings: Vec<String>,
+    /// Where the original file was copied, None on a dry run.
+    pub backup: Option<String>,
+}
+
+impl MigrationReport {
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{} {} todos to DB version {}",
+            if self.backup.is_some() {
+                "migrated"
+            } else {
+                "would migrate"
+            },
+            self.records,
+            DB_VERSION
+        )];
+        if let Some(backup) = &self.backup {
+            lines.push(format!("the original is kept as {}", backup));
+        }
+        for (field, count) in &self.defaulted {
+            lines.push(format!("defaulted {} on {} todos", field, count));
+        }
+        for warning in &self.warnings {
+            lines.push(format!("warning: {}", warning));
+        }
+        lines
+    }
+
+    /// One line for the TUI status bar.
+    pub fn summary(&self) -> String {
+        format!(
+            "migrated {} todos to DB version {}{}",
+            self.records,
+            DB_VERSION,
+            match self.warnings.len() {
+                0 => String::new(),
+                count => format!(", {} warnings, see `migrate --dry-run`", count),
+            }
+        )
+    }
+}
+
+/// The current version of a DB file's `content` and what changed, or None
+/// when it is current already.
+pub fn migrate(content: &str) -> Result<Option<(String, MigrationReport)>, Error> {
+    if !is_legacy(content) {
+        parse_db(content)?;
+        return Ok(None);
+    }
+    let records: Vec<Value> = serde_json::from_str(content)?;
+    let todos: Vec<Todo> = serde_json::from_str(content)?;
+
+    let mut report = MigrationReport {
+        records: todos.len(),
+        ..MigrationReport::default()
+    };
+    for record in &records {
+        for field in ADDED_FIELDS.iter().filter(|f| record.get(**f).is_none()) {
+            *report.defaulted.entry(field.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut duplicates: Vec<usize> = todos
+        .iter()
+        .filter(|t| !seen.insert(t.id))
+        .map(|t| t.id)
+        .collect();
+    duplicates.sort_unstable();
+    duplicates.dedup();
+    if !duplicates.is_empty() {
+        report.warnings.push(format!(
+            "duplicate ids {:?}, run `doctor --fix` to re-id them",
+            duplicates
+        ));
+    }
+    let undated = todos
+        .iter()
+        .filter(|t| t.status() == TodoStatus::Done && t.completed_at.is_none())
+        .count();
+    if undated > 0 {
+        report.warnings.push(format!(
+            "{} done todos have no completion time, they sort last in Done",
+            undated
+        ));
+    }
+    Ok(Some((db_json(&todos)?, report)))
+}
+
+/// Migrates `path` in place when it is a version 0 file, copying the
+/// original next to it first. None when there was nothing to do, including
+/// when the file doesn't exist yet.
+pub fn migrate_file(path: &str) -> Result<Option<MigrationReport>, Error> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Error::ReadFileError(path.to_string(), err)),
+    };
+    let (migrated, mut report) = match migrate(&content)? {
+        Some(migrated) => migrated,
+        None => return Ok(None),
+    };
+    let backup = format!("{}.v0-{}.bak", path, zone::now().format("%Y%m%d%H%M%S"));
+    write_atomic(&backup, &content)?;
+    write_atomic(path, &migrated)?;
+    report.backup = Some(backup);
+    Ok(Some(report))
+}
+
+/// Migrates the live DB under the lock, for startup. The lock is only
+/// taken when there is something to migrate.
+pub fn migrate_db() -> Result<Option<MigrationReport>, Error> {
+    match fs::read_to_string(DB_PATH) {
+        Ok(content) if is_legacy(&content) => {}
+        _ => return Ok(None),
+    }
+    let _lock = DbLock::acquire()?;
+    migrate_file(DB_PATH)
+}
+
+/// Runs `migrate [--dry-run] [FILE]` and returns the process exit code.
+/// FILE defaults to the live DB.
+pub fn run(args: &[String]) -> i32 {
+    let mut dry_run = false;
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg.as_str()),
+            _ => return cli::usage("usage: migrate [--dry-run] [FILE]"),
+        }
+    }
+    let path = path.unwrap_or(DB_PATH);
+
+    let report = if dry_run {
+        fs::read_to_string(path)
+            .map_err(|err| Error::ReadFileError(path.to_string(), err))
+            .and_then(|content| migrate(&content))
+            .map(|migrated| migrated.map(|(_, report)| report))
+    } else if path == DB_PATH {
+        migrate_db()
+    } else {
+        migrate_file(path)
+    };
+    match report {
+        Ok(Some(report)) => {
+            if dry_run {
+                println!("dry run, {} is left as it is", path);
+            }
+            for line in report.lines() {
+                println!("{}", line);
+            }
+            0
+        }
+        Ok(None) => {
+            println!("{} is at DB version {} already", path, DB_VERSION);
+            0
+        }
+        Err(err) => cli::fail(&err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A DB as the first release wrote it: a bare array with the five
+    /// original fields.
+    const ORIGINAL: &str = r#"[
+  {"id": 1, "title": "Pay rent", "description": "", "category": "home", "status": "Todo", "created_at": "2023-01-02T09:00:00Z"},
+  {"id": 2, "title": "Write report", "description": "Q4", "category": "work", "status": "Done", "created_at": "2023-01-03T09:00:00Z"},
+  {"id": 2, "title": "Call the bank", "description": "", "category": "errands", "status": "Doing", "created_at": "2023-01-04T09:00:00Z", "tags": ["phone"]}
+]"#;
+
+    /// A scratch directory of its own for `name`.
+    fn scratch(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("schema-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn the_original_format_is_wrapped_and_defaulted() {
+        let (migrated, report) = migrate(ORIGINAL).unwrap().unwrap();
+        assert!(!is_legacy(&migrated));
+        let envelope: Value = serde_json::from_str(&migrated).unwrap();
+        assert_eq!(envelope["version"], DB_VERSION);
+        let todos = parse_db(&migrated).unwrap();
+        let titles: Vec<&str> = todos.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, ["Pay rent", "Write report", "Call the bank"]);
+        assert_eq!(todos[2].tags, ["phone"]);
+
+        assert_eq!(report.records, 3);
+        assert_eq!(report.defaulted.len(), ADDED_FIELDS.len());
+        assert_eq!(report.defaulted["priority"], 3);
+        assert_eq!(report.defaulted["tags"], 2);
+        assert_eq!(
+            report.warnings,
+            [
+                "duplicate ids [2], run `doctor --fix` to re-id them",
+                "1 done todos have no completion time, they sort last in Done"
+            ]
+        );
+        assert_eq!(
+            report.summary(),
+            "migrated 3 todos to DB version 1, 2 warnings, see `migrate --dry-run`"
+        );
+        // a dry run has no backup to mention
+        assert_eq!(report.lines()[0], "would migrate 3 todos to DB version 1");
+        assert!(report
+            .lines()
+            .contains(&"defaulted tags on 2 todos".to_string()));
+    }
+
+    #[test]
+    fn an_empty_array_migrates_to_an_empty_envelope() {
+        let (migrated, report) = migrate(" []").unwrap().unwrap();
+        assert!(parse_db(&migrated).unwrap().is_empty());
+        assert_eq!(report.records, 0);
+        assert!(report.defaulted.is_empty() && report.warnings.is_empty());
+        assert_eq!(report.summary(), "migrated 0 todos to DB version 1");
+    }
+
+    #[test]
+    fn a_migrated_file_is_left_alone() {
+        let (migrated, _) = migrate(ORIGINAL).unwrap().unwrap();
+        assert!(migrate(&migrated).unwrap().is_none());
+        // and saving it again writes the same bytes
+        assert_eq!(db_json(&parse_db(&migrated).unwrap()).unwrap(), migrated);
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let newer = format!(r#"{{"version": {}, "todos": []}}"#, DB_VERSION + 1);
+        for result in [parse_db(&newer).err(), migrate(&newer).err()] {
+            assert!(matches!(result, Some(Error::SchemaError(message))
+                if message.starts_with("the DB is version 2, this build reads up to 1")));
+        }
+        assert!(parse_db(r#"{"version": 1, "todos": []}"#)
+            .unwrap()
+            .is_empty());
+        assert!(parse_db(r#"{"todos": []}"#).is_err());
+    }
+
+    #[test]
+    fn todos_are_saved_in_id_order() {
+        let mut todos = parse_db(ORIGINAL).unwrap();
+        todos[1].id = 3;
+        todos.reverse();
+        let saved = parse_db(&db_json(&todos).unwrap()).unwrap();
+        let ids: Vec<usize> = saved.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [1, 2, 3]);
+    }
+
+    /// Five todos in id order, as the last save left them.
+    fn saved_db(dir: &std::path::Path) -> String {
+        let todos: Vec<Todo> = (1..=5)
+            .map(|id| {
+                let mut todo = Todo::new(
+                    format!("todo {}", id),
+                    "work".to_string(),
+                    "2024-05-01T09:00:00Z".parse().unwrap(),
+                );
+                todo.id = id;
+                todo
+            })
+            .collect();
+        let path = dir.join("db.json");
+        crate::write_atomic(path.to_str().unwrap(), &db_json(&todos).unwrap()).unwrap();
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn saving_what_was_loaded_writes_the_same_bytes() {
+        let dir = scratch("resave");
+        let before = saved_db(&dir);
+        let mut todos = parse_db(&before).unwrap();
+        // the board holds them in its own order
+        todos.reverse();
+        let path = dir.join("db.json");
+        crate::write_atomic(path.to_str().unwrap(), &db_json(&todos).unwrap()).unwrap();
+        let after = fs::read_to_string(&path);
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(after.unwrap(), before);
+    }
+
+    #[test]
+    fn a_reorder_only_touches_the_records_that_moved() {
+        let dir = scratch("reorder");
+        let before = saved_db(&dir);
+        let _ = fs::remove_dir_all(&dir);
+        let mut todos = parse_db(&before).unwrap();
+        // pinning 4 takes it to the top of the column
+        todos[3].pinned = true;
+        todos[3].touch("2024-05-02T09:00:00Z".parse().unwrap());
+        crate::sort::sort_column(&mut todos);
+        assert_eq!(todos[0].id, 4);
+        let after = db_json(&todos).unwrap();
+
+        // every line outside the record of todo 4 stays as it was
+        let without_4 = |db: &str| {
+            let lines: Vec<&str> = db.lines().collect();
+            let id = lines.iter().position(|line| line.trim() == "\"id\": 4,");
+            let start = id.unwrap() - 1;
+            let close = lines[start..]
+                .iter()
+                .position(|line| line.trim().starts_with('}'));
+            let end = start + close.unwrap();
+            (
+                lines[..start].join("\n"),
+                lines[start..=end].join("\n"),
+                lines[end + 1..].join("\n"),
+            )
+        };
+        let (before, after) = (without_4(&before), without_4(&after));
+        assert_eq!((&before.0, &before.2), (&after.0, &after.2));
+        assert_ne!(before.1, after.1);
+        assert!(after.1.contains("\"pinned\": true"));
+    }
+
+    #[test]
+    fn migrating_a_file_keeps_a_backup_once() {
+        let dir = scratch("file");
+        let path = dir.join("db.json");
+        let path = path.to_str().unwrap();
+        assert!(migrate_file(path).unwrap().is_none());
+
+        fs::write(path, ORIGINAL).unwrap();
+        let report = migrate_file(path).unwrap().unwrap();
+        let backup = report.backup.clone().unwrap();
+        assert_eq!(fs::read_to_string(&backup).unwrap(), ORIGINAL);
+        assert!(report.lines()[0].starts_with("migrated 3 todos"));
+        assert_eq!(
+            report.lines()[1],
+            format!("the original is kept as {}", backup)
+        );
+        let migrated = fs::read_to_string(path).unwrap();
+        assert_eq!(parse_db(&migrated).unwrap().len(), 3);
+
+        assert!(migrate_file(path).unwrap().is_none());
+        assert_eq!(fs::read_to_string(path).unwrap(), migrated);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,326 @@
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::Paragraph,
+    Frame,
+};
+
+use crate::chrome::Chrome;
+
+/// One titled block of a scroll stack, with as many body lines as it needs.
+pub struct Section<'a> {
+    pub title: String,
+    pub lines: Vec<Spans<'a>>,
+}
+
+/// A vertical stack of sections. When they don't all fit, every section but
+/// the expanded one shrinks to its header, the stack scrolls to keep the
+/// focused header in view and hidden lines are announced above or below.
+#[derive(Default)]
+pub struct ScrollStack {
+    focused: usize,
+    expanded: Option<usize>,
+    offset: usize,
+}
+
+impl ScrollStack {
+    /// Moves the focus down; `render` keeps it on the last section.
+    pub fn focus_next(&mut self) {
+        self.focused += 1;
+    }
+
+    pub fn focus_previous(&mut self) {
+        self.focused = self.focused.saturating_sub(1);
+    }
+
+    /// Expands the focused section, or collapses it if it already is.
+    pub fn toggle_expanded(&mut self) {
+        self.expanded = if self.expanded == Some(self.focused) {
+            None
+        } else {
+            Some(self.focused)
+        };
+    }
+
+    /// Lays the sections out for `height` rows of `width` columns: the
+    /// wrapped lines to draw and the index of the focused header among them.
+    fn layout<'a>(
+        &self,
+        sections: &[Section<'a>],
+        width: usize,
+        height: usize,
+    ) -> (Vec<Spans<'a>>, usize) {
+        let bodies: Vec<Vec<Spans>> = sections
+            .iter()
+            .map(|s| s.lines.iter().flat_map(|l| wrap(l, width)).collect())
+            .collect();
+        let full: usize = bodies.iter().map(|body| 1 + body.len()).sum();
+        let collapse = full > height;
+
+        let mut lines = vec![];
+        let mut focused_line = 0;
+        for (i, (section, body)) in sections.iter().zip(bodies).enumerate() {
+            let open = !collapse || self.expanded == Some(i);
+            let style = if i == self.focused {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().add_modifier(Modifier::BOLD)
+            };
+            let header = if open {
+                format!("▾ {}", section.title)
+            } else {
+                format!("▸ {} ({} lines)", section.title, body.len())
+            };
+            if i == self.focused {
+                focused_line = lines.len();
+            }
+            lines.push(Spans::from(vec![Span::styled(header, style)]));
+            if open {
+                lines.extend(body);
+            }
+        }
+        (lines, focused_line)
+    }
+
+    pub fn render<B: Backend>(
+        &mut self,
+        rect: &mut Frame<B>,
+        sections: &[Section],
+        title: &str,
+        chrome: Chrome,
+        area: Rect,
+    ) {
+        self.focused = self.focused.min(sections.len().saturating_sub(1));
+        let width = area.width.saturating_sub(chrome.frame(false).0).max(1) as usize;
+        let height = area.height.saturating_sub(2) as usize;
+        let (lines, focused_line) = self.layout(sections, width, height);
+        let dim = Style::default().fg(Color::DarkGray);
+
+        let mut visible = if lines.len() <= height {
+            self.offset = 0;
+            lines
+        } else if height < 3 {
+            // no room for two indicators, show the focused header and a count
+            self.offset = focused_line;
+            let mut visible = vec![lines[focused_line].clone()];
+            if height == 2 {
+                visible.push(Spans::from(vec![Span::styled(
+                    format!("▲▼ {} more lines", lines.len() - 1),
+                    dim,
+                )]));
+            }
+            visible
+        } else {
+            // keep the focused header off the indicator rows where possible
+            let room = height - 2;
+            if focused_line < self.offset + 1 {
+                self.offset = focused_line.saturating_sub(1);
+            } else if focused_line >= self.offset + 1 + room {
+                self.offset = focused_line + 1 - room - 1;
+            }
+            self.offset = self.offset.min(lines.len() - height);
+
+            let mut visible: Vec<Spans> = lines[self.offset..self.offset + height].to_vec();
+            if self.offset > 0 {
+                visible[0] = Spans::from(vec![Span::styled(
+                    format!("▲ {} more lines", self.offset + 1),
+                    dim,
+                )]);
+            }
+            let below = lines.len() - self.offset - height;
+            if below > 0 {
+                let last = visible.len() - 1;
+                visible[last] = Spans::from(vec![Span::styled(
+                    format!("▼ {} more lines", below + 1),
+                    dim,
+                )]);
+            }
+            visible
+        };
+        if visible.is_empty() {
+            visible.push(Spans::from(vec![Span::raw("")]));
+        }
+
+        let paragraph = Paragraph::new(visible).block(
+            chrome
+                .block()
+                .title(format!("{} - j/k: section, Enter: expand", title)),
+        );
+        rect.render_widget(paragraph, area);
+    }
+}
+
+/// Breaks a line into rows of at most `width` chars, keeping span styles.
+fn wrap<'a>(line: &Spans<'a>, width: usize) -> Vec<Spans<'a>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut used = 0;
+    for span in &line.0 {
+        let mut rest: &str = &span.content;
+        while !rest.is_empty() {
+            if used == width {
+                rows.push(Spans::from(std::mem::take(&mut row)));
+                used = 0;
+            }
+            let take = rest
+                .char_indices()
+                .nth(width - used)
+                .map(|(i, _)| i)
+                .unwrap_or(rest.len());
+            used += rest[..take].chars().count();
+            row.push(Span::styled(rest[..take].to_string(), span.style));
+            rest = &rest[take..];
+        }
+    }
+    rows.push(Spans::from(row));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tui::{backend::TestBackend, Terminal};
+
+    fn section(title: &str, lines: usize) -> Section<'static> {
+        Section {
+            title: title.to_string(),
+            lines: (1..=lines)
+                .map(|n| Spans::from(format!("{} line {}", title, n)))
+                .collect(),
+        }
+    }
+
+    /// The rows `stack` draws for `sections` on a `width` by `height` screen.
+    fn screen(
+        stack: &mut ScrollStack,
+        sections: &[Section],
+        width: u16,
+        height: u16,
+    ) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|f| {
+                let area = f.size();
+                stack.render(f, sections, "Home", Chrome::new(area, None), area)
+            })
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content
+            .chunks(width as usize)
+            .map(|row| row.iter().map(|cell| cell.symbol.as_str()).collect())
+            .collect()
+    }
+
+    fn shows(rows: &[String], text: &str) -> bool {
+        rows.iter().any(|row| row.contains(text))
+    }
+
+    fn dashboard() -> Vec<Section<'static>> {
+        [
+            "Welcome",
+            "Today",
+            "Remaining work",
+            "Tracked this week",
+            "Flex time",
+            "Days off",
+            "Pomodoros",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, title)| section(title, 2 + i))
+        .collect()
+    }
+
+    #[test]
+    fn a_large_screen_shows_every_section_open() {
+        let sections = dashboard();
+        let rows = screen(&mut ScrollStack::default(), &sections, 200, 60);
+        for section in &sections {
+            assert!(
+                shows(&rows, &format!("▾ {}", section.title)),
+                "{}",
+                section.title
+            );
+            let last = section.lines.len();
+            assert!(shows(&rows, &format!("{} line {}", section.title, last)));
+        }
+        assert!(!shows(&rows, "more lines"));
+    }
+
+    #[test]
+    fn a_small_screen_collapses_sections_to_their_headers() {
+        let sections = dashboard();
+        let rows = screen(&mut ScrollStack::default(), &sections, 80, 24);
+        for section in &sections {
+            let header = format!("▸ {} ({} lines)", section.title, section.lines.len());
+            assert!(shows(&rows, &header), "{}", header);
+        }
+        assert!(!shows(&rows, "Today line 1"));
+    }
+
+    #[test]
+    fn nothing_is_clipped_without_an_indicator() {
+        let mut sections = dashboard();
+        sections.push(section("Long", 40));
+        let mut stack = ScrollStack::default();
+        for _ in 0..sections.len() {
+            stack.focus_next();
+        }
+        screen(&mut stack, &sections, 80, 24);
+        stack.toggle_expanded();
+        for _ in 0..=sections.len() {
+            let rows = screen(&mut stack, &sections, 80, 24);
+            let above = shows(&rows, "▲");
+            let below = shows(&rows, "▼");
+            assert!(above || below, "the expanded section can't fit");
+            let drawn: Vec<usize> = sections
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| shows(&rows, &format!(" {}", s.title)))
+                .map(|(i, _)| i)
+                .collect();
+            // a missing header is announced on the side it is on
+            for (i, section) in sections.iter().enumerate() {
+                if !drawn.contains(&i) {
+                    let side = if i < drawn[0] { above } else { below };
+                    assert!(side, "{} hidden silently", section.title);
+                }
+            }
+            stack.focus_previous();
+        }
+    }
+
+    #[test]
+    fn long_lines_wrap_instead_of_being_cut() {
+        let text = "word ".repeat(40);
+        let sections = vec![Section {
+            title: "Notes".to_string(),
+            lines: vec![Spans::from(text.clone())],
+        }];
+        let rows = screen(&mut ScrollStack::default(), &sections, 80, 24);
+        let inside: String = rows
+            .iter()
+            .map(|row| row.chars().skip(1).take(78).collect::<String>())
+            .collect();
+        assert!(inside.contains(text.trim_end()));
+    }
+
+    #[test]
+    fn wrap_keeps_styles_and_counts_chars() {
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let line = Spans::from(vec![Span::raw("ab"), Span::styled("cdé", bold)]);
+        let rows = wrap(&line, 2);
+        let text: Vec<String> = rows
+            .iter()
+            .map(|row| row.0.iter().map(|s| s.content.to_string()).collect())
+            .collect();
+        assert_eq!(text, ["ab", "cd", "é"]);
+        assert_eq!(rows[2].0[0].style, bold);
+        assert_eq!(wrap(&Spans::from(""), 5).len(), 1);
+    }
+}
@@ -0,0 +1,250 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::actions::fuzzy_score;
+use crate::cursor;
+use crate::glyph::Glyphs;
+use crate::widgets::{centered_rect, Input};
+use crate::Todo;
+
+/// Matches past this many aren't looked for, so a large archive can't
+/// slow down typing.
+pub const MAX_MATCHES: usize = 200;
+
+/// How far a search looks, cycled with Ctrl+a. It belongs to the open
+/// search only; the board filter stays as it is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchScope {
+    /// The todos the board shows.
+    Board,
+    /// Those and the archived ones.
+    Archived,
+    /// Those and every todo the board filter hides, done ones included.
+    Everything,
+}
+
+impl SearchScope {
+    pub fn next(self) -> SearchScope {
+        match self {
+            SearchScope::Board => SearchScope::Archived,
+            SearchScope::Archived => SearchScope::Everything,
+            SearchScope::Everything => SearchScope::Board,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SearchScope::Board => "board",
+            SearchScope::Archived => "board + archived",
+            SearchScope::Everything => "everything",
+        }
+    }
+}
+
+/// Where a found todo is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Location {
+    Board,
+    /// In the DB but hidden by the board filter.
+    Hidden,
+    Archived,
+}
+
+impl Location {
+    /// The tag shown after the title, none for the board.
+    fn tag(self) -> Option<&'static str> {
+        match self {
+            Location::Board => None,
+            Location::Hidden => Some("[filtered out]"),
+            Location::Archived => Some("[archived]"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hit {
+    pub id: usize,
+    pub title: String,
+    /// The category glyph and a space, or nothing.
+    pub glyph: String,
+    pub location: Location,
+}
+
+pub enum SearchAction {
+    Continue,
+    Cancel,
+    Open(Hit),
+}
+
+/// The popup finding a todo by its title. What it searches is read when
+/// it opens and kept in memory while it is up.
+pub struct Search {
+    input: Input,
+    scope: SearchScope,
+    board: Vec<Todo>,
+    hidden: Vec<Todo>,
+    archived: Vec<Todo>,
+    /// The glyph of each category any of them has.
+    glyphs: HashMap<String, String>,
+    hits: Vec<Hit>,
+    /// Whether the search stopped at `MAX_MATCHES`.
+    capped: bool,
+    selected: usize,
+}
+
+impl Search {
+    pub fn new(
+        board: Vec<Todo>,
+        hidden: Vec<Todo>,
+        archived: Vec<Todo>,
+        glyphs: &Glyphs,
+    ) -> Search {
+        let glyphs = board
+            .iter()
+            .chain(&hidden)
+            .chain(&archived)
+            .map(|todo| (todo.category.clone(), glyphs.prefix(&todo.category)))
+            .collect();
+        let mut search = Search {
+            input: Input::default(),
+            scope: SearchScope::Board,
+            board,
+            hidden,
+            archived,
+            glyphs,
+            hits: vec![],
+            capped: false,
+            selected: 0,
+        };
+        search.search();
+        search
+    }
+
+    /// Best matches first, board todos before the rest on a tie. Stops
+    /// looking at `MAX_MATCHES`.
+    fn search(&mut self) {
+        let query = self.input.value();
+        let mut sources = vec![(Location::Board, &self.board)];
+        if self.scope != SearchScope::Board {
+            sources.push((Location::Archived, &self.archived));
+        }
+        if self.scope == SearchScope::Everything {
+            sources.push((Location::Hidden, &self.hidden));
+        }
+        let mut scored: Vec<(i32, usize, Hit)> = vec![];
+        self.capped = false;
+        'sources: for (rank, (location, todos)) in sources.into_iter().enumerate() {
+            for todo in todos.iter() {
+                if let Some(score) = fuzzy_score(query, &todo.title) {
+                    if scored.len() == MAX_MATCHES {
+                        self.capped = true;
+                        break 'sources;
+                    }
+                    scored.push((
+                        score,
+                        rank,
+                        Hit {
+                            id: todo.id,
+                            title: todo.title.clone(),
+                            glyph: self.glyphs.get(&todo.category).cloned().unwrap_or_default(),
+                            location,
+                        },
+                    ));
+                }
+            }
+        }
+        scored.sort_by_key(|(score, rank, hit)| (-score, *rank, hit.id));
+        self.hits = scored.into_iter().map(|(_, _, hit)| hit).collect();
+        self.selected = 0;
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> SearchAction {
+        match key.code {
+            KeyCode::Esc => return SearchAction::Cancel,
+            KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.scope = self.scope.next();
+                self.search();
+            }
+            KeyCode::Enter => {
+                return match self.hits.get(self.selected) {
+                    Some(hit) => SearchAction::Open(hit.clone()),
+                    None => SearchAction::Continue,
+                }
+            }
+            KeyCode::Down | KeyCode::Tab => {
+                self.selected = (self.selected + 1).min(self.hits.len().saturating_sub(1))
+            }
+            KeyCode::Up | KeyCode::BackTab => self.selected = self.selected.saturating_sub(1),
+            _ => {
+                if self.input.handle_key(key) {
+                    self.search();
+                }
+            }
+        }
+        SearchAction::Continue
+    }
+}
+
+pub fn render_search<B: Backend>(rect: &mut Frame<B>, search: &Search, area: Rect) {
+    let popup = centered_rect(60, 60, area);
+    let rows = popup.height.saturating_sub(5) as usize;
+    let offset = (search.selected + 1).saturating_sub(rows);
+    let cursor = cursor::current();
+    let mut lines = vec![
+        Spans::from(vec![Span::raw(format!("> {}", search.input.value()))]),
+        Spans::from(vec![Span::raw("")]),
+    ];
+    for (row, hit) in search.hits.iter().enumerate().skip(offset).take(rows) {
+        let (marker, style) = cursor.row(
+            row == search.selected,
+            match hit.location {
+                Location::Board => Style::default().fg(Color::White),
+                _ => Style::default().fg(Color::Gray),
+            },
+        );
+        let mut spans = vec![Span::styled(
+            format!("{}#{} {}{}", marker, hit.id, hit.glyph, hit.title),
+            style,
+        )];
+        if let Some(tag) = hit.location.tag() {
+            spans.push(Span::styled(
+                format!(" {}", tag),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        lines.push(Spans::from(spans));
+    }
+    if search.hits.is_empty() {
+        lines.push(Spans::from(vec![Span::styled(
+            "no todo matches",
+            Style::default().fg(Color::DarkGray),
+        )]));
+    } else if search.capped {
+        lines.push(Spans::from(vec![Span::styled(
+            format!("showing first {} matches", MAX_MATCHES),
+            Style::default().fg(Color::DarkGray),
+        )]));
+    }
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(format!(
+                "Find todo in {} (Ctrl+a: scope, Enter: open, Esc: close)",
+                search.scope.label()
+            ))
+            .border_type(BorderType::Plain),
+    );
+
+    rect.render_widget(Clear, popup);
+    rect.render_widget(paragraph, popup);
+    rect.set_cursor(popup.x + 3 + search.input.cursor() as u16, popup.y + 1);
+}
@@ -0,0 +1,121 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::macros::Step;
+use crate::Error;
+
+const SESSION_PATH: &str = "./data/session.json";
+
+pub const DETAIL_STEP: u16 = 5;
+pub const MIN_DETAIL_PERCENT: u16 = 10;
+pub const MAX_DETAIL_PERCENT: u16 = 80;
+/// Lines the command history keeps.
+const MAX_COMMAND_HISTORY: usize = 200;
+
+/// UI state that survives restarts, kept apart from the todo data.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Session {
+    pub detail_visible: bool,
+    /// Share of the Todos tab height given to the detail pane.
+    pub detail_percent: u16,
+    /// Recorded keyboard macros by register letter.
+    pub macros: BTreeMap<char, Vec<Step>>,
+    /// Whether the remaining work estimate only counts todos due this week.
+    pub estimate_this_week: bool,
+    /// Board columns shrunk to a strip, ToDo, Doing and Done.
+    pub collapsed_columns: [bool; 3],
+    /// The local day the maintenance pass last ran.
+    pub last_maintenance: Option<NaiveDate>,
+    /// Per-category counts on a second status bar line.
+    pub badges_visible: bool,
+    /// Editor text not saved yet, by what it edits, e.g. "description:42",
+    /// kept while typing so a crash doesn't lose it.
+    pub drafts: BTreeMap<String, Draft>,
+    /// Lines run from the `:` command line, oldest first, each once.
+    pub command_history: Vec<String>,
+    /// The local day the TUI was last started on.
+    pub last_launch: Option<NaiveDate>,
+    /// The local day the reflection was last asked for, so it is asked
+    /// once even when skipped.
+    pub reflection_offered: Option<NaiveDate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Draft {
+    pub text: String,
+    pub saved_at: DateTime<Utc>,
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session {
+            detail_visible: true,
+            detail_percent: 20,
+            macros: BTreeMap::new(),
+            estimate_this_week: false,
+            collapsed_columns: [false; 3],
+            last_maintenance: None,
+            badges_visible: false,
+            drafts: BTreeMap::new(),
+            command_history: vec![],
+            last_launch: None,
+            reflection_offered: None,
+        }
+    }
+}
+
+impl Session {
+    /// Loads the session, falling back to defaults when the file is missing
+    /// or unreadable; losing UI state is never worth refusing to start.
+    pub fn load() -> Session {
+        fs::read_to_string(SESSION_PATH)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(SESSION_PATH, content).map_err(Error::WriteSessionError)
+    }
+
+    /// Adds `line` to the command history, or moves it to the end when it
+    /// is in already, dropping the oldest lines past the limit.
+    pub fn remember_command(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        self.command_history.retain(|earlier| earlier != line);
+        self.command_history.push(line.to_string());
+        let over = self
+            .command_history
+            .len()
+            .saturating_sub(MAX_COMMAND_HISTORY);
+        self.command_history.drain(..over);
+    }
+
+    pub fn resize_detail(&mut self, grow: bool) {
+        self.detail_percent = if grow {
+            (self.detail_percent + DETAIL_STEP).min(MAX_DETAIL_PERCENT)
+        } else {
+            self.detail_percent
+                .saturating_sub(DETAIL_STEP)
+                .max(MIN_DETAIL_PERCENT)
+        };
+    }
+
+    /// Collapses or expands a board column. Returns false, changing
+    /// nothing, when it is the last expanded one.
+    pub fn toggle_column(&mut self, column: usize) -> bool {
+        let expanded = self.collapsed_columns.iter().filter(|c| !**c).count();
+        if !self.collapsed_columns[column] && expanded == 1 {
+            return false;
+        }
+        self.collapsed_columns[column] = !self.collapsed_columns[column];
+        true
+    }
+}
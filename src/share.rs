@@ -0,0 +1,201 @@
+use chrono::prelude::*;
+use std::io::{self, Write};
+
+use crate::cli;
+use crate::history::{self, SnippetFormat};
+use crate::subtasks;
+use crate::timetracking;
+use crate::{read_db, Error, Todo};
+
+const USAGE: &str = "usage: show ID [--format md|text]";
+
+pub fn parse_format(value: &str) -> Option<SnippetFormat> {
+    match value {
+        "md" | "markdown" => Some(SnippetFormat::Markdown),
+        "text" => Some(SnippetFormat::Text),
+        _ => None,
+    }
+}
+
+/// The snippet for `todo` from its subtasks and time entries as they are
+/// now.
+pub fn todo_snippet(todo: &Todo, format: SnippetFormat) -> Result<String, Error> {
+    let now = Utc::now();
+    let todos = read_db()?;
+    let entries = timetracking::read_time_entries()?;
+    let history = history::work_history(&entries, todo.id, now);
+    let subtasks = subtasks::subtasks(todo.id, &todos);
+    Ok(history::snippet(todo, &subtasks, &history, format))
+}
+
+/// Runs `show ID [--format md|text]` and returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let mut id = None;
+    let mut format = SnippetFormat::Markdown;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(|value| parse_format(value)) {
+                Some(Some(parsed)) => format = parsed,
+                _ => return cli::usage("--format takes md or text"),
+            },
+            _ if id.is_none() => match arg.parse::<usize>() {
+                Ok(parsed) => id = Some(parsed),
+                Err(_) => return cli::usage(format!("invalid todo id '{}'", arg)),
+            },
+            _ => return cli::usage(USAGE),
+        }
+    }
+    let id = match id {
+        Some(id) => id,
+        None => return cli::usage(USAGE),
+    };
+    let todo = match read_db() {
+        Ok(todos) => todos.into_iter().find(|t| t.id == id),
+        Err(err) => return cli::fail(&err),
+    };
+    let todo = match todo {
+        Some(todo) => todo,
+        None => return cli::other(format!("no todo with id {}", id)),
+    };
+    match todo_snippet(&todo, format) {
+        Ok(snippet) => {
+            print!("{}", snippet);
+            0
+        }
+        Err(err) => cli::fail(&err),
+    }
+}
+
+/// Asks the terminal to put `text` on the clipboard with OSC 52. Terminals
+/// without support ignore it, so this can't tell whether it worked.
+pub fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetracking::push_running;
+    use crate::zone;
+    use crate::{Priority, TodoStatus};
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, day, hour, 0, 0).unwrap()
+    }
+
+    /// A todo with every field the snippet shows, two subtasks and two
+    /// sessions of tracked time.
+    fn fixture() -> (Todo, Vec<Todo>, history::WorkHistory) {
+        let mut todo = Todo::new("Ship the release".to_string(), "work".to_string(), at(1, 9));
+        todo.id = 7;
+        todo.set_description("Tag, build and *announce* it.\nSee #3.".to_string())
+            .unwrap();
+        todo.priority = Some(Priority::High);
+        todo.color = Some("red".to_string());
+        todo.due = Some(at(10, 17));
+        todo.attachments = vec!["/tmp/notes/release plan.md".into()];
+        let subtask = |id, title: &str, done| {
+            let mut subtask = Todo::new(title.to_string(), "work".to_string(), at(1, 9));
+            subtask.id = id;
+            subtask.parent = Some(7);
+            if done {
+                subtask.set_status(TodoStatus::Done, at(2, 9));
+            }
+            subtask
+        };
+        let subtasks = vec![subtask(8, "Tag", true), subtask(9, "Announce", false)];
+
+        let mut entries = vec![];
+        for (day, hours) in [(2, 1), (3, 2)] {
+            let id = push_running(&mut entries, "work", at(day, 9), None, Some(7), None);
+            entries[id - 1].end = Some(at(day, 9 + hours));
+        }
+        let history = history::work_history(&entries, 7, at(4, 9));
+        (todo, subtasks, history)
+    }
+
+    fn due() -> String {
+        at(10, 17)
+            .with_timezone(&zone::current())
+            .format("%Y-%m-%d %H:%M")
+            .to_string()
+    }
+
+    #[test]
+    fn the_markdown_snippet_of_a_full_todo() {
+        let (todo, subtasks, history) = fixture();
+        let subtasks: Vec<&Todo> = subtasks.iter().collect();
+        let expected = format!(
+            "## Ship the release\n\n\
+             work · High · label red · due {} · #7\n\n\
+             Tag, build and *announce* it.\nSee #3.\n\n\
+             - [x] Tag (#8)\n\
+             - [ ] Announce (#9)\n\n\
+             - [release plan.md](</tmp/notes/release plan.md>)\n\
+             Time spent: 3h 00m over 2 sessions\n",
+            due()
+        );
+        assert_eq!(
+            history::snippet(&todo, &subtasks, &history, SnippetFormat::Markdown),
+            expected
+        );
+    }
+
+    #[test]
+    fn the_text_snippet_of_a_full_todo() {
+        let (todo, subtasks, history) = fixture();
+        let subtasks: Vec<&Todo> = subtasks.iter().collect();
+        let expected = format!(
+            "Ship the release\n\
+             work · High · label red · due {} · #7\n\n\
+             Tag, build and *announce* it.\nSee #3.\n\n\
+             [x] Tag (#8)\n\
+             [ ] Announce (#9)\n\n\
+             - /tmp/notes/release plan.md\n\
+             Time spent: 3h 00m over 2 sessions\n",
+            due()
+        );
+        assert_eq!(
+            history::snippet(&todo, &subtasks, &history, SnippetFormat::Text),
+            expected
+        );
+    }
+
+    #[test]
+    fn formats_parse_and_base64_pads() {
+        assert!(matches!(parse_format("md"), Some(SnippetFormat::Markdown)));
+        assert!(matches!(
+            parse_format("markdown"),
+            Some(SnippetFormat::Markdown)
+        ));
+        assert!(matches!(parse_format("text"), Some(SnippetFormat::Text)));
+        assert!(parse_format("html").is_none());
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64("ü".as_bytes()), "w7w=");
+    }
+}
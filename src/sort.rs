@@ -0,0 +1,186 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::zone;
+use crate::{Todo, TodoStatus};
+
+/// Orders a column for display. Pinned todos always come first, then
+/// oldest id first. The order `db.json` stores them in is left alone, it
+/// is always by id.
+pub fn sort_column(todos: &mut [Todo]) {
+    todos.sort_by_key(|todo| (!todo.pinned, todo.id));
+}
+
+/// Orders the Done column like the others, pinned todos first, and then
+/// by completion, newest first. Todos finished before `completed_at`
+/// existed go last.
+pub fn sort_done(todos: &mut [Todo]) {
+    todos.sort_by_key(|todo| (!todo.pinned, Reverse(todo.completed_at)));
+}
+
+/// The position of every todo on the unfiltered board: the ToDo, Doing and
+/// Done columns one after the other, each in display order.
+pub fn board_order(todos: &[Todo]) -> HashMap<usize, usize> {
+    let mut order = HashMap::new();
+    for status in [TodoStatus::Todo, TodoStatus::Doing, TodoStatus::Done] {
+        let mut column: Vec<Todo> = todos
+            .iter()
+            .filter(|t| t.status() == status)
+            .cloned()
+            .collect();
+        if status == TodoStatus::Done {
+            sort_done(&mut column);
+        } else {
+            sort_column(&mut column);
+        }
+        for todo in column {
+            order.insert(todo.id, order.len());
+        }
+    }
+    order
+}
+
+/// A line of the Done column: either a day separator or a todo.
+pub enum DoneRow<'a> {
+    Day(String),
+    Todo(&'a Todo),
+}
+
+impl DoneRow<'_> {
+    pub fn is_selectable(&self) -> bool {
+        matches!(self, DoneRow::Todo(_))
+    }
+}
+
+/// Inserts a separator before the pinned todos and before each local
+/// completion day of the others in `todos`, which must already be in
+/// `sort_done` order.
+pub fn done_rows<'a>(todos: &'a [Todo], today: NaiveDate) -> Vec<DoneRow<'a>> {
+    let mut rows = vec![];
+    // None for the pinned todos
+    let mut current = None;
+    for todo in todos {
+        let group = (!todo.pinned).then(|| {
+            todo.completed_at
+                .map(|at| at.with_timezone(&zone::current()).date_naive())
+        });
+        if rows.is_empty() || group != current {
+            rows.push(DoneRow::Day(match group {
+                Some(day) => day_label(day, today),
+                None => "— Pinned —".to_string(),
+            }));
+            current = group;
+        }
+        rows.push(DoneRow::Todo(todo));
+    }
+    rows
+}
+
+fn day_label(day: Option<NaiveDate>, today: NaiveDate) -> String {
+    let label = match day {
+        Some(day) if day == today => "Today".to_string(),
+        Some(day) if day == today - Duration::days(1) => "Yesterday".to_string(),
+        Some(day) => day.format("%Y-%m-%d").to_string(),
+        None => "Earlier".to_string(),
+    };
+    format!("— {} —", label)
+}
+
+/// The next selectable row after `from` in the given direction, wrapping
+/// around, or None when nothing is selectable.
+pub fn next_selectable(rows: &[DoneRow], from: Option<usize>, forward: bool) -> Option<usize> {
+    let len = rows.len();
+    let start = match from {
+        Some(from) => from,
+        None if forward => len.checked_sub(1)?,
+        None => 0,
+    };
+    (1..=len)
+        .map(|step| {
+            if forward {
+                (start + step) % len
+            } else {
+                (start + len - step % len) % len
+            }
+        })
+        .find(|i| rows[*i].is_selectable())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetracking::local_midnight;
+
+    fn done(id: usize, completed_at: Option<DateTime<Utc>>, pinned: bool) -> Todo {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "title": format!("todo {}", id),
+            "description": "",
+            "category": "work",
+            "status": "Done",
+            "created_at": "2024-04-01T09:00:00Z",
+            "completed_at": completed_at,
+            "pinned": pinned,
+        }))
+        .unwrap()
+    }
+
+    fn labels(rows: &[DoneRow]) -> Vec<String> {
+        rows.iter()
+            .map(|row| match row {
+                DoneRow::Day(label) => label.clone(),
+                DoneRow::Todo(todo) => format!("#{}", todo.id),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn done_keeps_pinned_first_then_newest() {
+        let at = |hour| Some(Utc.with_ymd_and_hms(2024, 5, 15, hour, 0, 0).unwrap());
+        let mut todos = vec![
+            done(1, at(8), false),
+            done(2, None, false),
+            done(3, at(6), true),
+            done(4, at(9), false),
+        ];
+        sort_done(&mut todos);
+        let ids: Vec<usize> = todos.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [3, 4, 1, 2]);
+    }
+
+    #[test]
+    fn days_split_at_local_midnight() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 15).unwrap();
+        let midnight = local_midnight(today, zone::current());
+        let second = Duration::seconds(1);
+        let mut todos = vec![
+            done(1, Some(midnight), false),
+            done(2, Some(midnight - second), false),
+            done(3, Some(midnight - Duration::days(1) - second), false),
+            done(4, None, false),
+            done(5, Some(midnight - second), true),
+        ];
+        sort_done(&mut todos);
+        let rows = done_rows(&todos, today);
+        assert_eq!(
+            labels(&rows),
+            [
+                "— Pinned —",
+                "#5",
+                "— Today —",
+                "#1",
+                "— Yesterday —",
+                "#2",
+                "— 2024-05-13 —",
+                "#3",
+                "— Earlier —",
+                "#4"
+            ]
+        );
+        assert_eq!(next_selectable(&rows, Some(1), true), Some(3));
+        assert_eq!(next_selectable(&rows, Some(3), false), Some(1));
+        assert_eq!(next_selectable(&rows, Some(9), true), Some(1));
+    }
+}
@@ -0,0 +1,211 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use tui::symbols::bar;
+
+use crate::timetracking::{format_duration, local_midnight, time_per_category, TimeEntry};
+use crate::zone::Zone;
+
+/// How many days the status bar sparkline covers, today included.
+pub const SPARKLINE_DAYS: usize = 7;
+
+/// How many days, today included, the sparkline is scaled by: long enough
+/// for one day out of the ordinary to fall above the 95th percentile.
+pub const SCALE_DAYS: usize = 28;
+
+/// Drawn for a day above the scale.
+const OVERFLOW: &str = "↑";
+
+/// The bars by eighths, with a dot for nothing tracked so that the line
+/// keeps its length on the status bar border.
+const LEVELS: [&str; 9] = [
+    "·",
+    bar::ONE_EIGHTH,
+    bar::ONE_QUARTER,
+    bar::THREE_EIGHTHS,
+    bar::HALF,
+    bar::FIVE_EIGHTHS,
+    bar::THREE_QUARTERS,
+    bar::SEVEN_EIGHTHS,
+    bar::FULL,
+];
+
+/// The tracked time of each of the `days` days of `zone` up to `today`,
+/// oldest first, with running entries counting up to `now`.
+pub fn daily_totals(
+    entries: &[TimeEntry],
+    today: NaiveDate,
+    days: usize,
+    zone: Zone,
+    now: DateTime<Utc>,
+) -> Vec<Duration> {
+    (0..days as i64)
+        .rev()
+        .map(|ago| {
+            let day = today - Duration::days(ago);
+            time_per_category(
+                entries,
+                local_midnight(day, zone),
+                local_midnight(day + Duration::days(1), zone),
+                now,
+            )
+            .values()
+            .fold(Duration::zero(), |sum, total| sum + *total)
+        })
+        .collect()
+}
+
+/// The 95th percentile of the days with any time tracked, interpolating
+/// between the two nearest ranks. Days with nothing tracked are left out,
+/// or a month of weekends would pull the scale down.
+fn scale(days: &[Duration]) -> Duration {
+    let mut seconds: Vec<i64> = days
+        .iter()
+        .map(|day| day.num_seconds())
+        .filter(|seconds| *seconds > 0)
+        .collect();
+    seconds.sort_unstable();
+    if seconds.is_empty() {
+        return Duration::zero();
+    }
+    let rank = (seconds.len() - 1) as f64 * 0.95;
+    let below = seconds[rank.floor() as usize];
+    let above = seconds[rank.ceil() as usize];
+    Duration::seconds(below + ((above - below) as f64 * rank.fract()).round() as i64)
+}
+
+/// The last `shown` of `days` as bars of at most `width` columns, each
+/// day as wide as the width allows and the oldest days left out when it
+/// is narrower than one column a day. The scale tops out at the 95th
+/// percentile of all of `days`, days above it show as `↑`; a day with any
+/// time at all shows at least the lowest bar.
+pub fn sparkline(days: &[Duration], shown: usize, width: usize) -> String {
+    if days.is_empty() || shown == 0 || width == 0 {
+        return String::new();
+    }
+    let shown = &days[days.len().saturating_sub(shown.min(width))..];
+    let columns = width / shown.len();
+    let top = scale(days);
+    shown
+        .iter()
+        .map(|day| {
+            if *day > top {
+                return OVERFLOW.repeat(columns);
+            }
+            let level = if *day <= Duration::zero() {
+                0
+            } else {
+                let ratio = day.num_seconds() as f64 / top.num_seconds() as f64;
+                ((ratio * 8.0).round() as usize).clamp(1, 8)
+            };
+            LEVELS[level].repeat(columns)
+        })
+        .collect()
+}
+
+/// The exact figures behind the sparkline, e.g. "Mon 7h 30m · ... · today
+/// 1h 02m".
+pub fn figures(days: &[Duration], today: NaiveDate) -> String {
+    let last = days.len().saturating_sub(1);
+    days.iter()
+        .enumerate()
+        .map(|(i, total)| {
+            let day = today - Duration::days((last - i) as i64);
+            let label = if i == last {
+                "today".to_string()
+            } else {
+                day.format("%a").to_string()
+            };
+            format!("{} {}", label, format_duration(*total))
+        })
+        .collect::<Vec<String>>()
+        .join(" · ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hours(hours: &[i64]) -> Vec<Duration> {
+        hours.iter().map(|h| Duration::hours(*h)).collect()
+    }
+
+    #[test]
+    fn the_scale_is_the_95th_percentile_of_the_tracked_days() {
+        // ranks 0..19, the 95th percentile at 18.05
+        let days: Vec<i64> = (1..=20).collect();
+        assert_eq!(scale(&hours(&days)), Duration::seconds(19 * 3600 + 180));
+        // days with nothing tracked are not ranked
+        let mut with_weekends = hours(&days);
+        with_weekends.extend(hours(&[0; 8]));
+        assert_eq!(scale(&with_weekends), scale(&hours(&days)));
+        assert_eq!(scale(&hours(&[0, 0])), Duration::zero());
+        assert_eq!(scale(&[]), Duration::zero());
+        assert_eq!(scale(&hours(&[3])), Duration::hours(3));
+    }
+
+    #[test]
+    fn one_huge_day_overflows_without_flattening_the_rest() {
+        let mut days = hours(&[8; 27]);
+        days.push(Duration::hours(20));
+        assert_eq!(sparkline(&days, 7, 7), "██████↑");
+        // as the plain maximum it would have squashed the week
+        days.pop();
+        days.push(Duration::hours(4));
+        assert_eq!(sparkline(&days, 7, 7), "██████▄");
+    }
+
+    #[test]
+    fn a_big_day_before_the_week_only_moves_the_scale_a_little() {
+        let mut days = hours(&[20]);
+        days.extend(hours(&[4; 20]));
+        days.extend(hours(&[0, 1, 2, 3, 4, 5, 6]));
+        assert_eq!(sparkline(&days, 7, 7), "·▁▃▄▆▇↑");
+    }
+
+    #[test]
+    fn nothing_tracked_keeps_the_line_as_dots() {
+        assert_eq!(sparkline(&hours(&[0; 28]), 7, 7), "·······");
+    }
+
+    #[test]
+    fn any_time_at_all_shows_the_lowest_bar() {
+        let mut days = hours(&[8, 8, 8]);
+        days.extend([Duration::zero(), Duration::minutes(1), Duration::hours(8)]);
+        assert_eq!(sparkline(&days, 3, 3), "·▁█");
+    }
+
+    #[test]
+    fn the_width_drops_the_oldest_days_or_widens_each() {
+        let days = hours(&[8, 8, 8, 2, 4, 6, 8]);
+        assert_eq!(sparkline(&days, 4, 2), "▆█");
+        assert_eq!(sparkline(&days, 4, 9), "▂▂▄▄▆▆██");
+        assert_eq!(sparkline(&days, 2, 4), "▆▆██");
+        assert_eq!(sparkline(&days, 4, 0), "");
+        assert_eq!(sparkline(&[], 4, 4), "");
+    }
+
+    #[test]
+    fn daily_totals_split_at_midnight_and_count_a_running_entry() {
+        let entries: Vec<TimeEntry> = serde_json::from_value(serde_json::json!([
+            {"id": 1, "category": "work", "start": "2024-05-01T22:00:00Z", "end": "2024-05-02T01:00:00Z"},
+            {"id": 2, "category": "home", "start": "2024-05-03T09:00:00Z"},
+        ]))
+        .unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 5, 3, 9, 30, 0).unwrap();
+        let today = NaiveDate::from_ymd_opt(2024, 5, 3).unwrap();
+        let days = daily_totals(&entries, today, 4, Zone::Named(chrono_tz::UTC), now);
+        assert_eq!(
+            days,
+            [
+                Duration::zero(),
+                Duration::hours(2),
+                Duration::hours(1),
+                Duration::minutes(30),
+            ]
+        );
+        assert_eq!(
+            figures(&days[1..], today),
+            "Wed 2h 00m · Thu 1h 00m · today 30m"
+        );
+    }
+}
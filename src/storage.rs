@@ -0,0 +1,344 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use serde::Serialize;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Cell, Paragraph, Row, Table},
+    Frame,
+};
+
+use crate::activity::{self, ActivityEvent};
+use crate::chrome::Chrome;
+use crate::cli;
+use crate::flextime::{self, Overrides};
+use crate::pomodoro::{self, Pomodoro};
+use crate::timetracking::{self, TimeEntry};
+use crate::{read_db, Error, Todo, TodoStatus};
+
+/// Age past which done todos and activity events count as old.
+const OLD_DAYS: i64 = 365;
+
+/// The size of one kind of data, as it is written to disk.
+pub struct Usage {
+    pub name: &'static str,
+    pub count: usize,
+    pub bytes: usize,
+}
+
+pub struct Report {
+    pub usage: Vec<Usage>,
+    pub suggestions: Vec<String>,
+}
+
+/// Bytes `items` take serialized the way the data files are written.
+fn serialized_size<T: Serialize + ?Sized>(items: &T) -> usize {
+    serde_json::to_string_pretty(items)
+        .map(|s| s.len())
+        .unwrap_or(0)
+}
+
+fn usage<T: Serialize>(name: &'static str, items: &[T]) -> Usage {
+    Usage {
+        name,
+        count: items.len(),
+        bytes: serialized_size(items),
+    }
+}
+
+/// Measures each kind of data and suggests what could be trimmed.
+pub fn measure(
+    todos: &[Todo],
+    entries: &[TimeEntry],
+    activity: &[ActivityEvent],
+    overrides: &Overrides,
+    pomodoros: &[Pomodoro],
+    now: DateTime<Utc>,
+) -> Report {
+    let (done, open): (Vec<&Todo>, Vec<&Todo>) =
+        todos.iter().partition(|t| t.status() == TodoStatus::Done);
+    let old = now - Duration::days(OLD_DAYS);
+    let old_done: Vec<&Todo> = done
+        .iter()
+        .filter(|t| t.completed_at.unwrap_or_else(|| t.modified_at()) < old)
+        .cloned()
+        .collect();
+    let old_events: Vec<&ActivityEvent> = activity.iter().filter(|e| e.at < old).collect();
+
+    let mut suggestions = vec![];
+    if !old_done.is_empty() {
+        suggestions.push(format!(
+            "{} done todos finished over a year ago take {}; `export --format pws-archive` keeps a copy before you prune them",
+            old_done.len(),
+            format_bytes(serialized_size(&old_done))
+        ));
+    }
+    if !old_events.is_empty() {
+        suggestions.push(format!(
+            "{} activity events are older than a year and take {}",
+            old_events.len(),
+            format_bytes(serialized_size(&old_events))
+        ));
+    }
+
+    Report {
+        usage: vec![
+            usage("Open todos", &open),
+            usage("Done todos", &done),
+            usage("Time entries", entries),
+            usage("Activity log", activity),
+            usage("Pomodoros", pomodoros),
+            Usage {
+                name: "Target overrides",
+                count: overrides.len(),
+                bytes: serialized_size(overrides),
+            },
+        ],
+        suggestions,
+    }
+}
+
+/// Reads every data file and measures it.
+pub fn load_report(now: DateTime<Utc>) -> Result<Report, Error> {
+    Ok(measure(
+        &read_db()?,
+        &timetracking::read_time_entries()?,
+        &activity::read_activity()?,
+        &flextime::read_overrides()?,
+        &pomodoro::read_history()?,
+        now,
+    ))
+}
+
+pub fn format_bytes(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+/// Runs `storage` and returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    if !args.is_empty() {
+        return cli::usage("usage: storage");
+    }
+    let report = match load_report(Utc::now()) {
+        Ok(report) => report,
+        Err(err) => return cli::fail(&err),
+    };
+    for usage in &report.usage {
+        println!(
+            "{:<18} {:>7} {:>10}",
+            usage.name,
+            usage.count,
+            format_bytes(usage.bytes)
+        );
+    }
+    let total: usize = report.usage.iter().map(|u| u.bytes).sum();
+    println!("{:<18} {:>7} {:>10}", "Total", "", format_bytes(total));
+    for suggestion in &report.suggestions {
+        println!("\n{}", suggestion);
+    }
+    0
+}
+
+pub fn render_storage<B: Backend>(
+    rect: &mut Frame<B>,
+    report: &Report,
+    chrome: Chrome,
+    area: Rect,
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(report.usage.len() as u16 + 4),
+                Constraint::Min(3),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let total: usize = report.usage.iter().map(|u| u.bytes).sum();
+    let mut rows: Vec<Row> = report
+        .usage
+        .iter()
+        .map(|usage| {
+            Row::new(vec![
+                Cell::from(Span::raw(usage.name)),
+                Cell::from(Span::raw(usage.count.to_string())),
+                Cell::from(Span::raw(format_bytes(usage.bytes))),
+            ])
+        })
+        .collect();
+    rows.push(Row::new(vec![
+        Cell::from(Span::styled(
+            "Total",
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Cell::from(Span::raw("")),
+        Cell::from(Span::styled(
+            format_bytes(total),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+    ]));
+    let table = Table::new(rows)
+        .header(Row::new(
+            ["Data", "Count", "Size"]
+                .iter()
+                .map(|title| {
+                    Cell::from(Span::styled(
+                        *title,
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ))
+                })
+                .collect::<Vec<_>>(),
+        ))
+        .block(chrome.block().title("Storage (Esc: close)"))
+        .widths(&[
+            Constraint::Percentage(40), // data
+            Constraint::Percentage(20), // count
+            Constraint::Percentage(40), // size
+        ]);
+    rect.render_widget(table, chunks[0]);
+
+    let lines: Vec<Spans> = if report.suggestions.is_empty() {
+        vec![Spans::from(vec![Span::styled(
+            "nothing to trim",
+            Style::default().fg(Color::DarkGray),
+        )])]
+    } else {
+        report
+            .suggestions
+            .iter()
+            .map(|s| Spans::from(vec![Span::raw(s.clone())]))
+            .collect()
+    };
+    let suggestions = Paragraph::new(lines)
+        .wrap(tui::widgets::Wrap { trim: true })
+        .block(chrome.block().title("Suggestions"));
+    rect.render_widget(suggestions, chunks[1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity::ActivityKind;
+
+    /// `count` todos with a 1000 char description each, every other one
+    /// done `done_days_ago`.
+    fn todos(count: usize, done_days_ago: i64, now: DateTime<Utc>) -> Vec<Todo> {
+        (1..=count)
+            .map(|id| {
+                let mut todo = Todo::new(format!("todo {}", id), "work".to_string(), now);
+                todo.id = id;
+                todo.set_description("x".repeat(1000)).unwrap();
+                if id % 2 == 0 {
+                    todo.set_status(TodoStatus::Done, now - Duration::days(done_days_ago));
+                }
+                todo
+            })
+            .collect()
+    }
+
+    fn event(days_ago: i64, now: DateTime<Utc>) -> ActivityEvent {
+        ActivityEvent {
+            at: now - Duration::days(days_ago),
+            todo_id: 1,
+            title: "todo 1".to_string(),
+            kind: ActivityKind::Created,
+        }
+    }
+
+    fn find<'a>(report: &'a Report, name: &str) -> &'a Usage {
+        report.usage.iter().find(|u| u.name == name).unwrap()
+    }
+
+    #[test]
+    fn sizes_grow_with_the_data_within_known_bounds() {
+        let now = Utc::now();
+        for count in [10, 100, 1000] {
+            let report = measure(&todos(count, 1, now), &[], &[], &Overrides::new(), &[], now);
+            let open = find(&report, "Open todos");
+            let done = find(&report, "Done todos");
+            assert_eq!((open.count, done.count), (count / 2, count / 2));
+            // the descriptions plus at most 500 bytes of fields a todo
+            let range = (count / 2 * 1000)..=(count / 2 * 1500);
+            assert!(
+                range.contains(&open.bytes),
+                "{} not in {:?}",
+                open.bytes,
+                range
+            );
+            assert!(
+                range.contains(&done.bytes),
+                "{} not in {:?}",
+                done.bytes,
+                range
+            );
+        }
+    }
+
+    #[test]
+    fn the_sizes_are_those_of_the_files_as_written() {
+        let now = Utc::now();
+        let events = vec![event(1, now), event(2, now)];
+        let mut overrides = Overrides::new();
+        overrides.insert(NaiveDate::from_ymd_opt(2024, 5, 6).unwrap(), 4.0);
+        let report = measure(&[], &[], &events, &overrides, &[], now);
+
+        let activity = find(&report, "Activity log");
+        assert_eq!(activity.count, 2);
+        assert_eq!(
+            activity.bytes,
+            serde_json::to_string_pretty(&events).unwrap().len()
+        );
+        let targets = find(&report, "Target overrides");
+        assert_eq!(targets.count, 1);
+        assert_eq!(
+            targets.bytes,
+            serde_json::to_string_pretty(&overrides).unwrap().len()
+        );
+        // an empty list is still written as "[]"
+        assert_eq!(find(&report, "Time entries").bytes, 2);
+    }
+
+    #[test]
+    fn only_data_older_than_a_year_is_suggested_for_trimming() {
+        let now = Utc::now();
+        let recent = measure(
+            &todos(4, 30, now),
+            &[],
+            &[event(30, now)],
+            &Overrides::new(),
+            &[],
+            now,
+        );
+        assert!(recent.suggestions.is_empty());
+
+        let old = measure(
+            &todos(4, 400, now),
+            &[],
+            &[event(400, now), event(30, now)],
+            &Overrides::new(),
+            &[],
+            now,
+        );
+        assert_eq!(old.suggestions.len(), 2);
+        assert!(old.suggestions[0].starts_with("2 done todos finished over a year ago take "));
+        assert!(old.suggestions[1].starts_with("1 activity events are older than a year"));
+    }
+
+    #[test]
+    fn bytes_read_in_the_largest_unit_that_fits() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MB");
+    }
+}
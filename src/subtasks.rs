@@ -0,0 +1,207 @@
+use chrono::prelude::*;
+
+use crate::{Todo, TodoStatus};
+
+/// A todo's due date once its subtasks are taken into account.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rollup {
+    pub due: Option<DateTime<Utc>>,
+    /// The subtask whose due date beat the todo's own, if one did.
+    pub driver: Option<usize>,
+}
+
+/// The subtasks of the todo `parent`, lowest id first.
+pub fn subtasks(parent: usize, todos: &[Todo]) -> Vec<&Todo> {
+    let mut subtasks: Vec<&Todo> = todos.iter().filter(|t| t.parent == Some(parent)).collect();
+    subtasks.sort_by_key(|t| t.id);
+    subtasks
+}
+
+/// The todo's own due date, unless a subtask that isn't done yet is due
+/// earlier, or one of that subtask's own subtasks, however deep. A tie
+/// keeps the todo's own date; between subtasks due at the same time the
+/// lowest id drives. The driver is the subtask the date comes from, which
+/// may sit several levels down.
+pub fn rollup(todo: &Todo, todos: &[Todo]) -> Rollup {
+    // bounded in case the file holds a loop
+    rollup_within(todo, todos, todos.len())
+}
+
+fn rollup_within(todo: &Todo, todos: &[Todo], depth: usize) -> Rollup {
+    let earliest = match depth {
+        0 => None,
+        _ => todos
+            .iter()
+            .filter(|t| t.parent == Some(todo.id) && t.status() != TodoStatus::Done)
+            .filter_map(|t| {
+                let below = rollup_within(t, todos, depth - 1);
+                below.due.map(|due| (due, below.driver.unwrap_or(t.id)))
+            })
+            .min(),
+    };
+    match (todo.due, earliest) {
+        (Some(own), Some((due, id))) if due < own => Rollup {
+            due: Some(due),
+            driver: Some(id),
+        },
+        (None, Some((due, id))) => Rollup {
+            due: Some(due),
+            driver: Some(id),
+        },
+        (own, _) => Rollup {
+            due: own,
+            driver: None,
+        },
+    }
+}
+
+/// The due date the board, sorting, overdue styling and due alerts go by.
+/// `todos` has to hold the subtasks, so pass every todo, not a filtered
+/// column.
+pub fn effective_due(todo: &Todo, todos: &[Todo]) -> Option<DateTime<Utc>> {
+    rollup(todo, todos).due
+}
+
+/// Why `id` can't become a subtask of `parent`, if it can't: the parent
+/// has to exist and must not be `id` or below it.
+pub fn check_parent(id: usize, parent: usize, todos: &[Todo]) -> Result<(), String> {
+    if parent == id {
+        return Err("a todo can't be its own subtask".to_string());
+    }
+    if !todos.iter().any(|t| t.id == parent) {
+        return Err(format!("no todo {}", parent));
+    }
+    // bounded in case the file already holds a loop
+    let mut ancestor = Some(parent);
+    for _ in 0..todos.len() {
+        match ancestor {
+            Some(current) if current == id => {
+                return Err(format!("todo {} is already below todo {}", parent, id))
+            }
+            Some(current) => {
+                ancestor = todos
+                    .iter()
+                    .find(|t| t.id == current)
+                    .and_then(|t| t.parent)
+            }
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, day, 17, 0, 0).unwrap()
+    }
+
+    fn todo(id: usize, parent: Option<usize>, due: Option<u32>) -> Todo {
+        let mut todo = Todo::new(format!("todo {}", id), "work".to_string(), day(1));
+        todo.id = id;
+        todo.parent = parent;
+        todo.due = due.map(day);
+        todo
+    }
+
+    fn done(mut todo: Todo) -> Todo {
+        todo.set_status(TodoStatus::Done, day(1));
+        todo
+    }
+
+    fn rolled(todos: &[Todo]) -> Rollup {
+        rollup(&todos[0], todos)
+    }
+
+    fn by(due: u32, driver: Option<usize>) -> Rollup {
+        Rollup {
+            due: Some(day(due)),
+            driver,
+        }
+    }
+
+    #[test]
+    fn without_dated_subtasks_the_own_date_stands() {
+        let todos = [todo(1, None, Some(10)), todo(2, Some(1), None)];
+        assert_eq!(rolled(&todos), by(10, None));
+        let undated = [todo(1, None, None), todo(2, Some(1), None)];
+        assert_eq!(
+            rolled(&undated),
+            Rollup {
+                due: None,
+                driver: None
+            }
+        );
+    }
+
+    #[test]
+    fn the_earliest_of_several_dated_subtasks_drives() {
+        let todos = [
+            todo(1, None, Some(10)),
+            todo(2, Some(1), Some(8)),
+            todo(3, Some(1), Some(5)),
+            todo(4, Some(1), Some(12)),
+        ];
+        assert_eq!(rolled(&todos), by(5, Some(3)));
+        assert_eq!(effective_due(&todos[0], &todos), Some(day(5)));
+        // an undated parent takes the earliest subtask date
+        let mut undated = todos.clone();
+        undated[0].due = None;
+        assert_eq!(rolled(&undated), by(5, Some(3)));
+    }
+
+    #[test]
+    fn done_subtasks_no_longer_count() {
+        let todos = [
+            todo(1, None, Some(10)),
+            done(todo(2, Some(1), Some(5))),
+            todo(3, Some(1), Some(8)),
+        ];
+        assert_eq!(rolled(&todos), by(8, Some(3)));
+        let all_done = [
+            todo(1, None, Some(10)),
+            done(todo(2, Some(1), Some(5))),
+            done(todo(3, Some(1), Some(8))),
+        ];
+        assert_eq!(rolled(&all_done), by(10, None));
+    }
+
+    #[test]
+    fn ties_keep_the_own_date_or_the_lowest_subtask() {
+        let own = [todo(1, None, Some(10)), todo(2, Some(1), Some(10))];
+        assert_eq!(rolled(&own), by(10, None));
+        let subtasks = [
+            todo(1, None, Some(10)),
+            todo(3, Some(1), Some(5)),
+            todo(2, Some(1), Some(5)),
+        ];
+        assert_eq!(rolled(&subtasks), by(5, Some(2)));
+    }
+
+    #[test]
+    fn a_nested_subtask_rolls_up_through_its_parent() {
+        let todos = [
+            todo(1, None, Some(10)),
+            todo(2, Some(1), Some(8)),
+            todo(3, Some(2), None),
+            todo(4, Some(3), Some(4)),
+        ];
+        assert_eq!(rolled(&todos), by(4, Some(4)));
+        assert_eq!(rollup(&todos[1], &todos), by(4, Some(4)));
+        // under a done subtask nothing counts
+        let mut closed = todos.clone();
+        closed[1] = done(closed[1].clone());
+        assert_eq!(rolled(&closed), by(10, None));
+    }
+
+    #[test]
+    fn a_loop_in_the_file_does_not_hang() {
+        let mut todos = [todo(1, Some(2), Some(10)), todo(2, Some(1), Some(9))];
+        assert_eq!(rolled(&todos), by(9, Some(2)));
+        todos[1].due = Some(day(9) + Duration::days(3));
+        assert_eq!(rolled(&todos), by(10, None));
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::Todo;
+
+const STOPWORDS: [&str; 24] = [
+    "the", "and", "for", "with", "from", "into", "onto", "about", "this", "that", "then", "than",
+    "some", "more", "new", "fix", "add", "get", "make", "do", "to", "a", "an", "of",
+];
+
+pub struct Suggestion {
+    pub category: String,
+    /// The title word that led to the suggestion.
+    pub word: String,
+}
+
+/// Word to category counts over the existing todos, built once per load so
+/// suggesting is a cheap lookup.
+#[derive(Default)]
+pub struct CategoryIndex {
+    words: HashMap<String, BTreeMap<String, usize>>,
+}
+
+impl CategoryIndex {
+    pub fn build(todos: &[Todo]) -> CategoryIndex {
+        let mut index = CategoryIndex::default();
+        for todo in todos.iter().filter(|t| !t.category.is_empty()) {
+            for word in significant_words(&todo.title) {
+                *index
+                    .words
+                    .entry(word)
+                    .or_default()
+                    .entry(todo.category.clone())
+                    .or_insert(0) += 1;
+            }
+        }
+        index
+    }
+
+    /// The most frequent category among todos sharing a significant word
+    /// with `title`. Ties go to the earlier word, then the category name.
+    pub fn suggest(&self, title: &str) -> Option<Suggestion> {
+        let mut best: Option<(usize, Suggestion)> = None;
+        for word in significant_words(title) {
+            let categories = match self.words.get(&word) {
+                Some(categories) => categories,
+                None => continue,
+            };
+            // BTreeMap iterates by name, so max_by_key keeps the last of equal
+            // counts; reverse to keep the alphabetically first instead
+            if let Some((category, count)) = categories.iter().rev().max_by_key(|(_, c)| **c) {
+                if best.as_ref().is_none_or(|(c, _)| count > c) {
+                    best = Some((
+                        *count,
+                        Suggestion {
+                            category: category.clone(),
+                            word: word.clone(),
+                        },
+                    ));
+                }
+            }
+        }
+        best.map(|(_, suggestion)| suggestion)
+    }
+}
+
+fn significant_words(title: &str) -> Vec<String> {
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|w| w.chars().count() >= 3 && !STOPWORDS.contains(&w.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn todos(items: &[(&str, &str)]) -> Vec<Todo> {
+        items
+            .iter()
+            .map(|(title, category)| Todo::new(title.to_string(), category.to_string(), Utc::now()))
+            .collect()
+    }
+
+    fn suggested(index: &CategoryIndex, title: &str) -> Option<(String, String)> {
+        index.suggest(title).map(|s| (s.category, s.word))
+    }
+
+    #[test]
+    fn stopwords_and_short_words_are_not_significant() {
+        assert_eq!(
+            significant_words("Fix the CI-deploy for v2, ok?"),
+            ["deploy"]
+        );
+        assert_eq!(significant_words("Über Straße"), ["über", "straße"]);
+    }
+
+    #[test]
+    fn the_most_frequent_category_wins() {
+        let index = CategoryIndex::build(&todos(&[
+            ("Deploy the API", "work"),
+            ("Deploy staging", "work"),
+            ("Deploy the blog", "side"),
+            ("Water the plants", "home"),
+            ("Uncategorised deploy", ""),
+        ]));
+        assert_eq!(
+            suggested(&index, "deploy docs"),
+            Some(("work".to_string(), "deploy".to_string()))
+        );
+        assert_eq!(
+            suggested(&index, "PLANTS again"),
+            Some(("home".to_string(), "plants".to_string()))
+        );
+        assert_eq!(suggested(&index, "call the bank"), None);
+        assert_eq!(suggested(&CategoryIndex::default(), "deploy"), None);
+    }
+
+    #[test]
+    fn ties_go_to_the_earlier_word_then_the_category_name() {
+        let index = CategoryIndex::build(&todos(&[
+            ("invoice", "work"),
+            ("invoice", "admin"),
+            ("garden", "home"),
+            ("garden", "home"),
+            ("budget", "home"),
+        ]));
+        // both "invoice" categories count 1, the first by name is kept
+        assert_eq!(
+            suggested(&index, "invoice"),
+            Some(("admin".to_string(), "invoice".to_string()))
+        );
+        // "garden" counts more than "budget", wherever it comes
+        assert_eq!(
+            suggested(&index, "budget garden"),
+            Some(("home".to_string(), "garden".to_string()))
+        );
+        assert_eq!(
+            suggested(&index, "budget invoice"),
+            Some(("home".to_string(), "budget".to_string()))
+        );
+    }
+}
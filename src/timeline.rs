@@ -0,0 +1,379 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::Paragraph,
+    Frame,
+};
+
+use crate::chrome::Chrome;
+use crate::timetracking::{format_duration, local_midnight, TimeEntry};
+use crate::zone::{self, Zone};
+
+/// Where an entry lands on the bar: its first column, how many columns it
+/// covers and the row it is stacked on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Segment {
+    pub column: u16,
+    pub width: u16,
+    pub lane: usize,
+}
+
+/// Maps each `(start, end)` onto `width` columns spanning `from` to `to`.
+/// Every span gets at least one column, and spans that would share a
+/// column go to the first lane that is free there.
+pub fn layout(
+    spans: &[(DateTime<Utc>, DateTime<Utc>)],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    width: u16,
+) -> Vec<Segment> {
+    let total = (to - from).num_seconds().max(1) as f64;
+    let column = |at: DateTime<Utc>| {
+        let offset = (at - from).num_seconds().clamp(0, total as i64) as f64;
+        ((offset / total) * width as f64).floor() as u16
+    };
+    // the column after the last one taken, per lane
+    let mut lanes: Vec<u16> = vec![];
+    spans
+        .iter()
+        .map(|(start, end)| {
+            let first = column(*start).min(width.saturating_sub(1));
+            let width = column(*end).saturating_sub(first).max(1);
+            let lane = match lanes.iter().position(|free| *free <= first) {
+                Some(lane) => lane,
+                None => {
+                    lanes.push(0);
+                    lanes.len() - 1
+                }
+            };
+            lanes[lane] = first + width;
+            Segment {
+                column: first,
+                width,
+                lane,
+            }
+        })
+        .collect()
+}
+
+/// The entries that touch `day` in `zone`, oldest first.
+pub fn day_entries(
+    entries: &[TimeEntry],
+    day: NaiveDate,
+    zone: Zone,
+    now: DateTime<Utc>,
+) -> Vec<TimeEntry> {
+    let (from, to) = (
+        local_midnight(day, zone),
+        local_midnight(day + Duration::days(1), zone),
+    );
+    let mut day: Vec<TimeEntry> = entries
+        .iter()
+        .filter(|e| e.start < to && e.end.unwrap_or(now) > from)
+        .cloned()
+        .collect();
+    day.sort_by_key(|e| e.start);
+    day
+}
+
+/// The working hours of `day`, stretched to take in every entry.
+fn bounds(
+    entries: &[TimeEntry],
+    day: NaiveDate,
+    hours: [u32; 2],
+    zone: Zone,
+    now: DateTime<Utc>,
+) -> (DateTime<Utc>, DateTime<Utc>) {
+    let midnight = local_midnight(day, zone);
+    let from = entries
+        .iter()
+        .map(|e| e.start.max(midnight))
+        .fold(midnight + Duration::hours(hours[0] as i64), DateTime::min);
+    let next = local_midnight(day + Duration::days(1), zone);
+    let to = entries
+        .iter()
+        .map(|e| e.end.unwrap_or(now).min(next))
+        .fold(midnight + Duration::hours(hours[1] as i64), DateTime::max);
+    (from, to)
+}
+
+/// The same color for a category every time.
+fn category_color(category: &str) -> Color {
+    const COLORS: [Color; 6] = [
+        Color::Cyan,
+        Color::Green,
+        Color::Magenta,
+        Color::Blue,
+        Color::Red,
+        Color::LightYellow,
+    ];
+    let hash = category.bytes().fold(0usize, |hash, b| {
+        hash.wrapping_mul(31).wrapping_add(b as usize)
+    });
+    COLORS[hash % COLORS.len()]
+}
+
+/// One day as a bar over the working hours with an hour scale above and
+/// the `selected` entry described below.
+#[allow(clippy::too_many_arguments)]
+pub fn render_timeline<B: Backend>(
+    rect: &mut Frame<B>,
+    entries: &[TimeEntry],
+    day: NaiveDate,
+    hours: [u32; 2],
+    selected: usize,
+    now: DateTime<Utc>,
+    chrome: Chrome,
+    area: Rect,
+) {
+    let width = area.width.saturating_sub(chrome.frame(false).0);
+    let zone = zone::current();
+    let (from, to) = bounds(entries, day, hours, zone, now);
+    let spans: Vec<_> = entries
+        .iter()
+        .map(|e| (e.start.max(from), e.end.unwrap_or(now).min(to)))
+        .collect();
+    let segments = layout(&spans, from, to, width);
+
+    let mut scale = vec![' '; width as usize];
+    let mut hour = from.with_timezone(&zone).date_naive().and_hms_opt(0, 0, 0);
+    let total = (to - from).num_seconds().max(1);
+    while let Some(at) = hour.and_then(|h| zone.from_local_datetime(&h).earliest()) {
+        let at = at.with_timezone(&Utc);
+        if at >= to {
+            break;
+        }
+        if at >= from {
+            let column = ((at - from).num_seconds() * width as i64 / total) as usize;
+            let label = at.with_timezone(&zone).format("%H").to_string();
+            for (i, c) in label.chars().enumerate() {
+                if let Some(cell) = scale.get_mut(column + i) {
+                    *cell = c;
+                }
+            }
+        }
+        hour = hour.map(|h| h + Duration::hours(1));
+    }
+    let mut lines = vec![Spans::from(vec![Span::styled(
+        scale.into_iter().collect::<String>(),
+        Style::default().fg(Color::DarkGray),
+    )])];
+
+    let lanes = segments.iter().map(|s| s.lane + 1).max().unwrap_or(1);
+    for lane in 0..lanes {
+        let mut row = vec![];
+        let mut at = 0;
+        let mut on_lane: Vec<(usize, &Segment)> = segments
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.lane == lane)
+            .collect();
+        on_lane.sort_by_key(|(_, s)| s.column);
+        for (i, segment) in on_lane {
+            if segment.column > at {
+                row.push(Span::styled(
+                    "·".repeat((segment.column - at) as usize),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            let style = if i == selected {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(category_color(&entries[i].category))
+            };
+            let fill = if i == selected { "▓" } else { "█" };
+            row.push(Span::styled(fill.repeat(segment.width as usize), style));
+            at = segment.column + segment.width;
+        }
+        if at < width {
+            row.push(Span::styled(
+                "·".repeat((width - at) as usize),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        lines.push(Spans::from(row));
+    }
+
+    lines.push(Spans::from(vec![Span::raw("")]));
+    lines.push(match entries.get(selected) {
+        Some(entry) => Spans::from(vec![
+            Span::styled(
+                format!(
+                    "{}-{} {} ",
+                    entry.start.with_timezone(&zone).format("%H:%M"),
+                    match entry.end {
+                        Some(end) => end.with_timezone(&zone).format("%H:%M").to_string(),
+                        None => "running".to_string(),
+                    },
+                    format_duration(entry.end.unwrap_or(now) - entry.start)
+                ),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(
+                entry.category.clone(),
+                Style::default().fg(category_color(&entry.category)),
+            ),
+            Span::raw(format!(" {}", entry.note.clone().unwrap_or_default())),
+        ]),
+        None => Spans::from(vec![Span::styled(
+            "nothing tracked this day",
+            Style::default().fg(Color::DarkGray),
+        )]),
+    });
+
+    let paragraph = Paragraph::new(lines).block(chrome.block().title(format!(
+        "Timeline {} - j/k: entry, h/l: day, T: table",
+        day.format("%a %Y-%m-%d")
+    )));
+    rect.render_widget(paragraph, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UTC: Zone = Zone::Named(chrono_tz::UTC);
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, hour, minute, 0).unwrap()
+    }
+
+    fn entry(id: usize, start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> TimeEntry {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "category": "work",
+            "start": start,
+            "end": end,
+        }))
+        .unwrap()
+    }
+
+    fn segment(column: u16, width: u16, lane: usize) -> Segment {
+        Segment {
+            column,
+            width,
+            lane,
+        }
+    }
+
+    #[test]
+    fn times_map_onto_columns_at_any_width() {
+        let spans = [(at(9, 0), at(11, 0)), (at(13, 0), at(17, 0))];
+        assert_eq!(
+            layout(&spans, at(9, 0), at(17, 0), 8),
+            [segment(0, 2, 0), segment(4, 4, 0)]
+        );
+        assert_eq!(
+            layout(&spans, at(9, 0), at(17, 0), 80),
+            [segment(0, 20, 0), segment(40, 40, 0)]
+        );
+        // rounding down, the gap between them stays visible
+        assert_eq!(
+            layout(&spans, at(9, 0), at(17, 0), 7),
+            [segment(0, 1, 0), segment(3, 4, 0)]
+        );
+    }
+
+    #[test]
+    fn a_short_entry_still_takes_a_column() {
+        let spans = [(at(9, 0), at(9, 1)), (at(16, 59), at(17, 0))];
+        assert_eq!(
+            layout(&spans, at(9, 0), at(17, 0), 8),
+            [segment(0, 1, 0), segment(7, 1, 0)]
+        );
+        // even at the very end of the bar
+        assert_eq!(
+            layout(&[(at(17, 0), at(17, 0))], at(9, 0), at(17, 0), 8),
+            [segment(7, 1, 0)]
+        );
+    }
+
+    #[test]
+    fn overlapping_entries_stack_on_the_first_free_lane() {
+        let spans = [
+            (at(9, 0), at(13, 0)),
+            (at(10, 0), at(11, 0)),
+            (at(10, 30), at(12, 0)),
+            (at(13, 0), at(14, 0)),
+            (at(12, 0), at(15, 0)),
+        ];
+        assert_eq!(
+            layout(&spans, at(9, 0), at(17, 0), 8),
+            [
+                segment(0, 4, 0),
+                segment(1, 1, 1),
+                segment(1, 2, 2),
+                segment(4, 1, 0),
+                segment(3, 3, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_zero_length_day_does_not_divide_by_zero() {
+        assert_eq!(
+            layout(&[(at(9, 0), at(9, 0))], at(9, 0), at(9, 0), 10),
+            [segment(0, 1, 0)]
+        );
+        assert_eq!(
+            layout(&[(at(9, 0), at(10, 0))], at(9, 0), at(17, 0), 0)[0].width,
+            1
+        );
+    }
+
+    #[test]
+    fn the_day_takes_entries_touching_it_and_the_running_one() {
+        let day = at(0, 0).date_naive();
+        let now = at(15, 0);
+        let entries = [
+            entry(1, at(13, 0), None),
+            entry(2, at(9, 0), Some(at(10, 0))),
+            entry(3, at(0, 0) - Duration::hours(1), Some(at(0, 30))),
+            entry(
+                4,
+                at(0, 0) - Duration::hours(3),
+                Some(at(0, 0) - Duration::hours(2)),
+            ),
+            entry(5, at(0, 0) + Duration::days(1), None),
+        ];
+        let ids: Vec<usize> = day_entries(&entries, day, UTC, now)
+            .iter()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(ids, [3, 2, 1]);
+    }
+
+    #[test]
+    fn the_working_hours_stretch_to_take_in_every_entry() {
+        let day = at(0, 0).date_naive();
+        let now = at(20, 0);
+        assert_eq!(bounds(&[], day, [9, 17], UTC, now), (at(9, 0), at(17, 0)));
+
+        let entries = [
+            entry(1, at(7, 30), Some(at(8, 0))),
+            // the running timer extends the bar up to now
+            entry(2, at(16, 0), None),
+        ];
+        assert_eq!(
+            bounds(&entries, day, [9, 17], UTC, now),
+            (at(7, 30), at(20, 0))
+        );
+
+        // but never past the day itself
+        let overnight = [entry(
+            1,
+            at(0, 0) - Duration::hours(2),
+            Some(at(0, 0) + Duration::days(1) + Duration::hours(1)),
+        )];
+        assert_eq!(
+            bounds(&overnight, day, [9, 17], UTC, now),
+            (at(0, 0), at(0, 0) + Duration::days(1))
+        );
+    }
+}
@@ -0,0 +1,512 @@
+use chrono::prelude::*;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::{self, Config};
+use crate::pomodoro::{self, Outcome, Pomodoro};
+use crate::refs;
+use crate::timetracking::{
+    self, format_clock, format_duration, parse_duration, ConfigDuration, EntryKind, TimeEntry,
+};
+use crate::{cli, todo_ids};
+
+pub const DEFAULT_CATEGORY: &str = "timer";
+const REFRESH: Duration = Duration::from_millis(200);
+/// Refreshes between looks at the entry file for a break started elsewhere.
+const BREAK_POLL: u32 = 5;
+
+/// Runs `timer DURATION [LABEL] [--category NAME] [--todo ID]`: a
+/// countdown on one refreshing line, tracked as a running time entry that
+/// is closed when it ends. Every run lands in the pomodoro history, with
+/// the times Ctrl+C was pressed. A break started in the TUI ends the run
+/// as stopped at the break. With `auto_break` in the config a recorded run
+/// is followed by a break countdown. Returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let mut length = None;
+    let mut label = vec![];
+    let mut category = DEFAULT_CATEGORY.to_string();
+    let mut todo_id = None;
+    let mut tags: Option<Vec<String>> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--category" => match args.next() {
+                Some(value) => category = value.clone(),
+                None => return cli::usage("--category needs a value"),
+            },
+            "--tag" => match args.next() {
+                Some(value) => tags.get_or_insert_with(Vec::new).push(value.clone()),
+                None => return cli::usage("--tag needs a value"),
+            },
+            "--todo" => match args.next().map(|value| value.parse::<usize>()) {
+                Some(Ok(id)) => todo_id = Some(id),
+                _ => return cli::usage("--todo needs a todo id"),
+            },
+            _ if length.is_none() => match parse_duration(arg) {
+                Some(parsed) => length = Some(parsed),
+                None => {
+                    return cli::usage(format!(
+                        "invalid duration '{}', use e.g. 25m, 1h30m or 90s",
+                        arg
+                    ))
+                }
+            },
+            _ => label.push(arg.as_str()),
+        }
+    }
+    let length = match length {
+        Some(length) => length,
+        None => {
+            return cli::usage(
+                "usage: timer DURATION [LABEL] [--category NAME] [--todo ID] [--tag TAG]...",
+            )
+        }
+    };
+    let label = label.join(" ");
+    if todo_id.is_none() {
+        todo_id = match todo_ids() {
+            Ok(ids) => refs::linked_todo(&label, &ids),
+            Err(err) => return cli::fail(&err),
+        };
+    }
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    if let Err(err) = signal_hook::flag::register(signal_hook::SIGINT, Arc::clone(&interrupted)) {
+        return cli::other(format!("can't catch Ctrl+C: {}", err));
+    }
+
+    let start = Utc::now();
+    let end = start + length;
+    let note = if label.is_empty() {
+        None
+    } else {
+        Some(label.clone())
+    };
+    let entry_id = match timetracking::start_entry(&category, start, note, todo_id, tags) {
+        Ok(id) => id,
+        Err(err) => return cli::fail(&err),
+    };
+    let mut stdout = io::stdout();
+    let mut interruptions = 0;
+    let mut polls = 0;
+    let mut on_break = None;
+    let outcome = loop {
+        loop {
+            let now = Utc::now();
+            if interrupted.load(Ordering::Relaxed) || now >= end {
+                break;
+            }
+            polls += 1;
+            if polls % BREAK_POLL == 0 {
+                on_break = ended_elsewhere(entry_id);
+                if on_break.is_some() {
+                    break;
+                }
+            }
+            // the padding clears leftovers when the line gets shorter
+            print!("\r{} {}    ", countdown(end, now), label);
+            let _ = stdout.flush();
+            thread::sleep(REFRESH);
+        }
+        println!();
+        if on_break.is_some() {
+            break Outcome::Stopped;
+        }
+        if !interrupted.load(Ordering::Relaxed) {
+            print!("\x07");
+            notify(&label, length);
+            break Outcome::Completed;
+        }
+
+        // the clock keeps running while the question is open
+        interruptions += 1;
+        eprint!(
+            "stopped after {}, record it? [y/n, c to continue] ",
+            format_duration(Utc::now().min(end) - start)
+        );
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer).is_err() {
+            break Outcome::Abandoned;
+        }
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "c" => interrupted.store(false, Ordering::Relaxed),
+            "y" => break Outcome::Stopped,
+            _ => break Outcome::Abandoned,
+        }
+    };
+
+    let stopped = on_break.unwrap_or_else(|| Utc::now().min(end));
+    let finished = match (outcome, on_break) {
+        (Outcome::Abandoned, _) => timetracking::discard_entry(entry_id),
+        // the work interval ends here, the break mustn't restart it
+        (_, Some(_)) => timetracking::forget_paused(entry_id).map(|_| ()),
+        _ => timetracking::finish_entry(entry_id, stopped),
+    };
+    if let Err(err) = finished {
+        return cli::fail(&err);
+    }
+    // only once the entry is settled, so the history never counts a
+    // pomodoro whose time went unrecorded
+    let pomodoro = Pomodoro {
+        start,
+        planned_secs: length.num_seconds(),
+        outcome,
+        interruptions,
+    };
+    if let Err(err) = pomodoro::append(pomodoro) {
+        return cli::fail(&err);
+    }
+    if outcome == Outcome::Abandoned {
+        return 130;
+    }
+    if on_break.is_some() {
+        println!(
+            "on a break, recorded {} of {} as entry {}",
+            format_duration(stopped - start),
+            category,
+            entry_id
+        );
+        return 0;
+    }
+
+    println!(
+        "recorded {} of {} as entry {}",
+        format_duration(stopped - start),
+        category,
+        entry_id
+    );
+    match config::load() {
+        Ok(Config {
+            auto_break: Some(ConfigDuration(length)),
+            log_breaks,
+            ..
+        }) => {
+            interrupted.store(false, Ordering::Relaxed);
+            let task = if label.is_empty() { &category } else { &label };
+            take_break(length, task, log_breaks, &interrupted)
+        }
+        Ok(_) => 0,
+        Err(err) => cli::fail(&err),
+    }
+}
+
+/// What is left until `end` at `now`, worked out from the two each time
+/// rather than counted down per refresh, so late or missed refreshes can't
+/// make it drift. Rounded up, so a 25m timer starts at 25:00 and ends at
+/// 00:01.
+fn countdown(end: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    format_clock(end - now + chrono::Duration::milliseconds(999))
+}
+
+/// Why an auto-break ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BreakEnd {
+    /// It ran its length.
+    Over,
+    /// Ctrl+C was pressed.
+    Stopped,
+    /// A work timer started at this time.
+    WorkStarted(DateTime<Utc>),
+    /// `auto_break` was taken out of the config.
+    Disabled,
+}
+
+/// How an auto-break due to end at `end` stands at `now`, None while it
+/// goes on. Starting work or turning `auto_break` off ends it early.
+fn break_step(
+    now: DateTime<Utc>,
+    end: DateTime<Utc>,
+    work_started: Option<DateTime<Utc>>,
+    enabled: bool,
+    interrupted: bool,
+) -> Option<BreakEnd> {
+    if interrupted {
+        Some(BreakEnd::Stopped)
+    } else if let Some(at) = work_started {
+        Some(BreakEnd::WorkStarted(at))
+    } else if !enabled {
+        Some(BreakEnd::Disabled)
+    } else if now >= end {
+        Some(BreakEnd::Over)
+    } else {
+        None
+    }
+}
+
+/// When the first work entry still running started, if that was at or
+/// after `since`.
+fn work_started_since(entries: &[TimeEntry], since: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    entries
+        .iter()
+        .filter(|e| e.kind == EntryKind::Work && e.end.is_none() && e.start >= since)
+        .map(|e| e.start)
+        .min()
+}
+
+/// Counts down the break `auto_break` chains after a work interval on
+/// `task` and rings when it is over. With `log` the break is recorded as a
+/// break entry, up to where it ended. Returns the process exit code.
+fn take_break(length: chrono::Duration, task: &str, log: bool, interrupted: &AtomicBool) -> i32 {
+    let start = Utc::now();
+    let end = start + length;
+    let label = format!("break after {}", task);
+    let mut stdout = io::stdout();
+    let mut polls = 0;
+    let mut work_started = None;
+    let mut enabled = true;
+    let (ended, now) = loop {
+        let now = Utc::now();
+        let step = break_step(
+            now,
+            end,
+            work_started,
+            enabled,
+            interrupted.load(Ordering::Relaxed),
+        );
+        if let Some(ended) = step {
+            break (ended, now);
+        }
+        polls += 1;
+        if polls % BREAK_POLL == 0 {
+            work_started = timetracking::read_time_entries()
+                .ok()
+                .and_then(|entries| work_started_since(&entries, start));
+            // a config that doesn't parse mid-edit leaves the break running
+            enabled = !matches!(
+                config::load(),
+                Ok(Config {
+                    auto_break: None,
+                    ..
+                })
+            );
+        }
+        print!("\r{} {}    ", countdown(end, now), label);
+        let _ = stdout.flush();
+        thread::sleep(REFRESH);
+    };
+    println!();
+    let stopped = match ended {
+        BreakEnd::Over => {
+            print!("\x07");
+            send_notification(
+                "Break over",
+                &format!("{} {} is up", format_duration(length), label),
+            );
+            println!("break over");
+            end
+        }
+        BreakEnd::WorkStarted(at) => {
+            println!("break ended, work started");
+            at
+        }
+        BreakEnd::Disabled => {
+            println!("break cancelled, auto_break is off");
+            now
+        }
+        BreakEnd::Stopped => {
+            println!("break stopped");
+            now
+        }
+    };
+    if !log {
+        return 0;
+    }
+    match timetracking::log_break(start, stopped, Some(label)) {
+        Ok(()) => {
+            println!("recorded {} of break", format_duration(stopped - start));
+            0
+        }
+        Err(err) => cli::fail(&err),
+    }
+}
+
+/// When the entry was ended by someone else, i.e. a break paused it.
+fn ended_elsewhere(entry_id: usize) -> Option<DateTime<Utc>> {
+    timetracking::read_time_entries()
+        .ok()?
+        .into_iter()
+        .find(|e| e.id == entry_id)?
+        .end
+}
+
+fn notify(label: &str, length: chrono::Duration) {
+    let body = if label.is_empty() {
+        format!("{} is up", format_duration(length))
+    } else {
+        format!("{} is up: {}", format_duration(length), label)
+    };
+    send_notification("Timer done", &body);
+}
+
+/// Best effort desktop notification; callers ring the bell as well in
+/// case this isn't available.
+pub fn send_notification(summary: &str, body: &str) {
+    let _ = Command::new("notify-send")
+        .arg(summary)
+        .arg(body)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Refreshes as they come: on time, late, in a burst and after a stall.
+    const STEPS_MS: [i64; 8] = [200, 200, 1_350, 7, 3, 200, 59_000, 421];
+
+    fn at(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, 9, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn the_countdown_stays_exact_however_irregular_the_refreshes() {
+        let end = at(25);
+        assert_eq!(countdown(end, at(0)), "25:00");
+        let mut now = at(0);
+        for step in STEPS_MS.iter().cycle() {
+            now += chrono::Duration::milliseconds(*step);
+            if now >= end {
+                break;
+            }
+            let left = ((end - now).num_milliseconds() + 999) / 1000;
+            assert_eq!(
+                countdown(end, now),
+                format!("{:02}:{:02}", left / 60, left % 60),
+                "at {}",
+                now
+            );
+        }
+        assert_eq!(
+            countdown(end, end - chrono::Duration::milliseconds(1)),
+            "00:01"
+        );
+        assert_eq!(countdown(end, end), "00:00");
+        assert_eq!(countdown(end, end + chrono::Duration::minutes(1)), "00:00");
+        assert_eq!(
+            countdown(at(0) + chrono::Duration::hours(2), at(0)),
+            "2:00:00"
+        );
+    }
+
+    fn entry(id: usize, kind: &str, start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> TimeEntry {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "category": "work",
+            "start": start,
+            "end": end,
+            "kind": kind,
+        }))
+        .unwrap()
+    }
+
+    /// Steps a 5m break that started at 9:30 through the minutes, with
+    /// `entries` as the entry file reads at each and auto_break on until
+    /// `disabled_at`. Returns how and when it ended.
+    fn take(
+        entries: impl Fn(DateTime<Utc>) -> Vec<TimeEntry>,
+        disabled_at: Option<DateTime<Utc>>,
+    ) -> (BreakEnd, DateTime<Utc>) {
+        let (start, end) = (at(30), at(35));
+        let mut now = start;
+        loop {
+            let work = work_started_since(&entries(now), start);
+            let enabled = disabled_at.is_none_or(|off| now < off);
+            if let Some(ended) = break_step(now, end, work, enabled, false) {
+                return (ended, now);
+            }
+            now += chrono::Duration::seconds(30);
+        }
+    }
+
+    #[test]
+    fn a_break_left_alone_runs_its_length() {
+        // the work interval before it was stopped at 9:30
+        let stopped = entry(1, "work", at(5), Some(at(30)));
+        assert_eq!(
+            take(|_| vec![stopped.clone()], None),
+            (BreakEnd::Over, at(35))
+        );
+    }
+
+    #[test]
+    fn starting_work_ends_the_break_where_the_work_started() {
+        let started = |now: DateTime<Utc>| {
+            let mut entries = vec![entry(1, "work", at(5), Some(at(30)))];
+            if now >= at(32) {
+                entries.push(entry(2, "work", at(32), None));
+            }
+            entries
+        };
+        assert_eq!(take(started, None), (BreakEnd::WorkStarted(at(32)), at(32)));
+    }
+
+    #[test]
+    fn only_work_started_during_the_break_ends_it() {
+        let since = at(30);
+        let entries = [
+            // running since before the break, e.g. in another terminal
+            entry(1, "work", at(10), None),
+            entry(2, "work", at(31), Some(at(32))),
+            entry(3, "break", at(31), None),
+        ];
+        assert_eq!(work_started_since(&entries, since), None);
+        let mut more = entries.to_vec();
+        more.push(entry(4, "work", at(33), None));
+        more.push(entry(5, "work", at(32), None));
+        assert_eq!(work_started_since(&more, since), Some(at(32)));
+        assert_eq!(work_started_since(&more, at(34)), None);
+    }
+
+    #[test]
+    fn turning_auto_break_off_cancels_the_break() {
+        assert_eq!(take(|_| vec![], Some(at(33))), (BreakEnd::Disabled, at(33)));
+    }
+
+    #[test]
+    fn ctrl_c_stops_the_break_whatever_else_happened() {
+        let end = at(35);
+        for (work, enabled, now) in [
+            (None, true, at(31)),
+            (Some(at(32)), true, at(33)),
+            (None, false, at(33)),
+            (None, true, at(40)),
+        ] {
+            assert_eq!(
+                break_step(now, end, work, enabled, true),
+                Some(BreakEnd::Stopped)
+            );
+        }
+        // starting work wins over the config and the clock
+        assert_eq!(
+            break_step(at(40), end, Some(at(34)), false, false),
+            Some(BreakEnd::WorkStarted(at(34)))
+        );
+        assert_eq!(
+            break_step(at(40), end, None, false, false),
+            Some(BreakEnd::Disabled)
+        );
+        assert_eq!(break_step(at(34), end, None, true, false), None);
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn a_logged_break_is_a_break_entry_that_counts_no_work() {
+        crate::app::tests::with_app("timer-break", serde_json::json!([]), |_| {
+            timetracking::log_break(at(30), at(33), Some("break after report".to_string()))
+                .unwrap();
+            let entries = timetracking::read_time_entries().unwrap();
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].kind, EntryKind::Break);
+            assert_eq!(entries[0].end, Some(at(33)));
+            assert_eq!(entries[0].note.as_deref(), Some("break after report"));
+            assert!(timetracking::time_per_category(&entries, at(0), at(59), at(59)).is_empty());
+        });
+    }
+}
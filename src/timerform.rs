@@ -0,0 +1,210 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::complete::{Completer, Kind, Vocabulary};
+use crate::timer::DEFAULT_CATEGORY;
+use crate::widgets::{centered_rect, suggestions_line, Input};
+
+pub enum TimerFormAction {
+    Continue,
+    Cancel,
+    Submit { name: String, category: String },
+}
+
+const FIELD_LABELS: [&str; 2] = ["Name", "Category"];
+
+/// Form opened with `a` on the Timers tab to add a named timer. An empty
+/// category is the one `timer` uses.
+pub struct TimerForm {
+    focus: usize,
+    name: Input,
+    category: Input,
+    error: Option<String>,
+    vocabulary: Vocabulary,
+    completer: Completer,
+}
+
+impl TimerForm {
+    pub fn new(vocabulary: Vocabulary) -> TimerForm {
+        TimerForm {
+            focus: 0,
+            name: Input::default(),
+            category: Input::default(),
+            error: None,
+            vocabulary,
+            completer: Completer::default(),
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> TimerFormAction {
+        match key.code {
+            KeyCode::Esc => return TimerFormAction::Cancel,
+            KeyCode::Enter => match self.name.value().trim() {
+                "" => self.error = Some("the name can't be empty".to_string()),
+                name => {
+                    let category = match self.category.value().trim() {
+                        "" => DEFAULT_CATEGORY,
+                        category => category,
+                    };
+                    return TimerFormAction::Submit {
+                        name: name.to_string(),
+                        category: category.to_string(),
+                    };
+                }
+            },
+            KeyCode::Tab | KeyCode::Down => {
+                let completed = self.focus == 1
+                    && self.completer.cycle(
+                        &mut self.category,
+                        0,
+                        self.vocabulary.candidates(Kind::Category),
+                    );
+                if !completed {
+                    self.completer.reset();
+                    self.focus = (self.focus + 1) % FIELD_LABELS.len();
+                }
+            }
+            KeyCode::BackTab | KeyCode::Up => {
+                self.completer.reset();
+                self.focus = (self.focus + FIELD_LABELS.len() - 1) % FIELD_LABELS.len()
+            }
+            _ => {
+                self.completer.reset();
+                self.error = None;
+                match self.focus {
+                    0 => self.name.handle_key(key),
+                    _ => self.category.handle_key(key),
+                };
+            }
+        }
+        TimerFormAction::Continue
+    }
+
+    fn input(&self, field: usize) -> &Input {
+        match field {
+            0 => &self.name,
+            _ => &self.category,
+        }
+    }
+}
+
+pub fn render_timer_form<B: Backend>(rect: &mut Frame<B>, form: &TimerForm, area: Rect) {
+    let popup = centered_rect(60, 30, area);
+    let mut lines = vec![Spans::from(vec![Span::raw("")])];
+    let mut cursor = None;
+
+    for (i, label) in FIELD_LABELS.iter().enumerate() {
+        let input = form.input(i);
+        let style = if i == form.focus {
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::White)
+        };
+        let prefix = format!(" {:<10}", format!("{}:", label));
+        if i == form.focus {
+            cursor = Some((
+                popup.x + 1 + prefix.chars().count() as u16 + input.cursor() as u16,
+                popup.y + 1 + lines.len() as u16,
+            ));
+        }
+        let value = if i == 1 && input.value().is_empty() {
+            Span::styled(
+                format!("{} (default)", DEFAULT_CATEGORY),
+                Style::default().fg(Color::DarkGray),
+            )
+        } else {
+            Span::styled(input.value().to_string(), Style::default().fg(Color::White))
+        };
+        lines.push(Spans::from(vec![Span::styled(prefix, style), value]));
+        if i == 1 && i == form.focus {
+            let found = form.completer.suggestions(
+                &form.category,
+                0,
+                form.vocabulary.candidates(Kind::Category),
+            );
+            if !found.is_empty() {
+                let mut line = suggestions_line(&found, form.completer.current());
+                line.0.insert(0, Span::raw(" ".repeat(11)));
+                lines.push(line);
+            }
+        }
+    }
+
+    lines.push(Spans::from(vec![Span::raw("")]));
+    lines.push(Spans::from(vec![Span::styled(
+        match &form.error {
+            Some(err) => err.clone(),
+            None => " Tab/Up/Down: field  Enter: add  Esc: cancel".to_string(),
+        },
+        Style::default().fg(if form.error.is_some() {
+            Color::Red
+        } else {
+            Color::DarkGray
+        }),
+    )]));
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title("New timer")
+            .border_type(BorderType::Plain),
+    );
+
+    rect.render_widget(Clear, popup);
+    rect.render_widget(paragraph, popup);
+    if let Some((x, y)) = cursor {
+        rect.set_cursor(x, y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn press(form: &mut TimerForm, code: KeyCode) -> TimerFormAction {
+        form.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn submitted(action: TimerFormAction) -> Option<(String, String)> {
+        match action {
+            TimerFormAction::Submit { name, category } => Some((name, category)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn a_timer_needs_a_name_and_falls_back_to_the_default_category() {
+        let mut form = TimerForm::new(Vocabulary::build(&[]));
+        assert_eq!(submitted(press(&mut form, KeyCode::Enter)), None);
+        assert_eq!(form.error.as_deref(), Some("the name can't be empty"));
+
+        for c in " focus ".chars() {
+            press(&mut form, KeyCode::Char(c));
+        }
+        assert!(form.error.is_none());
+        assert_eq!(
+            submitted(press(&mut form, KeyCode::Enter)),
+            Some(("focus".to_string(), DEFAULT_CATEGORY.to_string()))
+        );
+
+        press(&mut form, KeyCode::Tab);
+        for c in "deep".chars() {
+            press(&mut form, KeyCode::Char(c));
+        }
+        assert_eq!(
+            submitted(press(&mut form, KeyCode::Enter)),
+            Some(("focus".to_string(), "deep".to_string()))
+        );
+    }
+}
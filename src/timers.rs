@@ -0,0 +1,328 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use std::fs;
+use std::io;
+#[cfg(feature = "tui")]
+use tui::{
+    layout::Constraint,
+    style::{Color, Modifier, Style},
+    text::Span,
+    widgets::{Cell, Row, Table},
+};
+
+#[cfg(feature = "tui")]
+use crate::chrome::Chrome;
+#[cfg(feature = "tui")]
+use crate::cursor;
+use crate::lock::DbLock;
+use crate::timetracking::{self, TimeEntry};
+use crate::txn::transaction;
+use crate::{write_atomic, Error, Timer};
+
+pub const TIMERS_PATH: &str = "./data/timers.json";
+
+/// `Timer::elapsed` as whole seconds on disk.
+pub mod seconds {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(elapsed: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(elapsed.num_seconds())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let seconds = i64::deserialize(deserializer)?;
+        crate::timetracking::checked_seconds(seconds)
+            .ok_or_else(|| serde::de::Error::custom(format!("{} seconds is out of range", seconds)))
+    }
+}
+
+impl Timer {
+    pub fn is_running(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    /// The time counted at `now`, the running stretch included.
+    pub fn elapsed_at(&self, now: DateTime<Utc>) -> Duration {
+        match self.started_at {
+            Some(start) => self.elapsed + (now - start).max(Duration::zero()),
+            None => self.elapsed,
+        }
+    }
+
+    /// Ends the running stretch at `now`, adding it to the time counted.
+    /// Returns its start and end, None when the timer wasn't running.
+    fn close_stretch(&mut self, now: DateTime<Utc>) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let start = self.started_at.take()?;
+        let end = now.max(start);
+        self.elapsed = self.elapsed + (end - start);
+        Some((start, end))
+    }
+}
+
+/// Logs a stretch `timer` ran for as a finished entry in its category,
+/// noted with its name, for the TimeTracking tab to count.
+fn log_stretch(
+    entries: &mut Vec<TimeEntry>,
+    timer: &Timer,
+    stretch: Option<(DateTime<Utc>, DateTime<Utc>)>,
+) {
+    if let Some((start, end)) = stretch {
+        timetracking::push_work(
+            entries,
+            &timer.category,
+            start,
+            end,
+            Some(timer.name.clone()),
+        );
+    }
+}
+
+pub fn read_timers() -> Result<Vec<Timer>, Error> {
+    match fs::read_to_string(TIMERS_PATH) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Read-modify-write of the timers under the DB lock, like `update_db`.
+fn update_timers<T>(change: impl FnOnce(&mut Vec<Timer>) -> T) -> Result<T, Error> {
+    let _lock = DbLock::acquire()?;
+    let mut timers = read_timers()?;
+    let result = change(&mut timers);
+    write_atomic(TIMERS_PATH, &serde_json::to_string_pretty(&timers)?)?;
+    Ok(result)
+}
+
+/// Adds a stopped timer and returns its id.
+pub fn add(name: &str, category: &str, now: DateTime<Utc>) -> Result<usize, Error> {
+    update_timers(|timers| {
+        let id = timers.iter().map(|t| t.id).max().unwrap_or(0) + 1;
+        timers.push(Timer {
+            id,
+            name: name.to_string(),
+            category: category.to_string(),
+            created_at: now,
+            elapsed: Duration::zero(),
+            started_at: None,
+        });
+        id
+    })
+}
+
+/// Starts or resumes the timer `id` at `now`. Returns the timer, None
+/// when there is no such timer.
+pub fn start(id: usize, now: DateTime<Utc>) -> Result<Option<Timer>, Error> {
+    update_timers(|timers| {
+        let timer = timers.iter_mut().find(|t| t.id == id)?;
+        timer.started_at.get_or_insert(now);
+        Some(timer.clone())
+    })
+}
+
+/// Pauses the timer `id` at `now`, logging the stretch it ran for; the
+/// time counted stays for the next start. The timer and its entry are
+/// saved together. Returns the timer, None when there is no such timer.
+pub fn pause(id: usize, now: DateTime<Utc>) -> Result<Option<Timer>, Error> {
+    transaction(|txn| {
+        let timer = match txn.timers()?.iter_mut().find(|t| t.id == id) {
+            Some(timer) => timer,
+            None => return Ok(None),
+        };
+        let stretch = timer.close_stretch(now);
+        let timer = timer.clone();
+        log_stretch(&mut txn.entries, &timer, stretch);
+        Ok(Some(timer))
+    })
+}
+
+/// Stops the timer `id` at `now`, logging the stretch it ran for, and
+/// sets it back to zero. The timer and its entry are saved together.
+/// Returns the time it had counted, None when there is no such timer.
+pub fn stop(id: usize, now: DateTime<Utc>) -> Result<Option<Duration>, Error> {
+    transaction(|txn| {
+        let timer = match txn.timers()?.iter_mut().find(|t| t.id == id) {
+            Some(timer) => timer,
+            None => return Ok(None),
+        };
+        let stretch = timer.close_stretch(now);
+        let counted = std::mem::replace(&mut timer.elapsed, Duration::zero());
+        let timer = timer.clone();
+        log_stretch(&mut txn.entries, &timer, stretch);
+        Ok(Some(counted))
+    })
+}
+
+#[cfg(feature = "tui")]
+pub fn render_timers<'a>(timers: &[Timer], now: DateTime<Utc>, chrome: Chrome) -> Table<'a> {
+    let rows: Vec<Row> = timers
+        .iter()
+        .map(|timer| {
+            let elapsed = timer.elapsed_at(now);
+            let (state, color) = if timer.is_running() {
+                ("running", Color::Green)
+            } else if elapsed > Duration::zero() {
+                ("paused", Color::Yellow)
+            } else {
+                ("stopped", Color::DarkGray)
+            };
+            Row::new(vec![
+                Cell::from(Span::raw(timer.name.clone())),
+                Cell::from(Span::raw(timer.category.clone())),
+                Cell::from(Span::raw(timetracking::format_clock(elapsed))),
+                Cell::from(Span::styled(state, Style::default().fg(color))),
+            ])
+        })
+        .collect();
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let title = if timers.is_empty() {
+        "Timers - none yet, a: add one"
+    } else {
+        "Timers - s: start, p: pause, x: stop and log, a: add"
+    };
+    Table::new(rows)
+        .header(Row::new(vec![
+            Cell::from(Span::styled("Name", bold)),
+            Cell::from(Span::styled("Category", bold)),
+            Cell::from(Span::styled("Elapsed", bold)),
+            Cell::from(Span::styled("State", bold)),
+        ]))
+        .block(chrome.block().title(title))
+        .highlight_style(cursor::current().highlight())
+        .highlight_symbol(cursor::current().symbol())
+        .widths(&[
+            Constraint::Percentage(40), // name
+            Constraint::Percentage(25), // category
+            Constraint::Percentage(20), // elapsed
+            Constraint::Percentage(15), // state
+        ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timer_json(elapsed: &str) -> String {
+        format!(
+            r#"{{"id": 1, "name": "focus", "category": "work",
+                "created_at": "2024-05-01T09:00:00Z", "elapsed": {}}}"#,
+            elapsed
+        )
+    }
+
+    #[test]
+    fn elapsed_reads_back_as_seconds() {
+        let timer: Timer = serde_json::from_str(&timer_json("90")).unwrap();
+        assert_eq!(timer.elapsed, Duration::seconds(90));
+        assert_eq!(serde_json::to_value(&timer).unwrap()["elapsed"], 90);
+    }
+
+    #[test]
+    fn elapsed_out_of_range_is_a_parse_error() {
+        for elapsed in ["9223372036854775807", "-9223372036854775808"] {
+            let err = serde_json::from_str::<Timer>(&timer_json(elapsed)).unwrap_err();
+            assert!(err.to_string().contains("out of range"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn a_closed_stretch_counts_and_logs_once() {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap();
+        let mut timer: Timer = serde_json::from_str(&timer_json("60")).unwrap();
+        timer.started_at = Some(start);
+        let mut entries = vec![];
+
+        let stretch = timer.close_stretch(start + Duration::minutes(30));
+        log_stretch(&mut entries, &timer, stretch);
+        assert_eq!(timer.elapsed, Duration::seconds(60 + 30 * 60));
+        assert!(!timer.is_running());
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].category, "work");
+        assert_eq!(entries[0].note.as_deref(), Some("focus"));
+
+        // a paused timer has no stretch left to log
+        let stretch = timer.close_stretch(start + Duration::hours(1));
+        log_stretch(&mut entries, &timer, stretch);
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn the_elapsed_time_stays_exact_however_irregular_the_refreshes() {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap();
+        let mut timer: Timer = serde_json::from_str(&timer_json("60")).unwrap();
+        timer.started_at = Some(start);
+        let mut now = start;
+        let mut running = Duration::zero();
+        for (i, step) in [200, 200, 1_350, 7, 3, 200, 59_000, 421]
+            .iter()
+            .cycle()
+            .take(200)
+            .enumerate()
+        {
+            let step = Duration::milliseconds(*step);
+            now += step;
+            if timer.is_running() {
+                running = running + step;
+            }
+            assert_eq!(timer.elapsed_at(now), Duration::seconds(60) + running);
+            // pausing and going on now and then, as a user would
+            if i % 37 == 36 {
+                if timer.is_running() {
+                    timer.close_stretch(now);
+                } else {
+                    timer.started_at = Some(now);
+                }
+            }
+        }
+        // a clock that went back shows no less than was counted
+        timer.started_at = Some(now);
+        assert_eq!(timer.elapsed_at(now - Duration::minutes(5)), timer.elapsed);
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn a_timer_runs_on_across_a_restart_and_logs_each_stretch() {
+        crate::app::tests::with_app("timers-lifecycle", serde_json::json!([]), |_| {
+            let start_at = Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap();
+            let at = |minutes| start_at + Duration::minutes(minutes);
+            let id = add("focus", "work", start_at).unwrap();
+            assert_eq!(add("reading", "home", start_at).unwrap(), id + 1);
+
+            start(id, at(0)).unwrap();
+            // starting it again keeps the first start
+            start(id, at(1)).unwrap();
+            // what the next run of the app reads back
+            let timer = read_timers().unwrap().remove(0);
+            assert_eq!(timer.started_at, Some(at(0)));
+            assert_eq!(timer.elapsed_at(at(5)), Duration::minutes(5));
+
+            let paused = pause(id, at(5)).unwrap().unwrap();
+            assert!(!paused.is_running());
+            assert_eq!(paused.elapsed_at(at(9)), Duration::minutes(5));
+            start(id, at(10)).unwrap();
+            assert_eq!(stop(id, at(12)).unwrap(), Some(Duration::minutes(7)));
+            let timer = read_timers().unwrap().remove(0);
+            assert!(!timer.is_running());
+            assert_eq!(timer.elapsed, Duration::zero());
+
+            let entries = timetracking::read_time_entries().unwrap();
+            let stretches: Vec<_> = entries
+                .iter()
+                .map(|e| (e.start, e.end, e.category.as_str(), e.note.as_deref()))
+                .collect();
+            assert_eq!(
+                stretches,
+                [
+                    (at(0), Some(at(5)), "work", Some("focus")),
+                    (at(10), Some(at(12)), "work", Some("focus")),
+                ]
+            );
+
+            assert!(start(99, at(20)).unwrap().is_none());
+            assert!(pause(99, at(20)).unwrap().is_none());
+            assert_eq!(stop(99, at(20)).unwrap(), None);
+        });
+    }
+}
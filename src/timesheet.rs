@@ -0,0 +1,556 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use std::collections::BTreeMap;
+use std::fs;
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    text::{Span, Spans},
+    widgets::Paragraph,
+    Frame,
+};
+
+use crate::chrome::Chrome;
+use crate::cli;
+use crate::timetracking::{
+    local_midnight, parse_duration, read_time_entries, time_per, ReportFilter, TimeEntry,
+};
+use crate::widgets;
+use crate::zone::{self, Zone};
+use crate::{read_db, Error, Todo};
+
+/// What `export-time --text` fits the grid into without `--width`.
+const DEFAULT_WIDTH: usize = 160;
+
+/// The day headers from longest to shortest, the first one that fits wins.
+const DAY_HEADERS: [fn(NaiveDate) -> String; 3] = [
+    |day| day.format("%a %d").to_string(),
+    |day| format!("{}{}", &day.format("%a").to_string()[..2], day.format("%d")),
+    |day| day.format("%d").to_string(),
+];
+
+/// The first day of the month `value` names, as YYYY-MM.
+pub fn parse_month(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(&format!("{}-01", value), "%Y-%m-%d").ok()
+}
+
+/// The first day of the month after the one `month` is in.
+pub fn next_month(month: NaiveDate) -> NaiveDate {
+    let first = month.with_day(1).expect("every month has a first day");
+    (first + Duration::days(32))
+        .with_day(1)
+        .expect("every month has a first day")
+}
+
+/// The first day of the month before the one `month` is in.
+pub fn previous_month(month: NaiveDate) -> NaiveDate {
+    (month.with_day(1).expect("every month has a first day") - Duration::days(1))
+        .with_day(1)
+        .expect("every month has a first day")
+}
+
+/// Tracked hours per category and day of one month, the classic grid a
+/// client wants with the invoice.
+pub struct Timesheet {
+    /// The first day of the month.
+    pub month: NaiveDate,
+    /// The zone the days are reckoned in.
+    pub zone: Zone,
+    pub days: Vec<NaiveDate>,
+    /// What the rows are, "category" or "tag".
+    pub rows_by: &'static str,
+    /// A row per category tracked in the month, by name, with a cell per
+    /// day; None where nothing was tracked.
+    pub rows: Vec<(String, Vec<Option<Duration>>)>,
+}
+
+impl Timesheet {
+    /// The grid for the month `month` is in, by the days of `zone`, of
+    /// the entries `filter` keeps and with its rows, going by `todos` for
+    /// the tags entries take from their todo. With `round`, every cell is
+    /// rounded to the nearest multiple of it before the totals add up.
+    pub fn build(
+        entries: &[TimeEntry],
+        todos: &[Todo],
+        filter: &ReportFilter,
+        month: NaiveDate,
+        round: Option<Duration>,
+        zone: Zone,
+        now: DateTime<Utc>,
+    ) -> Timesheet {
+        Timesheet::build_by(
+            &filter.apply(entries, todos),
+            month,
+            round,
+            zone,
+            now,
+            filter.rows_by(),
+            |entry| filter.rows(entry, todos),
+        )
+    }
+
+    /// The grid with a row per key `keys` gives the entries, `rows_by`
+    /// naming what they are. An entry under several keys counts in full
+    /// in each row, so the rows can add up to more than the day's total.
+    pub fn build_by(
+        entries: &[TimeEntry],
+        month: NaiveDate,
+        round: Option<Duration>,
+        zone: Zone,
+        now: DateTime<Utc>,
+        rows_by: &'static str,
+        keys: impl Fn(&TimeEntry) -> Vec<String>,
+    ) -> Timesheet {
+        let month = month.with_day(1).expect("every month has a first day");
+        let end = next_month(month);
+        let days: Vec<NaiveDate> = month.iter_days().take_while(|day| *day < end).collect();
+        let mut rows: BTreeMap<String, Vec<Option<Duration>>> = BTreeMap::new();
+        for (i, day) in days.iter().enumerate() {
+            let from = local_midnight(*day, zone);
+            let to = local_midnight(*day + Duration::days(1), zone);
+            for (category, total) in time_per(entries, from, to, now, &keys) {
+                let cells = rows
+                    .entry(category)
+                    .or_insert_with(|| vec![None; days.len()]);
+                cells[i] = Some(match round {
+                    Some(step) => rounded(total, step),
+                    None => total,
+                });
+            }
+        }
+        Timesheet {
+            month,
+            zone,
+            days,
+            rows_by,
+            rows: rows.into_iter().collect(),
+        }
+    }
+
+    /// The totals of each day, blank where no row has anything. By tag, an
+    /// entry with two tags counts twice.
+    pub fn day_totals(&self) -> Vec<Option<Duration>> {
+        (0..self.days.len())
+            .map(|i| sum(self.rows.iter().map(|(_, cells)| cells[i])))
+            .collect()
+    }
+
+    /// The zone with its offset when the month starts, so that a sheet
+    /// can be made again the same, e.g. "Europe/Berlin (+01:00)".
+    pub fn zone_label(&self) -> String {
+        self.zone.describe(local_midnight(self.month, self.zone))
+    }
+
+    /// The grid as CSV: a row per category, a column per day as
+    /// YYYY-MM-DD, totals last both ways. The corner names the zone.
+    pub fn csv(&self) -> String {
+        let mut header = vec![csv_field(&format!(
+            "{} ({})",
+            self.rows_by,
+            self.zone_label()
+        ))];
+        header.extend(
+            self.days
+                .iter()
+                .map(|day| day.format("%Y-%m-%d").to_string()),
+        );
+        header.push("total".to_string());
+        let mut lines = vec![header.join(",")];
+        for (category, cells) in self.grid_rows() {
+            let mut fields = vec![csv_field(&category)];
+            fields.extend(
+                cells
+                    .iter()
+                    .map(|cell| cell.map(format_cell).unwrap_or_default()),
+            );
+            lines.push(fields.join(","));
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    /// The grid as fixed-width text of at most `width` columns when the
+    /// numbers allow, abbreviating the day headers as far as it takes.
+    pub fn text(&self, width: usize) -> Vec<String> {
+        let rows = self.grid_rows();
+        let label_width = rows
+            .iter()
+            .map(|(label, _)| label.chars().count())
+            .max()
+            .unwrap_or(0);
+        let value_widths: Vec<usize> = (0..=self.days.len())
+            .map(|i| {
+                rows.iter()
+                    .map(|(_, cells)| cells[i].map_or(0, |cell| format_cell(cell).len()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let mut chosen = None;
+        for header in DAY_HEADERS {
+            let mut headers: Vec<String> = self.days.iter().map(|day| header(*day)).collect();
+            headers.push("Total".to_string());
+            let widths: Vec<usize> = headers
+                .iter()
+                .zip(&value_widths)
+                .map(|(header, value)| header.chars().count().max(*value))
+                .collect();
+            let total = label_width + widths.iter().map(|w| w + 1).sum::<usize>();
+            let fits = total <= width;
+            chosen = Some((headers, widths));
+            if fits {
+                break;
+            }
+        }
+        let (headers, widths) = chosen.expect("there are day headers");
+
+        let line = |label: &str, cells: Vec<String>| {
+            let mut line = format!("{:<width$}", label, width = label_width);
+            for (cell, width) in cells.iter().zip(&widths) {
+                line.push_str(&format!(" {:>width$}", cell, width = width));
+            }
+            line.trim_end().to_string()
+        };
+        let mut lines = vec![line("", headers)];
+        for (label, cells) in &rows {
+            let cells = cells
+                .iter()
+                .map(|cell| cell.map(format_cell).unwrap_or_default())
+                .collect();
+            lines.push(line(label, cells));
+        }
+        lines
+    }
+
+    /// Every row with its total appended, then the row of day totals.
+    fn grid_rows(&self) -> Vec<(String, Vec<Option<Duration>>)> {
+        let mut rows: Vec<(String, Vec<Option<Duration>>)> = self
+            .rows
+            .iter()
+            .map(|(category, cells)| {
+                let mut cells = cells.clone();
+                cells.push(sum(cells.iter().copied()));
+                (category.clone(), cells)
+            })
+            .collect();
+        let mut totals = self.day_totals();
+        totals.push(sum(totals.iter().copied()));
+        rows.push(("Total".to_string(), totals));
+        rows
+    }
+}
+
+/// A `--round` step: a duration up to a day, the most one cell holds.
+fn parse_round(value: &str) -> Option<Duration> {
+    parse_duration(value).filter(|step| *step <= Duration::days(1))
+}
+
+/// `duration` to the nearest multiple of `step`.
+fn rounded(duration: Duration, step: Duration) -> Duration {
+    let step = step.num_seconds();
+    Duration::seconds((duration.num_seconds() + step / 2) / step * step)
+}
+
+/// The sum of the cells with anything in them, None when none has.
+fn sum(cells: impl Iterator<Item = Option<Duration>>) -> Option<Duration> {
+    cells.flatten().reduce(|sum, cell| sum + cell)
+}
+
+/// Hours with at most two decimals, e.g. "7.5", "8" or "0.25".
+fn format_cell(duration: Duration) -> String {
+    let hours = format!("{:.2}", duration.num_seconds() as f64 / 3600.0);
+    hours
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Runs `export-time --format grid [--month YYYY-MM] [--round DURATION]
+/// [--tag TAG] [--by category|tag] [--text] [--width N] [--output FILE]`
+/// and returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let mut format = None;
+    let mut filter = ReportFilter::default();
+    let mut month = zone::now().date_naive();
+    let mut round = None;
+    let mut text = false;
+    let mut width = DEFAULT_WIDTH;
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--text" {
+            text = true;
+            continue;
+        }
+        let value = match arg.as_str() {
+            "--format" | "--month" | "--round" | "--tag" | "--by" | "--width" | "--output" => {
+                match args.next() {
+                    Some(value) => value,
+                    None => return cli::usage(format!("{} needs a value", arg)),
+                }
+            }
+            _ => return cli::usage(format!("unknown export-time argument '{}'", arg)),
+        };
+        match arg.as_str() {
+            "--format" => format = Some(value.as_str()),
+            "--month" => match parse_month(value) {
+                Some(first) => month = first,
+                None => return cli::usage("--month takes YYYY-MM"),
+            },
+            "--round" => match parse_round(value) {
+                Some(step) => round = Some(step),
+                None => return cli::usage("--round takes a duration like 15m or 1h, at most 24h"),
+            },
+            "--tag" => filter.tag = Some(value.clone()),
+            "--by" => match value.as_str() {
+                "category" => filter.by_tag = false,
+                "tag" => filter.by_tag = true,
+                _ => return cli::usage("--by takes category or tag"),
+            },
+            "--width" => match value.parse() {
+                Ok(columns) => width = columns,
+                Err(_) => return cli::usage("--width takes a number of columns"),
+            },
+            _ => output = Some(value),
+        }
+    }
+    if format != Some("grid") {
+        return cli::usage("unsupported export-time format, use --format grid");
+    }
+
+    let entries = match read_time_entries() {
+        Ok(entries) => entries,
+        Err(err) => return cli::fail(&err),
+    };
+    let todos = if filter.uses_tags() {
+        match read_db() {
+            Ok(todos) => todos,
+            Err(err) => return cli::fail(&err),
+        }
+    } else {
+        vec![]
+    };
+    let sheet = Timesheet::build(
+        &entries,
+        &todos,
+        &filter,
+        month,
+        round,
+        zone::current(),
+        Utc::now(),
+    );
+    let rendered = if text {
+        let mut lines = sheet.text(width);
+        lines.push(String::new());
+        lines.push(format!("Days in {}", sheet.zone_label()));
+        lines.push(String::new());
+        lines.join("\n")
+    } else {
+        sheet.csv()
+    };
+    match output {
+        Some(path) => {
+            if let Err(err) = fs::write(path, rendered) {
+                return cli::fail(&Error::WriteFileError(path.clone(), err));
+            }
+        }
+        None => print!("{}", rendered),
+    }
+    0
+}
+
+/// The TimeTracking tab's preview of a month's grid, as `export-time
+/// --text` prints it for the width of the screen.
+pub fn render_timesheet<B: Backend>(
+    rect: &mut Frame<B>,
+    sheet: &Timesheet,
+    filter: &ReportFilter,
+    chrome: Chrome,
+    area: Rect,
+) {
+    let (frame, _) = chrome.frame(false);
+    let width = area.width.saturating_sub(frame) as usize;
+    let mut lines: Vec<Spans> = sheet
+        .text(width)
+        .into_iter()
+        .map(|line| Spans::from(vec![Span::raw(line)]))
+        .collect();
+    if sheet.rows.is_empty() {
+        lines = vec![Spans::from(vec![Span::raw("nothing tracked this month")])];
+    }
+    let paragraph = Paragraph::new(lines).block(chrome.block().title(title(sheet.month, filter)));
+    rect.render_widget(paragraph, area);
+}
+
+/// The timesheet view while its grid isn't there, with `message` instead.
+pub fn render_placeholder<B: Backend>(
+    rect: &mut Frame<B>,
+    month: NaiveDate,
+    filter: &ReportFilter,
+    message: &str,
+    chrome: Chrome,
+    area: Rect,
+) {
+    widgets::render_placeholder(rect, &title(month, filter), message, chrome, area);
+}
+
+fn title(month: NaiveDate, filter: &ReportFilter) -> String {
+    format!(
+        "Timesheet {}{} (h/l: month, f: tag, B: by tag, Esc: close)",
+        month.format("%B %Y"),
+        filter.label()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UTC: Zone = Zone::Named(chrono_tz::UTC);
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 2, day, hour, minute, 0).unwrap()
+    }
+
+    fn entry(category: &str, start: DateTime<Utc>, minutes: i64) -> TimeEntry {
+        serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "category": category,
+            "start": start,
+            "end": start + Duration::minutes(minutes),
+        }))
+        .unwrap()
+    }
+
+    /// February 2024, 29 days: a few days of two clients, one entry past
+    /// midnight and one outside the month.
+    fn february(round: Option<Duration>) -> Timesheet {
+        let entries = [
+            entry("acme", at(1, 9, 0), 150),
+            entry("acme", at(1, 14, 0), 60),
+            entry("acme", at(5, 23, 0), 120),
+            entry("globex, inc", at(5, 10, 0), 20),
+            entry("globex, inc", at(29, 8, 0), 480),
+            entry("acme", at(1, 0, 0) - Duration::hours(3), 60),
+        ];
+        Timesheet::build_by(
+            &entries,
+            NaiveDate::from_ymd_opt(2024, 2, 14).unwrap(),
+            round,
+            UTC,
+            at(29, 23, 0),
+            "category",
+            |entry| vec![entry.category.clone()],
+        )
+    }
+
+    #[test]
+    fn the_csv_has_a_column_per_day_blanks_and_totals() {
+        let days = (1..=29)
+            .map(|day| format!("2024-02-{:02}", day))
+            .collect::<Vec<_>>()
+            .join(",");
+        let expected = format!(
+            "category (UTC (+00:00)),{},total\n\
+             acme,3.5,,,,1,1,,,,,,,,,,,,,,,,,,,,,,,,5.5\n\
+             \"globex, inc\",,,,,0.33,,,,,,,,,,,,,,,,,,,,,,,,8,8.33\n\
+             Total,3.5,,,,1.33,1,,,,,,,,,,,,,,,,,,,,,,,8,13.83\n",
+            days
+        );
+        assert_eq!(february(None).csv(), expected);
+    }
+
+    #[test]
+    fn the_csv_rounds_each_cell_to_the_step() {
+        let csv = february(Some(Duration::minutes(15))).csv();
+        let rows: Vec<&str> = csv.lines().skip(1).collect();
+        assert_eq!(
+            rows,
+            [
+                "acme,3.5,,,,1,1,,,,,,,,,,,,,,,,,,,,,,,,5.5",
+                "\"globex, inc\",,,,,0.25,,,,,,,,,,,,,,,,,,,,,,,,8,8.25",
+                "Total,3.5,,,,1.25,1,,,,,,,,,,,,,,,,,,,,,,,8,13.75",
+            ]
+        );
+    }
+
+    #[test]
+    fn a_narrow_grid_falls_back_to_day_numbers() {
+        let expected = [
+            "             01 02 03 04   05 06 07 08 09 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 Total",
+            "acme        3.5             1  1                                                                        5.5",
+            "globex, inc              0.33                                                                       8  8.33",
+            "Total       3.5          1.33  1                                                                    8 13.83",
+        ];
+        assert_eq!(february(None).text(120), expected);
+        // Too narrow for any header: the shortest one, not cut.
+        assert_eq!(february(None).text(60), expected);
+    }
+
+    #[test]
+    fn a_medium_grid_uses_two_letter_weekdays() {
+        assert_eq!(
+            february(None).text(200),
+            [
+                "            Th01 Fr02 Sa03 Su04 Mo05 Tu06 We07 Th08 Fr09 Sa10 Su11 Mo12 Tu13 We14 Th15 Fr16 Sa17 Su18 Mo19 Tu20 We21 Th22 Fr23 Sa24 Su25 Mo26 Tu27 We28 Th29 Total",
+                "acme         3.5                   1    1                                                                                                                      5.5",
+                "globex, inc                     0.33                                                                                                                       8  8.33",
+                "Total        3.5                1.33    1                                                                                                                  8 13.83",
+            ]
+        );
+    }
+
+    #[test]
+    fn text_uses_the_widest_header_that_fits() {
+        let lines = february(None).text(300);
+        assert!(lines[0].starts_with("            Thu 01 Fri 02 Sat 03 Sun 04 Mon 05 "));
+        assert!(lines[0].ends_with("Wed 28 Thu 29 Total"));
+        assert!(lines.iter().all(|line| line.chars().count() <= 300));
+        assert_eq!(
+            lines[1].split_whitespace().collect::<Vec<_>>(),
+            ["acme", "3.5", "1", "1", "5.5"]
+        );
+        let fit = lines[0].chars().count();
+        assert_eq!(february(None).text(fit)[0], lines[0]);
+        assert!(february(None).text(fit - 1)[0].contains("Th01"));
+    }
+
+    #[test]
+    fn round_steps_stay_within_a_day() {
+        assert_eq!(parse_round("15m"), Some(Duration::minutes(15)));
+        assert_eq!(parse_round("24h"), Some(Duration::days(1)));
+        for value in ["25h", "9999999999h", "9223372036854775807", "0m", "soon"] {
+            assert_eq!(parse_round(value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn cells_round_to_the_nearest_step() {
+        let step = Duration::minutes(15);
+        assert_eq!(rounded(Duration::minutes(7), step), Duration::zero());
+        assert_eq!(rounded(Duration::seconds(450), step), step);
+        assert_eq!(rounded(Duration::minutes(52), step), Duration::minutes(45));
+        assert_eq!(
+            rounded(Duration::hours(30), Duration::days(1)),
+            Duration::days(1)
+        );
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(csv_field("work"), "work");
+        assert_eq!(csv_field("work, deep"), "\"work, deep\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+}
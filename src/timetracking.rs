@@ -0,0 +1,1101 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+#[cfg(feature = "tui")]
+use tui::{
+    layout::Constraint,
+    style::{Color, Modifier, Style},
+    text::{Span, Text},
+    widgets::{Cell, Row, Table},
+};
+
+#[cfg(feature = "tui")]
+use crate::chrome::Chrome;
+use crate::config::Config;
+#[cfg(feature = "tui")]
+use crate::cursor;
+use crate::integrity;
+use crate::lock::DbLock;
+use crate::refs;
+use crate::undo::UndoStack;
+use crate::zone::{self, Zone};
+use crate::{write_atomic, Error, Todo};
+
+pub const TIME_ENTRIES_PATH: &str = "./data/time_entries.json";
+const GAUGE_WIDTH: usize = 20;
+
+const BREAK_CATEGORY: &str = "break";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    #[default]
+    Work,
+    /// Time away, left out of every total.
+    Break,
+}
+
+impl EntryKind {
+    fn is_work(&self) -> bool {
+        *self == EntryKind::Work
+    }
+}
+
+/// A tracked interval. An entry without an `end` is a running timer.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TimeEntry {
+    pub id: usize,
+    pub category: String,
+    pub start: DateTime<Utc>,
+    #[serde(default)]
+    pub end: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// The todo this time was spent on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub todo_id: Option<usize>,
+    #[serde(default, skip_serializing_if = "EntryKind::is_work")]
+    pub kind: EntryKind,
+    /// For a running break, the entries it paused, to resume when it ends.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paused: Vec<usize>,
+    /// The UTC offset in seconds of the zone in effect when the entry was
+    /// started, to show it by the clock it was recorded on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i32>,
+    /// Tags of its own, e.g. "deep" or "meeting". None takes the linked
+    /// todo's, see `entry_tags`; an empty list has none at all.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+}
+
+impl TimeEntry {
+    pub fn recorded_offset(&self) -> Option<FixedOffset> {
+        self.offset.and_then(FixedOffset::east_opt)
+    }
+
+    /// `at` by `clock`: the recorded offset when asked for and known, the
+    /// zone in effect otherwise.
+    fn shown_at(&self, at: DateTime<Utc>, clock: EntryClock) -> DateTime<FixedOffset> {
+        match (clock, self.recorded_offset()) {
+            (EntryClock::Recorded, Some(offset)) => at.with_timezone(&offset),
+            _ => at.with_timezone(&zone::current().offset_at(at)),
+        }
+    }
+
+    /// Whether it was recorded with another offset than the zone in effect
+    /// has at its start, e.g. while travelling.
+    pub fn recorded_elsewhere(&self) -> bool {
+        self.recorded_offset()
+            .is_some_and(|offset| offset != zone::current().offset_at(self.start))
+    }
+}
+
+/// Which clock the entries list shows times by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EntryClock {
+    /// The zone in effect now.
+    Current,
+    /// The offset each entry was recorded with.
+    Recorded,
+}
+
+/// The offset of the zone in effect at `at`, to record with an entry.
+fn offset_now(at: DateTime<Utc>) -> Option<i32> {
+    Some(zone::current().offset_at(at).local_minus_utc())
+}
+
+fn next_entry_id(entries: &[TimeEntry]) -> usize {
+    entries.iter().map(|e| e.id).max().unwrap_or(0) + 1
+}
+
+pub fn read_time_entries() -> Result<Vec<TimeEntry>, Error> {
+    match fs::read_to_string(TIME_ENTRIES_PATH) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn write_time_entries(entries: &[TimeEntry]) -> Result<(), Error> {
+    integrity::invalidate();
+    write_atomic(TIME_ENTRIES_PATH, &serde_json::to_string_pretty(entries)?)
+}
+
+/// Read-modify-write of the time entries under the DB lock, like
+/// `update_db`, so the TUI and `timer` never drop each other's entries.
+pub fn update_time_entries<T>(change: impl FnOnce(&mut Vec<TimeEntry>) -> T) -> Result<T, Error> {
+    let _lock = DbLock::acquire()?;
+    let mut entries = read_time_entries()?;
+    let result = change(&mut entries);
+    write_time_entries(&entries)?;
+    Ok(result)
+}
+
+/// Appends a running entry with the next free id and returns that id.
+pub fn start_entry(
+    category: &str,
+    start: DateTime<Utc>,
+    note: Option<String>,
+    todo_id: Option<usize>,
+    tags: Option<Vec<String>>,
+) -> Result<usize, Error> {
+    update_time_entries(|entries| push_running(entries, category, start, note, todo_id, tags))
+}
+
+/// `start_entry` on entries in memory.
+pub fn push_running(
+    entries: &mut Vec<TimeEntry>,
+    category: &str,
+    start: DateTime<Utc>,
+    note: Option<String>,
+    todo_id: Option<usize>,
+    tags: Option<Vec<String>>,
+) -> usize {
+    let id = next_entry_id(entries);
+    entries.push(TimeEntry {
+        id,
+        category: category.to_string(),
+        start,
+        end: None,
+        note,
+        todo_id,
+        kind: EntryKind::Work,
+        paused: vec![],
+        offset: offset_now(start),
+        tags,
+    });
+    id
+}
+
+/// The break in progress, if any.
+pub fn current_break(entries: &[TimeEntry]) -> Option<&TimeEntry> {
+    entries
+        .iter()
+        .find(|e| e.kind == EntryKind::Break && e.end.is_none())
+}
+
+/// Stops every running entry at `now` and starts a break entry remembering
+/// them. Returns how many were paused.
+pub fn start_break(now: DateTime<Utc>) -> Result<usize, Error> {
+    update_time_entries(|entries| {
+        if current_break(entries).is_some() {
+            return 0;
+        }
+        let mut paused = vec![];
+        for entry in entries.iter_mut().filter(|e| e.end.is_none()) {
+            entry.end = Some(now);
+            paused.push(entry.id);
+        }
+        let count = paused.len();
+        entries.push(TimeEntry {
+            id: next_entry_id(entries),
+            category: BREAK_CATEGORY.to_string(),
+            start: now,
+            end: None,
+            note: None,
+            todo_id: None,
+            kind: EntryKind::Break,
+            paused,
+            offset: offset_now(now),
+            tags: None,
+        });
+        count
+    })
+}
+
+/// Ends the break at `now`, keeping it as a break entry when `keep` and
+/// dropping it otherwise, and starts a fresh entry for each one it paused.
+/// Returns how many were resumed.
+pub fn end_break(keep: bool, now: DateTime<Utc>) -> Result<usize, Error> {
+    update_time_entries(|entries| {
+        let index = match entries
+            .iter()
+            .position(|e| e.kind == EntryKind::Break && e.end.is_none())
+        {
+            Some(index) => index,
+            None => return 0,
+        };
+        let paused = std::mem::take(&mut entries[index].paused);
+        if keep {
+            entries[index].end = Some(now);
+        } else {
+            entries.remove(index);
+        }
+        let resumed: Vec<TimeEntry> = paused
+            .iter()
+            .filter_map(|id| entries.iter().find(|e| e.id == *id))
+            .cloned()
+            .collect();
+        for entry in &resumed {
+            entries.push(TimeEntry {
+                id: next_entry_id(entries),
+                start: now,
+                end: None,
+                offset: offset_now(now),
+                ..entry.clone()
+            });
+        }
+        resumed.len()
+    })
+}
+
+/// Appends a finished break entry from `start` to `end`.
+pub fn log_break(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    note: Option<String>,
+) -> Result<(), Error> {
+    update_time_entries(|entries| {
+        entries.push(TimeEntry {
+            id: next_entry_id(entries),
+            category: BREAK_CATEGORY.to_string(),
+            start,
+            end: Some(end),
+            note,
+            todo_id: None,
+            kind: EntryKind::Break,
+            paused: vec![],
+            offset: offset_now(start),
+            tags: None,
+        })
+    })
+}
+
+/// Appends a finished work entry from `start` to `end` to `entries`.
+pub fn push_work(
+    entries: &mut Vec<TimeEntry>,
+    category: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    note: Option<String>,
+) {
+    entries.push(TimeEntry {
+        id: next_entry_id(entries),
+        category: category.to_string(),
+        start,
+        end: Some(end),
+        note,
+        todo_id: None,
+        kind: EntryKind::Work,
+        paused: vec![],
+        offset: offset_now(start),
+        tags: None,
+    })
+}
+
+/// Takes `id` off a running break's list, for a timer that won't come back
+/// after it. Returns whether a break had paused it.
+pub fn forget_paused(id: usize) -> Result<bool, Error> {
+    update_time_entries(|entries| {
+        match entries
+            .iter_mut()
+            .find(|e| e.kind == EntryKind::Break && e.end.is_none())
+        {
+            Some(current) if current.paused.contains(&id) => {
+                current.paused.retain(|paused| *paused != id);
+                true
+            }
+            _ => false,
+        }
+    })
+}
+
+pub fn finish_entry(id: usize, end: DateTime<Utc>) -> Result<(), Error> {
+    update_time_entries(|entries| {
+        for entry in entries.iter_mut().filter(|e| e.id == id) {
+            entry.end = Some(end);
+        }
+    })
+}
+
+pub fn discard_entry(id: usize) -> Result<(), Error> {
+    update_time_entries(|entries| entries.retain(|e| e.id != id))
+}
+
+/// The todo linked to the most recently started running entry, if any.
+pub fn running_todo(entries: &[TimeEntry]) -> Option<usize> {
+    entries
+        .iter()
+        .filter(|e| e.end.is_none())
+        .max_by_key(|e| e.start)
+        .and_then(|e| e.todo_id)
+}
+
+/// Sets or, with an empty note, clears the note of one entry. An entry not
+/// linked to a todo yet is linked to the one the note refers to.
+pub fn set_note(id: usize, note: &str, todo_ids: &HashSet<usize>) -> Result<(), Error> {
+    update_time_entries(|entries| {
+        for entry in entries.iter_mut().filter(|e| e.id == id) {
+            let note = note.trim();
+            entry.note = if note.is_empty() {
+                None
+            } else {
+                Some(note.to_string())
+            };
+            if entry.todo_id.is_none() {
+                entry.todo_id = refs::linked_todo(note, todo_ids);
+            }
+        }
+    })
+}
+
+/// Gives the entry `id` tags of its own, or with None the linked todo's
+/// again, as one undo step. Returns whether the entry was there.
+pub fn set_tags(id: usize, tags: Option<Vec<String>>, undo: &mut UndoStack) -> Result<bool, Error> {
+    let before = update_time_entries(|entries| {
+        let entry = entries.iter_mut().find(|e| e.id == id)?;
+        Some(std::mem::replace(&mut entry.tags, tags))
+    })?;
+    if let Some(before) = &before {
+        undo.push_entry_tags("tags", id, before.clone());
+    }
+    Ok(before.is_some())
+}
+
+/// Reads tags as typed, separated by commas or spaces: None for nothing,
+/// to take the todo's, and an empty list for "-", for no tags at all.
+pub fn parse_tags(value: &str) -> Option<Vec<String>> {
+    match value.trim() {
+        "" => None,
+        "-" => Some(vec![]),
+        value => Some(
+            value
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ),
+    }
+}
+
+/// The tags `entry` counts under: its own when it has them, else those of
+/// the todo it is linked to, if that is among `todos`.
+pub fn entry_tags(entry: &TimeEntry, todos: &[Todo]) -> Vec<String> {
+    match &entry.tags {
+        Some(tags) => tags.clone(),
+        None => entry
+            .todo_id
+            .and_then(|id| todos.iter().find(|t| t.id == id))
+            .map(|todo| todo.tags.clone())
+            .unwrap_or_default(),
+    }
+}
+
+/// The row of a report by tag for time whose entry and todo have no tags.
+pub const UNTAGGED: &str = "(untagged)";
+
+/// Which entries a report counts and what its rows are: `export-time
+/// --tag` and `--by`, and f and B in the TimeTracking tab.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReportFilter {
+    /// Only entries with this tag count.
+    pub tag: Option<String>,
+    /// A row per tag instead of per category.
+    pub by_tag: bool,
+}
+
+impl ReportFilter {
+    /// Whether it goes by tags, for which the todos are needed.
+    pub fn uses_tags(&self) -> bool {
+        self.tag.is_some() || self.by_tag
+    }
+
+    /// The entries of `entries` with the tag, going by `todos` for the ones
+    /// that take their todo's tags.
+    pub fn apply(&self, entries: &[TimeEntry], todos: &[Todo]) -> Vec<TimeEntry> {
+        entries
+            .iter()
+            .filter(|entry| match &self.tag {
+                Some(tag) => entry_tags(entry, todos).contains(tag),
+                None => true,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// The rows `entry` counts in: its category, or each of its tags, so
+    /// an entry with two tags counts in full in both.
+    pub fn rows(&self, entry: &TimeEntry, todos: &[Todo]) -> Vec<String> {
+        if !self.by_tag {
+            return vec![entry.category.clone()];
+        }
+        match entry_tags(entry, todos) {
+            tags if tags.is_empty() => vec![UNTAGGED.to_string()],
+            tags => tags,
+        }
+    }
+
+    /// What the rows are, "category" or "tag".
+    pub fn rows_by(&self) -> &'static str {
+        if self.by_tag {
+            "tag"
+        } else {
+            "category"
+        }
+    }
+
+    /// For a title, e.g. " - tag deep, by tag"; empty when it keeps every
+    /// entry by category.
+    pub fn label(&self) -> String {
+        let parts: Vec<String> = self
+            .tag
+            .iter()
+            .map(|tag| format!("tag {}", tag))
+            .chain(self.by_tag.then(|| "by tag".to_string()))
+            .collect();
+        match parts.is_empty() {
+            true => String::new(),
+            false => format!(" - {}", parts.join(", ")),
+        }
+    }
+}
+
+/// Entries overlapping the current week, newest first, as listed in the
+/// TimeTracking tab.
+pub fn week_entries(entries: &[TimeEntry], config: &Config, now: DateTime<Utc>) -> Vec<TimeEntry> {
+    let (from, to) = week_bounds(now.with_timezone(&zone::current()), config.week_start);
+    let mut listed: Vec<TimeEntry> = entries
+        .iter()
+        .filter(|e| e.start < to && e.end.unwrap_or(now) > from)
+        .cloned()
+        .collect();
+    listed.sort_by_key(|e| std::cmp::Reverse(e.start));
+    listed
+}
+
+/// The `[start, end)` range of the week containing `now`, in the zone of
+/// `now`.
+pub fn week_bounds(now: DateTime<Zone>, week_start: Weekday) -> (DateTime<Utc>, DateTime<Utc>) {
+    let zone = now.timezone();
+    let today = now.date_naive();
+    let days_in =
+        (today.weekday().num_days_from_monday() + 7 - week_start.num_days_from_monday()) % 7;
+    let first_day = today - Duration::days(days_in as i64);
+    (
+        local_midnight(first_day, zone),
+        local_midnight(first_day + Duration::days(7), zone),
+    )
+}
+
+/// The start of `date` in `zone`: its first existing moment when a DST
+/// change skips midnight.
+pub fn local_midnight(date: NaiveDate, zone: Zone) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).expect("midnight exists");
+    (0..=2)
+        .find_map(|hours| {
+            zone.from_local_datetime(&(midnight + Duration::hours(hours)))
+                .earliest()
+        })
+        .unwrap_or_else(|| zone.from_utc_datetime(&midnight))
+        .with_timezone(&Utc)
+}
+
+/// Tracked time per category inside `[from, to)`, clipping entries that
+/// cross the range and counting running entries up to `now`. Breaks aren't
+/// work and don't count.
+///
+/// ```
+/// use chrono::{Duration, TimeZone, Utc};
+/// use work_time_cli::{add_todo, start_timer, time_per_category, Db, Todo};
+///
+/// let nine = Utc.with_ymd_and_hms(2024, 5, 13, 9, 0, 0).unwrap();
+/// let mut db = Db::default();
+/// let id = add_todo(&mut db, Todo::new("Write report".into(), "work".into(), nine));
+/// start_timer(&mut db, id, nine);
+///
+/// let now = nine + Duration::minutes(90);
+/// let totals = time_per_category(&db.entries, nine, nine + Duration::days(1), now);
+/// assert_eq!(totals["work"], Duration::minutes(90));
+/// ```
+pub fn time_per_category(
+    entries: &[TimeEntry],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> BTreeMap<String, Duration> {
+    time_per(entries, from, to, now, |entry| vec![entry.category.clone()])
+}
+
+/// Tracked time inside `[from, to)` like `time_per_category`, under each
+/// of the keys `keys` gives an entry. An entry with several keys counts
+/// in full under each, one with none doesn't count.
+pub fn time_per(
+    entries: &[TimeEntry],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    now: DateTime<Utc>,
+    keys: impl Fn(&TimeEntry) -> Vec<String>,
+) -> BTreeMap<String, Duration> {
+    let mut totals = BTreeMap::new();
+    for entry in entries.iter().filter(|e| e.kind.is_work()) {
+        let start = entry.start.max(from);
+        let end = entry.end.unwrap_or(now).min(to);
+        if end > start {
+            for key in keys(entry) {
+                let total = totals.entry(key).or_insert_with(Duration::zero);
+                *total = *total + (end - start);
+            }
+        }
+    }
+    totals
+}
+
+pub struct BudgetUsage {
+    pub category: String,
+    pub used: Duration,
+    pub budget_hours: f64,
+}
+
+impl BudgetUsage {
+    pub fn ratio(&self) -> f64 {
+        if self.budget_hours <= 0.0 {
+            return 1.0;
+        }
+        hours(self.used) / self.budget_hours
+    }
+}
+
+pub fn budget_usage(
+    entries: &[TimeEntry],
+    config: &Config,
+    now: DateTime<Utc>,
+) -> Vec<BudgetUsage> {
+    let (from, to) = week_bounds(now.with_timezone(&zone::current()), config.week_start);
+    usages(
+        &time_per_category(entries, from, to, now),
+        &config.weekly_budgets,
+    )
+}
+
+fn usages(
+    totals: &BTreeMap<String, Duration>,
+    budgets: &BTreeMap<String, f64>,
+) -> Vec<BudgetUsage> {
+    budgets
+        .iter()
+        .map(|(category, budget_hours)| BudgetUsage {
+            category: category.clone(),
+            used: totals.get(category).cloned().unwrap_or_else(Duration::zero),
+            budget_hours: *budget_hours,
+        })
+        .collect()
+}
+
+/// The TimeTracking tab's summary of the current week: the categories
+/// with a budget, then the other rows time was tracked on.
+pub struct WeekSummary {
+    pub budgets: Vec<BudgetUsage>,
+    pub others: Vec<(String, Duration)>,
+    /// What the rows are, "category" or "tag".
+    pub rows_by: &'static str,
+}
+
+impl WeekSummary {
+    /// The summary of the week `now` is in, starting on `week_start`, of
+    /// the entries `filter` keeps. The budgets, which are per category,
+    /// only show without a filter.
+    pub fn build(
+        entries: &[TimeEntry],
+        todos: &[Todo],
+        filter: &ReportFilter,
+        week_start: Weekday,
+        budgets: &BTreeMap<String, f64>,
+        now: DateTime<Utc>,
+    ) -> WeekSummary {
+        let (from, to) = week_bounds(now.with_timezone(&zone::current()), week_start);
+        let entries = filter.apply(entries, todos);
+        let totals = time_per(&entries, from, to, now, |entry| filter.rows(entry, todos));
+        let budgets = match filter.uses_tags() {
+            true => BTreeMap::new(),
+            false => budgets.clone(),
+        };
+        WeekSummary {
+            budgets: usages(&totals, &budgets),
+            others: totals
+                .into_iter()
+                .filter(|(row, _)| !budgets.contains_key(row))
+                .collect(),
+            rows_by: filter.rows_by(),
+        }
+    }
+}
+
+/// Remembers which budget thresholds were already announced so the tick
+/// handler warns once per crossing.
+#[derive(Default)]
+pub struct BudgetWatch {
+    announced: HashMap<String, usize>,
+}
+
+const THRESHOLDS: [f64; 2] = [0.8, 1.0];
+
+impl BudgetWatch {
+    /// Returns a warning when the category of a running entry crossed 80%
+    /// or 100% of its weekly budget since the previous call.
+    pub fn check(
+        &mut self,
+        entries: &[TimeEntry],
+        config: &Config,
+        now: DateTime<Utc>,
+    ) -> Option<String> {
+        let mut warning = None;
+        for usage in budget_usage(entries, config, now) {
+            let level = THRESHOLDS.iter().filter(|t| usage.ratio() >= **t).count();
+            let running = entries
+                .iter()
+                .any(|e| e.end.is_none() && e.category == usage.category);
+            let announced = self.announced.entry(usage.category.clone()).or_insert(0);
+            if level < *announced {
+                // a new week started
+                *announced = level;
+            } else if level > *announced && running {
+                *announced = level;
+                warning = Some(format!(
+                    "{} {}/{}h{}",
+                    usage.category,
+                    format_hours(usage.used),
+                    usage.budget_hours,
+                    if level == THRESHOLDS.len() {
+                        " - weekly budget used up"
+                    } else {
+                        ""
+                    }
+                ));
+            }
+        }
+        warning
+    }
+}
+
+fn hours(duration: Duration) -> f64 {
+    duration.num_seconds() as f64 / 3600.0
+}
+
+/// Hours with at most one decimal, e.g. "5.5" or "6".
+pub fn format_hours(duration: Duration) -> String {
+    let rounded = (hours(duration) * 10.0).round() / 10.0;
+    format!("{}", rounded)
+}
+
+/// A human readable duration, e.g. "1h 05m" or "12m".
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    if minutes >= 60 {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// A countdown style duration, e.g. "1:04:05" or "24:59".
+pub fn format_clock(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// A duration in the config, written as `parse_duration` takes it, e.g.
+/// "5m".
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConfigDuration(pub Duration);
+
+impl<'de> Deserialize<'de> for ConfigDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ConfigDuration, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        parse_duration(&value).map(ConfigDuration).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "invalid duration '{}', use e.g. 5m, 1h30m or 90s",
+                value
+            ))
+        })
+    }
+}
+
+/// Parses "25m", "1h30m", "90s" or a bare number of minutes. None as well
+/// past `checked_seconds`' bound.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    if let Ok(minutes) = value.parse::<i64>() {
+        return checked_seconds(minutes.checked_mul(60)?).filter(|d| *d > Duration::zero());
+    }
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let amount: i64 = number.parse().ok()?;
+        number.clear();
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        seconds = seconds.checked_add(amount.checked_mul(unit)?)?;
+    }
+    if !number.is_empty() {
+        return None;
+    }
+    checked_seconds(seconds).filter(|d| *d > Duration::zero())
+}
+
+/// The longest duration taken from a file or from input, a hundred years:
+/// far beyond any timer or break, and short enough to add to any date.
+const MAX_SECONDS: i64 = 100 * 366 * 24 * 3600;
+
+/// `Duration::seconds` for amounts read from files or typed in, None past
+/// a hundred years either way, where adding it to a date or summing it
+/// could panic.
+pub fn checked_seconds(seconds: i64) -> Option<Duration> {
+    (-MAX_SECONDS..=MAX_SECONDS)
+        .contains(&seconds)
+        .then(|| Duration::seconds(seconds))
+}
+
+/// A duration with its sign, e.g. "+1h 05m" or "-12m".
+pub fn format_signed_duration(duration: Duration) -> String {
+    if duration < Duration::zero() {
+        format!("-{}", format_duration(-duration))
+    } else {
+        format!("+{}", format_duration(duration))
+    }
+}
+
+fn gauge(ratio: f64) -> String {
+    let filled = ((ratio.min(1.0) * GAUGE_WIDTH as f64).round() as usize).min(GAUGE_WIDTH);
+    format!("{}{}", "█".repeat(filled), "░".repeat(GAUGE_WIDTH - filled))
+}
+
+#[cfg(feature = "tui")]
+pub fn render_entries<'a>(
+    entries: &[TimeEntry],
+    todo_ids: &HashSet<usize>,
+    clock: EntryClock,
+    now: DateTime<Utc>,
+    chrome: Chrome,
+) -> Table<'a> {
+    let rows: Vec<Row> = entries
+        .iter()
+        .map(|entry| {
+            let mut description = Text::from(entry.category.clone());
+            if let Some(note) = &entry.note {
+                description.extend(vec![refs::spans(
+                    note,
+                    todo_ids,
+                    Style::default().fg(Color::DarkGray),
+                )]);
+            }
+            let height = description.height() as u16;
+            let end = match entry.end {
+                Some(end) => entry.shown_at(end, clock).format("%H:%M").to_string(),
+                None => "running".to_string(),
+            };
+            let mut start = entry
+                .shown_at(entry.start, clock)
+                .format("%a %d %H:%M")
+                .to_string();
+            if entry.recorded_elsewhere() {
+                start.push('*');
+            }
+            Row::new(vec![
+                Cell::from(Span::raw(start)),
+                Cell::from(Span::raw(end)),
+                Cell::from(Span::raw(format_duration(
+                    entry.end.unwrap_or(now) - entry.start,
+                ))),
+                Cell::from(description),
+            ])
+            .height(height)
+        })
+        .collect();
+
+    Table::new(rows)
+        .header(Row::new(vec![
+            Cell::from(Span::styled(
+                "Start",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "End",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Duration",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Category / note",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+        ]))
+        .block(chrome.block().title(format!(
+            "Entries by {} time, *: recorded elsewhere - n: edit note, Enter: go to todo, T: timeline, Z: clock",
+            match clock {
+                EntryClock::Current => zone::current().name(),
+                EntryClock::Recorded => "recorded".to_string(),
+            }
+        )))
+        .highlight_style(cursor::current().highlight())
+        .highlight_symbol(cursor::current().symbol())
+        .widths(&[
+            Constraint::Percentage(20), // start
+            Constraint::Percentage(10), // end
+            Constraint::Percentage(15), // duration
+            Constraint::Percentage(55), // category and note
+        ])
+}
+
+/// The title of the TimeTracking tab's week summary.
+pub fn week_title(filter: &ReportFilter) -> String {
+    format!("TimeTracking - this week{}", filter.label())
+}
+
+#[cfg(feature = "tui")]
+pub fn render_time_tracking<'a>(
+    week: &WeekSummary,
+    filter: &ReportFilter,
+    chrome: Chrome,
+) -> Table<'a> {
+    let mut rows: Vec<Row> = week
+        .budgets
+        .iter()
+        .map(|usage| {
+            let color = if usage.ratio() >= 1.0 {
+                Color::Red
+            } else if usage.ratio() >= 0.8 {
+                Color::Yellow
+            } else {
+                Color::Green
+            };
+            Row::new(vec![
+                Cell::from(Span::raw(usage.category.clone())),
+                Cell::from(Span::raw(format_duration(usage.used))),
+                Cell::from(Span::raw(format!(
+                    "{}/{}h",
+                    format_hours(usage.used),
+                    usage.budget_hours
+                ))),
+                Cell::from(Span::styled(
+                    gauge(usage.ratio()),
+                    Style::default().fg(color),
+                )),
+            ])
+        })
+        .collect();
+
+    rows.extend(week.others.iter().map(|(category, used)| {
+        Row::new(vec![
+            Cell::from(Span::raw(category.clone())),
+            Cell::from(Span::raw(format_duration(*used))),
+            Cell::from(Span::raw("-")),
+            Cell::from(Span::raw("")),
+        ])
+    }));
+
+    Table::new(rows)
+        .header(Row::new(vec![
+            Cell::from(Span::styled(
+                match week.rows_by {
+                    "tag" => "Tag",
+                    _ => "Category",
+                },
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "This week",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Budget",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+        ]))
+        .block(chrome.pane(true).title(week_title(filter)))
+        .widths(&[
+            Constraint::Percentage(25), // category
+            Constraint::Percentage(20), // used
+            Constraint::Percentage(20), // budget
+            Constraint::Length(GAUGE_WIDTH as u16),
+        ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_units_and_bare_minutes() {
+        assert_eq!(parse_duration("25m"), Some(Duration::minutes(25)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("90s"), Some(Duration::seconds(90)));
+        assert_eq!(parse_duration("45"), Some(Duration::minutes(45)));
+    }
+
+    #[test]
+    fn rejects_empty_zero_and_malformed_durations() {
+        for value in ["", "0", "0m", "-5", "5x", "1h30", "h"] {
+            assert_eq!(parse_duration(value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn rejects_durations_out_of_range_instead_of_panicking() {
+        for value in [
+            "99999999999999999",
+            "9223372036854775807",
+            "9223372036854775807h",
+            "9223372036854775807s",
+            "5000000000000h5000000000000h",
+            "1000000h",
+        ] {
+            assert_eq!(parse_duration(value), None, "{}", value);
+        }
+        assert!(parse_duration("800000h").is_some());
+    }
+
+    #[test]
+    fn config_duration_out_of_range_is_a_parse_error() {
+        assert!(serde_json::from_str::<ConfigDuration>("\"99999999999999999\"").is_err());
+        assert_eq!(
+            serde_json::from_str::<ConfigDuration>("\"5m\"").unwrap(),
+            ConfigDuration(Duration::minutes(5))
+        );
+    }
+
+    #[test]
+    fn checked_seconds_stops_at_a_hundred_years() {
+        assert_eq!(
+            checked_seconds(MAX_SECONDS),
+            Some(Duration::seconds(MAX_SECONDS))
+        );
+        assert_eq!(
+            checked_seconds(-MAX_SECONDS),
+            Some(Duration::seconds(-MAX_SECONDS))
+        );
+        assert_eq!(checked_seconds(MAX_SECONDS + 1), None);
+        assert_eq!(checked_seconds(i64::MIN), None);
+    }
+
+    fn entry(
+        id: usize,
+        category: &str,
+        tags: Option<&[&str]>,
+        todo_id: Option<usize>,
+    ) -> TimeEntry {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "category": category,
+            "start": "2024-05-15T09:00:00Z",
+            "end": "2024-05-15T10:00:00Z",
+            "todo_id": todo_id,
+            "tags": tags,
+        }))
+        .unwrap()
+    }
+
+    fn tagged_todo(id: usize, tags: &[&str]) -> Todo {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "title": "todo",
+            "description": "",
+            "category": "work",
+            "status": "Todo",
+            "created_at": "2024-05-01T09:00:00Z",
+            "tags": tags,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn report_filter_keeps_tagged_entries_and_rows_by_tag() {
+        let todos = [tagged_todo(7, &["meeting"])];
+        let entries = [
+            entry(1, "work", Some(&["deep", "client"]), None),
+            entry(2, "work", None, Some(7)),
+            entry(3, "home", None, None),
+            // its own empty tags override the todo's
+            entry(4, "work", Some(&[]), Some(7)),
+        ];
+        let ids = |filter: &ReportFilter| -> Vec<usize> {
+            filter
+                .apply(&entries, &todos)
+                .iter()
+                .map(|e| e.id)
+                .collect()
+        };
+        assert_eq!(ids(&ReportFilter::default()), [1, 2, 3, 4]);
+        let meeting = ReportFilter {
+            tag: Some("meeting".to_string()),
+            by_tag: false,
+        };
+        assert_eq!(ids(&meeting), [2]);
+        assert_eq!(meeting.rows(&entries[1], &todos), ["work"]);
+
+        let by_tag = ReportFilter {
+            tag: None,
+            by_tag: true,
+        };
+        assert_eq!(by_tag.rows(&entries[0], &todos), ["deep", "client"]);
+        assert_eq!(by_tag.rows(&entries[1], &todos), ["meeting"]);
+        assert_eq!(by_tag.rows(&entries[3], &todos), [UNTAGGED]);
+        assert_eq!(by_tag.label(), " - by tag");
+        assert_eq!(meeting.label(), " - tag meeting");
+        assert_eq!(ReportFilter::default().label(), "");
+    }
+
+    #[test]
+    fn week_summary_by_tag_drops_the_budgets() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 15, 12, 0, 0).unwrap();
+        let entries = [
+            entry(1, "work", Some(&["deep", "client"]), None),
+            entry(2, "home", None, None),
+        ];
+        let budgets: BTreeMap<String, f64> = [("work".to_string(), 10.0)].into();
+        let hour = Duration::hours(1);
+
+        let week = WeekSummary::build(
+            &entries,
+            &[],
+            &ReportFilter::default(),
+            Weekday::Mon,
+            &budgets,
+            now,
+        );
+        assert_eq!(week.budgets.len(), 1);
+        assert_eq!(week.budgets[0].used, hour);
+        assert_eq!(week.others, [("home".to_string(), hour)]);
+
+        let by_tag = ReportFilter {
+            tag: None,
+            by_tag: true,
+        };
+        let week = WeekSummary::build(&entries, &[], &by_tag, Weekday::Mon, &budgets, now);
+        assert!(week.budgets.is_empty());
+        assert_eq!(week.rows_by, "tag");
+        assert_eq!(
+            week.others,
+            [
+                (UNTAGGED.to_string(), hour),
+                ("client".to_string(), hour),
+                ("deep".to_string(), hour)
+            ]
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use crossterm::event::KeyEvent;
+use std::time::{Duration, Instant};
+
+use crate::actions::{ActionId, Key};
+
+/// How long an undo toast stays up.
+pub const UNDO_FOR: Duration = Duration::from_secs(10);
+/// How long a toast that only tells something stays up.
+pub const INFO_FOR: Duration = Duration::from_secs(5);
+
+/// What a toast's key does while the toast is up, e.g. `u` to undo.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToastAction {
+    pub key: Key,
+    pub id: ActionId,
+    /// What the action does, for the countdown, e.g. "undo".
+    pub verb: &'static str,
+}
+
+struct Toast {
+    text: String,
+    action: Option<ToastAction>,
+    expires: Instant,
+}
+
+/// Short-lived notices on the status bar. The newest is shown over older
+/// ones, which come back when it goes if they haven't run out meanwhile.
+/// While a toast with an action is shown its key runs the action, ahead of
+/// whatever the key does elsewhere. Toasts go by the clock, not by ticks,
+/// so a slow or missed tick can't stretch the countdown.
+#[derive(Default)]
+pub struct Toasts {
+    stack: Vec<Toast>,
+}
+
+impl Toasts {
+    /// Puts up a toast for `lasts` from `now`.
+    pub fn push(
+        &mut self,
+        text: impl Into<String>,
+        action: Option<ToastAction>,
+        lasts: Duration,
+        now: Instant,
+    ) {
+        self.stack.push(Toast {
+            text: text.into(),
+            action,
+            expires: now + lasts,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// Takes down the toasts that ran out by `now`.
+    pub fn expire(&mut self, now: Instant) {
+        self.stack.retain(|toast| toast.expires > now);
+    }
+
+    /// The shown toast with its countdown at `now`, whole seconds rounded
+    /// up, e.g. "archived 12 todos - press u within 9s to undo".
+    pub fn label(&self, now: Instant) -> Option<String> {
+        let toast = self.stack.iter().rev().find(|toast| toast.expires > now)?;
+        let left = toast.expires.saturating_duration_since(now);
+        Some(match toast.action {
+            Some(action) => format!(
+                "{} - press {} within {}s to {}",
+                toast.text,
+                action.key.label(),
+                left.as_millis().div_ceil(1000),
+                action.verb
+            ),
+            None => toast.text.clone(),
+        })
+    }
+
+    /// The action of the shown toast when `event` is its key. Running it
+    /// is up to the caller, and so is taking the toast down with
+    /// `dismiss_newest`.
+    pub fn action_for(&self, event: &KeyEvent) -> Option<ActionId> {
+        let action = self.stack.last()?.action?;
+        action.key.matches(event).then_some(action.id)
+    }
+
+    /// Takes down the newest toast offering `id`, once it has run however
+    /// it was reached.
+    pub fn dismiss_newest(&mut self, id: ActionId) {
+        if let Some(index) = self
+            .stack
+            .iter()
+            .rposition(|toast| toast.action.is_some_and(|action| action.id == id))
+        {
+            self.stack.remove(index);
+        }
+    }
+
+    /// Takes down every toast offering `id`, e.g. undo toasts once another
+    /// change lands on top of theirs on the undo stack.
+    pub fn dismiss_every(&mut self, id: ActionId) {
+        self.stack
+            .retain(|toast| toast.action.is_none_or(|action| action.id != id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::key;
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    fn undo() -> Option<ToastAction> {
+        Some(ToastAction {
+            key: key('u'),
+            id: ActionId::Undo,
+            verb: "undo",
+        })
+    }
+
+    fn press(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn the_countdown_rounds_up_and_the_toast_runs_out() {
+        let start = Instant::now();
+        let mut toasts = Toasts::default();
+        toasts.push("archived 12 todos", undo(), UNDO_FOR, start);
+        assert_eq!(
+            toasts.label(start).as_deref(),
+            Some("archived 12 todos - press u within 10s to undo")
+        );
+        let later = start + Duration::from_millis(8_500);
+        assert_eq!(
+            toasts.label(later).as_deref(),
+            Some("archived 12 todos - press u within 2s to undo")
+        );
+        assert_eq!(toasts.label(start + UNDO_FOR), None);
+
+        toasts.expire(start + UNDO_FOR - Duration::from_millis(1));
+        assert!(!toasts.is_empty());
+        toasts.expire(start + UNDO_FOR);
+        assert!(toasts.is_empty());
+    }
+
+    #[test]
+    fn a_toast_without_an_action_is_just_its_text() {
+        let now = Instant::now();
+        let mut toasts = Toasts::default();
+        toasts.push("saved", None, INFO_FOR, now);
+        assert_eq!(toasts.label(now).as_deref(), Some("saved"));
+        assert_eq!(toasts.action_for(&press('u')), None);
+    }
+
+    #[test]
+    fn the_shown_toast_claims_its_key() {
+        let now = Instant::now();
+        let mut toasts = Toasts::default();
+        assert_eq!(toasts.action_for(&press('u')), None);
+        toasts.push("deleted todo 3", undo(), UNDO_FOR, now);
+        assert_eq!(toasts.action_for(&press('u')), Some(ActionId::Undo));
+        assert_eq!(toasts.action_for(&press('x')), None);
+        let ctrl_u = KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL);
+        assert_eq!(toasts.action_for(&ctrl_u), None);
+
+        // an info toast on top hides the undo and its key
+        toasts.push("saved", None, INFO_FOR, now);
+        assert_eq!(toasts.action_for(&press('u')), None);
+    }
+
+    #[test]
+    fn stacked_toasts_show_the_newest_then_the_one_below() {
+        let start = Instant::now();
+        let mut toasts = Toasts::default();
+        toasts.push("deleted todo 3", undo(), UNDO_FOR, start);
+        toasts.push(
+            "moved todo 4",
+            undo(),
+            UNDO_FOR,
+            start + Duration::from_secs(2),
+        );
+        let now = start + Duration::from_secs(3);
+        assert!(toasts.label(now).unwrap().starts_with("moved todo 4"));
+
+        toasts.dismiss_newest(ActionId::Undo);
+        assert_eq!(
+            toasts.label(now).as_deref(),
+            Some("deleted todo 3 - press u within 7s to undo")
+        );
+        // once it ran out the older one is gone too, even undismissed
+        assert_eq!(toasts.label(start + UNDO_FOR), None);
+    }
+
+    #[test]
+    fn a_newer_short_toast_gives_way_to_an_older_long_one() {
+        let start = Instant::now();
+        let mut toasts = Toasts::default();
+        toasts.push("deleted todo 3", undo(), UNDO_FOR, start);
+        toasts.push("saved", None, INFO_FOR, start);
+        assert_eq!(toasts.label(start).as_deref(), Some("saved"));
+        let later = start + INFO_FOR;
+        toasts.expire(later);
+        assert_eq!(
+            toasts.label(later).as_deref(),
+            Some("deleted todo 3 - press u within 5s to undo")
+        );
+        assert_eq!(toasts.action_for(&press('u')), Some(ActionId::Undo));
+    }
+
+    #[test]
+    fn dismissing_every_undo_keeps_the_other_toasts() {
+        let now = Instant::now();
+        let mut toasts = Toasts::default();
+        toasts.push("deleted todo 3", undo(), UNDO_FOR, now);
+        toasts.push("saved", None, INFO_FOR, now);
+        toasts.push("moved todo 4", undo(), UNDO_FOR, now);
+        toasts.dismiss_every(ActionId::Undo);
+        assert_eq!(toasts.label(now).as_deref(), Some("saved"));
+        toasts.dismiss_newest(ActionId::Undo);
+        assert!(!toasts.is_empty());
+    }
+
+    #[test]
+    fn the_countdown_stays_exact_however_irregular_the_ticks() {
+        let start = Instant::now();
+        let mut toasts = Toasts::default();
+        toasts.push("archived 12 todos", undo(), UNDO_FOR, start);
+        let mut now = start;
+        for step in [200, 200, 1_350, 7, 3, 200, 2_900, 421].iter().cycle() {
+            now += Duration::from_millis(*step);
+            toasts.expire(now);
+            let left = (start + UNDO_FOR).saturating_duration_since(now);
+            if left.is_zero() {
+                break;
+            }
+            let expected = format!(
+                "archived 12 todos - press u within {}s to undo",
+                left.as_millis().div_ceil(1000)
+            );
+            assert_eq!(toasts.label(now), Some(expected));
+        }
+        assert!(toasts.is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::{Priority, TodoStatus};
+
+/// Longest description a todo takes, in chars.
+pub const MAX_DESCRIPTION_CHARS: usize = 10_000;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Todo {
+    pub id: usize,
+    pub title: String,
+    /// Changed through `set_description` only, which holds it to
+    /// `MAX_DESCRIPTION_CHARS`.
+    description: String,
+    pub category: String,
+    /// Changed through `set_status` only.
+    status: TodoStatus,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Expected effort in hours.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<f64>,
+    /// When the todo last moved to Done, cleared if it leaves Done again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    /// When the todo last moved to Doing, cleared if it leaves Doing again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doing_at: Option<DateTime<Utc>>,
+    /// Files that go with the todo, kept where they are.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<PathBuf>,
+    /// The todo this is a subtask of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+    /// When the todo last changed, set by `update_db` through `touch`.
+    /// Todos untouched since this field was added only have `created_at`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    updated_at: Option<DateTime<Utc>>,
+    /// Left out of `:carryover` until then.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deferred_until: Option<DateTime<Utc>>,
+    /// A color label such as "red", styling the title over everything
+    /// else, see `label::LABELS`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Where the todo was created, e.g. "cli", "stdin", "tui" or
+    /// "merge:laptop". Todos from before this was recorded have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl Todo {
+    /// A todo in ToDo with nothing but a title and category, created at
+    /// `now`.
+    pub fn new(title: String, category: String, now: DateTime<Utc>) -> Todo {
+        Todo {
+            id: 0,
+            title,
+            description: String::new(),
+            category,
+            status: TodoStatus::Todo,
+            created_at: now,
+            priority: None,
+            due: None,
+            tags: vec![],
+            pinned: false,
+            estimate: None,
+            completed_at: None,
+            doing_at: None,
+            attachments: vec![],
+            parent: None,
+            updated_at: None,
+            deferred_until: None,
+            color: None,
+            source: None,
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Replaces the description, unless it is longer than
+    /// `MAX_DESCRIPTION_CHARS`, which leaves the todo as it was.
+    pub fn set_description(&mut self, description: String) -> Result<(), String> {
+        if description.chars().count() > MAX_DESCRIPTION_CHARS {
+            return Err(format!(
+                "a description holds at most {} chars",
+                MAX_DESCRIPTION_CHARS
+            ));
+        }
+        self.description = description;
+        Ok(())
+    }
+
+    pub fn status(&self) -> TodoStatus {
+        self.status
+    }
+
+    /// Moves the todo to `status`, keeping `completed_at` and `doing_at`
+    /// in step.
+    pub fn set_status(&mut self, status: TodoStatus, now: DateTime<Utc>) {
+        if status == self.status {
+            return;
+        }
+        self.completed_at = (status == TodoStatus::Done).then_some(now);
+        self.doing_at = (status == TodoStatus::Doing).then_some(now);
+        self.status = status;
+    }
+
+    /// Records that the todo changed at `now`, see `stamp_updates`.
+    pub fn touch(&mut self, now: DateTime<Utc>) {
+        self.updated_at = Some(now);
+    }
+
+    pub fn modified_at(&self) -> DateTime<Utc> {
+        self.updated_at.unwrap_or(self.created_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_status_keeps_the_timestamps_in_step() {
+        let created = Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap();
+        let later = created + chrono::Duration::hours(1);
+        let mut todo = Todo::new("title".to_string(), "work".to_string(), created);
+        todo.set_status(TodoStatus::Doing, created);
+        assert_eq!((todo.doing_at, todo.completed_at), (Some(created), None));
+        todo.set_status(TodoStatus::Done, later);
+        assert_eq!(todo.status(), TodoStatus::Done);
+        assert_eq!((todo.doing_at, todo.completed_at), (None, Some(later)));
+        // staying put changes nothing
+        todo.set_status(TodoStatus::Done, later + chrono::Duration::hours(1));
+        assert_eq!(todo.completed_at, Some(later));
+    }
+
+    #[test]
+    fn only_touch_moves_the_modification_time() {
+        let created = Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap();
+        let mut todo = Todo::new("title".to_string(), "work".to_string(), created);
+        assert_eq!(todo.modified_at(), created);
+        todo.set_status(TodoStatus::Done, created + chrono::Duration::hours(1));
+        assert_eq!(todo.modified_at(), created);
+        let later = created + chrono::Duration::hours(2);
+        todo.touch(later);
+        assert_eq!(todo.modified_at(), later);
+    }
+
+    #[test]
+    fn a_description_over_the_limit_is_refused() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap();
+        let mut todo = Todo::new("title".to_string(), "work".to_string(), now);
+        // chars, not bytes
+        let longest = "é".repeat(MAX_DESCRIPTION_CHARS);
+        assert_eq!(todo.set_description(longest.clone()), Ok(()));
+        assert_eq!(
+            todo.set_description(format!("{}!", longest)),
+            Err("a description holds at most 10000 chars".to_string())
+        );
+        assert_eq!(todo.description(), longest);
+    }
+}
@@ -0,0 +1,245 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::add::{self, DEFAULT_CATEGORY};
+use crate::complete::{Completer, Kind, Vocabulary};
+use crate::suggest::CategoryIndex;
+use crate::widgets::{centered_rect, suggestions_line, Input};
+use crate::Todo;
+
+pub enum TodoFormAction {
+    Continue,
+    Cancel,
+    /// A new todo to add.
+    Submit(Box<Todo>),
+    /// The todo edited, under its id.
+    Save(Box<Todo>),
+}
+
+const FIELD_LABELS: [&str; 3] = ["Title", "Description", "Category"];
+
+/// Form opened with `n` on the Todos tab to add a todo, or with `e` to
+/// edit the selected one. An empty category takes the one guessed from
+/// the title, as `add` does.
+pub struct TodoForm {
+    focus: usize,
+    title: Input,
+    description: Input,
+    category: Input,
+    /// The todo being edited, None when adding one.
+    editing: Option<Todo>,
+    /// A description of several lines, which the one line field can't
+    /// hold; the description editor changes it.
+    description_locked: bool,
+    error: Option<String>,
+    categories: CategoryIndex,
+    vocabulary: Vocabulary,
+    completer: Completer,
+}
+
+impl TodoForm {
+    pub fn new(todos: &[Todo], vocabulary: Vocabulary) -> TodoForm {
+        TodoForm {
+            focus: 0,
+            title: Input::default(),
+            description: Input::default(),
+            category: Input::default(),
+            editing: None,
+            description_locked: false,
+            error: None,
+            categories: CategoryIndex::build(todos),
+            vocabulary,
+            completer: Completer::default(),
+        }
+    }
+
+    /// The form filled in with `todo`, to save it back under its id.
+    pub fn edit(todo: &Todo, todos: &[Todo], vocabulary: Vocabulary) -> TodoForm {
+        let description_locked = todo.description().contains('\n');
+        TodoForm {
+            title: Input::new(&todo.title),
+            description: if description_locked {
+                Input::default()
+            } else {
+                Input::new(todo.description())
+            },
+            category: Input::new(&todo.category),
+            editing: Some(todo.clone()),
+            description_locked,
+            ..TodoForm::new(todos, vocabulary)
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> TodoFormAction {
+        match key.code {
+            KeyCode::Esc => return TodoFormAction::Cancel,
+            KeyCode::Enter => match self.todo() {
+                Ok(todo) if self.editing.is_some() => return TodoFormAction::Save(Box::new(todo)),
+                Ok(todo) => return TodoFormAction::Submit(Box::new(todo)),
+                Err(err) => self.error = Some(err),
+            },
+            KeyCode::Tab | KeyCode::Down => {
+                let completed = self.focus == 2
+                    && self.completer.cycle(
+                        &mut self.category,
+                        0,
+                        self.vocabulary.candidates(Kind::Category),
+                    );
+                if !completed {
+                    self.completer.reset();
+                    self.focus = (self.focus + 1) % FIELD_LABELS.len();
+                }
+            }
+            KeyCode::BackTab | KeyCode::Up => {
+                self.completer.reset();
+                self.focus = (self.focus + FIELD_LABELS.len() - 1) % FIELD_LABELS.len()
+            }
+            _ => {
+                self.completer.reset();
+                self.error = None;
+                match self.focus {
+                    0 => self.title.handle_key(key),
+                    1 if self.description_locked => false,
+                    1 => self.description.handle_key(key),
+                    _ => self.category.handle_key(key),
+                };
+            }
+        }
+        TodoFormAction::Continue
+    }
+
+    /// The category an empty category field stands for.
+    fn guessed_category(&self) -> String {
+        self.categories
+            .suggest(self.title.value())
+            .map_or(DEFAULT_CATEGORY.to_string(), |s| s.category)
+    }
+
+    fn todo(&self) -> Result<Todo, String> {
+        let title = self.title.value().trim();
+        if title.is_empty() {
+            return Err("the title can't be empty".to_string());
+        }
+        let description = self.description.value().trim();
+        let category = match self.category.value().trim() {
+            "" => self.guessed_category(),
+            category => category.to_string(),
+        };
+        // an edit keeps everything else, the id, status and created_at too
+        let mut todo = match &self.editing {
+            Some(todo) => {
+                let mut todo = todo.clone();
+                todo.title = title.to_string();
+                todo.category = category;
+                todo
+            }
+            None => {
+                let mut todo = add::new_todo(title.to_string(), category);
+                todo.source = Some("tui".to_string());
+                todo
+            }
+        };
+        if !self.description_locked {
+            todo.set_description(description.to_string())?;
+        }
+        Ok(todo)
+    }
+
+    fn input(&self, field: usize) -> &Input {
+        match field {
+            0 => &self.title,
+            1 => &self.description,
+            _ => &self.category,
+        }
+    }
+}
+
+pub fn render_todo_form<B: Backend>(rect: &mut Frame<B>, form: &TodoForm, area: Rect) {
+    let popup = centered_rect(60, 40, area);
+    let mut lines = vec![Spans::from(vec![Span::raw("")])];
+    let mut cursor = None;
+
+    for (i, label) in FIELD_LABELS.iter().enumerate() {
+        let input = form.input(i);
+        let style = if i == form.focus {
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::White)
+        };
+        let prefix = format!(" {:<13}", format!("{}:", label));
+        if i == form.focus {
+            cursor = Some((
+                popup.x + 1 + prefix.chars().count() as u16 + input.cursor() as u16,
+                popup.y + 1 + lines.len() as u16,
+            ));
+        }
+        let value = if i == 1 && form.description_locked {
+            Span::styled(
+                "(several lines, edit them from the todo menu)",
+                Style::default().fg(Color::DarkGray),
+            )
+        } else if i == 2 && input.value().is_empty() {
+            Span::styled(
+                format!("{} (guessed)", form.guessed_category()),
+                Style::default().fg(Color::DarkGray),
+            )
+        } else {
+            Span::styled(input.value().to_string(), Style::default().fg(Color::White))
+        };
+        lines.push(Spans::from(vec![Span::styled(prefix, style), value]));
+        if i == 2 && i == form.focus {
+            let found = form.completer.suggestions(
+                &form.category,
+                0,
+                form.vocabulary.candidates(Kind::Category),
+            );
+            if !found.is_empty() {
+                let mut line = suggestions_line(&found, form.completer.current());
+                line.0.insert(0, Span::raw(" ".repeat(14)));
+                lines.push(line);
+            }
+        }
+    }
+
+    lines.push(Spans::from(vec![Span::raw("")]));
+    lines.push(Spans::from(vec![Span::styled(
+        match &form.error {
+            Some(err) => err.clone(),
+            None if form.editing.is_some() => {
+                " Tab/Up/Down: field  Enter: save  Esc: cancel".to_string()
+            }
+            None => " Tab/Up/Down: field  Enter: add  Esc: cancel".to_string(),
+        },
+        Style::default().fg(if form.error.is_some() {
+            Color::Red
+        } else {
+            Color::DarkGray
+        }),
+    )]));
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(match &form.editing {
+                Some(todo) => format!("Edit todo #{}", todo.id),
+                None => "New todo".to_string(),
+            })
+            .border_type(BorderType::Plain),
+    );
+
+    rect.render_widget(Clear, popup);
+    rect.render_widget(paragraph, popup);
+    if let Some((x, y)) = cursor {
+        rect.set_cursor(x, y);
+    }
+}
@@ -0,0 +1,169 @@
+use chrono::prelude::*;
+use std::fs;
+
+use crate::activity;
+use crate::autosave;
+use crate::bulk::{self, ARCHIVED_TODOS_PATH};
+use crate::integrity;
+use crate::lock::DbLock;
+use crate::schema;
+use crate::timers::{self, TIMERS_PATH};
+use crate::timetracking::{self, TimeEntry, TIME_ENTRIES_PATH};
+use crate::{
+    catch_up, check_protected, read_db, read_db_file, stamp_updates, Error, Timer, Todo, DB_PATH,
+};
+
+/// The todos and time entries as one transaction sees and changes them.
+pub struct Txn {
+    pub todos: Vec<Todo>,
+    pub entries: Vec<TimeEntry>,
+    /// Read on first use by `archived`, and only then saved.
+    archived: Option<Vec<Todo>>,
+    /// Read on first use by `timers`, and only then saved.
+    timers: Option<Vec<Timer>>,
+}
+
+impl Txn {
+    /// The todos `bulk --action archive` moved out of the DB, saved along
+    /// with the rest once asked for.
+    pub fn archived(&mut self) -> Result<&mut Vec<Todo>, Error> {
+        let archived = match self.archived.take() {
+            Some(archived) => archived,
+            None => bulk::read_archived()?,
+        };
+        Ok(self.archived.insert(archived))
+    }
+
+    /// The timers of the Timers tab, saved along with the rest once asked
+    /// for.
+    pub fn timers(&mut self) -> Result<&mut Vec<Timer>, Error> {
+        let timers = match self.timers.take() {
+            Some(timers) => timers,
+            None => timers::read_timers()?,
+        };
+        Ok(self.timers.insert(timers))
+    }
+}
+
+/// Lets `change` edit the todos and the time entries together, and the
+/// archive or the timers when it asks for them, for operations that touch
+/// more than one, under one hold of the DB lock. All or none of the files
+/// are saved: an error from `change` or from `check_protected` saves
+/// nothing, and every file is written out in full before any takes the
+/// place of the old one.
+///
+/// Unlike `update_db` this saves right away, with whatever changes to the
+/// todos were still held back.
+pub fn transaction<T>(change: impl FnOnce(&mut Txn) -> Result<T, Error>) -> Result<T, Error> {
+    let _lock = DbLock::acquire()?;
+    catch_up()?;
+    let before = read_db()?;
+    let mut txn = Txn {
+        todos: before.clone(),
+        entries: timetracking::read_time_entries()?,
+        archived: None,
+        timers: None,
+    };
+    let result = change(&mut txn)?;
+    check_protected(&before, &txn.todos)?;
+    stamp_updates(&before, &mut txn.todos, Utc::now());
+
+    let saved = read_db_file().unwrap_or_default();
+    let mut files = vec![
+        (DB_PATH, schema::db_json(&txn.todos)?),
+        (
+            TIME_ENTRIES_PATH,
+            serde_json::to_string_pretty(&txn.entries)?,
+        ),
+    ];
+    if let Some(archived) = &txn.archived {
+        files.push((ARCHIVED_TODOS_PATH, serde_json::to_string_pretty(archived)?));
+    }
+    if let Some(timers) = &txn.timers {
+        files.push((TIMERS_PATH, serde_json::to_string_pretty(timers)?));
+    }
+    commit(&files)?;
+    autosave::saved();
+    integrity::invalidate();
+    activity::record(&activity::changes(&saved, &txn.todos, Utc::now()));
+    Ok(result)
+}
+
+/// Writes every file to a temp file next to it, then renames them all in
+/// place. A failed write removes the temp files and leaves every file as
+/// it was; only the renames, each atomic on its own, come after the point
+/// of no return.
+fn commit(files: &[(&str, String)]) -> Result<(), Error> {
+    let tmp_path = |path: &str| format!("{}.tmp", path);
+    for (written, (path, content)) in files.iter().enumerate() {
+        if let Err(err) = fs::write(tmp_path(path), content) {
+            for (path, _) in &files[..=written] {
+                let _ = fs::remove_file(tmp_path(path));
+            }
+            return Err(Error::WriteDBError(err));
+        }
+    }
+    for (path, _) in files {
+        fs::rename(tmp_path(path), path).map_err(Error::WriteDBError)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// A directory of its own under the system temp dir, so tests running
+    /// side by side never share files.
+    fn scratch(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("txn-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn commit_writes_every_file() {
+        let dir = scratch("all");
+        let db = format!("{}/db.json", dir);
+        let archive = format!("{}/archived_todos.json", dir);
+        fs::write(&db, "old db").unwrap();
+        commit(&[
+            (&db, "new db".to_string()),
+            (&archive, "archive".to_string()),
+        ])
+        .unwrap();
+        assert_eq!(fs::read_to_string(&db).unwrap(), "new db");
+        assert_eq!(fs::read_to_string(&archive).unwrap(), "archive");
+        assert!(!Path::new(&format!("{}.tmp", db)).exists());
+    }
+
+    #[test]
+    fn a_failed_write_changes_nothing() {
+        let dir = scratch("fail");
+        let db = format!("{}/db.json", dir);
+        let entries = format!("{}/time_entries.json", dir);
+        // the archive's directory is missing, so the last write fails
+        // after the others succeeded
+        let archive = format!("{}/missing/archived_todos.json", dir);
+        fs::write(&db, "old db").unwrap();
+        fs::write(&entries, "old entries").unwrap();
+
+        let err = commit(&[
+            (&db, "new db".to_string()),
+            (&entries, "new entries".to_string()),
+            (&archive, "archive".to_string()),
+        ])
+        .unwrap_err();
+        assert!(matches!(err, Error::WriteDBError(_)));
+        assert_eq!(fs::read_to_string(&db).unwrap(), "old db");
+        assert_eq!(fs::read_to_string(&entries).unwrap(), "old entries");
+        let mut left: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["db.json", "time_entries.json"]);
+    }
+}
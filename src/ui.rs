@@ -0,0 +1,1774 @@
+use chrono::prelude::*;
+use crossterm::{
+    event::{self, Event as CEvent, KeyEvent},
+    terminal::{disable_raw_mode, enable_raw_mode},
+};
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+use tui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Cell, List, ListItem, Paragraph, Row, Table, Tabs},
+    Frame, Terminal,
+};
+
+use crate::actions;
+use crate::activity;
+use crate::add;
+use crate::agenda;
+use crate::app::{App, Modal, View};
+use crate::archive;
+use crate::attachments;
+use crate::autosave;
+use crate::badges;
+use crate::batch;
+use crate::breakdown;
+use crate::bulk;
+use crate::carryover;
+use crate::chrome::Chrome;
+use crate::cli;
+use crate::cmdline;
+use crate::config;
+use crate::cursor;
+use crate::daynotes;
+use crate::description;
+use crate::doctor;
+use crate::escalation::{self, EscalationRule};
+use crate::export;
+use crate::fields::{self, Field};
+use crate::filter::Filter;
+use crate::flextime;
+use crate::glyph::Glyphs;
+use crate::highlight::{Highlights, Target};
+use crate::history;
+use crate::integrity;
+use crate::keymap;
+use crate::label;
+use crate::list;
+use crate::maintenance;
+use crate::menu;
+use crate::month;
+use crate::pomodoro;
+use crate::power;
+use crate::preview;
+use crate::progress::Progress;
+use crate::quick;
+use crate::refs;
+use crate::reports::{self, ReportReady, Request};
+use crate::review;
+use crate::schema;
+use crate::search;
+use crate::session::Session;
+use crate::share;
+use crate::sort;
+use crate::sparkline;
+use crate::storage;
+use crate::subtasks;
+use crate::timeline;
+use crate::timer;
+use crate::timerform;
+use crate::timers;
+use crate::timesheet;
+use crate::timetracking;
+use crate::todoform;
+use crate::urgency;
+use crate::widgets;
+use crate::zone::{self, Zone};
+use crate::{column_todos, flush_db, MenuItem, Todo, TodoStatus, DB_PATH};
+
+enum Event<I> {
+    Input(I),
+    Tick,
+    /// The terminal changed size.
+    Resize,
+    /// The terminal couldn't be polled or read.
+    Error(String),
+    /// A report a worker thread built for a view.
+    ReportReady(Box<ReportReady>),
+}
+
+const MENU_TITLES: [&str; 5] = ["Home", "Todos", "Timers", "TimeTracking", "Quit"];
+
+/// Consecutive terminal errors after which the input thread gives up.
+const MAX_INPUT_ERRORS: usize = 5;
+/// Without even a tick for this long past the tick rate the input thread is
+/// gone.
+const INPUT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where `forward_input` reads the terminal from.
+trait EventSource {
+    fn poll(&mut self, timeout: Duration) -> crossterm::Result<bool>;
+    fn read(&mut self) -> crossterm::Result<CEvent>;
+}
+
+/// The terminal the TUI runs in.
+struct Stdin;
+
+impl EventSource for Stdin {
+    fn poll(&mut self, timeout: Duration) -> crossterm::Result<bool> {
+        event::poll(timeout)
+    }
+
+    fn read(&mut self) -> crossterm::Result<CEvent> {
+        event::read()
+    }
+}
+
+/// Forwards key presses and a tick every `tick_rate` milliseconds until the
+/// receiver is dropped. The rate is read again at least every fast tick, so
+/// a change takes effect without waiting out a slow tick. Terminal errors
+/// are forwarded too; after `MAX_INPUT_ERRORS` in a row the thread stops,
+/// which disconnects the channel.
+fn forward_input(
+    mut source: impl EventSource,
+    tx: mpsc::Sender<Event<KeyEvent>>,
+    tick_rate: Arc<AtomicU64>,
+) {
+    let mut last_tick = Instant::now();
+    let mut errors = 0;
+    loop {
+        let tick_rate = Duration::from_millis(tick_rate.load(Ordering::Relaxed));
+        let timeout = tick_rate
+            .checked_sub(last_tick.elapsed())
+            .unwrap_or_else(|| Duration::from_secs(0))
+            .min(power::FAST_TICK);
+
+        let event = match source.poll(timeout) {
+            Ok(true) => source.read().map(Some),
+            Ok(false) => Ok(None),
+            Err(err) => Err(err),
+        };
+        let sent = match event {
+            Ok(event) => {
+                errors = 0;
+                match event {
+                    Some(CEvent::Key(key)) => tx.send(Event::Input(key)),
+                    Some(CEvent::Resize(..)) => tx.send(Event::Resize),
+                    _ => Ok(()),
+                }
+            }
+            Err(err) => {
+                errors += 1;
+                if tx.send(Event::Error(err.to_string())).is_err() || errors >= MAX_INPUT_ERRORS {
+                    return;
+                }
+                thread::sleep(power::FAST_TICK);
+                Ok(())
+            }
+        };
+        if sent.is_err() {
+            return;
+        }
+
+        if last_tick.elapsed() >= tick_rate {
+            if tx.send(Event::Tick).is_err() {
+                return;
+            }
+            last_tick = Instant::now();
+        }
+    }
+}
+
+/// Runs the command in the arguments, or the TUI without one.
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("--format") {
+        match args.get(1).map(String::as_str) {
+            Some("json") => cli::set_json_errors(true),
+            Some("text") => {}
+            _ => std::process::exit(cli::usage("--format takes json or text")),
+        }
+        args.drain(..2);
+    }
+    if args.first().map(String::as_str) == Some("quick") {
+        if let Err(code) = quick::enter_workspace(&mut args) {
+            std::process::exit(code);
+        }
+    }
+    // a legacy DB is migrated by whatever runs first, except `migrate`
+    // itself so that a dry run sees the file as it is
+    let migrated = match args.first().map(String::as_str) {
+        Some("migrate" | "help" | "--help" | "-h") => None,
+        _ => match schema::migrate_db() {
+            Ok(report) => report,
+            Err(err) => std::process::exit(cli::fail(&err)),
+        },
+    };
+    // the zone applies to every command; a config that doesn't load is
+    // reported by whatever needs the rest of it
+    if let Ok(config) = config::load() {
+        zone::set(config.timezone.unwrap_or(Zone::System));
+        cursor::set(config.cursor_style);
+    }
+    if let Some(command) = args.first() {
+        if let Some(report) = &migrated {
+            for line in report.lines() {
+                eprintln!("{}", line);
+            }
+        }
+        let code = match command.as_str() {
+            "add" => add::run(&args[1..]),
+            "agenda" => agenda::run(&args[1..]),
+            "bulk" => bulk::run(&args[1..]),
+            "doctor" => doctor::run(&args[1..]),
+            "export" => export::run(&args[1..]),
+            "export-time" => timesheet::run(&args[1..]),
+            "import" => archive::run_import(&args[1..]),
+            "list" => list::run(&args[1..]),
+            "migrate" => schema::run(&args[1..]),
+            "quick" => quick::run(&args[1..]),
+            "show" => share::run(&args[1..]),
+            "storage" => storage::run(&args[1..]),
+            "timer" => timer::run(&args[1..]),
+            "help" | "--help" | "-h" => {
+                print!("{}", cli::HELP);
+                0
+            }
+            _ => cli::usage(format!("unknown command '{}', see --help", command)),
+        };
+        // a second change within the autosave interval is held back
+        let code = match flush_db(true) {
+            Ok(()) => code,
+            Err(err) => cli::fail(&err),
+        };
+        if let Some(warning) = activity::take_warning() {
+            eprintln!("warning: {}", warning);
+        }
+        if let Some(notice) = autosave::take_notice() {
+            eprintln!("warning: saved over another process's changes, {}", notice);
+        }
+        std::process::exit(code);
+    }
+
+    let config = config::load()?;
+    autosave::set_interval(Duration::from_millis(config.autosave_interval_ms));
+    autosave::hold_failed_saves();
+    enable_raw_mode().expect("can run in raw mode");
+
+    let (tx, rx) = mpsc::channel();
+    let tick_rate = Arc::new(AtomicU64::new(power::FAST_TICK.as_millis() as u64));
+    let input_tick_rate = Arc::clone(&tick_rate);
+    let report_tx = tx.clone();
+    thread::spawn(move || forward_input(Stdin, tx, input_tick_rate));
+
+    let stdout = io::stdout();
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+    terminal.clear()?;
+
+    autosave::seen();
+    let mut app = App::new(config);
+    app.run_daily_maintenance();
+    if let Some(report) = migrated {
+        app.status_message = Some(report.summary());
+    }
+    app.check_integrity();
+    app.run_startup();
+    app.apply_keymap();
+    app.offer_intention();
+    app.take_snapshot();
+    let mut stopped = None;
+
+    loop {
+        let request = match app.view {
+            Some(View::Timesheet(month)) => {
+                Some(Request::Timesheet(month, app.report_filter.clone()))
+            }
+            None if app.active_menu_item == MenuItem::TimeTracking => {
+                Some(Request::Week(app.report_filter.clone()))
+            }
+            _ => None,
+        };
+        match request {
+            Some(request) => {
+                if let Some(generation) = app.reports.request(request.clone(), Instant::now()) {
+                    let tx = report_tx.clone();
+                    let week_start = app.config.week_start;
+                    let budgets = app.config.weekly_budgets.clone();
+                    thread::spawn(move || {
+                        let ready = reports::build(generation, request, week_start, &budgets);
+                        let _ = tx.send(Event::ReportReady(Box::new(ready)));
+                    });
+                }
+            }
+            None => app.reports.clear(),
+        }
+        terminal.draw(|rect| draw(rect, &mut app))?;
+        if let Err(reason) = wait_for_redraw(&mut app, &rx, &tick_rate) {
+            stopped = Some(reason);
+            break;
+        }
+
+        if app.should_quit {
+            break;
+        }
+    }
+
+    let quit_script = if app.should_quit {
+        let script = app.config.on_quit.clone();
+        app.run_script("on_quit", &script).err()
+    } else {
+        None
+    };
+    let flushed = flush_db(true);
+    disable_raw_mode()?;
+    terminal.show_cursor()?;
+    if let Some(err) = quit_script {
+        eprintln!("{}", err);
+    }
+    if let Err(err) = flushed {
+        std::process::exit(cli::fail(&err));
+    }
+    if let (Some(reason), Some(_)) = (autosave::failure(), autosave::pending()) {
+        let message = match &app.rescued {
+            Some(path) => format!(
+                "cannot save: {}, the todos were written to {}, copy it over {} once that takes writes",
+                reason, path, DB_PATH
+            ),
+            None => format!(
+                "cannot save: {}, quit without the changes since the last save",
+                reason
+            ),
+        };
+        std::process::exit(cli::other(message));
+    }
+    if let Some(reason) = stopped {
+        terminal.clear()?;
+        std::process::exit(cli::other(reason));
+    }
+    Ok(())
+}
+
+/// Draws the whole screen: the tab bar, the tab or view that is open, the
+/// status bar and any modal on top.
+fn draw<B: Backend>(rect: &mut Frame<B>, app: &mut App) {
+    let size = rect.size();
+    let chrome = Chrome::new(size, app.compact);
+
+    // the board says why when the DB can't be read
+    let remaining = app.remaining_estimate().map(|r| r.label());
+    let mut status_parts = vec![];
+    match app.completed_today() {
+        0 => {}
+        count => status_parts.push(format!("✔ {} today", count)),
+    }
+    if let (MenuItem::Todos, Some(remaining)) = (app.active_menu_item, &remaining) {
+        status_parts.push(remaining.clone());
+    }
+    let hints = actions::hints(app.active_menu_item);
+    let on_break = app.snapshot.entries.as_ref().ok().and_then(|entries| {
+        timetracking::current_break(entries).map(|current| {
+            format!(
+                "ON BREAK {} (Ctrl+b: resume)",
+                timetracking::format_clock(Utc::now() - current.start)
+            )
+        })
+    });
+    let badge_line = app.session.badges_visible.then(|| {
+        // compact mode draws the bar without a block
+        let borders = if chrome.compact { 0 } else { 2 };
+        let width = size.width.saturating_sub(2 * chrome.margin() + borders);
+        let todos = app.snapshot.todos.as_deref().unwrap_or_default();
+        badges::badge_line(&badges::badges(todos), width as usize)
+    });
+    // compact mode has no status bar title, and keeps the bar itself
+    // only while it says something
+    let message = app
+        .toasts
+        .label(Instant::now())
+        .or_else(|| app.status_message.clone());
+    let footer_height = if !chrome.compact {
+        3
+    } else if on_break.is_some()
+        || message.is_some()
+        || autosave::failure().is_some()
+        || app.recording_macro().is_some()
+        || autosave::stale()
+    {
+        1
+    } else {
+        0
+    } + u16::from(badge_line.is_some());
+    let mut footer = vec![];
+    if let (true, Some(register)) = (chrome.compact, app.recording_macro()) {
+        footer.push(Span::styled(
+            format!("recording @{} ", register),
+            Style::default().fg(Color::LightRed),
+        ));
+    }
+    if autosave::stale() {
+        footer.push(Span::styled("● ", Style::default().fg(Color::Yellow)));
+    }
+    if let Some(reason) = autosave::failure() {
+        let kept = match autosave::changes() {
+            0 => "changes".to_string(),
+            1 => "1 change".to_string(),
+            changes => format!("{} changes", changes),
+        };
+        footer.push(Span::styled(
+            format!(
+                "cannot save: {} — {} kept in memory, Ctrl+s: retry ",
+                reason, kept
+            ),
+            Style::default()
+                .fg(Color::White)
+                .bg(Color::Red)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(on_break) = on_break {
+        footer.push(Span::styled(
+            on_break,
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    match message {
+        Some(message) => footer.push(Span::styled(
+            format!(" {}", message),
+            Style::default().fg(Color::LightCyan),
+        )),
+        None if footer.is_empty() => footer.push(Span::styled(
+            hints.as_str(),
+            Style::default().fg(Color::DarkGray),
+        )),
+        None => {}
+    }
+    // in compact mode an idle bar is down to the badges
+    let mut footer = if footer.is_empty() {
+        vec![]
+    } else {
+        vec![Spans::from(footer)]
+    };
+    if let Some(badge_line) = badge_line {
+        footer.push(Spans::from(vec![Span::styled(
+            badge_line,
+            Style::default().fg(Color::Gray),
+        )]));
+    }
+    let tracked = sparkline::sparkline(
+        &sparkline::daily_totals(
+            app.snapshot.entries.as_deref().unwrap_or_default(),
+            zone::now().date_naive(),
+            sparkline::SCALE_DAYS,
+            zone::current(),
+            Utc::now(),
+        ),
+        sparkline::SPARKLINE_DAYS,
+        sparkline::SPARKLINE_DAYS,
+    );
+    let mut copyright = Paragraph::new(footer).alignment(Alignment::Center);
+    if !chrome.compact {
+        copyright = copyright.block(chrome.block().title(match app.recording_macro() {
+            Some(register) => format!("recording @{}", register),
+            None if !status_parts.is_empty() => status_parts.join(" · "),
+            None => "Copyright".to_string(),
+        }));
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(chrome.margin())
+        .constraints(
+            [
+                Constraint::Length(if chrome.compact { 1 } else { 3 }),
+                Constraint::Min(2),
+                Constraint::Length(footer_height),
+            ]
+            .as_ref(),
+        )
+        .split(size);
+
+    let mut hotkey_set: HashSet<&str> = HashSet::new();
+    let menu = MENU_TITLES
+        .iter()
+        .map(|t| {
+            // the first letter no earlier title took, or else the third
+            let at = (0..2)
+                .find(|i| !hotkey_set.contains(&t[*i..*i + 1]))
+                .unwrap_or(2);
+            hotkey_set.insert(&t[at..at + 1]);
+            let (before, rest) = t.split_at(at);
+            let (letter, after) = rest.split_at(1);
+            let hotkey = Span::styled(
+                letter,
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::UNDERLINED),
+            );
+            if chrome.compact {
+                Spans::from(vec![hotkey])
+            } else {
+                Spans::from(vec![
+                    Span::styled(before, Style::default().fg(Color::White)),
+                    hotkey,
+                    Span::styled(after, Style::default().fg(Color::White)),
+                ])
+            }
+        })
+        .collect();
+
+    let mut tabs = Tabs::new(menu)
+        .select(app.active_menu_item.into())
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Yellow))
+        .divider(Span::raw("|"));
+    if !chrome.compact {
+        tabs = tabs.block(Block::default().title("Menu").borders(Borders::ALL));
+    }
+
+    rect.render_widget(tabs, chunks[0]);
+    match (&app.view, app.active_menu_item) {
+        (Some(View::Today(date)), _) => {
+            let title = "Today view (Esc: close)";
+            match (activity::read_activity(), &app.snapshot.entries) {
+                (Ok(log), Ok(entries)) => {
+                    let summary = review::day_summary(
+                        &log,
+                        entries,
+                        &daynotes::read_day_notes().unwrap_or_default(),
+                        *date,
+                        zone::current(),
+                        Utc::now(),
+                    );
+                    review::render_day_summary(rect, &summary, chrome, chunks[1]);
+                }
+                (Err(err), _) => widgets::render_error(rect, title, &err, chrome, chunks[1]),
+                (_, Err(err)) => widgets::render_error(rect, title, err, chrome, chunks[1]),
+            }
+        }
+        (Some(View::Month(selected)), _) => match &app.snapshot.todos {
+            Ok(todos) => {
+                let data = month::MonthData {
+                    todos,
+                    days_off: app.snapshot.calendar.as_deref().unwrap_or_default(),
+                    week_start: app.config.week_start,
+                    today: zone::now().date_naive(),
+                    zone: zone::current(),
+                };
+                month::render_month(rect, *selected, &data, chrome, chunks[1]);
+            }
+            Err(err) => widgets::render_error(
+                rect,
+                &format!("{} (Esc: close)", selected.format("%B %Y")),
+                err,
+                chrome,
+                chunks[1],
+            ),
+        },
+        (Some(View::Inconsistencies), _) => integrity::render_inconsistencies(
+            rect,
+            &integrity::problems(),
+            app.problem_selected,
+            chrome,
+            chunks[1],
+        ),
+        (Some(View::Storage(report)), _) => match report {
+            Ok(report) => storage::render_storage(rect, report, chrome, chunks[1]),
+            Err(err) => widgets::render_error(rect, "Storage (Esc: close)", err, chrome, chunks[1]),
+        },
+        (Some(View::Timesheet(month)), _) => {
+            match app.reports.timesheet(*month, &app.report_filter) {
+                Some(Ok(sheet)) => {
+                    timesheet::render_timesheet(rect, sheet, &app.report_filter, chrome, chunks[1])
+                }
+                Some(Err(err)) => timesheet::render_placeholder(
+                    rect,
+                    *month,
+                    &app.report_filter,
+                    err,
+                    chrome,
+                    chunks[1],
+                ),
+                None => timesheet::render_placeholder(
+                    rect,
+                    *month,
+                    &app.report_filter,
+                    "computing…",
+                    chrome,
+                    chunks[1],
+                ),
+            }
+        }
+        (Some(View::Help), _) => actions::render_help(rect, app.help_scroll, chrome, chunks[1]),
+        (Some(View::Stats(history)), _) => match history {
+            Ok(history) => {
+                let stats = pomodoro::stats(history, app.config.week_start, Utc::now());
+                app.stats_scroll.render(
+                    rect,
+                    &[pomodoro::section(history, &stats)],
+                    "Stats (Esc: close)",
+                    chrome,
+                    chunks[1],
+                );
+            }
+            Err(err) => widgets::render_error(rect, "Stats (Esc: close)", err, chrome, chunks[1]),
+        },
+        (Some(View::Todo(id)), _) => match (&app.snapshot.todos, &app.snapshot.entries) {
+            (Ok(todos), Ok(entries)) => {
+                if let Some(todo) = todos.iter().find(|t| t.id == *id) {
+                    let now = Utc::now();
+                    let selected_link = app
+                        .link_selected
+                        .and_then(|i| app.todo_links(todo.id).get(i).copied());
+                    let history = history::work_history(entries, todo.id, now);
+                    history::render_todo_view(
+                        rect,
+                        todo,
+                        todos,
+                        &history,
+                        &mut app.history_state,
+                        app.attachment_selected,
+                        &app.config.priority_escalation,
+                        &app.config.label_colors,
+                        app.references.referenced_by(todo.id),
+                        selected_link,
+                        now,
+                        chrome,
+                        chunks[1],
+                    );
+                }
+            }
+            (Err(err), _) | (_, Err(err)) => widgets::render_error(
+                rect,
+                &format!("Todo {} (Esc: close)", id),
+                err,
+                chrome,
+                chunks[1],
+            ),
+        },
+        (None, menu_item) => match menu_item {
+            MenuItem::Home => match (&app.snapshot.todos, &app.snapshot.entries) {
+                (Ok(_), Ok(_)) => {
+                    app.home_scroll
+                        .render(rect, &app.snapshot.home, "Home", chrome, chunks[1])
+                }
+                (Err(err), _) | (_, Err(err)) => {
+                    widgets::render_error(rect, "Home", err, chrome, chunks[1])
+                }
+            },
+            MenuItem::Todos => match &app.snapshot.todos {
+                Ok(all) => {
+                    let (board_area, detail_area) = todos_layout(chunks[1], &app.session);
+
+                    let collapsed = app.session.collapsed_columns;
+                    let widths = column_widths(board_area.width, collapsed, chrome);
+                    let todos_horizontal_chunks = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints(
+                            widths
+                                .iter()
+                                .map(|width| Constraint::Length(*width))
+                                .collect::<Vec<_>>(),
+                        )
+                        .split(board_area);
+
+                    let entries = app.snapshot.entries.as_deref().unwrap_or_default();
+                    let stale: HashSet<usize> = match app.config.stale_doing {
+                        Some(policy) => {
+                            maintenance::stale_doing(all, entries, policy.days, Utc::now())
+                                .iter()
+                                .map(|todo| todo.id)
+                                .collect()
+                        }
+                        None => HashSet::new(),
+                    };
+                    let (todo_list, doing_list, done_list, details_table) = render_todos(
+                        all,
+                        app.selected_in(all),
+                        &app.marked_todos,
+                        &app.board_filter,
+                        &app.highlights,
+                        timetracking::running_todo(entries),
+                        &BoardLayout {
+                            fields: &app.board_fields,
+                            escalation: &app.config.priority_escalation,
+                            label_colors: &app.config.label_colors,
+                            glyphs: &app.glyphs,
+                            stale: &stale,
+                            entries,
+                            progress_everywhere: app.config.progress_everywhere,
+                            following_timer: app.following_timer(),
+                            wip_limits: &app.config.wip_limits,
+                            widths,
+                            focused: app.focused_column,
+                            chrome,
+                        },
+                    );
+
+                    let lists = [todo_list, doing_list, done_list];
+                    for (column, list) in lists.into_iter().enumerate() {
+                        let area = todos_horizontal_chunks[column];
+                        if collapsed[column] {
+                            let status =
+                                [TodoStatus::Todo, TodoStatus::Doing, TodoStatus::Done][column];
+                            let count = column_todos(all, status, &app.board_filter).len();
+                            rect.render_widget(
+                                collapsed_column(
+                                    ["ToDo", "Doing", "Done"][column],
+                                    count,
+                                    chrome.pane(column < 2),
+                                ),
+                                area,
+                            );
+                            continue;
+                        }
+                        let state = match column {
+                            0 => &mut app.todo_list_state,
+                            1 => &mut app.doing_list_state,
+                            _ => &mut app.done_list_state,
+                        };
+                        rect.render_stateful_widget(list, area, state);
+                        if let (0, Some(bottom), Some(remaining)) =
+                            (column, column_bottom(area, chrome), &remaining)
+                        {
+                            rect.render_widget(
+                                Paragraph::new(format!(" {} ", remaining))
+                                    .style(Style::default().fg(Color::DarkGray)),
+                                bottom,
+                            );
+                        }
+                    }
+
+                    if let Some(detail_area) = detail_area {
+                        rect.render_widget(details_table, detail_area);
+                    }
+                }
+                Err(err) => widgets::render_error(rect, "Todos", err, chrome, chunks[1]),
+            },
+            MenuItem::Timers => match &app.snapshot.timers {
+                Ok(timers) => rect.render_stateful_widget(
+                    timers::render_timers(timers, Utc::now(), chrome),
+                    chunks[1],
+                    &mut app.timers_table_state,
+                ),
+                Err(err) => widgets::render_error(rect, "Timers", err, chrome, chunks[1]),
+            },
+            MenuItem::TimeTracking => {
+                let now = Utc::now();
+                // the panes that need the entries say so when they can't be read
+                let entries = &app.snapshot.entries;
+                let time_tracking_chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+                    .split(chunks[1]);
+                let summary_chunks = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+                    .split(time_tracking_chunks[0]);
+                match app.reports.week(&app.report_filter) {
+                    Some(Ok(week)) => rect.render_widget(
+                        timetracking::render_time_tracking(week, &app.report_filter, chrome),
+                        summary_chunks[0],
+                    ),
+                    Some(Err(err)) => widgets::render_error(
+                        rect,
+                        &timetracking::week_title(&app.report_filter),
+                        &err,
+                        chrome,
+                        summary_chunks[0],
+                    ),
+                    None => widgets::render_placeholder(
+                        rect,
+                        &timetracking::week_title(&app.report_filter),
+                        "computing…",
+                        chrome,
+                        summary_chunks[0],
+                    ),
+                }
+                match (entries, &app.snapshot.overrides, &app.snapshot.calendar) {
+                    (Ok(entries), Ok(overrides), Ok(calendar)) => rect.render_widget(
+                        flextime::render_days(
+                            entries,
+                            overrides,
+                            calendar,
+                            &app.config,
+                            now,
+                            chrome,
+                        ),
+                        summary_chunks[1],
+                    ),
+                    (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => {
+                        widgets::render_error(rect, "Days", err, chrome, summary_chunks[1])
+                    }
+                }
+                let detail_chunks = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+                    .split(time_tracking_chunks[1]);
+                match &app.breakdown {
+                    Some(breakdown) => breakdown::render_breakdown(
+                        rect,
+                        breakdown,
+                        app.breakdown_scale,
+                        chrome,
+                        detail_chunks[1],
+                    ),
+                    None => widgets::render_placeholder(
+                        rect,
+                        "Time per category",
+                        "computing…",
+                        chrome,
+                        detail_chunks[1],
+                    ),
+                }
+                match (entries, app.timeline) {
+                    (Err(err), _) => {
+                        widgets::render_error(rect, "Entries", err, chrome, detail_chunks[0])
+                    }
+                    (Ok(entries), Some(day)) => timeline::render_timeline(
+                        rect,
+                        &timeline::day_entries(entries, day, zone::current(), now),
+                        day,
+                        app.config.working_hours,
+                        app.timeline_selected,
+                        now,
+                        chrome,
+                        detail_chunks[0],
+                    ),
+                    (Ok(entries), None) => match &app.snapshot.todos {
+                        Ok(todos) => rect.render_stateful_widget(
+                            timetracking::render_entries(
+                                &timetracking::week_entries(entries, &app.config, now),
+                                &todos.iter().map(|t| t.id).collect(),
+                                app.entry_clock,
+                                now,
+                                chrome,
+                            ),
+                            detail_chunks[0],
+                            &mut app.entries_table_state,
+                        ),
+                        Err(err) => {
+                            widgets::render_error(rect, "Entries", err, chrome, detail_chunks[0])
+                        }
+                    },
+                }
+            }
+        },
+    }
+    rect.render_widget(copyright, chunks[2]);
+    // the sparkline sits in the right corner of the status bar's
+    // top border, or of its first line in compact mode
+    let corner = if chrome.compact { 0 } else { 1 };
+    let tracked_width = tracked.chars().count() as u16;
+    if chunks[2].height > 0 && chunks[2].width > tracked_width + 2 * corner {
+        let area = Rect {
+            x: chunks[2].right() - tracked_width - corner,
+            y: chunks[2].y,
+            width: tracked_width,
+            height: 1,
+        };
+        rect.render_widget(
+            Paragraph::new(Span::styled(
+                tracked,
+                Style::default().fg(Color::LightGreen),
+            )),
+            area,
+        );
+    }
+
+    match &app.modal {
+        Some(Modal::BatchEdit(form)) => batch::render_batch_form(rect, form, size),
+        Some(Modal::TimerForm(form)) => timerform::render_timer_form(rect, form, size),
+        Some(Modal::TodoForm(form)) => todoform::render_todo_form(rect, form, size),
+        Some(Modal::KeyConflicts(screen)) => keymap::render_key_conflicts(rect, screen, size),
+        Some(Modal::Preview(preview)) => preview::render_preview(rect, preview, size),
+        Some(Modal::Description(editor)) => {
+            description::render_description_editor(rect, editor, size)
+        }
+        Some(Modal::DiscardDraft(editor)) => {
+            description::render_description_editor(rect, editor, size);
+            widgets::render_question(rect, "Discard the changes to this description? y/n", size)
+        }
+        Some(Modal::ResumeDraft { editor, draft }) => widgets::render_question(
+            rect,
+            &description::resume_question(editor.todo_id, draft.saved_at, Utc::now()),
+            size,
+        ),
+        Some(Modal::Carryover(carryover)) => carryover::render_carryover(rect, carryover, size),
+        Some(Modal::Palette(palette)) => actions::render_palette(rect, palette, size),
+        Some(Modal::ConfirmQuit(check)) => widgets::render_question(rect, &check.question(), size),
+        Some(Modal::Search(search)) => search::render_search(rect, search, size),
+        Some(Modal::Day(detail)) => month::render_day_detail(rect, detail, size),
+        Some(Modal::Menu(menu)) => menu::render_menu(rect, menu, size),
+        Some(Modal::ConfirmDelete(id, title)) => widgets::render_question(
+            rect,
+            &format!("Delete todo {} \"{}\"? (y/n)", id, title),
+            size,
+        ),
+        Some(Modal::ConfirmDetach(id, _, path)) => widgets::render_question(
+            rect,
+            &format!(
+                "Remove {} from todo {}? (y/n)",
+                attachments::label(path),
+                id
+            ),
+            size,
+        ),
+        Some(Modal::Unarchive(hit)) => widgets::render_question(
+            rect,
+            &format!("Unarchive todo {} \"{}\"? y/n", hit.id, hit.title),
+            size,
+        ),
+        Some(Modal::Relink(relink)) => widgets::render_question(rect, &relink.question(), size),
+        Some(Modal::EndBreak { length }) => {
+            widgets::render_question(rect, &end_break_question(*length), size)
+        }
+        Some(Modal::EntryNote { prompt, .. })
+        | Some(Modal::EntryTags { prompt, .. })
+        | Some(Modal::ReportTag(prompt))
+        | Some(Modal::DayNote { prompt, .. })
+        | Some(Modal::ArchiveOld(prompt))
+        | Some(Modal::WipLimit { prompt, .. })
+        | Some(Modal::Rescue(prompt))
+        | Some(Modal::Attachment { prompt, .. }) => widgets::render_prompt(rect, prompt, size),
+        Some(Modal::Command(line)) => cmdline::render_command_line(rect, line, size),
+        None => {}
+    }
+}
+
+/// Handles events until one needs a redraw, telling the input thread the
+/// tick rate the app's activity calls for. Idle ticks that change nothing
+/// on screen are waited through. Err with the reason to exit when the input
+/// thread is gone.
+fn wait_for_redraw(
+    app: &mut App,
+    rx: &mpsc::Receiver<Event<KeyEvent>>,
+    tick_rate: &AtomicU64,
+) -> Result<(), &'static str> {
+    loop {
+        let activity = app.activity();
+        let idle_tick = Duration::from_millis(app.config.idle_tick_ms);
+        let rate = power::tick_rate(activity, idle_tick);
+        tick_rate.store(rate.as_millis() as u64, Ordering::Relaxed);
+        match rx.recv_timeout(rate + INPUT_TIMEOUT) {
+            Ok(Event::Input(event)) => {
+                app.handle_key(event);
+                app.reports.expire();
+                return Ok(());
+            }
+            Ok(Event::Tick) => {
+                let message = app.status_message.clone();
+                let filter = app.board_filter.label();
+                app.on_tick();
+                // a fading toast or highlight still needs its last frame,
+                // and a stopped timer the filter it gives back
+                if !activity.is_idle()
+                    || app.status_message != message
+                    || app.board_filter.label() != filter
+                {
+                    return Ok(());
+                }
+            }
+            Ok(Event::Resize) => return Ok(()),
+            Ok(Event::ReportReady(ready)) => {
+                if app.reports.deliver(*ready, Instant::now()) {
+                    return Ok(());
+                }
+            }
+            Ok(Event::Error(err)) => {
+                app.status_message = Some(format!("terminal input failed: {}", err));
+                return Ok(());
+            }
+            Err(_) => return Err("terminal input stopped, exiting"),
+        }
+    }
+}
+
+const MIN_BOARD_HEIGHT: u16 = 5;
+// borders, header and one row
+const MIN_DETAIL_HEIGHT: u16 = 4;
+
+/// Splits the Todos tab into the board and, when shown, the detail pane,
+/// keeping both above their minimum height. The board wins when the area
+/// can't fit both.
+fn todos_layout(area: Rect, session: &Session) -> (Rect, Option<Rect>) {
+    if !session.detail_visible || area.height < MIN_BOARD_HEIGHT + MIN_DETAIL_HEIGHT {
+        return (area, None);
+    }
+    let detail_height = (area.height * session.detail_percent / 100)
+        .max(MIN_DETAIL_HEIGHT)
+        .min(area.height - MIN_BOARD_HEIGHT);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Min(MIN_BOARD_HEIGHT),
+                Constraint::Length(detail_height),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+    (chunks[0], Some(chunks[1]))
+}
+
+/// Width of a collapsed board column: a letter and its borders.
+fn collapsed_width(chrome: Chrome) -> u16 {
+    1 + chrome.frame(true).0
+}
+
+/// Splits `width` between the board columns: collapsed ones get a strip
+/// and the rest share what is left evenly, the last one taking the
+/// remainder.
+fn column_widths(width: u16, collapsed: [bool; 3], chrome: Chrome) -> [u16; 3] {
+    let strip = collapsed_width(chrome);
+    let strips = collapsed.iter().filter(|c| **c).count() as u16;
+    let expanded = (3 - strips).max(1);
+    let rest = width.saturating_sub(strips * strip);
+    let last = collapsed.iter().rposition(|c| !c);
+    let mut widths = [0; 3];
+    for (column, width) in widths.iter_mut().enumerate() {
+        *width = if collapsed[column] {
+            strip
+        } else if Some(column) == last {
+            rest - rest / expanded * (expanded - 1)
+        } else {
+            rest / expanded
+        };
+    }
+    widths
+}
+
+/// A collapsed board column: its name and count written downwards.
+fn collapsed_column<'a>(name: &str, count: usize, block: Block<'a>) -> Paragraph<'a> {
+    let mut lines: Vec<Spans> = name
+        .chars()
+        .map(|c| Spans::from(vec![Span::raw(c.to_string())]))
+        .collect();
+    lines.push(Spans::from(vec![Span::raw("")]));
+    lines.extend(count.to_string().chars().map(|c| {
+        Spans::from(vec![Span::styled(
+            c.to_string(),
+            Style::default().fg(Color::DarkGray),
+        )])
+    }));
+    Paragraph::new(lines).block(block)
+}
+
+/// The bottom border of a column, where a single line summary fits
+/// without taking a row from the list. Compact columns have none.
+fn column_bottom(column: Rect, chrome: Chrome) -> Option<Rect> {
+    if chrome.compact || column.height < 3 || column.width < 4 {
+        return None;
+    }
+    Some(Rect {
+        x: column.x + 1,
+        y: column.y + column.height - 1,
+        width: column.width - 2,
+        height: 1,
+    })
+}
+
+fn render_todos<'a>(
+    all: &[Todo],
+    selected_todo: Option<Todo>,
+    marked_todos: &HashSet<usize>,
+    filter: &Filter,
+    highlights: &Highlights,
+    running_todo: Option<usize>,
+    layout: &BoardLayout,
+) -> (List<'a>, List<'a>, List<'a>, Table<'a>) {
+    let title = |name: &str| match filter.label() {
+        Some(label) if layout.following_timer => {
+            format!("{} [{}] (following timer)", name, label)
+        }
+        Some(label) => format!("{} [{}]", name, label),
+        None => name.to_string(),
+    };
+
+    let border = |column: usize| {
+        Style::default().fg(if column == 2 && highlights.is_lit(Target::DoneColumn) {
+            Color::Green
+        } else if column == layout.focused {
+            Color::Yellow
+        } else {
+            Color::White
+        })
+    };
+
+    let todo_list = column_todos(all, TodoStatus::Todo, filter);
+    let doing_list = column_todos(all, TodoStatus::Doing, filter);
+    let done_list = column_todos(all, TodoStatus::Done, filter);
+
+    // a column with a WIP limit shows its count against it, in red past it
+    let column_block = |column: usize, name: &str, count: usize| {
+        let block = layout.chrome.pane(column < 2).border_style(border(column));
+        match layout.wip_limits.get(name) {
+            Some(limit) => block.title(Span::styled(
+                format!("{} {}/{}", title(name), count, limit),
+                if count > *limit {
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                },
+            )),
+            None => block.title(title(name)),
+        }
+    };
+    let todos_block = column_block(0, "ToDo", todo_list.len());
+    let doing_block = column_block(1, "Doing", doing_list.len());
+    let done_block = column_block(2, "Done", done_list.len());
+
+    let cursor = cursor::current();
+    let item = |todo: &Todo, column: usize| {
+        todo_list_item(
+            todo,
+            all,
+            marked_todos,
+            running_todo,
+            highlights,
+            layout,
+            // inside the column borders, past the cursor's symbol
+            layout.widths[column]
+                .saturating_sub(layout.chrome.frame(column < 2).0)
+                .saturating_sub(cursor.width()),
+        )
+    };
+    let items_todo: Vec<_> = todo_list.iter().map(|todo| item(todo, 0)).collect();
+
+    let items_doing: Vec<_> = doing_list.iter().map(|todo| item(todo, 1)).collect();
+
+    let items_done: Vec<_> = sort::done_rows(&done_list, zone::now().date_naive())
+        .iter()
+        .map(|row| match row {
+            sort::DoneRow::Day(label) => ListItem::new(Spans::from(vec![Span::styled(
+                label.clone(),
+                Style::default().fg(Color::DarkGray),
+            )])),
+            sort::DoneRow::Todo(todo) => item(todo, 2),
+        })
+        .collect();
+
+    // only the focused column shows its selection, so there's one cursor
+    let list = |items: Vec<ListItem<'a>>, block: Block<'a>, column: usize| {
+        let list = List::new(items).block(block);
+        if column == layout.focused {
+            list.highlight_style(cursor.highlight())
+                .highlight_symbol(cursor.symbol())
+        } else {
+            list
+        }
+    };
+    let list_todo = list(items_todo, todos_block, 0);
+    let list_doing = list(items_doing, doing_block, 1);
+    let list_done = list(items_done, done_block, 2);
+
+    let detail_rows = match selected_todo {
+        Some(selected_todo) => {
+            let due = subtasks::effective_due(&selected_todo, all);
+            vec![Row::new(vec![
+                Cell::from(Span::raw(selected_todo.id.to_string())),
+                Cell::from(if running_todo == Some(selected_todo.id) {
+                    Span::styled(format!("▶ {}", selected_todo.title), running_style())
+                } else {
+                    let style = label::title_style(
+                        selected_todo.color.as_deref(),
+                        title_cues(&selected_todo, all, layout, Utc::now()),
+                        layout.label_colors,
+                    );
+                    Span::styled(selected_todo.title.clone(), style)
+                }),
+                Cell::from(refs::spans(
+                    selected_todo.description(),
+                    &all.iter().map(|t| t.id).collect(),
+                    Style::default(),
+                )),
+                Cell::from(Span::raw(selected_todo.category)),
+                Cell::from(Span::raw(selected_todo.created_at.to_string())),
+                Cell::from(match due {
+                    Some(due) if due < Utc::now() => Span::styled(
+                        urgency::due_in(due, Utc::now()),
+                        Style::default().fg(Color::Red),
+                    ),
+                    Some(due) => Span::raw(urgency::due_in(due, Utc::now())),
+                    None => Span::raw(""),
+                }),
+                Cell::from(Span::raw(timetracking::format_duration(
+                    history::time_spent(layout.entries, selected_todo.id, Utc::now()),
+                ))),
+            ])]
+        }
+        None => vec![],
+    };
+
+    let todo_detail = Table::new(detail_rows)
+        .header(Row::new(vec![
+            Cell::from(Span::styled(
+                "ID",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Title",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Description",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Category",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Created At",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Due in",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Spent",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+        ]))
+        .block(
+            layout
+                .chrome
+                .block()
+                .title("Detail - o: open, a: attach a file"),
+        )
+        .widths(&[
+            Constraint::Percentage(5),  // id
+            Constraint::Percentage(20), // title
+            Constraint::Percentage(20), // description
+            Constraint::Percentage(10), // category
+            Constraint::Percentage(20), // date
+            Constraint::Percentage(10), // due in
+            Constraint::Percentage(10), // time spent
+        ]);
+
+    (list_todo, list_doing, list_done, todo_detail)
+}
+
+fn end_break_question(length: chrono::Duration) -> String {
+    format!(
+        "Back after {}. Log the break as a time entry? y: log, n: discard, Esc: stay on break",
+        timetracking::format_duration(length)
+    )
+}
+
+fn running_style() -> Style {
+    Style::default()
+        .fg(Color::LightGreen)
+        .add_modifier(Modifier::BOLD)
+}
+
+/// How the board is drawn: the decorations items show, what colors their
+/// titles, the width of each column, the focused one and the chrome around
+/// them.
+struct BoardLayout<'f> {
+    fields: &'f [Field],
+    escalation: &'f [EscalationRule],
+    label_colors: &'f BTreeMap<String, Color>,
+    glyphs: &'f Glyphs,
+    /// The todos left in Doing for longer than `stale_doing` allows.
+    stale: &'f HashSet<usize>,
+    entries: &'f [timetracking::TimeEntry],
+    /// Progress readouts on every column, not only Doing.
+    progress_everywhere: bool,
+    /// The filter is the one the running timer set.
+    following_timer: bool,
+    /// Most todos each column should hold, by column title.
+    wip_limits: &'f BTreeMap<String, usize>,
+    widths: [u16; 3],
+    focused: usize,
+    chrome: Chrome,
+}
+
+fn todo_list_item<'a>(
+    todo: &Todo,
+    todos: &[Todo],
+    marked_todos: &HashSet<usize>,
+    running_todo: Option<usize>,
+    highlights: &Highlights,
+    layout: &BoardLayout,
+    width: u16,
+) -> ListItem<'a> {
+    let mut prefix = vec![];
+    let running = running_todo == Some(todo.id);
+    if running {
+        prefix.push(Span::styled("▶ ", running_style()));
+    }
+    if todo.pinned {
+        prefix.push(Span::styled("⚑ ", Style::default().fg(Color::Magenta)));
+    }
+    if let Some(glyph) = layout.glyphs.for_category(&todo.category) {
+        prefix.push(Span::raw(format!("{} ", glyph)));
+    }
+    let now = zone::now();
+    let title = if marked_todos.contains(&todo.id) {
+        Span::styled(
+            format!("● {}", todo.title),
+            Style::default().fg(Color::LightCyan),
+        )
+    } else if running {
+        Span::styled(todo.title.clone(), running_style())
+    } else {
+        let style = label::title_style(
+            todo.color.as_deref(),
+            title_cues(todo, todos, layout, now.with_timezone(&Utc)),
+            layout.label_colors,
+        );
+        if todo.pinned {
+            Span::styled(todo.title.clone(), style.add_modifier(Modifier::BOLD))
+        } else {
+            Span::styled(todo.title.clone(), style)
+        }
+    };
+    let progress = (layout.progress_everywhere || todo.status() == TodoStatus::Doing)
+        .then(|| Progress::of(todo, todos, layout.entries, now.with_timezone(&Utc)));
+    let spans = fields::fit(
+        prefix,
+        title,
+        todo,
+        todos,
+        progress.as_ref(),
+        layout.fields,
+        layout.escalation,
+        now,
+        width as usize,
+    );
+    let item = ListItem::new(Spans::from(spans));
+    if highlights.is_lit(Target::Todo(todo.id)) {
+        item.style(Style::default().bg(Color::Green))
+    } else {
+        item
+    }
+}
+
+/// What colors `todo`'s title on the board besides its label.
+fn title_cues(
+    todo: &Todo,
+    todos: &[Todo],
+    layout: &BoardLayout,
+    now: DateTime<Utc>,
+) -> label::Cues {
+    label::Cues {
+        overdue: todo.status() != TodoStatus::Done
+            && subtasks::effective_due(todo, todos).is_some_and(|due| due < now),
+        stale: layout.stale.contains(&todo.id),
+        priority: escalation::effective_priority(todo, todos, now, layout.escalation).priority,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::tests::with_app;
+    use crate::cursor::CursorStyle;
+    use crossterm::event::{KeyCode, KeyModifiers};
+    use crossterm::ErrorKind;
+    use std::collections::VecDeque;
+    use tui::backend::TestBackend;
+    use tui::buffer::{Buffer, Cell};
+
+    fn eio() -> ErrorKind {
+        ErrorKind::IoError(io::Error::other("EIO"))
+    }
+
+    /// Hands out its events in turn, then fails like a detached terminal.
+    struct Script(VecDeque<crossterm::Result<CEvent>>);
+
+    impl EventSource for Script {
+        fn poll(&mut self, _timeout: Duration) -> crossterm::Result<bool> {
+            match self.0.front() {
+                Some(Ok(_)) => Ok(true),
+                Some(Err(_)) | None => {
+                    Err(self.0.pop_front().and_then(Result::err).unwrap_or_else(eio))
+                }
+            }
+        }
+
+        fn read(&mut self) -> crossterm::Result<CEvent> {
+            self.0.pop_front().unwrap_or_else(|| Err(eio()))
+        }
+    }
+
+    /// A key press whenever asked.
+    struct Keys;
+
+    impl EventSource for Keys {
+        fn poll(&mut self, _timeout: Duration) -> crossterm::Result<bool> {
+            Ok(true)
+        }
+
+        fn read(&mut self) -> crossterm::Result<CEvent> {
+            Ok(CEvent::Key(KeyEvent::new(
+                KeyCode::Char('x'),
+                KeyModifiers::NONE,
+            )))
+        }
+    }
+
+    fn spawn(
+        source: impl EventSource + Send + 'static,
+    ) -> (mpsc::Receiver<Event<KeyEvent>>, thread::JoinHandle<()>) {
+        let rate = Arc::new(AtomicU64::new(power::FAST_TICK.as_millis() as u64));
+        spawn_at(source, rate)
+    }
+
+    fn spawn_at(
+        source: impl EventSource + Send + 'static,
+        rate: Arc<AtomicU64>,
+    ) -> (mpsc::Receiver<Event<KeyEvent>>, thread::JoinHandle<()>) {
+        let (tx, rx) = mpsc::channel();
+        (rx, thread::spawn(move || forward_input(source, tx, rate)))
+    }
+
+    /// No input, waiting out each poll like a terminal would. Fails the
+    /// poll once one waits longer than the fast tick.
+    struct Quiet;
+
+    impl EventSource for Quiet {
+        fn poll(&mut self, timeout: Duration) -> crossterm::Result<bool> {
+            if timeout > power::FAST_TICK {
+                return Err(eio());
+            }
+            thread::sleep(timeout);
+            Ok(false)
+        }
+
+        fn read(&mut self) -> crossterm::Result<CEvent> {
+            Err(eio())
+        }
+    }
+
+    #[test]
+    fn the_input_thread_gives_up_after_repeated_errors() {
+        let (rx, input) = spawn(Script(VecDeque::new()));
+        let errors = rx
+            .iter()
+            .filter(|event| matches!(event, Event::Error(_)))
+            .count();
+        assert_eq!(errors, MAX_INPUT_ERRORS);
+        input.join().unwrap();
+    }
+
+    #[test]
+    fn the_input_thread_stops_once_nobody_listens() {
+        let (rx, input) = spawn(Keys);
+        assert!(matches!(rx.recv().unwrap(), Event::Input(_)));
+        drop(rx);
+        input.join().unwrap();
+    }
+
+    #[test]
+    fn the_input_thread_follows_a_change_of_tick_rate() {
+        let rate = Arc::new(AtomicU64::new(60_000));
+        let (rx, input) = spawn_at(Quiet, Arc::clone(&rate));
+        // slow: nothing within a few fast ticks, and no poll waits longer
+        let slow = rx.recv_timeout(3 * power::FAST_TICK);
+        assert!(slow.is_err(), "{:?}", slow.map(|_| ()));
+
+        rate.store(10, Ordering::Relaxed);
+        let started = Instant::now();
+        assert!(matches!(
+            rx.recv_timeout(Duration::from_secs(2)),
+            Ok(Event::Tick)
+        ));
+        // the change is seen within one fast tick, not after the slow one
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(matches!(
+            rx.recv_timeout(Duration::from_secs(2)),
+            Ok(Event::Tick)
+        ));
+        drop(rx);
+        input.join().unwrap();
+    }
+
+    #[test]
+    fn the_app_asks_for_the_idle_tick_only_when_idle() {
+        with_app("idle-tick", serde_json::json!([]), |app| {
+            let (tx, rx) = mpsc::channel();
+            let tick_rate = AtomicU64::new(0);
+            // bound to nothing
+            let key = KeyEvent::new(KeyCode::F(12), KeyModifiers::NONE);
+
+            tx.send(Event::Input(key)).unwrap();
+            assert_eq!(wait_for_redraw(app, &rx, &tick_rate), Ok(()));
+            assert_eq!(tick_rate.load(Ordering::Relaxed), app.config.idle_tick_ms);
+
+            app.toasts
+                .push("saved", None, crate::toast::INFO_FOR, Instant::now());
+            tx.send(Event::Input(key)).unwrap();
+            assert_eq!(wait_for_redraw(app, &rx, &tick_rate), Ok(()));
+            assert_eq!(
+                tick_rate.load(Ordering::Relaxed),
+                power::FAST_TICK.as_millis() as u64
+            );
+        });
+    }
+
+    #[test]
+    fn the_app_reports_input_errors_then_exits() {
+        with_app("input-errors", serde_json::json!([]), |app| {
+            let key = KeyEvent::new(KeyCode::Char('t'), KeyModifiers::NONE);
+            let (rx, input) = spawn(Script(
+                vec![Ok(CEvent::Key(key)), Err(eio())].into_iter().collect(),
+            ));
+            let tick_rate = AtomicU64::new(0);
+
+            assert_eq!(wait_for_redraw(app, &rx, &tick_rate), Ok(()));
+            assert_eq!(app.active_menu_item, MenuItem::Todos);
+            assert_eq!(wait_for_redraw(app, &rx, &tick_rate), Ok(()));
+            assert!(app
+                .status_message
+                .as_deref()
+                .is_some_and(|m| m.starts_with("terminal input failed")));
+
+            let stopped = (0..2 * MAX_INPUT_ERRORS)
+                .map(|_| wait_for_redraw(app, &rx, &tick_rate))
+                .find(Result::is_err);
+            assert_eq!(stopped, Some(Err("terminal input stopped, exiting")));
+            input.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn column_widths_fill_the_board_for_every_collapse_combination() {
+        for compact in [false, true] {
+            let chrome = Chrome { compact };
+            let strip = collapsed_width(chrome);
+            for mask in 0..7u8 {
+                let collapsed = [mask & 1 != 0, mask & 2 != 0, mask & 4 != 0];
+                for width in [40, 80, 81, 200] {
+                    let widths = column_widths(width, collapsed, chrome);
+                    assert_eq!(
+                        widths.iter().sum::<u16>(),
+                        width,
+                        "{:?} {}",
+                        collapsed,
+                        width
+                    );
+                    let expanded = collapsed.iter().filter(|c| !**c).count() as u16;
+                    let share = (width - strip * (3 - expanded)) / expanded;
+                    for (column, width) in widths.iter().enumerate() {
+                        if collapsed[column] {
+                            assert_eq!(*width, strip);
+                        } else {
+                            // the last expanded column takes the remainder
+                            assert!((share..share + expanded).contains(width));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// What `app` draws on a `width` by `height` terminal.
+    fn buffer(app: &mut App, width: u16, height: u16) -> Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|rect| draw(rect, app)).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    /// The rows of the screen `app` draws on a `width` by `height` terminal.
+    fn screen(app: &mut App, width: u16, height: u16) -> Vec<String> {
+        buffer(app, width, height)
+            .content
+            .chunks(width as usize)
+            .map(|row| row.iter().map(|cell| cell.symbol.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn compact_mode_shows_more_of_the_lists_at_60_by_15() {
+        let todos: Vec<_> = (1..=12)
+            .map(|id| {
+                serde_json::json!({
+                    "id": id,
+                    "title": format!("task{:02}", id),
+                    "description": "",
+                    "category": "work",
+                    "status": "Todo",
+                    "created_at": "2024-05-01T09:00:00Z",
+                })
+            })
+            .collect();
+        with_app("compact", serde_json::json!(todos), |app| {
+            app.perform(crate::actions::ActionId::TodosTab);
+            app.take_snapshot();
+            // the detail pane below the board shows the selected one too
+            let list_rows = |rows: &[String]| {
+                rows.iter()
+                    .filter(|row| row.trim_start_matches([' ', '│']).starts_with("task"))
+                    .count()
+            };
+
+            app.compact = Some(false);
+            let boxed = screen(app, 60, 15);
+            app.compact = Some(true);
+            let compact = screen(app, 60, 15);
+            assert_eq!(list_rows(&boxed), 3, "{:#?}", boxed);
+            assert_eq!(list_rows(&compact), 9, "{:#?}", compact);
+            // no margin and no box around the tab bar, which is down to letters
+            assert!(compact[0].starts_with(" H"), "{:?}", compact[0]);
+            assert!(!compact[0].contains("Todos"));
+            assert!(boxed[3].contains("Todos"));
+            // the status bar only shows up with something to say
+            let says = |rows: &[String], text: &str| rows.iter().any(|row| row.contains(text));
+            assert!(says(&boxed, "command palette"));
+            assert!(!says(&compact, "command palette"));
+            app.status_message = Some("saved".to_string());
+            assert!(says(&screen(app, 60, 15), " saved"));
+            app.status_message = None;
+
+            // 60x15 is below the size compact mode turns itself on at
+            app.compact = None;
+            assert_eq!(screen(app, 60, 15), compact);
+            assert_eq!(list_rows(&screen(app, 100, 30)), 12);
+        });
+    }
+
+    #[test]
+    fn each_cursor_style_shows_one_selection_on_the_board() {
+        let todo = |id: usize, status: &str| {
+            serde_json::json!({
+                "id": id,
+                "title": format!("task{}", id),
+                "description": "",
+                "category": "work",
+                "status": status,
+                "created_at": "2024-05-01T09:00:00Z",
+                "completed_at": (status == "Done").then(|| Utc::now().to_rfc3339()),
+            })
+        };
+        let todos = serde_json::json!([todo(1, "Todo"), todo(2, "Doing"), todo(3, "Done")]);
+        with_app("cursor-styles", todos, |app| {
+            app.perform(crate::actions::ActionId::TodosTab);
+            app.take_snapshot();
+            app.todo_list_state.select(Some(0));
+            app.doing_list_state.select(Some(0));
+            // past the day header
+            app.done_list_state.select(Some(1));
+            // how each style marks a cell of the selected row; the tab
+            // hotkeys are underlined too, but not bold
+            let marks = |style: CursorStyle, cell: &Cell| match style {
+                CursorStyle::Block => cell.bg == Color::Yellow,
+                CursorStyle::Prefix => cell.symbol == "▶",
+                CursorStyle::UnderlineBold => cell
+                    .modifier
+                    .contains(Modifier::UNDERLINED | Modifier::BOLD),
+            };
+            for style in [
+                CursorStyle::Block,
+                CursorStyle::Prefix,
+                CursorStyle::UnderlineBold,
+            ] {
+                cursor::set(style);
+                for column in 0..3 {
+                    app.focused_column = column;
+                    let buffer = buffer(app, 100, 30);
+                    // each marked row from its first marked cell on
+                    let marked: Vec<String> = buffer
+                        .content
+                        .chunks(100)
+                        .filter_map(|row| {
+                            let first = row.iter().position(|cell| marks(style, cell))?;
+                            Some(
+                                row[first..]
+                                    .iter()
+                                    .map(|cell| cell.symbol.as_str())
+                                    .collect(),
+                            )
+                        })
+                        .collect();
+                    assert_eq!(marked.len(), 1, "{:?} {}: {:#?}", style, column, marked);
+                    let title = marked[0].trim_start_matches(style.symbol());
+                    assert!(
+                        title.starts_with(&format!("task{}", column + 1)),
+                        "{:?} {}: {:?}",
+                        style,
+                        column,
+                        marked[0]
+                    );
+                }
+            }
+            cursor::set(CursorStyle::Block);
+        });
+    }
+
+    #[test]
+    fn a_timer_started_before_the_app_shows_its_time_counted_since() {
+        with_app("timers-restart", serde_json::json!([]), |app| {
+            let started_at = Utc::now() - chrono::Duration::hours(1);
+            let timers = serde_json::json!([{
+                "id": 1, "name": "focus", "category": "work",
+                "created_at": "2024-05-01T09:00:00Z", "elapsed": 90,
+                "started_at": started_at,
+            }]);
+            std::fs::write(timers::TIMERS_PATH, timers.to_string()).unwrap();
+            app.perform(crate::actions::ActionId::TimersTab);
+            app.take_snapshot();
+            let row = screen(app, 100, 30)
+                .into_iter()
+                .find(|row| row.contains("focus"))
+                .unwrap();
+            assert!(row.contains("1:01:3"), "{}", row);
+            assert!(row.contains("running"), "{}", row);
+        });
+    }
+
+    #[test]
+    fn another_process_writing_the_db_shows_a_dot_until_f5_reloads_it() {
+        let todo = |id: usize| {
+            serde_json::json!({
+                "id": id,
+                "title": format!("task{}", id),
+                "description": "",
+                "category": "work",
+                "status": "Todo",
+                "created_at": "2024-05-01T09:00:00Z",
+            })
+        };
+        let todos = serde_json::json!([todo(1), todo(2), todo(3)]);
+        with_app("stale", todos.clone(), |app| {
+            let key = |app: &mut App, code| app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+            let dot = |app: &mut App| screen(app, 100, 30).iter().any(|row| row.contains("● "));
+            app.perform(crate::actions::ActionId::TodosTab);
+            app.todo_list_state.select(Some(0));
+            key(app, KeyCode::Down);
+            assert_eq!(app.selected_todo().map(|todo| todo.id), Some(2));
+            assert!(!dot(app));
+
+            // the CLI adds a pinned todo, which goes to the top of the column
+            let mut written = todos.as_array().unwrap().clone();
+            let mut pinned = todo(4);
+            pinned["pinned"] = true.into();
+            written.push(pinned);
+            std::fs::write(DB_PATH, serde_json::json!(written).to_string()).unwrap();
+            let later = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+            std::fs::File::options()
+                .write(true)
+                .open(DB_PATH)
+                .and_then(|file| file.set_modified(later))
+                .unwrap();
+            assert!(autosave::stale());
+            assert!(dot(app));
+
+            key(app, KeyCode::F(5));
+            assert!(!autosave::stale());
+            assert!(!dot(app));
+            assert_eq!(app.status_message.as_deref(), Some("refreshed"));
+            assert_eq!(crate::read_db().unwrap().len(), 4);
+            assert_eq!(app.todo_list_state.selected(), Some(2));
+            assert_eq!(app.selected_todo().map(|todo| todo.id), Some(2));
+        });
+    }
+
+    #[test]
+    fn all_columns_collapsed_are_all_strips() {
+        let chrome = Chrome { compact: false };
+        let strip = collapsed_width(chrome);
+        assert_eq!(column_widths(80, [true; 3], chrome), [strip; 3]);
+    }
+}
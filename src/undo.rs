@@ -0,0 +1,228 @@
+use crate::timetracking::update_time_entries;
+use crate::txn::transaction;
+use crate::{update_db, Error, Todo};
+
+const MAX_UNDO_ENTRIES: usize = 50;
+
+pub struct UndoEntry {
+    pub label: String,
+    snapshot: Snapshot,
+}
+
+/// What an undo entry puts back.
+enum Snapshot {
+    Todos {
+        todos: Vec<Todo>,
+        /// The archive as it was, for an operation that moved todos in or
+        /// out of it.
+        archived: Option<Vec<Todo>>,
+        /// The largest id the operation left in the DB, see `restore`.
+        newest: usize,
+    },
+    /// The tags the time entry `id` had of its own.
+    EntryTags {
+        id: usize,
+        tags: Option<Vec<String>>,
+    },
+}
+
+/// Snapshots of the whole todo list taken before each logical operation.
+#[derive(Default)]
+pub struct UndoStack {
+    entries: Vec<UndoEntry>,
+}
+
+impl UndoStack {
+    /// Records `todos` as they were before an operation. `newest` is the
+    /// largest id the operation left in the DB, so the todos it added go
+    /// on undo; 0 for one that added none.
+    pub fn push(&mut self, label: impl Into<String>, todos: Vec<Todo>, newest: usize) {
+        let newest = todos.iter().map(|t| t.id).fold(newest, usize::max);
+        self.push_entry(
+            label,
+            Snapshot::Todos {
+                todos,
+                archived: None,
+                newest,
+            },
+        );
+    }
+
+    /// Like `push`, for an operation that moved todos in or out of the
+    /// archive, whose snapshot `archived` is put back too. `newest` is the
+    /// largest id the operation left in the DB, so a todo it brought back
+    /// under a new id goes again on undo.
+    pub fn push_archive(
+        &mut self,
+        label: impl Into<String>,
+        todos: Vec<Todo>,
+        archived: Vec<Todo>,
+        newest: usize,
+    ) {
+        let newest = todos.iter().map(|t| t.id).fold(newest, usize::max);
+        self.push_entry(
+            label,
+            Snapshot::Todos {
+                todos,
+                archived: Some(archived),
+                newest,
+            },
+        );
+    }
+
+    /// For a change of the tags of the time entry `id`, which had `tags`
+    /// before. The todos stay as they are on undo.
+    pub fn push_entry_tags(
+        &mut self,
+        label: impl Into<String>,
+        id: usize,
+        tags: Option<Vec<String>>,
+    ) {
+        self.push_entry(label, Snapshot::EntryTags { id, tags });
+    }
+
+    fn push_entry(&mut self, label: impl Into<String>, snapshot: Snapshot) {
+        if self.entries.len() == MAX_UNDO_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.entries.push(UndoEntry {
+            label: label.into(),
+            snapshot,
+        });
+    }
+
+    pub fn pop(&mut self) -> Option<UndoEntry> {
+        self.entries.pop()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl UndoEntry {
+    /// The snapshot plus any todo created after the operation, e.g. by
+    /// `add` from another terminal, so undoing never drops foreign records.
+    /// Ids only grow, so anything above the largest id the operation left
+    /// is newer. None for an entry that doesn't put todos back.
+    pub fn restore(&self, current: &[Todo]) -> Option<Vec<Todo>> {
+        match &self.snapshot {
+            Snapshot::Todos { todos, newest, .. } => {
+                let mut todos = todos.clone();
+                todos.extend(current.iter().filter(|t| t.id > *newest).cloned());
+                Some(todos)
+            }
+            Snapshot::EntryTags { .. } => None,
+        }
+    }
+
+    /// Puts the snapshot back, and the archive along with it when the
+    /// entry has one.
+    pub fn apply(&self) -> Result<(), Error> {
+        match &self.snapshot {
+            Snapshot::Todos { archived: None, .. } => update_db(|todos| {
+                if let Some(restored) = self.restore(todos) {
+                    *todos = restored;
+                }
+            }),
+            Snapshot::Todos {
+                archived: Some(archived),
+                ..
+            } => transaction(|txn| {
+                if let Some(restored) = self.restore(&txn.todos) {
+                    txn.todos = restored;
+                }
+                *txn.archived()? = archived.clone();
+                Ok(())
+            }),
+            Snapshot::EntryTags { id, tags } => update_time_entries(|entries| {
+                for entry in entries.iter_mut().filter(|e| e.id == *id) {
+                    entry.tags = tags.clone();
+                }
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(id: usize) -> Todo {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "title": format!("todo {}", id),
+            "description": "",
+            "category": "work",
+            "status": "Todo",
+            "created_at": "2024-05-01T09:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    fn ids(todos: &[Todo]) -> Vec<usize> {
+        todos.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn restore_keeps_todos_added_since() {
+        let mut undo = UndoStack::default();
+        undo.push("delete", vec![todo(1), todo(2)], 0);
+        let entry = undo.pop().unwrap();
+        // 2 was deleted, 3 added from another terminal afterwards
+        assert_eq!(
+            entry.restore(&[todo(1), todo(3)]).as_deref().map(ids),
+            Some(vec![1, 2, 3])
+        );
+        assert!(undo.is_empty());
+    }
+
+    #[test]
+    fn restore_drops_a_todo_unarchived_under_a_new_id() {
+        let mut undo = UndoStack::default();
+        // archived 2 came back as 5, past every id the DB had
+        undo.push_archive("unarchive", vec![todo(1), todo(4)], vec![todo(2)], 5);
+        let entry = undo.pop().unwrap();
+        assert_eq!(
+            entry
+                .restore(&[todo(1), todo(4), todo(5), todo(6)])
+                .as_deref()
+                .map(ids),
+            Some(vec![1, 4, 6])
+        );
+        match &entry.snapshot {
+            Snapshot::Todos { archived, .. } => {
+                assert_eq!(archived.as_deref().map(ids), Some(vec![2]))
+            }
+            Snapshot::EntryTags { .. } => panic!("not an archive entry"),
+        }
+    }
+
+    #[test]
+    fn entry_tags_leave_the_todos_alone() {
+        let mut undo = UndoStack::default();
+        undo.push_entry_tags("tags", 3, Some(vec!["deep".to_string()]));
+        let entry = undo.pop().unwrap();
+        assert_eq!(entry.restore(&[todo(1)]).as_deref().map(ids), None);
+        match entry.snapshot {
+            Snapshot::EntryTags { id, tags } => {
+                assert_eq!((id, tags), (3, Some(vec!["deep".to_string()])))
+            }
+            Snapshot::Todos { .. } => panic!("not a tags entry"),
+        }
+    }
+
+    #[test]
+    fn the_oldest_entry_goes_past_the_limit() {
+        let mut undo = UndoStack::default();
+        for id in 0..=MAX_UNDO_ENTRIES {
+            undo.push(format!("edit {}", id), vec![todo(id)], 0);
+        }
+        let mut labels = vec![];
+        while let Some(entry) = undo.pop() {
+            labels.push(entry.label);
+        }
+        assert_eq!(labels.len(), MAX_UNDO_ENTRIES);
+        assert_eq!(labels.last().map(String::as_str), Some("edit 1"));
+    }
+}
@@ -0,0 +1,295 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use serde::Deserialize;
+use std::sync::Mutex;
+
+use crate::escalation::{effective_priority, EscalationRule};
+use crate::subtasks::effective_due;
+use crate::{Priority, Todo};
+
+/// Multipliers for the three parts of the urgency score, see [`score`],
+/// e.g. `{"priority": 2}` to let priority count double.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct UrgencyWeights {
+    pub overdue: f64,
+    pub proximity: f64,
+    pub priority: f64,
+}
+
+impl Default for UrgencyWeights {
+    fn default() -> UrgencyWeights {
+        UrgencyWeights {
+            overdue: 1.0,
+            proximity: 1.0,
+            priority: 1.0,
+        }
+    }
+}
+
+/// Days overdue past which a todo gets no more urgent.
+const MAX_OVERDUE_DAYS: f64 = 14.0;
+
+/// How urgent `todo` is at `now`, the higher the sooner it wants doing.
+/// The sum of, each times its weight:
+///
+/// - overdue: 10 once the effective due date has passed, plus a point a
+///   day overdue up to 14 more
+/// - proximity: before that, 10 / (1 + days left), so 10 when due now, 5
+///   a day ahead and 1 nine days ahead
+/// - priority: 1, 2, 4 or 8 for Low to Urgent, as escalated; 0 for none
+///
+/// With the default weights an overdue todo beats one of the same priority
+/// that isn't due yet, and an Urgent todo due in a week beats a Low one due
+/// tomorrow.
+pub fn score(
+    todo: &Todo,
+    todos: &[Todo],
+    now: DateTime<Utc>,
+    escalation: &[EscalationRule],
+    weights: &UrgencyWeights,
+) -> f64 {
+    let due = match effective_due(todo, todos) {
+        Some(due) if due <= now => {
+            let days = (now - due).num_minutes() as f64 / (24.0 * 60.0);
+            weights.overdue * (10.0 + days.min(MAX_OVERDUE_DAYS))
+        }
+        Some(due) => {
+            let days = (due - now).num_minutes() as f64 / (24.0 * 60.0);
+            weights.proximity * 10.0 / (1.0 + days)
+        }
+        None => 0.0,
+    };
+    let priority = match effective_priority(todo, todos, now, escalation).priority {
+        Some(Priority::Urgent) => 8.0,
+        Some(Priority::High) => 4.0,
+        Some(Priority::Medium) => 2.0,
+        Some(Priority::Low) => 1.0,
+        None => 0.0,
+    };
+    due + weights.priority * priority
+}
+
+/// Orders a column most urgent first. Pinned todos still come first, and
+/// ties go to the lower id, so the order holds from one redraw to the next.
+pub fn sort(column: &mut [Todo], todos: &[Todo], now: DateTime<Utc>, urgency: &Urgency) {
+    let mut scored: Vec<(f64, Todo)> = column
+        .iter()
+        .map(|todo| {
+            let score = score(todo, todos, now, &urgency.escalation, &urgency.weights);
+            (score, todo.clone())
+        })
+        .collect();
+    scored.sort_by(|(a_score, a), (b_score, b)| {
+        (!a.pinned)
+            .cmp(&!b.pinned)
+            .then(b_score.total_cmp(a_score))
+            .then(a.id.cmp(&b.id))
+    });
+    for (slot, (_, todo)) in column.iter_mut().zip(scored) {
+        *slot = todo;
+    }
+}
+
+/// What the board needs to sort its columns by urgency.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Urgency {
+    pub weights: UrgencyWeights,
+    pub escalation: Vec<EscalationRule>,
+}
+
+static BOARD: Mutex<Option<Urgency>> = Mutex::new(None);
+
+/// Sorts the ToDo and Doing columns by urgency, or back in their stored
+/// order with None, as `:set sort` asks.
+pub fn set_board(urgency: Option<Urgency>) {
+    *BOARD
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = urgency;
+}
+
+/// How the board sorts its columns, None for pinned first, then by id.
+pub fn board() -> Option<Urgency> {
+    BOARD
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+/// The time left until `due`, e.g. "3d 4h", "5h 20m" or "overdue 2d 1h".
+pub fn due_in(due: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    if due < now {
+        format!("overdue {}", span(now - due))
+    } else {
+        span(due - now)
+    }
+}
+
+/// The two largest units of `duration`, days and hours or hours and
+/// minutes.
+fn span(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    let (days, hours) = (minutes / (24 * 60), minutes / 60 % 24);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn now() -> DateTime<Utc> {
+        "2024-05-15T12:00:00Z".parse().unwrap()
+    }
+
+    /// A todo due `days` days from `now`, overdue when negative.
+    fn todo(id: usize, due_in_days: Option<f64>, priority: Option<&str>) -> Todo {
+        let due = due_in_days.map(|days| now() + Duration::minutes((days * 24.0 * 60.0) as i64));
+        serde_json::from_value(json!({
+            "id": id,
+            "title": format!("todo {}", id),
+            "description": "",
+            "category": "work",
+            "status": "Todo",
+            "created_at": "2024-05-01T09:00:00Z",
+            "due": due,
+            "priority": priority,
+        }))
+        .unwrap()
+    }
+
+    fn plain(todo: &Todo, todos: &[Todo]) -> f64 {
+        score(todo, todos, now(), &[], &UrgencyWeights::default())
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn scores_overdue_days_proximity_and_priority() {
+        let todos = [
+            todo(1, None, None),
+            todo(2, Some(0.0), None),
+            todo(3, Some(1.0), None),
+            todo(4, Some(9.0), None),
+            todo(5, Some(-3.0), None),
+            todo(6, Some(-30.0), None),
+            todo(7, None, Some("Urgent")),
+            todo(8, Some(1.0), Some("Low")),
+        ];
+        let scores: Vec<f64> = todos.iter().map(|t| plain(t, &todos)).collect();
+        let expected = [0.0, 10.0, 5.0, 1.0, 13.0, 24.0, 8.0, 6.0];
+        for (id, (score, expected)) in scores.iter().zip(expected).enumerate() {
+            assert!(close(*score, expected), "todo {}: {}", id + 1, score);
+        }
+    }
+
+    #[test]
+    fn weights_scale_each_part() {
+        let weights = UrgencyWeights {
+            overdue: 2.0,
+            proximity: 0.5,
+            priority: 3.0,
+        };
+        let overdue = todo(1, Some(-2.0), Some("High"));
+        let ahead = todo(2, Some(1.0), Some("Medium"));
+        let todos = [overdue.clone(), ahead.clone()];
+        assert!(close(
+            score(&overdue, &todos, now(), &[], &weights),
+            2.0 * 12.0 + 3.0 * 4.0
+        ));
+        assert!(close(
+            score(&ahead, &todos, now(), &[], &weights),
+            0.5 * 5.0 + 3.0 * 2.0
+        ));
+
+        // missing weights stay at 1
+        let weights: UrgencyWeights = serde_json::from_value(json!({ "priority": 2 })).unwrap();
+        assert_eq!(
+            weights,
+            UrgencyWeights {
+                overdue: 1.0,
+                proximity: 1.0,
+                priority: 2.0,
+            }
+        );
+    }
+
+    #[test]
+    fn the_defaults_rank_overdue_and_urgent_work_as_documented() {
+        let overdue = todo(1, Some(-0.1), Some("Medium"));
+        let not_yet = todo(2, Some(0.1), Some("Medium"));
+        let urgent_next_week = todo(3, Some(7.0), Some("Urgent"));
+        let low_tomorrow = todo(4, Some(1.0), Some("Low"));
+        let todos = [
+            overdue.clone(),
+            not_yet.clone(),
+            urgent_next_week.clone(),
+            low_tomorrow.clone(),
+        ];
+        assert!(plain(&overdue, &todos) > plain(&not_yet, &todos));
+        assert!(plain(&urgent_next_week, &todos) > plain(&low_tomorrow, &todos));
+    }
+
+    #[test]
+    fn escalation_and_subtask_due_dates_count() {
+        let rule = EscalationRule {
+            to: Priority::Urgent,
+            hours_before: 48.0,
+        };
+        let soon = todo(1, Some(1.0), None);
+        let todos = [soon.clone()];
+        let escalated = score(&soon, &todos, now(), &[rule], &UrgencyWeights::default());
+        assert!(close(escalated, 5.0 + 8.0));
+
+        // a subtask due before its parent makes the parent due then
+        let parent = todo(2, Some(9.0), None);
+        let mut subtask = todo(3, Some(1.0), None);
+        subtask.parent = Some(2);
+        let todos = [parent.clone(), subtask];
+        assert!(close(plain(&parent, &todos), 5.0));
+    }
+
+    #[test]
+    fn sorting_keeps_pinned_first_and_breaks_ties_by_id() {
+        let mut pinned = todo(9, None, None);
+        pinned.pinned = true;
+        let todos = vec![
+            todo(5, Some(3.0), None),
+            todo(2, Some(3.0), None),
+            pinned,
+            todo(7, Some(-1.0), None),
+            todo(1, None, None),
+        ];
+        let urgency = Urgency {
+            weights: UrgencyWeights::default(),
+            escalation: vec![],
+        };
+        let mut column = todos.clone();
+        sort(&mut column, &todos, now(), &urgency);
+        let order: Vec<usize> = column.iter().map(|t| t.id).collect();
+        assert_eq!(order, [9, 7, 2, 5, 1]);
+
+        // the same order whatever it started from
+        let mut reversed: Vec<Todo> = todos.iter().rev().cloned().collect();
+        sort(&mut reversed, &todos, now(), &urgency);
+        assert_eq!(reversed.iter().map(|t| t.id).collect::<Vec<_>>(), order);
+    }
+
+    #[test]
+    fn due_in_shows_the_two_largest_units() {
+        let at = |minutes: i64| now() + Duration::minutes(minutes);
+        assert_eq!(due_in(at(3 * 24 * 60 + 4 * 60 + 10), now()), "3d 4h");
+        assert_eq!(due_in(at(5 * 60 + 20), now()), "5h 20m");
+        assert_eq!(due_in(at(42), now()), "42m");
+        assert_eq!(due_in(at(0), now()), "0m");
+        assert_eq!(due_in(at(-(2 * 24 * 60 + 61)), now()), "overdue 2d 1h");
+    }
+}
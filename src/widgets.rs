@@ -0,0 +1,658 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use std::cell::Cell;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::chrome::Chrome;
+use crate::complete::{Completer, Kind, Vocabulary};
+
+/// A single line text buffer with a cursor, shared by every form.
+#[derive(Clone, Debug, Default)]
+pub struct Input {
+    value: String,
+    // counted in chars, not bytes
+    cursor: usize,
+}
+
+impl Input {
+    pub fn new(value: &str) -> Input {
+        Input {
+            value: value.to_string(),
+            cursor: value.chars().count(),
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Replaces the text, with the cursor at its end.
+    pub fn set_value(&mut self, value: &str) {
+        *self = Input::new(value);
+    }
+
+    /// Applies an editing key, returning false when the key isn't an editing key.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char(c) => {
+                let at = self.byte_index();
+                self.value.insert(at, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    let at = self.byte_index();
+                    self.value.remove(at);
+                }
+            }
+            KeyCode::Delete => {
+                if self.cursor < self.value.chars().count() {
+                    let at = self.byte_index();
+                    self.value.remove(at);
+                }
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.value.chars().count()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.value.chars().count(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn byte_index(&self) -> usize {
+        self.value
+            .char_indices()
+            .nth(self.cursor)
+            .map(|(i, _)| i)
+            .unwrap_or_else(|| self.value.len())
+    }
+}
+
+/// A multi-line text buffer with a cursor. Lines wrap at the width the
+/// area was last drawn with, and Up/Down move by those screen rows.
+#[derive(Clone, Debug, Default)]
+pub struct TextArea {
+    value: String,
+    // counted in chars, not bytes
+    cursor: usize,
+    /// Typing stops at this many chars.
+    max_chars: Option<usize>,
+    // set while drawing, the keys that follow go by the drawn layout
+    width: Cell<usize>,
+    scroll: Cell<usize>,
+}
+
+impl TextArea {
+    pub fn new(value: &str, max_chars: Option<usize>) -> TextArea {
+        TextArea {
+            value: value.to_string(),
+            cursor: value.chars().count(),
+            max_chars,
+            ..TextArea::default()
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn chars(&self) -> usize {
+        self.value.chars().count()
+    }
+
+    pub fn words(&self) -> usize {
+        self.value.split_whitespace().count()
+    }
+
+    /// Whether `max_chars` leaves no room for another char.
+    pub fn is_full(&self) -> bool {
+        self.max_chars.is_some_and(|max| self.chars() >= max)
+    }
+
+    /// Applies an editing key, Enter starting a new line. Returns false
+    /// when the key isn't an editing key or the text is full.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char(c) => return self.insert(c),
+            KeyCode::Enter => return self.insert('\n'),
+            KeyCode::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    let at = self.byte_index(self.cursor);
+                    self.value.remove(at);
+                }
+            }
+            KeyCode::Delete => {
+                if self.cursor < self.chars() {
+                    let at = self.byte_index(self.cursor);
+                    self.value.remove(at);
+                }
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.chars()),
+            KeyCode::Up => self.move_vertically(false),
+            KeyCode::Down => self.move_vertically(true),
+            KeyCode::Home => {
+                let before: Vec<char> = self.value.chars().take(self.cursor).collect();
+                self.cursor = before
+                    .iter()
+                    .rposition(|c| *c == '\n')
+                    .map_or(0, |newline| newline + 1);
+            }
+            KeyCode::End => {
+                let after = self.value.chars().skip(self.cursor);
+                self.cursor += after.take_while(|c| *c != '\n').count();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn insert(&mut self, c: char) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        let at = self.byte_index(self.cursor);
+        self.value.insert(at, c);
+        self.cursor += 1;
+        true
+    }
+
+    fn byte_index(&self, chars: usize) -> usize {
+        self.value
+            .char_indices()
+            .nth(chars)
+            .map(|(i, _)| i)
+            .unwrap_or_else(|| self.value.len())
+    }
+
+    /// The screen rows as char ranges `[start, end)`, wrapping lines at
+    /// `width` columns. Every line has a row, even an empty one, and a row
+    /// holds at least one char so a wide char can't get stuck.
+    fn rows(&self, width: usize) -> Vec<(usize, usize)> {
+        let width = width.max(1);
+        let mut rows = vec![];
+        let (mut start, mut used) = (0, 0);
+        for (i, c) in self.value.chars().enumerate() {
+            if c == '\n' {
+                rows.push((start, i));
+                start = i + 1;
+                used = 0;
+                continue;
+            }
+            let w = c.width().unwrap_or(0);
+            if used + w > width && i > start {
+                rows.push((start, i));
+                start = i;
+                used = 0;
+            }
+            used += w;
+        }
+        rows.push((start, self.chars()));
+        rows
+    }
+
+    /// The row the cursor is on and its column in cells. At the end of a
+    /// wrapped row the cursor sits at the start of the next one.
+    fn cursor_row(&self, rows: &[(usize, usize)]) -> (usize, usize) {
+        let row = rows
+            .iter()
+            .rposition(|(start, _)| *start <= self.cursor)
+            .unwrap_or(0);
+        let col = self
+            .value
+            .chars()
+            .skip(rows[row].0)
+            .take(self.cursor - rows[row].0)
+            .map(|c| c.width().unwrap_or(0))
+            .sum();
+        (row, col)
+    }
+
+    /// Moves to the char under the cursor's column one row up or down,
+    /// or to the start or end of the text from the first or last row.
+    fn move_vertically(&mut self, down: bool) {
+        let rows = self.rows(self.width.get());
+        let (row, col) = self.cursor_row(&rows);
+        let target = match (down, row) {
+            (false, 0) => {
+                self.cursor = 0;
+                return;
+            }
+            (false, row) => row - 1,
+            (true, row) if row + 1 == rows.len() => {
+                self.cursor = self.chars();
+                return;
+            }
+            (true, row) => row + 1,
+        };
+        let (start, end) = rows[target];
+        let mut at = start;
+        let mut used = 0;
+        for c in self.value.chars().skip(start).take(end - start) {
+            let w = c.width().unwrap_or(0);
+            if used + w > col {
+                break;
+            }
+            used += w;
+            at += 1;
+        }
+        // a wrapped row's end is where the next row starts
+        if at == end && rows.get(target + 1).is_some_and(|next| next.0 == end) {
+            at = end - 1;
+        }
+        self.cursor = at;
+    }
+}
+
+/// Draws `text` into `area` without a border, scrolling to keep the
+/// cursor in view, and puts the terminal cursor on it.
+pub fn render_text_area<B: Backend>(rect: &mut Frame<B>, text: &TextArea, area: Rect) {
+    let (width, height) = (area.width as usize, (area.height as usize).max(1));
+    text.width.set(width);
+    let rows = text.rows(width);
+    let (mut row, mut col) = text.cursor_row(&rows);
+    // past a full last row the cursor goes below it
+    if col >= width.max(1) {
+        row += 1;
+        col = 0;
+    }
+    let mut scroll = text.scroll.get().min(row);
+    if row >= scroll + height {
+        scroll = row + 1 - height;
+    }
+    text.scroll.set(scroll);
+
+    let chars: Vec<char> = text.value.chars().collect();
+    let lines: Vec<Spans> = rows
+        .iter()
+        .skip(scroll)
+        .take(height)
+        .map(|(start, end)| {
+            Spans::from(vec![Span::raw(
+                chars[*start..*end].iter().collect::<String>(),
+            )])
+        })
+        .collect();
+    rect.render_widget(Paragraph::new(lines), area);
+    rect.set_cursor(area.x + col as u16, area.y + (row - scroll) as u16);
+}
+
+/// Cycles through a fixed list of labelled options with Left/Right.
+#[derive(Clone, Debug)]
+pub struct Picker {
+    options: Vec<String>,
+    selected: usize,
+}
+
+impl Picker {
+    pub fn new(options: Vec<String>) -> Picker {
+        Picker {
+            options,
+            selected: 0,
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn label(&self) -> &str {
+        &self.options[self.selected]
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Left => {
+                if self.selected > 0 {
+                    self.selected -= 1;
+                } else {
+                    self.selected = self.options.len() - 1;
+                }
+            }
+            KeyCode::Right | KeyCode::Char(' ') => {
+                self.selected = (self.selected + 1) % self.options.len();
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+pub enum PromptAction {
+    Continue,
+    Cancel,
+    Submit(String),
+}
+
+/// Finds the word to complete in a prompt's text: the char it starts at and
+/// what it names, or None when the text isn't at a completable spot.
+pub type Locate = fn(&str) -> Option<(usize, Kind)>;
+
+/// A one line popup asking for a single value.
+pub struct Prompt {
+    title: String,
+    input: Input,
+    completion: Option<(Vocabulary, Locate, Completer)>,
+}
+
+impl Prompt {
+    pub fn new(title: impl Into<String>, value: &str) -> Prompt {
+        Prompt {
+            title: title.into(),
+            input: Input::new(value),
+            completion: None,
+        }
+    }
+
+    /// Offers completions from `vocabulary` wherever `locate` finds a word.
+    pub fn with_completion(mut self, vocabulary: Vocabulary, locate: Locate) -> Prompt {
+        self.completion = Some((vocabulary, locate, Completer::default()));
+        self
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> PromptAction {
+        match key.code {
+            KeyCode::Esc => PromptAction::Cancel,
+            KeyCode::Enter => PromptAction::Submit(self.input.value().to_string()),
+            KeyCode::Tab | KeyCode::Down => {
+                if let Some((vocabulary, locate, completer)) = &mut self.completion {
+                    if let Some((start, kind)) = locate(self.input.value()) {
+                        completer.cycle(&mut self.input, start, vocabulary.candidates(kind));
+                    }
+                }
+                PromptAction::Continue
+            }
+            _ => {
+                if let Some((_, _, completer)) = &mut self.completion {
+                    completer.reset();
+                }
+                self.input.handle_key(key);
+                PromptAction::Continue
+            }
+        }
+    }
+
+    fn suggestions(&self) -> (Vec<&str>, Option<usize>) {
+        match &self.completion {
+            Some((vocabulary, locate, completer)) => match locate(self.input.value()) {
+                Some((start, kind)) => (
+                    completer.suggestions(&self.input, start, vocabulary.candidates(kind)),
+                    completer.current(),
+                ),
+                None => (vec![], None),
+            },
+            None => (vec![], None),
+        }
+    }
+}
+
+/// The suggestions as one line, the one the cycle shows highlighted.
+pub fn suggestions_line<'a>(found: &[&str], current: Option<usize>) -> Spans<'a> {
+    let mut spans = vec![Span::styled("↳ ", Style::default().fg(Color::DarkGray))];
+    for (i, value) in found.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::styled(" · ", Style::default().fg(Color::DarkGray)));
+        }
+        let style = if current.map(|c| c % found.len()) == Some(i) {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default().fg(Color::Cyan)
+        };
+        spans.push(Span::styled(value.to_string(), style));
+    }
+    spans.push(Span::styled(
+        "  Tab: complete",
+        Style::default().fg(Color::DarkGray),
+    ));
+    Spans::from(spans)
+}
+
+pub fn render_prompt<B: Backend>(rect: &mut Frame<B>, prompt: &Prompt, area: Rect) {
+    let popup = Rect {
+        y: area.y + area.height.saturating_sub(4) / 2,
+        height: area.height.min(4),
+        ..centered_rect(60, 20, area)
+    };
+    let (found, current) = prompt.suggestions();
+    let paragraph = Paragraph::new(vec![
+        Spans::from(vec![Span::raw(prompt.input.value().to_string())]),
+        if found.is_empty() {
+            Spans::from(vec![Span::styled(
+                "Enter: save  Esc: cancel",
+                Style::default().fg(Color::DarkGray),
+            )])
+        } else {
+            suggestions_line(&found, current)
+        },
+    ])
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(prompt.title.clone())
+            .border_type(BorderType::Plain),
+    );
+
+    rect.render_widget(Clear, popup);
+    rect.render_widget(paragraph, popup);
+    rect.set_cursor(popup.x + 1 + prompt.input.cursor() as u16, popup.y + 1);
+}
+
+/// A popup asking something answered with a single key, tall enough for
+/// the question to wrap.
+pub fn render_question<B: Backend>(rect: &mut Frame<B>, question: &str, area: Rect) {
+    let popup = centered_rect(60, 20, area);
+    let inner_width = popup.width.saturating_sub(2).max(1) as usize;
+    let height = (question.width().div_ceil(inner_width) as u16 + 3).max(4);
+    let popup = Rect {
+        y: area.y + area.height.saturating_sub(height) / 2,
+        height: area.height.min(height),
+        ..popup
+    };
+    let paragraph = Paragraph::new(question.to_string())
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .border_type(BorderType::Plain),
+        );
+    rect.render_widget(Clear, popup);
+    rect.render_widget(paragraph, popup);
+}
+
+/// A pane whose data couldn't be read, saying why in place of its content.
+pub fn render_error<B: Backend>(
+    rect: &mut Frame<B>,
+    title: &str,
+    err: &dyn std::fmt::Display,
+    chrome: Chrome,
+    area: Rect,
+) {
+    let paragraph = Paragraph::new(format!("can't show this: {}", err))
+        .style(Style::default().fg(Color::LightRed))
+        .wrap(Wrap { trim: true })
+        .block(chrome.block().title(title.to_string()));
+    rect.render_widget(paragraph, area);
+}
+
+/// A pane titled `title` whose content isn't there yet, with `message`
+/// instead.
+pub fn render_placeholder<B: Backend>(
+    rect: &mut Frame<B>,
+    title: &str,
+    message: &str,
+    chrome: Chrome,
+    area: Rect,
+) {
+    let paragraph = Paragraph::new(Span::styled(
+        message.to_string(),
+        Style::default().fg(Color::DarkGray),
+    ))
+    .block(chrome.block().title(title.to_string()));
+    rect.render_widget(paragraph, area);
+}
+
+/// Returns a rect of the given percentage size centered in `area`, for popups.
+pub fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_y) / 2),
+                Constraint::Percentage(percent_y),
+                Constraint::Percentage((100 - percent_y) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_x) / 2),
+                Constraint::Percentage(percent_x),
+                Constraint::Percentage((100 - percent_x) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(vertical[1])[1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tui::{backend::TestBackend, Terminal};
+
+    fn type_keys(text: &mut TextArea, codes: &[KeyCode]) {
+        for code in codes {
+            text.handle_key(KeyEvent::new(*code, crossterm::event::KeyModifiers::NONE));
+        }
+    }
+
+    /// `value` as drawn `width` columns wide, with the cursor at `cursor`.
+    fn laid_out(value: &str, cursor: usize, width: usize) -> TextArea {
+        let text = TextArea {
+            cursor,
+            ..TextArea::new(value, None)
+        };
+        text.width.set(width);
+        text
+    }
+
+    /// The rows `text` draws into `width` by `height` cells, and the cursor.
+    fn screen(text: &TextArea, width: u16, height: u16) -> (Vec<String>, (u16, u16)) {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|f| render_text_area(f, text, f.size()))
+            .unwrap();
+        let rows = terminal
+            .backend()
+            .buffer()
+            .content
+            .chunks(width as usize)
+            .map(|row| row.iter().map(|cell| cell.symbol.as_str()).collect())
+            .collect();
+        (rows, terminal.get_cursor().unwrap())
+    }
+
+    #[test]
+    fn rows_wrap_at_the_width_and_break_at_newlines() {
+        let text = TextArea::new("abcdefg\n\nhi", None);
+        assert_eq!(text.rows(3), [(0, 3), (3, 6), (6, 7), (8, 8), (9, 11)]);
+        // wide chars take two cells, and one still fits a row too narrow
+        let wide = TextArea::new("日本語", None);
+        assert_eq!(wide.rows(4), [(0, 2), (2, 3)]);
+        assert_eq!(wide.rows(1), [(0, 1), (1, 2), (2, 3)]);
+    }
+
+    #[test]
+    fn the_cursor_column_counts_cells() {
+        let text = laid_out("a日本b", 3, 10);
+        assert_eq!(text.cursor_row(&text.rows(10)), (0, 5));
+        // at the end of a wrapped row it starts the next one
+        let wrapped = laid_out("abcdef", 3, 3);
+        assert_eq!(wrapped.cursor_row(&wrapped.rows(3)), (1, 0));
+    }
+
+    #[test]
+    fn up_and_down_keep_the_column_across_wrapped_rows() {
+        let mut text = laid_out("abcdefgh", 7, 3);
+        type_keys(&mut text, &[KeyCode::Up]);
+        assert_eq!(text.cursor, 4);
+        type_keys(&mut text, &[KeyCode::Up]);
+        assert_eq!(text.cursor, 1);
+        // from the first row Up goes to the start, from the last Down to the end
+        type_keys(&mut text, &[KeyCode::Up]);
+        assert_eq!(text.cursor, 0);
+        type_keys(&mut text, &[KeyCode::Down, KeyCode::Down, KeyCode::Down]);
+        assert_eq!(text.cursor, 8);
+    }
+
+    #[test]
+    fn up_and_down_land_before_a_wide_char_that_would_overshoot() {
+        // "ab" above "日本": column 1 falls inside 日, so the cursor goes before it
+        let mut text = laid_out("ab\n日本", 1, 10);
+        type_keys(&mut text, &[KeyCode::Down]);
+        assert_eq!(text.cursor, 3);
+        type_keys(&mut text, &[KeyCode::Right, KeyCode::Up]);
+        assert_eq!(text.cursor, 2);
+        // a shorter row below takes the cursor to its end
+        let mut text = laid_out("abcdef\nxy", 5, 10);
+        type_keys(&mut text, &[KeyCode::Down]);
+        assert_eq!(text.cursor, 9);
+    }
+
+    #[test]
+    fn home_end_and_deletes_work_on_the_current_line() {
+        let mut text = TextArea::new("one\ntwo", None);
+        type_keys(&mut text, &[KeyCode::Home]);
+        assert_eq!(text.cursor, 4);
+        type_keys(&mut text, &[KeyCode::Backspace]);
+        assert_eq!(text.value(), "onetwo");
+        type_keys(&mut text, &[KeyCode::End, KeyCode::Delete, KeyCode::Left]);
+        assert_eq!((text.value(), text.cursor), ("onetwo", 5));
+        type_keys(&mut text, &[KeyCode::Char('é'), KeyCode::Enter]);
+        assert_eq!(text.value(), "onetwé\no");
+    }
+
+    #[test]
+    fn a_full_text_area_takes_no_more_chars() {
+        let mut text = TextArea::new("ab", Some(3));
+        assert!(text.handle_key(KeyEvent::from(KeyCode::Char('c'))));
+        assert!(text.is_full());
+        assert!(!text.handle_key(KeyEvent::from(KeyCode::Char('d'))));
+        assert!(!text.handle_key(KeyEvent::from(KeyCode::Enter)));
+        assert!(text.handle_key(KeyEvent::from(KeyCode::Backspace)));
+        assert!(!text.is_full());
+        assert_eq!(text.value(), "ab");
+    }
+
+    #[test]
+    fn drawing_scrolls_to_keep_the_cursor_in_view() {
+        let text = TextArea::new("one\ntwo\nthree\nfour", None);
+        let (rows, cursor) = screen(&text, 6, 2);
+        assert_eq!(rows, ["three ", "four  "]);
+        assert_eq!(cursor, (4, 1));
+        // past a full last row the cursor moves below it
+        let full = TextArea::new("abc", None);
+        let (rows, cursor) = screen(&full, 3, 2);
+        assert_eq!(rows, ["abc", "   "]);
+        assert_eq!(cursor, (0, 1));
+    }
+}
@@ -0,0 +1,309 @@
+use chrono::prelude::*;
+use chrono::{FixedOffset, LocalResult};
+use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::sync::Mutex;
+
+/// The time zone days, weeks and due dates are reckoned in: the system's,
+/// or the one the config names with `timezone`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Zone {
+    System,
+    Named(Tz),
+}
+
+static CURRENT: Mutex<Zone> = Mutex::new(Zone::System);
+
+/// Makes `zone` the one everything is shown and bucketed in.
+pub fn set(zone: Zone) {
+    *CURRENT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = zone;
+}
+
+/// The zone in effect, the system's unless the config named another.
+pub fn current() -> Zone {
+    *CURRENT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The current time in the zone in effect.
+pub fn now() -> DateTime<Zone> {
+    Utc::now().with_timezone(&current())
+}
+
+impl Zone {
+    /// The zone an IANA name such as `Europe/Berlin` names.
+    pub fn parse(name: &str) -> Option<Zone> {
+        name.parse().ok().map(Zone::Named)
+    }
+
+    /// The IANA name, or "system" for the system's zone.
+    pub fn name(&self) -> String {
+        match self {
+            Zone::System => "system".to_string(),
+            Zone::Named(tz) => tz.name().to_string(),
+        }
+    }
+
+    /// The zone's offset from UTC at `at`.
+    pub fn offset_at(&self, at: DateTime<Utc>) -> FixedOffset {
+        self.offset_from_utc_datetime(&at.naive_utc()).fix()
+    }
+
+    /// The name, with the offset at `at` so that a report says exactly
+    /// which clock it was made by, e.g. "Europe/Berlin (+02:00)".
+    pub fn describe(&self, at: DateTime<Utc>) -> String {
+        format!("{} ({})", self.name(), self.offset_at(at))
+    }
+
+    fn offset(&self, fixed: impl Offset) -> ZoneOffset {
+        ZoneOffset {
+            zone: *self,
+            fixed: fixed.fix(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Zone {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Zone, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Zone::parse(&name).ok_or_else(|| {
+            serde::de::Error::custom(format!("unknown time zone '{}', use an IANA name", name))
+        })
+    }
+}
+
+/// An offset of a [`Zone`], which keeps the zone it belongs to.
+#[derive(Clone, Copy, Debug)]
+pub struct ZoneOffset {
+    zone: Zone,
+    fixed: FixedOffset,
+}
+
+impl Offset for ZoneOffset {
+    fn fix(&self) -> FixedOffset {
+        self.fixed
+    }
+}
+
+impl fmt::Display for ZoneOffset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fixed.fmt(f)
+    }
+}
+
+impl TimeZone for Zone {
+    type Offset = ZoneOffset;
+
+    fn from_offset(offset: &ZoneOffset) -> Zone {
+        offset.zone
+    }
+
+    #[allow(deprecated)]
+    fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<ZoneOffset> {
+        match self {
+            Zone::System => Local.offset_from_local_date(local).map(|o| self.offset(o)),
+            Zone::Named(tz) => tz.offset_from_local_date(local).map(|o| self.offset(o)),
+        }
+    }
+
+    fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<ZoneOffset> {
+        match self {
+            Zone::System => Local
+                .offset_from_local_datetime(local)
+                .map(|o| self.offset(o)),
+            Zone::Named(tz) => tz.offset_from_local_datetime(local).map(|o| self.offset(o)),
+        }
+    }
+
+    #[allow(deprecated)]
+    fn offset_from_utc_date(&self, utc: &NaiveDate) -> ZoneOffset {
+        match self {
+            Zone::System => self.offset(Local.offset_from_utc_date(utc)),
+            Zone::Named(tz) => self.offset(tz.offset_from_utc_date(utc)),
+        }
+    }
+
+    fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> ZoneOffset {
+        match self {
+            Zone::System => self.offset(Local.offset_from_utc_datetime(utc)),
+            Zone::Named(tz) => self.offset(tz.offset_from_utc_datetime(utc)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetracking::{local_midnight, time_per_category, week_bounds, TimeEntry};
+    use chrono::Duration;
+
+    fn named(name: &str) -> Zone {
+        Zone::parse(name).unwrap()
+    }
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn local(zone: Zone, value: &str) -> LocalResult<DateTime<Zone>> {
+        zone.from_local_datetime(&value.parse().unwrap())
+    }
+
+    /// How long `date` lasts in `zone`.
+    fn day_length(date: NaiveDate, zone: Zone) -> Duration {
+        local_midnight(date + Duration::days(1), zone) - local_midnight(date, zone)
+    }
+
+    #[test]
+    fn zones_go_by_iana_names() {
+        assert_eq!(named("Europe/Berlin").name(), "Europe/Berlin");
+        assert_eq!(Zone::parse("Mars/Olympus"), None);
+        assert_eq!(Zone::parse("+02:00"), None);
+        assert_eq!(Zone::System.name(), "system");
+
+        let parsed: Zone = serde_json::from_value(serde_json::json!("Asia/Kolkata")).unwrap();
+        assert_eq!(parsed, named("Asia/Kolkata"));
+        let unknown = serde_json::from_value::<Zone>(serde_json::json!("Berlin")).unwrap_err();
+        assert!(unknown
+            .to_string()
+            .contains("unknown time zone 'Berlin', use an IANA name"));
+    }
+
+    #[test]
+    fn the_offset_follows_dst_where_there_is_any() {
+        let berlin = named("Europe/Berlin");
+        let kolkata = named("Asia/Kolkata");
+        let winter = utc("2024-01-15T12:00:00Z");
+        let summer = utc("2024-07-15T12:00:00Z");
+        assert_eq!(berlin.describe(winter), "Europe/Berlin (+01:00)");
+        assert_eq!(berlin.describe(summer), "Europe/Berlin (+02:00)");
+        assert_eq!(kolkata.describe(winter), "Asia/Kolkata (+05:30)");
+        assert_eq!(kolkata.describe(summer), "Asia/Kolkata (+05:30)");
+        assert_eq!(named("UTC").describe(summer), "UTC (+00:00)");
+
+        // the switches, to the second
+        let hour = FixedOffset::east_opt(3600).unwrap();
+        let two = FixedOffset::east_opt(7200).unwrap();
+        assert_eq!(berlin.offset_at(utc("2024-03-31T00:59:59Z")), hour);
+        assert_eq!(berlin.offset_at(utc("2024-03-31T01:00:00Z")), two);
+        assert_eq!(berlin.offset_at(utc("2024-10-27T00:59:59Z")), two);
+        assert_eq!(berlin.offset_at(utc("2024-10-27T01:00:00Z")), hour);
+    }
+
+    #[test]
+    fn local_times_keep_their_zone_and_offset() {
+        let berlin = named("Europe/Berlin");
+        let shown = utc("2024-07-15T12:00:00Z").with_timezone(&berlin);
+        assert_eq!(shown.format("%H:%M %:z").to_string(), "14:00 +02:00");
+        assert_eq!(shown.timezone(), berlin);
+        assert_eq!(shown.with_timezone(&Utc), utc("2024-07-15T12:00:00Z"));
+    }
+
+    #[test]
+    fn a_skipped_local_time_has_none_and_a_repeated_one_has_two() {
+        let berlin = named("Europe/Berlin");
+        assert!(matches!(
+            local(berlin, "2024-03-31T02:30:00"),
+            LocalResult::None
+        ));
+        let repeated = local(berlin, "2024-10-27T02:30:00");
+        assert!(matches!(repeated, LocalResult::Ambiguous(..)));
+        assert_eq!(
+            repeated.earliest().unwrap().with_timezone(&Utc),
+            utc("2024-10-27T00:30:00Z")
+        );
+        assert_eq!(
+            repeated.latest().unwrap().with_timezone(&Utc),
+            utc("2024-10-27T01:30:00Z")
+        );
+        // without DST every local time exists once
+        let kolkata = named("Asia/Kolkata");
+        assert!(matches!(
+            local(kolkata, "2024-03-31T02:30:00"),
+            LocalResult::Single(..)
+        ));
+    }
+
+    #[test]
+    fn days_around_a_dst_change_are_23_and_25_hours_long() {
+        let berlin = named("Europe/Berlin");
+        assert_eq!(
+            local_midnight(date(2024, 3, 31), berlin),
+            utc("2024-03-30T23:00:00Z")
+        );
+        assert_eq!(day_length(date(2024, 3, 31), berlin), Duration::hours(23));
+        assert_eq!(day_length(date(2024, 10, 27), berlin), Duration::hours(25));
+        assert_eq!(day_length(date(2024, 7, 1), berlin), Duration::hours(24));
+        for day in [date(2024, 3, 31), date(2024, 10, 27)] {
+            assert_eq!(day_length(day, named("Asia/Kolkata")), Duration::hours(24));
+        }
+    }
+
+    #[test]
+    fn a_day_whose_midnight_is_skipped_starts_at_its_first_moment() {
+        // São Paulo moved its clocks from 00:00 to 01:00 on 2018-11-04
+        let sao_paulo = named("America/Sao_Paulo");
+        assert_eq!(
+            local_midnight(date(2018, 11, 4), sao_paulo),
+            utc("2018-11-04T03:00:00Z")
+        );
+        assert_eq!(
+            day_length(date(2018, 11, 4), sao_paulo),
+            Duration::hours(23)
+        );
+    }
+
+    #[test]
+    fn the_week_of_a_dst_change_is_an_hour_short() {
+        let berlin = named("Europe/Berlin");
+        let wednesday = utc("2024-03-27T12:00:00Z").with_timezone(&berlin);
+        let (from, to) = week_bounds(wednesday, Weekday::Mon);
+        assert_eq!(from, utc("2024-03-24T23:00:00Z"));
+        assert_eq!(to, utc("2024-03-31T22:00:00Z"));
+        assert_eq!(to - from, Duration::days(7) - Duration::hours(1));
+
+        let (from, _) = week_bounds(wednesday, Weekday::Sun);
+        assert_eq!(from, utc("2024-03-23T23:00:00Z"));
+    }
+
+    #[test]
+    fn the_same_entry_lands_on_the_day_of_the_zone_it_is_bucketed_in() {
+        let entries: Vec<TimeEntry> = serde_json::from_value(serde_json::json!([{
+            "id": 1,
+            "category": "work",
+            "start": "2024-05-01T23:30:00Z",
+            "end": "2024-05-02T00:30:00Z",
+        }]))
+        .unwrap();
+        let now = utc("2024-05-03T00:00:00Z");
+        let on = |day: NaiveDate, zone: Zone| {
+            time_per_category(
+                &entries,
+                local_midnight(day, zone),
+                local_midnight(day + Duration::days(1), zone),
+                now,
+            )
+            .get("work")
+            .copied()
+            .unwrap_or_else(Duration::zero)
+        };
+        let (first, second) = (date(2024, 5, 1), date(2024, 5, 2));
+        let utc_zone = named("UTC");
+        assert_eq!(on(first, utc_zone), Duration::minutes(30));
+        assert_eq!(on(second, utc_zone), Duration::minutes(30));
+        for zone in [named("Europe/Berlin"), named("Asia/Kolkata")] {
+            assert_eq!(on(first, zone), Duration::zero());
+            assert_eq!(on(second, zone), Duration::hours(1));
+        }
+        let new_york = named("America/New_York");
+        assert_eq!(on(first, new_york), Duration::hours(1));
+    }
+}